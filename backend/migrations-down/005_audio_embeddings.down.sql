@@ -0,0 +1,11 @@
+-- Revert 005: remove audio embeddings
+-- DESTRUCTIVE: all computed embeddings are lost and must be regenerated after re-applying.
+-- The `vector` extension is left installed in case other objects depend on it.
+
+DROP VIEW IF EXISTS embedding_coverage;
+DROP FUNCTION IF EXISTS find_transition_tracks(VARCHAR, VARCHAR, VARCHAR[], INTEGER);
+DROP FUNCTION IF EXISTS find_similar_tracks(VARCHAR, VARCHAR[], INTEGER);
+DROP FUNCTION IF EXISTS update_embedding_stats();
+DROP TABLE IF EXISTS embedding_failures;
+DROP TABLE IF EXISTS embedding_processing_status;
+DROP TABLE IF EXISTS track_embeddings;
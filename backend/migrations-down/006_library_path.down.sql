@@ -0,0 +1,3 @@
+-- Revert 006: remove file paths from the library index
+DROP INDEX IF EXISTS idx_library_index_path;
+ALTER TABLE library_index DROP COLUMN IF EXISTS path;
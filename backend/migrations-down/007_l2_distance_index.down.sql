@@ -0,0 +1,8 @@
+-- Revert 007: switch the similarity index back to cosine distance
+-- Embeddings cleared by 007 cannot be restored; re-run embedding indexing afterwards.
+
+DROP INDEX IF EXISTS idx_track_embeddings_vector;
+
+CREATE INDEX idx_track_embeddings_vector
+ON track_embeddings
+USING ivfflat (embedding vector_cosine_ops) WITH (lists = 100);
@@ -0,0 +1,5 @@
+-- Revert 008: remove the 2D visualization cache
+DROP TABLE IF EXISTS visualization_config;
+DROP INDEX IF EXISTS idx_track_embeddings_viz;
+ALTER TABLE track_embeddings DROP COLUMN IF EXISTS viz_y;
+ALTER TABLE track_embeddings DROP COLUMN IF EXISTS viz_x;
@@ -0,0 +1,2 @@
+-- Revert 009: remove application settings
+DROP TABLE IF EXISTS app_settings;
@@ -0,0 +1,2 @@
+-- Revert 010: remove background jobs
+DROP TABLE IF EXISTS background_jobs;
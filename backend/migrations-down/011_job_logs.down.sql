@@ -0,0 +1,2 @@
+-- Revert 011: remove per-job log lines
+DROP TABLE IF EXISTS background_job_logs;
@@ -0,0 +1,3 @@
+-- Revert 012: remove outbound webhooks and their delivery log
+DROP TABLE IF EXISTS webhook_deliveries;
+DROP TABLE IF EXISTS webhooks;
@@ -0,0 +1,3 @@
+-- Revert 013: forget Navidrome internet radio IDs
+-- Entries already published in Navidrome are not removed; delete them there if needed.
+ALTER TABLE stations DROP COLUMN IF EXISTS navidrome_radio_id;
@@ -0,0 +1,2 @@
+-- Revert 014: remove API keys (existing integrations stop authenticating)
+DROP TABLE IF EXISTS api_keys;
@@ -0,0 +1,4 @@
+-- Revert 015: demote curators and moderators to listeners
+UPDATE users SET role = 'listener' WHERE role IN ('curator', 'moderator');
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_role_check;
+ALTER TABLE users ADD CONSTRAINT users_role_check CHECK (role IN ('admin', 'listener'));
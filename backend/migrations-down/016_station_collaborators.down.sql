@@ -0,0 +1,2 @@
+-- Revert 016: remove station sharing (stations stay with their creators)
+DROP TABLE IF EXISTS station_collaborators;
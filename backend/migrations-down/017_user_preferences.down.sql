@@ -0,0 +1,2 @@
+-- Revert 017: drop per-user preferences
+ALTER TABLE users DROP COLUMN IF EXISTS preferences;
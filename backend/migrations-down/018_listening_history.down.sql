@@ -0,0 +1,4 @@
+-- Revert 018: drop listening history and favorites
+DROP TABLE IF EXISTS user_favorite_tracks;
+DROP TABLE IF EXISTS user_favorite_stations;
+DROP TABLE IF EXISTS listening_history;
@@ -0,0 +1,3 @@
+-- Revert 019: drop Daily Mix stations and the marker column
+DELETE FROM stations WHERE daily_mix_user_id IS NOT NULL;
+ALTER TABLE stations DROP COLUMN IF EXISTS daily_mix_user_id;
@@ -0,0 +1,3 @@
+-- Revert 020: drop the audit log and re-enable disabled accounts
+DROP TABLE IF EXISTS admin_audit_log;
+ALTER TABLE users DROP COLUMN IF EXISTS disabled_at;
@@ -0,0 +1,12 @@
+-- Revert 021: restore the original rating trigger function
+CREATE OR REPLACE FUNCTION update_track_ratings()
+RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE library_index
+    SET
+        avg_rating = (SELECT AVG(rating) FROM user_track_ratings WHERE track_id = NEW.track_id),
+        rating_count = (SELECT COUNT(*) FROM user_track_ratings WHERE track_id = NEW.track_id)
+    WHERE id = NEW.track_id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,7 @@
+-- Revert 022: drop anonymized history and restore cascading deletes
+DELETE FROM listening_history WHERE user_id IS NULL;
+ALTER TABLE listening_history DROP CONSTRAINT IF EXISTS listening_history_user_id_fkey;
+ALTER TABLE listening_history
+    ADD CONSTRAINT listening_history_user_id_fkey
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE;
+ALTER TABLE listening_history ALTER COLUMN user_id SET NOT NULL;
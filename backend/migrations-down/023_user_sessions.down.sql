@@ -0,0 +1,2 @@
+-- Revert 023: drop sessions (tokens issued since keep working until they expire)
+DROP TABLE IF EXISTS user_sessions;
@@ -0,0 +1,3 @@
+-- Revert 024: delete stream-only keys (older builds don't know the scope) and the station restriction
+DELETE FROM api_keys WHERE scopes ? 'stream' OR station_ids IS NOT NULL;
+ALTER TABLE api_keys DROP COLUMN IF EXISTS station_ids;
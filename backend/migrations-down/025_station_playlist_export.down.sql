@@ -0,0 +1,2 @@
+-- Revert 025: forget exported playlist IDs (the playlists stay in Navidrome)
+ALTER TABLE stations DROP COLUMN IF EXISTS navidrome_playlist_id;
@@ -0,0 +1,6 @@
+-- Revert 026: imported stations keep their tracks but stop following the playlist
+DROP INDEX IF EXISTS idx_stations_source_playlist;
+ALTER TABLE stations
+    DROP COLUMN IF EXISTS source_playlist_synced_at,
+    DROP COLUMN IF EXISTS source_playlist_sync,
+    DROP COLUMN IF EXISTS source_playlist_id;
@@ -0,0 +1,2 @@
+-- Revert 027: forget users' Navidrome accounts
+DROP TABLE IF EXISTS user_navidrome_accounts;
@@ -0,0 +1,6 @@
+-- Revert 028: drop measured track loudness
+DROP INDEX IF EXISTS idx_library_index_loudness_pending;
+ALTER TABLE library_index
+    DROP COLUMN IF EXISTS loudness_analyzed_at,
+    DROP COLUMN IF EXISTS sample_peak,
+    DROP COLUMN IF EXISTS loudness_lufs;
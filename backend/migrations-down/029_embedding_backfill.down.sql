@@ -0,0 +1,6 @@
+-- Revert 029: forget the embedding backfill position
+ALTER TABLE embedding_processing_status
+    DROP COLUMN IF EXISTS backfill_errors,
+    DROP COLUMN IF EXISTS backfill_success,
+    DROP COLUMN IF EXISTS backfill_paused,
+    DROP COLUMN IF EXISTS backfill_cursor;
@@ -0,0 +1,7 @@
+-- Revert 030: back to the IVFFlat similarity index
+DROP INDEX IF EXISTS idx_track_embeddings_vector_new;
+DROP INDEX IF EXISTS idx_track_embeddings_vector;
+
+CREATE INDEX idx_track_embeddings_vector
+ON track_embeddings
+USING ivfflat (embedding vector_l2_ops) WITH (lists = 100);
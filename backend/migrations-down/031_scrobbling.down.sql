@@ -0,0 +1,3 @@
+-- Revert 031: drop scrobble accounts and pending listens
+DROP TABLE IF EXISTS scrobble_queue;
+DROP TABLE IF EXISTS user_scrobble_accounts;
@@ -0,0 +1,3 @@
+-- Revert 032: drop fetched artist metadata
+DROP INDEX IF EXISTS idx_library_index_artist_lower;
+DROP TABLE IF EXISTS external_metadata;
@@ -0,0 +1,2 @@
+-- Revert 033: drop station program blocks
+DROP TABLE IF EXISTS station_schedule_blocks;
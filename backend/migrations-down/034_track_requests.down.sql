@@ -0,0 +1,3 @@
+-- Revert 034: drop track requests and their votes
+DROP TABLE IF EXISTS track_request_votes;
+DROP TABLE IF EXISTS track_requests;
@@ -0,0 +1,2 @@
+-- Revert 035: drop thumbs up/down feedback
+DROP TABLE IF EXISTS user_track_feedback;
@@ -0,0 +1,2 @@
+-- Revert 036: drop per-window embeddings
+DROP TABLE IF EXISTS track_embedding_windows;
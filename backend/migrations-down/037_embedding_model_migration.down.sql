@@ -0,0 +1,3 @@
+-- Revert 037: drop staged embeddings of an unfinished model migration
+DROP INDEX IF EXISTS idx_track_embeddings_model_version;
+DROP TABLE IF EXISTS track_embeddings_staging;
@@ -0,0 +1,2 @@
+-- Revert 038: drop station event history
+DROP TABLE IF EXISTS station_analytics_events;
@@ -0,0 +1,3 @@
+-- Revert 039: drop OpenID Connect identities
+ALTER TABLE users DROP COLUMN IF EXISTS has_password;
+DROP TABLE IF EXISTS user_identities;
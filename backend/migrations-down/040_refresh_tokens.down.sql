@@ -0,0 +1,7 @@
+-- Revert 040: drop session refresh tokens
+DROP INDEX IF EXISTS idx_user_sessions_previous_refresh_token;
+DROP INDEX IF EXISTS idx_user_sessions_refresh_token;
+ALTER TABLE user_sessions
+    DROP COLUMN IF EXISTS refreshed_at,
+    DROP COLUMN IF EXISTS previous_refresh_token_hash,
+    DROP COLUMN IF EXISTS refresh_token_hash;
@@ -0,0 +1,5 @@
+-- Revert 041: back to station sharing (moderator grants are dropped)
+DELETE FROM station_permissions WHERE role <> 'curator';
+ALTER TABLE station_permissions DROP COLUMN IF EXISTS role;
+ALTER INDEX IF EXISTS idx_station_permissions_user RENAME TO idx_station_collaborators_user;
+ALTER TABLE IF EXISTS station_permissions RENAME TO station_collaborators;
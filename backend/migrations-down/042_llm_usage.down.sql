@@ -0,0 +1,3 @@
+-- Revert 042: drop LLM usage accounting and the response cache
+DROP TABLE IF EXISTS llm_response_cache;
+DROP TABLE IF EXISTS llm_usage;
@@ -0,0 +1,8 @@
+-- Revert 043: drop MusicBrainz/AcousticBrainz track metadata
+DROP INDEX IF EXISTS idx_library_index_musicbrainz_tags;
+ALTER TABLE library_index
+    DROP COLUMN IF EXISTS features_source,
+    DROP COLUMN IF EXISTS musical_key,
+    DROP COLUMN IF EXISTS musicbrainz_tags,
+    DROP COLUMN IF EXISTS original_year;
+DROP TABLE IF EXISTS track_external_metadata;
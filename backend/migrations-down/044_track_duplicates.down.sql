@@ -0,0 +1,2 @@
+-- Revert 044: drop duplicate track groups
+DROP TABLE IF EXISTS track_duplicates;
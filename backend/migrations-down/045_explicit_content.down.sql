@@ -0,0 +1,6 @@
+-- Revert 045: drop track explicit status and restore the explicit_content station setting
+UPDATE stations
+SET config = (config - 'allow_explicit') || jsonb_build_object('explicit_content', config->'allow_explicit')
+WHERE config ? 'allow_explicit';
+DROP INDEX IF EXISTS idx_library_index_explicit;
+ALTER TABLE library_index DROP COLUMN IF EXISTS explicit;
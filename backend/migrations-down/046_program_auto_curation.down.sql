@@ -0,0 +1,4 @@
+-- Revert 046: drop program block auto-curation
+ALTER TABLE station_schedule_blocks
+    DROP COLUMN IF EXISTS curated_at,
+    DROP COLUMN IF EXISTS curation_prompt;
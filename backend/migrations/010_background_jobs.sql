@@ -0,0 +1,30 @@
+-- Background jobs
+-- Persistent record of long-running work (library sync, AI analysis, embedding indexing, ...)
+
+CREATE TABLE background_jobs (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    kind VARCHAR(50) NOT NULL,
+    status TEXT NOT NULL DEFAULT 'queued'
+        CHECK (status IN ('queued', 'running', 'completed', 'failed', 'cancelled')),
+    payload JSONB NOT NULL DEFAULT '{}'::jsonb,
+    result JSONB,
+    progress FLOAT NOT NULL DEFAULT 0,  -- 0.0 to 1.0
+    message TEXT,
+    error TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL DEFAULT 1,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_background_jobs_status ON background_jobs(status, created_at DESC);
+CREATE INDEX idx_background_jobs_kind ON background_jobs(kind, created_at DESC);
+
+-- Only one active job of each kind at a time
+CREATE UNIQUE INDEX idx_background_jobs_active_kind ON background_jobs(kind)
+    WHERE status IN ('queued', 'running');
+
+CREATE TRIGGER update_background_jobs_updated_at BEFORE UPDATE ON background_jobs
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
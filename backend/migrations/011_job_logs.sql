@@ -0,0 +1,10 @@
+-- Per-job log lines for the admin jobs dashboard
+CREATE TABLE background_job_logs (
+    id BIGSERIAL PRIMARY KEY,
+    job_id UUID NOT NULL REFERENCES background_jobs(id) ON DELETE CASCADE,
+    level VARCHAR(10) NOT NULL CHECK (level IN ('info', 'warn', 'error')),
+    message TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_background_job_logs_job ON background_job_logs(job_id, id);
@@ -0,0 +1,2 @@
+-- Navidrome internet radio station ID for stations published back into Navidrome
+ALTER TABLE stations ADD COLUMN navidrome_radio_id VARCHAR(100);
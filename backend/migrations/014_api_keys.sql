@@ -0,0 +1,17 @@
+-- Long-lived API keys for automation (Home Assistant, cron jobs, ...)
+-- Only a SHA-256 hash of the key is stored; the plaintext is shown once at creation.
+
+CREATE TABLE api_keys (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    name VARCHAR(100) NOT NULL,
+    key_prefix VARCHAR(16) NOT NULL,        -- First characters of the key, for identification
+    key_hash VARCHAR(64) UNIQUE NOT NULL,   -- Hex SHA-256 of the full key
+    scopes JSONB NOT NULL DEFAULT '[]'::jsonb,
+    created_by UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ,
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_api_keys_created_by ON api_keys(created_by);
@@ -0,0 +1,6 @@
+-- Intermediate roles between admin and listener:
+--   curator   - creates and manages their own stations
+--   moderator - approves requests and skips tracks
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_role_check;
+ALTER TABLE users ADD CONSTRAINT users_role_check
+    CHECK (role IN ('admin', 'curator', 'moderator', 'listener'));
@@ -0,0 +1,12 @@
+-- Users who co-manage a station alongside its creator
+-- Collaborators can edit, start and stop the station; only the creator (or an admin)
+-- can delete it or change who it is shared with.
+CREATE TABLE station_collaborators (
+    station_id UUID NOT NULL REFERENCES stations(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    granted_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (station_id, user_id)
+);
+
+CREATE INDEX idx_station_collaborators_user ON station_collaborators(user_id);
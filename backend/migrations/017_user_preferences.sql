@@ -0,0 +1,2 @@
+-- Per-user preferences (default station, stream bitrate, visualization, theme)
+ALTER TABLE users ADD COLUMN preferences JSONB NOT NULL DEFAULT '{}'::jsonb;
@@ -0,0 +1,28 @@
+-- Per-user listening history and favorites (feeds personalization)
+
+-- One row per track a logged-in user heard on a station, extended by each heartbeat
+CREATE TABLE listening_history (
+    id BIGSERIAL PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    station_id UUID NOT NULL REFERENCES stations(id) ON DELETE CASCADE,
+    track_id VARCHAR(100) NOT NULL,
+    first_heard_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_heard_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_listening_history_user ON listening_history(user_id, last_heard_at DESC);
+CREATE INDEX idx_listening_history_user_station ON listening_history(user_id, station_id, last_heard_at DESC);
+
+CREATE TABLE user_favorite_stations (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    station_id UUID NOT NULL REFERENCES stations(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, station_id)
+);
+
+CREATE TABLE user_favorite_tracks (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    track_id VARCHAR(100) NOT NULL REFERENCES library_index(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, track_id)
+);
@@ -0,0 +1,5 @@
+-- Personal "Daily Mix" stations
+-- A station with daily_mix_user_id set is that user's auto-generated mix, rebuilt each morning
+
+ALTER TABLE stations
+    ADD COLUMN daily_mix_user_id UUID UNIQUE REFERENCES users(id) ON DELETE CASCADE;
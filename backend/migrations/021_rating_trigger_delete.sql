@@ -0,0 +1,16 @@
+-- Keep library_index rating aggregates correct when a rating is deleted
+-- (the original trigger read NEW, which is NULL for DELETE)
+
+CREATE OR REPLACE FUNCTION update_track_ratings()
+RETURNS TRIGGER AS $$
+DECLARE
+    affected_track VARCHAR(100) := COALESCE(NEW.track_id, OLD.track_id);
+BEGIN
+    UPDATE library_index
+    SET
+        avg_rating = (SELECT AVG(rating) FROM user_track_ratings WHERE track_id = affected_track),
+        rating_count = (SELECT COUNT(*) FROM user_track_ratings WHERE track_id = affected_track)
+    WHERE id = affected_track;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
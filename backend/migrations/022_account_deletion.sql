@@ -0,0 +1,8 @@
+-- Keep listening history when an account is deleted, detached from the user,
+-- so station play statistics survive account deletion without identifying anyone
+
+ALTER TABLE listening_history ALTER COLUMN user_id DROP NOT NULL;
+ALTER TABLE listening_history DROP CONSTRAINT listening_history_user_id_fkey;
+ALTER TABLE listening_history
+    ADD CONSTRAINT listening_history_user_id_fkey
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL;
@@ -0,0 +1,4 @@
+-- Restrict an API key to specific stations (NULL = all stations).
+-- Used by stream-only keys embedded in hardware players' stream URLs.
+
+ALTER TABLE api_keys ADD COLUMN station_ids UUID[];
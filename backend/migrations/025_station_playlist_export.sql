@@ -0,0 +1,4 @@
+-- Navidrome playlist a station was last exported to, so later exports update it
+-- instead of creating another copy.
+
+ALTER TABLE stations ADD COLUMN navidrome_playlist_id TEXT;
@@ -0,0 +1,11 @@
+-- Stations imported from a Navidrome playlist. With source_playlist_sync set, the
+-- station's track_ids follow the playlist; source_playlist_synced_at holds the
+-- playlist's "changed" timestamp as of the last sync.
+
+ALTER TABLE stations
+    ADD COLUMN source_playlist_id TEXT,
+    ADD COLUMN source_playlist_sync BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN source_playlist_synced_at TIMESTAMPTZ;
+
+CREATE INDEX idx_stations_source_playlist ON stations(source_playlist_id)
+    WHERE source_playlist_sync;
@@ -0,0 +1,12 @@
+-- Users' own Navidrome accounts. The password or API key is encrypted by the app
+-- (nonce || AES-256-GCM ciphertext), since it must be recoverable to call Navidrome.
+
+CREATE TABLE user_navidrome_accounts (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    navidrome_username VARCHAR(255) NOT NULL,
+    auth_type VARCHAR(20) NOT NULL CHECK (auth_type IN ('password', 'api_key')),
+    secret BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_verified_at TIMESTAMPTZ
+);
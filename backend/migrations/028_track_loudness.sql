@@ -0,0 +1,10 @@
+-- EBU R128 loudness of each track, measured by the loudness analysis job and used
+-- to normalize broadcast output. NULL until the track has been analyzed.
+
+ALTER TABLE library_index
+    ADD COLUMN loudness_lufs REAL,
+    ADD COLUMN sample_peak REAL,
+    ADD COLUMN loudness_analyzed_at TIMESTAMPTZ;
+
+CREATE INDEX idx_library_index_loudness_pending ON library_index(id)
+    WHERE loudness_analyzed_at IS NULL;
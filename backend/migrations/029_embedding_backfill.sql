@@ -0,0 +1,13 @@
+-- Position of the running embedding backfill, so it resumes after a restart
+-- instead of starting over.
+
+ALTER TABLE embedding_processing_status
+    ADD COLUMN backfill_cursor VARCHAR(100),
+    ADD COLUMN backfill_paused BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN backfill_success INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN backfill_errors INTEGER NOT NULL DEFAULT 0;
+
+-- Nothing has maintained this flag before
+UPDATE embedding_processing_status SET is_processing = false;
+
+INSERT INTO embedding_processing_status (id) VALUES (1) ON CONFLICT (id) DO NOTHING;
@@ -0,0 +1,10 @@
+-- Replace the IVFFlat similarity index with HNSW (pgvector 0.5+). The IVFFlat lists
+-- were trained when migration 007 had just emptied the table, so its recall is poor;
+-- HNSW needs no training and stays accurate as embeddings are added.
+-- The index can be rebuilt with other parameters via POST /api/v1/embeddings/vector-index.
+
+DROP INDEX IF EXISTS idx_track_embeddings_vector;
+
+CREATE INDEX idx_track_embeddings_vector
+ON track_embeddings
+USING hnsw (embedding vector_l2_ops) WITH (m = 16, ef_construction = 64);
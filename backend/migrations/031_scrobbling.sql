@@ -0,0 +1,26 @@
+-- Scrobbling: users' scrobble service credentials and the queue of listens waiting to
+-- be submitted. Tokens are encrypted by the app like Navidrome credentials.
+
+CREATE TABLE user_scrobble_accounts (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    service VARCHAR(20) NOT NULL,
+    username VARCHAR(255) NOT NULL,
+    secret BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, service)
+);
+
+-- user_id NULL: the listen belongs to the server's own account (the station's plays)
+CREATE TABLE scrobble_queue (
+    id BIGSERIAL PRIMARY KEY,
+    service VARCHAR(20) NOT NULL,
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    station_id UUID REFERENCES stations(id) ON DELETE SET NULL,
+    listen JSONB NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_scrobble_queue_due ON scrobble_queue(service, next_attempt_at);
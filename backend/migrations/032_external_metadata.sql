@@ -0,0 +1,20 @@
+-- Artist metadata from external services (Last.fm): tags and similar artists, used by
+-- curation for tracks without embeddings. Keyed by the lowercased artist name, since
+-- that is all the services and the library share.
+
+CREATE TABLE external_metadata (
+    source VARCHAR(20) NOT NULL,
+    artist_key VARCHAR(500) NOT NULL,
+    artist VARCHAR(500) NOT NULL,
+    -- Lowercased tag names, strongest first
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    -- [{"name": "...", "match": 0.0-1.0}], best match first
+    similar_artists JSONB NOT NULL DEFAULT '[]',
+    fetched_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (source, artist_key)
+);
+
+CREATE INDEX idx_external_metadata_tags ON external_metadata USING GIN (tags);
+
+-- Matches external artist names against the library
+CREATE INDEX idx_library_index_artist_lower ON library_index (LOWER(artist));
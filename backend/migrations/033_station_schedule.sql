@@ -0,0 +1,19 @@
+-- Program blocks: time windows in which a station plays from its own genres or track
+-- pool instead of the station's. Times are in the station's timezone (config.timezone).
+
+CREATE TABLE station_schedule_blocks (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    station_id UUID NOT NULL REFERENCES stations(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    -- Days the block starts on, 0 = Monday .. 6 = Sunday; empty = every day
+    days SMALLINT[] NOT NULL DEFAULT '{}',
+    -- A block ending before it starts runs past midnight; equal times cover the whole day
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+    genres JSONB NOT NULL DEFAULT '[]',
+    track_ids JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_station_schedule_blocks_station ON station_schedule_blocks(station_id);
@@ -0,0 +1,26 @@
+-- Listener track requests: pending requests are played before curated selection,
+-- highest-voted first. The requester's own vote is a row in track_request_votes.
+
+CREATE TABLE track_requests (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    station_id UUID NOT NULL REFERENCES stations(id) ON DELETE CASCADE,
+    track_id VARCHAR(255) NOT NULL,
+    requested_by UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- pending, played or expired
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    played_at TIMESTAMPTZ
+);
+
+-- A track has at most one pending request per station; requesting it again votes for it
+CREATE UNIQUE INDEX idx_track_requests_pending ON track_requests(station_id, track_id)
+    WHERE status = 'pending';
+-- Per-user rate limit
+CREATE INDEX idx_track_requests_user ON track_requests(requested_by, created_at);
+
+CREATE TABLE track_request_votes (
+    request_id UUID NOT NULL REFERENCES track_requests(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (request_id, user_id)
+);
@@ -0,0 +1,15 @@
+-- Thumbs up/down on tracks, one per user and track. station_id is where the listener
+-- gave it (NULL from the library).
+
+CREATE TABLE user_track_feedback (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    track_id VARCHAR(100) NOT NULL REFERENCES library_index(id) ON DELETE CASCADE,
+    -- 1 = thumbs up, -1 = thumbs down
+    value SMALLINT NOT NULL CHECK (value IN (-1, 1)),
+    station_id UUID REFERENCES stations(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, track_id)
+);
+
+CREATE INDEX idx_user_track_feedback_track ON user_track_feedback(track_id);
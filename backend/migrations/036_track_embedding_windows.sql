@@ -0,0 +1,11 @@
+-- Embeddings of the individual windows of a track, kept when EMBEDDING_STORE_WINDOWS
+-- is on. track_embeddings holds their average.
+
+CREATE TABLE track_embedding_windows (
+    track_id VARCHAR(100) NOT NULL REFERENCES library_index(id) ON DELETE CASCADE,
+    -- Position in the track, from 0
+    window_index SMALLINT NOT NULL,
+    start_ms INTEGER NOT NULL,
+    embedding vector(100) NOT NULL,
+    PRIMARY KEY (track_id, window_index)
+);
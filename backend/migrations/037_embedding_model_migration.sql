@@ -0,0 +1,14 @@
+-- Re-embedding after the audio encoder model changes. New embeddings are staged here
+-- while similarity search keeps using the old ones, and swapped in once every
+-- outdated track has been re-encoded.
+
+CREATE TABLE track_embeddings_staging (
+    track_id VARCHAR(100) PRIMARY KEY REFERENCES library_index(id) ON DELETE CASCADE,
+    embedding vector(100) NOT NULL,
+    model_version VARCHAR(50) NOT NULL,
+    processing_time_ms INTEGER,
+    computed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Finding outdated embeddings
+CREATE INDEX idx_track_embeddings_model_version ON track_embeddings(model_version);
@@ -0,0 +1,19 @@
+-- Station event history for analytics: plays, skips, listener joins and leaves, and
+-- curation runs. listeners is the station's listener count at a play; duration_ms is
+-- how long a curation run took.
+
+CREATE TABLE station_analytics_events (
+    id BIGSERIAL PRIMARY KEY,
+    station_id UUID NOT NULL REFERENCES stations(id) ON DELETE CASCADE,
+    -- play, skip, listener_join, listener_leave or curation
+    kind VARCHAR(20) NOT NULL,
+    track_id VARCHAR(255),
+    session_id VARCHAR(255),
+    listeners INTEGER,
+    details JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_station_analytics_events_station ON station_analytics_events(station_id, kind, created_at);
+CREATE INDEX idx_station_analytics_events_session ON station_analytics_events(station_id, session_id)
+    WHERE session_id IS NOT NULL;
@@ -0,0 +1,12 @@
+-- Refresh tokens of login sessions. Only hashes are stored. Each refresh rotates the
+-- token; the one it replaced is kept so a stolen, already-used token can be recognized
+-- and its session revoked.
+
+ALTER TABLE user_sessions
+    ADD COLUMN refresh_token_hash VARCHAR(64),
+    ADD COLUMN previous_refresh_token_hash VARCHAR(64),
+    ADD COLUMN refreshed_at TIMESTAMPTZ;
+
+CREATE UNIQUE INDEX idx_user_sessions_refresh_token ON user_sessions(refresh_token_hash);
+CREATE INDEX idx_user_sessions_previous_refresh_token ON user_sessions(previous_refresh_token_hash)
+    WHERE previous_refresh_token_hash IS NOT NULL;
@@ -0,0 +1,11 @@
+-- Per-station roles. Station sharing becomes a grant of a role on one station:
+-- curator (edit config, start, stop, skip, schedule, export) or moderator (skip).
+-- Grants work for any user, whatever their global role, so a listener can run a single
+-- station while every other station stays with its creator and the admins.
+
+ALTER TABLE station_collaborators RENAME TO station_permissions;
+ALTER INDEX idx_station_collaborators_user RENAME TO idx_station_permissions_user;
+
+ALTER TABLE station_permissions
+    ADD COLUMN role VARCHAR(20) NOT NULL DEFAULT 'curator'
+        CHECK (role IN ('curator', 'moderator'));
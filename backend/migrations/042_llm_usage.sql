@@ -0,0 +1,28 @@
+-- Token and cost accounting for LLM calls, and a cache of their responses
+
+CREATE TABLE llm_usage (
+    id BIGSERIAL PRIMARY KEY,
+    feature VARCHAR(50) NOT NULL,
+    model VARCHAR(100) NOT NULL,
+    input_tokens BIGINT NOT NULL DEFAULT 0,
+    output_tokens BIGINT NOT NULL DEFAULT 0,
+    -- Estimated from list prices; 0 for calls answered from the cache
+    cost_usd DOUBLE PRECISION NOT NULL DEFAULT 0,
+    cached BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_llm_usage_created_at ON llm_usage(created_at);
+
+CREATE TABLE llm_response_cache (
+    -- SHA-256 of model, max_tokens and prompt
+    prompt_hash VARCHAR(64) PRIMARY KEY,
+    feature VARCHAR(50) NOT NULL,
+    model VARCHAR(100) NOT NULL,
+    response TEXT NOT NULL,
+    hits INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_llm_response_cache_created_at ON llm_response_cache(created_at);
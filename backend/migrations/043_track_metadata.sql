@@ -0,0 +1,28 @@
+-- Per-track metadata from MusicBrainz and AcousticBrainz. Artist-level metadata stays
+-- in external_metadata; these rows belong to a single library track.
+
+CREATE TABLE track_external_metadata (
+    track_id VARCHAR(100) NOT NULL REFERENCES library_index(id) ON DELETE CASCADE,
+    source VARCHAR(20) NOT NULL,
+    -- MusicBrainz recording the data belongs to; NULL when no recording matched
+    recording_id VARCHAR(36),
+    -- Lowercased tag names, strongest first
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    -- Year of the recording's first release
+    year INTEGER,
+    -- Source-specific values, e.g. AcousticBrainz bpm, key and classifier probabilities
+    features JSONB NOT NULL DEFAULT '{}',
+    fetched_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (track_id, source)
+);
+
+ALTER TABLE library_index
+    -- First release year when it differs from the tagged one (remasters, compilations)
+    ADD COLUMN original_year INTEGER,
+    ADD COLUMN musicbrainz_tags TEXT[] NOT NULL DEFAULT '{}',
+    -- e.g. "A minor"
+    ADD COLUMN musical_key VARCHAR(12),
+    -- Where measured audio features came from; AI analysis doesn't overwrite them
+    ADD COLUMN features_source VARCHAR(20);
+
+CREATE INDEX idx_library_index_musicbrainz_tags ON library_index USING GIN (musicbrainz_tags);
@@ -0,0 +1,11 @@
+-- Tracks found to be versions of the same song (rips, remasters, compilation copies).
+-- Rebuilt by each duplicate scan; tracks without a duplicate have no row.
+
+CREATE TABLE track_duplicates (
+    track_id VARCHAR(100) PRIMARY KEY REFERENCES library_index(id) ON DELETE CASCADE,
+    -- ID of the group's preferred version (its own row has track_id = group_id)
+    group_id VARCHAR(100) NOT NULL,
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_track_duplicates_group ON track_duplicates(group_id);
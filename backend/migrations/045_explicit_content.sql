@@ -0,0 +1,13 @@
+-- Explicit-content status per track and the per-station switch that keeps explicit
+-- tracks off the air.
+
+ALTER TABLE library_index
+    -- From Navidrome's explicit tag, else AI analysis; NULL when unknown
+    ADD COLUMN explicit BOOLEAN;
+
+CREATE INDEX idx_library_index_explicit ON library_index(id) WHERE explicit;
+
+-- The station setting was renamed from explicit_content to allow_explicit
+UPDATE stations
+SET config = (config - 'explicit_content') || jsonb_build_object('allow_explicit', config->'explicit_content')
+WHERE config ? 'explicit_content';
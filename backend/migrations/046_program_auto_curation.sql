@@ -0,0 +1,8 @@
+-- Program blocks whose track pool is curated automatically from a prompt each time
+-- they come on air
+
+ALTER TABLE station_schedule_blocks
+    -- e.g. "upbeat morning pop"; NULL for blocks with a fixed pool or genres
+    ADD COLUMN curation_prompt TEXT,
+    -- When track_ids were last curated from the prompt
+    ADD COLUMN curated_at TIMESTAMPTZ;
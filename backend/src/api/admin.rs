@@ -0,0 +1,424 @@
+use crate::api::middleware::RequireAdmin;
+use crate::api::stations::AppState;
+use crate::error::{AppError, Result};
+use crate::models::{
+    AdminCreateUserRequest, AdminSession, ApiKey, AuditLogEntry, CreateApiKeyRequest, CreateApiKeyResponse, Job,
+    JobKind, JobLog, JobStatus, UpdateUserRequest, User,
+};
+use crate::services::{audit, sessions};
+use crate::services::migrations::{self, MigrationStatus};
+use crate::services::navidrome::NavidromeStatus;
+use axum::{
+    extract::{Path, Query, State},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Deserialize)]
+struct ListJobsQuery {
+    status: Option<JobStatus>,
+    kind: Option<JobKind>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+struct ListJobsResponse {
+    jobs: Vec<Job>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NavidromeStatusQuery {
+    #[serde(default)]
+    refresh: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct AuditLogQuery {
+    target_type: Option<String>,
+    target_id: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+struct JobDetailsResponse {
+    job: Job,
+    logs: Vec<JobLog>,
+}
+
+pub fn admin_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/jobs", get(list_jobs))
+        .route("/jobs/:id", get(get_job))
+        .route("/jobs/:id/cancel", post(cancel_job))
+        .route("/jobs/:id/retry", post(retry_job))
+        .route("/navidrome/status", get(navidrome_status))
+        .route("/navidrome/radio-sync", post(sync_navidrome_radio))
+        .route("/daily-mix/rebuild", post(rebuild_daily_mixes))
+        .route("/migrations", get(migration_status))
+        .route("/api-keys", get(list_api_keys).post(create_api_key))
+        .route("/api-keys/:id", delete(revoke_api_key))
+        .route("/users", get(list_users).post(create_user))
+        .route("/users/:id", get(get_user).patch(update_user).delete(delete_user))
+        .route("/users/:id/sessions", delete(revoke_user_sessions))
+        .route("/sessions", get(list_sessions))
+        .route("/sessions/:id", delete(revoke_session))
+        .route("/audit-log", get(list_audit_log))
+}
+
+/// GET /api/v1/admin/jobs
+/// List background jobs, optionally filtered by status and kind
+async fn list_jobs(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+    Query(params): Query<ListJobsQuery>,
+) -> Result<Json<ListJobsResponse>> {
+    let jobs = state
+        .jobs
+        .list(
+            params.status,
+            params.kind,
+            params.limit.unwrap_or(50),
+            params.offset.unwrap_or(0),
+        )
+        .await?;
+
+    Ok(Json(ListJobsResponse { jobs }))
+}
+
+/// GET /api/v1/admin/jobs/:id
+/// Get a job's progress and log lines
+async fn get_job(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+    Path(id): Path<Uuid>,
+) -> Result<Json<JobDetailsResponse>> {
+    let job = state.jobs.get(id).await?;
+    let logs = state.jobs.logs(id).await?;
+
+    Ok(Json(JobDetailsResponse { job, logs }))
+}
+
+/// POST /api/v1/admin/jobs/:id/cancel
+/// Request cancellation of a queued or running job
+async fn cancel_job(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+    Path(id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>> {
+    state.jobs.cancel(id).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Cancellation requested",
+        "job_id": id
+    })))
+}
+
+/// POST /api/v1/admin/jobs/:id/retry
+/// Re-run a failed or cancelled job
+async fn retry_job(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Job>> {
+    let job = state.jobs.retry(id).await?;
+    Ok(Json(job))
+}
+
+/// GET /api/v1/admin/navidrome/status
+/// Navidrome reachability and version; `?refresh=true` pings it now instead of using the cache
+async fn navidrome_status(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+    Query(params): Query<NavidromeStatusQuery>,
+) -> Result<Json<NavidromeStatus>> {
+    let status = if params.refresh {
+        state.navidrome_client.ping().await
+    } else {
+        state.navidrome_client.status().await
+    };
+    Ok(Json(status))
+}
+
+/// POST /api/v1/admin/navidrome/radio-sync
+/// Re-publish all stations into Navidrome's internet radio list
+async fn sync_navidrome_radio(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+) -> Result<Json<serde_json::Value>> {
+    let publisher = state.radio_publisher.as_ref().ok_or_else(|| {
+        AppError::BadRequest(
+            "Station publishing not enabled - set NAVIDROME_PUBLISH_STATIONS and PUBLIC_URL".to_string(),
+        )
+    })?;
+
+    let synced = publisher.sync_all().await?;
+
+    Ok(Json(serde_json::json!({
+        "message": format!("Synced {} station(s) to Navidrome", synced),
+        "synced": synced
+    })))
+}
+
+/// POST /api/v1/admin/daily-mix/rebuild
+/// Rebuild every user's Daily Mix now instead of waiting for the daily run
+async fn rebuild_daily_mixes(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+) -> Result<Json<serde_json::Value>> {
+    let daily_mix = state.daily_mix.as_ref().ok_or_else(|| {
+        AppError::BadRequest(
+            "Daily Mix not available - requires the hybrid curator and DAILY_MIX_ENABLED".to_string(),
+        )
+    })?;
+
+    let job_id = daily_mix.submit(&state.jobs).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Daily Mix rebuild started",
+        "job_id": job_id
+    })))
+}
+
+/// GET /api/v1/admin/migrations
+/// Current schema version, pending migrations and any drift from this build
+async fn migration_status(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+) -> Result<Json<MigrationStatus>> {
+    let status = migrations::status(&state.db).await?;
+    Ok(Json(status))
+}
+
+/// GET /api/v1/admin/api-keys
+/// List API keys (including revoked ones); secrets are never returned
+async fn list_api_keys(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+) -> Result<Json<Vec<ApiKey>>> {
+    let keys = state.auth_service.list_api_keys().await?;
+    Ok(Json(keys))
+}
+
+/// POST /api/v1/admin/api-keys
+/// Create a scoped API key owned by the calling admin; the key is only shown in this response
+async fn create_api_key(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(claims): RequireAdmin,
+    Json(req): Json<CreateApiKeyRequest>,
+) -> Result<Json<CreateApiKeyResponse>> {
+    req.validate()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    let response = state.auth_service.create_api_key(claims.sub, req).await?;
+    audit::record(
+        &state.db,
+        claims.sub,
+        "api_key.create",
+        "api_key",
+        response.api_key.id,
+        serde_json::json!({ "name": response.api_key.name, "scopes": response.api_key.scopes }),
+    )
+    .await;
+
+    Ok(Json(response))
+}
+
+/// DELETE /api/v1/admin/api-keys/:id
+/// Revoke an API key immediately
+async fn revoke_api_key(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(claims): RequireAdmin,
+    Path(id): Path<Uuid>,
+) -> Result<Json<()>> {
+    state.auth_service.revoke_api_key(id, None).await?;
+    audit::record(&state.db, claims.sub, "api_key.revoke", "api_key", id, serde_json::json!({})).await;
+    Ok(Json(()))
+}
+
+/// GET /api/v1/admin/users
+/// List all accounts, including disabled ones
+async fn list_users(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+) -> Result<Json<Vec<User>>> {
+    let users = state.auth_service.list_users().await?;
+    Ok(Json(users))
+}
+
+/// POST /api/v1/admin/users
+/// Create an account with any role
+async fn create_user(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(claims): RequireAdmin,
+    Json(req): Json<AdminCreateUserRequest>,
+) -> Result<Json<User>> {
+    req.validate()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    let user = state.auth_service.create_user(req).await?;
+    audit::record(
+        &state.db,
+        claims.sub,
+        "user.create",
+        "user",
+        user.id,
+        serde_json::json!({ "username": user.username, "role": user.role }),
+    )
+    .await;
+
+    Ok(Json(user))
+}
+
+/// GET /api/v1/admin/users/:id
+/// Get one account
+async fn get_user(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+    Path(id): Path<Uuid>,
+) -> Result<Json<User>> {
+    let user = state.auth_service.get_user_by_id(id).await?;
+    Ok(Json(user))
+}
+
+/// PATCH /api/v1/admin/users/:id
+/// Change a user's role or email, disable or re-enable the account, or reset its password.
+/// Takes effect immediately, including for tokens already issued.
+async fn update_user(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(claims): RequireAdmin,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdateUserRequest>,
+) -> Result<Json<User>> {
+    req.validate()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    if id == claims.sub && (req.disabled == Some(true) || req.role.is_some()) {
+        return Err(AppError::BadRequest(
+            "You can't disable your own account or change your own role".to_string(),
+        ));
+    }
+
+    let before = state.auth_service.get_user_by_id(id).await?;
+    let user = state.auth_service.update_user(id, &req).await?;
+
+    let mut changes = serde_json::Map::new();
+    if before.role != user.role {
+        changes.insert("role".into(), serde_json::json!({ "from": before.role, "to": user.role }));
+    }
+    if before.email != user.email {
+        changes.insert("email".into(), serde_json::json!({ "from": before.email, "to": user.email }));
+    }
+    if before.disabled_at.is_some() != user.disabled_at.is_some() {
+        changes.insert("disabled".into(), serde_json::json!(user.disabled_at.is_some()));
+    }
+    if req.password.is_some() {
+        changes.insert("password_reset".into(), serde_json::json!(true));
+    }
+    if !changes.is_empty() {
+        audit::record(&state.db, claims.sub, "user.update", "user", id, changes.into()).await;
+    }
+
+    Ok(Json(user))
+}
+
+/// DELETE /api/v1/admin/users/:id
+/// Delete an account. Stations it created are transferred to the calling admin.
+async fn delete_user(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(claims): RequireAdmin,
+    Path(id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>> {
+    if id == claims.sub {
+        return Err(AppError::BadRequest("You can't delete your own account".to_string()));
+    }
+
+    let user = state.auth_service.get_user_by_id(id).await?;
+    let stations_transferred = state.auth_service.delete_user(id, claims.sub).await?;
+    audit::record(
+        &state.db,
+        claims.sub,
+        "user.delete",
+        "user",
+        id,
+        serde_json::json!({
+            "username": user.username,
+            "role": user.role,
+            "stations_transferred": stations_transferred,
+        }),
+    )
+    .await;
+
+    Ok(Json(serde_json::json!({
+        "message": format!("Deleted user {}", user.username),
+        "stations_transferred": stations_transferred
+    })))
+}
+
+/// GET /api/v1/admin/sessions
+/// Every active login session, most recently used first
+async fn list_sessions(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(claims): RequireAdmin,
+) -> Result<Json<Vec<AdminSession>>> {
+    let sessions = sessions::list_all(&state.db, claims.sid).await?;
+    Ok(Json(sessions))
+}
+
+/// DELETE /api/v1/admin/sessions/:id
+/// End a session; its access and refresh tokens stop working immediately
+async fn revoke_session(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(claims): RequireAdmin,
+    Path(id): Path<Uuid>,
+) -> Result<Json<()>> {
+    sessions::revoke_any(&state.db, id).await?;
+    audit::record(&state.db, claims.sub, "session.revoke", "session", id, serde_json::json!({})).await;
+    Ok(Json(()))
+}
+
+/// DELETE /api/v1/admin/users/:id/sessions
+/// Sign a user out everywhere
+async fn revoke_user_sessions(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(claims): RequireAdmin,
+    Path(id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>> {
+    let user = state.auth_service.get_user_by_id(id).await?;
+    let revoked = sessions::revoke_all(&state.db, id).await?;
+    audit::record(
+        &state.db,
+        claims.sub,
+        "user.sessions_revoke",
+        "user",
+        id,
+        serde_json::json!({ "username": user.username, "sessions": revoked }),
+    )
+    .await;
+
+    Ok(Json(serde_json::json!({ "revoked": revoked })))
+}
+
+/// GET /api/v1/admin/audit-log
+/// Recent admin actions, optionally filtered by `target_type` and `target_id`
+async fn list_audit_log(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+    Query(params): Query<AuditLogQuery>,
+) -> Result<Json<Vec<AuditLogEntry>>> {
+    let entries = audit::list(
+        &state.db,
+        params.target_type.as_deref(),
+        params.target_id.as_deref(),
+        params.limit.unwrap_or(100),
+        params.offset.unwrap_or(0),
+    )
+    .await?;
+
+    Ok(Json(entries))
+}
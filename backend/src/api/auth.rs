@@ -1,11 +1,18 @@
+use crate::api::middleware::RequireAuth;
 use crate::api::stations::AppState;
-use crate::error::Result;
-use crate::models::{AuthResponse, CreateUserRequest, LoginRequest};
+use crate::error::{AppError, Result};
+use crate::models::{
+    AuthResponse, CreateUserRequest, DeviceInfo, LoginRequest, UserPreferences, STREAM_BITRATES,
+};
+use crate::services::oidc::OidcClient;
+use crate::services::sessions;
 use axum::{
-    extract::State,
+    extract::{Query, State},
+    response::Redirect,
     routing::{get, post},
     Json, Router,
 };
+use serde::Deserialize;
 use std::sync::Arc;
 use validator::Validate;
 
@@ -13,31 +20,96 @@ pub fn auth_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/register", post(register))
         .route("/login", post(login))
+        .route("/refresh", post(refresh))
+        .route("/logout", post(logout))
+        .route("/logout-all", post(logout_all))
         .route("/me", get(me))
+        .route("/me/preferences", get(get_preferences).put(update_preferences))
+        .route("/oidc/login", get(oidc_login))
+        .route("/oidc/callback", get(oidc_callback))
+        .route("/oidc/refresh", post(oidc_refresh))
 }
 
 async fn register(
     State(state): State<Arc<AppState>>,
+    device: DeviceInfo,
     Json(req): Json<CreateUserRequest>,
 ) -> Result<Json<AuthResponse>> {
     req.validate()
         .map_err(|e| crate::error::AppError::Validation(e.to_string()))?;
 
-    let response = state.auth_service.register(req).await?;
+    let response = state.auth_service.register(req, &device).await?;
     Ok(Json(response))
 }
 
 async fn login(
     State(state): State<Arc<AppState>>,
+    device: DeviceInfo,
     Json(req): Json<LoginRequest>,
 ) -> Result<Json<AuthResponse>> {
     req.validate()
         .map_err(|e| crate::error::AppError::Validation(e.to_string()))?;
 
-    let response = state.auth_service.login(req).await?;
+    let username = req.username.clone();
+    let ip = device.ip;
+    state.login_guard.check(&username, ip).await?;
+
+    match state.auth_service.login(req, &device).await {
+        Ok(response) => {
+            state.login_guard.record_success(&username).await;
+            Ok(Json(response))
+        }
+        Err(AppError::InvalidCredentials) => {
+            let delay = state.login_guard.record_failure(&username, ip).await;
+            tokio::time::sleep(delay).await;
+            Err(AppError::InvalidCredentials)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RefreshRequest {
+    refresh_token: String,
+}
+
+/// POST /api/v1/auth/refresh
+/// Trade a refresh token for a new access token and refresh token. The presented
+/// refresh token stops working.
+async fn refresh(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<RefreshRequest>,
+) -> Result<Json<AuthResponse>> {
+    let response = state.auth_service.refresh(&req.refresh_token).await?;
     Ok(Json(response))
 }
 
+/// POST /api/v1/auth/logout
+/// End the current session
+async fn logout(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+) -> Result<Json<()>> {
+    let sid = claims
+        .sid
+        .ok_or_else(|| AppError::BadRequest("Not a login session".to_string()))?;
+    sessions::revoke(&state.db, claims.sub, sid).await?;
+    Ok(Json(()))
+}
+
+/// POST /api/v1/auth/logout-all
+/// End every session of the current user, on all devices
+async fn logout_all(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+) -> Result<Json<serde_json::Value>> {
+    if claims.api_key_id.is_some() {
+        return Err(AppError::Forbidden);
+    }
+    let revoked = sessions::revoke_all(&state.db, claims.sub).await?;
+    Ok(Json(serde_json::json!({ "revoked": revoked })))
+}
+
 async fn me(
     State(state): State<Arc<AppState>>,
     crate::api::middleware::RequireAuth(claims): crate::api::middleware::RequireAuth,
@@ -45,3 +117,178 @@ async fn me(
     let user = state.auth_service.get_user_by_id(claims.sub).await?;
     Ok(Json(user.into()))
 }
+
+/// GET /api/v1/auth/me/preferences
+/// The current user's preferences
+async fn get_preferences(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+) -> Result<Json<UserPreferences>> {
+    let user = state.auth_service.get_user_by_id(claims.sub).await?;
+    Ok(Json(user.preferences))
+}
+
+/// PUT /api/v1/auth/me/preferences
+/// Replace the current user's preferences (omitted fields reset to defaults)
+async fn update_preferences(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Json(preferences): Json<UserPreferences>,
+) -> Result<Json<UserPreferences>> {
+    if let Some(bitrate) = preferences.stream_bitrate {
+        if !STREAM_BITRATES.contains(&bitrate) {
+            return Err(AppError::Validation(format!(
+                "Unsupported stream bitrate {} (expected one of {:?})",
+                bitrate, STREAM_BITRATES
+            )));
+        }
+    }
+
+    if let Some(station_id) = preferences.default_station_id {
+        let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM stations WHERE id = $1)")
+            .bind(station_id)
+            .fetch_one(&state.db)
+            .await?;
+        if !exists {
+            return Err(AppError::NotFound("Station not found".to_string()));
+        }
+    }
+
+    let user = state
+        .auth_service
+        .update_preferences(claims.sub, &preferences)
+        .await?;
+    Ok(Json(user.preferences))
+}
+
+fn oidc_client(state: &AppState) -> Result<&Arc<OidcClient>> {
+    state
+        .oidc
+        .as_ref()
+        .ok_or_else(|| AppError::NotConfigured("OpenID Connect login is not configured".to_string()))
+}
+
+#[derive(Debug, Deserialize)]
+struct OidcLoginQuery {
+    /// Path in the app to return to after signing in
+    redirect: Option<String>,
+}
+
+/// GET /api/v1/auth/oidc/login
+/// Send the browser to the identity provider's login page
+async fn oidc_login(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<OidcLoginQuery>,
+) -> Result<Redirect> {
+    let url = oidc_client(&state)?.authorization_url(query.redirect).await?;
+    Ok(Redirect::to(&url))
+}
+
+#[derive(Debug, Deserialize)]
+struct OidcCallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    /// Set instead of `code` when the provider refused the login
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+/// GET /api/v1/auth/oidc/callback
+/// Where the identity provider returns to. Hands the token (or the error) to the login
+/// page in the URL fragment, which never reaches server logs.
+async fn oidc_callback(
+    State(state): State<Arc<AppState>>,
+    device: DeviceInfo,
+    Query(query): Query<OidcCallbackQuery>,
+) -> Redirect {
+    match complete_oidc_login(&state, &device, query).await {
+        Ok((response, return_to)) => {
+            let mut fragment = format!(
+                "token={}&refresh_token={}",
+                encode_component(&response.token),
+                encode_component(&response.refresh_token)
+            );
+            if let Some(path) = return_to {
+                fragment.push_str(&format!("&redirect={}", encode_component(&path)));
+            }
+            Redirect::to(&format!("/login#{}", fragment))
+        }
+        Err(message) => Redirect::to(&format!("/login#error={}", encode_component(&message))),
+    }
+}
+
+async fn complete_oidc_login(
+    state: &AppState,
+    device: &DeviceInfo,
+    query: OidcCallbackQuery,
+) -> std::result::Result<(AuthResponse, Option<String>), String> {
+    if let Some(error) = query.error {
+        return Err(query.error_description.unwrap_or(error));
+    }
+    let (Some(code), Some(login_state)) = (query.code, query.state) else {
+        return Err("The identity provider sent an incomplete response".to_string());
+    };
+    let oidc = oidc_client(state).map_err(|e| e.to_string())?;
+
+    let outcome = async {
+        let (identity, return_to) = oidc.complete(&code, &login_state).await?;
+        let response = state.auth_service.login_oidc(&identity, device).await?;
+        oidc.save_identity(response.user.id, &identity).await?;
+        Ok::<_, AppError>((response, return_to))
+    }
+    .await;
+
+    outcome.map_err(|e| {
+        tracing::warn!("OpenID Connect login failed: {}", e);
+        match e {
+            AppError::Unauthorized => "The login expired or was not accepted; please try again".to_string(),
+            AppError::AuthenticationFailed => "This account is disabled".to_string(),
+            _ => "Signing in with the identity provider failed".to_string(),
+        }
+    })
+}
+
+/// POST /api/v1/auth/oidc/refresh
+/// Renew the session of a user who signed in through the identity provider. Their
+/// role is brought in line with the provider's claims, and the old token stops working.
+async fn oidc_refresh(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    device: DeviceInfo,
+) -> Result<Json<AuthResponse>> {
+    let oidc = oidc_client(&state)?;
+    if claims.api_key_id.is_some() {
+        return Err(AppError::Forbidden);
+    }
+
+    let (subject, refresh_token) = oidc
+        .stored_identity(claims.sub)
+        .await?
+        .ok_or_else(|| AppError::NotFound("No identity provider session to refresh".to_string()))?;
+    let identity = oidc.refresh(&subject, &refresh_token).await?;
+
+    let response = state.auth_service.login_oidc(&identity, &device).await?;
+    if response.user.id != claims.sub {
+        return Err(AppError::Unauthorized);
+    }
+    oidc.save_identity(claims.sub, &identity).await?;
+    if let Some(sid) = claims.sid {
+        sessions::revoke(&state.db, claims.sub, sid).await?;
+    }
+
+    Ok(Json(response))
+}
+
+/// Percent-encode a value for a URL fragment
+fn encode_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-_.~/".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect()
+}
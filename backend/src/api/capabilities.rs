@@ -0,0 +1,143 @@
+//! Feature discovery: which optional subsystems this deployment runs
+//!
+//! AI curation, the audio encoder, hybrid curation and broadcasting all depend on
+//! runtime configuration (API keys, model files, `SERVER_ROLE`). Clients read
+//! `GET /api/v1/capabilities` once to decide which parts of the UI to show.
+
+use crate::api::stations::AppState;
+use crate::models::station::{MAX_CROSSFADE_MS, MAX_TRACK_DURATION_SECS, STATION_SAMPLE_RATES};
+use crate::models::{AudioCodec, STREAM_BITRATES};
+use crate::services::{ai_curator, audio_broadcaster, curation};
+use axum::{extract::State, routing::get, Json, Router};
+use serde::Serialize;
+use std::sync::Arc;
+
+#[derive(Debug, Serialize)]
+struct Capabilities {
+    /// Server version
+    version: &'static str,
+    /// `all`, `api` or `streamer`
+    role: &'static str,
+    ai: AiCapability,
+    audio_encoder: AudioEncoderCapability,
+    hybrid_curation: HybridCurationCapability,
+    broadcasting: BroadcastingCapability,
+    daily_mix: bool,
+    /// Streams and station info are available without logging in
+    anonymous_listening: bool,
+    oidc: OidcCapability,
+    limits: Limits,
+}
+
+#[derive(Debug, Serialize)]
+struct AiCapability {
+    enabled: bool,
+    /// Model used for curation, seed selection and track analysis
+    curation_model: Option<&'static str>,
+    /// Model used to turn station descriptions into search terms
+    description_model: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+struct AudioEncoderCapability {
+    enabled: bool,
+    model_version: Option<String>,
+    /// Sample rate audio is analyzed at
+    sample_rate: Option<u32>,
+    /// Length of the clip each embedding is computed from
+    clip_secs: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+struct HybridCurationCapability {
+    enabled: bool,
+    seed_count: Option<usize>,
+    playlist_size: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct BroadcastingCapability {
+    /// Whether this node serves streams itself (API nodes redirect to streamers)
+    local: bool,
+    /// Codecs a station can be broadcast with
+    codecs: &'static [AudioCodec],
+    segment_duration_secs: f32,
+    /// Recent segments survive a restart
+    segment_spill: bool,
+    /// Stations can record their broadcasts (`archive`)
+    archives: bool,
+    audio_cache: bool,
+    /// Stations can have tracks announced (`announce_every`)
+    announcer: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct OidcCapability {
+    /// Users can sign in through an OpenID Connect provider (`/auth/oidc/login`)
+    enabled: bool,
+    /// Shown on the login button
+    provider_name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Limits {
+    stream_bitrates: &'static [u32],
+    station_sample_rates: &'static [u32],
+    max_crossfade_ms: u32,
+    max_track_duration_secs: u32,
+}
+
+pub fn capabilities_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/capabilities", get(get_capabilities))
+}
+
+/// GET /api/v1/capabilities
+/// Available subsystems, model versions and limits (public)
+async fn get_capabilities(State(state): State<Arc<AppState>>) -> Json<Capabilities> {
+    let ai_enabled = state.curation_engine.has_ai_capabilities();
+    let encoder = state.audio_encoder.as_ref().map(|encoder| encoder.config());
+    let hybrid = state.hybrid_curator.as_ref().map(|curator| curator.config());
+    let role = state.cluster.role();
+
+    Json(Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        role: role.as_str(),
+        ai: AiCapability {
+            enabled: ai_enabled,
+            curation_model: ai_enabled.then_some(ai_curator::CLAUDE_MODEL),
+            description_model: ai_enabled.then_some(curation::DESCRIPTION_MODEL),
+        },
+        audio_encoder: AudioEncoderCapability {
+            enabled: encoder.is_some(),
+            model_version: encoder.map(|config| config.model_version.clone()),
+            sample_rate: encoder.map(|config| config.sample_rate),
+            clip_secs: encoder.map(|config| config.duration_secs),
+        },
+        hybrid_curation: HybridCurationCapability {
+            enabled: hybrid.is_some(),
+            seed_count: hybrid.map(|config| config.seed_count),
+            playlist_size: hybrid.map(|config| config.playlist_size),
+        },
+        broadcasting: BroadcastingCapability {
+            local: role.streams(),
+            codecs: AudioCodec::ALL,
+            segment_duration_secs: audio_broadcaster::HLS_SEGMENT_DURATION,
+            segment_spill: state.hls_spill_dir.is_some(),
+            archives: state.archive.is_some(),
+            audio_cache: state.audio_cache.is_some(),
+            announcer: state.announcer.is_some(),
+        },
+        daily_mix: state.daily_mix.is_some(),
+        anonymous_listening: state.allow_anonymous_listening,
+        oidc: OidcCapability {
+            enabled: state.oidc.is_some(),
+            provider_name: state.oidc.as_ref().map(|oidc| oidc.provider_name().to_string()),
+        },
+        limits: Limits {
+            stream_bitrates: STREAM_BITRATES,
+            station_sample_rates: STATION_SAMPLE_RATES,
+            max_crossfade_ms: MAX_CROSSFADE_MS,
+            max_track_duration_secs: MAX_TRACK_DURATION_SECS,
+        },
+    })
+}
@@ -0,0 +1,234 @@
+//! Liveness and readiness probes with per-dependency status
+//!
+//! Both endpoints check Postgres, Redis, Navidrome and the audio encoder model, and the
+//! broadcasts' watchdog on streaming nodes, and report each of them. `GET /healthz` answers 200 as long as the process serves
+//! requests, so a Navidrome outage shows up in its body without getting the container
+//! restarted. `GET /readyz` answers 503 while Postgres or Redis is unreachable, which
+//! the API can't work without; Navidrome, the model and stalled or silent broadcasts
+//! only make it `degraded`.
+
+use crate::api::stations::AppState;
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Longest a single dependency check may take
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum CheckStatus {
+    Ok,
+    Error,
+    /// Not configured on this deployment
+    Disabled,
+}
+
+#[derive(Debug, Serialize)]
+struct DependencyCheck {
+    status: CheckStatus,
+    /// Readiness fails while this dependency does
+    critical: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct Checks {
+    postgres: DependencyCheck,
+    redis: DependencyCheck,
+    navidrome: DependencyCheck,
+    audio_encoder: DependencyCheck,
+    broadcasts: DependencyCheck,
+}
+
+impl Checks {
+    fn all(&self) -> [&DependencyCheck; 5] {
+        [&self.postgres, &self.redis, &self.navidrome, &self.audio_encoder, &self.broadcasts]
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct HealthReport {
+    /// `ok`, `degraded` (an optional dependency failed) or `unavailable` (a critical
+    /// one did)
+    status: &'static str,
+    version: &'static str,
+    role: &'static str,
+    checks: Checks,
+}
+
+pub fn health_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+}
+
+/// GET /healthz
+/// Liveness: always 200 while the server responds, with the state of its dependencies
+async fn healthz(State(state): State<Arc<AppState>>) -> (StatusCode, Json<HealthReport>) {
+    (StatusCode::OK, Json(report(&state).await))
+}
+
+/// GET /readyz
+/// Readiness: 503 while a critical dependency (Postgres, Redis) is unreachable
+async fn readyz(State(state): State<Arc<AppState>>) -> (StatusCode, Json<HealthReport>) {
+    let report = report(&state).await;
+    let code = if report.status == "unavailable" {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+    (code, Json(report))
+}
+
+async fn report(state: &AppState) -> HealthReport {
+    let (postgres, redis, navidrome) = tokio::join!(
+        probe(true, async {
+            sqlx::query("SELECT 1")
+                .execute(&state.db)
+                .await
+                .map(|_| None)
+                .map_err(|e| e.to_string())
+        }),
+        probe(true, async {
+            let mut redis = state.redis.clone();
+            redis::cmd("PING")
+                .query_async::<_, String>(&mut redis)
+                .await
+                .map(|_| None)
+                .map_err(|e| e.to_string())
+        }),
+        probe(false, async {
+            let status = state.navidrome_client.ping().await;
+            if !status.reachable {
+                return Err(status.last_error.unwrap_or_else(|| "Unreachable".to_string()));
+            }
+            let detail = serde_json::json!({
+                "server_type": status.server_type,
+                "server_version": status.server_version,
+                "consecutive_failures": status.consecutive_failures,
+            });
+            Ok(Some(detail))
+        }),
+    );
+    let checks = Checks {
+        postgres,
+        redis,
+        navidrome,
+        audio_encoder: audio_encoder_check(state),
+        broadcasts: broadcasts_check(state).await,
+    };
+
+    let failed = |critical: bool| {
+        checks
+            .all()
+            .iter()
+            .any(|check| check.critical == critical && check.status == CheckStatus::Error)
+    };
+    let status = if failed(true) {
+        "unavailable"
+    } else if failed(false) {
+        "degraded"
+    } else {
+        "ok"
+    };
+
+    HealthReport {
+        status,
+        version: env!("CARGO_PKG_VERSION"),
+        role: state.cluster.role().as_str(),
+        checks,
+    }
+}
+
+/// Time a check, giving up after `PROBE_TIMEOUT`
+async fn probe(
+    critical: bool,
+    check: impl Future<Output = Result<Option<serde_json::Value>, String>>,
+) -> DependencyCheck {
+    let start = Instant::now();
+    let outcome = tokio::time::timeout(PROBE_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err(format!("No response within {}s", PROBE_TIMEOUT.as_secs())));
+    let latency_ms = Some(start.elapsed().as_millis() as u64);
+    match outcome {
+        Ok(detail) => DependencyCheck {
+            status: CheckStatus::Ok,
+            critical,
+            latency_ms,
+            error: None,
+            detail,
+        },
+        Err(error) => DependencyCheck {
+            status: CheckStatus::Error,
+            critical,
+            latency_ms,
+            error: Some(error),
+            detail: None,
+        },
+    }
+}
+
+/// The model is loaded once at startup; check that it was, and that its file is still there
+fn audio_encoder_check(state: &AppState) -> DependencyCheck {
+    let Some(encoder) = &state.audio_encoder else {
+        return DependencyCheck {
+            status: CheckStatus::Disabled,
+            critical: false,
+            latency_ms: None,
+            error: None,
+            detail: None,
+        };
+    };
+
+    let model_path = &encoder.config().model_path;
+    let detail = serde_json::json!({
+        "model_version": encoder.model_version(),
+        "execution_provider": encoder.execution_provider().active,
+    });
+    let (status, error) = if model_path.exists() {
+        (CheckStatus::Ok, None)
+    } else {
+        (CheckStatus::Error, Some(format!("Model file {:?} is missing", model_path)))
+    };
+    DependencyCheck {
+        status,
+        critical: false,
+        latency_ms: None,
+        error,
+        detail: Some(detail),
+    }
+}
+
+/// Broadcasts the watchdog currently finds stalled or silent, on nodes that stream
+async fn broadcasts_check(state: &AppState) -> DependencyCheck {
+    if !state.cluster.role().streams() {
+        return DependencyCheck {
+            status: CheckStatus::Disabled,
+            critical: false,
+            latency_ms: None,
+            error: None,
+            detail: None,
+        };
+    }
+
+    let (status, error) = if state.station_manager.has_unhealthy_streams().await {
+        (CheckStatus::Error, Some("A broadcast is stalled or silent".to_string()))
+    } else {
+        (CheckStatus::Ok, None)
+    };
+    DependencyCheck {
+        status,
+        critical: false,
+        latency_ms: None,
+        error,
+        detail: Some(state.station_manager.watchdog_report().await),
+    }
+}
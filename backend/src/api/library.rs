@@ -1,8 +1,20 @@
-use crate::api::middleware::RequireAdmin;
-use crate::api::stations::{AppState, EmbeddingControlState};
+use crate::api::middleware::{OptionalAuth, RequireAdmin, RequireAuth, RequireCurator};
+use crate::api::stations::AppState;
 use crate::error::{AppError, Result};
-use crate::models::{EmbeddingProgress, LibraryStats, LibrarySyncStatus, SyncProgress};
+use crate::models::{
+    AiUsage, EmbeddingProgress, JobKind, JobUpdate, LibraryStats, LibrarySyncStatus, SyncProgress, UserRole,
+};
+use crate::services::audio_cache;
+use crate::services::audio_encoder::{self, EmbeddingStatus};
+use crate::services::duplicates::{self, DuplicateReport};
+use crate::services::execution_provider::ExecutionProviderStatus;
+use crate::services::embedding_worker::EmbeddingControlState;
+use crate::services::energy_arc::EnergyArc;
 use crate::services::hybrid_curator::HybridCurationProgress;
+use crate::services::jobs::JobContext;
+use crate::services::library_scheduler::SchedulerStatus;
+use crate::services::ratings::{self, RatingBias, Thumb};
+use crate::services::vector_index::{self, VectorIndexInfo, VectorIndexParams};
 use axum::{
     extract::{Path, Query, State},
     http::HeaderMap,
@@ -14,7 +26,6 @@ use futures::stream::Stream;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::convert::Infallible;
-use std::time::Instant;
 use tokio::sync::{broadcast, mpsc};
 
 #[derive(Debug, Deserialize)]
@@ -32,6 +43,8 @@ struct AnalyzeTracksResponse {
 struct CurateTracksRequest {
     query: String,
     limit: Option<usize>,
+    /// false leaves out tracks marked explicit, for stations that don't allow them
+    allow_explicit: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
@@ -60,6 +73,19 @@ struct RateTrackResponse {
     message: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct ThumbTrackRequest {
+    thumb: Thumb,
+    /// The station the listener is tuned in to, if any
+    station_id: Option<uuid::Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+struct ThumbTrackResponse {
+    track_id: String,
+    thumb: Thumb,
+}
+
 #[derive(Debug, Deserialize)]
 struct GetTracksByIdsRequest {
     ids: Vec<String>,
@@ -78,11 +104,25 @@ struct GetTracksByIdsResponse {
     tracks: Vec<TrackDetails>,
 }
 
+#[derive(Debug, Deserialize)]
+struct SearchLibraryQuery {
+    q: String,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+struct SearchResult {
+    id: String,
+    title: String,
+    artist: String,
+    album: String,
+    duration: i32,
+}
+
 #[derive(Debug, Serialize)]
 struct EmbeddingStatusResponse {
-    total_tracks: i64,
-    tracks_with_embeddings: i64,
-    coverage_percent: f64,
+    #[serde(flatten)]
+    status: EmbeddingStatus,
     indexing_in_progress: bool,
     control_state: String,
 }
@@ -97,12 +137,17 @@ struct IndexEmbeddingsRequest {
 struct IndexEmbeddingsResponse {
     message: String,
     status: String,
+    job_id: uuid::Uuid,
 }
 
 #[derive(Debug, Deserialize)]
 struct HybridCurateRequest {
     query: String,
     limit: Option<usize>,
+    /// false leaves out tracks marked explicit
+    allow_explicit: Option<bool>,
+    /// Intensity curve, e.g. "peak" or [0.2, 0.9, 0.4] (hybrid curation only)
+    energy_arc: Option<EnergyArc>,
 }
 
 // === Two-phase curation types ===
@@ -147,6 +192,8 @@ struct FillGapsRequest {
     query: String,
     seed_ids: Vec<String>,
     total_size: Option<usize>,
+    /// false fills the gaps without tracks marked explicit
+    allow_explicit: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
@@ -157,6 +204,25 @@ struct FillGapsResponse {
     filled_count: usize,
 }
 
+/// Longest journey `POST /ai/journey` builds
+const MAX_JOURNEY_LENGTH: usize = 100;
+
+#[derive(Debug, Deserialize)]
+struct JourneyRequest {
+    start_track_id: String,
+    end_track_id: String,
+    /// Tracks including both ends (default 20)
+    length: Option<usize>,
+    /// How much farther than the nearest track a pick may be from the path (default 0.1)
+    drift_tolerance: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+struct JourneyResponse {
+    track_ids: Vec<String>,
+    tracks: Vec<TrackInfo>,
+}
+
 #[derive(Debug, Serialize)]
 struct HybridCurateResponse {
     track_ids: Vec<String>,
@@ -168,36 +234,71 @@ struct HybridCurateResponse {
 pub fn library_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/library/sync", post(trigger_full_sync))
+        .route("/library/scan-complete", post(scan_complete))
         .route("/library/sync-stream", get(sync_stream))
         .route("/library/analyze", post(trigger_ai_analysis))
+        .route("/library/loudness", post(trigger_loudness_analysis))
+        .route("/library/artist-metadata", post(fetch_artist_metadata))
+        .route("/library/track-metadata", post(fetch_track_metadata))
+        .route("/library/duplicates", get(get_duplicates).post(scan_duplicates))
         .route("/library/stats", get(get_library_stats))
         .route("/library/sync-status", get(get_sync_status))
+        .route("/library/scheduler", get(get_scheduler_status))
         .route("/library/curate", post(curate_tracks))
         .route("/library/tracks", post(get_tracks_by_ids))
+        .route("/library/search", get(search_library))
         .route("/tracks/:id/rate", post(rate_track))
-        .route("/tracks/:id/rating", get(get_track_rating))
+        .route("/library/tracks/:id/rate", post(thumb_track).delete(delete_track_thumb))
+        .route("/tracks/:id/rating", get(get_track_rating).delete(delete_track_rating))
         // Embedding/ML-powered curation endpoints
         .route("/embeddings/status", get(get_embedding_status))
+        .route("/embeddings/runtime", get(get_embedding_runtime))
         .route("/embeddings/index", post(index_embeddings))
         .route("/embeddings/index-stream", get(index_embeddings_stream))
+        .route("/library/embeddings/stream", get(embedding_backfill_stream))
+        .route("/library/embeddings/migrate", post(migrate_embeddings))
         .route("/embeddings/pause", post(pause_embeddings))
         .route("/embeddings/resume", post(resume_embeddings))
         .route("/embeddings/stop", post(stop_embeddings))
+        .route("/embeddings/vector-index", get(get_vector_index).post(rebuild_vector_index))
         .route("/embeddings/visualization", get(get_embeddings_for_visualization))
+        .route("/embeddings/visualization/progress", get(visualization_progress))
         .route("/ai/hybrid-curate", post(hybrid_curate))
         .route("/ai/hybrid-curate-stream", get(hybrid_curate_stream))
         // Two-phase curation endpoints (for seed review UI)
         .route("/ai/select-seeds", post(select_seeds))
         .route("/ai/regenerate-seed", post(regenerate_seed))
         .route("/ai/fill-gaps", post(fill_gaps))
+        .route("/ai/journey", post(journey))
+        .route("/ai/usage", get(ai_usage))
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SyncMode {
+    #[default]
+    Full,
+    /// Only apply tracks added, changed or removed since the last sync
+    Incremental,
+}
+
+#[derive(Debug, Deserialize)]
+struct SyncQuery {
+    #[serde(default)]
+    mode: SyncMode,
 }
 
 /// POST /api/v1/library/sync
-/// Trigger a full library sync from Navidrome
+/// Trigger a library sync from Navidrome (`?mode=incremental` for changes only)
 async fn trigger_full_sync(
     State(state): State<Arc<AppState>>,
-    RequireAdmin(_): RequireAdmin,
+    admin: RequireAdmin,
+    Query(params): Query<SyncQuery>,
 ) -> Result<Json<serde_json::Value>> {
+    if matches!(params.mode, SyncMode::Incremental) {
+        return scan_complete(State(state), admin).await;
+    }
+
     // Check if sync is already in progress
     let status = state.library_indexer.get_sync_status().await?;
 
@@ -207,17 +308,40 @@ async fn trigger_full_sync(
         ));
     }
 
-    // Spawn sync task in background
+    // Run sync as a background job
     let indexer = Arc::clone(&state.library_indexer);
-    tokio::spawn(async move {
-        if let Err(e) = indexer.sync_full(None).await {
-            tracing::error!("Background sync failed: {}", e);
-        }
-    });
+    let job_id = state
+        .jobs
+        .submit(JobKind::LibrarySync, serde_json::json!({}), 1, move |ctx| {
+            let indexer = indexer.clone();
+            async move {
+                let (tx, _rx) = broadcast::channel::<SyncProgress>(100);
+                record_sync_progress(ctx, &tx);
+                indexer.sync_full(Some(tx)).await?;
+                Ok(serde_json::json!({}))
+            }
+        })
+        .await?;
 
     Ok(Json(serde_json::json!({
         "message": "Full library sync started",
-        "status": "in_progress"
+        "status": "in_progress",
+        "job_id": job_id
+    })))
+}
+
+/// POST /api/v1/library/scan-complete
+/// Sync the changes of a Navidrome scan (for scan hooks, instead of waiting for the poll)
+async fn scan_complete(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+) -> Result<Json<serde_json::Value>> {
+    let job_id = state.library_watcher.scan_completed().await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Incremental library sync started",
+        "status": "in_progress",
+        "job_id": job_id
     })))
 }
 
@@ -230,13 +354,8 @@ async fn trigger_ai_analysis(
 ) -> Result<Json<AnalyzeTracksResponse>> {
     let limit = req.limit.unwrap_or(100);
 
-    // Spawn analysis task in background
-    let indexer = Arc::clone(&state.library_indexer);
-    tokio::spawn(async move {
-        if let Err(e) = indexer.analyze_unanalyzed_tracks(limit).await {
-            tracing::error!("Background analysis failed: {}", e);
-        }
-    });
+    // Run analysis as a background job (retried once on failure)
+    state.library_indexer.submit_ai_analysis(limit).await?;
 
     Ok(Json(AnalyzeTracksResponse {
         tracks_analyzed: 0,
@@ -244,6 +363,130 @@ async fn trigger_ai_analysis(
     }))
 }
 
+/// POST /api/v1/library/loudness
+/// Measure the loudness of tracks that haven't been analyzed, for normalization
+async fn trigger_loudness_analysis(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+    Json(req): Json<AnalyzeTracksRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let limit = req.limit.unwrap_or(1000);
+
+    let analyzer = Arc::clone(&state.loudness_analyzer);
+    let job_id = state
+        .jobs
+        .submit(JobKind::LoudnessAnalysis, serde_json::json!({ "limit": limit }), 1, move |ctx| {
+            let analyzer = analyzer.clone();
+            async move {
+                let summary = analyzer.analyze_pending(&ctx, limit as i64).await?;
+                Ok(serde_json::to_value(summary)?)
+            }
+        })
+        .await?;
+
+    Ok(Json(serde_json::json!({
+        "message": format!("Loudness analysis started for up to {} tracks", limit),
+        "status": "in_progress",
+        "job_id": job_id
+    })))
+}
+
+/// POST /api/v1/library/artist-metadata
+/// Fetch Last.fm tags and similar artists of library artists, used by curation fallbacks
+async fn fetch_artist_metadata(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+    Json(req): Json<AnalyzeTracksRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let lastfm = state
+        .lastfm
+        .clone()
+        .ok_or_else(|| AppError::NotConfigured("LASTFM_API_KEY is not set".to_string()))?;
+    let limit = req.limit.unwrap_or(500);
+
+    let job_id = state
+        .jobs
+        .submit(JobKind::ArtistMetadata, serde_json::json!({ "limit": limit }), 1, move |ctx| {
+            let lastfm = lastfm.clone();
+            async move {
+                let summary = lastfm.fetch_artist_metadata(&ctx, limit as i64).await?;
+                Ok(serde_json::to_value(summary)?)
+            }
+        })
+        .await?;
+
+    Ok(Json(serde_json::json!({
+        "message": format!("Fetching Last.fm metadata for up to {} artists", limit),
+        "status": "in_progress",
+        "job_id": job_id
+    })))
+}
+
+/// POST /api/v1/library/track-metadata
+/// Look up library tracks on MusicBrainz and AcousticBrainz
+async fn fetch_track_metadata(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+    Json(req): Json<AnalyzeTracksRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let musicbrainz = state
+        .musicbrainz
+        .clone()
+        .ok_or_else(|| AppError::NotConfigured("MusicBrainz lookups are disabled (MUSICBRAINZ_ENABLED)".to_string()))?;
+    let limit = req.limit.unwrap_or(500);
+
+    let job_id = state
+        .jobs
+        .submit(JobKind::TrackMetadata, serde_json::json!({ "limit": limit }), 1, move |ctx| {
+            let musicbrainz = musicbrainz.clone();
+            async move {
+                let summary = musicbrainz.enrich_tracks(&ctx, limit as i64).await?;
+                Ok(serde_json::to_value(summary)?)
+            }
+        })
+        .await?;
+
+    Ok(Json(serde_json::json!({
+        "message": format!("Fetching MusicBrainz metadata for up to {} tracks", limit),
+        "status": "in_progress",
+        "job_id": job_id
+    })))
+}
+
+/// GET /api/v1/library/duplicates
+/// Groups of tracks found to be versions of the same song by the last scan
+async fn get_duplicates(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+) -> Result<Json<DuplicateReport>> {
+    Ok(Json(duplicates::report(&state.db).await?))
+}
+
+/// POST /api/v1/library/duplicates
+/// Find duplicate tracks by title, artist and audio embedding
+async fn scan_duplicates(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+) -> Result<Json<serde_json::Value>> {
+    let db = state.db.clone();
+    let job_id = state
+        .jobs
+        .submit(JobKind::DuplicateScan, serde_json::json!({}), 1, move |ctx| {
+            let db = db.clone();
+            async move {
+                let summary = duplicates::scan(&db, &ctx).await?;
+                Ok(serde_json::to_value(summary)?)
+            }
+        })
+        .await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Scanning the library for duplicate tracks",
+        "status": "in_progress",
+        "job_id": job_id
+    })))
+}
+
 /// GET /api/v1/library/stats
 /// Get current library statistics
 async fn get_library_stats(
@@ -262,17 +505,26 @@ async fn get_sync_status(
     Ok(Json(status))
 }
 
+/// GET /api/v1/library/scheduler
+/// Scheduled library tasks with their last outcome and next run
+async fn get_scheduler_status(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+) -> Json<SchedulerStatus> {
+    Json(state.library_scheduler.status())
+}
+
 /// POST /api/v1/library/curate
 /// AI-powered track curation based on natural language query
 async fn curate_tracks(
     State(state): State<Arc<AppState>>,
-    RequireAdmin(_): RequireAdmin,
+    RequireCurator(claims): RequireCurator,
     Json(req): Json<CurateTracksRequest>,
 ) -> Result<Json<CurateTracksResponse>> {
     let curator = state
         .ai_curator
         .as_ref()
-        .ok_or_else(|| AppError::ExternalApi("AI curator not available - ANTHROPIC_API_KEY not configured".to_string()))?;
+        .ok_or_else(|| AppError::NotConfigured("AI curator not available - ANTHROPIC_API_KEY not configured".to_string()))?;
 
     let limit = req.limit.unwrap_or(20);
 
@@ -280,7 +532,10 @@ async fn curate_tracks(
         return Err(AppError::Validation("Query cannot be empty".to_string()));
     }
 
-    let track_ids = curator.curate_tracks(req.query.clone(), limit).await?;
+    let track_ids = curator
+        .curate_tracks(req.query.clone(), limit, Some(claims.sub), req.allow_explicit.unwrap_or(true))
+        .await?;
+    state.webhooks.curation_completed("llm", &req.query, &track_ids, Some(claims.sub));
 
     // Fetch track details from library_index
     let mut tracks = Vec::new();
@@ -308,21 +563,14 @@ async fn curate_tracks(
 }
 
 /// POST /api/v1/tracks/:id/rate
-/// Rate a track (user rating)
+/// Rate a track (0-5) as the calling user; replaces any earlier rating
 async fn rate_track(
-    State(_state): State<Arc<AppState>>,
-    RequireAdmin(_): RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
     Path(track_id): Path<String>,
     Json(req): Json<RateTrackRequest>,
 ) -> Result<Json<RateTrackResponse>> {
-    if req.rating < 0.0 || req.rating > 5.0 {
-        return Err(AppError::Validation(
-            "Rating must be between 0.0 and 5.0".to_string(),
-        ));
-    }
-
-    // TODO: Implement user rating persistence
-    // For now, just return success
+    ratings::rate(&state.db, claims.sub, &track_id, req.rating).await?;
 
     Ok(Json(RateTrackResponse {
         track_id: track_id.clone(),
@@ -332,22 +580,78 @@ async fn rate_track(
 }
 
 /// GET /api/v1/tracks/:id/rating
-/// Get track rating information
+/// Get track rating information (including the caller's own rating when logged in)
 async fn get_track_rating(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
+    OptionalAuth(claims): OptionalAuth,
     Path(track_id): Path<String>,
 ) -> Result<Json<serde_json::Value>> {
-    // TODO: Implement track rating retrieval
-    // For now, return placeholder
+    let (avg_rating, rating_count): (Option<f64>, i32) =
+        sqlx::query_as("SELECT avg_rating, rating_count FROM library_index WHERE id = $1")
+            .bind(&track_id)
+            .fetch_optional(&state.db)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Track not found".to_string()))?;
+
+    let (user_rating, user_thumb) = match claims {
+        Some(claims) => (
+            ratings::user_rating(&state.db, claims.sub, &track_id).await?,
+            ratings::user_thumb(&state.db, claims.sub, &track_id).await?,
+        ),
+        None => (None, None),
+    };
 
     Ok(Json(serde_json::json!({
         "track_id": track_id,
-        "user_rating": null,
-        "avg_rating": null,
-        "rating_count": 0
+        "user_rating": user_rating,
+        "user_thumb": user_thumb,
+        "avg_rating": avg_rating,
+        "rating_count": rating_count
     })))
 }
 
+/// DELETE /api/v1/tracks/:id/rating
+/// Remove the caller's rating for a track
+async fn delete_track_rating(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path(track_id): Path<String>,
+) -> Result<Json<()>> {
+    if !ratings::remove(&state.db, claims.sub, &track_id).await? {
+        return Err(AppError::NotFound("No rating for this track".to_string()));
+    }
+    Ok(Json(()))
+}
+
+/// POST /api/v1/library/tracks/:id/rate
+/// Thumbs up or down a track as the calling user; replaces any earlier one
+async fn thumb_track(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path(track_id): Path<String>,
+    Json(req): Json<ThumbTrackRequest>,
+) -> Result<Json<ThumbTrackResponse>> {
+    ratings::set_thumb(&state.db, claims.sub, &track_id, req.thumb, req.station_id).await?;
+
+    Ok(Json(ThumbTrackResponse {
+        track_id,
+        thumb: req.thumb,
+    }))
+}
+
+/// DELETE /api/v1/library/tracks/:id/rate
+/// Remove the caller's thumbs up/down for a track
+async fn delete_track_thumb(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path(track_id): Path<String>,
+) -> Result<Json<()>> {
+    if !ratings::remove_thumb(&state.db, claims.sub, &track_id).await? {
+        return Err(AppError::NotFound("No thumbs up or down for this track".to_string()));
+    }
+    Ok(Json(()))
+}
+
 /// GET /api/v1/library/sync-stream
 /// Stream library sync progress via Server-Sent Events
 async fn sync_stream(
@@ -371,20 +675,34 @@ async fn sync_stream(
             message: "Sync already in progress".to_string(),
         });
     } else {
-        // Spawn sync task with progress reporting
+        // Run sync as a background job with progress reporting
         let tx_clone = tx.clone();
         let indexer = Arc::clone(&state.library_indexer);
-        tokio::spawn(async move {
-            // Send started event
-            let _ = tx_clone.send(SyncProgress::Started {
-                message: "Starting library sync".to_string(),
-            });
+        let submitted = state
+            .jobs
+            .submit(JobKind::LibrarySync, serde_json::json!({}), 1, move |ctx| {
+                let tx = tx_clone.clone();
+                let indexer = indexer.clone();
+                async move {
+                    record_sync_progress(ctx, &tx);
+
+                    // Send started event
+                    let _ = tx.send(SyncProgress::Started {
+                        message: "Starting library sync".to_string(),
+                    });
 
-            // Perform the sync with progress reporting
-            if let Err(e) = indexer.sync_full(Some(tx_clone)).await {
-                tracing::error!("Library sync failed: {}", e);
-            }
-        });
+                    // Perform the sync with progress reporting
+                    indexer.sync_full(Some(tx)).await?;
+                    Ok(serde_json::json!({}))
+                }
+            })
+            .await;
+
+        if let Err(e) = submitted {
+            let _ = tx.send(SyncProgress::Error {
+                message: e.to_string(),
+            });
+        }
     }
 
     // Convert broadcast receiver to SSE stream
@@ -410,6 +728,36 @@ async fn sync_stream(
     Sse::new(stream).keep_alive(axum::response::sse::KeepAlive::default())
 }
 
+/// Mirror library sync progress events into the job record
+fn record_sync_progress(ctx: JobContext, tx: &broadcast::Sender<SyncProgress>) {
+    let mut rx = tx.subscribe();
+    tokio::spawn(async move {
+        let mut fraction = 0.0;
+        loop {
+            let progress = match rx.recv().await {
+                Ok(progress) => progress,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+
+            match progress {
+                SyncProgress::Processing { current, total, message, .. } => {
+                    if total > 0 {
+                        fraction = current as f64 / total as f64;
+                    }
+                    ctx.progress(fraction, message).await;
+                }
+                SyncProgress::Started { message }
+                | SyncProgress::Fetching { message, .. }
+                | SyncProgress::ComputingStats { message } => {
+                    ctx.progress(fraction, message).await;
+                }
+                SyncProgress::Completed { .. } | SyncProgress::Error { .. } => break,
+            }
+        }
+    });
+}
+
 /// POST /api/v1/library/tracks
 /// Get track details by IDs
 async fn get_tracks_by_ids(
@@ -450,32 +798,50 @@ async fn get_tracks_by_ids(
     Ok(Json(GetTracksByIdsResponse { tracks }))
 }
 
-/// GET /api/v1/embeddings/status
-/// Get audio embedding indexing status
-async fn get_embedding_status(
+/// GET /api/v1/library/search?q=...&limit=N
+/// Tracks whose title, artist or album contain every word of the query (for requests)
+async fn search_library(
     State(state): State<Arc<AppState>>,
-) -> Result<Json<EmbeddingStatusResponse>> {
-    // Get total tracks from library_index
-    let total_tracks: i64 = sqlx::query_scalar!(
-        "SELECT COUNT(*) FROM library_index"
-    )
-    .fetch_one(&state.db)
-    .await?
-    .unwrap_or(0);
+    RequireAuth(_): RequireAuth,
+    Query(query): Query<SearchLibraryQuery>,
+) -> Result<Json<Vec<SearchResult>>> {
+    let words: Vec<String> = query
+        .q
+        .split_whitespace()
+        .take(10)
+        .map(|word| format!("%{}%", word.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")))
+        .collect();
+    if words.is_empty() {
+        return Ok(Json(vec![]));
+    }
 
-    // Get tracks with embeddings from track_embeddings
-    let tracks_with_embeddings: i64 = sqlx::query_scalar!(
-        "SELECT COUNT(*) FROM track_embeddings"
+    let results = sqlx::query_as::<_, SearchResult>(
+        r#"
+        SELECT id, title, artist, album, duration
+        FROM library_index
+        WHERE (SELECT bool_and(title || ' ' || artist || ' ' || album ILIKE word) FROM unnest($1::text[]) AS word)
+        ORDER BY artist, album, title
+        LIMIT $2
+        "#,
     )
-    .fetch_one(&state.db)
-    .await?
-    .unwrap_or(0);
+    .bind(&words)
+    .bind(query.limit.unwrap_or(20).clamp(1, 100))
+    .fetch_all(&state.db)
+    .await?;
 
-    let coverage_percent = if total_tracks > 0 {
-        (tracks_with_embeddings as f64 / total_tracks as f64) * 100.0
-    } else {
-        0.0
-    };
+    Ok(Json(results))
+}
+
+/// GET /api/v1/embeddings/status
+/// Get audio embedding indexing status, with failures by type, processing times and an ETA
+async fn get_embedding_status(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<EmbeddingStatusResponse>> {
+    let model_version = state
+        .audio_encoder
+        .as_ref()
+        .map_or(audio_encoder::MODEL_VERSION, |encoder| encoder.model_version());
+    let status = audio_encoder::embedding_status(&state.db, model_version).await?;
 
     // Get current control state
     let control_state = *state.embedding_control.read().await;
@@ -490,14 +856,25 @@ async fn get_embedding_status(
     };
 
     Ok(Json(EmbeddingStatusResponse {
-        total_tracks,
-        tracks_with_embeddings,
-        coverage_percent,
+        status,
         indexing_in_progress,
         control_state: control_state_str.to_string(),
     }))
 }
 
+/// GET /api/v1/embeddings/runtime
+/// The ONNX Runtime execution provider the audio encoder runs on, and why any preferred
+/// ones were skipped
+async fn get_embedding_runtime(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+) -> Result<Json<ExecutionProviderStatus>> {
+    let encoder = state.audio_encoder.as_ref().ok_or_else(|| {
+        AppError::NotConfigured("Audio encoder not available - AUDIO_ENCODER_MODEL_PATH not configured".to_string())
+    })?;
+    Ok(Json(encoder.execution_provider().clone()))
+}
+
 #[derive(Debug, Deserialize)]
 struct EmbeddingVisualizationQuery {
     limit: Option<i64>,
@@ -517,12 +894,13 @@ struct EmbeddingPoint {
 #[derive(Debug, Serialize)]
 struct EmbeddingVisualizationResponse {
     points: Vec<EmbeddingPoint>,
-    cache_rebuilt: bool,
+    /// Set while the cache is being rebuilt; the points are from the previous build
+    rebuild_job_id: Option<uuid::Uuid>,
 }
 
 /// GET /api/v1/embeddings/visualization
 /// Get pre-computed 2D coordinates for embedding visualization
-/// Returns cached PCA projections for fast loading
+/// Returns cached PCA projections for fast loading; a stale cache is rebuilt in the background
 async fn get_embeddings_for_visualization(
     State(state): State<Arc<AppState>>,
     Query(params): Query<EmbeddingVisualizationQuery>,
@@ -530,15 +908,13 @@ async fn get_embeddings_for_visualization(
     // If limit is provided, use it; otherwise return all embeddings
     let limit = params.limit;
 
-    // Check if we need to rebuild the visualization cache
-    let mut cache_rebuilt = false;
+    // Start (or join) a rebuild if the cache is stale, without waiting for it
+    let mut rebuild_job_id = None;
     if let Some(ref encoder) = state.audio_encoder {
         if encoder.is_visualization_cache_stale().await.unwrap_or(true) {
-            tracing::info!("Visualization cache is stale, rebuilding...");
-            if let Err(e) = encoder.rebuild_visualization_cache().await {
-                tracing::error!("Failed to rebuild visualization cache: {}", e);
-            } else {
-                cache_rebuilt = true;
+            match encoder.submit_visualization_rebuild(&state.jobs).await {
+                Ok(job_id) => rebuild_job_id = Some(job_id),
+                Err(e) => tracing::error!("Failed to start visualization rebuild: {}", e),
             }
         }
     }
@@ -614,10 +990,58 @@ async fn get_embeddings_for_visualization(
 
     Ok(Json(EmbeddingVisualizationResponse {
         points,
-        cache_rebuilt,
+        rebuild_job_id,
     }))
 }
 
+/// GET /api/v1/embeddings/visualization/progress
+/// Stream progress of the running visualization rebuild via Server-Sent Events.
+/// Ends when the rebuild finishes (immediately if none is running).
+async fn visualization_progress(
+    State(state): State<Arc<AppState>>,
+) -> Sse<impl Stream<Item = std::result::Result<Event, Infallible>>> {
+    // Subscribe before looking up the job, so no update falls in between
+    let mut rx = state.jobs.subscribe();
+    let active = state
+        .jobs
+        .find_active(JobKind::VisualizationRebuild)
+        .await
+        .ok()
+        .flatten();
+
+    let stream = async_stream::stream! {
+        let Some(job) = active else { return };
+        let snapshot = JobUpdate {
+            job_id: job.id,
+            kind: job.kind,
+            status: job.status,
+            progress: job.progress,
+            message: job.message,
+        };
+        if let Ok(event) = Event::default().json_data(&snapshot) {
+            yield Ok::<Event, Infallible>(event);
+        }
+
+        loop {
+            match rx.recv().await {
+                Ok(update) if update.job_id == job.id => {
+                    let is_terminal = update.status.is_terminal();
+                    if let Ok(event) = Event::default().json_data(&update) {
+                        yield Ok::<Event, Infallible>(event);
+                    }
+                    if is_terminal {
+                        break;
+                    }
+                }
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(axum::response::sse::KeepAlive::default())
+}
+
 /// POST /api/v1/embeddings/index
 /// Start audio embedding indexing for tracks without embeddings
 async fn index_embeddings(
@@ -628,81 +1052,123 @@ async fn index_embeddings(
     let encoder = state
         .audio_encoder
         .as_ref()
-        .ok_or_else(|| AppError::ExternalApi(
+        .ok_or_else(|| AppError::NotConfigured(
             "Audio encoder not available - AUDIO_ENCODER_MODEL_PATH not configured".to_string()
         ))?;
 
-    let library_path = state
-        .navidrome_library_path
-        .as_ref()
-        .ok_or_else(|| AppError::ExternalApi(
-            "Library path not configured - NAVIDROME_LIBRARY_PATH not set".to_string()
-        ))?;
+    if state.navidrome_library_path.is_none() && state.audio_cache.is_none() {
+        return Err(AppError::NotConfigured(
+            "No audio source - set NAVIDROME_LIBRARY_PATH or AUDIO_CACHE_DIR".to_string()
+        ));
+    }
 
     let batch_size = req.batch_size.unwrap_or(10);
     let max_tracks = req.max_tracks.unwrap_or(100);
 
     // Spawn indexing task in background
     let encoder = Arc::clone(encoder);
-    let library_path = library_path.clone();
+    let library_path = state.navidrome_library_path.clone();
+    let audio_cache = state.audio_cache.clone();
+    let navidrome = state.navidrome_client.clone();
     let db = state.db.clone();
 
-    tokio::spawn(async move {
-        tracing::info!("Starting audio embedding indexing (batch_size={}, max_tracks={})", batch_size, max_tracks);
+    let job_id = state
+        .jobs
+        .submit(
+            JobKind::EmbeddingIndex,
+            serde_json::json!({ "batch_size": batch_size, "max_tracks": max_tracks }),
+            1,
+            move |ctx| {
+                let encoder = encoder.clone();
+                let library_path = library_path.clone();
+                let audio_cache = audio_cache.clone();
+                let navidrome = navidrome.clone();
+                let db = db.clone();
+                async move {
+                    tracing::info!("Starting audio embedding indexing (batch_size={}, max_tracks={})", batch_size, max_tracks);
+
+                    // Get tracks without embeddings in random order for diversity testing
+                    let tracks: Vec<(String, String)> = sqlx::query_as(
+                        r#"
+                        SELECT li.id, li.path
+                        FROM library_index li
+                        WHERE li.path IS NOT NULL
+                        AND NOT EXISTS (SELECT 1 FROM track_embeddings te WHERE te.track_id = li.id)
+                        ORDER BY RANDOM()
+                        LIMIT $1
+                        "#
+                    )
+                    .bind(max_tracks as i64)
+                    .fetch_all(&db)
+                    .await?;
 
-        // Get tracks without embeddings in random order for diversity testing
-        let tracks: Vec<(String, String)> = sqlx::query_as(
-            r#"
-            SELECT li.id, li.path
-            FROM library_index li
-            WHERE li.path IS NOT NULL
-            AND NOT EXISTS (SELECT 1 FROM track_embeddings te WHERE te.track_id = li.id)
-            ORDER BY RANDOM()
-            LIMIT $1
-            "#
-        )
-        .bind(max_tracks as i64)
-        .fetch_all(&db)
-        .await
-        .unwrap_or_default();
+                    let total = tracks.len();
+                    tracing::info!("Found {} tracks to index", total);
 
-        tracing::info!("Found {} tracks to index", tracks.len());
+                    let mut success_count = 0;
+                    let mut error_count = 0;
 
-        let mut success_count = 0;
-        let mut error_count = 0;
+                    for (index, (track_id, relative_path)) in tracks.into_iter().enumerate() {
+                        if ctx.is_cancelled() {
+                            tracing::info!("Embedding indexing cancelled");
+                            break;
+                        }
 
-        for (track_id, relative_path) in tracks {
-            let full_path = std::path::Path::new(&library_path).join(&relative_path);
+                        let full_path = match audio_cache::track_file(
+                            library_path.as_deref(),
+                            audio_cache.as_deref(),
+                            &navidrome,
+                            &track_id,
+                            &relative_path,
+                        )
+                        .await
+                        {
+                            Ok(path) => path,
+                            Err(e) => {
+                                tracing::warn!("No audio for track {}: {}", track_id, e);
+                                error_count += 1;
+                                continue;
+                            }
+                        };
 
-            if !full_path.exists() {
-                tracing::warn!("Track file not found: {:?}", full_path);
-                error_count += 1;
-                continue;
-            }
+                        match encoder.process_track(&track_id, &full_path).await {
+                            Ok(_) => {
+                                success_count += 1;
+                                if success_count % 10 == 0 {
+                                    tracing::info!("Indexed {} tracks so far", success_count);
+                                }
+                            }
+                            Err(e) => {
+                                tracing::warn!("Failed to encode track {}: {}", track_id, e);
+                                error_count += 1;
+                            }
+                        }
 
-            match encoder.process_track(&track_id, &full_path).await {
-                Ok(_) => {
-                    success_count += 1;
-                    if success_count % 10 == 0 {
-                        tracing::info!("Indexed {} tracks so far", success_count);
+                        ctx.progress(
+                            (index + 1) as f64 / total as f64,
+                            format!("{} success, {} errors", success_count, error_count),
+                        )
+                        .await;
                     }
-                }
-                Err(e) => {
-                    tracing::warn!("Failed to encode track {}: {}", track_id, e);
-                    error_count += 1;
-                }
-            }
-        }
 
-        tracing::info!(
-            "Embedding indexing complete: {} success, {} errors",
-            success_count, error_count
-        );
-    });
+                    tracing::info!(
+                        "Embedding indexing complete: {} success, {} errors",
+                        success_count, error_count
+                    );
+
+                    Ok(serde_json::json!({
+                        "success_count": success_count,
+                        "error_count": error_count
+                    }))
+                }
+            },
+        )
+        .await?;
 
     Ok(Json(IndexEmbeddingsResponse {
         message: format!("Embedding indexing started (batch_size={}, max_tracks={})", batch_size, max_tracks),
         status: "in_progress".to_string(),
+        job_id,
     }))
 }
 
@@ -719,285 +1185,129 @@ struct HybridCurateStreamQuery {
     token: String,
     query: String,
     limit: Option<usize>,
+    /// false leaves out tracks marked explicit
+    allow_explicit: Option<bool>,
+    /// Intensity curve, e.g. "peak" or "0.2,0.9,0.4"
+    energy_arc: Option<EnergyArc>,
 }
 
 /// GET /api/v1/embeddings/index-stream
-/// Stream audio embedding indexing progress via Server-Sent Events
+/// Start the embedding backfill and stream its progress via Server-Sent Events
 async fn index_embeddings_stream(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Query(params): Query<IndexEmbeddingsStreamQuery>,
 ) -> Sse<impl Stream<Item = std::result::Result<Event, Infallible>>> {
-    // Extract token from Authorization header or query param
-    let token = headers
-        .get("authorization")
-        .and_then(|v| v.to_str().ok())
-        .and_then(|v| v.strip_prefix("Bearer "))
-        .map(|s| s.to_string())
-        .or(params.token);
-
-    // Validate token
-    let auth_service = &state.auth_service;
-    let token_valid = match &token {
-        Some(t) => auth_service.validate_admin_token(t).await.is_ok(),
-        None => false,
+    let started = match sse_admin(&state, &headers, params.token).await {
+        Err(message) => Err(message),
+        Ok(()) => match &state.embedding_worker {
+            None => Err("Audio encoder not configured".to_string()),
+            Some(worker) => {
+                // Subscribe before starting, so the start event isn't missed
+                let (_, rx) = worker.subscribe();
+                match worker.start(&state.jobs).await {
+                    Ok(_) => Ok((None, rx)),
+                    Err(AppError::Conflict(message)) => Err(message),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+        },
     };
 
-    // Create a broadcast channel for progress updates
-    let (tx, _rx) = broadcast::channel::<EmbeddingProgress>(100);
-    let mut rx = tx.subscribe();
-
-    if !token_valid {
-        let _ = tx.send(EmbeddingProgress::Error {
-            message: "Unauthorized".to_string(),
-        });
-    } else {
-        let encoder = state.audio_encoder.clone();
-        let library_path = state.navidrome_library_path.clone();
-        let db = state.db.clone();
-        let embedding_control = state.embedding_control.clone();
+    embedding_progress_sse(started)
+}
 
-        // Check if already running
-        {
-            let control = embedding_control.read().await;
-            if *control == EmbeddingControlState::Running || *control == EmbeddingControlState::Paused {
-                let _ = tx.send(EmbeddingProgress::Error {
-                    message: "Embedding indexing is already running".to_string(),
-                });
-                // Return early via the stream below
-            }
-        }
+/// POST /api/v1/library/embeddings/migrate
+/// Re-encode embeddings of older model versions with the configured model. Similarity
+/// search keeps using the old embeddings until every track has been re-encoded.
+async fn migrate_embeddings(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+) -> Result<Json<IndexEmbeddingsResponse>> {
+    let worker = state.embedding_worker.as_ref().ok_or_else(|| {
+        AppError::NotConfigured("Audio encoder not available - AUDIO_ENCODER_MODEL_PATH not configured".to_string())
+    })?;
+    let job_id = worker.start_migration(&state.jobs).await?;
 
-        if encoder.is_none() || library_path.is_none() {
-            let _ = tx.send(EmbeddingProgress::Error {
-                message: "Audio encoder not configured".to_string(),
-            });
-        } else {
-            let encoder = encoder.unwrap();
-            let library_path = library_path.unwrap();
-            let tx_clone = tx.clone();
-
-            // Set state to Running
-            {
-                let mut control = embedding_control.write().await;
-                *control = EmbeddingControlState::Running;
-            }
+    Ok(Json(IndexEmbeddingsResponse {
+        message: "Embedding migration started".to_string(),
+        status: "in_progress".to_string(),
+        job_id,
+    }))
+}
 
-            tokio::spawn(async move {
-                let start_time = Instant::now();
-
-                // Get ALL tracks without embeddings in random order for diversity testing
-                let tracks: Vec<(String, String, String, String)> = match sqlx::query_as(
-                    r#"
-                    SELECT li.id, li.path, li.title, li.artist
-                    FROM library_index li
-                    WHERE li.path IS NOT NULL
-                    AND NOT EXISTS (SELECT 1 FROM track_embeddings te WHERE te.track_id = li.id)
-                    ORDER BY RANDOM()
-                    "#
-                )
-                .fetch_all(&db)
-                .await {
-                    Ok(t) => t,
-                    Err(e) => {
-                        let _ = tx_clone.send(EmbeddingProgress::Error {
-                            message: format!("Database error: {}", e),
-                        });
-                        // Reset control state
-                        let mut control = embedding_control.write().await;
-                        *control = EmbeddingControlState::Idle;
-                        return;
-                    }
-                };
-
-                let total = tracks.len();
-                if total == 0 {
-                    let _ = tx_clone.send(EmbeddingProgress::Completed {
-                        success_count: 0,
-                        error_count: 0,
-                        total_time_secs: 0.0,
-                        message: "No tracks to index - all tracks already have embeddings".to_string(),
-                    });
-                    // Reset control state
-                    let mut control = embedding_control.write().await;
-                    *control = EmbeddingControlState::Idle;
-                    return;
-                }
+#[derive(Debug, Deserialize)]
+struct EmbeddingStreamQuery {
+    token: Option<String>,
+}
 
-                // Determine parallelism based on available cores
-                let concurrency = std::thread::available_parallelism()
-                    .map(|p| p.get())
-                    .unwrap_or(4)
-                    .min(8); // Cap at 8 for resource management
-
-                let _ = tx_clone.send(EmbeddingProgress::Started {
-                    message: format!("Starting embedding indexing for {} tracks ({} parallel)", total, concurrency),
-                    total_tracks: total,
-                });
-
-                // Shared state for tracking progress
-                use std::sync::atomic::{AtomicUsize, Ordering, AtomicBool};
-                let success_count = Arc::new(AtomicUsize::new(0));
-                let error_count = Arc::new(AtomicUsize::new(0));
-                let completed_count = Arc::new(AtomicUsize::new(0));
-                let in_progress: Arc<tokio::sync::Mutex<Vec<String>>> = Arc::new(tokio::sync::Mutex::new(Vec::new()));
-                let should_stop = Arc::new(AtomicBool::new(false));
-
-                // Use futures stream for parallel processing with pause/stop support
-                use futures::stream::{self, StreamExt};
-
-                // Clone embedding_control for use inside the stream
-                let embedding_control_inner = embedding_control.clone();
-                let should_stop_inner = should_stop.clone();
-
-                let _results: Vec<_> = stream::iter(tracks.into_iter())
-                    .map(|(track_id, relative_path, title, artist)| {
-                        let encoder = encoder.clone();
-                        let library_path = library_path.clone();
-                        let tx = tx_clone.clone();
-                        let success_count = success_count.clone();
-                        let error_count = error_count.clone();
-                        let completed_count = completed_count.clone();
-                        let in_progress = in_progress.clone();
-                        let embedding_control = embedding_control_inner.clone();
-                        let should_stop = should_stop_inner.clone();
-
-                        async move {
-                            // Check for stop signal at the start of each track
-                            if should_stop.load(Ordering::Relaxed) {
-                                return (track_id, Err("Stopped".to_string()));
-                            }
+/// GET /api/v1/library/embeddings/stream
+/// Follow the running embedding backfill via Server-Sent Events, starting with its
+/// latest progress. Ends when the backfill finishes (immediately if none is running).
+async fn embedding_backfill_stream(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<EmbeddingStreamQuery>,
+) -> Sse<impl Stream<Item = std::result::Result<Event, Infallible>>> {
+    let subscribed = match sse_admin(&state, &headers, params.token).await {
+        Err(message) => Err(message),
+        Ok(()) => match &state.embedding_worker {
+            None => Err("Audio encoder not configured".to_string()),
+            Some(worker) => match worker.subscribe() {
+                (Some(latest), rx) => Ok((Some(latest), rx)),
+                (None, _) => Err("Embedding indexing is not running".to_string()),
+            },
+        },
+    };
 
-                            // Check for pause/stop - wait if paused
-                            loop {
-                                let control = embedding_control.read().await;
-                                match *control {
-                                    EmbeddingControlState::Stopping => {
-                                        should_stop.store(true, Ordering::Relaxed);
-                                        return (track_id, Err("Stopped".to_string()));
-                                    }
-                                    EmbeddingControlState::Paused => {
-                                        drop(control); // Release lock before sleeping
-                                        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-                                        continue;
-                                    }
-                                    EmbeddingControlState::Idle => {
-                                        // Something cancelled us
-                                        return (track_id, Err("Cancelled".to_string()));
-                                    }
-                                    EmbeddingControlState::Running => break,
-                                }
-                            }
+    embedding_progress_sse(subscribed)
+}
 
-                            let track_name = format!("{} - {}", artist, title);
-                            let full_path = std::path::Path::new(&library_path).join(&relative_path);
-
-                            // Add to in_progress and send update
-                            {
-                                let mut ip = in_progress.lock().await;
-                                ip.push(track_name.clone());
-                                let _ = tx.send(EmbeddingProgress::Processing {
-                                    completed: completed_count.load(Ordering::Relaxed),
-                                    total,
-                                    success_count: success_count.load(Ordering::Relaxed),
-                                    error_count: error_count.load(Ordering::Relaxed),
-                                    in_progress: ip.clone(),
-                                    message: format!("Processing {} tracks in parallel", ip.len()),
-                                });
-                            }
+/// Check the admin token of an SSE request (EventSource can't set headers, so it may
+/// come as a query parameter)
+async fn sse_admin(
+    state: &AppState,
+    headers: &HeaderMap,
+    query_token: Option<String>,
+) -> std::result::Result<(), String> {
+    let token = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|s| s.to_string())
+        .or(query_token);
 
-                            let result = if !full_path.exists() {
-                                Err("File not found".to_string())
-                            } else {
-                                let track_start = Instant::now();
-                                match encoder.process_track(&track_id, &full_path).await {
-                                    Ok(_) => Ok(track_start.elapsed().as_millis() as u64),
-                                    Err(e) => Err(e.to_string()),
-                                }
-                            };
-
-                            // Remove from in_progress and update counters
-                            {
-                                let mut ip = in_progress.lock().await;
-                                ip.retain(|n| n != &track_name);
-                                completed_count.fetch_add(1, Ordering::Relaxed);
-
-                                match &result {
-                                    Ok(processing_time_ms) => {
-                                        success_count.fetch_add(1, Ordering::Relaxed);
-                                        let _ = tx.send(EmbeddingProgress::TrackComplete {
-                                            track_id: track_id.clone(),
-                                            track_name: track_name.clone(),
-                                            processing_time_ms: *processing_time_ms,
-                                            current: completed_count.load(Ordering::Relaxed),
-                                            total,
-                                        });
-                                    }
-                                    Err(error) => {
-                                        error_count.fetch_add(1, Ordering::Relaxed);
-                                        let _ = tx.send(EmbeddingProgress::TrackError {
-                                            track_id: track_id.clone(),
-                                            track_name: track_name.clone(),
-                                            error: error.clone(),
-                                            current: completed_count.load(Ordering::Relaxed),
-                                            total,
-                                        });
-                                    }
-                                }
+    match &token {
+        Some(t) if state.auth_service.validate_admin_token(t).await.is_ok() => Ok(()),
+        _ => Err("Unauthorized".to_string()),
+    }
+}
 
-                                // Send processing update if there are still tracks in progress
-                                if !ip.is_empty() {
-                                    let _ = tx.send(EmbeddingProgress::Processing {
-                                        completed: completed_count.load(Ordering::Relaxed),
-                                        total,
-                                        success_count: success_count.load(Ordering::Relaxed),
-                                        error_count: error_count.load(Ordering::Relaxed),
-                                        in_progress: ip.clone(),
-                                        message: format!("Processing {} tracks in parallel", ip.len()),
-                                    });
-                                }
-                            }
+/// Embedding progress as SSE events, until the backfill completes or fails. An error
+/// message is sent as a single error event.
+fn embedding_progress_sse(
+    progress: std::result::Result<
+        (Option<EmbeddingProgress>, broadcast::Receiver<EmbeddingProgress>),
+        String,
+    >,
+) -> Sse<impl Stream<Item = std::result::Result<Event, Infallible>>> {
+    let stream = async_stream::stream! {
+        let (latest, mut rx) = match progress {
+            Ok(progress) => progress,
+            Err(message) => {
+                if let Ok(event) = Event::default().json_data(&EmbeddingProgress::Error { message }) {
+                    yield Ok::<Event, Infallible>(event);
+                }
+                return;
+            }
+        };
 
-                            (track_id, result)
-                        }
-                    })
-                    .buffer_unordered(concurrency)
-                    .collect()
-                    .await;
-
-                let success_count = success_count.load(Ordering::Relaxed);
-                let error_count = error_count.load(Ordering::Relaxed);
-                let was_stopped = should_stop.load(Ordering::Relaxed);
-
-                let total_time_secs = start_time.elapsed().as_secs_f64();
-                let message = if was_stopped {
-                    format!(
-                        "Embedding indexing stopped: {} success, {} errors in {:.1}s (stopped early)",
-                        success_count, error_count, total_time_secs
-                    )
-                } else {
-                    format!(
-                        "Embedding indexing complete: {} success, {} errors in {:.1}s",
-                        success_count, error_count, total_time_secs
-                    )
-                };
-
-                let _ = tx_clone.send(EmbeddingProgress::Completed {
-                    success_count,
-                    error_count,
-                    total_time_secs,
-                    message,
-                });
-
-                // Reset control state to Idle
-                let mut control = embedding_control.write().await;
-                *control = EmbeddingControlState::Idle;
-            });
+        if let Some(latest) = latest {
+            if let Ok(event) = Event::default().json_data(&latest) {
+                yield Ok::<Event, Infallible>(event);
+            }
         }
-    }
 
-    // Convert broadcast receiver to SSE stream
-    let stream = async_stream::stream! {
         loop {
             match rx.recv().await {
                 Ok(progress) => {
@@ -1011,7 +1321,8 @@ async fn index_embeddings_stream(
                         break;
                     }
                 }
-                Err(_) => break,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
             }
         }
     };
@@ -1019,6 +1330,49 @@ async fn index_embeddings_stream(
     Sse::new(stream).keep_alive(axum::response::sse::KeepAlive::default())
 }
 
+/// GET /api/v1/embeddings/vector-index
+/// The similarity search index and its query settings
+async fn get_vector_index(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+) -> Result<Json<VectorIndexInfo>> {
+    let search = state
+        .audio_encoder
+        .as_ref()
+        .map(|encoder| encoder.vector_search())
+        .unwrap_or_default();
+    Ok(Json(vector_index::index_info(&state.db, search).await?))
+}
+
+/// POST /api/v1/embeddings/vector-index
+/// Rebuild the similarity search index (HNSW or IVFFlat) as a background job
+async fn rebuild_vector_index(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+    Json(params): Json<VectorIndexParams>,
+) -> Result<Json<serde_json::Value>> {
+    params.validate()?;
+
+    let db = state.db.clone();
+    let job_id = state
+        .jobs
+        .submit(JobKind::VectorIndexRebuild, serde_json::to_value(params)?, 1, move |ctx| {
+            let db = db.clone();
+            async move {
+                ctx.progress(0.0, "Building index").await;
+                vector_index::rebuild(&db, &params).await?;
+                Ok(serde_json::json!({}))
+            }
+        })
+        .await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Vector index rebuild started",
+        "status": "in_progress",
+        "job_id": job_id
+    })))
+}
+
 /// POST /api/v1/embeddings/pause
 /// Pause audio embedding indexing
 async fn pause_embeddings(
@@ -1092,7 +1446,7 @@ async fn stop_embeddings(
 /// Hybrid AI-powered track curation (LLM seeds + audio similarity)
 async fn hybrid_curate(
     State(state): State<Arc<AppState>>,
-    RequireAdmin(_): RequireAdmin,
+    RequireCurator(claims): RequireCurator,
     Json(req): Json<HybridCurateRequest>,
 ) -> Result<Json<HybridCurateResponse>> {
     if req.query.trim().is_empty() {
@@ -1100,19 +1454,23 @@ async fn hybrid_curate(
     }
 
     let limit = req.limit.unwrap_or(20);
+    let allow_explicit = req.allow_explicit.unwrap_or(true);
     let (track_ids, method) = if let Some(hybrid_curator) = &state.hybrid_curator {
         // Use hybrid curation (LLM + audio embeddings)
-        let ids = hybrid_curator.curate(&req.query, limit).await?;
+        let ids = hybrid_curator
+            .curate(&req.query, limit, Some(claims.sub), allow_explicit, req.energy_arc.as_ref())
+            .await?;
         (ids, "hybrid".to_string())
     } else if let Some(ai_curator) = &state.ai_curator {
         // Fall back to LLM-only curation
-        let ids = ai_curator.curate_tracks(req.query.clone(), limit).await?;
+        let ids = ai_curator.curate_tracks(req.query.clone(), limit, Some(claims.sub), allow_explicit).await?;
         (ids, "llm".to_string())
     } else {
-        return Err(AppError::ExternalApi(
+        return Err(AppError::NotConfigured(
             "No curation method available - configure ANTHROPIC_API_KEY".to_string()
         ));
     };
+    state.webhooks.curation_completed(&method, &req.query, &track_ids, Some(claims.sub));
 
     // Fetch track details
     let mut tracks = Vec::new();
@@ -1148,7 +1506,12 @@ async fn hybrid_curate_stream(
 ) -> Sse<impl Stream<Item = std::result::Result<Event, Infallible>>> {
     // Validate token
     let auth_service = &state.auth_service;
-    let token_valid = auth_service.validate_admin_token(&params.token).await.is_ok();
+    let user_id = auth_service
+        .validate_token_role(&params.token, UserRole::Curator)
+        .await
+        .ok()
+        .map(|claims| claims.sub);
+    let token_valid = user_id.is_some();
 
     // Create mpsc channel for progress updates
     let (tx, mut rx) = mpsc::channel::<HybridCurationProgress>(100);
@@ -1164,15 +1527,22 @@ async fn hybrid_curate_stream(
     } else {
         let hybrid_curator = state.hybrid_curator.clone();
         let ai_curator = state.ai_curator.clone();
+        let webhooks = state.webhooks.clone();
         let query = params.query.clone();
         let limit = params.limit.unwrap_or(50);
+        let allow_explicit = params.allow_explicit.unwrap_or(true);
+        let energy_arc = params.energy_arc.clone();
 
         tokio::spawn(async move {
             if let Some(curator) = hybrid_curator {
                 // Use hybrid curation with progress
-                match curator.curate_with_progress(&query, limit, tx.clone()).await {
-                    Ok(_) => {
+                match curator
+                    .curate_with_progress(&query, limit, user_id, allow_explicit, energy_arc.as_ref(), tx.clone())
+                    .await
+                {
+                    Ok(track_ids) => {
                         // Progress already sent by curate_with_progress
+                        webhooks.curation_completed("hybrid", &query, &track_ids, user_id);
                     }
                     Err(e) => {
                         let _ = tx.send(HybridCurationProgress::Error {
@@ -1186,8 +1556,9 @@ async fn hybrid_curate_stream(
                     message: "Using LLM-only curation (no audio embeddings)...".to_string(),
                 }).await;
 
-                match ai_curator.curate_tracks(query.clone(), limit).await {
+                match ai_curator.curate_tracks(query.clone(), limit, user_id, allow_explicit).await {
                     Ok(track_ids) => {
+                        webhooks.curation_completed("llm", &query, &track_ids, user_id);
                         let _ = tx.send(HybridCurationProgress::Completed {
                             message: format!("Selected {} tracks", track_ids.len()),
                             total_tracks: track_ids.len(),
@@ -1238,7 +1609,7 @@ async fn hybrid_curate_stream(
 /// Phase 1: Select seed tracks for user review
 async fn select_seeds(
     State(state): State<Arc<AppState>>,
-    RequireAdmin(_): RequireAdmin,
+    RequireCurator(_): RequireCurator,
     Json(req): Json<SelectSeedsRequest>,
 ) -> Result<Json<SelectSeedsResponse>> {
     if req.query.trim().is_empty() {
@@ -1247,14 +1618,11 @@ async fn select_seeds(
 
     let seed_count = req.seed_count.unwrap_or(5);
 
-    // Get seed selector - requires ANTHROPIC_API_KEY in environment
-    let anthropic_key = std::env::var("ANTHROPIC_API_KEY")
-        .map_err(|_| AppError::ExternalApi("ANTHROPIC_API_KEY not configured".to_string()))?;
-
-    let seed_selector = crate::services::seed_selector::SeedSelector::new(
-        anthropic_key,
-        state.db.clone(),
-    );
+    let llm = state
+        .llm
+        .clone()
+        .ok_or_else(|| AppError::NotConfigured("ANTHROPIC_API_KEY not configured".to_string()))?;
+    let seed_selector = crate::services::seed_selector::SeedSelector::new(llm, state.db.clone());
 
     // Select seeds with genres
     let result = seed_selector.select_seeds_with_genres(&req.query, seed_count, 200).await?;
@@ -1289,20 +1657,18 @@ async fn select_seeds(
 /// Regenerate a single seed at a specific position
 async fn regenerate_seed(
     State(state): State<Arc<AppState>>,
-    RequireAdmin(_): RequireAdmin,
+    RequireCurator(_): RequireCurator,
     Json(req): Json<RegenerateSeedRequest>,
 ) -> Result<Json<RegenerateSeedResponse>> {
     if req.query.trim().is_empty() {
         return Err(AppError::Validation("Query cannot be empty".to_string()));
     }
 
-    let anthropic_key = std::env::var("ANTHROPIC_API_KEY")
-        .map_err(|_| AppError::ExternalApi("ANTHROPIC_API_KEY not configured".to_string()))?;
-
-    let seed_selector = crate::services::seed_selector::SeedSelector::new(
-        anthropic_key,
-        state.db.clone(),
-    );
+    let llm = state
+        .llm
+        .clone()
+        .ok_or_else(|| AppError::NotConfigured("ANTHROPIC_API_KEY not configured".to_string()))?;
+    let seed_selector = crate::services::seed_selector::SeedSelector::new(llm, state.db.clone());
 
     // Select a single new seed, excluding the ones already selected
     let verified_seeds = seed_selector.select_seeds_excluding(&req.query, 1, &req.exclude_ids).await?;
@@ -1333,7 +1699,7 @@ async fn regenerate_seed(
 /// Phase 2: Fill gaps between approved seeds using audio similarity
 async fn fill_gaps(
     State(state): State<Arc<AppState>>,
-    RequireAdmin(_): RequireAdmin,
+    RequireCurator(claims): RequireCurator,
     Json(req): Json<FillGapsRequest>,
 ) -> Result<Json<FillGapsResponse>> {
     if req.seed_ids.is_empty() {
@@ -1343,15 +1709,23 @@ async fn fill_gaps(
     let total_size = req.total_size.unwrap_or(200);
 
     let audio_encoder = state.audio_encoder.as_ref()
-        .ok_or_else(|| AppError::ExternalApi("Audio encoder not available".to_string()))?;
+        .ok_or_else(|| AppError::NotConfigured("Audio encoder not available".to_string()))?;
 
     let library_path = state.navidrome_library_path.as_ref()
-        .ok_or_else(|| AppError::ExternalApi("Library path not configured".to_string()))?;
+        .ok_or_else(|| AppError::NotConfigured("Library path not configured".to_string()))?;
     let library_path = std::path::Path::new(library_path);
 
-    // Build the playlist by filling gaps between seeds
+    // Build the playlist by filling gaps between seeds, never using tracks the curator rated 1 star
+    // or less (or explicit ones, unless allowed)
     let mut playlist = Vec::with_capacity(total_size);
     let mut used_ids: Vec<String> = req.seed_ids.clone();
+    used_ids.extend(
+        RatingBias::load(&state.db, Some(claims.sub))
+            .await?
+            .with_explicit(&state.db, req.allow_explicit.unwrap_or(true))
+            .await?
+            .excluded_ids(),
+    );
 
     // Calculate tracks per gap
     let num_seeds = req.seed_ids.len();
@@ -1471,3 +1845,73 @@ async fn fill_gaps(
         filled_count,
     }))
 }
+
+/// POST /api/v1/ai/journey
+/// A playlist that moves from one track to another through embedding space, for a
+/// station's curated tracks in journey mode
+async fn journey(
+    State(state): State<Arc<AppState>>,
+    RequireCurator(claims): RequireCurator,
+    Json(req): Json<JourneyRequest>,
+) -> Result<Json<JourneyResponse>> {
+    let length = req.length.unwrap_or(20);
+    if !(3..=MAX_JOURNEY_LENGTH).contains(&length) {
+        return Err(AppError::Validation(format!(
+            "length must be between 3 and {}",
+            MAX_JOURNEY_LENGTH
+        )));
+    }
+    let drift_tolerance = req.drift_tolerance.unwrap_or(0.1);
+    if !(0.0..=2.0).contains(&drift_tolerance) {
+        return Err(AppError::Validation("drift_tolerance must be between 0 and 2".to_string()));
+    }
+    if req.start_track_id == req.end_track_id {
+        return Err(AppError::Validation("start and end tracks must differ".to_string()));
+    }
+
+    let audio_encoder = state.audio_encoder.as_ref()
+        .ok_or_else(|| AppError::NotConfigured("Audio encoder not available".to_string()))?;
+
+    // Never route through tracks the curator rated 1 star or less
+    let excluded = RatingBias::load(&state.db, Some(claims.sub)).await?.excluded_ids();
+    let track_ids = audio_encoder
+        .journey(&req.start_track_id, &req.end_track_id, length, drift_tolerance, &excluded)
+        .await?;
+
+    let tracks: Vec<TrackInfo> = sqlx::query_as::<_, (String, String, String)>(
+        "SELECT li.id, li.title, li.artist
+         FROM unnest($1::varchar[]) WITH ORDINALITY AS j(id, position)
+         JOIN library_index li ON li.id = j.id
+         ORDER BY j.position",
+    )
+    .bind(&track_ids)
+    .fetch_all(&state.db)
+    .await?
+    .into_iter()
+    .map(|(id, title, artist)| TrackInfo { id, title, artist })
+    .collect();
+
+    Ok(Json(JourneyResponse { track_ids, tracks }))
+}
+
+#[derive(Debug, Deserialize)]
+struct AiUsageQuery {
+    days: Option<i64>,
+}
+
+/// GET /api/v1/ai/usage
+/// Tokens and estimated cost of LLM calls per feature and per day, and what is left
+/// of today's budget
+async fn ai_usage(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+    Query(params): Query<AiUsageQuery>,
+) -> Result<Json<AiUsage>> {
+    let llm = state
+        .llm
+        .as_ref()
+        .ok_or_else(|| AppError::NotConfigured("ANTHROPIC_API_KEY not configured".to_string()))?;
+
+    let days = params.days.unwrap_or(30).clamp(1, 365);
+    Ok(Json(llm.usage(days).await?))
+}
@@ -0,0 +1,392 @@
+use crate::api::middleware::RequireAuth;
+use crate::api::stations::AppState;
+use crate::error::{AppError, Result};
+use crate::models::{
+    ApiKey, CreateApiKeyResponse, CreateStreamTokenRequest, DeleteAccountRequest, FavoriteStation, FavoriteTrack, Favorites, ListeningHistoryEntry, Station,
+    ConnectListenBrainzRequest, ConnectNavidromeRequest, NavidromeAccount, ScrobbleAccount, UserSession,
+};
+use crate::services::{sessions, user_data};
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    station_id: Option<Uuid>,
+}
+
+pub fn me_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/history", get(get_history))
+        .route("/favorites", get(get_favorites))
+        .route("/daily-mix", get(get_daily_mix))
+        .route(
+            "/favorites/stations/:id",
+            put(add_favorite_station).delete(remove_favorite_station),
+        )
+        .route(
+            "/favorites/tracks/:id",
+            put(add_favorite_track).delete(remove_favorite_track),
+        )
+        .route("/sessions", get(list_sessions))
+        .route("/sessions/:id", delete(revoke_session))
+        .route("/stream-tokens", get(list_stream_tokens).post(create_stream_token))
+        .route("/stream-tokens/:id", delete(revoke_stream_token))
+        .route(
+            "/navidrome",
+            get(get_navidrome_account).put(connect_navidrome_account).delete(disconnect_navidrome_account),
+        )
+        .route(
+            "/listenbrainz",
+            get(get_listenbrainz_account).put(connect_listenbrainz_account).delete(disconnect_listenbrainz_account),
+        )
+        .route("/export", get(export_data))
+        .route("/delete", post(delete_account))
+}
+
+/// GET /api/v1/me/navidrome
+/// The Navidrome account connected by the current user (null when using the shared one)
+async fn get_navidrome_account(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+) -> Result<Json<Option<NavidromeAccount>>> {
+    Ok(Json(state.navidrome_accounts.get(claims.sub).await?))
+}
+
+/// PUT /api/v1/me/navidrome
+/// Connect the current user's own Navidrome account (credentials are checked first)
+async fn connect_navidrome_account(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Json(req): Json<ConnectNavidromeRequest>,
+) -> Result<Json<NavidromeAccount>> {
+    req.validate()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    let account = state.navidrome_accounts.connect(claims.sub, req).await?;
+    Ok(Json(account))
+}
+
+/// DELETE /api/v1/me/navidrome
+/// Go back to using the shared Navidrome account
+async fn disconnect_navidrome_account(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+) -> Result<Json<()>> {
+    if !state.navidrome_accounts.disconnect(claims.sub).await? {
+        return Err(AppError::NotFound("No Navidrome account connected".to_string()));
+    }
+    Ok(Json(()))
+}
+
+/// GET /api/v1/me/listenbrainz
+/// The ListenBrainz account the current user's listens are submitted to (null if none)
+async fn get_listenbrainz_account(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+) -> Result<Json<Option<ScrobbleAccount>>> {
+    Ok(Json(state.listenbrainz.account(claims.sub).await?))
+}
+
+/// PUT /api/v1/me/listenbrainz
+/// Submit the current user's listens to ListenBrainz with their user token (checked first)
+async fn connect_listenbrainz_account(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Json(req): Json<ConnectListenBrainzRequest>,
+) -> Result<Json<ScrobbleAccount>> {
+    req.validate()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    let account = state.listenbrainz.connect(claims.sub, req.token.trim()).await?;
+    Ok(Json(account))
+}
+
+/// DELETE /api/v1/me/listenbrainz
+/// Stop submitting the current user's listens (listens not yet sent are discarded)
+async fn disconnect_listenbrainz_account(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+) -> Result<Json<()>> {
+    if !state.listenbrainz.disconnect(claims.sub).await? {
+        return Err(AppError::NotFound("No ListenBrainz account connected".to_string()));
+    }
+    Ok(Json(()))
+}
+
+/// GET /api/v1/me/sessions
+/// Devices the current user is signed in on, most recently used first
+async fn list_sessions(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+) -> Result<Json<Vec<UserSession>>> {
+    let sessions = sessions::list(&state.db, claims.sub, claims.sid).await?;
+    Ok(Json(sessions))
+}
+
+/// DELETE /api/v1/me/sessions/:id
+/// Sign out a device; its token stops working immediately
+async fn revoke_session(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path(id): Path<Uuid>,
+) -> Result<Json<()>> {
+    sessions::revoke(&state.db, claims.sub, id).await?;
+    Ok(Json(()))
+}
+
+/// GET /api/v1/me/stream-tokens
+/// The current user's stream-only tokens
+async fn list_stream_tokens(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+) -> Result<Json<Vec<ApiKey>>> {
+    let tokens = state.auth_service.list_stream_tokens(claims.sub).await?;
+    Ok(Json(tokens))
+}
+
+/// POST /api/v1/me/stream-tokens
+/// Create a token that can only listen to the given stations, for use in a player's
+/// stream URL (`.../stream/playlist.m3u8?token=...`). The token is only shown here.
+async fn create_stream_token(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Json(req): Json<CreateStreamTokenRequest>,
+) -> Result<Json<CreateApiKeyResponse>> {
+    req.validate()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    let response = state
+        .auth_service
+        .create_api_key(claims.sub, req.into())
+        .await?;
+    Ok(Json(response))
+}
+
+/// DELETE /api/v1/me/stream-tokens/:id
+/// Revoke one of the current user's stream tokens
+async fn revoke_stream_token(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path(id): Path<Uuid>,
+) -> Result<Json<()>> {
+    state.auth_service.revoke_api_key(id, Some(claims.sub)).await?;
+    Ok(Json(()))
+}
+
+/// GET /api/v1/me/export
+/// Download everything stored about the current user as a JSON file
+async fn export_data(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+) -> Result<impl IntoResponse> {
+    let user = state.auth_service.get_user_by_id(claims.sub).await?;
+    let export = user_data::export(&state.db, &user).await?;
+
+    let disposition = format!(
+        "attachment; filename=\"navidrome-radio-export-{}.json\"",
+        Utc::now().format("%Y%m%d")
+    );
+    Ok(([(header::CONTENT_DISPOSITION, disposition)], Json(export)))
+}
+
+/// POST /api/v1/me/delete
+/// Permanently delete the current user's account (password required, unless the
+/// account only signs in through the OpenID Connect provider). Ratings,
+/// favorites and API keys are deleted, listening history is kept without the user,
+/// and stations the user created are handed to an admin.
+async fn delete_account(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Json(req): Json<DeleteAccountRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let stations_transferred = state
+        .auth_service
+        .delete_own_account(claims.sub, req.password.as_deref())
+        .await?;
+    tracing::info!(
+        "User {} deleted their account ({} station(s) transferred)",
+        claims.sub, stations_transferred
+    );
+
+    Ok(Json(serde_json::json!({
+        "message": "Account deleted",
+        "stations_transferred": stations_transferred
+    })))
+}
+
+/// GET /api/v1/me/history
+/// Tracks the current user listened to, most recent first
+async fn get_history(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Query(params): Query<HistoryQuery>,
+) -> Result<Json<Vec<ListeningHistoryEntry>>> {
+    let entries = sqlx::query_as::<_, ListeningHistoryEntry>(
+        r#"
+        SELECT h.id, h.station_id, s.name AS station_name, h.track_id,
+               l.title, l.artist, l.album, h.first_heard_at, h.last_heard_at,
+               EXTRACT(EPOCH FROM (h.last_heard_at - h.first_heard_at))::BIGINT AS listened_secs
+        FROM listening_history h
+        JOIN stations s ON s.id = h.station_id
+        LEFT JOIN library_index l ON l.id = h.track_id
+        WHERE h.user_id = $1
+          AND ($2::UUID IS NULL OR h.station_id = $2)
+        ORDER BY h.last_heard_at DESC
+        LIMIT $3 OFFSET $4
+        "#,
+    )
+    .bind(claims.sub)
+    .bind(params.station_id)
+    .bind(params.limit.unwrap_or(50).clamp(1, 500))
+    .bind(params.offset.unwrap_or(0).max(0))
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(entries))
+}
+
+/// GET /api/v1/me/daily-mix
+/// The current user's Daily Mix station (404 until one has been built)
+async fn get_daily_mix(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+) -> Result<Json<Station>> {
+    let station = sqlx::query_as::<_, Station>("SELECT * FROM stations WHERE daily_mix_user_id = $1")
+        .bind(claims.sub)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("No Daily Mix yet - listen to a few stations first".to_string()))?;
+
+    Ok(Json(station))
+}
+
+/// GET /api/v1/me/favorites
+/// The current user's favorite stations and tracks
+async fn get_favorites(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+) -> Result<Json<Favorites>> {
+    let stations = sqlx::query_as::<_, FavoriteStation>(
+        r#"
+        SELECT f.station_id, s.path, s.name, s.active, f.created_at
+        FROM user_favorite_stations f
+        JOIN stations s ON s.id = f.station_id
+        WHERE f.user_id = $1
+        ORDER BY f.created_at DESC
+        "#,
+    )
+    .bind(claims.sub)
+    .fetch_all(&state.db)
+    .await?;
+
+    let tracks = sqlx::query_as::<_, FavoriteTrack>(
+        r#"
+        SELECT f.track_id, l.title, l.artist, l.album, f.created_at
+        FROM user_favorite_tracks f
+        JOIN library_index l ON l.id = f.track_id
+        WHERE f.user_id = $1
+        ORDER BY f.created_at DESC
+        "#,
+    )
+    .bind(claims.sub)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(Favorites { stations, tracks }))
+}
+
+/// PUT /api/v1/me/favorites/stations/:id
+/// Add a station to the current user's favorites
+async fn add_favorite_station(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path(id): Path<Uuid>,
+) -> Result<Json<()>> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM stations WHERE id = $1)")
+        .bind(id)
+        .fetch_one(&state.db)
+        .await?;
+    if !exists {
+        return Err(AppError::NotFound("Station not found".to_string()));
+    }
+
+    sqlx::query(
+        "INSERT INTO user_favorite_stations (user_id, station_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(claims.sub)
+    .bind(id)
+    .execute(&state.db)
+    .await?;
+
+    Ok(Json(()))
+}
+
+/// DELETE /api/v1/me/favorites/stations/:id
+/// Remove a station from the current user's favorites
+async fn remove_favorite_station(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path(id): Path<Uuid>,
+) -> Result<Json<()>> {
+    sqlx::query("DELETE FROM user_favorite_stations WHERE user_id = $1 AND station_id = $2")
+        .bind(claims.sub)
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(Json(()))
+}
+
+/// PUT /api/v1/me/favorites/tracks/:id
+/// Add a library track to the current user's favorites
+async fn add_favorite_track(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path(id): Path<String>,
+) -> Result<Json<()>> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM library_index WHERE id = $1)")
+        .bind(&id)
+        .fetch_one(&state.db)
+        .await?;
+    if !exists {
+        return Err(AppError::NotFound("Track not found".to_string()));
+    }
+
+    sqlx::query(
+        "INSERT INTO user_favorite_tracks (user_id, track_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(claims.sub)
+    .bind(&id)
+    .execute(&state.db)
+    .await?;
+
+    Ok(Json(()))
+}
+
+/// DELETE /api/v1/me/favorites/tracks/:id
+/// Remove a track from the current user's favorites
+async fn remove_favorite_track(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path(id): Path<String>,
+) -> Result<Json<()>> {
+    sqlx::query("DELETE FROM user_favorite_tracks WHERE user_id = $1 AND track_id = $2")
+        .bind(claims.sub)
+        .bind(&id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(Json(()))
+}
@@ -1,14 +1,21 @@
 use crate::api::stations::AppState;
 use crate::error::{AppError, Result};
-use crate::models::UserRole;
-use crate::services::auth::Claims;
+use crate::models::{DeviceInfo, UserRole};
+use crate::services::auth::{Claims, API_KEY_PREFIX};
+use crate::services::rate_limit::{Client, RouteBudget};
 use axum::{
     async_trait,
-    extract::FromRequestParts,
-    http::request::Parts,
+    extract::{ConnectInfo, FromRequestParts, OriginalUri, Request, State},
+    http::{header, request::Parts, HeaderMap, Method},
+    middleware::Next,
+    response::Response,
 };
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
 
+/// Header scripts and home automation can send an API key in instead of `Authorization`
+pub const API_KEY_HEADER: &str = "x-api-key";
+
 pub struct RequireAuth(pub Claims);
 
 #[async_trait]
@@ -19,24 +26,20 @@ impl FromRequestParts<Arc<AppState>> for RequireAuth {
         parts: &mut Parts,
         state: &Arc<AppState>,
     ) -> Result<Self> {
-        // Try to get token from Authorization header first
-        let token = parts
-            .headers
-            .get("Authorization")
-            .and_then(|v| v.to_str().ok())
-            .and_then(|v| v.strip_prefix("Bearer "))
-            .or_else(|| {
-                // Fall back to query parameter for SSE (EventSource can't send custom headers)
-                parts
-                    .uri
-                    .query()
-                    .and_then(|q| {
-                        q.split('&')
-                            .find(|p| p.starts_with("token="))
-                            .and_then(|p| p.strip_prefix("token="))
-                    })
-            })
-            .ok_or(AppError::Unauthorized)?;
+        if let Some(key) = parts.headers.get(API_KEY_HEADER) {
+            let key = key
+                .to_str()
+                .ok()
+                .filter(|key| key.starts_with(API_KEY_PREFIX))
+                .ok_or(AppError::Unauthorized)?;
+            return Ok(RequireAuth(verify_api_key(parts, state, key).await?));
+        }
+
+        let token = bearer_token(parts).ok_or(AppError::Unauthorized)?;
+
+        if token.starts_with(API_KEY_PREFIX) {
+            return Ok(RequireAuth(verify_api_key(parts, state, token).await?));
+        }
 
         // Verify token
         let claims = state.auth_service.verify_token(token).await?;
@@ -45,6 +48,127 @@ impl FromRequestParts<Arc<AppState>> for RequireAuth {
     }
 }
 
+/// Token from the Authorization header, or the `token` query parameter
+fn bearer_token(parts: &Parts) -> Option<&str> {
+    // Try to get token from Authorization header first
+    parts
+        .headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| {
+            // Fall back to query parameter for SSE (EventSource can't send custom headers)
+            parts
+                .uri
+                .query()
+                .and_then(|q| {
+                    q.split('&')
+                        .find(|p| p.starts_with("token="))
+                        .and_then(|p| p.strip_prefix("token="))
+                })
+        })
+}
+
+/// API keys are checked against their scopes using the full request path
+async fn verify_api_key(parts: &Parts, state: &Arc<AppState>, key: &str) -> Result<Claims> {
+    state.auth_service.verify_api_key(key, &parts.method, api_path(parts)).await
+}
+
+/// Request path below `/api/v1`, also when read from a nested router
+fn api_path(parts: &Parts) -> &str {
+    let path = parts
+        .extensions
+        .get::<OriginalUri>()
+        .map(|uri| uri.path())
+        .unwrap_or_else(|| parts.uri.path());
+    path.strip_prefix("/api/v1").unwrap_or(path)
+}
+
+/// Rate limiting layer for the API (see [`crate::services::rate_limit`]). Requests with
+/// a validly signed token count against their user; anonymous and API key requests
+/// count against the client IP.
+pub async fn rate_limit(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Result<Response> {
+    let Some(limiter) = state.rate_limiter.clone() else {
+        return Ok(next.run(request).await);
+    };
+
+    let (mut parts, body) = request.into_parts();
+    let user = bearer_token(&parts)
+        .filter(|token| !token.starts_with(API_KEY_PREFIX))
+        .and_then(|token| state.auth_service.token_subject(token));
+    let client = match user {
+        Some(id) => Client::User(id),
+        None => Client::Ip(ClientIp::from_request_parts(&mut parts, &state).await?.0),
+    };
+
+    limiter
+        .check(client, RouteBudget::for_request(&parts.method, api_path(&parts)))
+        .await?;
+
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
+
+/// Claims when the request carries a valid token, None for anonymous requests.
+/// An invalid or expired token is treated as anonymous rather than rejected.
+pub struct OptionalAuth(pub Option<Claims>);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for OptionalAuth {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self> {
+        let claims = RequireAuth::from_request_parts(parts, state)
+            .await
+            .ok()
+            .map(|RequireAuth(claims)| claims);
+        Ok(OptionalAuth(claims))
+    }
+}
+
+/// Gate for the listening routes (stations, now playing, streams). Anonymous requests
+/// may only read and send listener heartbeats, and only while ALLOW_ANONYMOUS_LISTENING
+/// is on; everything else needs a valid token. Handlers still apply their own role checks.
+pub struct ListenerAccess;
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for ListenerAccess {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self> {
+        let anonymous_allowed = state.allow_anonymous_listening
+            && (matches!(parts.method, Method::GET | Method::HEAD | Method::OPTIONS)
+                || is_listener_presence(parts.uri.path()));
+
+        if !anonymous_allowed {
+            RequireAuth::from_request_parts(parts, state).await?;
+        }
+
+        Ok(ListenerAccess)
+    }
+}
+
+/// Heartbeat and leave requests sent by anonymous players
+fn is_listener_presence(path: &str) -> bool {
+    path.ends_with("/listener/heartbeat") || path.ends_with("/listener/leave")
+}
+
+/// Authenticate and require at least the given role
+async fn require_role(parts: &mut Parts, state: &Arc<AppState>, role: UserRole) -> Result<Claims> {
+    let RequireAuth(claims) = RequireAuth::from_request_parts(parts, state).await?;
+
+    if !claims.role.includes(&role) {
+        return Err(AppError::Forbidden);
+    }
+
+    Ok(claims)
+}
+
 pub struct RequireAdmin(pub Claims);
 
 #[async_trait]
@@ -55,12 +179,106 @@ impl FromRequestParts<Arc<AppState>> for RequireAdmin {
         parts: &mut Parts,
         state: &Arc<AppState>,
     ) -> Result<Self> {
-        let RequireAuth(claims) = RequireAuth::from_request_parts(parts, state).await?;
+        let claims = require_role(parts, state, UserRole::Admin).await?;
+        Ok(RequireAdmin(claims))
+    }
+}
+
+/// Curator or admin, for creating stations and the curation tools. Actions on an existing
+/// station are authorized by the caller's access to that station instead, which per-station
+/// grants can give users of any role.
+pub struct RequireCurator(pub Claims);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for RequireCurator {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self> {
+        let claims = require_role(parts, state, UserRole::Curator).await?;
+        Ok(RequireCurator(claims))
+    }
+}
+
+/// Client IP address: the socket peer, or the address the trusted proxy forwarded when
+/// TRUST_PROXY_HEADERS is enabled
+pub struct ClientIp(pub IpAddr);
+
+/// The address the proxy in front of us saw. Proxies append the peer they received a
+/// request from to `X-Forwarded-For`, so only its last entry is trustworthy; the ones
+/// before it come from the client.
+fn forwarded_ip(headers: &HeaderMap) -> Option<IpAddr> {
+    headers
+        .get("X-Forwarded-For")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.rsplit(',').next())
+        .or_else(|| headers.get("X-Real-IP").and_then(|v| v.to_str().ok()))
+        .and_then(|v| v.trim().parse().ok())
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for ClientIp {
+    type Rejection = AppError;
 
-        if claims.role != UserRole::Admin {
-            return Err(AppError::Forbidden);
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self> {
+        if state.trust_proxy_headers {
+            if let Some(ip) = forwarded_ip(&parts.headers) {
+                return Ok(ClientIp(ip));
+            }
         }
 
-        Ok(RequireAdmin(claims))
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        Ok(ClientIp(ip))
+    }
+}
+
+/// Client IP and user agent, recorded on the session a login creates
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for DeviceInfo {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self> {
+        let ClientIp(ip) = ClientIp::from_request_parts(parts, state).await?;
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        Ok(DeviceInfo { ip, user_agent })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forwarded_ip_is_the_one_the_proxy_appended() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(forwarded_ip(&headers), None);
+
+        headers.insert("X-Real-IP", "198.51.100.9".parse().unwrap());
+        assert_eq!(forwarded_ip(&headers), Some("198.51.100.9".parse().unwrap()));
+
+        // The client can put anything in front of the entry the proxy appends
+        headers.insert("X-Forwarded-For", "203.0.113.1, 192.0.2.7".parse().unwrap());
+        assert_eq!(forwarded_ip(&headers), Some("192.0.2.7".parse().unwrap()));
+
+        headers.insert("X-Forwarded-For", "192.0.2.7".parse().unwrap());
+        assert_eq!(forwarded_ip(&headers), Some("192.0.2.7".parse().unwrap()));
     }
 }
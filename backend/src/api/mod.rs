@@ -1,12 +1,22 @@
+pub mod admin;
 pub mod auth;
+pub mod capabilities;
+pub mod health;
 pub mod library;
+pub mod me;
 pub mod settings;
 pub mod stations;
 pub mod streaming;
 pub mod middleware;
+pub mod webhooks;
 
+pub use admin::admin_routes;
 pub use auth::auth_routes;
+pub use capabilities::capabilities_routes;
+pub use health::health_routes;
 pub use library::library_routes;
+pub use me::me_routes;
 pub use settings::router as settings_routes;
 pub use stations::station_routes;
-pub use streaming::streaming_routes;
+pub use streaming::{station_stream_routes, streaming_routes};
+pub use webhooks::webhook_routes;
@@ -1,60 +1,125 @@
-use crate::api::middleware::RequireAdmin;
+use crate::api::middleware::{OptionalAuth, RequireAuth, RequireCurator};
 use crate::error::{AppError, Result};
-use crate::models::{CreateStationRequest, CurationProgress, NowPlaying, Station, UpdateStationRequest};
+use crate::models::{
+    AddCollaboratorRequest, AnalyticsWindow, CreateStationRequest, CreateTrackRequest, CurationProgress, ImportPlaylistRequest, NowPlaying,
+    ScheduleBlock, ScheduleBlockRequest, Station, StationAnalytics, StationCollaborator, StationConfig, StationImportRequest, StationRole,
+    TrackRequest, UpdateStationRequest, User, UserRole,
+};
 use crate::services::{
-    audio_broadcaster::{AudioBroadcaster, AudioBroadcasterConfig},
+    announcer::Announcer,
+    audio_broadcaster::{AudioBroadcaster, HlsSegment, INIT_SEGMENT_PATH},
+    audio_cache::AudioCache,
     audio_encoder::AudioEncoder,
-    audio_pipeline::{AudioPipeline, AudioPipelineConfig, QueuedTrack},
+    broadcast_archive::{self, ArchiveInfo, BroadcastArchive, CueSheet},
+    embedding_worker::{EmbeddingControlState, EmbeddingWorker},
     hybrid_curator::HybridCurator,
     library_indexer::LibraryIndexer,
-    AiCurator, AuthService, CurationEngine, NavidromeClient, StationManager,
+    library_scheduler::LibraryScheduler,
+    library_watcher::LibraryWatcher,
+    lastfm::LastFmClient,
+    listenbrainz::ListenBrainzClient,
+    listening_history,
+    llm::LlmClient,
+    musicbrainz::MusicBrainzClient,
+    loudness::LoudnessAnalyzer,
+    navidrome::NavidromePlaylist,
+    navidrome_accounts::NavidromeAccounts,
+    oidc::OidcClient,
+    playlist_import::{self, ImportEntry, UnresolvedEntry},
+    radio_publisher::RadioPublisher,
+    rate_limit::RateLimiter,
+    schedule,
+    station_manager::HLS_LATENCY_SECS,
+    station_analytics,
+    station_events::{StationEvent, StationEventBus},
+    track_requests,
+    auth::Claims,
+    cluster::{Cluster, StreamLocation},
+    AiCurator, AuthService, CurationEngine, DailyMixService, JobManager, LoginGuard, NavidromeClient, StationManager,
+    WebhookDispatcher,
 };
 use axum::{
     body::Body,
-    extract::{Path, State},
-    http::{header, StatusCode},
-    response::{sse::{Event, KeepAlive, Sse}, Response},
-    routing::{get, post},
+    extract::{OriginalUri, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{sse::{Event, KeepAlive, Sse}, IntoResponse, Redirect, Response},
+    routing::{delete, get, post, put},
     Json, Router,
 };
 use futures::{stream::Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use std::{collections::HashMap, convert::Infallible, sync::Arc};
+use std::{collections::{HashMap, HashSet}, convert::Infallible, sync::Arc};
 use tokio::sync::{mpsc, RwLock};
 use tokio_stream::wrappers::ReceiverStream;
 use uuid::Uuid;
 use validator::Validate;
 
-/// State for controlling embedding indexing
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum EmbeddingControlState {
-    Idle,
-    Running,
-    Paused,
-    Stopping,
-}
-
-impl Default for EmbeddingControlState {
-    fn default() -> Self {
-        Self::Idle
-    }
-}
-
 pub struct AppState {
     pub db: PgPool,
     pub auth_service: Arc<AuthService>,
+    pub login_guard: Arc<LoginGuard>,
+    /// Per-user and per-IP request budgets (RATE_LIMIT_PER_MIN), None when disabled
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Trust X-Forwarded-For / X-Real-IP for the client IP (TRUST_PROXY_HEADERS)
+    pub trust_proxy_headers: bool,
+    /// Serve streams and station info without a login (ALLOW_ANONYMOUS_LISTENING)
+    pub allow_anonymous_listening: bool,
     pub station_manager: Arc<StationManager>,
     pub curation_engine: Arc<CurationEngine>,
     pub library_indexer: Arc<LibraryIndexer>,
+    /// Background job runner for long-running work (sync, analysis, indexing)
+    pub jobs: Arc<JobManager>,
     pub ai_curator: Option<Arc<AiCurator>>,
     pub audio_encoder: Option<Arc<AudioEncoder>>,
     pub hybrid_curator: Option<Arc<HybridCurator>>,
     pub navidrome_client: Arc<NavidromeClient>,
+    /// Users' own Navidrome accounts, for actions taken on their behalf
+    pub navidrome_accounts: Arc<NavidromeAccounts>,
+    /// Submits station plays and connected listeners' listens to ListenBrainz
+    pub listenbrainz: Arc<ListenBrainzClient>,
+    /// Last.fm scrobbling and artist metadata (LASTFM_API_KEY)
+    pub lastfm: Option<Arc<LastFmClient>>,
+    /// MusicBrainz/AcousticBrainz track metadata (MUSICBRAINZ_ENABLED)
+    pub musicbrainz: Option<Arc<MusicBrainzClient>>,
+    /// Speaks links between tracks (ANNOUNCER_TTS_URL or ANNOUNCER_TTS_COMMAND)
+    pub announcer: Option<Arc<Announcer>>,
+    /// Publishes stations into Navidrome's internet radio list (NAVIDROME_PUBLISH_STATIONS)
+    pub radio_publisher: Option<Arc<RadioPublisher>>,
     pub navidrome_library_path: Option<String>,
     pub embedding_control: Arc<tokio::sync::RwLock<EmbeddingControlState>>,
+    /// Resumable embedding backfill (requires the audio encoder and an audio source)
+    pub embedding_worker: Option<Arc<EmbeddingWorker>>,
     /// Per-station audio broadcasters for HLS streaming
     pub station_broadcasters: Arc<RwLock<HashMap<Uuid, Arc<AudioBroadcaster>>>>,
+    /// Builds personal Daily Mix stations (requires the hybrid curator)
+    pub daily_mix: Option<Arc<DailyMixService>>,
+    /// Disk cache of tracks fetched for playback and embeddings (AUDIO_CACHE_DIR)
+    pub audio_cache: Option<Arc<AudioCache>>,
+    /// Imports tracks after Navidrome scans (LIBRARY_WATCH_SECS)
+    pub library_watcher: Arc<LibraryWatcher>,
+    /// Periodic library syncs, analysis, embedding backfills and stats refreshes
+    pub library_scheduler: Arc<LibraryScheduler>,
+    /// Measures track loudness for normalization (LOUDNESS_TARGET_LUFS)
+    pub loudness_analyzer: Arc<LoudnessAnalyzer>,
+    /// Where broadcasters spill their segment windows, one directory per station (HLS_SPILL_DIR)
+    pub hls_spill_dir: Option<std::path::PathBuf>,
+    /// Recorded broadcasts (ARCHIVE_DIR)
+    pub archive: Option<Arc<BroadcastArchive>>,
+    /// Station changes shared with other API instances over Redis pub/sub
+    pub station_events: Arc<StationEventBus>,
+    /// Which node streams which station when API and streamer roles are split (SERVER_ROLE)
+    pub cluster: Arc<Cluster>,
+    /// Shared Redis connection, probed by the readiness check
+    pub redis: redis::aio::ConnectionManager,
+    /// Delivers events to admin-registered webhooks
+    pub webhooks: WebhookDispatcher,
+    /// Sign-in through an OpenID Connect provider (OIDC_ISSUER_URL)
+    pub oidc: Option<Arc<OidcClient>>,
+    /// Claude client shared by the AI features, with budget and usage accounting (ANTHROPIC_API_KEY)
+    pub llm: Option<Arc<LlmClient>>,
+    /// Track requests each user may make per hour (REQUESTS_PER_HOUR)
+    pub requests_per_hour: u32,
 }
 
 #[derive(Debug, Serialize)]
@@ -79,28 +144,68 @@ pub fn station_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/stations", get(list_stations).post(create_station))
         .route("/stations/listeners", get(get_all_listener_counts))  // Must be before :id route
+        .route("/stations/config-schema", get(station_config_schema))  // Must be before :id route
+        .route("/stations/managed", get(list_managed_stations))  // Must be before :id route
+        .route(
+            "/stations/from-playlist",  // Must be before :id route
+            get(list_navidrome_playlists).post(import_playlist_station),
+        )
+        .route("/stations/import", post(import_station))  // Must be before :id route
         .route("/stations/:id", get(get_station).patch(update_station).delete(delete_station))
         .route("/stations/:id/start", post(start_station))
         .route("/stations/:id/stop", post(stop_station))
         .route("/stations/:id/skip", post(skip_track))
         .route("/stations/:id/nowplaying", get(now_playing))
+        .route("/stations/:id/nowplaying/stream", get(now_playing_stream))
         .route("/stations/:id/tracks", get(get_station_tracks))
         .route("/stations/:id/playlist", post(create_navidrome_playlist))
+        .route("/stations/:id/collaborators", get(list_collaborators).post(add_collaborator))
+        .route("/stations/:id/collaborators/:user_id", delete(remove_collaborator))
+        .route("/stations/:id/schedule", get(get_schedule).post(create_schedule_block))
+        .route("/stations/:id/schedule/:block_id", put(update_schedule_block).delete(delete_schedule_block))
+        .route("/stations/:id/requests", get(list_track_requests).post(create_track_request))
+        .route("/requests/:id/vote", post(vote_track_request).delete(unvote_track_request))
         .route("/stations/:id/listener/heartbeat", post(listener_heartbeat))
         .route("/stations/:id/listener/leave", post(listener_leave))
+        .route("/stations/:id/analytics", get(get_station_analytics))
+        .route("/stations/:id/archives", get(list_archives))
+        .route("/stations/:id/archives/:name", get(download_archive))
+        .route("/stations/:id/archives/:name/cues", get(get_archive_cues))
         // HLS Streaming endpoints
+        .route("/stations/:id/stream/master.m3u8", get(get_hls_master_playlist))
         .route("/stations/:id/stream/playlist.m3u8", get(get_hls_playlist))
         .route("/stations/:id/stream/segment/:seq", get(get_hls_segment))
+        .route("/stations/:id/stream/variant/:bitrate/playlist.m3u8", get(get_hls_variant_playlist))
+        .route("/stations/:id/stream/variant/:bitrate/segment/:seq", get(get_hls_variant_segment))
         .route("/stations/:id/stream/visualization", get(visualization_sse))
         .route("/ai/capabilities", get(ai_capabilities))
         .route("/ai/analyze-description", post(analyze_description))
         .route("/ai/curate", post(curate_tracks_sse))
 }
 
+/// GET /api/v1/stations/config-schema
+/// JSON Schema of a station's `config`, for rendering the settings form
+async fn station_config_schema() -> Json<serde_json::Value> {
+    Json(StationConfig::json_schema())
+}
+
+/// Validate a station config from a create/update request
+fn parse_station_config(config: Option<serde_json::Value>) -> Result<Option<StationConfig>> {
+    config
+        .map(|value| {
+            StationConfig::from_json(value)
+                .map_err(|errors| AppError::Validation(format!("Invalid station config: {}", errors.join("; "))))
+        })
+        .transpose()
+}
+
 async fn list_stations(State(state): State<Arc<AppState>>) -> Result<Json<Vec<Station>>> {
-    let stations = sqlx::query_as::<_, Station>("SELECT * FROM stations ORDER BY created_at DESC")
-        .fetch_all(&state.db)
-        .await?;
+    // Daily Mix stations are personal and only listed through /me/daily-mix
+    let stations = sqlx::query_as::<_, Station>(
+        "SELECT * FROM stations WHERE daily_mix_user_id IS NULL ORDER BY created_at DESC",
+    )
+    .fetch_all(&state.db)
+    .await?;
 
     Ok(Json(stations))
 }
@@ -120,7 +225,7 @@ async fn get_station(
 
 async fn create_station(
     State(state): State<Arc<AppState>>,
-    RequireAdmin(claims): RequireAdmin,
+    RequireCurator(claims): RequireCurator,
     Json(req): Json<CreateStationRequest>,
 ) -> Result<Json<Station>> {
     req.validate()
@@ -140,7 +245,7 @@ async fn create_station(
         return Err(AppError::Validation("Station path already exists".to_string()));
     }
 
-    let config = req.config.unwrap_or_default();
+    let config = parse_station_config(req.config)?.unwrap_or_default();
     let track_ids = req.track_ids.unwrap_or_default();
 
     let station = sqlx::query_as::<_, Station>(
@@ -161,15 +266,243 @@ async fn create_station(
     .fetch_one(&state.db)
     .await?;
 
+    publish_to_navidrome(&state, &station);
+
     Ok(Json(station))
 }
 
+/// GET /api/v1/stations/from-playlist
+/// Playlists that stations can be imported from
+async fn list_navidrome_playlists(
+    State(state): State<Arc<AppState>>,
+    RequireCurator(claims): RequireCurator,
+) -> Result<Json<Vec<NavidromePlaylist>>> {
+    let navidrome = state.navidrome_accounts.client_or_shared(claims.sub).await?;
+    Ok(Json(navidrome.get_playlists().await?))
+}
+
+/// POST /api/v1/stations/from-playlist
+/// Create a station playing a Navidrome playlist, optionally following later changes to it
+async fn import_playlist_station(
+    State(state): State<Arc<AppState>>,
+    RequireCurator(claims): RequireCurator,
+    Json(req): Json<ImportPlaylistRequest>,
+) -> Result<Json<Station>> {
+    req.validate()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM stations WHERE path = $1)")
+        .bind(&req.path)
+        .fetch_one(&state.db)
+        .await?;
+
+    if exists {
+        return Err(AppError::Validation("Station path already exists".to_string()));
+    }
+
+    // The caller's own Navidrome account sees their private playlists too
+    let navidrome = state.navidrome_accounts.client_or_shared(claims.sub).await?;
+    let playlist = navidrome
+        .get_playlists()
+        .await?
+        .into_iter()
+        .find(|p| p.id == req.playlist_id)
+        .ok_or_else(|| AppError::NotFound("Playlist not found".to_string()))?;
+
+    let track_ids = navidrome.get_playlist_track_ids(&playlist.id).await?;
+    if track_ids.is_empty() {
+        return Err(AppError::Validation("Playlist is empty".to_string()));
+    }
+
+    let genres = match req.genres.filter(|g| !g.is_empty()) {
+        Some(genres) => genres,
+        None => top_genres(&state.db, &track_ids).await?,
+    };
+
+    let name = req.name.unwrap_or_else(|| playlist.name.clone());
+    let description = req
+        .description
+        .or_else(|| playlist.comment.clone().filter(|c| c.trim().len() >= 10))
+        .unwrap_or_else(|| format!("Tracks from the Navidrome playlist \"{}\"", playlist.name));
+    let config = parse_station_config(req.config)?.unwrap_or_default();
+
+    tracing::info!(
+        "Importing playlist '{}' ({} tracks) as station '{}'",
+        playlist.name,
+        track_ids.len(),
+        req.path
+    );
+
+    let station = sqlx::query_as::<_, Station>(
+        r#"
+        INSERT INTO stations (path, name, description, genres, mood_tags, created_by, config, track_ids,
+                              source_playlist_id, source_playlist_sync, source_playlist_synced_at)
+        VALUES ($1, $2, $3, $4, '[]'::jsonb, $5, $6, $7, $8, $9, $10)
+        RETURNING *
+        "#,
+    )
+    .bind(&req.path)
+    .bind(&name)
+    .bind(&description)
+    .bind(serde_json::to_value(&genres).unwrap())
+    .bind(claims.sub)
+    .bind(serde_json::to_value(&config).unwrap())
+    .bind(serde_json::to_value(&track_ids).unwrap())
+    .bind(&playlist.id)
+    .bind(req.sync)
+    .bind(playlist.changed.unwrap_or_else(chrono::Utc::now))
+    .fetch_one(&state.db)
+    .await?;
+
+    publish_to_navidrome(&state, &station);
+
+    Ok(Json(station))
+}
+
+/// The most common genres of a set of tracks, or the wildcard if they have none
+async fn top_genres(db: &PgPool, track_ids: &[String]) -> Result<Vec<String>> {
+    let genres: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT genre
+        FROM library_index, jsonb_array_elements_text(genres) AS genre
+        WHERE id = ANY($1)
+        GROUP BY genre
+        ORDER BY COUNT(*) DESC
+        LIMIT 3
+        "#,
+    )
+    .bind(track_ids)
+    .fetch_all(db)
+    .await?;
+
+    if genres.is_empty() {
+        Ok(vec!["*".to_string()])
+    } else {
+        Ok(genres)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct StationImport {
+    station: Station,
+    /// Playlist entries
+    entries: usize,
+    /// Entries matched only by title/artist similarity
+    fuzzy_matched: usize,
+    unresolved: Vec<UnresolvedEntry>,
+}
+
+/// POST /api/v1/stations/import
+/// Create a station whose curated pool is a Navidrome playlist or an M3U file, matched
+/// against the library; entries that match nothing are listed in the response
+async fn import_station(
+    State(state): State<Arc<AppState>>,
+    RequireCurator(claims): RequireCurator,
+    Json(req): Json<StationImportRequest>,
+) -> Result<Json<StationImport>> {
+    req.validate()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM stations WHERE path = $1)")
+        .bind(&req.path)
+        .fetch_one(&state.db)
+        .await?;
+
+    if exists {
+        return Err(AppError::Validation("Station path already exists".to_string()));
+    }
+
+    let (playlist_name, entries): (Option<String>, Vec<ImportEntry>) = match (&req.playlist_id, &req.m3u) {
+        (Some(playlist_id), None) => {
+            let navidrome = state.navidrome_accounts.client_or_shared(claims.sub).await?;
+            let playlist = navidrome
+                .get_playlists()
+                .await?
+                .into_iter()
+                .find(|p| &p.id == playlist_id)
+                .ok_or_else(|| AppError::NotFound("Playlist not found".to_string()))?;
+            let entries = navidrome.get_playlist_entries(&playlist.id).await?;
+            (Some(playlist.name), entries.into_iter().map(ImportEntry::from).collect())
+        }
+        (None, Some(m3u)) => {
+            let playlist = playlist_import::parse_m3u(m3u);
+            (playlist.name, playlist.entries)
+        }
+        _ => {
+            return Err(AppError::Validation(
+                "Provide either playlist_id or m3u".to_string(),
+            ))
+        }
+    };
+    if entries.is_empty() {
+        return Err(AppError::Validation("Playlist is empty".to_string()));
+    }
+    let name = req
+        .name
+        .or(playlist_name)
+        .ok_or_else(|| AppError::Validation("name is required for this playlist".to_string()))?;
+
+    let resolution = playlist_import::resolve(&state.db, &entries).await?;
+    if resolution.track_ids.is_empty() {
+        return Err(AppError::Validation(format!(
+            "None of the playlist's {} entries are in the library",
+            entries.len()
+        )));
+    }
+
+    let genres = match req.genres.filter(|g| !g.is_empty()) {
+        Some(genres) => genres,
+        None => top_genres(&state.db, &resolution.track_ids).await?,
+    };
+    let description = req
+        .description
+        .unwrap_or_else(|| format!("Tracks imported from the playlist \"{}\"", name));
+    let config = parse_station_config(req.config)?.unwrap_or_default();
+
+    tracing::info!(
+        "Importing playlist '{}' as station '{}': {} of {} entries matched ({} fuzzy)",
+        name,
+        req.path,
+        entries.len() - resolution.unresolved.len(),
+        entries.len(),
+        resolution.fuzzy_matched
+    );
+
+    let station = sqlx::query_as::<_, Station>(
+        r#"
+        INSERT INTO stations (path, name, description, genres, mood_tags, created_by, config, track_ids)
+        VALUES ($1, $2, $3, $4, '[]'::jsonb, $5, $6, $7)
+        RETURNING *
+        "#,
+    )
+    .bind(&req.path)
+    .bind(&name)
+    .bind(&description)
+    .bind(serde_json::to_value(&genres)?)
+    .bind(claims.sub)
+    .bind(serde_json::to_value(&config)?)
+    .bind(serde_json::to_value(&resolution.track_ids)?)
+    .fetch_one(&state.db)
+    .await?;
+
+    publish_to_navidrome(&state, &station);
+
+    Ok(Json(StationImport {
+        station,
+        entries: entries.len(),
+        fuzzy_matched: resolution.fuzzy_matched,
+        unresolved: resolution.unresolved,
+    }))
+}
+
 async fn update_station(
     State(state): State<Arc<AppState>>,
-    RequireAdmin(_): RequireAdmin,
+    RequireAuth(claims): RequireAuth,
     Path(id): Path<Uuid>,
     Json(req): Json<UpdateStationRequest>,
 ) -> Result<Json<Station>> {
+    fetch_managed_station(&state, &claims, id).await?;
+
     // Build dynamic update query
     let mut query = String::from("UPDATE stations SET ");
     let mut updates = Vec::new();
@@ -217,7 +550,7 @@ async fn update_station(
     if let Some(mood_tags) = req.mood_tags {
         query_builder = query_builder.bind(serde_json::to_value(mood_tags).unwrap());
     }
-    if let Some(config) = req.config {
+    if let Some(config) = parse_station_config(req.config)? {
         query_builder = query_builder.bind(serde_json::to_value(config).unwrap());
     }
 
@@ -227,62 +560,411 @@ async fn update_station(
         .await?
         .ok_or_else(|| AppError::NotFound("Station not found".to_string()))?;
 
+    publish_to_navidrome(&state, &station);
+
     Ok(Json(station))
 }
 
 async fn delete_station(
     State(state): State<Arc<AppState>>,
-    RequireAdmin(_): RequireAdmin,
+    RequireAuth(claims): RequireAuth,
     Path(id): Path<Uuid>,
 ) -> Result<Json<()>> {
+    fetch_owned_station(&state, &claims, id).await?;
+
     // Stop station if active
     let _ = state.station_manager.stop_station(id).await;
 
+    let navidrome_radio_id: Option<String> =
+        sqlx::query_scalar("SELECT navidrome_radio_id FROM stations WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&state.db)
+            .await?
+            .flatten();
+
     sqlx::query("DELETE FROM stations WHERE id = $1")
         .bind(id)
         .execute(&state.db)
         .await?;
 
+    // Remove the station from Navidrome's radio list in the background
+    if let (Some(publisher), Some(radio_id)) = (state.radio_publisher.clone(), navidrome_radio_id) {
+        tokio::spawn(async move {
+            if let Err(e) = publisher.unpublish(&radio_id).await {
+                tracing::warn!("Failed to remove station from Navidrome radio list: {}", e);
+            }
+        });
+    }
+
     Ok(Json(()))
 }
 
+/// How much control a user has over a station
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum StationAccess {
+    None,
+    /// Granted moderator on the station, or a moderator globally: may skip
+    Moderator,
+    /// Granted curator on the station: may also edit, start, stop, schedule and export
+    Curator,
+    /// Creator or admin: may also delete and change grants
+    Owner,
+}
+
+async fn station_access(state: &AppState, claims: &Claims, station: &Station) -> Result<StationAccess> {
+    if claims.role == UserRole::Admin || station.created_by == claims.sub {
+        return Ok(StationAccess::Owner);
+    }
+
+    let granted: Option<StationRole> =
+        sqlx::query_scalar("SELECT role FROM station_permissions WHERE station_id = $1 AND user_id = $2")
+            .bind(station.id)
+            .bind(claims.sub)
+            .fetch_optional(&state.db)
+            .await?;
+    let granted = match granted {
+        Some(StationRole::Curator) => StationAccess::Curator,
+        Some(StationRole::Moderator) => StationAccess::Moderator,
+        None => StationAccess::None,
+    };
+
+    // Global moderators (and curators) skip on every station
+    let global = if claims.role.includes(&UserRole::Moderator) {
+        StationAccess::Moderator
+    } else {
+        StationAccess::None
+    };
+
+    Ok(granted.max(global))
+}
+
+/// Load a station, failing unless the caller has at least the given access
+async fn fetch_station_with_access(
+    state: &AppState,
+    claims: &Claims,
+    id: Uuid,
+    required: StationAccess,
+) -> Result<Station> {
+    let station = sqlx::query_as::<_, Station>("SELECT * FROM stations WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Station not found".to_string()))?;
+
+    if station_access(state, claims, &station).await? < required {
+        return Err(AppError::Forbidden);
+    }
+
+    Ok(station)
+}
+
+/// Load a station the caller created, is a curator of, or (as admin) can manage anyway
+async fn fetch_managed_station(state: &AppState, claims: &Claims, id: Uuid) -> Result<Station> {
+    fetch_station_with_access(state, claims, id, StationAccess::Curator).await
+}
+
+/// Load a station the caller created (or any station, for admins)
+async fn fetch_owned_station(state: &AppState, claims: &Claims, id: Uuid) -> Result<Station> {
+    fetch_station_with_access(state, claims, id, StationAccess::Owner).await
+}
+
+/// GET /api/v1/stations/managed
+/// Stations the caller can manage (all stations for admins), including ones they were
+/// made curator of whatever their global role
+async fn list_managed_stations(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+) -> Result<Json<Vec<Station>>> {
+    let stations = sqlx::query_as::<_, Station>(
+        r#"
+        SELECT s.* FROM stations s
+        WHERE $2
+           OR s.created_by = $1
+           OR EXISTS (
+               SELECT 1 FROM station_permissions sp
+               WHERE sp.station_id = s.id AND sp.user_id = $1 AND sp.role = 'curator'
+           )
+        ORDER BY s.created_at DESC
+        "#,
+    )
+    .bind(claims.sub)
+    .bind(claims.role == UserRole::Admin)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(stations))
+}
+
+/// GET /api/v1/stations/:id/collaborators
+/// Users granted a role on the station
+async fn list_collaborators(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<StationCollaborator>>> {
+    fetch_managed_station(&state, &claims, id).await?;
+
+    let collaborators = sqlx::query_as::<_, StationCollaborator>(
+        r#"
+        SELECT sp.station_id, sp.user_id, u.username, sp.role, sp.granted_by, sp.created_at
+        FROM station_permissions sp
+        JOIN users u ON u.id = sp.user_id
+        WHERE sp.station_id = $1
+        ORDER BY sp.created_at
+        "#,
+    )
+    .bind(id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(collaborators))
+}
+
+/// POST /api/v1/stations/:id/collaborators
+/// Grant a user the curator or moderator role on the station, or change the role they
+/// have (creator or admin only)
+async fn add_collaborator(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path(id): Path<Uuid>,
+    Json(req): Json<AddCollaboratorRequest>,
+) -> Result<Json<StationCollaborator>> {
+    req.validate()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    let station = fetch_owned_station(&state, &claims, id).await?;
+
+    let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = $1")
+        .bind(&req.username)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    if user.id == station.created_by {
+        return Err(AppError::Validation("User already owns this station".to_string()));
+    }
+
+    let collaborator = sqlx::query_as::<_, StationCollaborator>(
+        r#"
+        INSERT INTO station_permissions (station_id, user_id, role, granted_by)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (station_id, user_id) DO UPDATE
+        SET role = EXCLUDED.role, granted_by = EXCLUDED.granted_by
+        RETURNING station_id, user_id, $5::text AS username, role, granted_by, created_at
+        "#,
+    )
+    .bind(id)
+    .bind(user.id)
+    .bind(req.role)
+    .bind(claims.sub)
+    .bind(&user.username)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(Json(collaborator))
+}
+
+/// DELETE /api/v1/stations/:id/collaborators/:user_id
+/// Take away a user's role on the station (creator or admin only)
+async fn remove_collaborator(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<()>> {
+    fetch_owned_station(&state, &claims, id).await?;
+
+    let result = sqlx::query("DELETE FROM station_permissions WHERE station_id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user_id)
+        .execute(&state.db)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Collaborator not found".to_string()));
+    }
+
+    Ok(Json(()))
+}
+
+#[derive(Debug, Serialize)]
+struct StationSchedule {
+    timezone: String,
+    /// The block on air right now
+    on_air: Option<Uuid>,
+    blocks: Vec<ScheduleBlock>,
+}
+
+/// GET /api/v1/stations/:id/schedule
+async fn get_schedule(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<StationSchedule>> {
+    let station = sqlx::query_as::<_, Station>("SELECT * FROM stations WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Station not found".to_string()))?;
+
+    let on_air = schedule::active_block(&state.db, &station, chrono::Utc::now()).await?;
+    Ok(Json(StationSchedule {
+        timezone: station.config.timezone,
+        on_air: on_air.map(|block| block.id),
+        blocks: schedule::list(&state.db, id).await?,
+    }))
+}
+
+/// POST /api/v1/stations/:id/schedule
+/// Add a program block; it may not overlap the station's other blocks
+async fn create_schedule_block(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path(id): Path<Uuid>,
+    Json(req): Json<ScheduleBlockRequest>,
+) -> Result<Json<ScheduleBlock>> {
+    fetch_managed_station(&state, &claims, id).await?;
+    require_auto_curation(&state, &req)?;
+    Ok(Json(schedule::create(&state.db, id, &req).await?))
+}
+
+/// PUT /api/v1/stations/:id/schedule/:block_id
+async fn update_schedule_block(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path((id, block_id)): Path<(Uuid, Uuid)>,
+    Json(req): Json<ScheduleBlockRequest>,
+) -> Result<Json<ScheduleBlock>> {
+    fetch_managed_station(&state, &claims, id).await?;
+    require_auto_curation(&state, &req)?;
+    Ok(Json(schedule::update(&state.db, id, block_id, &req).await?))
+}
+
+/// Blocks with a curation prompt need the hybrid curator to fill their pool
+fn require_auto_curation(state: &AppState, req: &ScheduleBlockRequest) -> Result<()> {
+    if req.prompt().is_some() && state.hybrid_curator.is_none() {
+        return Err(AppError::NotConfigured(
+            "Auto-curated programs need hybrid curation (an Anthropic API key and audio embeddings)".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// DELETE /api/v1/stations/:id/schedule/:block_id
+async fn delete_schedule_block(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path((id, block_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<()>> {
+    fetch_managed_station(&state, &claims, id).await?;
+    if !schedule::delete(&state.db, id, block_id).await? {
+        return Err(AppError::NotFound("Schedule block not found".to_string()));
+    }
+    Ok(Json(()))
+}
+
+/// GET /api/v1/stations/:id/requests
+/// Pending track requests, most votes first
+async fn list_track_requests(
+    State(state): State<Arc<AppState>>,
+    OptionalAuth(claims): OptionalAuth,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<TrackRequest>>> {
+    let viewer = claims.map(|claims| claims.sub);
+    Ok(Json(track_requests::list(&state.db, id, viewer).await?))
+}
+
+/// POST /api/v1/stations/:id/requests
+/// Request a track (or vote for it if it is already requested)
+async fn create_track_request(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path(id): Path<Uuid>,
+    Json(req): Json<CreateTrackRequest>,
+) -> Result<Json<TrackRequest>> {
+    req.validate()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    let station = sqlx::query_as::<_, Station>("SELECT * FROM stations WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Station not found".to_string()))?;
+
+    let request = track_requests::create(
+        &state.db,
+        &station,
+        claims.sub,
+        &req.track_id,
+        state.requests_per_hour,
+    )
+    .await?;
+    Ok(Json(request))
+}
+
+/// POST /api/v1/requests/:id/vote
+async fn vote_track_request(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path(id): Path<Uuid>,
+) -> Result<Json<TrackRequest>> {
+    Ok(Json(track_requests::vote(&state.db, id, claims.sub).await?))
+}
+
+/// DELETE /api/v1/requests/:id/vote
+async fn unvote_track_request(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path(id): Path<Uuid>,
+) -> Result<Json<TrackRequest>> {
+    Ok(Json(track_requests::unvote(&state.db, id, claims.sub).await?))
+}
+
+/// Create or refresh the station's Navidrome radio entry in the background (if enabled)
+fn publish_to_navidrome(state: &AppState, station: &Station) {
+    if let Some(publisher) = state.radio_publisher.clone() {
+        let station = station.clone();
+        tokio::spawn(async move {
+            if let Err(e) = publisher.publish(&station).await {
+                tracing::warn!("Failed to publish station {} to Navidrome: {}", station.path, e);
+            }
+        });
+    }
+}
+
 async fn start_station(
     State(state): State<Arc<AppState>>,
-    RequireAdmin(_): RequireAdmin,
+    RequireAuth(claims): RequireAuth,
     Path(id): Path<Uuid>,
 ) -> Result<Json<()>> {
+    fetch_managed_station(&state, &claims, id).await?;
     state.station_manager.start_station(id).await?;
     Ok(Json(()))
 }
 
 async fn stop_station(
     State(state): State<Arc<AppState>>,
-    RequireAdmin(_): RequireAdmin,
+    RequireAuth(claims): RequireAuth,
     Path(id): Path<Uuid>,
 ) -> Result<Json<()>> {
+    fetch_managed_station(&state, &claims, id).await?;
     state.station_manager.stop_station(id).await?;
     Ok(Json(()))
 }
 
 async fn skip_track(
     State(state): State<Arc<AppState>>,
-    RequireAdmin(_): RequireAdmin,
+    RequireAuth(claims): RequireAuth,
     Path(id): Path<Uuid>,
 ) -> Result<Json<()>> {
-    // Check if there's an active HLS broadcaster - if so, skip in the pipeline
-    {
-        let broadcasters = state.station_broadcasters.read().await;
-        if let Some(broadcaster) = broadcasters.get(&id) {
-            if broadcaster.is_running() {
-                broadcaster.skip().await?;
-                tracing::info!("Skipped track in HLS pipeline for station {}", id);
-                return Ok(Json(()));
-            }
-        }
-    }
+    fetch_station_with_access(&state, &claims, id, StationAccess::Moderator).await?;
 
-    // Fall back to station manager skip
+    // Skips in the HLS pipeline if the station is broadcasting here
     state.station_manager.skip_track(id).await?;
+
+    // Other instances skip their own broadcasters
+    state
+        .station_events
+        .publish(StationEvent::Skipped { station_id: id })
+        .await;
     Ok(Json(()))
 }
 
@@ -290,6 +972,12 @@ async fn now_playing(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
 ) -> Result<Json<NowPlaying>> {
+    Ok(Json(current_now_playing(&state, id).await?))
+}
+
+/// What listeners of a station hear now: the HLS broadcaster's track when one is
+/// running, otherwise the station manager's
+async fn current_now_playing(state: &AppState, id: Uuid) -> Result<NowPlaying> {
     // Check if there's an active HLS broadcaster - if so, use its current track
     {
         let broadcasters = state.station_broadcasters.read().await;
@@ -327,58 +1015,60 @@ async fn now_playing(
                 };
 
                 if let Some(track_state) = track_state {
-                    // Fetch full track info from the database
-                    let row = sqlx::query(
-                        r#"
-                        SELECT id, title, artist, album, duration
-                        FROM library_index
-                        WHERE id = $1
-                        "#,
-                    )
-                    .bind(&track_state.track_id)
-                    .fetch_optional(&state.db)
-                    .await?;
-
-                    if let Some(row) = row {
-                        use sqlx::Row;
-                        let track_id: String = row.get("id");
-                        let info = crate::models::TrackInfo {
-                            id: track_id.clone(),
-                            title: row.get("title"),
-                            artist: row.get("artist"),
-                            album: row.get("album"),
-                            duration: row.get("duration"),
-                            album_art: Some(format!("/api/v1/navidrome/cover/{}", track_id)),
-                        };
-
-                        // Get listener count from station manager
-                        let listeners = state
-                            .station_manager
-                            .get_now_playing(id)
-                            .await
-                            .map(|np| np.listeners)
-                            .unwrap_or(0);
-
-                        // Account for HLS buffering latency (~6 seconds for 3 segments at 2s each)
-                        // The client is behind the server, so from the client's perspective
-                        // less of the track has played
-                        const HLS_LATENCY_SECS: i64 = 6;
-                        let client_position_secs = (track_state.position_secs as i64 - HLS_LATENCY_SECS).max(0);
-
-                        return Ok(Json(NowPlaying {
-                            track: info,
-                            started_at: chrono::Utc::now() - chrono::Duration::seconds(client_position_secs),
-                            listeners,
-                        }));
-                    }
+                    // Account for HLS buffering latency: the client is behind the
+                    // server, so from the client's perspective less of the track has played
+                    let client_position_secs =
+                        (track_state.position_secs - HLS_LATENCY_SECS as f32).max(0.0);
+
+                    return Ok(state
+                        .station_manager
+                        .pipeline_now_playing(id, &track_state, client_position_secs)
+                        .await);
                 }
             }
         }
     }
 
     // Fall back to station manager's now playing
-    let np = state.station_manager.get_now_playing(id).await?;
-    Ok(Json(np))
+    state.station_manager.get_now_playing(id).await
+}
+
+/// Server-sent events with the station's now playing: the current track on connect,
+/// then every track change as listeners hear it
+async fn now_playing_stream(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
+    sqlx::query("SELECT 1 FROM stations WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Station not found".to_string()))?;
+
+    // Subscribe before reading the current track so a change in between isn't lost
+    let mut updates = state.station_manager.subscribe_now_playing();
+    let current = current_now_playing(&state, id).await.ok();
+
+    let stream = async_stream::stream! {
+        if let Some(now_playing) = current {
+            if let Ok(json) = serde_json::to_string(&now_playing) {
+                yield Ok::<_, Infallible>(Event::default().data(json));
+            }
+        }
+        loop {
+            match updates.recv().await {
+                Ok((station_id, now_playing)) if station_id == id => {
+                    if let Ok(json) = serde_json::to_string(&now_playing) {
+                        yield Ok(Event::default().data(json));
+                    }
+                }
+                Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    };
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
 }
 
 #[derive(Debug, Deserialize)]
@@ -393,6 +1083,7 @@ struct HeartbeatResponse {
 
 async fn listener_heartbeat(
     State(state): State<Arc<AppState>>,
+    OptionalAuth(claims): OptionalAuth,
     Path(id): Path<Uuid>,
     Json(req): Json<HeartbeatRequest>,
 ) -> Result<Json<HeartbeatResponse>> {
@@ -400,9 +1091,42 @@ async fn listener_heartbeat(
         .station_manager
         .listener_heartbeat(id, req.session_id)
         .await?;
+
+    // Logged-in listeners build up a listening history from their heartbeats
+    if let Some(claims) = claims {
+        if let Some(track_id) = current_track_id(&state, id).await {
+            if let Err(e) = listening_history::record_listen(&state.db, claims.sub, id, &track_id).await {
+                tracing::warn!("Failed to record listening history for user {}: {}", claims.sub, e);
+            }
+        }
+    }
+
     Ok(Json(HeartbeatResponse { listeners }))
 }
 
+/// Track currently playing on a station, without waiting for a cold-starting pipeline
+async fn current_track_id(state: &AppState, station_id: Uuid) -> Option<String> {
+    {
+        let broadcasters = state.station_broadcasters.read().await;
+        if let Some(broadcaster) = broadcasters.get(&station_id) {
+            if broadcaster.is_running() {
+                return broadcaster
+                    .current_track()
+                    .await
+                    .map(|track| track.track_id)
+                    .filter(|id| !id.is_empty());
+            }
+        }
+    }
+
+    state
+        .station_manager
+        .get_now_playing(station_id)
+        .await
+        .ok()
+        .map(|np| np.track.id)
+}
+
 #[derive(Debug, Deserialize)]
 struct LeaveRequest {
     session_id: String,
@@ -432,6 +1156,101 @@ async fn get_all_listener_counts(
     Ok(Json(ListenerCountsResponse { counts }))
 }
 
+#[derive(Debug, Deserialize)]
+struct AnalyticsQuery {
+    /// 24h, 7d (default), 30d or 90d
+    #[serde(default)]
+    window: AnalyticsWindow,
+}
+
+/// GET /api/v1/stations/:id/analytics
+/// Plays, skips, listeners and retention of a station over a time window (curator,
+/// own or shared stations)
+async fn get_station_analytics(
+    State(state): State<Arc<AppState>>,
+    RequireAuth(claims): RequireAuth,
+    Path(id): Path<Uuid>,
+    Query(query): Query<AnalyticsQuery>,
+) -> Result<Json<StationAnalytics>> {
+    fetch_managed_station(&state, &claims, id).await?;
+    Ok(Json(station_analytics::station_analytics(&state.db, id, query.window).await?))
+}
+
+/// Size of the chunks archive downloads are streamed in
+const ARCHIVE_CHUNK_BYTES: usize = 64 * 1024;
+
+fn archive(state: &AppState) -> Result<&BroadcastArchive> {
+    state
+        .archive
+        .as_deref()
+        .ok_or_else(|| AppError::NotConfigured("Broadcast archiving is not enabled (set ARCHIVE_DIR)".to_string()))
+}
+
+/// GET /api/v1/stations/:id/archives
+/// Recorded hours of a station's broadcast, newest first
+async fn list_archives(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<ArchiveInfo>>> {
+    Ok(Json(archive(&state)?.list(id).await?))
+}
+
+/// GET /api/v1/stations/:id/archives/:name/cues
+/// Tracks of a recorded hour with their offsets into the file
+async fn get_archive_cues(
+    State(state): State<Arc<AppState>>,
+    Path((id, name)): Path<(Uuid, String)>,
+) -> Result<Json<CueSheet>> {
+    Ok(Json(archive(&state)?.cue_sheet(id, &name).await?))
+}
+
+/// GET /api/v1/stations/:id/archives/:name
+/// Audio of a recorded hour, as a download
+async fn download_archive(
+    State(state): State<Arc<AppState>>,
+    Path((id, name)): Path<(Uuid, String)>,
+) -> Result<Response> {
+    let (path, codec) = archive(&state)?.audio_file(id, &name).await?;
+    let mut file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|e| AppError::Storage(format!("Failed to open archive {:?}: {}", path, e)))?;
+    let size = file
+        .metadata()
+        .await
+        .map_err(|e| AppError::Storage(format!("Failed to read archive {:?}: {}", path, e)))?
+        .len();
+
+    // The current hour keeps growing, so only the bytes there now are sent
+    let body = async_stream::stream! {
+        use tokio::io::AsyncReadExt;
+        let mut remaining = size;
+        let mut buffer = vec![0u8; ARCHIVE_CHUNK_BYTES];
+        while remaining > 0 {
+            let want = buffer.len().min(remaining as usize);
+            match file.read(&mut buffer[..want]).await {
+                Ok(0) => break,
+                Ok(read) => {
+                    remaining -= read as u64;
+                    yield Ok::<_, std::io::Error>(bytes::Bytes::copy_from_slice(&buffer[..read]));
+                }
+                Err(e) => {
+                    yield Err(e);
+                    break;
+                }
+            }
+        }
+    };
+
+    let file_name = format!("{}-{}.{}", id, name, broadcast_archive::file_extension(codec));
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, codec.content_type())
+        .header(header::CONTENT_LENGTH, size)
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name))
+        .body(Body::from_stream(body))
+        .map_err(|e| AppError::InternalMessage(format!("Failed to build response: {}", e)))
+}
+
 #[derive(Debug, Deserialize)]
 struct GetTracksQuery {
     limit: Option<i64>,
@@ -557,28 +1376,37 @@ async fn get_station_tracks(
 #[derive(Debug, Deserialize)]
 struct CreatePlaylistRequest {
     name: Option<String>,
+    /// Add tracks missing from the station's existing playlist instead of replacing it
+    #[serde(default)]
+    append: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum PlaylistExport {
+    Created,
+    Replaced,
+    Appended,
 }
 
 #[derive(Debug, Serialize)]
 struct CreatePlaylistResponse {
     playlist_id: String,
     name: String,
+    /// Tracks written to the playlist (only the new ones when appending)
     track_count: usize,
+    mode: PlaylistExport,
 }
 
-/// Create a Navidrome playlist from a station's tracks
+/// Export a station's tracks to Navidrome. The first export creates a playlist; later
+/// exports replace its entries (or append to it) as long as it still exists.
 async fn create_navidrome_playlist(
     State(state): State<Arc<AppState>>,
-    RequireAdmin(_): RequireAdmin,
+    RequireAuth(claims): RequireAuth,
     Path(id): Path<Uuid>,
     Json(req): Json<CreatePlaylistRequest>,
 ) -> Result<Json<CreatePlaylistResponse>> {
-    // Get the station
-    let station = sqlx::query_as::<_, Station>("SELECT * FROM stations WHERE id = $1")
-        .bind(id)
-        .fetch_optional(&state.db)
-        .await?
-        .ok_or_else(|| AppError::NotFound("Station not found".to_string()))?;
+    let station = fetch_managed_station(&state, &claims, id).await?;
 
     // Get track IDs - prefer curated tracks, fall back to playlist history
     let track_ids: Vec<String> = if !station.track_ids.is_empty() {
@@ -599,21 +1427,74 @@ async fn create_navidrome_playlist(
         return Err(AppError::Validation("Station has no tracks to export".to_string()));
     }
 
-    // Generate playlist name if not provided
-    let playlist_name = req.name.unwrap_or_else(|| {
-        format!("{} - Radio", station.name)
-    });
+    // Export into the caller's own Navidrome account if they connected one
+    let navidrome = state.navidrome_accounts.client_or_shared(claims.sub).await?;
+
+    // The previous export may have been deleted in Navidrome since (or belong to
+    // another account)
+    let existing = match &station.navidrome_playlist_id {
+        Some(playlist_id) => navidrome
+            .get_playlists()
+            .await?
+            .into_iter()
+            .find(|p| p.id == *playlist_id),
+        None => None,
+    };
 
-    // Create the playlist in Navidrome
-    let playlist_id = state
-        .navidrome_client
-        .create_playlist(&playlist_name, &track_ids)
-        .await?;
+    let Some(existing) = existing else {
+        // Generate playlist name if not provided
+        let playlist_name = req.name.unwrap_or_else(|| {
+            format!("{} - Radio", station.name)
+        });
+
+        // Create the playlist in Navidrome
+        let playlist_id = navidrome
+            .create_playlist(&playlist_name, &track_ids)
+            .await?;
+
+        sqlx::query("UPDATE stations SET navidrome_playlist_id = $2 WHERE id = $1")
+            .bind(id)
+            .bind(&playlist_id)
+            .execute(&state.db)
+            .await?;
+
+        return Ok(Json(CreatePlaylistResponse {
+            playlist_id,
+            name: playlist_name,
+            track_count: track_ids.len(),
+            mode: PlaylistExport::Created,
+        }));
+    };
+
+    let (track_count, mode) = if req.append {
+        let present: HashSet<String> = navidrome
+            .get_playlist_track_ids(&existing.id)
+            .await?
+            .into_iter()
+            .collect();
+        let new_ids: Vec<String> = track_ids.into_iter().filter(|t| !present.contains(t)).collect();
+        if !new_ids.is_empty() {
+            navidrome.append_to_playlist(&existing.id, &new_ids).await?;
+        }
+        (new_ids.len(), PlaylistExport::Appended)
+    } else {
+        navidrome.replace_playlist(&existing.id, &track_ids).await?;
+        (track_ids.len(), PlaylistExport::Replaced)
+    };
+
+    let name = match req.name {
+        Some(name) if name != existing.name => {
+            navidrome.rename_playlist(&existing.id, &name).await?;
+            name
+        }
+        _ => existing.name,
+    };
 
     Ok(Json(CreatePlaylistResponse {
-        playlist_id,
-        name: playlist_name,
-        track_count: track_ids.len(),
+        playlist_id: existing.id,
+        name,
+        track_count,
+        mode,
     }))
 }
 
@@ -635,7 +1516,7 @@ async fn ai_capabilities(State(state): State<Arc<AppState>>) -> Result<Json<AiCa
 
 async fn analyze_description(
     State(state): State<Arc<AppState>>,
-    RequireAdmin(_): RequireAdmin,
+    RequireCurator(_): RequireCurator,
     Json(req): Json<AnalyzeDescriptionRequest>,
 ) -> Result<Json<AnalyzeDescriptionResponse>> {
     if req.description.trim().is_empty() {
@@ -666,6 +1547,8 @@ struct CurateRequest {
     query: String,
     #[serde(default = "default_limit")]
     limit: usize,
+    /// false leaves out tracks marked explicit
+    allow_explicit: Option<bool>,
 }
 
 fn default_limit() -> usize {
@@ -680,15 +1563,17 @@ struct CurationResult {
 /// SSE endpoint for AI curation with real-time progress updates
 async fn curate_tracks_sse(
     State(state): State<Arc<AppState>>,
-    RequireAdmin(_): RequireAdmin,
+    RequireCurator(claims): RequireCurator,
     Json(req): Json<CurateRequest>,
 ) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
     let ai_curator = state.ai_curator.clone().ok_or_else(|| {
-        AppError::ExternalApi("AI curation not available (no API key configured)".to_string())
+        AppError::NotConfigured("AI curation not available (no API key configured)".to_string())
     })?;
 
     let query = req.query.clone();
     let limit = req.limit;
+    let allow_explicit = req.allow_explicit.unwrap_or(true);
+    let webhooks = state.webhooks.clone();
 
     // Create a channel for progress updates
     let (progress_tx, progress_rx) = mpsc::channel::<CurationProgress>(32);
@@ -696,12 +1581,13 @@ async fn curate_tracks_sse(
     // Spawn the curation task
     tokio::spawn(async move {
         let result = ai_curator
-            .curate_tracks_with_progress(query, limit, progress_tx.clone())
+            .curate_tracks_with_progress(query.clone(), limit, Some(claims.sub), allow_explicit, progress_tx.clone())
             .await;
 
         // Send final result or error
         match result {
             Ok(track_ids) => {
+                webhooks.curation_completed("llm", &query, &track_ids, Some(claims.sub));
                 // The completed message is already sent by the curator
                 // But we can send the actual result as a separate event
                 let _ = progress_tx
@@ -735,176 +1621,67 @@ async fn curate_tracks_sse(
 // HLS Streaming Endpoints
 // ============================================================================
 
-/// Stored broadcaster with its pipeline for control
-#[allow(dead_code)]
-pub struct StationBroadcaster {
-    pub pipeline: Arc<RwLock<AudioPipeline>>,
-    pub broadcaster: Arc<AudioBroadcaster>,
-}
-
-/// Get or create the broadcaster for a station
-async fn get_or_create_broadcaster(
-    state: &Arc<AppState>,
-    station_id: Uuid,
-) -> Result<Arc<AudioBroadcaster>> {
-    // Check if broadcaster already exists and is running
-    {
-        let broadcasters = state.station_broadcasters.read().await;
-        if let Some(broadcaster) = broadcasters.get(&station_id) {
-            if broadcaster.is_running() {
-                return Ok(broadcaster.clone());
-            }
-        }
-    }
-
-    // Get station and its tracks
-    let station = sqlx::query_as::<_, Station>("SELECT * FROM stations WHERE id = $1")
-        .bind(station_id)
-        .fetch_optional(&state.db)
-        .await?
-        .ok_or_else(|| AppError::NotFound("Station not found".to_string()))?;
-
-    // Create new pipeline
-    let mut pipeline = AudioPipeline::new(
-        state.navidrome_client.clone(),
-        AudioPipelineConfig::default(),
-    );
-
-    // Queue tracks from the station's track list
-    if !station.track_ids.is_empty() {
-        // Get track info from library_index
-        let track_ids = &station.track_ids;
-        let rows = sqlx::query(
-            r#"
-            SELECT id, title, artist
-            FROM library_index
-            WHERE id = ANY($1)
-            "#,
-        )
-        .bind(track_ids)
-        .fetch_all(&state.db)
-        .await?;
-
-        // Build a map of track_id -> (title, artist)
-        let track_info: std::collections::HashMap<String, (String, String)> = rows
-            .iter()
-            .map(|row| {
-                use sqlx::Row;
-                let id: String = row.get("id");
-                let title: String = row.get("title");
-                let artist: String = row.get("artist");
-                (id, (title, artist))
-            })
-            .collect();
-
-        // Queue tracks in order
-        for track_id in track_ids {
-            if let Some((title, artist)) = track_info.get(track_id) {
-                let queued = QueuedTrack {
-                    track_id: track_id.clone(),
-                    title: title.clone(),
-                    artist: artist.clone(),
-                };
-                pipeline.queue_track(queued).await?;
-            }
-        }
-
-        tracing::info!(
-            "Queued {} tracks for station {} HLS stream",
-            track_ids.len(),
-            station.name
-        );
-    } else {
-        // No curated tracks - get from current now playing or playlist history
-        let now_playing = state.station_manager.get_now_playing(station_id).await.ok();
-
-        if let Some(np) = now_playing {
-            let queued = QueuedTrack {
-                track_id: np.track.id.clone(),
-                title: np.track.title.clone(),
-                artist: np.track.artist.clone(),
-            };
-            pipeline.queue_track(queued).await?;
-            tracing::info!("Queued current track for station {} HLS stream", station.name);
-        } else {
-            tracing::warn!("No tracks available for station {} HLS stream", station.name);
+/// Redirect a stream request to the streamer node that owns the station, unless this
+/// process serves it
+pub(crate) async fn redirect_to_streamer(state: &AppState, station_id: Uuid, uri: &axum::http::Uri) -> Result<Option<Response>> {
+    match state.cluster.stream_location(station_id).await? {
+        StreamLocation::Local => Ok(None),
+        StreamLocation::Remote(base_url) => {
+            let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or_else(|| uri.path());
+            Ok(Some(Redirect::temporary(&format!("{}{}", base_url, path)).into_response()))
         }
     }
+}
 
-    // Start the pipeline
-    pipeline.start().await?;
-    tracing::info!("Started audio pipeline for station {}", station.name);
-
-    let pipeline_arc = Arc::new(pipeline);
-    let broadcaster = Arc::new(AudioBroadcaster::new(
-        pipeline_arc.clone(),
-        AudioBroadcasterConfig::default(),
-    ));
-
-    // Store it
-    {
-        let mut broadcasters = state.station_broadcasters.write().await;
-        broadcasters.insert(station_id, broadcaster.clone());
-    }
-
-    // Spawn a background task to keep the queue filled
-    let state_clone = state.clone();
-    let broadcaster_clone = broadcaster.clone();
-    let pipeline_for_refill = pipeline_arc.clone();
-    tokio::spawn(async move {
-        let station_id = station_id;
-        let mut last_queued_track_id: Option<String> = None;
-
-        loop {
-            // Check if broadcaster is still running
-            if !broadcaster_clone.is_running() {
-                tracing::debug!("Broadcaster stopped, ending refill task for station {}", station_id);
-                break;
-            }
-
-            // Check queue length
-            let queue_len = pipeline_for_refill.queue_length().await;
-
-            // If queue is running low (less than 2 tracks), add more
-            if queue_len < 2 {
-                // Get next track from station manager
-                match state_clone.station_manager.get_now_playing(station_id).await {
-                    Ok(np) => {
-                        // Only queue if it's a different track than last time
-                        let track_id = np.track.id.clone();
-                        if last_queued_track_id.as_ref() != Some(&track_id) {
-                            let queued = QueuedTrack {
-                                track_id: track_id.clone(),
-                                title: np.track.title.clone(),
-                                artist: np.track.artist.clone(),
-                            };
-                            if let Err(e) = pipeline_for_refill.queue_track(queued).await {
-                                tracing::error!("Failed to queue track for station {}: {:?}", station_id, e);
-                            } else {
-                                tracing::debug!("Refilled queue with track: {} for station {}", np.track.title, station_id);
-                                last_queued_track_id = Some(track_id);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        tracing::debug!("Could not get now playing for refill: {:?}", e);
-                    }
-                }
-            }
+#[derive(Debug, Deserialize)]
+struct StreamQuery {
+    token: Option<String>,
+}
 
-            // Wait before checking again
-            tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-        }
-        tracing::info!("Refill task ended for station {}", station_id);
-    });
+/// The HLS playlist a request is for
+enum PlaylistKind {
+    /// Lists the main rendition and the variant bitrates
+    Master,
+    Main,
+    Variant(u32),
+}
 
-    Ok(broadcaster)
+/// Get the HLS master playlist (m3u8) for a station
+async fn get_hls_master_playlist(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<StreamQuery>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Response> {
+    serve_playlist(&state, id, PlaylistKind::Master, params, &uri).await
 }
 
 /// Get HLS playlist (m3u8) for a station
 async fn get_hls_playlist(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
+    Query(params): Query<StreamQuery>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Response> {
+    serve_playlist(&state, id, PlaylistKind::Main, params, &uri).await
+}
+
+/// Get the HLS playlist of one of a station's variant bitrates
+async fn get_hls_variant_playlist(
+    State(state): State<Arc<AppState>>,
+    Path((id, bitrate)): Path<(Uuid, u32)>,
+    Query(params): Query<StreamQuery>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Response> {
+    serve_playlist(&state, id, PlaylistKind::Variant(bitrate), params, &uri).await
+}
+
+async fn serve_playlist(
+    state: &AppState,
+    id: Uuid,
+    kind: PlaylistKind,
+    params: StreamQuery,
+    uri: &axum::http::Uri,
 ) -> Result<Response> {
     // Verify station exists
     let _station = sqlx::query_as::<_, Station>("SELECT * FROM stations WHERE id = $1")
@@ -913,14 +1690,41 @@ async fn get_hls_playlist(
         .await?
         .ok_or_else(|| AppError::NotFound("Station not found".to_string()))?;
 
-    let broadcaster = get_or_create_broadcaster(&state, id).await?;
-
-    // Start broadcaster if not running
-    if !broadcaster.is_running() {
-        broadcaster.start().await?;
+    // Segment URLs are relative, so after the redirect players fetch them from the
+    // streamer directly
+    if let Some(redirect) = redirect_to_streamer(state, id, uri).await? {
+        return Ok(redirect);
     }
 
-    let playlist = broadcaster.get_playlist().await;
+    let broadcaster = state.station_manager.broadcaster(id).await?;
+    let mut playlist = match kind {
+        PlaylistKind::Master => broadcaster.get_master_playlist().await,
+        PlaylistKind::Main => broadcaster.get_playlist().await,
+        PlaylistKind::Variant(bitrate) => broadcaster
+            .get_variant_playlist(bitrate)
+            .await
+            .ok_or_else(|| AppError::NotFound(format!("Station has no {} kbps variant", bitrate)))?,
+    };
+
+    // Players that can't send headers (native HLS) authenticate with ?token=, which
+    // has to be carried over to the playlist and segment URLs
+    if let Some(token) = params.token.filter(|t| !t.is_empty()) {
+        playlist = playlist
+            .lines()
+            .map(|line| {
+                if !line.is_empty() && !line.starts_with('#') {
+                    format!("{}?token={}\n", line, token)
+                } else if let Some(uri) = line
+                    .strip_prefix("#EXT-X-MAP:URI=\"")
+                    .and_then(|rest| rest.strip_suffix('"'))
+                {
+                    format!("#EXT-X-MAP:URI=\"{}?token={}\"\n", uri, token)
+                } else {
+                    format!("{}\n", line)
+                }
+            })
+            .collect();
+    }
 
     let response = Response::builder()
         .status(StatusCode::OK)
@@ -933,35 +1737,94 @@ async fn get_hls_playlist(
 }
 
 /// Get an HLS segment (audio chunk)
+/// Segments never change once published, so they are served as immutable with an ETag
+/// and conditional requests are answered with 304.
 async fn get_hls_segment(
     State(state): State<Arc<AppState>>,
     Path((id, seq_str)): Path<(Uuid, String)>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
 ) -> Result<Response> {
-    // Strip .mp3 extension if present
-    let seq_clean = seq_str.trim_end_matches(".mp3");
-    let seq: u64 = seq_clean
-        .parse()
-        .map_err(|_| AppError::Validation(format!("Invalid segment number: {}", seq_str)))?;
+    serve_segment(&state, id, None, &seq_str, &uri, &headers).await
+}
 
-    let broadcaster = {
-        let broadcasters = state.station_broadcasters.read().await;
-        broadcasters
-            .get(&id)
-            .cloned()
-            .ok_or_else(|| AppError::NotFound("Stream not found".to_string()))?
+/// Get a segment of one of a station's variant bitrates
+async fn get_hls_variant_segment(
+    State(state): State<Arc<AppState>>,
+    Path((id, bitrate, seq_str)): Path<(Uuid, u32, String)>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+) -> Result<Response> {
+    serve_segment(&state, id, Some(bitrate), &seq_str, &uri, &headers).await
+}
+
+async fn serve_segment(
+    state: &AppState,
+    id: Uuid,
+    variant: Option<u32>,
+    seq_str: &str,
+    uri: &axum::http::Uri,
+    headers: &HeaderMap,
+) -> Result<Response> {
+    let broadcaster = state.station_broadcasters.read().await.get(&id).cloned();
+    let broadcaster = match broadcaster {
+        Some(broadcaster) => broadcaster,
+        None => {
+            if let Some(redirect) = redirect_to_streamer(state, id, uri).await? {
+                return Ok(redirect);
+            }
+            return Err(AppError::NotFound("Stream not found".to_string()));
+        }
     };
 
-    let segment = broadcaster
-        .get_segment(seq)
-        .await
-        .ok_or_else(|| AppError::NotFound("Segment not found".to_string()))?;
+    // The init segment is the same for every bitrate
+    let segment = if Some(seq_str) == INIT_SEGMENT_PATH.strip_prefix("segment/") {
+        let data = broadcaster
+            .init_segment()
+            .ok_or_else(|| AppError::NotFound("Stream has no init segment".to_string()))?;
+        HlsSegment {
+            sequence: 0,
+            duration: 0.0,
+            etag: format!("\"{:x}\"", md5::compute(&data)),
+            data,
+            track_id: String::new(),
+            metadata_len: 0,
+        }
+    } else {
+        // Strip the extension (.mp3, .aac or .m4s)
+        let seq_clean = seq_str.split('.').next().unwrap_or_default();
+        let seq: u64 = seq_clean
+            .parse()
+            .map_err(|_| AppError::Validation(format!("Invalid segment number: {}", seq_str)))?;
+
+        match variant {
+            Some(bitrate) => broadcaster.get_variant_segment(bitrate, seq).await,
+            None => broadcaster.get_segment(seq).await,
+        }
+        .ok_or_else(|| AppError::NotFound("Segment not found".to_string()))?
+    };
 
-    let response = Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "audio/mpeg")
-        .header(header::CACHE_CONTROL, "public, max-age=3600")
-        .body(Body::from(segment.data))
-        .map_err(|e| AppError::InternalMessage(format!("Failed to build response: {}", e)))?;
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|tag| {
+            let tag = tag.trim();
+            tag == "*" || tag.trim_start_matches("W/") == segment.etag
+        }));
+
+    let builder = Response::builder()
+        .header(header::ETAG, &segment.etag)
+        .header(header::CACHE_CONTROL, "public, max-age=31536000, immutable");
+    let response = if not_modified {
+        builder.status(StatusCode::NOT_MODIFIED).body(Body::empty())
+    } else {
+        builder
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, broadcaster.codec().content_type())
+            .header(header::CONTENT_LENGTH, segment.data.len())
+            .body(Body::from(segment.data))
+    }
+    .map_err(|e| AppError::InternalMessage(format!("Failed to build response: {}", e)))?;
 
     Ok(response)
 }
@@ -970,13 +1833,17 @@ async fn get_hls_segment(
 async fn visualization_sse(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
-    let broadcaster = {
-        let broadcasters = state.station_broadcasters.read().await;
-        broadcasters
-            .get(&id)
-            .cloned()
-            .ok_or_else(|| AppError::NotFound("Stream not found".to_string()))?
+    OriginalUri(uri): OriginalUri,
+) -> Result<Response> {
+    let broadcaster = state.station_broadcasters.read().await.get(&id).cloned();
+    let broadcaster = match broadcaster {
+        Some(broadcaster) => broadcaster,
+        None => {
+            if let Some(redirect) = redirect_to_streamer(&state, id, &uri).await? {
+                return Ok(redirect);
+            }
+            return Err(AppError::NotFound("Stream not found".to_string()));
+        }
     };
 
     let mut rx = broadcaster.subscribe_visualization();
@@ -987,7 +1854,7 @@ async fn visualization_sse(
             match rx.recv().await {
                 Ok(viz) => {
                     let data = serde_json::to_string(&viz).unwrap_or_else(|_| "{}".to_string());
-                    yield Ok(Event::default().data(data));
+                    yield Ok::<_, Infallible>(Event::default().data(data));
                 }
                 Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
                     // Receiver fell behind, continue
@@ -1001,5 +1868,5 @@ async fn visualization_sse(
         }
     };
 
-    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()).into_response())
 }
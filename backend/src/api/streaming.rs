@@ -1,13 +1,27 @@
-use crate::services::NavidromeClient;
+use crate::api::stations::{redirect_to_streamer, AppState};
+use crate::error::AppError;
+use crate::models::Station;
+use crate::services::{audio_codec, NavidromeClient};
 use axum::{
     body::Body,
-    extract::{Path, State},
-    http::{header, StatusCode},
+    extract::{OriginalUri, Path, State},
+    http::{header, HeaderMap, StatusCode},
     response::Response,
     routing::get,
     Router,
 };
+use bytes::Bytes;
+use sqlx::PgPool;
+use std::collections::HashMap;
 use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+/// Audio bytes between ICY metadata blocks (the usual Shoutcast value)
+const ICY_METAINT: usize = 16_000;
+
+/// Segments sent immediately to a new listener, so players can fill their buffer
+const BURST_SEGMENTS: usize = 2;
 
 pub fn streaming_routes() -> Router<Arc<NavidromeClient>> {
     Router::new()
@@ -15,6 +29,11 @@ pub fn streaming_routes() -> Router<Arc<NavidromeClient>> {
         .route("/cover/:track_id", get(get_cover))
 }
 
+/// Continuous streams of station broadcasts, for players without HLS support
+pub fn station_stream_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/stations/:id/stream.mp3", get(stream_station_mp3))
+}
+
 async fn stream_track(
     State(navidrome): State<Arc<NavidromeClient>>,
     Path(track_id): Path<String>,
@@ -22,10 +41,8 @@ async fn stream_track(
     let stream_url = navidrome.get_stream_url(&track_id).await;
 
     // Proxy the stream through our backend
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&stream_url)
-        .send()
+    let response = navidrome
+        .send(navidrome.client().get(&stream_url))
         .await
         .map_err(|_| StatusCode::BAD_GATEWAY)?;
 
@@ -61,10 +78,8 @@ async fn get_cover(
     let cover_url = navidrome.get_cover_url(&track_id).await;
 
     // Proxy the cover through our backend
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&cover_url)
-        .send()
+    let response = navidrome
+        .send(navidrome.client().get(&cover_url))
         .await
         .map_err(|_| StatusCode::BAD_GATEWAY)?;
 
@@ -87,3 +102,246 @@ async fn get_cover(
 
     Ok(builder.body(Body::from(bytes)).unwrap())
 }
+
+/// GET /api/v1/stations/:id/stream.mp3
+/// Icecast/Shoutcast-style stream of a station: MP3, or ADTS AAC for AAC stations.
+/// Clients that send `Icy-MetaData: 1` get `StreamTitle` metadata every `icy-metaint`
+/// bytes, updated on track changes.
+async fn stream_station_mp3(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+) -> crate::error::Result<Response> {
+    let station = sqlx::query_as::<_, Station>("SELECT * FROM stations WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Station not found".to_string()))?;
+
+    if let Some(redirect) = redirect_to_streamer(&state, id, &uri).await? {
+        return Ok(redirect);
+    }
+
+    let broadcaster = state.station_manager.broadcaster(id).await?;
+    // MP3 and ADTS AAC segments play back to back once their ID3 tags are stripped;
+    // Opus ones are fMP4 fragments, which ICY clients can't play
+    let codec = broadcaster.codec();
+    if !audio_codec::is_packed_audio(codec) {
+        return Err(AppError::BadRequest(format!(
+            "Station is broadcast as {}; use the HLS playlist instead",
+            codec.as_str()
+        )));
+    }
+
+    // Subscribe before taking the burst so no segment falls between the two
+    let mut segments = broadcaster.subscribe_segments();
+    let burst = broadcaster.recent_segments(BURST_SEGMENTS).await;
+    let mut last_sequence = burst.last().map(|s| s.sequence);
+
+    let wants_metadata = headers
+        .get("icy-metadata")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim() == "1");
+    let mut icy = wants_metadata.then(|| IcyInjector::new(ICY_METAINT));
+    let db = state.db.clone();
+
+    let body = async_stream::stream! {
+        let mut titles = TitleCache::default();
+
+        for segment in burst {
+            yield Ok::<_, std::io::Error>(frame(&mut icy, &mut titles, &db, &segment.track_id, &segment.audio()).await);
+        }
+
+        loop {
+            match segments.recv().await {
+                Ok(segment) => {
+                    // The burst may already have covered it
+                    if last_sequence.is_some_and(|last| segment.sequence <= last) {
+                        continue;
+                    }
+                    last_sequence = Some(segment.sequence);
+                    yield Ok(frame(&mut icy, &mut titles, &db, &segment.track_id, &segment.audio()).await);
+                }
+                // A slow client skips ahead rather than falling further behind
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::debug!("Stream listener fell behind by {} segments", missed);
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    };
+
+    let mut builder = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, codec.content_type())
+        .header(header::CACHE_CONTROL, "no-cache, no-store")
+        .header("icy-name", sanitize_header(&station.name))
+        .header("icy-description", sanitize_header(&station.description))
+        .header("icy-genre", sanitize_header(&station.genres.join(", ")))
+        .header("icy-br", station.config.bitrate.to_string())
+        .header("icy-pub", "0");
+    if wants_metadata {
+        builder = builder.header("icy-metaint", ICY_METAINT.to_string());
+    }
+
+    builder
+        .body(Body::from_stream(body))
+        .map_err(|e| AppError::InternalMessage(format!("Failed to build response: {}", e)))
+}
+
+/// Audio for one segment, with ICY metadata interleaved if the client asked for it
+async fn frame(
+    icy: &mut Option<IcyInjector>,
+    titles: &mut TitleCache,
+    db: &PgPool,
+    track_id: &str,
+    data: &Bytes,
+) -> Bytes {
+    match icy {
+        Some(icy) => {
+            icy.set_title(titles.get(db, track_id).await);
+            Bytes::from(icy.push(data))
+        }
+        None => data.clone(),
+    }
+}
+
+/// "Artist - Title" of tracks seen on a stream
+#[derive(Default)]
+struct TitleCache {
+    titles: HashMap<String, String>,
+}
+
+impl TitleCache {
+    async fn get(&mut self, db: &PgPool, track_id: &str) -> String {
+        if let Some(title) = self.titles.get(track_id) {
+            return title.clone();
+        }
+
+        let row: Option<(String, String)> =
+            sqlx::query_as("SELECT artist, title FROM library_index WHERE id = $1")
+                .bind(track_id)
+                .fetch_optional(db)
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::debug!("Failed to look up stream title for {}: {}", track_id, e);
+                    None
+                });
+        let title = row
+            .map(|(artist, title)| format!("{} - {}", artist, title))
+            .unwrap_or_default();
+        self.titles.insert(track_id.to_string(), title.clone());
+        title
+    }
+}
+
+/// Interleaves ICY metadata blocks into an audio byte stream. After every `metaint`
+/// audio bytes comes a length byte (in 16-byte units) and the metadata, padded with
+/// zeros; a zero length means the title hasn't changed.
+struct IcyInjector {
+    metaint: usize,
+    /// Audio bytes left until the next metadata block
+    remaining: usize,
+    title: String,
+    /// Title not yet sent to the client
+    pending: Option<String>,
+}
+
+impl IcyInjector {
+    fn new(metaint: usize) -> Self {
+        Self {
+            metaint,
+            remaining: metaint,
+            title: String::new(),
+            pending: None,
+        }
+    }
+
+    fn set_title(&mut self, title: String) {
+        if title != self.title {
+            self.title = title.clone();
+            self.pending = Some(title);
+        }
+    }
+
+    fn push(&mut self, mut data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len() + data.len() / self.metaint * 16 + 16);
+        while !data.is_empty() {
+            let n = self.remaining.min(data.len());
+            out.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            self.remaining -= n;
+
+            if self.remaining == 0 {
+                out.extend_from_slice(&metadata_block(self.pending.take().as_deref()));
+                self.remaining = self.metaint;
+            }
+        }
+        out
+    }
+}
+
+/// One ICY metadata block: a length byte, then the text padded to a multiple of 16
+fn metadata_block(title: Option<&str>) -> Vec<u8> {
+    let Some(title) = title else {
+        return vec![0];
+    };
+
+    // Quotes end the value in every player's parser
+    let mut text = format!("StreamTitle='{}';", title.replace('\'', "\u{2019}"));
+    const MAX_LEN: usize = 255 * 16;
+    if text.len() > MAX_LEN {
+        let mut cut = MAX_LEN - 2;
+        while !text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        text.truncate(cut);
+        text.push_str("';");
+    }
+
+    let blocks = text.len().div_ceil(16);
+    let mut block = Vec::with_capacity(1 + blocks * 16);
+    block.push(blocks as u8);
+    block.extend_from_slice(text.as_bytes());
+    block.resize(1 + blocks * 16, 0);
+    block
+}
+
+/// Header values can't contain control characters
+fn sanitize_header(value: &str) -> String {
+    value.chars().filter(|c| !c.is_control()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_block_is_padded() {
+        let block = metadata_block(Some("Artist - Title"));
+        let text = b"StreamTitle='Artist - Title';";
+        assert_eq!(block[0] as usize, text.len().div_ceil(16));
+        assert_eq!(block.len(), 1 + block[0] as usize * 16);
+        assert_eq!(&block[1..1 + text.len()], text);
+        assert!(block[1 + text.len()..].iter().all(|&b| b == 0));
+
+        assert_eq!(metadata_block(None), vec![0]);
+    }
+
+    #[test]
+    fn test_metadata_is_interleaved_every_metaint_bytes() {
+        let mut icy = IcyInjector::new(4);
+        icy.set_title("A - B".to_string());
+
+        let out = icy.push(&[1, 2, 3, 4, 5, 6]);
+        assert_eq!(&out[..4], &[1, 2, 3, 4]);
+        let meta_len = 1 + out[4] as usize * 16;
+        assert!(out[4] > 0);
+        assert_eq!(&out[4 + meta_len..], &[5, 6]);
+
+        // Unchanged title: empty block after the next 4 bytes
+        let out = icy.push(&[7, 8, 9]);
+        assert_eq!(out, vec![7, 8, 0, 9]);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::api::middleware::RequireAdmin;
+use crate::api::stations::AppState;
+use crate::error::{AppError, Result};
+use crate::models::{CreateWebhookRequest, UpdateWebhookRequest, Webhook, WebhookDelivery};
+use axum::{
+    extract::{Path, Query, State},
+    routing::get,
+    Json, Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Deserialize)]
+struct ListDeliveriesQuery {
+    limit: Option<i64>,
+}
+
+pub fn webhook_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/webhooks", get(list_webhooks).post(create_webhook))
+        .route(
+            "/webhooks/:id",
+            get(get_webhook).patch(update_webhook).delete(delete_webhook),
+        )
+        .route("/webhooks/:id/deliveries", get(list_deliveries))
+}
+
+/// GET /api/v1/webhooks
+/// List registered webhooks
+async fn list_webhooks(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+) -> Result<Json<Vec<Webhook>>> {
+    let webhooks = sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks ORDER BY created_at")
+        .fetch_all(&state.db)
+        .await?;
+
+    Ok(Json(webhooks))
+}
+
+/// POST /api/v1/webhooks
+/// Register a webhook (empty or omitted `events` subscribes to all events)
+async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(claims): RequireAdmin,
+    Json(req): Json<CreateWebhookRequest>,
+) -> Result<Json<Webhook>> {
+    req.validate()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    let webhook = sqlx::query_as::<_, Webhook>(
+        r#"
+        INSERT INTO webhooks (url, secret, events, created_by)
+        VALUES ($1, $2, $3, $4)
+        RETURNING *
+        "#,
+    )
+    .bind(&req.url)
+    .bind(&req.secret)
+    .bind(serde_json::to_value(req.events.unwrap_or_default())?)
+    .bind(claims.sub)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(Json(webhook))
+}
+
+/// GET /api/v1/webhooks/:id
+/// Get a webhook
+async fn get_webhook(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Webhook>> {
+    let webhook = fetch_webhook(&state, id).await?;
+    Ok(Json(webhook))
+}
+
+/// PATCH /api/v1/webhooks/:id
+/// Update a webhook's URL, secret, event filter or active flag
+async fn update_webhook(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdateWebhookRequest>,
+) -> Result<Json<Webhook>> {
+    req.validate()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    let events = req.events.map(serde_json::to_value).transpose()?;
+
+    let webhook = sqlx::query_as::<_, Webhook>(
+        r#"
+        UPDATE webhooks
+        SET url = COALESCE($2, url),
+            secret = COALESCE($3, secret),
+            events = COALESCE($4, events),
+            active = COALESCE($5, active)
+        WHERE id = $1
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(req.url)
+    .bind(req.secret)
+    .bind(events)
+    .bind(req.active)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Webhook not found".to_string()))?;
+
+    Ok(Json(webhook))
+}
+
+/// DELETE /api/v1/webhooks/:id
+/// Remove a webhook and its delivery log
+async fn delete_webhook(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+    Path(id): Path<Uuid>,
+) -> Result<Json<()>> {
+    let result = sqlx::query("DELETE FROM webhooks WHERE id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Webhook not found".to_string()));
+    }
+
+    Ok(Json(()))
+}
+
+/// GET /api/v1/webhooks/:id/deliveries
+/// Recent delivery attempts for a webhook, newest first
+async fn list_deliveries(
+    State(state): State<Arc<AppState>>,
+    RequireAdmin(_): RequireAdmin,
+    Path(id): Path<Uuid>,
+    Query(params): Query<ListDeliveriesQuery>,
+) -> Result<Json<Vec<WebhookDelivery>>> {
+    fetch_webhook(&state, id).await?;
+
+    let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+        r#"
+        SELECT * FROM webhook_deliveries
+        WHERE webhook_id = $1
+        ORDER BY created_at DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(id)
+    .bind(params.limit.unwrap_or(50).clamp(1, 500))
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(deliveries))
+}
+
+async fn fetch_webhook(state: &AppState, id: Uuid) -> Result<Webhook> {
+    sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Webhook not found".to_string()))
+}
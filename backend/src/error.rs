@@ -1,5 +1,18 @@
+//! Application errors and their HTTP representation
+//!
+//! Every error is returned as an RFC 7807 `application/problem+json` document:
+//!
+//! ```json
+//! { "type": "/problems/not-found", "title": "Not Found", "status": 404,
+//!   "detail": "Station not found", "retryable": false }
+//! ```
+//!
+//! `type` identifies the kind of error (one per variant) and `retryable` tells clients
+//! whether the same request may succeed later (upstream outages, timeouts, rate limits)
+//! or needs to change first.
+
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -47,6 +60,29 @@ pub enum AppError {
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    #[error("Too many requests")]
+    TooManyRequests { retry_after_secs: u64 },
+
+    /// An optional feature whose configuration is missing (API key, model, paths)
+    #[error("Not configured: {0}")]
+    NotConfigured(String),
+
+    /// A component that is temporarily unable to take work (shutting down, restarting)
+    #[error("Service unavailable: {0}")]
+    Unavailable(String),
+
+    /// Audio that can't be decoded or is unsuitable for analysis
+    #[error("Audio decode error: {0}")]
+    Decode(String),
+
+    /// Reading or writing local files (audio cache, library mount)
+    #[error("Storage error: {0}")]
+    Storage(String),
+
+    /// Loading or running the audio embedding model
+    #[error("Model error: {0}")]
+    Model(String),
+
     #[error("Internal server error: {0}")]
     InternalMessage(String),
 
@@ -54,44 +90,149 @@ pub enum AppError {
     Internal(#[from] anyhow::Error),
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            AppError::AuthenticationFailed | AppError::InvalidCredentials => {
-                (StatusCode::UNAUTHORIZED, self.to_string())
+impl AppError {
+    /// Problem type slug, used as `/problems/<kind>`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::Database(_) => "database",
+            AppError::Redis(_) => "cache",
+            AppError::AuthenticationFailed | AppError::InvalidCredentials | AppError::Unauthorized => {
+                "unauthorized"
             }
-            AppError::Unauthorized => (StatusCode::UNAUTHORIZED, self.to_string()),
-            AppError::Forbidden => (StatusCode::FORBIDDEN, self.to_string()),
-            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
-            AppError::Validation(msg) => (StatusCode::BAD_REQUEST, msg),
-            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg),
-            AppError::Database(ref e) => {
-                tracing::error!("Database error: {:?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
-            }
-            AppError::Redis(ref e) => {
-                tracing::error!("Redis error: {:?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Cache error".to_string())
+            AppError::Forbidden => "forbidden",
+            AppError::NotFound(_) => "not-found",
+            AppError::Validation(_) => "validation",
+            AppError::Conflict(_) => "conflict",
+            AppError::ExternalApi(_) => "upstream",
+            AppError::Navidrome(_) => "navidrome",
+            AppError::Streaming(_) => "streaming",
+            AppError::BadRequest(_) => "bad-request",
+            AppError::TooManyRequests { .. } => "rate-limited",
+            AppError::NotConfigured(_) => "not-configured",
+            AppError::Unavailable(_) => "unavailable",
+            AppError::Decode(_) => "decode",
+            AppError::Storage(_) => "storage",
+            AppError::Model(_) => "model",
+            AppError::InternalMessage(_) | AppError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::AuthenticationFailed | AppError::InvalidCredentials | AppError::Unauthorized => {
+                StatusCode::UNAUTHORIZED
             }
-            AppError::ExternalApi(msg) => (StatusCode::BAD_GATEWAY, msg),
-            AppError::Navidrome(msg) => (StatusCode::BAD_GATEWAY, msg),
-            AppError::Streaming(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
-            AppError::InternalMessage(msg) => {
-                tracing::error!("Internal error: {}", msg);
-                (StatusCode::INTERNAL_SERVER_ERROR, msg)
+            AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Validation(_) | AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::ExternalApi(_) | AppError::Navidrome(_) => StatusCode::BAD_GATEWAY,
+            AppError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::NotConfigured(_) | AppError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Decode(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Database(e) if is_transient_db_error(e) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Database(_)
+            | AppError::Redis(_)
+            | AppError::Streaming(_)
+            | AppError::Storage(_)
+            | AppError::Model(_)
+            | AppError::InternalMessage(_)
+            | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Whether repeating the same request later may succeed
+    pub fn retryable(&self) -> bool {
+        match self {
+            AppError::ExternalApi(_)
+            | AppError::Navidrome(_)
+            | AppError::Streaming(_)
+            | AppError::TooManyRequests { .. }
+            | AppError::Unavailable(_) => true,
+            AppError::Database(e) => is_transient_db_error(e),
+            AppError::Redis(e) => e.is_timeout() || e.is_connection_dropped() || e.is_io_error(),
+            _ => false,
+        }
+    }
+
+    /// Message safe to show to clients; internal details stay in the logs
+    fn detail(&self) -> String {
+        match self {
+            AppError::NotFound(msg)
+            | AppError::Validation(msg)
+            | AppError::Conflict(msg)
+            | AppError::ExternalApi(msg)
+            | AppError::Navidrome(msg)
+            | AppError::Streaming(msg)
+            | AppError::BadRequest(msg)
+            | AppError::NotConfigured(msg)
+            | AppError::Unavailable(msg)
+            | AppError::Decode(msg)
+            | AppError::Storage(msg)
+            | AppError::Model(msg)
+            | AppError::InternalMessage(msg) => msg.clone(),
+            AppError::Database(_) if self.retryable() => "Database temporarily unavailable".to_string(),
+            AppError::Database(_) => "Database error".to_string(),
+            AppError::Redis(_) => "Cache error".to_string(),
+            AppError::TooManyRequests { .. } => "Too many attempts. Try again later.".to_string(),
+            AppError::Internal(_) => "Internal server error".to_string(),
+            AppError::AuthenticationFailed
+            | AppError::InvalidCredentials
+            | AppError::Unauthorized
+            | AppError::Forbidden => self.to_string(),
+        }
+    }
+}
+
+/// Connection and pool failures, and transactions Postgres asked to retry
+/// (serialization failure, deadlock)
+fn is_transient_db_error(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed => true,
+        sqlx::Error::Database(db_err) => matches!(db_err.code().as_deref(), Some("40001" | "40P01")),
+        _ => false,
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status.is_server_error() {
+            match &self {
+                AppError::Database(e) => tracing::error!("Database error: {:?}", e),
+                AppError::Redis(e) => tracing::error!("Redis error: {:?}", e),
+                AppError::Internal(e) => tracing::error!("Internal error: {:?}", e),
+                AppError::NotConfigured(_) => {}
+                e => tracing::error!("{}", e),
             }
-            AppError::Internal(ref e) => {
-                tracing::error!("Internal error: {:?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
+        }
+
+        let mut body = json!({
+            "type": format!("/problems/{}", self.kind()),
+            "title": status.canonical_reason().unwrap_or("Error"),
+            "status": status.as_u16(),
+            "detail": self.detail(),
+            "retryable": self.retryable(),
+        });
+
+        let mut response = match self {
+            AppError::TooManyRequests { retry_after_secs } => {
+                body["retry_after_secs"] = json!(retry_after_secs);
+                (
+                    status,
+                    [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                    Json(body),
+                )
+                    .into_response()
             }
+            _ => (status, Json(body)).into_response(),
         };
 
-        let body = Json(json!({
-            "error": error_message,
-        }));
-
-        (status, body).into_response()
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        );
+        response
     }
 }
 
@@ -102,3 +243,64 @@ impl From<serde_json::Error> for AppError {
         AppError::Internal(anyhow::anyhow!(err))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn problem(error: AppError) -> (StatusCode, Option<String>, serde_json::Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, content_type, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_problem_documents() {
+        let (status, content_type, body) = problem(AppError::NotFound("Station not found".to_string())).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(content_type.as_deref(), Some("application/problem+json"));
+        assert_eq!(body["type"], "/problems/not-found");
+        assert_eq!(body["title"], "Not Found");
+        assert_eq!(body["status"], 404);
+        assert_eq!(body["detail"], "Station not found");
+        assert_eq!(body["retryable"], false);
+    }
+
+    #[tokio::test]
+    async fn test_transient_errors_are_retryable() {
+        let (status, _, body) = problem(AppError::Navidrome("Connection refused".to_string())).await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert_eq!(body["retryable"], true);
+
+        let (status, _, body) = problem(AppError::Database(sqlx::Error::PoolTimedOut)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["retryable"], true);
+
+        let (status, _, body) = problem(AppError::Database(sqlx::Error::RowNotFound)).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["detail"], "Database error");
+        assert_eq!(body["retryable"], false);
+
+        let (status, _, body) = problem(AppError::NotConfigured("No API key".to_string())).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["retryable"], false);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_carries_retry_after() {
+        let response = AppError::TooManyRequests { retry_after_secs: 30 }.into_response();
+        assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+
+        let (status, _, body) = problem(AppError::TooManyRequests { retry_after_secs: 30 }).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body["retry_after_secs"], 30);
+        assert_eq!(body["retryable"], true);
+    }
+}
@@ -1,6 +1,17 @@
+//! The SvelteKit SPA, embedded into the binary
+//!
+//! The static build (`frontend/build`) is compiled in with rust-embed. The adapter
+//! precompresses every asset, so `.br` and `.gz` variants are served as-is to clients
+//! that accept them instead of compressing on each request. Fingerprinted files under
+//! `_app/immutable/` are cached for a year; everything else is revalidated with its ETag.
+//!
+//! Unknown paths that look like client-side routes get `index.html`; missing files
+//! (a known file extension, or anything under `_app/`) and unknown API paths are 404s.
+
+use crate::error::AppError;
 use axum::{
     body::Body,
-    http::{header, StatusCode, Uri},
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
     response::{IntoResponse, Response},
 };
 use rust_embed::RustEmbed;
@@ -10,40 +21,110 @@ use rust_embed::RustEmbed;
 #[folder = "../frontend/build"]
 pub struct Assets;
 
-pub async fn serve_frontend(uri: Uri) -> impl IntoResponse {
+/// Content encodings of precompressed variants, most preferred first
+const ENCODINGS: &[(&str, &str)] = &[("br", "br"), ("gzip", "gz")];
+
+pub async fn serve_frontend(uri: Uri, headers: HeaderMap) -> Response {
     let path = uri.path().trim_start_matches('/');
 
-    // Try to serve the requested file
-    if let Some(content) = Assets::get(path) {
-        return serve_asset(path, content.data.into_owned());
+    if path.starts_with("api/") {
+        return AppError::NotFound(format!("No API route for /{}", path)).into_response();
     }
 
-    // If not found, check if it's a directory index
-    let index_path = format!("{}/index.html", path);
-    if let Some(content) = Assets::get(&index_path) {
-        return serve_asset(&index_path, content.data.into_owned());
+    // The requested file, then a directory index
+    let index_path = format!("{}/index.html", path.trim_end_matches('/'));
+    for candidate in [path, index_path.trim_start_matches('/')] {
+        if !candidate.is_empty() && Assets::get(candidate).is_some() {
+            return serve_asset(candidate, &headers);
+        }
     }
 
-    // For SPA routing, fall back to index.html for non-API routes
-    if !path.starts_with("api/") {
-        if let Some(content) = Assets::get("index.html") {
-            return serve_asset("index.html", content.data.into_owned());
-        }
+    // Missing assets must not turn into an HTML page the browser tries to run as a script
+    if is_asset_path(path) {
+        return not_found();
+    }
+
+    // For SPA routing, fall back to index.html
+    if Assets::get("index.html").is_some() {
+        return serve_asset("index.html", &headers);
     }
 
-    // File not found
     not_found()
 }
 
-fn serve_asset(path: &str, data: Vec<u8>) -> Response {
+/// Whether a path names a file rather than a client-side route. Station paths may
+/// contain dots, so only extensions with a known file type count.
+fn is_asset_path(path: &str) -> bool {
+    path.starts_with("_app/") || mime_guess::from_path(path).first().is_some()
+}
+
+fn serve_asset(path: &str, headers: &HeaderMap) -> Response {
+    let Some(original) = Assets::get(path) else {
+        return not_found();
+    };
+    let etag = format!("\"{}\"", hex::encode(&original.metadata.sha256_hash()[..16]));
     let mime = mime_guess::from_path(path).first_or_octet_stream();
 
-    Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, mime.as_ref())
+    let builder = Response::builder()
+        .header(header::ETAG, &etag)
         .header(header::CACHE_CONTROL, cache_control_value(path))
-        .body(Body::from(data))
-        .unwrap()
+        .header(header::VARY, "Accept-Encoding");
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|tag| {
+            let tag = tag.trim();
+            tag == "*" || tag.trim_start_matches("W/") == etag
+        }));
+    if not_modified {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .unwrap();
+    }
+
+    // Prefer a precompressed variant the client accepts
+    let accept_encoding = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let variant = ENCODINGS
+        .iter()
+        .filter(|(encoding, _)| accepts_encoding(accept_encoding, encoding))
+        .find_map(|(encoding, extension)| {
+            Assets::get(&format!("{}.{}", path, extension)).map(|file| (*encoding, file))
+        });
+
+    let builder = builder.status(StatusCode::OK).header(header::CONTENT_TYPE, mime.as_ref());
+    match variant {
+        Some((encoding, file)) => builder
+            .header(header::CONTENT_ENCODING, HeaderValue::from_static(encoding))
+            .body(Body::from(file.data.into_owned())),
+        None => builder.body(Body::from(original.data.into_owned())),
+    }
+    .unwrap()
+}
+
+/// Whether an `Accept-Encoding` header allows the encoding (explicitly or via `*`)
+fn accepts_encoding(accept_encoding: &str, encoding: &str) -> bool {
+    let mut wildcard = false;
+    for item in accept_encoding.split(',') {
+        let mut parts = item.split(';');
+        let name = parts.next().unwrap_or_default().trim();
+        let quality = parts
+            .find_map(|p| p.trim().strip_prefix("q="))
+            .and_then(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        if name.eq_ignore_ascii_case(encoding) {
+            return quality > 0.0;
+        }
+        if name == "*" {
+            wildcard = quality > 0.0;
+        }
+    }
+    wildcard
 }
 
 fn cache_control_value(path: &str) -> &'static str {
@@ -60,6 +141,34 @@ fn cache_control_value(path: &str) -> &'static str {
 fn not_found() -> Response {
     Response::builder()
         .status(StatusCode::NOT_FOUND)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
         .body(Body::from("404 Not Found"))
         .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_encoding() {
+        assert!(accepts_encoding("gzip, deflate, br", "br"));
+        assert!(accepts_encoding("gzip, deflate, br", "gzip"));
+        assert!(!accepts_encoding("gzip", "br"));
+        assert!(!accepts_encoding("br;q=0, gzip", "br"));
+        assert!(accepts_encoding("*", "br"));
+        assert!(!accepts_encoding("*, br;q=0", "br"));
+        assert!(!accepts_encoding("", "gzip"));
+    }
+
+    #[test]
+    fn test_asset_paths_are_not_spa_routes() {
+        assert!(is_asset_path("_app/immutable/chunks/missing.js"));
+        assert!(is_asset_path("favicon.png"));
+        assert!(is_asset_path("_app/version.json"));
+        assert!(!is_asset_path("station/jazz"));
+        assert!(!is_asset_path("station/late.night"));
+        assert!(!is_asset_path("admin"));
+        assert!(!is_asset_path(""));
+    }
+}
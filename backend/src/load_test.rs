@@ -0,0 +1,443 @@
+//! Synthetic listener load test (`--simulate-listeners N`)
+//!
+//! Spawns N HLS clients against a running server, each polling a station's playlist
+//! and downloading new segments the way a player does, then reports segment fetch
+//! latency, playlist staleness (how long a playlist went without a new segment) and
+//! CPU usage. Meant for validating broadcaster changes against a local instance
+//! before they reach real listeners:
+//!
+//! ```bash
+//! cargo run --release -- --simulate-listeners 200 --station <uuid> --duration 60
+//! ```
+//!
+//! CPU is sampled from `/proc`: system-wide usage covers a server on the same host,
+//! and the load tester's own share is reported separately so it can be discounted.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// How often each client re-fetches the playlist (half the HLS target duration, like hls.js)
+const PLAYLIST_POLL_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// How often CPU usage is sampled
+const CPU_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Client start-up is spread over this window so the server isn't hit all at once
+const RAMP_UP: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone)]
+pub struct SimulationOptions {
+    /// Server root, e.g. `http://127.0.0.1:8000`
+    pub base_url: String,
+    pub station_id: Uuid,
+    pub listeners: usize,
+    pub duration: Duration,
+    /// Access token for stations that don't allow anonymous listening
+    pub token: Option<String>,
+}
+
+impl SimulationOptions {
+    /// Parse `--simulate-listeners N` and its companion flags (`--station <uuid>`,
+    /// `--duration <secs>`, `--url <base url>`, `--token <jwt>`) from the command line
+    pub fn from_args() -> anyhow::Result<Option<Self>> {
+        let args: Vec<String> = std::env::args().collect();
+        let value = |flag: &str| -> anyhow::Result<Option<&str>> {
+            match args.iter().position(|arg| arg == flag) {
+                Some(i) => args
+                    .get(i + 1)
+                    .map(|v| Some(v.as_str()))
+                    .ok_or_else(|| anyhow::anyhow!("{} requires a value", flag)),
+                None => Ok(None),
+            }
+        };
+
+        let Some(listeners) = value("--simulate-listeners")? else {
+            return Ok(None);
+        };
+        let listeners: usize = listeners.parse()?;
+        if listeners == 0 {
+            anyhow::bail!("--simulate-listeners needs at least one listener");
+        }
+
+        let station_id = value("--station")?
+            .ok_or_else(|| anyhow::anyhow!("--simulate-listeners requires --station <uuid>"))?
+            .parse()?;
+        let duration = Duration::from_secs(value("--duration")?.map(str::parse).transpose()?.unwrap_or(60));
+        let base_url = match value("--url")? {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => format!(
+                "http://127.0.0.1:{}",
+                std::env::var("SERVER_PORT").unwrap_or_else(|_| "8000".to_string())
+            ),
+        };
+        let token = value("--token")?
+            .map(str::to_string)
+            .or_else(|| std::env::var("LOAD_TEST_TOKEN").ok())
+            .filter(|t| !t.is_empty());
+
+        Ok(Some(Self {
+            base_url,
+            station_id,
+            listeners,
+            duration,
+            token,
+        }))
+    }
+}
+
+/// Measurements collected by all clients
+#[derive(Debug, Default)]
+struct Samples {
+    /// Time to download each segment, in milliseconds
+    segment_latency_ms: Vec<f64>,
+    segment_bytes: u64,
+    /// Age of the newest segment at each playlist poll, in milliseconds
+    playlist_staleness_ms: Vec<f64>,
+    playlist_requests: u64,
+    /// Failed requests by reason
+    errors: HashMap<String, u64>,
+}
+
+impl Samples {
+    fn error(&mut self, reason: impl Into<String>) {
+        *self.errors.entry(reason.into()).or_default() += 1;
+    }
+}
+
+#[derive(Debug)]
+pub struct SimulationReport {
+    listeners: usize,
+    elapsed: Duration,
+    samples: Samples,
+    /// Busy share of all CPUs over the run (0-100)
+    system_cpu_percent: Option<f64>,
+    /// CPU used by the load tester itself, in percent of one core
+    own_cpu_percent: Option<f64>,
+}
+
+/// Run the simulation and collect a report
+pub async fn simulate_listeners(options: SimulationOptions) -> anyhow::Result<SimulationReport> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .pool_max_idle_per_host(options.listeners)
+        .build()?;
+    let playlist_url = format!(
+        "{}/api/v1/stations/{}/stream/playlist.m3u8",
+        options.base_url, options.station_id
+    );
+
+    // Fail fast on a wrong URL, station or token instead of N identical errors
+    let probe = authorized(client.get(&playlist_url), options.token.as_deref())
+        .send()
+        .await?;
+    if !probe.status().is_success() {
+        anyhow::bail!("Playlist request failed with {}: {}", probe.status(), probe.text().await?);
+    }
+
+    tracing::info!(
+        "Simulating {} listener(s) on {} for {}s",
+        options.listeners,
+        playlist_url,
+        options.duration.as_secs()
+    );
+
+    let samples = Arc::new(Mutex::new(Samples::default()));
+    let started = Instant::now();
+    let deadline = started + options.duration;
+    let cpu_start = CpuSnapshot::read();
+
+    let mut clients = Vec::with_capacity(options.listeners);
+    for i in 0..options.listeners {
+        let delay = RAMP_UP.mul_f64(i as f64 / options.listeners as f64);
+        clients.push(tokio::spawn(run_listener(
+            client.clone(),
+            playlist_url.clone(),
+            options.token.clone(),
+            delay,
+            deadline,
+            samples.clone(),
+        )));
+    }
+
+    // Progress line every few seconds so long runs show they're alive
+    let mut ticker = tokio::time::interval(CPU_SAMPLE_INTERVAL * 5);
+    ticker.tick().await;
+    while Instant::now() < deadline {
+        tokio::select! {
+            _ = ticker.tick() => {
+                let samples = samples.lock().await;
+                tracing::info!(
+                    "{:.0}s: {} segments, {} playlist polls, {} errors",
+                    started.elapsed().as_secs_f64(),
+                    samples.segment_latency_ms.len(),
+                    samples.playlist_requests,
+                    samples.errors.values().sum::<u64>()
+                );
+            }
+            _ = tokio::time::sleep_until(deadline.into()) => {}
+        }
+    }
+
+    for client in clients {
+        let _ = client.await;
+    }
+
+    let elapsed = started.elapsed();
+    let cpu_end = CpuSnapshot::read();
+    let (system_cpu_percent, own_cpu_percent) = match (cpu_start, cpu_end) {
+        (Some(start), Some(end)) => (start.system_busy_percent(&end), start.own_percent(&end, elapsed)),
+        _ => (None, None),
+    };
+
+    let samples = std::mem::take(&mut *samples.lock().await);
+    Ok(SimulationReport {
+        listeners: options.listeners,
+        elapsed,
+        samples,
+        system_cpu_percent,
+        own_cpu_percent,
+    })
+}
+
+fn authorized(request: reqwest::RequestBuilder, token: Option<&str>) -> reqwest::RequestBuilder {
+    match token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    }
+}
+
+/// One simulated player: poll the playlist and download every segment not seen yet
+async fn run_listener(
+    client: reqwest::Client,
+    playlist_url: String,
+    token: Option<String>,
+    delay: Duration,
+    deadline: Instant,
+    samples: Arc<Mutex<Samples>>,
+) {
+    tokio::time::sleep(delay).await;
+    let segment_base = playlist_url.trim_end_matches("playlist.m3u8").to_string();
+
+    let mut last_sequence: Option<u64> = None;
+    let mut newest_seen_at = Instant::now();
+
+    while Instant::now() < deadline {
+        let poll_started = Instant::now();
+        let playlist = match fetch_text(&client, &playlist_url, token.as_deref()).await {
+            Ok(playlist) => playlist,
+            Err(reason) => {
+                samples.lock().await.error(format!("playlist: {}", reason));
+                tokio::time::sleep(PLAYLIST_POLL_INTERVAL).await;
+                continue;
+            }
+        };
+
+        let segments = parse_segments(&playlist);
+        let newest = segments.last().map(|(sequence, _)| *sequence);
+        if newest > last_sequence {
+            newest_seen_at = poll_started;
+        }
+        let staleness = poll_started.duration_since(newest_seen_at);
+        {
+            let mut samples = samples.lock().await;
+            samples.playlist_requests += 1;
+            samples.playlist_staleness_ms.push(staleness.as_secs_f64() * 1000.0);
+        }
+
+        // A player joining mid-stream starts near the live edge, not at the window start
+        let first_new = match last_sequence {
+            Some(last) => last + 1,
+            None => newest.map(|n| n.saturating_sub(2)).unwrap_or(0),
+        };
+        for (sequence, uri) in segments.iter().filter(|(sequence, _)| *sequence >= first_new) {
+            let url = format!("{}{}", segment_base, uri.split('?').next().unwrap_or(uri));
+            let fetch_started = Instant::now();
+            let result = fetch_bytes(&client, &url, token.as_deref()).await;
+            let latency = fetch_started.elapsed();
+
+            let mut samples = samples.lock().await;
+            match result {
+                Ok(bytes) => {
+                    samples.segment_latency_ms.push(latency.as_secs_f64() * 1000.0);
+                    samples.segment_bytes += bytes;
+                }
+                Err(reason) => samples.error(format!("segment: {}", reason)),
+            }
+            last_sequence = Some(*sequence);
+        }
+
+        tokio::time::sleep(PLAYLIST_POLL_INTERVAL.saturating_sub(poll_started.elapsed())).await;
+    }
+}
+
+async fn fetch_text(client: &reqwest::Client, url: &str, token: Option<&str>) -> Result<String, String> {
+    let response = authorized(client.get(url), token).send().await.map_err(request_error)?;
+    if !response.status().is_success() {
+        return Err(response.status().to_string());
+    }
+    response.text().await.map_err(request_error)
+}
+
+async fn fetch_bytes(client: &reqwest::Client, url: &str, token: Option<&str>) -> Result<u64, String> {
+    let response = authorized(client.get(url), token).send().await.map_err(request_error)?;
+    if !response.status().is_success() {
+        return Err(response.status().to_string());
+    }
+    Ok(response.bytes().await.map_err(request_error)?.len() as u64)
+}
+
+fn request_error(e: reqwest::Error) -> String {
+    if e.is_timeout() {
+        "timeout".to_string()
+    } else if e.is_connect() {
+        "connection failed".to_string()
+    } else {
+        "request failed".to_string()
+    }
+}
+
+/// (media sequence, URI) of each segment in a media playlist
+fn parse_segments(playlist: &str) -> Vec<(u64, String)> {
+    let mut sequence = playlist
+        .lines()
+        .find_map(|line| line.strip_prefix("#EXT-X-MEDIA-SEQUENCE:"))
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(0);
+
+    let mut segments = Vec::new();
+    for line in playlist.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        segments.push((sequence, line.to_string()));
+        sequence += 1;
+    }
+    segments
+}
+
+/// Cumulative CPU counters from `/proc` (Linux only)
+#[derive(Debug, Clone, Copy)]
+struct CpuSnapshot {
+    /// Busy and total jiffies across all CPUs
+    system_busy: u64,
+    system_total: u64,
+    /// utime + stime of this process, in jiffies
+    own: u64,
+}
+
+/// Kernel clock ticks per second; 100 on every mainstream Linux configuration
+const CLOCK_TICKS_PER_SEC: f64 = 100.0;
+
+impl CpuSnapshot {
+    fn read() -> Option<Self> {
+        let stat = std::fs::read_to_string("/proc/stat").ok()?;
+        let fields: Vec<u64> = stat
+            .lines()
+            .next()?
+            .split_whitespace()
+            .skip(1)
+            .filter_map(|v| v.parse().ok())
+            .collect();
+        let system_total: u64 = fields.iter().sum();
+        // idle + iowait
+        let idle = fields.get(3).copied().unwrap_or(0) + fields.get(4).copied().unwrap_or(0);
+
+        // Fields after the parenthesised command name; utime and stime are the 14th and 15th
+        let own_stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+        let after_comm: Vec<&str> = own_stat.rsplit_once(')')?.1.split_whitespace().collect();
+        let own = after_comm.get(11)?.parse::<u64>().ok()? + after_comm.get(12)?.parse::<u64>().ok()?;
+
+        Some(Self {
+            system_busy: system_total.saturating_sub(idle),
+            system_total,
+            own,
+        })
+    }
+
+    fn system_busy_percent(&self, end: &Self) -> Option<f64> {
+        let total = end.system_total.checked_sub(self.system_total)?;
+        let busy = end.system_busy.checked_sub(self.system_busy)?;
+        (total > 0).then(|| busy as f64 / total as f64 * 100.0)
+    }
+
+    fn own_percent(&self, end: &Self, elapsed: Duration) -> Option<f64> {
+        let own = end.own.checked_sub(self.own)? as f64 / CLOCK_TICKS_PER_SEC;
+        (elapsed.as_secs_f64() > 0.0).then(|| own / elapsed.as_secs_f64() * 100.0)
+    }
+}
+
+/// Value at the given percentile (0-100) of sorted samples
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (p / 100.0 * (sorted.len() - 1) as f64).round() as usize;
+    sorted[rank.min(sorted.len() - 1)]
+}
+
+fn sorted(values: &[f64]) -> Vec<f64> {
+    let mut values = values.to_vec();
+    values.sort_by(|a, b| a.total_cmp(b));
+    values
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let samples = &self.samples;
+        let secs = self.elapsed.as_secs_f64();
+        writeln!(f, "Simulated {} listener(s) for {:.0}s", self.listeners, secs)?;
+
+        let latency = sorted(&samples.segment_latency_ms);
+        writeln!(
+            f,
+            "  segments:  {} fetched, {:.1} MB ({:.0} kbit/s total)",
+            latency.len(),
+            samples.segment_bytes as f64 / 1_048_576.0,
+            samples.segment_bytes as f64 * 8.0 / 1000.0 / secs.max(1.0)
+        )?;
+        writeln!(
+            f,
+            "  latency:   p50 {:.0} ms, p95 {:.0} ms, p99 {:.0} ms, max {:.0} ms",
+            percentile(&latency, 50.0),
+            percentile(&latency, 95.0),
+            percentile(&latency, 99.0),
+            latency.last().copied().unwrap_or(0.0)
+        )?;
+
+        let staleness = sorted(&samples.playlist_staleness_ms);
+        writeln!(
+            f,
+            "  staleness: p50 {:.0} ms, p95 {:.0} ms, max {:.0} ms over {} playlist polls",
+            percentile(&staleness, 50.0),
+            percentile(&staleness, 95.0),
+            staleness.last().copied().unwrap_or(0.0),
+            samples.playlist_requests
+        )?;
+
+        match (self.system_cpu_percent, self.own_cpu_percent) {
+            (Some(system), Some(own)) => writeln!(
+                f,
+                "  cpu:       {:.1}% of all cores system-wide, load tester {:.1}% of one core",
+                system, own
+            )?,
+            _ => writeln!(f, "  cpu:       unavailable (needs /proc)")?,
+        }
+
+        let total_errors: u64 = samples.errors.values().sum();
+        if total_errors == 0 {
+            write!(f, "  errors:    none")
+        } else {
+            write!(f, "  errors:    {}", total_errors)?;
+            let mut errors: Vec<_> = samples.errors.iter().collect();
+            errors.sort_by(|a, b| b.1.cmp(a.1));
+            for (reason, count) in errors {
+                write!(f, "\n    {}: {}", reason, count)?;
+            }
+            Ok(())
+        }
+    }
+}
@@ -2,18 +2,48 @@ mod api;
 mod config;
 mod error;
 mod frontend;
+mod load_test;
 mod models;
+mod seed;
 mod services;
 
+use crate::api::middleware::ListenerAccess;
 use crate::api::stations::AppState;
-use crate::config::Config;
+use crate::config::{Config, ServerRole};
 use crate::services::{
-    audio_encoder::{AudioEncoder, AudioEncoderConfig},
+    announcer::{Announcer, CommandSpeech, OpenAiSpeech, SpeechBackend},
+    audio_cache::AudioCache,
+    audio_encoder::{self, AudioEncoder, AudioEncoderConfig},
+    audio_pipeline::{StreamOptions, DECODABLE_FORMATS},
+    broadcast_archive::BroadcastArchive,
+    cluster::Cluster,
+    embedding_worker::{EmbeddingControlState, EmbeddingWorker},
     hybrid_curator::{HybridCurator, HybridCurationConfig},
     library_indexer::{LibraryIndexer, TrackAnalyzer},
-    AiCurator, AuthService, CurationEngine, NavidromeClient, StationManager,
+    lastfm::{LastFmClient, LastFmConfig},
+    library_scheduler::{LibraryScheduler, ScheduleConfig},
+    library_watcher::LibraryWatcher,
+    llm::LlmClient,
+    musicbrainz::{MusicBrainzClient, MusicBrainzConfig},
+    listenbrainz::ListenBrainzClient,
+    loudness::LoudnessAnalyzer,
+    migrations,
+    model_manifest::{self, ModelManifest},
+    navidrome_accounts::NavidromeAccounts,
+    oidc::OidcClient,
+    playlist_sync::PlaylistSync,
+    program_curator::ProgramCurator,
+    radio_publisher::RadioPublisher,
+    rate_limit::RateLimiter,
+    secret_box::SecretBox,
+    station_events::StationEventBus,
+    station_manager::{BroadcastSettings, WatchdogSettings},
+    vector_index::VectorSearchConfig,
+    AiCurator, AuthService, CurationEngine, DailyMixService, JobManager, LoginGuard, NavidromeClient, StationManager,
+    WebhookDispatcher,
 };
-use std::path::PathBuf;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use axum::{
     http::{header, HeaderValue, Method, StatusCode},
     response::IntoResponse,
@@ -23,6 +53,7 @@ use axum::{
 use serde_json::json;
 use sqlx::postgres::PgPoolOptions;
 use std::sync::Arc;
+use std::time::Duration;
 use tower_http::{
     compression::CompressionLayer,
     cors::CorsLayer,
@@ -40,57 +71,297 @@ async fn main() -> anyhow::Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    // `--simulate-listeners N`: load-test a running server with synthetic HLS clients and exit
+    dotenvy::dotenv().ok();
+    if let Some(options) = load_test::SimulationOptions::from_args()? {
+        let report = load_test::simulate_listeners(options).await?;
+        println!("{}", report);
+        return Ok(());
+    }
+
     // Load configuration
-    let config = Config::from_env()?;
+    let mut config = Config::from_env()?;
     tracing::info!("Configuration loaded");
 
-    // Connect to database
-    let db = PgPoolOptions::new()
-        .max_connections(50)
-        .connect(&config.database_url)
-        .await?;
-    tracing::info!("Connected to database");
-
-    // Run migrations
-    sqlx::migrate!("./migrations")
-        .run(&db)
-        .await?;
+    // Connect to database (retries while Postgres is still starting up)
+    let db = connect_database(&config).await?;
+    tracing::info!(
+        "Connected to database (pool max={}, min={})",
+        config.db_max_connections,
+        config.db_min_connections
+    );
+
+    // `--rollback-to <version>`: revert migrations newer than <version> and exit
+    if let Some(target) = rollback_target()? {
+        let reverted = migrations::rollback_to(&db, target).await?;
+        tracing::info!("Rolled back migration(s) {:?}; schema is now at version {}", reverted, target);
+        return Ok(());
+    }
+
+    // Run migrations (drift is logged first so a refusal to migrate has context)
+    if let Err(e) = migrations::warn_on_drift(&db).await {
+        tracing::warn!("Failed to check migration status: {}", e);
+    }
+    migrations::MIGRATOR.run(&db).await?;
     tracing::info!("Database migrations completed");
 
-    // Connect to Redis
-    let redis_client = redis::Client::open(config.redis_url.as_str())?;
-    let redis = redis::aio::ConnectionManager::new(redis_client).await?;
+    // `--seed-demo`: populate demo data for local development and exit
+    if std::env::args().any(|arg| arg == "--seed-demo") {
+        let auth_service = AuthService::new(db.clone(), &config);
+        seed::seed_demo(&db, &auth_service).await?;
+        return Ok(());
+    }
+
+    // Connect to Redis (the connection manager reconnects automatically after drops)
+    let redis = connect_redis(&config).await?;
     tracing::info!("Connected to Redis");
 
     // Initialize services
-    let navidrome_client = Arc::new(NavidromeClient::new(
-        config.navidrome_url.clone(),
-        config.navidrome_user.clone(),
-        config.navidrome_password.clone(),
+    // The client keeps the only copy of the Navidrome secret
+    let navidrome_client = Arc::new(match (config.navidrome_api_key.take(), config.navidrome_password.take()) {
+        (Some(api_key), _) => {
+            NavidromeClient::with_api_key(config.navidrome_url.clone(), config.navidrome_user.clone(), api_key)
+        }
+        (None, Some(password)) => {
+            NavidromeClient::new(config.navidrome_url.clone(), config.navidrome_user.clone(), password)
+        }
+        (None, None) => unreachable!("Config::from_env requires a Navidrome password or API key"),
+    }
+    .with_limits(config.navidrome_max_rps, config.navidrome_max_concurrent));
+    if config.navidrome_health_check_secs > 0 {
+        navidrome_client
+            .clone()
+            .spawn_health_check(Duration::from_secs(config.navidrome_health_check_secs));
+    }
+
+    let navidrome_accounts = Arc::new(NavidromeAccounts::new(
+        db.clone(),
+        SecretBox::new(&config.credentials_secret),
+        navidrome_client.clone(),
     ));
 
+    // Streamer nodes leave schedulers and other background work to the API nodes
+    let background_jobs = config.server_role.runs_background_jobs();
+    tracing::info!("Server role: {:?}", config.server_role);
+
+    // Keep playlist-sourced stations in step with their playlists
+    if background_jobs && config.playlist_sync_interval_secs > 0 {
+        Arc::new(PlaylistSync::new(db.clone(), navidrome_accounts.clone()))
+            .spawn(Duration::from_secs(config.playlist_sync_interval_secs));
+    }
+
+    let webhooks = WebhookDispatcher::new(db.clone());
     let auth_service = Arc::new(AuthService::new(db.clone(), &config));
-    let curation_engine = Arc::new(CurationEngine::new(navidrome_client.clone(), &config));
-    let station_manager = Arc::new(StationManager::new(
+    let login_guard = Arc::new(LoginGuard::new(redis.clone(), config.login_max_failures));
+    let rate_limiter = config.rate_limit_enabled.then(|| {
+        Arc::new(RateLimiter::new(
+            redis.clone(),
+            config.rate_limit_per_min,
+            config.rate_limit_expensive_per_min,
+        ))
+    });
+    let oidc = config.oidc.clone().map(|oidc_config| {
+        tracing::info!("OpenID Connect login enabled ({})", oidc_config.issuer_url);
+        Arc::new(OidcClient::new(
+            oidc_config,
+            db.clone(),
+            redis.clone(),
+            SecretBox::new(&config.credentials_secret),
+        ))
+    });
+
+    // Every Claude call goes through one client, which enforces the daily budget and caches replies
+    let llm = config
+        .anthropic_api_key
+        .as_ref()
+        .map(|api_key| Arc::new(LlmClient::new(api_key.clone(), db.clone(), &config)));
+    let curation_engine = Arc::new(CurationEngine::new(navidrome_client.clone(), llm.clone()).with_external_metadata(db.clone()));
+    // Skips and track changes are shared with other instances behind the same Redis
+    let station_events = Arc::new(StationEventBus::new(
+        redis::Client::open(config.redis_url.as_str())?,
+        redis.clone(),
+    ));
+
+    if let Some(format) = &config.navidrome_transcode_format {
+        if !DECODABLE_FORMATS.contains(&format.as_str()) {
+            tracing::warn!(
+                "NAVIDROME_TRANSCODE_FORMAT={} can't be decoded for broadcasting (supported: {:?})",
+                format, DECODABLE_FORMATS
+            );
+        }
+    }
+    let stream_options = StreamOptions {
+        format: config.navidrome_transcode_format.clone(),
+        max_bit_rate: config.navidrome_max_bitrate,
+    };
+
+    let audio_cache = match &config.audio_cache_dir {
+        Some(dir) => match AudioCache::open(dir, config.audio_cache_max_mb * 1024 * 1024) {
+            Ok(cache) => Some(Arc::new(cache)),
+            Err(e) => {
+                tracing::warn!("Audio cache disabled: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    // Listens are queued by whichever node advances a station; background nodes submit them
+    let listenbrainz = Arc::new(ListenBrainzClient::new(
+        db.clone(),
+        SecretBox::new(&config.credentials_secret),
+        config.listenbrainz_api_url.clone(),
+        config.listenbrainz_token.clone(),
+    ));
+    if background_jobs {
+        listenbrainz.clone().spawn_flusher();
+    }
+
+    let lastfm = config.lastfm_api_key.clone().map(|api_key| {
+        Arc::new(LastFmClient::new(
+            db.clone(),
+            LastFmConfig {
+                api_key,
+                api_secret: config.lastfm_api_secret.clone(),
+                session_key: config.lastfm_session_key.clone(),
+                username: config.lastfm_username.clone(),
+                password: config.lastfm_password.take(),
+                api_url: crate::services::lastfm::DEFAULT_API_URL.to_string(),
+            },
+        ))
+    });
+    if let Some(lastfm) = lastfm.as_ref().filter(|lastfm| background_jobs && lastfm.can_scrobble()) {
+        lastfm.clone().spawn_flusher();
+    }
+
+    let musicbrainz = config.musicbrainz_enabled.then(|| {
+        Arc::new(MusicBrainzClient::new(
+            db.clone(),
+            MusicBrainzConfig {
+                api_url: config.musicbrainz_api_url.clone(),
+                acousticbrainz_url: config.acousticbrainz_api_url.clone(),
+            },
+        ))
+    });
+
+    // Spoken links between tracks, for stations that ask for them
+    let speech: Option<Box<dyn SpeechBackend>> = match (&config.announcer_tts_command, &config.announcer_tts_url) {
+        (Some(command), _) => Some(Box::new(CommandSpeech::new(command))),
+        (None, Some(url)) => Some(Box::new(OpenAiSpeech::new(
+            url,
+            config.announcer_tts_api_key.clone(),
+            config.announcer_tts_model.clone(),
+        ))),
+        (None, None) => None,
+    };
+    let announcer = speech.map(|backend| {
+        let cache = match &config.announcer_cache_dir {
+            Some(dir) => match AudioCache::open(dir, config.announcer_cache_max_mb * 1024 * 1024) {
+                Ok(cache) => Some(cache),
+                Err(e) => {
+                    tracing::warn!("Announcement cache disabled: {}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        Arc::new(Announcer::new(backend, config.announcer_voice.clone(), cache))
+    });
+
+    // Hourly recordings of the stations that ask for them
+    let archive = config
+        .archive_dir
+        .as_ref()
+        .map(|dir| Arc::new(BroadcastArchive::new(PathBuf::from(dir), config.archive_retention_days)));
+
+    // Pipelines and broadcasters of the stations this process streams
+    let station_broadcasters = Arc::new(tokio::sync::RwLock::new(std::collections::HashMap::new()));
+    let mut station_manager = StationManager::new(
         db.clone(),
         redis.clone(),
         curation_engine.clone(),
         navidrome_client.clone(),
+        webhooks.clone(),
+        station_events.clone(),
+    )
+    .with_broadcasting(
+        station_broadcasters.clone(),
+        BroadcastSettings {
+            enabled: config.server_role.streams(),
+            // With a single node nothing else will start the stream
+            eager: config.server_role == ServerRole::All,
+            stream_options: stream_options.clone(),
+            audio_cache: audio_cache.clone(),
+            spill_dir: config.hls_spill_dir.as_ref().map(PathBuf::from),
+            archive: archive.clone(),
+            fallback_dir: config.fallback_audio_dir.as_ref().map(PathBuf::from),
+            library_path: config.navidrome_library_path.as_ref().map(PathBuf::from),
+            loudness_target_lufs: config.loudness_target_lufs,
+            announcer: announcer.clone(),
+            resample_quality: config.resample_quality,
+            watchdog: WatchdogSettings {
+                stall_secs: config.watchdog_stall_secs,
+                silence_secs: config.watchdog_silence_secs,
+            },
+        },
+    )
+    .with_listenbrainz(listenbrainz.clone());
+    if let Some(lastfm) = &lastfm {
+        station_manager = station_manager.with_lastfm(lastfm.clone());
+    }
+    let station_manager = Arc::new(station_manager);
+
+    // Measures track loudness for normalization (run from the admin library page)
+    let loudness_analyzer = Arc::new(LoudnessAnalyzer::new(
+        db.clone(),
+        navidrome_client.clone(),
+        stream_options,
+        audio_cache.clone(),
+        config.resample_quality,
     ));
 
+    // Publish stations into Navidrome's internet radio list (optional)
+    let radio_publisher = match (config.navidrome_publish_stations, &config.public_url) {
+        (true, Some(public_url)) => {
+            tracing::info!("Publishing stations to Navidrome radio list");
+            Some(Arc::new(RadioPublisher::new(
+                db.clone(),
+                navidrome_client.clone(),
+                public_url.clone(),
+            )))
+        }
+        (true, None) => {
+            tracing::warn!("NAVIDROME_PUBLISH_STATIONS is set but PUBLIC_URL is not - station publishing disabled");
+            None
+        }
+        (false, _) => None,
+    };
+
+    // Initialize background job runner (jobs left over from a previous run are marked failed)
+    let jobs = Arc::new(JobManager::new(db.clone(), webhooks.clone()));
+    if background_jobs {
+        match jobs.recover_interrupted().await {
+            Ok(0) => {}
+            Ok(n) => tracing::warn!("Marked {} interrupted background job(s) as failed", n),
+            Err(e) => tracing::error!("Failed to recover interrupted background jobs: {:?}", e),
+        }
+    }
+
     // Initialize library indexing services
-    let track_analyzer = config.anthropic_api_key.as_ref().map(|api_key| {
-        Arc::new(TrackAnalyzer::new(api_key.clone()))
+    let track_analyzer = llm.as_ref().map(|llm| {
+        Arc::new(TrackAnalyzer::new(llm.clone()))
     });
 
     let library_indexer = Arc::new(LibraryIndexer::new(
         db.clone(),
         navidrome_client.clone(),
         track_analyzer,
+        jobs.clone(),
+        webhooks.clone(),
     ));
 
-    let ai_curator = config.anthropic_api_key.as_ref().map(|api_key| {
-        Arc::new(AiCurator::new(api_key.clone(), db.clone()))
+    let ai_curator = llm.as_ref().map(|llm| {
+        Arc::new(AiCurator::new(llm.clone(), db.clone()))
     });
 
     if ai_curator.is_some() {
@@ -101,13 +372,44 @@ async fn main() -> anyhow::Result<()> {
 
     // Initialize audio encoder (optional - requires ONNX model)
     // Will auto-download from GitHub releases if not found locally
-    let audio_encoder = initialize_audio_encoder(&config, &db).await;
+    let audio_encoder = initialize_audio_encoder(&config, &db, &navidrome_client).await;
+
+    // Keep the embedding map up to date as embeddings are added
+    if let (Some(encoder), true) = (&audio_encoder, background_jobs && config.visualization_refresh_secs > 0) {
+        encoder
+            .clone()
+            .spawn_visualization_refresh(jobs.clone(), Duration::from_secs(config.visualization_refresh_secs));
+    }
+
+    // Embedding backfill (needs the encoder and somewhere to read audio from)
+    let embedding_control = Arc::new(tokio::sync::RwLock::new(EmbeddingControlState::default()));
+    let embedding_worker = match &audio_encoder {
+        Some(encoder) if config.navidrome_library_path.is_some() || audio_cache.is_some() => {
+            let worker = Arc::new(EmbeddingWorker::new(
+                db.clone(),
+                encoder.clone(),
+                navidrome_client.clone(),
+                config.navidrome_library_path.clone(),
+                audio_cache.clone(),
+                embedding_control.clone(),
+            ));
+            if background_jobs {
+                match worker.resume_interrupted(&jobs).await {
+                    Ok(Some(job_id)) => tracing::info!("Resumed interrupted embedding backfill as job {}", job_id),
+                    Ok(None) => {}
+                    Err(e) => tracing::error!("Failed to resume embedding backfill: {:?}", e),
+                }
+            }
+            Some(worker)
+        }
+        _ => None,
+    };
 
     // Initialize hybrid curator (optional - requires both API key and audio encoder)
-    let hybrid_curator = match (&config.anthropic_api_key, &audio_encoder) {
-        (Some(api_key), Some(encoder)) => {
+    let hybrid_curator = match (&llm, &audio_encoder) {
+        (Some(llm), Some(encoder)) => {
             let curator = HybridCurator::new(
-                api_key.clone(),
+                llm.clone(),
                 Some(encoder.clone()),
                 db.clone(),
                 HybridCurationConfig::default(),
@@ -126,26 +428,138 @@ async fn main() -> anyhow::Result<()> {
         }
     };
 
+    // Personal Daily Mix stations, rebuilt each morning (optional - requires the hybrid curator)
+    let daily_mix = match (&hybrid_curator, background_jobs && config.daily_mix_enabled) {
+        (Some(curator), true) => {
+            let service = Arc::new(DailyMixService::new(
+                db.clone(),
+                curator.clone(),
+                station_broadcasters.clone(),
+            ));
+            service.clone().spawn_scheduler(jobs.clone(), config.daily_mix_hour);
+            tracing::info!("Daily Mix stations enabled (rebuilt at {:02}:00 UTC)", config.daily_mix_hour);
+            Some(service)
+        }
+        (None, true) => {
+            tracing::info!("Daily Mix stations disabled - hybrid curator not available");
+            None
+        }
+        (_, false) => None,
+    };
+
+    // Program blocks curated from a prompt before each airing (requires the hybrid curator)
+    if let (Some(curator), true) = (&hybrid_curator, background_jobs) {
+        Arc::new(ProgramCurator::new(db.clone(), curator.clone())).spawn();
+    }
+
+    // Import new tracks once Navidrome finishes scanning its library
+    let library_watcher = Arc::new(LibraryWatcher::new(
+        db.clone(),
+        navidrome_client.clone(),
+        library_indexer.clone(),
+        jobs.clone(),
+        audio_encoder.clone(),
+        config.navidrome_library_path.clone(),
+        audio_cache.clone(),
+    ));
+    if background_jobs && config.library_watch_secs > 0 {
+        library_watcher
+            .clone()
+            .spawn(Duration::from_secs(config.library_watch_secs));
+    }
+
+    // Periodic syncs, AI analysis, embedding backfills and stats refreshes
+    let library_scheduler = Arc::new(LibraryScheduler::new(
+        jobs.clone(),
+        library_indexer.clone(),
+        library_watcher.clone(),
+        embedding_worker.clone(),
+        ScheduleConfig {
+            sync: Duration::from_secs(config.library_sync_interval_secs),
+            analysis: Duration::from_secs(config.library_analysis_interval_secs),
+            analysis_batch: config.library_analysis_batch.max(1),
+            embeddings: Duration::from_secs(config.embedding_backfill_interval_secs),
+            stats: Duration::from_secs(config.library_stats_interval_secs),
+        },
+    ));
+    if background_jobs {
+        library_scheduler.clone().spawn();
+    }
+
+    let cluster = Arc::new(Cluster::new(
+        config.server_role,
+        config.streamer_url.clone(),
+        redis.clone(),
+    ));
+    cluster.clone().spawn_heartbeat(station_broadcasters.clone());
+    station_manager.clone().spawn_failover(cluster.clone());
+    station_manager.clone().spawn_listener_sweep();
+
     let app_state = Arc::new(AppState {
         db: db.clone(),
         auth_service: auth_service.clone(),
+        login_guard,
+        rate_limiter,
+        trust_proxy_headers: config.trust_proxy_headers,
+        allow_anonymous_listening: config.allow_anonymous_listening,
         station_manager: station_manager.clone(),
         curation_engine: curation_engine.clone(),
         library_indexer: library_indexer.clone(),
+        jobs: jobs.clone(),
         ai_curator: ai_curator.clone(),
         audio_encoder,
         hybrid_curator,
         navidrome_client: navidrome_client.clone(),
+        navidrome_accounts,
+        listenbrainz,
+        lastfm,
+        musicbrainz,
+        announcer,
+        radio_publisher: radio_publisher.clone(),
         navidrome_library_path: config.navidrome_library_path.clone(),
-        embedding_control: Arc::new(tokio::sync::RwLock::new(
-            crate::api::stations::EmbeddingControlState::default(),
-        )),
-        station_broadcasters: Arc::new(tokio::sync::RwLock::new(std::collections::HashMap::new())),
+        embedding_control,
+        embedding_worker,
+        station_broadcasters,
+        daily_mix,
+        audio_cache,
+        library_watcher,
+        library_scheduler,
+        loudness_analyzer,
+        hls_spill_dir: config.hls_spill_dir.as_ref().map(std::path::PathBuf::from),
+        archive,
+        station_events: station_events.clone(),
+        cluster,
+        redis: redis.clone(),
+        webhooks: webhooks.clone(),
+        oidc,
+        llm,
+        requests_per_hour: config.requests_per_hour,
     });
 
+    // Apply skips and track changes made on other instances
+    {
+        let station_manager = station_manager.clone();
+        station_events.spawn_subscriber(move |event| {
+            let station_manager = station_manager.clone();
+            async move { station_manager.apply_remote_event(&event).await }
+        });
+    }
+
     // Load active stations on startup
-    if let Err(e) = station_manager.load_active_stations().await {
-        tracing::error!("Failed to load active stations: {:?}", e);
+    if background_jobs {
+        if let Err(e) = station_manager.load_active_stations().await {
+            tracing::error!("Failed to load active stations: {:?}", e);
+        }
+    }
+
+    // Bring Navidrome's radio list in line with current stations
+    if let Some(publisher) = radio_publisher.filter(|_| background_jobs) {
+        tokio::spawn(async move {
+            match publisher.sync_all().await {
+                Ok(count) => tracing::info!("Synced {} station(s) to Navidrome radio list", count),
+                Err(e) => tracing::warn!("Failed to sync stations to Navidrome radio list: {}", e),
+            }
+        });
     }
 
     // Build CORS layer from configuration
@@ -156,15 +570,34 @@ async fn main() -> anyhow::Result<()> {
     let app = Router::new()
         // Health check endpoint (outside /api/v1 for standard monitoring)
         .route("/health", get(health_check))
+        // Liveness and readiness with per-dependency status
+        .merge(api::health_routes().with_state(app_state.clone()))
         // API routes
         .nest(
             "/api/v1",
             Router::new()
                 .nest("/auth", api::auth_routes())
+                .nest("/me", api::me_routes())
                 .nest("/settings", api::settings_routes())
-                .merge(api::station_routes())
+                .nest("/admin", api::admin_routes())
+                .merge(api::capabilities_routes())
+                .merge(api::station_routes().route_layer(
+                    axum::middleware::from_extractor_with_state::<ListenerAccess, _>(app_state.clone()),
+                ))
+                .merge(api::station_stream_routes().route_layer(
+                    axum::middleware::from_extractor_with_state::<ListenerAccess, _>(app_state.clone()),
+                ))
                 .merge(api::library_routes())
-                .nest("/navidrome", api::streaming_routes().with_state(navidrome_client.clone()))
+                .merge(api::webhook_routes())
+                .nest(
+                    "/navidrome",
+                    api::streaming_routes()
+                        .with_state(navidrome_client.clone())
+                        .route_layer(axum::middleware::from_extractor_with_state::<ListenerAccess, _>(
+                            app_state.clone(),
+                        )),
+                )
+                .layer(axum::middleware::from_fn_with_state(app_state.clone(), api::middleware::rate_limit))
                 .with_state(app_state.clone()),
         )
         // Frontend SPA - catch-all route (must be last)
@@ -178,13 +611,100 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("Starting server on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    // Connect info gives handlers the peer address (used for login brute-force protection)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
 
+/// Parse `--rollback-to <version>` from the command line
+fn rollback_target() -> anyhow::Result<Option<i64>> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--rollback-to");
+    if args.next().is_none() {
+        return Ok(None);
+    }
+
+    let version = args
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("--rollback-to requires a migration version"))?;
+    Ok(Some(version.parse()?))
+}
+
+/// Initial delay between startup connection attempts (doubles each retry)
+const STARTUP_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Upper bound for the delay between startup connection attempts
+const STARTUP_RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Backoff delay before the given (1-based) retry attempt
+fn startup_backoff(attempt: u32) -> Duration {
+    STARTUP_RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(STARTUP_RETRY_MAX_DELAY)
+}
+
+/// Create the Postgres pool, retrying with exponential backoff until the database accepts connections
+async fn connect_database(config: &Config) -> anyhow::Result<sqlx::PgPool> {
+    let options = PgPoolOptions::new()
+        .max_connections(config.db_max_connections)
+        .min_connections(config.db_min_connections)
+        .acquire_timeout(Duration::from_secs(config.db_acquire_timeout_secs))
+        .idle_timeout(Duration::from_secs(config.db_idle_timeout_secs))
+        // Validate connections before handing them out so dropped sockets are replaced transparently
+        .test_before_acquire(true);
+
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        match options.clone().connect(&config.database_url).await {
+            Ok(pool) => return Ok(pool),
+            Err(e) if attempt < config.startup_connect_retries => {
+                let delay = startup_backoff(attempt);
+                tracing::warn!(
+                    "Database not ready (attempt {}/{}): {}. Retrying in {:?}",
+                    attempt,
+                    config.startup_connect_retries,
+                    e,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Create the Redis connection manager, retrying with exponential backoff until Redis is reachable.
+/// Once established, the manager reconnects on its own (with backoff) if the connection drops.
+async fn connect_redis(config: &Config) -> anyhow::Result<redis::aio::ConnectionManager> {
+    let client = redis::Client::open(config.redis_url.as_str())?;
+
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        // exponent_base=2, factor=100ms, up to 6 reconnect attempts per failure
+        match redis::aio::ConnectionManager::new_with_backoff(client.clone(), 2, 100, 6).await {
+            Ok(manager) => return Ok(manager),
+            Err(e) if attempt < config.startup_connect_retries => {
+                let delay = startup_backoff(attempt);
+                tracing::warn!(
+                    "Redis not ready (attempt {}/{}): {}. Retrying in {:?}",
+                    attempt,
+                    config.startup_connect_retries,
+                    e,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
 /// GitHub releases URL for the audio encoder model
 const MODEL_RELEASE_URL: &str = "https://github.com/ethanbarclay/navidrome-radio/releases/latest/download/audio_encoder.onnx";
+/// Version and checksum of the released model
+const MODEL_MANIFEST_URL: &str = "https://github.com/ethanbarclay/navidrome-radio/releases/latest/download/audio_encoder.json";
 
 /// Default model locations to check
 const MODEL_PATHS: &[&str] = &[
@@ -197,12 +717,13 @@ const MODEL_PATHS: &[&str] = &[
 async fn initialize_audio_encoder(
     config: &Config,
     db: &sqlx::PgPool,
+    navidrome_client: &Arc<NavidromeClient>,
 ) -> Option<Arc<AudioEncoder>> {
     // Check env var first
     if let Some(ref env_path) = config.audio_encoder_model_path {
         let path = PathBuf::from(env_path);
-        if path.exists() {
-            return create_audio_encoder(path, db);
+        if path.exists() && model_intact(&path) {
+            return create_audio_encoder(config, path, db, navidrome_client);
         }
         tracing::warn!("AUDIO_ENCODER_MODEL_PATH set but file not found: {:?}", path);
     }
@@ -210,9 +731,9 @@ async fn initialize_audio_encoder(
     // Check default locations
     for path_str in MODEL_PATHS {
         let path = PathBuf::from(path_str);
-        if path.exists() {
+        if path.exists() && model_intact(&path) {
             tracing::info!("Found audio encoder model at: {:?}", path);
-            return create_audio_encoder(path, db);
+            return create_audio_encoder(config, path, db, navidrome_client);
         }
     }
 
@@ -229,7 +750,7 @@ async fn initialize_audio_encoder(
     match download_model(&download_path).await {
         Ok(()) => {
             tracing::info!("Successfully downloaded audio encoder model to {:?}", download_path);
-            create_audio_encoder(download_path, db)
+            create_audio_encoder(config, download_path, db, navidrome_client)
         }
         Err(e) => {
             tracing::warn!("Failed to download audio encoder model: {}. ML features will be disabled.", e);
@@ -238,7 +759,25 @@ async fn initialize_audio_encoder(
     }
 }
 
-/// Download the ONNX model from GitHub releases
+/// Whether a model matches its saved manifest (models without one are trusted)
+fn model_intact(path: &Path) -> bool {
+    let Some(manifest) = ModelManifest::for_model(path) else {
+        return true;
+    };
+    match manifest.verify_file(path) {
+        Ok(true) => true,
+        Ok(false) => {
+            tracing::warn!("Audio encoder model {:?} doesn't match its manifest checksum, ignoring it", path);
+            false
+        }
+        Err(e) => {
+            tracing::warn!("Failed to read audio encoder model {:?}: {}", path, e);
+            false
+        }
+    }
+}
+
+/// Download the ONNX model from GitHub releases, verified against its manifest
 async fn download_model(dest: &PathBuf) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     use tokio::io::AsyncWriteExt;
 
@@ -254,6 +793,12 @@ async fn download_model(dest: &PathBuf) -> Result<(), Box<dyn std::error::Error
         .redirect(reqwest::redirect::Policy::limited(10))
         .build()?;
 
+    let response = client.get(MODEL_MANIFEST_URL).send().await?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}: {}", response.status(), MODEL_MANIFEST_URL).into());
+    }
+    let manifest: ModelManifest = response.json().await?;
+
     let response = client.get(MODEL_RELEASE_URL).send().await?;
 
     if !response.status().is_success() {
@@ -266,26 +811,61 @@ async fn download_model(dest: &PathBuf) -> Result<(), Box<dyn std::error::Error
     }
 
     let bytes = response.bytes().await?;
+    if !manifest.matches(&bytes) {
+        return Err(format!(
+            "Checksum mismatch: expected {}, got {}",
+            manifest.sha256,
+            model_manifest::checksum(&bytes)
+        )
+        .into());
+    }
 
     let mut file = tokio::fs::File::create(dest).await?;
     file.write_all(&bytes).await?;
     file.flush().await?;
+    manifest.save_for_model(dest)?;
 
     tracing::info!("Download complete: {:?} ({:.1} MB)", dest, bytes.len() as f64 / 1_000_000.0);
     Ok(())
 }
 
 /// Create an AudioEncoder instance from a model path
-fn create_audio_encoder(path: PathBuf, db: &sqlx::PgPool) -> Option<Arc<AudioEncoder>> {
+fn create_audio_encoder(
+    config: &Config,
+    path: PathBuf,
+    db: &sqlx::PgPool,
+    navidrome_client: &Arc<NavidromeClient>,
+) -> Option<Arc<AudioEncoder>> {
+    // Stored with each embedding, so a different model can be migrated to
+    let model_version = config
+        .audio_encoder_model_version
+        .clone()
+        .or_else(|| ModelManifest::for_model(&path).map(|manifest| manifest.version))
+        .unwrap_or_else(|| audio_encoder::MODEL_VERSION.to_string());
     let encoder_config = AudioEncoderConfig {
         model_path: path.clone(),
+        model_version,
+        resample_quality: config.resample_quality,
+        windows: config.embedding_windows,
+        store_windows: config.embedding_store_windows,
+        analyze_features: config.audio_features_enabled,
+        batch_size: config.embedding_batch_size,
+        execution_providers: config.onnx_execution_providers.clone(),
         ..Default::default()
     };
+    let vector_search = VectorSearchConfig {
+        ef_search: config.hnsw_ef_search,
+        probes: config.ivfflat_probes,
+    };
 
     match AudioEncoder::new(encoder_config, db.clone()) {
         Ok(encoder) => {
             tracing::info!("Audio encoder initialized from: {:?}", path);
-            Some(Arc::new(encoder))
+            Some(Arc::new(
+                encoder
+                    .with_navidrome(navidrome_client.clone())
+                    .with_vector_search(vector_search),
+            ))
         }
         Err(e) => {
             tracing::warn!("Failed to initialize audio encoder: {}", e);
@@ -298,7 +878,11 @@ fn create_audio_encoder(path: PathBuf, db: &sqlx::PgPool) -> Option<Arc<AudioEnc
 fn build_cors_layer(config: &Config) -> CorsLayer {
     let cors = CorsLayer::new()
         .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
-        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE]);
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            header::HeaderName::from_static(api::middleware::API_KEY_HEADER),
+        ]);
 
     // Check if wildcard is configured (development mode)
     if config.cors_origins.iter().any(|o| o == "*") {
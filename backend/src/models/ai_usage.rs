@@ -0,0 +1,39 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+
+/// LLM spending over the last `days` days, as returned by `GET /api/v1/ai/usage`
+#[derive(Debug, Serialize)]
+pub struct AiUsage {
+    pub days: i64,
+    pub from: DateTime<Utc>,
+    /// LLM_DAILY_BUDGET_USD, None when unlimited
+    pub daily_budget_usd: Option<f64>,
+    pub spent_today_usd: f64,
+    pub remaining_today_usd: Option<f64>,
+    pub total_cost_usd: f64,
+    pub features: Vec<FeatureUsage>,
+    /// Days without calls are left out
+    pub daily: Vec<DailyUsage>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct FeatureUsage {
+    pub feature: String,
+    pub calls: i64,
+    /// Calls answered from the response cache, at no cost
+    pub cached_calls: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cost_usd: f64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct DailyUsage {
+    pub day: NaiveDate,
+    pub calls: i64,
+    pub cached_calls: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cost_usd: f64,
+}
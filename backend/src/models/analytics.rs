@@ -0,0 +1,103 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// What happened on a station, as recorded in `station_analytics_events`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StationEventKind {
+    Play,
+    Skip,
+    ListenerJoin,
+    ListenerLeave,
+    /// The curation engine picked the next track
+    Curation,
+}
+
+impl StationEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StationEventKind::Play => "play",
+            StationEventKind::Skip => "skip",
+            StationEventKind::ListenerJoin => "listener_join",
+            StationEventKind::ListenerLeave => "listener_leave",
+            StationEventKind::Curation => "curation",
+        }
+    }
+}
+
+/// Time span analytics are computed over, ending now
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AnalyticsWindow {
+    #[serde(rename = "24h")]
+    Day,
+    #[default]
+    #[serde(rename = "7d")]
+    Week,
+    #[serde(rename = "30d")]
+    Month,
+    #[serde(rename = "90d")]
+    Quarter,
+}
+
+impl AnalyticsWindow {
+    pub fn duration(self) -> Duration {
+        match self {
+            AnalyticsWindow::Day => Duration::hours(24),
+            AnalyticsWindow::Week => Duration::days(7),
+            AnalyticsWindow::Month => Duration::days(30),
+            AnalyticsWindow::Quarter => Duration::days(90),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StationAnalytics {
+    pub window: AnalyticsWindow,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub plays: i64,
+    pub skips: i64,
+    pub curation_runs: i64,
+    /// Listener count averaged over the window's plays
+    pub average_listeners: Option<f64>,
+    /// Hours without plays are left out
+    pub plays_per_hour: Vec<HourlyPlays>,
+    pub top_artists: Vec<ArtistPlays>,
+    /// Most played tracks, with how often each was skipped
+    pub tracks: Vec<TrackSkipRate>,
+    /// Listener sessions started in the window
+    pub sessions: i64,
+    pub retention: Vec<RetentionPoint>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct HourlyPlays {
+    pub hour: DateTime<Utc>,
+    pub plays: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ArtistPlays {
+    pub artist: String,
+    pub plays: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TrackSkipRate {
+    pub track_id: String,
+    /// None for tracks no longer in the library
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub plays: i64,
+    pub skips: i64,
+    pub skip_rate: f64,
+}
+
+/// Share of listener sessions still listening `minutes` after they joined. Sessions
+/// still open count as lasting until now.
+#[derive(Debug, Serialize, FromRow)]
+pub struct RetentionPoint {
+    pub minutes: i32,
+    pub retained: f64,
+}
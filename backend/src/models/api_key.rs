@@ -0,0 +1,197 @@
+use axum::http::Method;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use validator::Validate;
+
+/// What an API key is allowed to do, checked against the request method and path
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyScope {
+    /// Any GET request outside `/admin` and `/me` (user management, audit logs, sessions
+    /// and account exports need `Admin`), except the SSE endpoints that start a sync,
+    /// backfill or curation run
+    #[serde(alias = "read-only")]
+    Read,
+    /// Station control and management (`/stations/...`: start, stop, skip, edit)
+    #[serde(alias = "station-control")]
+    Stations,
+    /// Library sync, analysis and embeddings (`/library/...`, `/embeddings/...`)
+    #[serde(alias = "library-admin")]
+    Library,
+    /// Everything the key's owner can do
+    Admin,
+    /// Listening only: station info, now playing, the HLS stream and listener heartbeats.
+    /// Meant for stream URLs embedded in hardware players.
+    Stream,
+}
+
+impl ApiKeyScope {
+    /// Whether this scope covers a request. `path` is relative to `/api/v1`.
+    pub fn allows(&self, method: &Method, path: &str) -> bool {
+        match self {
+            ApiKeyScope::Read => {
+                method == Method::GET && !is_account_path(path) && !STARTING_STREAMS.contains(&path)
+            }
+            ApiKeyScope::Stations => path.starts_with("/stations"),
+            ApiKeyScope::Library => {
+                path.starts_with("/library") || path.starts_with("/embeddings")
+            }
+            ApiKeyScope::Admin => true,
+            ApiKeyScope::Stream => is_stream_request(method, path),
+        }
+    }
+}
+
+/// GET endpoints that start work and stream its progress, so `Read` doesn't cover them
+const STARTING_STREAMS: &[&str] = &[
+    "/library/sync-stream",
+    "/embeddings/index-stream",
+    "/ai/hybrid-curate-stream",
+];
+
+/// Administration and the owner's own account, which only `Admin` keys reach
+fn is_account_path(path: &str) -> bool {
+    ["/admin", "/me"]
+        .iter()
+        .any(|prefix| path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/')))
+}
+
+/// Requests a player needs to listen to one station
+fn is_stream_request(method: &Method, path: &str) -> bool {
+    if station_id_in_path(path).is_none() {
+        return false;
+    }
+    // Everything after the station ID
+    let rest = path["/stations/".len()..]
+        .split_once('/')
+        .map_or("", |(_, rest)| rest);
+
+    match *method {
+        Method::GET => {
+            rest.is_empty()
+                || rest == "nowplaying"
+                || rest == "nowplaying/stream"
+                || rest == "stream.mp3"
+                || rest.starts_with("stream/")
+        }
+        Method::POST => rest == "listener/heartbeat" || rest == "listener/leave",
+        _ => false,
+    }
+}
+
+/// Station a `/stations/:id/...` path refers to
+fn station_id_in_path(path: &str) -> Option<Uuid> {
+    path.strip_prefix("/stations/")?
+        .split('/')
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub key_prefix: String,
+    #[sqlx(json)]
+    pub scopes: Vec<ApiKeyScope>,
+    /// Stations the key is limited to (None = all)
+    pub station_ids: Option<Vec<Uuid>>,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    pub fn allows(&self, method: &Method, path: &str) -> bool {
+        if let Some(station_ids) = &self.station_ids {
+            if !station_id_in_path(path).is_some_and(|id| station_ids.contains(&id)) {
+                return false;
+            }
+        }
+        self.scopes.iter().any(|scope| scope.allows(method, path))
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateApiKeyRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(min = 1))]
+    pub scopes: Vec<ApiKeyScope>,
+    /// Days until the key expires (omit for a key that never expires)
+    #[validate(range(min = 1, max = 3650))]
+    pub expires_in_days: Option<i64>,
+    /// Limit the key to these stations (omit for all stations)
+    pub station_ids: Option<Vec<Uuid>>,
+}
+
+/// A stream-only key for one or more stations, created by any user for their own players
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateStreamTokenRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(min = 1, max = 50))]
+    pub station_ids: Vec<Uuid>,
+    #[validate(range(min = 1, max = 3650))]
+    pub expires_in_days: Option<i64>,
+}
+
+impl From<CreateStreamTokenRequest> for CreateApiKeyRequest {
+    fn from(req: CreateStreamTokenRequest) -> Self {
+        Self {
+            name: req.name,
+            scopes: vec![ApiKeyScope::Stream],
+            expires_in_days: req.expires_in_days,
+            station_ids: Some(req.station_ids),
+        }
+    }
+}
+
+/// Returned once at creation; the plaintext key cannot be retrieved later
+#[derive(Debug, Serialize)]
+pub struct CreateApiKeyResponse {
+    pub key: String,
+    pub api_key: ApiKey,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_aliases() {
+        let scopes: Vec<ApiKeyScope> =
+            serde_json::from_str(r#"["read-only", "station-control", "library-admin", "stream"]"#).unwrap();
+        assert_eq!(
+            scopes,
+            vec![ApiKeyScope::Read, ApiKeyScope::Stations, ApiKeyScope::Library, ApiKeyScope::Stream]
+        );
+    }
+
+    #[test]
+    fn test_scope_allows() {
+        let station = "/stations/00000000-0000-0000-0000-000000000001";
+        assert!(ApiKeyScope::Read.allows(&Method::GET, "/library/tracks"));
+        assert!(!ApiKeyScope::Read.allows(&Method::POST, &format!("{}/skip", station)));
+        for path in ["/admin/api-keys", "/admin/users", "/admin/audit", "/admin/jobs", "/me", "/me/export"] {
+            assert!(!ApiKeyScope::Read.allows(&Method::GET, path), "{}", path);
+            assert!(ApiKeyScope::Admin.allows(&Method::GET, path), "{}", path);
+        }
+        assert!(ApiKeyScope::Read.allows(&Method::GET, "/stations"));
+        assert!(ApiKeyScope::Read.allows(&Method::GET, "/library/sync-status"));
+        assert!(!ApiKeyScope::Read.allows(&Method::GET, "/library/sync-stream"));
+        assert!(ApiKeyScope::Library.allows(&Method::GET, "/library/sync-stream"));
+        assert!(ApiKeyScope::Stations.allows(&Method::POST, &format!("{}/skip", station)));
+        assert!(!ApiKeyScope::Stations.allows(&Method::POST, "/library/sync"));
+        assert!(ApiKeyScope::Library.allows(&Method::POST, "/library/sync"));
+        assert!(ApiKeyScope::Stream.allows(&Method::GET, &format!("{}/stream/playlist.m3u8", station)));
+        assert!(ApiKeyScope::Stream.allows(&Method::GET, &format!("{}/stream.mp3", station)));
+        assert!(ApiKeyScope::Stream.allows(&Method::GET, &format!("{}/nowplaying/stream", station)));
+        assert!(!ApiKeyScope::Stream.allows(&Method::POST, &format!("{}/skip", station)));
+    }
+}
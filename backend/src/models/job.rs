@@ -0,0 +1,109 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Kind of long-running work tracked by the job subsystem
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq, Eq, Hash)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    LibrarySync,
+    AiAnalysis,
+    LibraryStats,
+    EmbeddingIndex,
+    VisualizationRebuild,
+    DailyMix,
+    LoudnessAnalysis,
+    VectorIndexRebuild,
+    ArtistMetadata,
+    EmbeddingMigration,
+    TrackMetadata,
+    DuplicateScan,
+}
+
+impl std::fmt::Display for JobKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobKind::LibrarySync => write!(f, "library_sync"),
+            JobKind::AiAnalysis => write!(f, "ai_analysis"),
+            JobKind::LibraryStats => write!(f, "library_stats"),
+            JobKind::EmbeddingIndex => write!(f, "embedding_index"),
+            JobKind::VisualizationRebuild => write!(f, "visualization_rebuild"),
+            JobKind::DailyMix => write!(f, "daily_mix"),
+            JobKind::LoudnessAnalysis => write!(f, "loudness_analysis"),
+            JobKind::VectorIndexRebuild => write!(f, "vector_index_rebuild"),
+            JobKind::ArtistMetadata => write!(f, "artist_metadata"),
+            JobKind::EmbeddingMigration => write!(f, "embedding_migration"),
+            JobKind::TrackMetadata => write!(f, "track_metadata"),
+            JobKind::DuplicateScan => write!(f, "duplicate_scan"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Whether the job has finished (successfully or not)
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+impl std::fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobStatus::Queued => write!(f, "queued"),
+            JobStatus::Running => write!(f, "running"),
+            JobStatus::Completed => write!(f, "completed"),
+            JobStatus::Failed => write!(f, "failed"),
+            JobStatus::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Job {
+    pub id: Uuid,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub payload: serde_json::Value,
+    pub result: Option<serde_json::Value>,
+    pub progress: f64,
+    pub message: Option<String>,
+    pub error: Option<String>,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Live job state change, broadcast to in-process subscribers
+#[derive(Debug, Clone, Serialize)]
+pub struct JobUpdate {
+    pub job_id: Uuid,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub progress: f64,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct JobLog {
+    pub id: i64,
+    pub job_id: Uuid,
+    pub level: String,
+    pub message: String,
+    pub created_at: DateTime<Utc>,
+}
@@ -162,6 +162,9 @@ pub struct TrackAnalysisResult {
     pub themes: Vec<String>,
     pub acousticness: Option<f64>,
     pub instrumentalness: Option<f64>,
+    /// Whether the lyrics are explicit; None when the model doesn't know them
+    #[serde(default)]
+    pub explicit: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -178,7 +181,7 @@ pub struct QueryAnalysisResult {
     pub confidence: f32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct QueryFilters {
     pub genres: Option<Vec<String>>,
     pub artists: Option<Vec<String>>,
@@ -0,0 +1,44 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// A track a user heard on a station
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ListeningHistoryEntry {
+    pub id: i64,
+    pub station_id: Uuid,
+    pub station_name: String,
+    pub track_id: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub first_heard_at: DateTime<Utc>,
+    pub last_heard_at: DateTime<Utc>,
+    /// Approximate seconds listened (heartbeat resolution)
+    pub listened_secs: i64,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct FavoriteTrack {
+    pub track_id: String,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct FavoriteStation {
+    pub station_id: Uuid,
+    pub path: String,
+    pub name: String,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Favorites {
+    pub stations: Vec<FavoriteStation>,
+    pub tracks: Vec<FavoriteTrack>,
+}
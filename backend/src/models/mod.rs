@@ -1,14 +1,50 @@
+pub mod ai_usage;
+pub mod analytics;
+pub mod api_key;
+pub mod audit;
+pub mod job;
 pub mod library;
+pub mod listening;
+pub mod navidrome_account;
+pub mod schedule;
+pub mod scrobble;
+pub mod session;
 pub mod user;
 pub mod station;
 pub mod track;
+pub mod track_request;
+pub mod webhook;
 
+pub use ai_usage::{AiUsage, DailyUsage, FeatureUsage};
+pub use analytics::{
+    AnalyticsWindow, ArtistPlays, HourlyPlays, RetentionPoint, StationAnalytics, StationEventKind, TrackSkipRate,
+};
+pub use api_key::{
+    ApiKey, CreateApiKeyRequest, CreateApiKeyResponse, CreateStreamTokenRequest,
+};
+pub use audit::AuditLogEntry;
+pub use job::{Job, JobKind, JobLog, JobStatus, JobUpdate};
 pub use library::{
     LibraryTrack, LibraryStats, LibrarySyncStatus,
     TrackAnalysisRequest, TrackAnalysisResult, QueryAnalysisResult,
     QueryFilters, TrackSelectionResult, SyncProgress, CurationProgress,
     EmbeddingProgress,
 };
-pub use user::{User, UserRole, UserInfo, CreateUserRequest, LoginRequest, AuthResponse};
-pub use station::{Station, SelectionMode, CreateStationRequest, UpdateStationRequest};
+pub use listening::{FavoriteStation, FavoriteTrack, Favorites, ListeningHistoryEntry};
+pub use navidrome_account::{ConnectNavidromeRequest, NavidromeAccount};
+pub use schedule::{ScheduleBlock, ScheduleBlockRequest};
+pub use scrobble::{ConnectListenBrainzRequest, ScrobbleAccount};
+pub use user::{
+    User, UserRole, UserInfo, UserPreferences, CreateUserRequest, LoginRequest,
+    AuthResponse, AdminCreateUserRequest, UpdateUserRequest, DeleteAccountRequest, STREAM_BITRATES,
+};
+pub use session::{AdminSession, DeviceInfo, UserSession};
+pub use station::{
+    Station, StationConfig, SelectionMode, CreateStationRequest, UpdateStationRequest, StationCollaborator,
+    StationRole, AddCollaboratorRequest, ImportPlaylistRequest, StationImportRequest, AudioCodec,
+};
 pub use track::{Track, TrackInfo, NowPlaying};
+pub use track_request::{CreateTrackRequest, TrackRequest};
+pub use webhook::{
+    CreateWebhookRequest, UpdateWebhookRequest, Webhook, WebhookDelivery, WebhookEvent,
+};
@@ -0,0 +1,26 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+/// A user's own Navidrome account (the stored secret is never returned)
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct NavidromeAccount {
+    pub navidrome_username: String,
+    /// "password" or "api_key"
+    pub auth_type: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub last_verified_at: Option<DateTime<Utc>>,
+}
+
+/// Connect a Navidrome account with either its password or an OpenSubsonic API key
+#[derive(Debug, Deserialize, Validate)]
+pub struct ConnectNavidromeRequest {
+    #[validate(length(min = 1, max = 255))]
+    pub username: String,
+    #[validate(length(min = 1, max = 1024))]
+    pub password: Option<String>,
+    #[validate(length(min = 1, max = 1024))]
+    pub api_key: Option<String>,
+}
@@ -0,0 +1,243 @@
+use chrono::{DateTime, Datelike, NaiveDateTime, NaiveTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use validator::Validate;
+
+use super::station::Station;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+const MINUTES_PER_WEEK: u32 = 7 * MINUTES_PER_DAY;
+
+/// Auto-curated blocks are curated this long before they come on air
+const CURATION_LEAD_MINUTES: i64 = 15;
+
+/// Pools curated longer ago than this are stale for the next airing
+const CURATION_MAX_AGE_HOURS: i64 = 12;
+
+/// A time window in which a station plays from its own genres or track pool, in the
+/// station's timezone (`config.timezone`). With a curation prompt, the pool is curated
+/// anew before each airing.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ScheduleBlock {
+    pub id: Uuid,
+    pub station_id: Uuid,
+    pub name: String,
+    /// Days the block starts on, 0 = Monday .. 6 = Sunday (empty = every day)
+    pub days: Vec<i16>,
+    pub start_time: NaiveTime,
+    /// Before `start_time` for blocks running past midnight; equal to it for all-day blocks
+    pub end_time: NaiveTime,
+    #[sqlx(json)]
+    pub genres: Vec<String>,
+    /// Played in order instead of the station's tracks (takes precedence over `genres`)
+    #[sqlx(json)]
+    pub track_ids: Vec<String>,
+    /// Description the hybrid curator fills `track_ids` from, e.g. "upbeat morning pop"
+    pub curation_prompt: Option<String>,
+    /// When `track_ids` were last curated from the prompt
+    pub curated_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ScheduleBlock {
+    /// Whether the block is on air at a time in the station's timezone
+    pub fn is_active(&self, local: NaiveDateTime) -> bool {
+        let now = minute_of_week(local.weekday().num_days_from_monday(), local.time());
+        week_ranges(&self.days, self.start_time, self.end_time)
+            .iter()
+            .any(|(start, end)| (*start..*end).contains(&now))
+    }
+
+    /// Whether the block's pool should be curated from its prompt now: it is on air or
+    /// about to be, and wasn't curated for this airing
+    pub fn needs_curation(&self, local: NaiveDateTime, now: DateTime<Utc>) -> bool {
+        self.curation_prompt.is_some()
+            && (self.is_active(local) || self.is_active(local + chrono::Duration::minutes(CURATION_LEAD_MINUTES)))
+            && self
+                .curated_at
+                .is_none_or(|at| now - at > chrono::Duration::hours(CURATION_MAX_AGE_HOURS))
+    }
+
+    /// The station as it plays during this block: its tracks, or its genres when it has
+    /// no tracks
+    pub fn apply(&self, station: &Station) -> Station {
+        let mut station = station.clone();
+        if self.track_ids.is_empty() {
+            station.genres = self.genres.clone();
+        }
+        station.track_ids = self.track_ids.clone();
+        station
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ScheduleBlockRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[serde(default)]
+    pub days: Vec<i16>,
+    /// "HH:MM:SS"
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    #[serde(default)]
+    pub track_ids: Vec<String>,
+    /// Curate the pool from this description before each airing (replaces `track_ids`)
+    #[validate(length(max = 500))]
+    pub curation_prompt: Option<String>,
+}
+
+impl ScheduleBlockRequest {
+    /// Checks beyond the field validators
+    pub fn check(&self) -> Result<(), String> {
+        if self.days.iter().any(|day| !(0..=6).contains(day)) {
+            return Err("days must be between 0 (Monday) and 6 (Sunday)".to_string());
+        }
+        if self.genres.is_empty() && self.track_ids.is_empty() && self.prompt().is_none() {
+            return Err("A program block needs genres, tracks or a curation prompt to play".to_string());
+        }
+        Ok(())
+    }
+
+    /// The curation prompt, unless blank
+    pub fn prompt(&self) -> Option<&str> {
+        self.curation_prompt.as_deref().map(str::trim).filter(|p| !p.is_empty())
+    }
+
+    /// Whether this block would be on air at the same time as `other` at some point
+    /// of the week
+    pub fn overlaps(&self, other: &ScheduleBlock) -> bool {
+        let ours = week_ranges(&self.days, self.start_time, self.end_time);
+        let theirs = week_ranges(&other.days, other.start_time, other.end_time);
+        ours.iter()
+            .any(|(start, end)| theirs.iter().any(|(s, e)| start < e && s < end))
+    }
+}
+
+fn minute_of_week(day: u32, time: NaiveTime) -> u32 {
+    day * MINUTES_PER_DAY + time.hour() * 60 + time.minute()
+}
+
+/// Minute ranges of the week (Monday 00:00 = 0) covered by a block. Blocks running past
+/// the end of Sunday are split in two.
+fn week_ranges(days: &[i16], start_time: NaiveTime, end_time: NaiveTime) -> Vec<(u32, u32)> {
+    let start = start_time.hour() * 60 + start_time.minute();
+    let end = end_time.hour() * 60 + end_time.minute();
+    let length = if end > start { end - start } else { end + MINUTES_PER_DAY - start };
+
+    let days: Vec<u32> = if days.is_empty() {
+        (0..7).collect()
+    } else {
+        days.iter().map(|day| *day as u32).collect()
+    };
+
+    let mut ranges = Vec::new();
+    for day in days {
+        let from = day * MINUTES_PER_DAY + start;
+        let to = from + length;
+        if to > MINUTES_PER_WEEK {
+            ranges.push((from, MINUTES_PER_WEEK));
+            ranges.push((0, to - MINUTES_PER_WEEK));
+        } else {
+            ranges.push((from, to));
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn block(days: Vec<i16>, start: NaiveTime, end: NaiveTime) -> ScheduleBlock {
+        ScheduleBlock {
+            id: Uuid::new_v4(),
+            station_id: Uuid::new_v4(),
+            name: "Block".to_string(),
+            days,
+            start_time: start,
+            end_time: end,
+            genres: vec!["Jazz".to_string()],
+            track_ids: Vec::new(),
+            curation_prompt: None,
+            curated_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_is_active() {
+        // 2024-01-01 is a Monday
+        let at = |day: u32, hour: u32, minute: u32| {
+            NaiveDate::from_ymd_opt(2024, 1, day).unwrap().and_time(time(hour, minute))
+        };
+
+        let mornings = block(vec![], time(6, 0), time(10, 0));
+        assert!(mornings.is_active(at(3, 6, 0)));
+        assert!(!mornings.is_active(at(3, 10, 0)));
+
+        // Friday and Saturday nights, running into the next morning
+        let nights = block(vec![4, 5], time(22, 0), time(2, 0));
+        assert!(nights.is_active(at(5, 23, 30)));
+        assert!(nights.is_active(at(7, 1, 0)));
+        assert!(!nights.is_active(at(8, 1, 0)));
+        assert!(!nights.is_active(at(4, 23, 0)));
+
+        // Sunday night wraps to Monday morning
+        let sunday = block(vec![6], time(23, 0), time(1, 0));
+        assert!(sunday.is_active(at(8, 0, 30)));
+
+        let all_day = block(vec![2], time(0, 0), time(0, 0));
+        assert!(all_day.is_active(at(3, 12, 0)));
+        assert!(!all_day.is_active(at(4, 0, 0)));
+    }
+
+    #[test]
+    fn test_needs_curation() {
+        let at = |hour: u32, minute: u32| NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_time(time(hour, minute));
+        let utc = |hour: u32, minute: u32| at(hour, minute).and_utc();
+
+        let mut mornings = block(vec![], time(6, 0), time(10, 0));
+        assert!(!mornings.needs_curation(at(5, 50), utc(5, 50)));
+
+        mornings.curation_prompt = Some("upbeat".to_string());
+        assert!(mornings.needs_curation(at(5, 50), utc(5, 50)));
+        assert!(!mornings.needs_curation(at(5, 30), utc(5, 30)));
+        assert!(!mornings.needs_curation(at(10, 0), utc(10, 0)));
+
+        // Curated for this airing, stale by the next one
+        mornings.curated_at = Some(utc(5, 50));
+        assert!(!mornings.needs_curation(at(9, 0), utc(9, 0)));
+        assert!(mornings.needs_curation(at(5, 50), utc(5, 50) + chrono::Duration::days(1)));
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let request = |days: Vec<i16>, start: NaiveTime, end: NaiveTime| ScheduleBlockRequest {
+            name: "Request".to_string(),
+            days,
+            start_time: start,
+            end_time: end,
+            genres: vec!["Rock".to_string()],
+            track_ids: Vec::new(),
+            curation_prompt: None,
+        };
+
+        let mornings = block(vec![], time(6, 0), time(10, 0));
+        assert!(!request(vec![], time(10, 0), time(18, 0)).overlaps(&mornings));
+        assert!(request(vec![1], time(9, 0), time(11, 0)).overlaps(&mornings));
+
+        let sunday_night = block(vec![6], time(22, 0), time(7, 0));
+        assert!(request(vec![0], time(6, 30), time(8, 0)).overlaps(&sunday_night));
+        assert!(!request(vec![1], time(6, 30), time(8, 0)).overlaps(&sunday_night));
+    }
+}
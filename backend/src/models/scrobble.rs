@@ -0,0 +1,20 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+/// A scrobble service account connected by a user (the stored token is never returned)
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ScrobbleAccount {
+    /// "listenbrainz"
+    pub service: String,
+    pub username: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Connect a ListenBrainz account with its user token
+#[derive(Debug, Deserialize, Validate)]
+pub struct ConnectListenBrainzRequest {
+    #[validate(length(min = 1, max = 255))]
+    pub token: String,
+}
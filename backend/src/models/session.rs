@@ -0,0 +1,40 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+/// A signed-in device: one per login, until it expires or is revoked
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct UserSession {
+    pub id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Whether this is the session making the request
+    pub current: bool,
+}
+
+/// An active session of any user, as listed to admins
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AdminSession {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub username: String,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Whether this is the session making the request
+    pub current: bool,
+}
+
+/// Where a login came from, recorded on its session
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub ip: IpAddr,
+    pub user_agent: Option<String>,
+}
@@ -4,6 +4,8 @@ use sqlx::FromRow;
 use uuid::Uuid;
 use validator::Validate;
 
+use super::user::STREAM_BITRATES;
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type, PartialEq)]
 #[sqlx(type_name = "text")]
 #[serde(rename_all = "snake_case")]
@@ -16,30 +18,394 @@ pub enum SelectionMode {
     Random,
     #[sqlx(rename = "hybrid")]
     Hybrid,
+    /// Once a track is picked, the rest of its album follows in order, gaplessly
+    #[sqlx(rename = "album_sequential")]
+    AlbumSequential,
+    /// Curated tracks play in list order (e.g. a journey from `POST /ai/journey`),
+    /// starting over after the last one
+    #[sqlx(rename = "journey")]
+    Journey,
+}
+
+/// Codec a station's HLS stream is encoded with
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioCodec {
+    /// MPEG audio segments, playable everywhere
+    #[default]
+    Mp3,
+    /// AAC-LC in ADTS segments
+    Aac,
+    /// Opus in fragmented MP4 segments (smallest at low bitrates)
+    Opus,
 }
 
+impl AudioCodec {
+    pub const ALL: &'static [AudioCodec] = &[AudioCodec::Mp3, AudioCodec::Aac, AudioCodec::Opus];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AudioCodec::Mp3 => "mp3",
+            AudioCodec::Aac => "aac",
+            AudioCodec::Opus => "opus",
+        }
+    }
+
+    /// File extension of the HLS segments
+    pub fn segment_extension(&self) -> &'static str {
+        match self {
+            AudioCodec::Mp3 => "mp3",
+            AudioCodec::Aac => "aac",
+            AudioCodec::Opus => "m4s",
+        }
+    }
+
+    /// Content type of the HLS segments
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            AudioCodec::Mp3 => "audio/mpeg",
+            AudioCodec::Aac => "audio/aac",
+            AudioCodec::Opus => "audio/mp4",
+        }
+    }
+}
+
+/// Output sample rates (Hz) a station can use
+pub const STATION_SAMPLE_RATES: &[u32] = &[22050, 32000, 44100, 48000];
+
+/// Longest crossfade between tracks
+pub const MAX_CROSSFADE_MS: u32 = 12_000;
+
+/// Upper bound for the track duration filters (2 hours)
+pub const MAX_TRACK_DURATION_SECS: u32 = 7200;
+
+/// Longest gap between spoken announcements
+pub const MAX_ANNOUNCE_EVERY: u32 = 20;
+
+/// Most plays a separation rule can span
+pub const MAX_SEPARATION: u32 = 100;
+
+/// Most lower-bitrate renditions a station can be encoded in besides its bitrate
+pub const MAX_VARIANT_BITRATES: usize = 2;
+
+/// Most tracks a station's fallback pool can hold
+pub const MAX_FALLBACK_TRACKS: usize = 100;
+
+/// Missing fields take their defaults, so clients can send only what they change
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct StationConfig {
+    pub codec: AudioCodec,
+    /// Stream bitrate in kbps
     pub bitrate: u32,
+    /// Lower bitrates (kbps) also encoded for adaptive playback on poor connections
+    pub variant_bitrates: Vec<u32>,
     pub sample_rate: u32,
     pub crossfade_ms: u32,
     pub track_selection_mode: SelectionMode,
     pub min_track_duration: u32,
     pub max_track_duration: u32,
-    pub explicit_content: bool,
+    /// Plays before a track can repeat
+    pub track_separation: u32,
+    /// Plays between two tracks by the same artist
+    pub artist_separation: u32,
+    /// Plays between two tracks from the same album (album-sequential stations
+    /// continue albums regardless)
+    pub album_separation: u32,
+    /// Play tracks marked explicit (by their tags or AI analysis)
+    #[serde(alias = "explicit_content")]
+    pub allow_explicit: bool,
+    /// Submit the station's plays to scrobble services (the server's account and
+    /// listeners who connected their own)
+    pub scrobble: bool,
+    /// IANA timezone the station's schedule is written in
+    pub timezone: String,
+    /// Speak a short link before every nth track (0 = never; needs a TTS backend)
+    pub announce_every: u32,
+    /// TTS voice of the announcements (empty = the server's default)
+    pub announcer_voice: String,
+    /// Let signed-in listeners request tracks, which play before curated ones
+    pub accept_requests: bool,
+    /// Record the broadcast to hourly archive files (needs ARCHIVE_DIR)
+    pub archive: bool,
+    /// Tracks played while the station's own can't be loaded (Navidrome errors) or
+    /// curation has none to offer
+    pub fallback_track_ids: Vec<String>,
 }
 
 impl Default for StationConfig {
     fn default() -> Self {
         Self {
+            codec: AudioCodec::Mp3,
             bitrate: 192,
+            variant_bitrates: Vec::new(),
             sample_rate: 44100,
             crossfade_ms: 0,
             track_selection_mode: SelectionMode::Random,
             min_track_duration: 60,
             max_track_duration: 600,
-            explicit_content: true,
+            track_separation: 20,
+            artist_separation: 5,
+            album_separation: 10,
+            allow_explicit: true,
+            scrobble: true,
+            timezone: "UTC".to_string(),
+            announce_every: 0,
+            announcer_voice: String::new(),
+            accept_requests: true,
+            archive: false,
+            fallback_track_ids: Vec::new(),
+        }
+    }
+}
+
+impl StationConfig {
+    /// Parse a config sent by a client. Unknown fields, wrong types and out-of-range
+    /// values are rejected with one message per problem.
+    pub fn from_json(value: serde_json::Value) -> Result<Self, Vec<String>> {
+        let serde_json::Value::Object(fields) = &value else {
+            return Err(vec!["config must be an object".to_string()]);
+        };
+
+        let schema = Self::json_schema();
+        let known = schema["properties"].as_object().cloned().unwrap_or_default();
+        let mut errors = Vec::new();
+        for (key, field) in fields {
+            if !known.contains_key(key) {
+                let expected: Vec<&str> = known.keys().map(String::as_str).collect();
+                errors.push(format!("unknown field `{}` (expected one of {})", key, expected.join(", ")));
+                continue;
+            }
+            // Parse each field on its own so every bad one is reported by name
+            let single = serde_json::Map::from_iter([(key.clone(), field.clone())]);
+            if let Err(e) = serde_json::from_value::<StationConfig>(single.into()) {
+                errors.push(format!("{}: {}", key, e));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let config: StationConfig = serde_json::from_value(value).map_err(|e| vec![e.to_string()])?;
+        let errors = config.validate();
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Range and consistency checks that the types alone don't cover
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !STREAM_BITRATES.contains(&self.bitrate) {
+            errors.push(format!("bitrate: must be one of {:?}", STREAM_BITRATES));
+        }
+        if self.variant_bitrates.len() > MAX_VARIANT_BITRATES {
+            errors.push(format!("variant_bitrates: must have at most {} entries", MAX_VARIANT_BITRATES));
+        }
+        if self
+            .variant_bitrates
+            .iter()
+            .enumerate()
+            .any(|(i, b)| !STREAM_BITRATES.contains(b) || *b >= self.bitrate || self.variant_bitrates[..i].contains(b))
+        {
+            errors.push(format!(
+                "variant_bitrates: must be distinct values of {:?} below bitrate",
+                STREAM_BITRATES
+            ));
+        }
+        if !STATION_SAMPLE_RATES.contains(&self.sample_rate) {
+            errors.push(format!("sample_rate: must be one of {:?}", STATION_SAMPLE_RATES));
         }
+        if self.crossfade_ms > MAX_CROSSFADE_MS {
+            errors.push(format!("crossfade_ms: must be at most {}", MAX_CROSSFADE_MS));
+        }
+        if self.max_track_duration == 0 || self.max_track_duration > MAX_TRACK_DURATION_SECS {
+            errors.push(format!(
+                "max_track_duration: must be between 1 and {}",
+                MAX_TRACK_DURATION_SECS
+            ));
+        }
+        if self.min_track_duration > self.max_track_duration {
+            errors.push("min_track_duration: must not exceed max_track_duration".to_string());
+        }
+        for (field, value) in [
+            ("track_separation", self.track_separation),
+            ("artist_separation", self.artist_separation),
+            ("album_separation", self.album_separation),
+        ] {
+            if value > MAX_SEPARATION {
+                errors.push(format!("{}: must be at most {}", field, MAX_SEPARATION));
+            }
+        }
+        if self.announce_every > MAX_ANNOUNCE_EVERY {
+            errors.push(format!("announce_every: must be at most {}", MAX_ANNOUNCE_EVERY));
+        }
+        if self.announcer_voice.len() > 100 {
+            errors.push("announcer_voice: must be at most 100 characters".to_string());
+        }
+        if self.fallback_track_ids.len() > MAX_FALLBACK_TRACKS {
+            errors.push(format!("fallback_track_ids: must have at most {} entries", MAX_FALLBACK_TRACKS));
+        }
+        if self.fallback_track_ids.iter().any(|id| id.trim().is_empty()) {
+            errors.push("fallback_track_ids: must not contain empty ids".to_string());
+        }
+        if self.timezone.parse::<chrono_tz::Tz>().is_err() {
+            errors.push("timezone: must be an IANA timezone such as Europe/Berlin".to_string());
+        }
+        errors
+    }
+
+    /// The station's timezone (UTC if it doesn't parse, which validation prevents)
+    pub fn tz(&self) -> chrono_tz::Tz {
+        self.timezone.parse().unwrap_or(chrono_tz::UTC)
+    }
+
+    /// JSON Schema (draft 2020-12) of the config, for clients that render it as a form
+    pub fn json_schema() -> serde_json::Value {
+        let defaults = Self::default();
+        serde_json::json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "StationConfig",
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "codec": {
+                    "type": "string",
+                    "title": "Codec",
+                    "enum": AudioCodec::ALL,
+                    "default": defaults.codec,
+                },
+                "bitrate": {
+                    "type": "integer",
+                    "title": "Bitrate (kbps)",
+                    "enum": STREAM_BITRATES,
+                    "default": defaults.bitrate,
+                },
+                "variant_bitrates": {
+                    "type": "array",
+                    "title": "Adaptive bitrates (kbps)",
+                    "description": "Lower bitrates also encoded, for listeners on poor connections. Must be below bitrate",
+                    "items": { "type": "integer", "enum": STREAM_BITRATES },
+                    "maxItems": MAX_VARIANT_BITRATES,
+                    "uniqueItems": true,
+                    "default": defaults.variant_bitrates,
+                },
+                "sample_rate": {
+                    "type": "integer",
+                    "title": "Sample rate (Hz)",
+                    "enum": STATION_SAMPLE_RATES,
+                    "default": defaults.sample_rate,
+                },
+                "crossfade_ms": {
+                    "type": "integer",
+                    "title": "Crossfade (ms)",
+                    "description": "Tracks from the same album always play back to back without a fade",
+                    "minimum": 0,
+                    "maximum": MAX_CROSSFADE_MS,
+                    "default": defaults.crossfade_ms,
+                },
+                "track_selection_mode": {
+                    "type": "string",
+                    "title": "Track selection",
+                    "enum": ["ai_contextual", "ai_embeddings", "random", "hybrid", "album_sequential", "journey"],
+                    "default": defaults.track_selection_mode,
+                },
+                "min_track_duration": {
+                    "type": "integer",
+                    "title": "Shortest track (seconds)",
+                    "description": "Must not exceed max_track_duration",
+                    "minimum": 0,
+                    "maximum": MAX_TRACK_DURATION_SECS,
+                    "default": defaults.min_track_duration,
+                },
+                "max_track_duration": {
+                    "type": "integer",
+                    "title": "Longest track (seconds)",
+                    "minimum": 1,
+                    "maximum": MAX_TRACK_DURATION_SECS,
+                    "default": defaults.max_track_duration,
+                },
+                "track_separation": {
+                    "type": "integer",
+                    "title": "Tracks before a repeat",
+                    "description": "A track is not played again within this many plays",
+                    "minimum": 0,
+                    "maximum": MAX_SEPARATION,
+                    "default": defaults.track_separation,
+                },
+                "artist_separation": {
+                    "type": "integer",
+                    "title": "Tracks between the same artist",
+                    "description": "An artist is not played again within this many plays (0 = no rule). Relaxed when no other track qualifies",
+                    "minimum": 0,
+                    "maximum": MAX_SEPARATION,
+                    "default": defaults.artist_separation,
+                },
+                "album_separation": {
+                    "type": "integer",
+                    "title": "Tracks between the same album",
+                    "description": "An album is not played again within this many plays (0 = no rule). Relaxed when no other track qualifies; album-sequential stations still finish albums",
+                    "minimum": 0,
+                    "maximum": MAX_SEPARATION,
+                    "default": defaults.album_separation,
+                },
+                "allow_explicit": {
+                    "type": "boolean",
+                    "title": "Allow explicit tracks",
+                    "description": "Tracks tagged explicit in Navidrome, or classified explicit by AI analysis, are never picked when off. Tracks of unknown status still play",
+                    "default": defaults.allow_explicit,
+                },
+                "scrobble": {
+                    "type": "boolean",
+                    "title": "Scrobble plays",
+                    "description": "Submit played tracks to ListenBrainz and Last.fm for the server, and to ListenBrainz for listeners who connected an account",
+                    "default": defaults.scrobble,
+                },
+                "timezone": {
+                    "type": "string",
+                    "title": "Timezone",
+                    "description": "IANA timezone (e.g. America/New_York) the station's program schedule follows",
+                    "default": defaults.timezone,
+                },
+                "announce_every": {
+                    "type": "integer",
+                    "title": "Announce every n tracks",
+                    "description": "Speak \"That was ..., up next ...\" before every nth track (0 = never). Requires a TTS backend on the server",
+                    "minimum": 0,
+                    "maximum": MAX_ANNOUNCE_EVERY,
+                    "default": defaults.announce_every,
+                },
+                "announcer_voice": {
+                    "type": "string",
+                    "title": "Announcer voice",
+                    "description": "Voice name of the TTS backend (empty for the server's default)",
+                    "maxLength": 100,
+                    "default": defaults.announcer_voice,
+                },
+                "accept_requests": {
+                    "type": "boolean",
+                    "title": "Accept track requests",
+                    "description": "Signed-in listeners can request tracks; the most-voted pending request plays next",
+                    "default": defaults.accept_requests,
+                },
+                "archive": {
+                    "type": "boolean",
+                    "title": "Archive broadcasts",
+                    "description": "Record the stream to hourly files with a track list (needs ARCHIVE_DIR on the server)",
+                    "default": defaults.archive,
+                },
+                "fallback_track_ids": {
+                    "type": "array",
+                    "title": "Fallback tracks",
+                    "description": "Navidrome track ids played when the station's tracks fail to load or curation finds none, until it recovers. Cached tracks keep playing through a Navidrome outage",
+                    "items": { "type": "string", "minLength": 1 },
+                    "maxItems": MAX_FALLBACK_TRACKS,
+                    "default": defaults.fallback_track_ids,
+                },
+            },
+        })
     }
 }
 
@@ -61,6 +427,14 @@ pub struct Station {
     pub config: StationConfig,
     #[sqlx(json)]
     pub track_ids: Vec<String>,
+    /// Set when this is a user's auto-generated Daily Mix
+    pub daily_mix_user_id: Option<Uuid>,
+    /// Navidrome playlist the station was last exported to
+    pub navidrome_playlist_id: Option<String>,
+    /// Navidrome playlist the station was imported from
+    pub source_playlist_id: Option<String>,
+    /// Whether the station's tracks follow changes to the source playlist
+    pub source_playlist_sync: bool,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -74,15 +448,158 @@ pub struct CreateStationRequest {
     #[validate(length(min = 1))]
     pub genres: Vec<String>,
     pub mood_tags: Option<Vec<String>>,
-    pub config: Option<StationConfig>,
+    /// Checked with [`StationConfig::from_json`]
+    pub config: Option<serde_json::Value>,
     pub track_ids: Option<Vec<String>>,
 }
 
+/// Create a station from a Navidrome playlist
+#[derive(Debug, Deserialize, Validate)]
+pub struct ImportPlaylistRequest {
+    #[validate(length(min = 1))]
+    pub playlist_id: String,
+    #[validate(length(min = 1, max = 100))]
+    pub path: String,
+    /// Defaults to the playlist name
+    #[validate(length(min = 1, max = 255))]
+    pub name: Option<String>,
+    /// Defaults to the playlist comment
+    #[validate(length(min = 10, max = 2000))]
+    pub description: Option<String>,
+    /// Defaults to the most common genres of the playlist's tracks
+    pub genres: Option<Vec<String>>,
+    /// Checked with [`StationConfig::from_json`]
+    pub config: Option<serde_json::Value>,
+    /// Keep the station's tracks in sync with the playlist
+    #[serde(default)]
+    pub sync: bool,
+}
+
+/// Create a station from a Navidrome playlist or an M3U file, matching its entries
+/// against the library. Exactly one of `playlist_id` and `m3u` is required.
+#[derive(Debug, Deserialize, Validate)]
+pub struct StationImportRequest {
+    #[validate(length(min = 1))]
+    pub playlist_id: Option<String>,
+    /// Contents of an M3U/M3U8 file
+    #[validate(length(min = 1, max = 2_000_000))]
+    pub m3u: Option<String>,
+    #[validate(length(min = 1, max = 100))]
+    pub path: String,
+    /// Defaults to the playlist name (required for M3U files without `#PLAYLIST:`)
+    #[validate(length(min = 1, max = 255))]
+    pub name: Option<String>,
+    #[validate(length(min = 10, max = 2000))]
+    pub description: Option<String>,
+    /// Defaults to the most common genres of the matched tracks
+    pub genres: Option<Vec<String>>,
+    /// Checked with [`StationConfig::from_json`]
+    pub config: Option<serde_json::Value>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct UpdateStationRequest {
     pub name: Option<String>,
     pub description: Option<String>,
     pub genres: Option<Vec<String>>,
     pub mood_tags: Option<Vec<String>>,
-    pub config: Option<StationConfig>,
+    /// Checked with [`StationConfig::from_json`]
+    pub config: Option<serde_json::Value>,
+}
+
+/// A role granted on a single station, independent of the user's global role
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum StationRole {
+    /// Edits the station's config, starts and stops it, skips, schedules and exports
+    #[default]
+    Curator,
+    /// Skips tracks
+    Moderator,
+}
+
+/// A user granted a role on a station by its creator or an admin
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct StationCollaborator {
+    pub station_id: Uuid,
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: StationRole,
+    pub granted_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct AddCollaboratorRequest {
+    #[validate(length(min = 1, max = 50))]
+    pub username: String,
+    #[serde(default)]
+    pub role: StationRole,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_partial_config_takes_defaults() {
+        let config = StationConfig::from_json(json!({ "bitrate": 128 })).unwrap();
+        assert_eq!(config.bitrate, 128);
+        assert_eq!(config.sample_rate, StationConfig::default().sample_rate);
+        assert_eq!(config.codec, AudioCodec::Mp3);
+
+        let config = StationConfig::from_json(json!({ "codec": "opus" })).unwrap();
+        assert_eq!(config.codec, AudioCodec::Opus);
+        assert!(StationConfig::from_json(json!({ "codec": "flac" })).is_err());
+    }
+
+    #[test]
+    fn test_every_problem_is_reported() {
+        let errors = StationConfig::from_json(json!({
+            "bitrate": "fast",
+            "track_selection_mode": "shuffle",
+            "volume": 11,
+        }))
+        .unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert!(errors.iter().any(|e| e.starts_with("bitrate:")));
+        assert!(errors.iter().any(|e| e.starts_with("track_selection_mode:")));
+        assert!(errors.iter().any(|e| e.contains("unknown field `volume`")));
+    }
+
+    #[test]
+    fn test_ranges_are_checked() {
+        let errors = StationConfig::from_json(json!({
+            "bitrate": 100,
+            "min_track_duration": 900,
+            "max_track_duration": 300,
+            "artist_separation": 500,
+        }))
+        .unwrap_err();
+        assert!(errors.iter().any(|e| e.starts_with("bitrate:")));
+        assert!(StationConfig::from_json(json!({ "bitrate": 128, "variant_bitrates": [192] })).is_err());
+        assert!(StationConfig::from_json(json!({ "bitrate": 128, "variant_bitrates": [64, 64] })).is_err());
+        assert!(StationConfig::from_json(json!({ "bitrate": 192, "variant_bitrates": [128, 64] })).is_ok());
+        assert!(StationConfig::from_json(json!({ "fallback_track_ids": ["t1", " "] })).is_err());
+        assert!(errors.iter().any(|e| e.starts_with("min_track_duration:")));
+        assert!(errors.iter().any(|e| e.starts_with("artist_separation:")));
+    }
+
+    #[test]
+    fn test_stored_explicit_content_setting_is_read() {
+        let config: StationConfig = serde_json::from_value(json!({ "explicit_content": false })).unwrap();
+        assert!(!config.allow_explicit);
+    }
+
+    #[test]
+    fn test_schema_lists_every_field() {
+        let schema = StationConfig::json_schema();
+        let properties = schema["properties"].as_object().unwrap();
+        let serialized = serde_json::to_value(StationConfig::default()).unwrap();
+        for key in serialized.as_object().unwrap().keys() {
+            assert!(properties.contains_key(key), "schema is missing {}", key);
+        }
+    }
 }
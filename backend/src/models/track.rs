@@ -19,6 +19,16 @@ pub struct Track {
     pub last_synced: DateTime<Utc>,
 }
 
+/// `Track::metadata` key of the explicit-content status Navidrome reports
+pub const EXPLICIT_METADATA_KEY: &str = "explicit";
+
+impl Track {
+    /// Whether the track is tagged explicit (Some(false) for clean), if Navidrome says
+    pub fn explicit(&self) -> Option<bool> {
+        self.metadata.as_ref()?.get(EXPLICIT_METADATA_KEY)?.parse().ok()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NowPlaying {
     pub track: TrackInfo,
@@ -0,0 +1,32 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use validator::Validate;
+
+/// A listener's request for a track on a station
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct TrackRequest {
+    pub id: Uuid,
+    pub station_id: Uuid,
+    pub track_id: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub requested_by: Uuid,
+    pub requested_by_username: String,
+    /// `pending`, `played` or `expired`
+    pub status: String,
+    /// Including the requester's
+    pub votes: i64,
+    /// Whether the caller voted for it
+    pub voted: bool,
+    pub created_at: DateTime<Utc>,
+    pub played_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateTrackRequest {
+    #[validate(length(min = 1, max = 255))]
+    pub track_id: String,
+}
@@ -9,13 +9,36 @@ use validator::Validate;
 #[serde(rename_all = "lowercase")]
 pub enum UserRole {
     Admin,
+    /// Creates and manages their own stations
+    Curator,
+    /// Approves requests and skips tracks
+    Moderator,
     Listener,
 }
 
+impl UserRole {
+    /// Position in the role hierarchy (admin > curator > moderator > listener)
+    fn level(&self) -> u8 {
+        match self {
+            UserRole::Admin => 3,
+            UserRole::Curator => 2,
+            UserRole::Moderator => 1,
+            UserRole::Listener => 0,
+        }
+    }
+
+    /// Whether this role has at least the permissions of `role`
+    pub fn includes(&self, role: &UserRole) -> bool {
+        self.level() >= role.level()
+    }
+}
+
 impl std::fmt::Display for UserRole {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             UserRole::Admin => write!(f, "admin"),
+            UserRole::Curator => write!(f, "curator"),
+            UserRole::Moderator => write!(f, "moderator"),
             UserRole::Listener => write!(f, "listener"),
         }
     }
@@ -32,6 +55,45 @@ pub struct User {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub last_login: Option<DateTime<Utc>>,
+    #[sqlx(json)]
+    pub preferences: UserPreferences,
+    /// Set while an admin has disabled the account
+    pub disabled_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UiTheme {
+    #[default]
+    Dark,
+    Light,
+    System,
+}
+
+/// Stream bitrates (kbps) a user can choose as their preference
+pub const STREAM_BITRATES: &[u32] = &[64, 96, 128, 192, 256, 320];
+
+/// Settings that follow a user across devices
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserPreferences {
+    /// Station opened by default
+    pub default_station_id: Option<Uuid>,
+    /// Preferred stream bitrate in kbps (None = station default)
+    pub stream_bitrate: Option<u32>,
+    pub visualization_enabled: bool,
+    pub theme: UiTheme,
+}
+
+impl Default for UserPreferences {
+    fn default() -> Self {
+        Self {
+            default_station_id: None,
+            stream_bitrate: None,
+            visualization_enabled: true,
+            theme: UiTheme::default(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -42,7 +104,36 @@ pub struct CreateUserRequest {
     pub email: String,
     #[validate(length(min = 8))]
     pub password: String,
+}
+
+/// Admin-created account with an explicit role
+#[derive(Debug, Deserialize, Validate)]
+pub struct AdminCreateUserRequest {
+    #[validate(length(min = 3, max = 50))]
+    pub username: String,
+    #[validate(email)]
+    pub email: String,
+    #[validate(length(min = 8))]
+    pub password: String,
+    pub role: UserRole,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateUserRequest {
+    #[validate(email)]
+    pub email: Option<String>,
     pub role: Option<UserRole>,
+    pub disabled: Option<bool>,
+    /// Set a new password (e.g. for a user who is locked out)
+    #[validate(length(min = 8))]
+    pub password: Option<String>,
+}
+
+/// Confirms a user's request to delete their own account
+#[derive(Debug, Deserialize)]
+pub struct DeleteAccountRequest {
+    /// Not needed for accounts that only sign in through the OpenID Connect provider
+    pub password: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -53,7 +144,12 @@ pub struct LoginRequest {
 
 #[derive(Debug, Serialize)]
 pub struct AuthResponse {
+    /// Short-lived access token (JWT)
     pub token: String,
+    /// Seconds until `token` expires
+    pub expires_in: i64,
+    /// Exchanged for a new token pair at `/auth/refresh`; each can be used once
+    pub refresh_token: String,
     pub user: UserInfo,
 }
 
@@ -63,6 +159,7 @@ pub struct UserInfo {
     pub username: String,
     pub email: String,
     pub role: UserRole,
+    pub preferences: UserPreferences,
 }
 
 impl From<User> for UserInfo {
@@ -72,6 +169,7 @@ impl From<User> for UserInfo {
             username: user.username,
             email: user.email,
             role: user.role,
+            preferences: user.preferences,
         }
     }
 }
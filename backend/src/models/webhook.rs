@@ -0,0 +1,106 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use validator::Validate;
+
+/// Events that can be delivered to webhooks
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum WebhookEvent {
+    #[serde(rename = "track.changed")]
+    TrackChanged,
+    #[serde(rename = "station.started")]
+    StationStarted,
+    #[serde(rename = "station.stopped")]
+    StationStopped,
+    #[serde(rename = "sync.completed")]
+    SyncCompleted,
+    #[serde(rename = "curation.completed")]
+    CurationCompleted,
+    /// An embedding backfill or model migration finished
+    #[serde(rename = "embeddings.completed")]
+    EmbeddingsCompleted,
+    /// A broadcast stalled or went silent and the watchdog stepped in
+    #[serde(rename = "stream.unhealthy")]
+    StreamUnhealthy,
+    #[serde(rename = "stream.recovered")]
+    StreamRecovered,
+    #[serde(rename = "error")]
+    Error,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::TrackChanged => "track.changed",
+            WebhookEvent::StationStarted => "station.started",
+            WebhookEvent::StationStopped => "station.stopped",
+            WebhookEvent::SyncCompleted => "sync.completed",
+            WebhookEvent::CurationCompleted => "curation.completed",
+            WebhookEvent::EmbeddingsCompleted => "embeddings.completed",
+            WebhookEvent::StreamUnhealthy => "stream.unhealthy",
+            WebhookEvent::StreamRecovered => "stream.recovered",
+            WebhookEvent::Error => "error",
+        }
+    }
+}
+
+impl std::fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    #[sqlx(json)]
+    pub events: Vec<WebhookEvent>,
+    pub active: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    /// Whether this webhook is subscribed to the given event (empty filter = all events)
+    pub fn wants(&self, event: WebhookEvent) -> bool {
+        self.active && (self.events.is_empty() || self.events.contains(&event))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateWebhookRequest {
+    #[validate(url)]
+    pub url: String,
+    #[validate(length(min = 16, max = 255))]
+    pub secret: String,
+    pub events: Option<Vec<WebhookEvent>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateWebhookRequest {
+    #[validate(url)]
+    pub url: Option<String>,
+    #[validate(length(min = 16, max = 255))]
+    pub secret: Option<String>,
+    pub events: Option<Vec<WebhookEvent>>,
+    pub active: Option<bool>,
+}
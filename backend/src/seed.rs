@@ -0,0 +1,203 @@
+//! Demo data for local development (`--seed-demo`)
+//!
+//! Creates sample users, a synthetic library index with AI metadata and audio
+//! embeddings, and a couple of stations, so the frontend and API can be worked on
+//! without a real Navidrome server. Seeding is idempotent: existing rows are left
+//! untouched, so it can be re-run safely.
+
+use crate::models::station::StationConfig;
+use crate::models::UserRole;
+use crate::services::AuthService;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use sqlx::PgPool;
+
+/// Embedding dimension of `track_embeddings.embedding`
+const EMBEDDING_DIM: usize = 100;
+
+/// Tracks generated per demo artist
+const TRACKS_PER_ARTIST: usize = 12;
+
+pub const DEMO_ADMIN: (&str, &str) = ("demo-admin", "demo-admin-password");
+pub const DEMO_LISTENER: (&str, &str) = ("demo-listener", "demo-listener-password");
+
+/// (genre, artists, moods, energy range)
+type DemoGenre = (&'static str, &'static [&'static str], &'static [&'static str], (f64, f64));
+
+const DEMO_GENRES: &[DemoGenre] = &[
+    ("Electronic", &["Neon Drift", "Pulse Theory", "Circuit Bloom"], &["energetic", "hypnotic"], (0.6, 0.95)),
+    ("Jazz", &["The Blue Hour Trio", "Marla Quinn"], &["smooth", "relaxed"], (0.2, 0.5)),
+    ("Rock", &["Broken Compass", "The Static Lines", "Iron Orchard"], &["energetic", "rebellious"], (0.6, 0.9)),
+    ("Ambient", &["Low Tide", "Glass Meadow"], &["calm", "dreamy"], (0.05, 0.3)),
+    ("Hip-Hop", &["Verse Theory", "K. Mercer"], &["confident", "upbeat"], (0.5, 0.85)),
+];
+
+const TITLE_WORDS: &[&str] = &[
+    "Midnight", "Echo", "Signal", "Golden", "Harbor", "Static", "Velvet", "Horizon",
+    "Paper", "Ghost", "Summer", "Wire", "River", "Neon", "Quiet", "Orbit",
+];
+
+pub async fn seed_demo(db: &PgPool, auth_service: &AuthService) -> anyhow::Result<()> {
+    let mut rng = StdRng::seed_from_u64(42);
+
+    // Users
+    let admin_id = upsert_user(db, auth_service, DEMO_ADMIN, "admin@demo.local", UserRole::Admin).await?;
+    upsert_user(db, auth_service, DEMO_LISTENER, "listener@demo.local", UserRole::Listener).await?;
+    tracing::info!("Seeded demo users");
+
+    // Library index + embeddings (embeddings cluster by genre so similarity search is meaningful)
+    let mut tracks_by_genre: Vec<(&str, Vec<String>)> = Vec::new();
+    let mut track_number = 0;
+
+    for (genre_index, (genre, artists, moods, (energy_min, energy_max))) in DEMO_GENRES.iter().enumerate() {
+        let centroid: Vec<f32> = (0..EMBEDDING_DIM)
+            .map(|d| if d % DEMO_GENRES.len() == genre_index { 1.0 } else { 0.0 })
+            .collect();
+        let mut genre_tracks = Vec::new();
+
+        for artist in artists.iter() {
+            let album = format!("{} {}", TITLE_WORDS[rng.gen_range(0..TITLE_WORDS.len())], "Sessions");
+            let year = rng.gen_range(1975..2025);
+
+            for n in 0..TRACKS_PER_ARTIST {
+                track_number += 1;
+                let id = format!("demo-{:04}", track_number);
+                let title = format!(
+                    "{} {}",
+                    TITLE_WORDS[rng.gen_range(0..TITLE_WORDS.len())],
+                    TITLE_WORDS[rng.gen_range(0..TITLE_WORDS.len())]
+                );
+
+                sqlx::query(
+                    r#"
+                    INSERT INTO library_index (
+                        id, title, artist, album, year, duration, genres, mood_tags,
+                        energy_level, danceability, valence, tempo, path, ai_analyzed
+                    )
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, true)
+                    ON CONFLICT (id) DO NOTHING
+                    "#,
+                )
+                .bind(&id)
+                .bind(&title)
+                .bind(artist)
+                .bind(&album)
+                .bind(year)
+                .bind(rng.gen_range(150..420))
+                .bind(serde_json::json!([genre]))
+                .bind(serde_json::json!(moods))
+                .bind(rng.gen_range(*energy_min..*energy_max))
+                .bind(rng.gen_range(0.2..0.9))
+                .bind(rng.gen_range(0.1..0.9))
+                .bind(rng.gen_range(70.0..160.0))
+                .bind(format!("demo/{}/{}/{:02} {}.mp3", artist, album, n + 1, title))
+                .execute(db)
+                .await?;
+
+                // Centroid plus noise, L2-normalized
+                let mut embedding: Vec<f32> = centroid
+                    .iter()
+                    .map(|c| c + rng.gen_range(-0.35..0.35))
+                    .collect();
+                let norm = embedding.iter().map(|v| v * v).sum::<f32>().sqrt().max(f32::EPSILON);
+                embedding.iter_mut().for_each(|v| *v /= norm);
+
+                let vector_str = format!(
+                    "[{}]",
+                    embedding.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",")
+                );
+
+                sqlx::query(
+                    r#"
+                    INSERT INTO track_embeddings (track_id, embedding, model_version)
+                    VALUES ($1, $2::vector, 'demo')
+                    ON CONFLICT (track_id) DO NOTHING
+                    "#,
+                )
+                .bind(&id)
+                .bind(&vector_str)
+                .execute(db)
+                .await?;
+
+                genre_tracks.push(id);
+            }
+        }
+
+        tracks_by_genre.push((*genre, genre_tracks));
+    }
+    tracing::info!("Seeded {} demo tracks with embeddings", track_number);
+
+    // Stations
+    let demo_stations = [
+        ("demo-electronic", "Demo Electronic", "Late-night synths and driving beats from the demo library.", "Electronic"),
+        ("demo-chill", "Demo Chill", "Ambient textures and smooth jazz for focused, quiet hours.", "Ambient"),
+    ];
+
+    for (path, name, description, genre) in demo_stations {
+        let track_ids: Vec<String> = tracks_by_genre
+            .iter()
+            .filter(|(g, _)| *g == genre || (genre == "Ambient" && *g == "Jazz"))
+            .flat_map(|(_, ids)| ids.iter().cloned())
+            .collect();
+
+        sqlx::query(
+            r#"
+            INSERT INTO stations (path, name, description, genres, mood_tags, created_by, config, track_ids)
+            VALUES ($1, $2, $3, $4, '[]'::jsonb, $5, $6, $7)
+            ON CONFLICT (path) DO NOTHING
+            "#,
+        )
+        .bind(path)
+        .bind(name)
+        .bind(description)
+        .bind(serde_json::json!([genre]))
+        .bind(admin_id)
+        .bind(serde_json::to_value(StationConfig::default())?)
+        .bind(serde_json::to_value(&track_ids)?)
+        .execute(db)
+        .await?;
+    }
+    tracing::info!("Seeded {} demo stations", demo_stations.len());
+
+    sqlx::query("UPDATE library_sync_status SET tracks_synced = $1, tracks_analyzed = $1 WHERE id = 1")
+        .bind(track_number)
+        .execute(db)
+        .await?;
+    sqlx::query("SELECT update_library_stats()").execute(db).await?;
+
+    tracing::info!(
+        "Demo data ready. Log in as {} / {} (admin) or {} / {} (listener)",
+        DEMO_ADMIN.0, DEMO_ADMIN.1, DEMO_LISTENER.0, DEMO_LISTENER.1
+    );
+    Ok(())
+}
+
+/// Insert a user if the username is free, returning the user's ID either way
+async fn upsert_user(
+    db: &PgPool,
+    auth_service: &AuthService,
+    (username, password): (&str, &str),
+    email: &str,
+    role: UserRole,
+) -> anyhow::Result<uuid::Uuid> {
+    if let Some(id) = sqlx::query_scalar("SELECT id FROM users WHERE username = $1")
+        .bind(username)
+        .fetch_optional(db)
+        .await?
+    {
+        return Ok(id);
+    }
+
+    let password_hash = auth_service.hash_password(password)?;
+    let id = sqlx::query_scalar(
+        "INSERT INTO users (username, email, password_hash, role) VALUES ($1, $2, $3, $4) RETURNING id",
+    )
+    .bind(username)
+    .bind(email)
+    .bind(password_hash)
+    .bind(role)
+    .fetch_one(db)
+    .await?;
+
+    Ok(id)
+}
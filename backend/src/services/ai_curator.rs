@@ -3,23 +3,28 @@ use crate::models::{
     CurationProgress, LibraryStats, LibraryTrack, QueryAnalysisResult,
     QueryFilters, TrackSelectionResult,
 };
-use sqlx::PgPool;
+use crate::services::llm::{LlmClient, LlmFeature, LlmRequest};
+use crate::services::ratings::RatingBias;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use std::sync::Arc;
 use tokio::sync::mpsc;
 use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Claude model used for query analysis, curation, seed selection and track analysis
+pub const CLAUDE_MODEL: &str = "claude-sonnet-4-5-20250929";
 
 /// Multi-layered AI music curator
 /// Uses Claude to intelligently analyze queries and select tracks
 pub struct AiCurator {
-    anthropic_api_key: String,
-    client: reqwest::Client,
+    llm: Arc<LlmClient>,
     db: PgPool,
 }
 
 impl AiCurator {
-    pub fn new(anthropic_api_key: String, db: PgPool) -> Self {
+    pub fn new(llm: Arc<LlmClient>, db: PgPool) -> Self {
         Self {
-            anthropic_api_key,
-            client: reqwest::Client::new(),
+            llm,
             db,
         }
     }
@@ -29,10 +34,19 @@ impl AiCurator {
     /// 1. Get library context
     /// 2. Analyze query to extract filters
     /// 3. Select and rank specific tracks
-    pub async fn curate_tracks(&self, query: String, limit: usize) -> Result<Vec<String>> {
+    ///
+    /// With `for_user`, that user's ratings personalize the result (see [`RatingBias`]).
+    /// Without `allow_explicit`, tracks marked explicit are left out.
+    pub async fn curate_tracks(
+        &self,
+        query: String,
+        limit: usize,
+        for_user: Option<Uuid>,
+        allow_explicit: bool,
+    ) -> Result<Vec<String>> {
         // Use the progress version but discard the receiver
         let (tx, _rx) = mpsc::channel(10);
-        self.curate_tracks_with_progress(query, limit, tx).await
+        self.curate_tracks_with_progress(query, limit, for_user, allow_explicit, tx).await
     }
 
     /// Curate tracks with progress updates via the provided channel
@@ -40,9 +54,15 @@ impl AiCurator {
         &self,
         query: String,
         limit: usize,
+        for_user: Option<Uuid>,
+        allow_explicit: bool,
         progress_tx: mpsc::Sender<CurationProgress>,
     ) -> Result<Vec<String>> {
         info!("Curating tracks for query: {}", query);
+        let bias = RatingBias::load(&self.db, for_user)
+            .await?
+            .with_explicit(&self.db, allow_explicit)
+            .await?;
 
         // Helper to send progress (ignoring errors if receiver dropped)
         let send_progress = |p: CurationProgress| {
@@ -71,7 +91,8 @@ impl AiCurator {
 
             // Get matching tracks using cached filters (cap to avoid API rate limits)
             let max_candidates = 100;  // Keep low to avoid rate limits
-            let tracks = self.get_matching_tracks(&cached.filters, max_candidates).await?;
+            let tracks = self.get_matching_tracks(&cached.filters, max_candidates, allow_explicit).await?;
+            let tracks = prioritize_candidates(tracks, &bias);
 
             // If we found tracks with cached filters, use them (skip Layer 2)
             if !tracks.is_empty() {
@@ -84,7 +105,9 @@ impl AiCurator {
                 }).await;
 
                 // Use AI for final selection with original query for strict matching
-                let result = self.ai_select_tracks(&query, tracks, actual_limit).await?;
+                let result = self
+                    .ai_select_tracks(&query, tracks, actual_limit, &bias, &progress_tx)
+                    .await?;
 
                 send_progress(CurationProgress::Completed {
                     message: "Curation complete!".to_string(),
@@ -126,8 +149,9 @@ impl AiCurator {
         // Cap candidates to avoid hitting API rate limits (100 track descriptions max)
         let max_candidates = 100;
         let candidate_tracks = self
-            .get_matching_tracks(&analysis.filters, max_candidates)
+            .get_matching_tracks(&analysis.filters, max_candidates, allow_explicit)
             .await?;
+        let candidate_tracks = prioritize_candidates(candidate_tracks, &bias);
 
         info!(
             "Found {} candidate tracks matching filters",
@@ -141,7 +165,7 @@ impl AiCurator {
                 filters_applied: None,
             }).await;
             // Fallback: just get random tracks from library
-            let result = self.get_random_tracks(limit).await?;
+            let result = bias.filter(self.get_random_tracks(limit).await?);
 
             send_progress(CurationProgress::Completed {
                 message: "Curation complete (using random selection)".to_string(),
@@ -163,7 +187,9 @@ impl AiCurator {
             )),
         }).await;
 
-        let result = self.ai_select_tracks(&query, candidate_tracks, limit).await?;
+        let result = self
+            .ai_select_tracks(&query, candidate_tracks, limit, &bias, &progress_tx)
+            .await?;
 
         send_progress(CurationProgress::Completed {
             message: "Curation complete!".to_string(),
@@ -298,7 +324,7 @@ Respond with ONLY a JSON object:
             query,  // Second instance for "Think about what genres" line
         );
 
-        let analysis = self.call_claude(&prompt).await?;
+        let analysis = self.call_claude(LlmFeature::QueryAnalysis, prompt).await?;
 
         Ok(analysis)
     }
@@ -307,79 +333,30 @@ Respond with ONLY a JSON object:
         &self,
         filters: &QueryFilters,
         limit: usize,
+        allow_explicit: bool,
     ) -> Result<Vec<LibraryTrack>> {
-        let mut query_parts = vec!["SELECT * FROM library_index WHERE 1=1".to_string()];
-
-        // Build dynamic SQL query based on filters
-        if let Some(genres) = &filters.genres {
-            if !genres.is_empty() {
-                query_parts.push(format!(
-                    "AND genres ?| ARRAY[{}]",
-                    genres
-                        .iter()
-                        .map(|g| format!("'{}'", g))
-                        .collect::<Vec<_>>()
-                        .join(",")
-                ));
-            }
-        }
+        let mut query = matching_tracks_query(filters, limit, allow_explicit);
 
-        if let Some(moods) = &filters.moods {
-            if !moods.is_empty() {
-                query_parts.push(format!(
-                    "AND mood_tags ?| ARRAY[{}]",
-                    moods
-                        .iter()
-                        .map(|m| format!("'{}'", m))
-                        .collect::<Vec<_>>()
-                        .join(",")
-                ));
-            }
-        }
-
-        if let Some((min_energy, max_energy)) = filters.energy_range {
-            // Include tracks with NULL energy_level (not yet analyzed)
-            query_parts.push(format!(
-                "AND (energy_level IS NULL OR energy_level BETWEEN {} AND {})",
-                min_energy, max_energy
-            ));
-        }
-
-        if let Some((min_year, max_year)) = filters.year_range {
-            // Include tracks with NULL year
-            query_parts.push(format!(
-                "AND (year IS NULL OR year BETWEEN {} AND {})",
-                min_year, max_year
-            ));
-        }
-
-        if let Some(min_rating) = filters.min_rating {
-            // Include tracks with NULL rating
-            query_parts.push(format!(
-                "AND (avg_rating IS NULL OR avg_rating >= {})",
-                min_rating
-            ));
-        }
-
-        query_parts.push(format!("LIMIT {}", limit));
-
-        let query_str = query_parts.join(" ");
-
-        info!("Executing track query: {}", query_str);
+        info!("Executing track query: {}", query.sql());
 
         // Execute the query
-        let tracks = sqlx::query_as::<_, LibraryTrack>(&query_str)
+        let tracks = query
+            .build_query_as::<LibraryTrack>()
             .fetch_all(&self.db)
             .await?;
 
         Ok(tracks)
     }
 
+    /// Layer 3. The model's reasoning is streamed into `AiSelectingTracks` progress
+    /// events as it writes it.
     async fn ai_select_tracks(
         &self,
         original_query: &str,
         candidates: Vec<LibraryTrack>,
         limit: usize,
+        bias: &RatingBias,
+        progress_tx: &mpsc::Sender<CurationProgress>,
     ) -> Result<Vec<String>> {
         info!(
             "Using AI to select best {} tracks from {} candidates (Layer 3)",
@@ -391,8 +368,12 @@ Respond with ONLY a JSON object:
         let candidate_descriptions: Vec<String> = candidates
             .iter()
             .map(|t| {
+                let liked = bias
+                    .boost(&t.id)
+                    .map(|rating| format!(" | Listener rated {:.0}/5", rating))
+                    .unwrap_or_default();
                 format!(
-                    "ID: {} | \"{}\" by {} ({}) | Genres: {} | Moods: {} | Energy: {:.2} | Year: {}",
+                    "ID: {} | \"{}\" by {} ({}) | Genres: {} | Moods: {} | Energy: {:.2} | Year: {}{}",
                     t.id,
                     t.title,
                     t.artist,
@@ -400,7 +381,8 @@ Respond with ONLY a JSON object:
                     t.genres.join(", "),
                     t.mood_tags.join(", "),
                     t.energy_level.unwrap_or(0.5),
-                    t.year.unwrap_or(0)
+                    t.year.unwrap_or(0),
+                    liked
                 )
             })
             .collect();
@@ -421,15 +403,16 @@ CRITICAL INSTRUCTIONS:
 5. Be STRICT - it's better to return fewer tracks than to include ones that break the vibe
 6. Only select tracks where the artist, genre, and mood genuinely match the request
 7. Use your knowledge of music to identify mismatches (e.g., Art Tatum is jazz, not hip hop)
+8. Among tracks that fit, prefer ones the listener rated highly
 
 Select up to {} tracks that GENUINELY match "{}".
 If fewer than {} tracks truly match, return fewer. Quality over quantity.
 
-Respond with ONLY a JSON object:
+Respond with ONLY a JSON object, starting with your reasoning:
 {{
+  "reasoning": "Brief explanation of why these tracks match and what was rejected",
   "selected_tracks": ["track_id_1", "track_id_2", ...],
-  "scores": [0.95, 0.88, ...],
-  "reasoning": "Brief explanation of why these tracks match and what was rejected"
+  "scores": [0.95, 0.88, ...]
 }}"#,
             original_query,
             candidate_descriptions.join("\n"),
@@ -438,64 +421,42 @@ Respond with ONLY a JSON object:
             limit
         );
 
-        let result: TrackSelectionResult = self.call_claude(&prompt).await?;
+        let candidate_count = candidates.len();
+        let request = LlmRequest::new(LlmFeature::TrackSelection, CLAUDE_MODEL, prompt).max_tokens(8192);
+        let (delta_tx, mut delta_rx) = mpsc::channel::<String>(64);
+
+        // Forward the reasoning whenever it grows; the track IDs after it aren't shown
+        let forward_reasoning = async {
+            let mut reply = String::new();
+            let mut shown = 0;
+            while let Some(delta) = delta_rx.recv().await {
+                reply.push_str(&delta);
+                if let Some(reasoning) = partial_json_string(&reply, "reasoning").filter(|r| r.len() > shown) {
+                    shown = reasoning.len();
+                    let _ = progress_tx
+                        .send(CurationProgress::AiSelectingTracks {
+                            message: "AI is selecting tracks...".to_string(),
+                            candidate_count,
+                            thinking: Some(reasoning),
+                        })
+                        .await;
+                }
+            }
+        };
+
+        let (response, ()) = tokio::join!(self.llm.complete_streaming(request, delta_tx), forward_reasoning);
+        let result: TrackSelectionResult = parse_reply(&response?)?;
 
-        Ok(result.selected_tracks)
+        Ok(bias.filter(result.selected_tracks))
     }
 
-    async fn call_claude<T: serde::de::DeserializeOwned>(&self, prompt: &str) -> Result<T> {
+    async fn call_claude<T: serde::de::DeserializeOwned>(&self, feature: LlmFeature, prompt: String) -> Result<T> {
+        // Enough for ~150 track IDs + scores in response
         let response = self
-            .client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", &self.anthropic_api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("content-type", "application/json")
-            .json(&serde_json::json!({
-                "model": "claude-sonnet-4-5-20250929",
-                "max_tokens": 8192,  // Enough for ~150 track IDs + scores in response
-                "messages": [{
-                    "role": "user",
-                    "content": prompt
-                }]
-            }))
-            .send()
-            .await
-            .map_err(|e| AppError::ExternalApi(format!("Failed to call Claude API: {}", e)))?;
-
-        // Check HTTP status code
-        let status = response.status();
-        if !status.is_success() {
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AppError::ExternalApi(format!(
-                "Claude API returned error status {}: {}",
-                status, error_text
-            )));
-        }
-
-        let response_json: serde_json::Value = response
-            .json()
-            .await
-            .map_err(|e| AppError::ExternalApi(format!("Failed to parse Claude response: {}", e)))?;
-
-        // Extract the text content from Claude's response
-        let content_text = response_json["content"][0]["text"]
-            .as_str()
-            .ok_or_else(|| AppError::ExternalApi("Invalid response format from Claude".to_string()))?;
-
-        // Strip markdown code fences if present (Claude sometimes wraps JSON in ```json ... ```)
-        let json_text = content_text
-            .trim()
-            .strip_prefix("```json")
-            .or_else(|| content_text.trim().strip_prefix("```"))
-            .map(|s| s.strip_suffix("```").unwrap_or(s))
-            .unwrap_or(content_text)
-            .trim();
-
-        // Parse the JSON from the text content
-        let result: T = serde_json::from_str(json_text)
-            .map_err(|e| AppError::ExternalApi(format!("Failed to parse Claude JSON response: {} | Response was: {}", e, json_text)))?;
-
-        Ok(result)
+            .llm
+            .complete(LlmRequest::new(feature, CLAUDE_MODEL, prompt).max_tokens(8192))
+            .await?;
+        parse_reply(&response)
     }
 
     async fn get_cached_query(&self, query_hash: &str) -> Result<Option<QueryAnalysisResult>> {
@@ -578,3 +539,225 @@ Respond with ONLY a JSON object:
         Ok(tracks)
     }
 }
+
+/// Parse the JSON object of a reply
+fn parse_reply<T: serde::de::DeserializeOwned>(content_text: &str) -> Result<T> {
+    // Strip markdown code fences if present (Claude sometimes wraps JSON in ```json ... ```)
+    let json_text = content_text
+        .trim()
+        .strip_prefix("```json")
+        .or_else(|| content_text.trim().strip_prefix("```"))
+        .map(|s| s.strip_suffix("```").unwrap_or(s))
+        .unwrap_or(content_text)
+        .trim();
+
+    // Parse the JSON from the text content
+    serde_json::from_str(json_text)
+        .map_err(|e| AppError::ExternalApi(format!("Failed to parse Claude JSON response: {} | Response was: {}", e, json_text)))
+}
+
+/// The value of a string field in a JSON object that is still being written: as much of
+/// it as has arrived, or None until its opening quote has
+fn partial_json_string(text: &str, key: &str) -> Option<String> {
+    let after_key = &text[text.find(&format!("\"{}\"", key))? + key.len() + 2..];
+    let value = after_key.trim_start().strip_prefix(':')?.trim_start().strip_prefix('"')?;
+
+    let mut result = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some('r') => {}
+                Some('u') => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    match u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                        Some(decoded) if code.len() == 4 => result.push(decoded),
+                        _ => break,
+                    }
+                }
+                Some(escaped) => result.push(escaped),
+                None => break,
+            },
+            c => result.push(c),
+        }
+    }
+    Some(result)
+}
+
+/// Drop tracks the listener rated 1 star or less and move their highly rated tracks
+/// to the front, so the model sees them first
+fn prioritize_candidates(mut candidates: Vec<LibraryTrack>, bias: &RatingBias) -> Vec<LibraryTrack> {
+    candidates.retain(|t| !bias.is_excluded(&t.id));
+    candidates.sort_by_key(|t| bias.boost(&t.id).is_none());
+    candidates
+}
+
+/// Candidate query for the LLM's filters. Filter values come from the model, so they are
+/// always bound as parameters, never spliced into the SQL.
+fn matching_tracks_query(filters: &QueryFilters, limit: usize, allow_explicit: bool) -> QueryBuilder<'_, Postgres> {
+    let mut query = QueryBuilder::new("SELECT * FROM library_index WHERE 1=1");
+
+    if let Some(genres) = filters.genres.as_ref().filter(|g| !g.is_empty()) {
+        // MusicBrainz tags are lowercased; Navidrome genres are matched as tagged
+        let tags: Vec<String> = genres.iter().map(|g| g.trim().to_lowercase()).collect();
+        query
+            .push(" AND (genres ?| ")
+            .push_bind(genres)
+            .push(" OR musicbrainz_tags && ")
+            .push_bind(tags)
+            .push(")");
+    }
+
+    if let Some(moods) = filters.moods.as_ref().filter(|m| !m.is_empty()) {
+        query.push(" AND mood_tags ?| ").push_bind(moods);
+    }
+
+    if let Some((min_energy, max_energy)) = filters.energy_range {
+        // Include tracks with NULL energy_level (not yet analyzed)
+        query
+            .push(" AND (energy_level IS NULL OR energy_level BETWEEN ")
+            .push_bind(min_energy as f64)
+            .push(" AND ")
+            .push_bind(max_energy as f64)
+            .push(")");
+    }
+
+    if let Some((min_year, max_year)) = filters.year_range {
+        // Include tracks with NULL year; the first release year wins over the tagged one
+        query
+            .push(" AND (COALESCE(original_year, year) IS NULL OR COALESCE(original_year, year) BETWEEN ")
+            .push_bind(min_year)
+            .push(" AND ")
+            .push_bind(max_year)
+            .push(")");
+    }
+
+    if let Some((min_tempo, max_tempo)) = filters.tempo_range {
+        // Include tracks with unknown tempo
+        query
+            .push(" AND (tempo IS NULL OR tempo BETWEEN ")
+            .push_bind(min_tempo as f64)
+            .push(" AND ")
+            .push_bind(max_tempo as f64)
+            .push(")");
+    }
+
+    if let Some((min_valence, max_valence)) = filters.valence_range {
+        query
+            .push(" AND (valence IS NULL OR valence BETWEEN ")
+            .push_bind(min_valence as f64)
+            .push(" AND ")
+            .push_bind(max_valence as f64)
+            .push(")");
+    }
+
+    if let Some(min_rating) = filters.min_rating {
+        // Include tracks with NULL rating
+        query
+            .push(" AND (avg_rating IS NULL OR avg_rating >= ")
+            .push_bind(min_rating as f64)
+            .push(")");
+    }
+
+    if !allow_explicit {
+        // Tracks of unknown status are kept
+        query.push(" AND explicit IS NOT TRUE");
+    }
+
+    query.push(" LIMIT ").push_bind(limit as i64);
+    query
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_reasoning_is_read_while_streaming() {
+        assert_eq!(partial_json_string(r#"{ "reas"#, "reasoning"), None);
+        assert_eq!(partial_json_string(r#"{ "reasoning": "#, "reasoning"), None);
+        assert_eq!(
+            partial_json_string(r#"{ "reasoning": "Kept the \"quiet\" ones,\nskipped"#, "reasoning").as_deref(),
+            Some("Kept the \"quiet\" ones,\nskipped")
+        );
+        assert_eq!(
+            partial_json_string(r#"{"reasoning":"Café jazz", "selected_tracks": ["a"]}"#, "reasoning").as_deref(),
+            Some("Café jazz")
+        );
+        // An escape cut off mid-stream is left out until the rest arrives
+        assert_eq!(partial_json_string(r#"{"reasoning":"Caf\u00"#, "reasoning").as_deref(), Some("Caf"));
+    }
+
+    #[test]
+    fn test_hostile_filter_values_are_bound_not_spliced() {
+        let hostile = [
+            "Rock'n'Roll",
+            "'; DROP TABLE library_index; --",
+            "Children's\\",
+            "$1",
+        ];
+        let filters = QueryFilters {
+            genres: Some(hostile.iter().map(|g| g.to_string()).collect()),
+            moods: Some(vec!["Don't Stop".to_string()]),
+            ..QueryFilters::default()
+        };
+
+        let query = matching_tracks_query(&filters, 100, true);
+        let sql = query.sql();
+
+        assert_eq!(
+            sql,
+            "SELECT * FROM library_index WHERE 1=1 AND (genres ?| $1 OR musicbrainz_tags && $2) \
+             AND mood_tags ?| $3 LIMIT $4"
+        );
+        assert!(!sql.contains('\''));
+    }
+
+    #[test]
+    fn test_every_filter_gets_its_own_placeholder() {
+        let filters = QueryFilters {
+            genres: Some(vec!["jazz".to_string()]),
+            moods: Some(vec!["calm".to_string()]),
+            energy_range: Some((0.1, 0.4)),
+            year_range: Some((1955, 1965)),
+            tempo_range: Some((90.0, 120.0)),
+            valence_range: Some((0.2, 0.6)),
+            min_rating: Some(3.5),
+            ..QueryFilters::default()
+        };
+
+        let query = matching_tracks_query(&filters, 50, true);
+        let sql = query.sql();
+
+        for n in 1..=13 {
+            assert!(sql.contains(&format!("${}", n)), "${} missing from {}", n, sql);
+        }
+        assert!(!sql.contains("$14"));
+        assert!(!sql.contains("1955") && !sql.contains("0.4") && !sql.contains("120"));
+    }
+
+    #[test]
+    fn test_empty_lists_add_no_conditions() {
+        let filters = QueryFilters {
+            genres: Some(vec![]),
+            moods: Some(vec![]),
+            ..QueryFilters::default()
+        };
+
+        assert_eq!(
+            matching_tracks_query(&filters, 10, true).sql(),
+            "SELECT * FROM library_index WHERE 1=1 LIMIT $1"
+        );
+    }
+
+    #[test]
+    fn test_explicit_tracks_are_left_out_when_not_allowed() {
+        assert_eq!(
+            matching_tracks_query(&QueryFilters::default(), 10, false).sql(),
+            "SELECT * FROM library_index WHERE 1=1 AND explicit IS NOT TRUE LIMIT $1"
+        );
+    }
+}
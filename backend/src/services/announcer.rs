@@ -0,0 +1,333 @@
+//! Spoken track announcements
+//!
+//! With a text-to-speech backend configured (`ANNOUNCER_TTS_URL` or
+//! `ANNOUNCER_TTS_COMMAND`), stations that set `announce_every` get a short spoken
+//! link before every nth track: "That was X by A. Up next, Y by B." The audio pipeline
+//! requests it while it opens the track and plays it right before the track starts.
+//!
+//! The back-announcement and the intro are separate clips, so each is reused whenever
+//! the same track comes up again. Generated clips are kept in memory and, with
+//! `ANNOUNCER_CACHE_DIR` set, on disk.
+
+use crate::error::{AppError, Result};
+use crate::services::audio_cache::AudioCache;
+use crate::services::audio_pipeline;
+use crate::services::resampler::ResampleQuality;
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+/// Silence between the back-announcement and the intro
+const PAUSE_SECS: f32 = 0.3;
+
+/// Generated clips kept in memory (a few dozen KB each)
+const MEMORY_CLIPS: usize = 256;
+
+const SYNTHESIS_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Turns text into speech
+#[async_trait]
+pub trait SpeechBackend: Send + Sync {
+    /// Identifies the backend and its settings in cache keys, so changing them
+    /// generates new clips
+    fn id(&self) -> String;
+
+    /// Speak `text` in `voice`, returning audio in any format the pipeline can decode
+    async fn synthesize(&self, text: &str, voice: &str) -> Result<Vec<u8>>;
+}
+
+/// OpenAI's `/v1/audio/speech` API, which several self-hosted TTS servers offer too
+pub struct OpenAiSpeech {
+    http: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+impl OpenAiSpeech {
+    pub fn new(url: &str, api_key: Option<String>, model: String) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(SYNTHESIS_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            http,
+            url: url.trim_end_matches('/').to_string(),
+            api_key,
+            model,
+        }
+    }
+}
+
+#[async_trait]
+impl SpeechBackend for OpenAiSpeech {
+    fn id(&self) -> String {
+        format!("openai|{}|{}", self.url, self.model)
+    }
+
+    async fn synthesize(&self, text: &str, voice: &str) -> Result<Vec<u8>> {
+        let mut request = self.http.post(format!("{}/v1/audio/speech", self.url)).json(&serde_json::json!({
+            "model": self.model,
+            "input": text,
+            "voice": voice,
+            "response_format": "mp3",
+        }));
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| AppError::ExternalApi(format!("TTS request failed: {}", e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(AppError::ExternalApi(format!(
+                "TTS request failed: {}: {}",
+                status,
+                body.chars().take(200).collect::<String>()
+            )));
+        }
+
+        let audio = response
+            .bytes()
+            .await
+            .map_err(|e| AppError::ExternalApi(format!("Failed to read TTS audio: {}", e)))?;
+        Ok(audio.to_vec())
+    }
+}
+
+/// A local program, such as `piper` or `espeak-ng --stdout`, that reads text on stdin
+/// and writes audio to stdout. `{voice}` in its arguments is replaced with the voice
+/// (arguments left empty are dropped).
+pub struct CommandSpeech {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandSpeech {
+    pub fn new(command: &str) -> Self {
+        let mut words = command.split_whitespace().map(str::to_string);
+        Self {
+            program: words.next().unwrap_or_default(),
+            args: words.collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl SpeechBackend for CommandSpeech {
+    fn id(&self) -> String {
+        format!("command|{} {}", self.program, self.args.join(" "))
+    }
+
+    async fn synthesize(&self, text: &str, voice: &str) -> Result<Vec<u8>> {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| arg.replace("{voice}", voice))
+            .filter(|arg| !arg.is_empty());
+        let mut child = tokio::process::Command::new(&self.program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| AppError::ExternalApi(format!("Failed to run {}: {}", self.program, e)))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(text.as_bytes())
+                .await
+                .map_err(|e| AppError::ExternalApi(format!("Failed to write to {}: {}", self.program, e)))?;
+        }
+
+        let output = tokio::time::timeout(SYNTHESIS_TIMEOUT, child.wait_with_output())
+            .await
+            .map_err(|_| AppError::ExternalApi(format!("{} timed out", self.program)))?
+            .map_err(|e| AppError::ExternalApi(format!("Failed to run {}: {}", self.program, e)))?;
+        if !output.status.success() || output.stdout.is_empty() {
+            return Err(AppError::ExternalApi(format!(
+                "{} failed ({}): {}",
+                self.program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(output.stdout)
+    }
+}
+
+/// Generated clips, oldest dropped first
+#[derive(Default)]
+struct ClipMemory {
+    clips: HashMap<String, Arc<Vec<u8>>>,
+    order: VecDeque<String>,
+}
+
+pub struct Announcer {
+    backend: Box<dyn SpeechBackend>,
+    /// Voice of stations that don't pick one (ANNOUNCER_VOICE)
+    default_voice: String,
+    disk: Option<AudioCache>,
+    memory: Mutex<ClipMemory>,
+}
+
+impl std::fmt::Debug for Announcer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Announcer")
+            .field("backend", &self.backend.id())
+            .field("default_voice", &self.default_voice)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Announcer {
+    pub fn new(backend: Box<dyn SpeechBackend>, default_voice: String, disk: Option<AudioCache>) -> Self {
+        Self {
+            backend,
+            default_voice,
+            disk,
+            memory: Mutex::new(ClipMemory::default()),
+        }
+    }
+
+    /// Decoded samples of the link into a track: the back-announcement of `previous`
+    /// (title, artist), if given, then the intro of the next track
+    pub async fn announcement(
+        &self,
+        previous: Option<(&str, &str)>,
+        next: (&str, &str),
+        voice: &str,
+        sample_rate: u32,
+        channels: usize,
+        resample_quality: ResampleQuality,
+    ) -> Result<Vec<f32>> {
+        let voice = if voice.is_empty() { self.default_voice.as_str() } else { voice };
+
+        let mut samples = Vec::new();
+        for (i, line) in script(previous, next).iter().enumerate() {
+            if i > 0 {
+                let pause = (PAUSE_SECS * sample_rate as f32) as usize * channels;
+                samples.resize(samples.len() + pause, 0.0);
+            }
+            let clip = self.clip(line, voice).await?;
+            let decoded = audio_pipeline::decode_clip(clip.to_vec(), sample_rate, channels, resample_quality).await?;
+            samples.extend(decoded);
+        }
+        Ok(samples)
+    }
+
+    /// Encoded speech of one line, from the caches or generated
+    async fn clip(&self, text: &str, voice: &str) -> Result<Arc<Vec<u8>>> {
+        let raw = format!("{}|{}|{}", self.backend.id(), voice, text);
+        let key = hex::encode(&Sha256::digest(raw.as_bytes())[..16]);
+
+        if let Some(clip) = self.memory.lock().unwrap().clips.get(&key) {
+            return Ok(clip.clone());
+        }
+        let cached = self.disk.as_ref().and_then(|disk| disk.get(&key));
+        let audio = match cached.map(std::fs::read) {
+            Some(Ok(audio)) => audio,
+            _ => {
+                let audio = self.backend.synthesize(text, voice).await?;
+                if let Some(disk) = &self.disk {
+                    store(disk, &key, &audio).await;
+                }
+                audio
+            }
+        };
+
+        let clip = Arc::new(audio);
+        let mut memory = self.memory.lock().unwrap();
+        if memory.clips.insert(key.clone(), clip.clone()).is_none() {
+            memory.order.push_back(key);
+        }
+        while memory.order.len() > MEMORY_CLIPS {
+            if let Some(oldest) = memory.order.pop_front() {
+                memory.clips.remove(&oldest);
+            }
+        }
+        Ok(clip)
+    }
+}
+
+async fn store(disk: &AudioCache, key: &str, audio: &[u8]) {
+    let temp_path = disk.temp_path(key);
+    if let Err(e) = tokio::fs::write(&temp_path, audio).await {
+        tracing::warn!("Failed to cache announcement: {}", e);
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return;
+    }
+    if let Err(e) = disk.insert(key, "clip", &temp_path) {
+        tracing::warn!("{}", e);
+    }
+}
+
+/// The lines spoken before a track
+fn script(previous: Option<(&str, &str)>, next: (&str, &str)) -> Vec<String> {
+    let mut lines = Vec::new();
+    if let Some((title, artist)) = previous {
+        lines.push(format!("That was {} by {}.", spoken_title(title), artist));
+    }
+    lines.push(format!("Up next, {} by {}.", spoken_title(next.0), next.1));
+    lines
+}
+
+/// A title without trailing annotations like "(Remastered 2011)" or "[Live]"
+fn spoken_title(title: &str) -> &str {
+    let mut spoken = title.trim();
+    while let Some(open) = spoken.rfind(['(', '[']) {
+        if open == 0 || !spoken.ends_with([')', ']']) {
+            break;
+        }
+        spoken = spoken[..open].trim_end();
+    }
+    let spoken = spoken.trim_end_matches([' ', '-']);
+    if spoken.is_empty() {
+        title.trim()
+    } else {
+        spoken
+    }
+}
+
+/// How a station's pipeline announces tracks
+#[derive(Debug, Clone)]
+pub struct StationAnnouncer {
+    pub announcer: Arc<Announcer>,
+    /// Announce before every nth track
+    pub every: u32,
+    /// Empty for the default voice
+    pub voice: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spoken_title() {
+        assert_eq!(spoken_title("Heroes"), "Heroes");
+        assert_eq!(spoken_title("Heroes (2017 Remaster)"), "Heroes");
+        assert_eq!(spoken_title("Song [Live] (Deluxe Edition)"), "Song");
+        assert_eq!(spoken_title("Song - (Bonus Track)"), "Song");
+        assert_eq!(spoken_title("(Untitled)"), "(Untitled)");
+        assert_eq!(spoken_title("Pt. (2) Reprise"), "Pt. (2) Reprise");
+    }
+
+    #[test]
+    fn test_script() {
+        assert_eq!(script(None, ("Heroes", "David Bowie")), vec!["Up next, Heroes by David Bowie."]);
+        assert_eq!(
+            script(Some(("Hyperballad", "Björk")), ("Heroes (Remastered)", "David Bowie")),
+            vec!["That was Hyperballad by Björk.", "Up next, Heroes by David Bowie."]
+        );
+    }
+}
@@ -1,16 +1,31 @@
 //! Audio Broadcaster Service
 //!
 //! Encodes PCM audio from the pipeline and broadcasts via HLS (HTTP Live Streaming).
-//! Creates MP3 segments and generates m3u8 playlists for clients.
+//! Creates MP3, AAC or Opus segments (see [`audio_codec`]) and generates m3u8
+//! playlists for clients. MP3 and AAC segments start with a timed ID3 tag naming the
+//! track (see [`id3`]), so players can show now playing in step with the audio.
+//!
+//! Stations can also be encoded at lower variant bitrates from the same PCM, each with
+//! its own segment window; the master playlist lists them all so players switch
+//! between them as their connection allows. Variant segments share the main
+//! rendition's sequence numbers, so a switch lands on the same audio.
+//!
+//! With a spill directory configured, the segment window is also written to disk, so a
+//! restarted instance serves the previous window (instead of 404s) while it catches up.
+//! Only the main rendition is spilled.
 
 #![allow(dead_code)]
 
-use crate::error::Result;
+use crate::error::{AppError, Result};
+use crate::models::AudioCodec;
+use crate::services::audio_codec;
 use crate::services::audio_pipeline::{AudioPipeline, PipelineEvent, OUTPUT_CHANNELS, OUTPUT_SAMPLE_RATE};
-use mp3lame_encoder::{Builder, InterleavedPcm};
+use crate::services::id3::{self, TaggedTrack};
+use crate::services::resampler::ResampleQuality;
+use bytes::Bytes;
 use rustfft::{num_complex::Complex, FftPlanner};
 use std::collections::VecDeque;
-use std::mem::MaybeUninit;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::{broadcast, RwLock};
@@ -18,7 +33,7 @@ use tracing::{debug, error, info, warn};
 
 /// Message sent to the encoder thread
 enum EncoderMessage {
-    /// Encode these samples and return MP3 data
+    /// Encode these samples and return a packaged segment
     Encode(Vec<f32>),
     /// Reset the encoder (e.g., after skip)
     Reset,
@@ -26,27 +41,44 @@ enum EncoderMessage {
     Shutdown,
 }
 
-/// Spawns a dedicated encoder thread that maintains encoder state for gapless encoding
-fn spawn_encoder_thread() -> (std::sync::mpsc::Sender<EncoderMessage>, std::sync::mpsc::Receiver<Vec<u8>>) {
+/// Spawns a dedicated encoder thread that maintains encoder state for gapless encoding.
+/// Fails if the codec can't be set up with the configured bitrate.
+async fn spawn_encoder_thread(
+    codec: AudioCodec,
+    bitrate: u32,
+    resample_quality: ResampleQuality,
+) -> Result<(std::sync::mpsc::Sender<EncoderMessage>, std::sync::mpsc::Receiver<Vec<u8>>)> {
     let (sample_tx, sample_rx) = std::sync::mpsc::channel::<EncoderMessage>();
-    let (mp3_tx, mp3_rx) = std::sync::mpsc::channel::<Vec<u8>>();
+    let (segment_tx, segment_rx) = std::sync::mpsc::channel::<Vec<u8>>();
+    let (ready_tx, ready_rx) = tokio::sync::oneshot::channel::<std::result::Result<(), String>>();
 
     std::thread::spawn(move || {
         // Create encoder once for the entire stream lifetime
-        let mut encoder = create_encoder();
+        let mut encoder = match audio_codec::create_encoder(codec, bitrate, resample_quality) {
+            Ok(encoder) => {
+                let _ = ready_tx.send(Ok(()));
+                encoder
+            }
+            Err(e) => {
+                let _ = ready_tx.send(Err(e));
+                return;
+            }
+        };
 
         for msg in sample_rx {
             match msg {
                 EncoderMessage::Encode(samples) => {
-                    let mp3_data = encode_samples(&mut encoder, &samples);
-                    if mp3_tx.send(mp3_data).is_err() {
+                    let data = encoder.encode_segment(&samples);
+                    if segment_tx.send(data).is_err() {
                         break; // Receiver dropped
                     }
                 }
                 EncoderMessage::Reset => {
-                    // Create fresh encoder after skip to avoid artifacts from old audio
-                    encoder = create_encoder();
-                    debug!("Encoder reset");
+                    // Start clean after skip to avoid artifacts from old audio
+                    match encoder.reset() {
+                        Ok(()) => debug!("Encoder reset"),
+                        Err(e) => error!("Encoder reset failed: {}", e),
+                    }
                 }
                 EncoderMessage::Shutdown => {
                     break;
@@ -56,58 +88,11 @@ fn spawn_encoder_thread() -> (std::sync::mpsc::Sender<EncoderMessage>, std::sync
         info!("Encoder thread shutting down");
     });
 
-    (sample_tx, mp3_rx)
-}
-
-fn create_encoder() -> mp3lame_encoder::Encoder {
-    let mut builder = Builder::new().expect("Failed to create MP3 encoder builder");
-    builder.set_num_channels(OUTPUT_CHANNELS as u8).expect("Failed to set channels");
-    builder.set_sample_rate(OUTPUT_SAMPLE_RATE).expect("Failed to set sample rate");
-    builder.set_brate(mp3lame_encoder::Birtate::Kbps192).expect("Failed to set bitrate");
-    builder.set_quality(mp3lame_encoder::Quality::Best).expect("Failed to set quality");
-    builder.build().expect("Failed to build encoder")
-}
-
-fn encode_samples(encoder: &mut mp3lame_encoder::Encoder, samples: &[f32]) -> Vec<u8> {
-    // Convert f32 samples to i16
-    let pcm: Vec<i16> = samples
-        .iter()
-        .map(|&s| (s.clamp(-1.0, 1.0) * 32767.0) as i16)
-        .collect();
-
-    // Allocate output buffer (generous size)
-    let mp3_buffer_size = (pcm.len() as f32 * 1.25) as usize + 7200;
-    let mut mp3_buffer: Vec<MaybeUninit<u8>> = vec![MaybeUninit::uninit(); mp3_buffer_size];
-
-    // Encode - no flush, encoder maintains state for gapless output
-    let input = InterleavedPcm(&pcm);
-    let bytes_written = match encoder.encode(input, &mut mp3_buffer) {
-        Ok(size) => size,
-        Err(e) => {
-            error!("MP3 encoding failed: {:?}", e);
-            return Vec::new();
-        }
-    };
-
-    // Defensive check: ensure encoder did not report writing beyond buffer
-    if bytes_written > mp3_buffer_size {
-        error!(
-            "MP3 encoder reported {} bytes written, exceeds buffer size {}",
-            bytes_written, mp3_buffer_size
-        );
-        return Vec::new();
+    match ready_rx.await {
+        Ok(Ok(())) => Ok((sample_tx, segment_rx)),
+        Ok(Err(e)) => Err(AppError::Streaming(e)),
+        Err(_) => Err(AppError::Streaming("Encoder thread exited during setup".to_string())),
     }
-
-    // Copy to safe Vec
-    let mut mp3_data = Vec::with_capacity(bytes_written);
-    unsafe {
-        mp3_data.extend_from_slice(
-            std::slice::from_raw_parts(mp3_buffer.as_ptr() as *const u8, bytes_written)
-        );
-    }
-
-    debug!("Encoded {} samples -> {} bytes MP3", samples.len(), mp3_data.len());
-    mp3_data
 }
 
 /// HLS segment duration in seconds
@@ -118,6 +103,16 @@ pub const HLS_PLAYLIST_LENGTH: usize = 5;
 pub const FFT_SIZE: usize = 2048;
 /// Visualization update rate (Hz)
 pub const VIZ_UPDATE_RATE: u32 = 30;
+/// Spilled segments older than this are not restored (listeners have moved on)
+const SPILL_MAX_AGE_SECS: u64 = 120;
+/// Playlist URI of the fMP4 initialization segment
+pub const INIT_SEGMENT_PATH: &str = "segment/init.mp4";
+/// Segment metadata file in the spill directory
+const SPILL_MANIFEST: &str = "segments.json";
+/// Packaging overhead added to a rendition's bitrate for its `BANDWIDTH` attribute
+const BANDWIDTH_OVERHEAD_PERCENT: u32 = 10;
+/// Segments peaking below this (-60 dBFS) count as silence
+const SILENCE_PEAK: f32 = 0.001;
 
 /// Configuration for the audio broadcaster
 #[derive(Debug, Clone)]
@@ -126,10 +121,18 @@ pub struct AudioBroadcasterConfig {
     pub segment_duration: f32,
     /// Number of segments to keep in playlist
     pub playlist_length: usize,
-    /// MP3 bitrate in kbps
+    /// Codec segments are encoded with
+    pub codec: AudioCodec,
+    /// Bitrate in kbps
     pub bitrate: u32,
+    /// Lower bitrates (kbps) also encoded from the same audio
+    pub variant_bitrates: Vec<u32>,
     /// Enable visualization data generation
     pub enable_visualization: bool,
+    /// Directory the segment window is written to for crash recovery (None disables)
+    pub spill_dir: Option<PathBuf>,
+    /// Resampling for codecs that can't take the pipeline's sample rate
+    pub resample_quality: ResampleQuality,
 }
 
 impl Default for AudioBroadcasterConfig {
@@ -137,8 +140,12 @@ impl Default for AudioBroadcasterConfig {
         Self {
             segment_duration: HLS_SEGMENT_DURATION,
             playlist_length: HLS_PLAYLIST_LENGTH,
+            codec: AudioCodec::Mp3,
             bitrate: 192,
+            variant_bitrates: Vec::new(),
             enable_visualization: true,
+            spill_dir: None,
+            resample_quality: ResampleQuality::default(),
         }
     }
 }
@@ -150,10 +157,41 @@ pub struct HlsSegment {
     pub sequence: u64,
     /// Duration in seconds
     pub duration: f32,
-    /// MP3 encoded audio data
-    pub data: Vec<u8>,
+    /// Encoded, packaged audio data (shared, so serving a segment doesn't copy it)
+    pub data: Bytes,
+    /// Strong validator for conditional requests (hash of `data`)
+    pub etag: String,
     /// Track ID for this segment
     pub track_id: String,
+    /// Bytes of timed ID3 metadata at the start of `data`
+    pub metadata_len: usize,
+}
+
+impl HlsSegment {
+    /// The encoded audio without the segment's ID3 tag, for continuous streams
+    pub fn audio(&self) -> Bytes {
+        self.data.slice(self.metadata_len.min(self.data.len())..)
+    }
+}
+
+/// Segment metadata as written to the spill manifest
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct SpilledSegment {
+    sequence: u64,
+    duration: f32,
+    etag: String,
+    track_id: String,
+    #[serde(default)]
+    metadata_len: usize,
+}
+
+/// How long a broadcast has gone without producing audio, for the watchdog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BroadcastHealth {
+    /// Seconds since the last segment (or since starting, before the first one)
+    pub stalled_secs: u64,
+    /// Seconds the segments have been silent for
+    pub silent_secs: u64,
 }
 
 /// Visualization data for a time slice
@@ -181,10 +219,24 @@ pub struct BroadcasterState {
     playlist_length: usize,
     /// Current track info
     current_track_id: String,
+    /// Title and artist of the current track, for the segments' ID3 tags
+    current_track: TaggedTrack,
     /// Media sequence of first segment in playlist
     media_sequence: u64,
     /// Whether a discontinuity occurred (e.g., track skip)
     discontinuity: bool,
+    /// First segment produced after restoring spilled ones, preceded by a discontinuity
+    resume_sequence: Option<u64>,
+    /// Windows of the variant renditions whose encoders started
+    variants: Vec<VariantWindow>,
+}
+
+/// Recent segments of a variant rendition
+struct VariantWindow {
+    bitrate: u32,
+    segments: VecDeque<HlsSegment>,
+    /// Whether a skip happened since the rendition's playlist was last served
+    discontinuity: bool,
 }
 
 /// The audio broadcaster that encodes and serves HLS streams
@@ -194,50 +246,119 @@ pub struct AudioBroadcaster {
     state: Arc<RwLock<BroadcasterState>>,
     /// Broadcast channel for visualization data
     viz_tx: broadcast::Sender<VisualizationData>,
+    /// Every segment as it is produced, for continuous (non-HLS) streams
+    segment_tx: broadcast::Sender<HlsSegment>,
     /// Running flag
     running: Arc<std::sync::atomic::AtomicBool>,
     /// Broadcast start time for timestamps
     start_time: Arc<AtomicU64>,
     /// Signal to clear local buffers (set by skip, cleared by broadcast loop)
     clear_buffers: Arc<std::sync::atomic::AtomicBool>,
-    /// Channel to send messages to the encoder thread
-    encoder_tx: Arc<std::sync::Mutex<Option<std::sync::mpsc::Sender<EncoderMessage>>>>,
+    /// Channels to send messages to the encoder threads (main rendition first)
+    encoder_txs: Arc<std::sync::Mutex<Vec<std::sync::mpsc::Sender<EncoderMessage>>>>,
+    /// fMP4 initialization segment (Opus only)
+    init_segment: Option<Bytes>,
+    /// When the last segment was produced (ms since the epoch)
+    last_segment_ms: Arc<AtomicU64>,
+    /// When the current run of silent segments started (ms since the epoch, 0 = not silent)
+    silent_since_ms: Arc<AtomicU64>,
 }
 
 impl AudioBroadcaster {
     /// Create a new audio broadcaster
     pub fn new(pipeline: Arc<AudioPipeline>, config: AudioBroadcasterConfig) -> Self {
         let (viz_tx, _) = broadcast::channel(100);
+        let (segment_tx, _) = broadcast::channel(16);
+
+        // Number segments from the creation time, so a restarted broadcaster never reuses
+        // the URL of a segment a proxy may still have cached
+        let first_sequence = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        // Serve the window a previous instance left behind until new segments replace it
+        let mut segments = VecDeque::with_capacity(config.playlist_length + 2);
+        if let Some(dir) = &config.spill_dir {
+            segments.extend(restore_segments(dir, config.codec, config.playlist_length + 2));
+        }
+        let (sequence, media_sequence, resume_sequence) = match (segments.front(), segments.back()) {
+            (Some(first), Some(last)) => {
+                let sequence = first_sequence.max(last.sequence + 1);
+                info!("Restored {} spilled HLS segments", segments.len());
+                (sequence, first.sequence, Some(sequence))
+            }
+            _ => (first_sequence, first_sequence, None),
+        };
 
         Self {
             config: config.clone(),
             pipeline,
             state: Arc::new(RwLock::new(BroadcasterState {
-                segments: VecDeque::with_capacity(config.playlist_length + 2),
-                sequence: 0,
+                segments,
+                sequence,
                 playlist_length: config.playlist_length,
                 current_track_id: String::new(),
-                media_sequence: 0,
+                current_track: TaggedTrack::default(),
+                media_sequence,
                 discontinuity: false,
+                resume_sequence,
+                variants: Vec::new(),
             })),
             viz_tx,
+            segment_tx,
             running: Arc::new(std::sync::atomic::AtomicBool::new(false)),
             start_time: Arc::new(AtomicU64::new(0)),
             clear_buffers: Arc::new(std::sync::atomic::AtomicBool::new(false)),
-            encoder_tx: Arc::new(std::sync::Mutex::new(None)),
+            encoder_txs: Arc::new(std::sync::Mutex::new(Vec::new())),
+            init_segment: audio_codec::init_segment(config.codec).map(Bytes::from),
+            last_segment_ms: Arc::new(AtomicU64::new(0)),
+            silent_since_ms: Arc::new(AtomicU64::new(0)),
         }
     }
 
+    /// Codec of the segments
+    pub fn codec(&self) -> AudioCodec {
+        self.config.codec
+    }
+
+    /// Initialization segment players load before the media segments (fMP4 only)
+    pub fn init_segment(&self) -> Option<Bytes> {
+        self.init_segment.clone()
+    }
+
     /// Subscribe to visualization data updates
     pub fn subscribe_visualization(&self) -> broadcast::Receiver<VisualizationData> {
         self.viz_tx.subscribe()
     }
 
+    /// Subscribe to segments as they are produced
+    pub fn subscribe_segments(&self) -> broadcast::Receiver<HlsSegment> {
+        self.segment_tx.subscribe()
+    }
+
+    /// The newest `count` segments, oldest first
+    pub async fn recent_segments(&self, count: usize) -> Vec<HlsSegment> {
+        let state = self.state.read().await;
+        let skip = state.segments.len().saturating_sub(count);
+        state.segments.iter().skip(skip).cloned().collect()
+    }
+
     /// Check if broadcaster is running
     pub fn is_running(&self) -> bool {
         self.running.load(Ordering::Relaxed)
     }
 
+    /// Time without audio, measured from the last segment produced
+    pub fn health(&self) -> BroadcastHealth {
+        let now = unix_millis();
+        let since = |ms: u64| if ms == 0 { 0 } else { now.saturating_sub(ms) / 1000 };
+        BroadcastHealth {
+            stalled_secs: since(self.last_segment_ms.load(Ordering::Relaxed)),
+            silent_secs: since(self.silent_since_ms.load(Ordering::Relaxed)),
+        }
+    }
+
     /// Get the current track being played by the pipeline
     pub async fn current_track(&self) -> Option<crate::services::audio_pipeline::TrackState> {
         self.pipeline.current_track().await
@@ -247,10 +368,12 @@ impl AudioBroadcaster {
     pub async fn skip(&self) -> crate::error::Result<()> {
         // Signal the broadcast loop to clear its local buffers
         self.clear_buffers.store(true, Ordering::SeqCst);
+        // Silence of the skipped track doesn't count against the next one
+        self.silent_since_ms.store(0, Ordering::Relaxed);
 
-        // Reset the encoder to avoid artifacts from previous track's encoder state
-        if let Ok(guard) = self.encoder_tx.lock() {
-            if let Some(tx) = guard.as_ref() {
+        // Reset the encoders to avoid artifacts from previous track's encoder state
+        if let Ok(guard) = self.encoder_txs.lock() {
+            for tx in guard.iter() {
                 let _ = tx.send(EncoderMessage::Reset);
             }
         }
@@ -265,6 +388,10 @@ impl AudioBroadcaster {
             state.media_sequence += old_count as u64;
             state.segments.clear();
             state.discontinuity = true;
+            for window in &mut state.variants {
+                window.segments.clear();
+                window.discontinuity = true;
+            }
             info!("Skip: cleared {} segments, set clear_buffers flag, marked discontinuity", old_count);
         }
 
@@ -296,28 +423,56 @@ impl AudioBroadcaster {
             return Ok(()); // Already running
         }
 
-        let start = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
-        self.start_time.store(start, Ordering::Relaxed);
-
         // Spawn the persistent encoder thread
-        let (encoder_tx, encoder_rx) = spawn_encoder_thread();
+        let encoder = spawn_encoder_thread(self.config.codec, self.config.bitrate, self.config.resample_quality);
+        let (encoder_tx, encoder_rx) = match encoder.await {
+            Ok(channels) => channels,
+            Err(e) => {
+                self.running.store(false, Ordering::SeqCst);
+                return Err(e);
+            }
+        };
+
+        let start = unix_millis();
+        self.start_time.store(start, Ordering::Relaxed);
+        // A broadcaster that never produces a segment counts as stalled from its start
+        self.last_segment_ms.store(start, Ordering::Relaxed);
+
+        // Variants are best effort: one whose encoder can't start is left out
+        let mut variant_encoders = Vec::new();
+        for &bitrate in &self.config.variant_bitrates {
+            match spawn_encoder_thread(self.config.codec, bitrate, self.config.resample_quality).await {
+                Ok((tx, rx)) => variant_encoders.push((bitrate, tx, rx)),
+                Err(e) => warn!("Not encoding the {} kbps variant: {}", bitrate, e),
+            }
+        }
+        self.state.write().await.variants = variant_encoders
+            .iter()
+            .map(|&(bitrate, _, _)| VariantWindow {
+                bitrate,
+                segments: VecDeque::new(),
+                discontinuity: false,
+            })
+            .collect();
 
-        // Store encoder_tx for skip resets
+        // Store the encoder channels for skip resets
         {
-            let mut guard = self.encoder_tx.lock().expect("encoder_tx mutex poisoned");
-            *guard = Some(encoder_tx.clone());
+            let mut guard = self.encoder_txs.lock().expect("encoder_txs mutex poisoned");
+            *guard = std::iter::once(encoder_tx.clone())
+                .chain(variant_encoders.iter().map(|(_, tx, _)| tx.clone()))
+                .collect();
         }
 
         let pipeline = self.pipeline.clone();
         let state = self.state.clone();
         let viz_tx = self.viz_tx.clone();
+        let segment_tx = self.segment_tx.clone();
         let config = self.config.clone();
         let running = self.running.clone();
         let start_time = self.start_time.clone();
         let clear_buffers = self.clear_buffers.clone();
+        let last_segment_ms = self.last_segment_ms.clone();
+        let silent_since_ms = self.silent_since_ms.clone();
 
         // Subscribe to pipeline events for track changes
         let mut pipeline_events = pipeline.subscribe();
@@ -326,19 +481,20 @@ impl AudioBroadcaster {
         tokio::spawn(async move {
             info!("Audio broadcaster started with persistent encoder");
 
-            // Samples needed per segment - aligned to MP3 frame boundaries
-            // MP3 frames are 1152 samples per channel (2304 for stereo)
+            // Samples needed per segment - aligned to codec frame boundaries
+            // (e.g. MP3 frames are 1152 samples per channel, 2304 for stereo)
             // Aligning prevents encoding artifacts at segment boundaries
-            const MP3_FRAME_SAMPLES: usize = 1152 * OUTPUT_CHANNELS; // 2304 for stereo
+            let frame_samples = audio_codec::frame_samples(config.codec) * OUTPUT_CHANNELS;
             let raw_samples = (config.segment_duration * OUTPUT_SAMPLE_RATE as f32) as usize
                 * OUTPUT_CHANNELS;
-            // Round up to nearest MP3 frame boundary
-            let samples_per_segment = ((raw_samples + MP3_FRAME_SAMPLES - 1) / MP3_FRAME_SAMPLES)
-                * MP3_FRAME_SAMPLES;
+            // Round up to nearest frame boundary
+            let samples_per_segment = ((raw_samples + frame_samples - 1) / frame_samples)
+                * frame_samples;
             // Calculate actual segment duration after alignment
             let actual_segment_duration = samples_per_segment as f32 / (OUTPUT_SAMPLE_RATE as f32 * OUTPUT_CHANNELS as f32);
-            info!("Segment size: {} samples ({:.4}s, {} MP3 frames)",
-                  samples_per_segment, actual_segment_duration, samples_per_segment / MP3_FRAME_SAMPLES);
+            info!("Segment size: {} samples ({:.4}s, {} {} frames)",
+                  samples_per_segment, actual_segment_duration, samples_per_segment / frame_samples,
+                  config.codec.as_str());
 
             // Buffer for accumulating samples
             let mut sample_buffer: Vec<f32> = Vec::with_capacity(samples_per_segment);
@@ -355,8 +511,15 @@ impl AudioBroadcaster {
 
             let mut current_track = String::new();
 
+            // Timed metadata: samples per channel encoded so far (the segment timestamps)
+            // and the track the previous segment belonged to
+            let packed_audio = audio_codec::is_packed_audio(config.codec);
+            let mut position: u64 = 0;
+            let mut tagged_track_id = String::new();
+
             // Real-time throttling: track when we started and how many segments we've produced
             let broadcast_start = std::time::Instant::now();
+            let first_sequence = state.read().await.sequence;
             let segment_duration_ms = (actual_segment_duration * 1000.0) as u64;
             // Allow producing up to 3 segments ahead of real-time for buffering
             let max_lead_segments: u64 = 3;
@@ -378,6 +541,11 @@ impl AudioBroadcaster {
                     Ok(PipelineEvent::TrackStarted(track)) => {
                         current_track = track.track_id.clone();
                         let mut st = state.write().await;
+                        st.current_track = TaggedTrack {
+                            track_id: track.track_id.clone(),
+                            title: track.title.clone(),
+                            artist: track.artist.clone(),
+                        };
                         st.current_track_id = track.track_id;
                         info!("Broadcaster: track started - {} - {}", track.artist, track.title);
                     }
@@ -456,7 +624,7 @@ impl AudioBroadcaster {
 
                     // Calculate when this segment SHOULD be produced in real-time
                     // Segment N represents audio from time N*segment_duration to (N+1)*segment_duration
-                    let expected_time_ms = current_sequence * segment_duration_ms;
+                    let expected_time_ms = (current_sequence - first_sequence) * segment_duration_ms;
                     let actual_elapsed_ms = broadcast_start.elapsed().as_millis() as u64;
                     let max_lead_ms = max_lead_segments * segment_duration_ms;
 
@@ -473,14 +641,21 @@ impl AudioBroadcaster {
                     }
 
                     let segment_samples: Vec<f32> = sample_buffer.drain(..samples_per_segment).collect();
-
-                    // Encode to MP3 using persistent encoder thread (gapless)
+                    let silent = segment_samples.iter().all(|s| s.abs() < SILENCE_PEAK);
+                    let timestamp = position * 90_000 / OUTPUT_SAMPLE_RATE as u64;
+                    position += (segment_samples.len() / OUTPUT_CHANNELS) as u64;
+
+                    // Encode using persistent encoder threads (gapless), the variants
+                    // in parallel with the main rendition
+                    for (_, tx, _) in &variant_encoders {
+                        let _ = tx.send(EncoderMessage::Encode(segment_samples.clone()));
+                    }
                     if encoder_tx.send(EncoderMessage::Encode(segment_samples)).is_err() {
                         error!("Failed to send to encoder thread");
                         break;
                     }
 
-                    let mp3_data = match encoder_rx.recv() {
+                    let segment_data = match encoder_rx.recv() {
                         Ok(data) => data,
                         Err(_) => {
                             error!("Encoder thread disconnected");
@@ -488,24 +663,67 @@ impl AudioBroadcaster {
                         }
                     };
 
+                    // A variant whose encoder went away just stops getting segments
+                    let variant_data: Vec<Vec<u8>> = variant_encoders
+                        .iter()
+                        .map(|(_, _, rx)| rx.recv().unwrap_or_default())
+                        .collect();
+
                     // Skip empty segments
-                    if mp3_data.is_empty() {
+                    if segment_data.is_empty() {
                         warn!("Segment encoding produced no data, skipping");
                         continue;
                     }
 
+                    let now = unix_millis();
+                    last_segment_ms.store(now, Ordering::Relaxed);
+                    if !silent {
+                        silent_since_ms.store(0, Ordering::Relaxed);
+                    } else if silent_since_ms.load(Ordering::Relaxed) == 0 {
+                        silent_since_ms.store(now, Ordering::Relaxed);
+                    }
+
                     let mut st = state.write().await;
                     let sequence = st.sequence;
                     st.sequence += 1;
 
-                    let segment = HlsSegment {
-                        sequence,
-                        duration: actual_segment_duration,
-                        data: mp3_data,
-                        track_id: st.current_track_id.clone(),
+                    let tag = packed_audio.then(|| {
+                        let track = (!st.current_track.track_id.is_empty()).then_some(&st.current_track);
+                        let track_change = track.is_some_and(|t| t.track_id != tagged_track_id);
+                        id3::segment_tag(timestamp, track, track_change)
+                    });
+                    tagged_track_id = st.current_track.track_id.clone();
+                    let track_id = st.current_track_id.clone();
+                    let package = |audio: Vec<u8>| {
+                        let (data, metadata_len) = match &tag {
+                            Some(tag) => ([tag.as_slice(), &audio].concat(), tag.len()),
+                            None => (audio, 0),
+                        };
+                        HlsSegment {
+                            sequence,
+                            duration: actual_segment_duration,
+                            etag: format!("\"{:x}\"", md5::compute(&data)),
+                            data: Bytes::from(data),
+                            track_id: track_id.clone(),
+                            metadata_len,
+                        }
                     };
 
-                    // Add to circular buffer
+                    let window_len = st.playlist_length + 2;
+                    for (window, data) in st.variants.iter_mut().zip(variant_data) {
+                        if data.is_empty() {
+                            continue;
+                        }
+                        window.segments.push_back(package(data));
+                        while window.segments.len() > window_len {
+                            window.segments.pop_front();
+                        }
+                    }
+
+                    let segment = package(segment_data);
+
+                    // Add to circular buffer (continuous streams get it right away)
+                    let _ = segment_tx.send(segment.clone());
                     st.segments.push_back(segment);
 
                     // Remove old segments
@@ -520,11 +738,22 @@ impl AudioBroadcaster {
                         st.segments.back().map(|s| s.data.len()).unwrap_or(0),
                         broadcast_start.elapsed().as_secs_f32()
                     );
+
+                    if let Some(dir) = &config.spill_dir {
+                        let window: Vec<HlsSegment> = st.segments.iter().cloned().collect();
+                        drop(st);
+                        if let Err(e) = spill_segments(dir, config.codec, &window).await {
+                            warn!("Failed to spill HLS segments to {:?}: {}", dir, e);
+                        }
+                    }
                 }
             }
 
-            // Shutdown encoder thread
+            // Shutdown encoder threads
             let _ = encoder_tx.send(EncoderMessage::Shutdown);
+            for (_, tx, _) in &variant_encoders {
+                let _ = tx.send(EncoderMessage::Shutdown);
+            }
             info!("Audio broadcaster stopped");
         });
 
@@ -534,46 +763,39 @@ impl AudioBroadcaster {
     /// Stop the broadcaster
     pub fn stop(&self) {
         self.running.store(false, Ordering::Relaxed);
-        // Signal encoder thread to shutdown
-        if let Ok(guard) = self.encoder_tx.lock() {
-            if let Some(tx) = guard.as_ref() {
+        // Signal encoder threads to shutdown
+        if let Ok(guard) = self.encoder_txs.lock() {
+            for tx in guard.iter() {
                 let _ = tx.send(EncoderMessage::Shutdown);
             }
         }
     }
 
+    /// Stop the broadcaster and the pipeline feeding it
+    pub async fn shutdown(&self) {
+        self.stop();
+        if let Err(e) = self.pipeline.stop().await {
+            warn!("Failed to stop audio pipeline: {}", e);
+        }
+    }
+
     /// Generate the HLS playlist (m3u8)
     pub async fn get_playlist(&self) -> String {
         let mut state = self.state.write().await;
 
-        let mut playlist = String::new();
-        playlist.push_str("#EXTM3U\n");
-        playlist.push_str("#EXT-X-VERSION:3\n");
-        playlist.push_str(&format!(
-            "#EXT-X-TARGETDURATION:{}\n",
-            self.config.segment_duration.ceil() as u32
-        ));
-        playlist.push_str(&format!("#EXT-X-MEDIA-SEQUENCE:{}\n", state.media_sequence));
-
         // Only include segments that actually exist
         if state.segments.is_empty() {
             debug!("HLS playlist: no segments available yet");
         }
 
-        // Add discontinuity tag if a skip occurred
-        let has_discontinuity = state.discontinuity;
-        if has_discontinuity {
-            state.discontinuity = false; // Clear the flag
-        }
-
-        for (i, segment) in state.segments.iter().enumerate() {
-            // Add discontinuity before the first segment after a skip
-            if i == 0 && has_discontinuity {
-                playlist.push_str("#EXT-X-DISCONTINUITY\n");
-            }
-            playlist.push_str(&format!("#EXTINF:{:.3},\n", segment.duration));
-            playlist.push_str(&format!("segment/{}.mp3\n", segment.sequence));
-        }
+        // Add discontinuity tag if a skip occurred (and clear the flag)
+        let has_discontinuity = std::mem::take(&mut state.discontinuity);
+        let playlist = self.render_playlist(
+            &state.segments,
+            state.media_sequence,
+            has_discontinuity,
+            state.resume_sequence,
+        );
 
         debug!(
             "HLS playlist: {} segments, sequence range {}-{}, discontinuity: {}",
@@ -586,6 +808,85 @@ impl AudioBroadcaster {
         playlist
     }
 
+    /// Playlist of the rendition encoded at `bitrate`, if it is one of the variants
+    pub async fn get_variant_playlist(&self, bitrate: u32) -> Option<String> {
+        let mut state = self.state.write().await;
+        let media_sequence = state.media_sequence;
+        let window = state.variants.iter_mut().find(|v| v.bitrate == bitrate)?;
+        let has_discontinuity = std::mem::take(&mut window.discontinuity);
+        let media_sequence = window.segments.front().map_or(media_sequence, |s| s.sequence);
+        Some(self.render_playlist(&window.segments, media_sequence, has_discontinuity, None))
+    }
+
+    /// Master playlist listing the main rendition and the variants, highest bitrate first
+    pub async fn get_master_playlist(&self) -> String {
+        let mut renditions = vec![(self.config.bitrate, "playlist.m3u8".to_string())];
+        renditions.extend(
+            self.variant_bitrates()
+                .await
+                .into_iter()
+                .map(|bitrate| (bitrate, format!("variant/{}/playlist.m3u8", bitrate))),
+        );
+
+        let mut playlist = String::new();
+        playlist.push_str("#EXTM3U\n");
+        playlist.push_str(&format!("#EXT-X-VERSION:{}\n", self.playlist_version()));
+        for (bitrate, uri) in renditions {
+            playlist.push_str(&format!(
+                "#EXT-X-STREAM-INF:BANDWIDTH={},CODECS=\"{}\"\n{}\n",
+                bitrate * 10 * (100 + BANDWIDTH_OVERHEAD_PERCENT),
+                audio_codec::codecs_attribute(self.config.codec),
+                uri
+            ));
+        }
+        playlist
+    }
+
+    /// Bitrates of the variant renditions being encoded
+    pub async fn variant_bitrates(&self) -> Vec<u32> {
+        self.state.read().await.variants.iter().map(|v| v.bitrate).collect()
+    }
+
+    // EXT-X-MAP needs version 6 or later
+    fn playlist_version(&self) -> u32 {
+        if self.init_segment.is_some() { 7 } else { 3 }
+    }
+
+    fn render_playlist<'a>(
+        &self,
+        segments: impl IntoIterator<Item = &'a HlsSegment>,
+        media_sequence: u64,
+        has_discontinuity: bool,
+        resume_sequence: Option<u64>,
+    ) -> String {
+        let mut playlist = String::new();
+        playlist.push_str("#EXTM3U\n");
+        playlist.push_str(&format!("#EXT-X-VERSION:{}\n", self.playlist_version()));
+        playlist.push_str(&format!(
+            "#EXT-X-TARGETDURATION:{}\n",
+            self.config.segment_duration.ceil() as u32
+        ));
+        playlist.push_str(&format!("#EXT-X-MEDIA-SEQUENCE:{}\n", media_sequence));
+        if self.init_segment.is_some() {
+            playlist.push_str(&format!("#EXT-X-MAP:URI=\"{}\"\n", INIT_SEGMENT_PATH));
+        }
+
+        for (i, segment) in segments.into_iter().enumerate() {
+            // Add discontinuity before the first segment after a skip, and between the
+            // restored window and the first segment of this instance
+            if (i == 0 && has_discontinuity) || (i > 0 && resume_sequence == Some(segment.sequence)) {
+                playlist.push_str("#EXT-X-DISCONTINUITY\n");
+            }
+            playlist.push_str(&format!("#EXTINF:{:.3},\n", segment.duration));
+            playlist.push_str(&format!(
+                "segment/{}.{}\n",
+                segment.sequence,
+                self.config.codec.segment_extension()
+            ));
+        }
+        playlist
+    }
+
     /// Get a specific segment by sequence number
     pub async fn get_segment(&self, sequence: u64) -> Option<HlsSegment> {
         let state = self.state.read().await;
@@ -596,6 +897,19 @@ impl AudioBroadcaster {
             .cloned()
     }
 
+    /// A segment of the variant rendition encoded at `bitrate`
+    pub async fn get_variant_segment(&self, bitrate: u32, sequence: u64) -> Option<HlsSegment> {
+        let state = self.state.read().await;
+        state
+            .variants
+            .iter()
+            .find(|v| v.bitrate == bitrate)?
+            .segments
+            .iter()
+            .find(|s| s.sequence == sequence)
+            .cloned()
+    }
+
     /// Get the number of segments currently available
     pub async fn segment_count(&self) -> usize {
         let state = self.state.read().await;
@@ -696,3 +1010,147 @@ impl AudioBroadcaster {
         }
     }
 }
+
+fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+/// Write the segment window to `dir`: audio of segments not written yet, then the
+/// manifest. Files of segments that left the window are removed.
+async fn spill_segments(dir: &Path, codec: AudioCodec, window: &[HlsSegment]) -> std::io::Result<()> {
+    tokio::fs::create_dir_all(dir).await?;
+    let extension = codec.segment_extension();
+
+    for segment in window {
+        let path = dir.join(format!("{}.{}", segment.sequence, extension));
+        if !tokio::fs::try_exists(&path).await? {
+            tokio::fs::write(&path, &segment.data).await?;
+        }
+    }
+
+    // Replace the manifest atomically, so a crash never leaves half of it behind
+    let manifest: Vec<SpilledSegment> = window
+        .iter()
+        .map(|s| SpilledSegment {
+            sequence: s.sequence,
+            duration: s.duration,
+            etag: s.etag.clone(),
+            track_id: s.track_id.clone(),
+            metadata_len: s.metadata_len,
+        })
+        .collect();
+    let json = serde_json::to_vec(&manifest).map_err(std::io::Error::other)?;
+    let temp_path = dir.join(format!("{}.tmp", SPILL_MANIFEST));
+    tokio::fs::write(&temp_path, json).await?;
+    tokio::fs::rename(&temp_path, dir.join(SPILL_MANIFEST)).await?;
+
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let Some(file_extension) = path.extension().and_then(|e| e.to_str()) else { continue };
+        if !AudioCodec::ALL.iter().any(|c| c.segment_extension() == file_extension) {
+            continue;
+        }
+        // Segments of another codec are left from before the station's codec changed
+        let stale = file_extension != extension
+            || path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse::<u64>().ok())
+                .is_none_or(|sequence| !window.iter().any(|s| s.sequence == sequence));
+        if stale {
+            let _ = tokio::fs::remove_file(&path).await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Segments spilled by a previous instance, if they are recent enough to still be
+/// useful to listeners. Unreadable or missing files end the restored window.
+fn restore_segments(dir: &Path, codec: AudioCodec, max_segments: usize) -> Vec<HlsSegment> {
+    let manifest_path = dir.join(SPILL_MANIFEST);
+    let fresh = std::fs::metadata(&manifest_path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age.as_secs() <= SPILL_MAX_AGE_SECS);
+    if !fresh {
+        return Vec::new();
+    }
+
+    let manifest: Vec<SpilledSegment> = match std::fs::read(&manifest_path)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+    {
+        Some(manifest) => manifest,
+        None => {
+            warn!("Ignoring unreadable HLS spill manifest {:?}", manifest_path);
+            return Vec::new();
+        }
+    };
+
+    let mut segments = Vec::new();
+    for spilled in manifest.into_iter().rev().take(max_segments) {
+        let path = dir.join(format!("{}.{}", spilled.sequence, codec.segment_extension()));
+        let Ok(data) = std::fs::read(path) else {
+            break;
+        };
+        segments.push(HlsSegment {
+            sequence: spilled.sequence,
+            duration: spilled.duration,
+            data: Bytes::from(data),
+            etag: spilled.etag,
+            track_id: spilled.track_id,
+            metadata_len: spilled.metadata_len,
+        });
+    }
+    segments.reverse();
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(sequence: u64) -> HlsSegment {
+        let data = vec![sequence as u8; 16];
+        HlsSegment {
+            sequence,
+            duration: 2.0,
+            etag: format!("\"{:x}\"", md5::compute(&data)),
+            data: Bytes::from(data),
+            track_id: format!("track-{}", sequence),
+            metadata_len: 4,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spilled_segments_are_restored() {
+        let dir = std::env::temp_dir().join(format!("hls-spill-{}", uuid::Uuid::new_v4()));
+
+        spill_segments(&dir, AudioCodec::Mp3, &[segment(10), segment(11), segment(12)]).await.unwrap();
+        // The window moves on; segment 10 leaves it
+        spill_segments(&dir, AudioCodec::Mp3, &[segment(11), segment(12), segment(13)]).await.unwrap();
+        assert!(!dir.join("10.mp3").exists());
+
+        let restored = restore_segments(&dir, AudioCodec::Mp3, 2);
+        let sequences: Vec<u64> = restored.iter().map(|s| s.sequence).collect();
+        assert_eq!(sequences, vec![12, 13]);
+        assert_eq!(restored[1].data, segment(13).data);
+        assert_eq!(restored[1].audio().len(), 12);
+        assert_eq!(restored[1].etag, segment(13).etag);
+        assert_eq!(restored[1].track_id, "track-13");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_spill_restores_nothing() {
+        let dir = std::env::temp_dir().join(format!("hls-spill-{}", uuid::Uuid::new_v4()));
+        assert!(restore_segments(&dir, AudioCodec::Mp3, 5).is_empty());
+    }
+}
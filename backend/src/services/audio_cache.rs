@@ -0,0 +1,301 @@
+//! Bounded on-disk cache of audio fetched from Navidrome
+//!
+//! Station playback and embedding generation both download whole tracks. With
+//! `AUDIO_CACHE_DIR` set, finished downloads are kept there, keyed by track ID and the
+//! requested transcoding, and served from disk next time. Once the cache grows past
+//! `AUDIO_CACHE_MAX_MB`, the least recently used files are deleted.
+//!
+//! Downloads are written to a `.part` file first and only renamed into place when
+//! complete, so an interrupted download never shows up as a cached track.
+
+use crate::error::{AppError, Result};
+use crate::services::audio_pipeline::StreamOptions;
+use crate::services::NavidromeClient;
+use futures::StreamExt;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tokio::io::AsyncWriteExt;
+
+#[derive(Debug)]
+pub struct AudioCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    /// Cached files by key
+    entries: HashMap<String, CacheEntry>,
+    total_bytes: u64,
+    /// Logical clock for recency; bumped on every hit and insert
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    file_name: String,
+    size: u64,
+    last_used: u64,
+}
+
+impl AudioCache {
+    /// Open (or create) the cache directory and index the files already in it.
+    /// Leftover partial downloads are removed.
+    pub fn open(dir: impl Into<PathBuf>, max_bytes: u64) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).map_err(|e| {
+            AppError::Storage(format!("Failed to create audio cache dir {:?}: {}", dir, e))
+        })?;
+
+        let mut files = Vec::new();
+        let read_dir = std::fs::read_dir(&dir).map_err(|e| {
+            AppError::Storage(format!("Failed to read audio cache dir {:?}: {}", dir, e))
+        })?;
+        for entry in read_dir.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else { continue };
+            if !metadata.is_file() {
+                continue;
+            }
+            if path.extension().is_some_and(|e| e == "part") {
+                let _ = std::fs::remove_file(&path);
+                continue;
+            }
+            let (Some(key), Some(file_name)) = (
+                path.file_stem().and_then(|s| s.to_str()),
+                path.file_name().and_then(|s| s.to_str()),
+            ) else {
+                continue;
+            };
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((modified, key.to_string(), file_name.to_string(), metadata.len()));
+        }
+
+        // Oldest files count as least recently used
+        files.sort_by_key(|(modified, ..)| *modified);
+        let mut state = CacheState::default();
+        for (_, key, file_name, size) in files {
+            state.clock += 1;
+            state.total_bytes += size;
+            state.entries.insert(
+                key,
+                CacheEntry {
+                    file_name,
+                    size,
+                    last_used: state.clock,
+                },
+            );
+        }
+
+        tracing::info!(
+            "Audio cache at {:?}: {} files, {:.1} of {:.1} MB",
+            dir,
+            state.entries.len(),
+            state.total_bytes as f64 / 1_048_576.0,
+            max_bytes as f64 / 1_048_576.0
+        );
+
+        let cache = Self {
+            dir,
+            max_bytes,
+            state: Mutex::new(state),
+        };
+        cache.evict(None);
+        Ok(cache)
+    }
+
+    /// Cache key for a track fetched with the given transcoding
+    pub fn key(track_id: &str, options: &StreamOptions) -> String {
+        let raw = format!(
+            "{}|{}|{}",
+            track_id,
+            options.format.as_deref().unwrap_or(""),
+            options.max_bit_rate.unwrap_or(0)
+        );
+        hex::encode(&Sha256::digest(raw.as_bytes())[..16])
+    }
+
+    /// Path of a cached file, marking it as recently used
+    pub fn get(&self, key: &str) -> Option<PathBuf> {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+
+        let entry = state.entries.get_mut(key)?;
+        let path = self.dir.join(&entry.file_name);
+        if path.exists() {
+            entry.last_used = clock;
+            return Some(path);
+        }
+
+        // Deleted behind our back
+        let size = entry.size;
+        state.entries.remove(key);
+        state.total_bytes = state.total_bytes.saturating_sub(size);
+        None
+    }
+
+    /// Where to write a download before handing it to [`AudioCache::insert`]
+    pub fn temp_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}.part", key, uuid::Uuid::new_v4().simple()))
+    }
+
+    /// Move a finished download into the cache and evict old files to make room
+    pub fn insert(&self, key: &str, extension: &str, temp_path: &Path) -> Result<PathBuf> {
+        let file_name = format!("{}.{}", key, extension);
+        let path = self.dir.join(&file_name);
+        let size = std::fs::metadata(temp_path)
+            .and_then(|m| {
+                std::fs::rename(temp_path, &path)?;
+                Ok(m.len())
+            })
+            .map_err(|e| {
+                let _ = std::fs::remove_file(temp_path);
+                AppError::Storage(format!("Failed to store cached audio: {}", e))
+            })?;
+
+        {
+            let mut state = self.state.lock().unwrap();
+            state.clock += 1;
+            let entry = CacheEntry {
+                file_name: file_name.clone(),
+                size,
+                last_used: state.clock,
+            };
+            if let Some(old) = state.entries.insert(key.to_string(), entry) {
+                state.total_bytes = state.total_bytes.saturating_sub(old.size);
+                if old.file_name != file_name {
+                    let _ = std::fs::remove_file(self.dir.join(old.file_name));
+                }
+            }
+            state.total_bytes += size;
+        }
+
+        self.evict(Some(key));
+        Ok(path)
+    }
+
+    /// The original file of a track, downloaded into the cache if it isn't there yet.
+    /// Used where a file path is needed, e.g. to compute embeddings without the library
+    /// mounted locally.
+    pub async fn fetch(&self, navidrome: &NavidromeClient, track_id: &str) -> Result<PathBuf> {
+        let options = StreamOptions {
+            format: Some("raw".to_string()),
+            max_bit_rate: None,
+        };
+        let key = Self::key(track_id, &options);
+        if let Some(path) = self.get(&key) {
+            return Ok(path);
+        }
+
+        let url = format!("{}/rest/stream", navidrome.base_url());
+        let params = navidrome.build_params(vec![("id", track_id), ("format", "raw")]);
+        let response = navidrome
+            .send(navidrome.client().get(&url).query(&params))
+            .await
+            .map_err(|e| AppError::Navidrome(format!("Failed to download track: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(AppError::Navidrome(format!(
+                "Track download failed: {}",
+                response.status()
+            )));
+        }
+
+        let extension = extension_for(
+            response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok()),
+        );
+        let temp_path = self.temp_path(&key);
+        if let Err(e) = download_to(response, &temp_path).await {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+
+        self.insert(&key, extension, &temp_path)
+    }
+
+    /// Delete least recently used files until the cache fits, never touching `keep`
+    fn evict(&self, keep: Option<&str>) {
+        let mut state = self.state.lock().unwrap();
+        while state.total_bytes > self.max_bytes {
+            let Some(oldest) = state
+                .entries
+                .iter()
+                .filter(|(key, _)| Some(key.as_str()) != keep)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+
+            if let Some(entry) = state.entries.remove(&oldest) {
+                state.total_bytes = state.total_bytes.saturating_sub(entry.size);
+                if let Err(e) = std::fs::remove_file(self.dir.join(&entry.file_name)) {
+                    tracing::debug!("Failed to evict cached audio {}: {}", entry.file_name, e);
+                }
+            }
+        }
+    }
+}
+
+/// Audio file to compute a track's embedding from: the copy in the mounted library when
+/// it is there, otherwise a download from Navidrome through the audio cache
+pub async fn track_file(
+    library_path: Option<&str>,
+    audio_cache: Option<&AudioCache>,
+    navidrome: &NavidromeClient,
+    track_id: &str,
+    relative_path: &str,
+) -> std::result::Result<PathBuf, String> {
+    if let Some(library_path) = library_path {
+        let full_path = Path::new(library_path).join(relative_path);
+        if full_path.exists() {
+            return Ok(full_path);
+        }
+    }
+
+    match audio_cache {
+        Some(cache) => cache.fetch(navidrome, track_id).await.map_err(|e| e.to_string()),
+        None => Err("File not found".to_string()),
+    }
+}
+
+/// File extension for a downloaded track, so decoders get a format hint
+pub fn extension_for(content_type: Option<&str>) -> &'static str {
+    let content_type = content_type.unwrap_or_default();
+    if content_type.contains("mpeg") || content_type.contains("mp3") {
+        "mp3"
+    } else if content_type.contains("flac") {
+        "flac"
+    } else if content_type.contains("mp4") || content_type.contains("m4a") {
+        "m4a"
+    } else if content_type.contains("aac") {
+        "aac"
+    } else if content_type.contains("ogg") || content_type.contains("opus") {
+        "ogg"
+    } else if content_type.contains("wav") {
+        "wav"
+    } else {
+        "audio"
+    }
+}
+
+/// Write a response body to `path` as it arrives
+pub(crate) async fn download_to(response: reqwest::Response, path: &Path) -> Result<()> {
+    let write_error = |e: std::io::Error| AppError::Storage(format!("Failed to write cached audio: {}", e));
+
+    let mut file = tokio::fs::File::create(path).await.map_err(write_error)?;
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|e| AppError::Navidrome(format!("Track download failed: {}", e)))?;
+        file.write_all(&chunk).await.map_err(write_error)?;
+    }
+    file.flush().await.map_err(write_error)?;
+    Ok(())
+}
@@ -0,0 +1,535 @@
+//! Encoders for the HLS broadcaster and how their segments are packaged
+//!
+//! - MP3: raw MPEG audio frames (packed audio)
+//! - AAC: ADTS frames (packed audio)
+//! - Opus: fragmented MP4 (`moof` + `mdat` per segment) behind an init segment that
+//!   the playlist references with `EXT-X-MAP`. Opus has no 44.1kHz mode, so the
+//!   pipeline output is resampled to 48kHz first.
+//!
+//! Encoders keep their state from one segment to the next so the stream is gapless.
+//! The broadcaster puts the timed ID3 tag packed audio segments start with in front
+//! of the encoded audio (see [`id3`](crate::services::id3)).
+
+use crate::models::AudioCodec;
+use crate::services::audio_pipeline::{OUTPUT_CHANNELS, OUTPUT_SAMPLE_RATE};
+use crate::services::resampler::{self, Resample, ResampleQuality};
+use mp3lame_encoder::{Builder, InterleavedPcm};
+use std::mem::MaybeUninit;
+use tracing::{debug, error};
+
+/// Sample rate Opus streams are encoded at
+pub const OPUS_SAMPLE_RATE: u32 = 48_000;
+
+/// Samples per channel in one Opus packet (20ms)
+const OPUS_FRAME_SAMPLES: usize = 960;
+
+/// Samples the decoder drops at the start of an Opus stream (the encoder's lookahead)
+const OPUS_PRE_SKIP: u16 = 312;
+
+/// Largest Opus packet we expect; libopus recommends 4000 bytes
+const OPUS_MAX_PACKET: usize = 4000;
+
+/// fMP4 track ID of the single audio track
+const MP4_TRACK_ID: u32 = 1;
+
+/// Samples per channel in one frame of the codec, at the pipeline's output rate.
+/// Segments are cut at frame boundaries to avoid artifacts where they meet.
+pub fn frame_samples(codec: AudioCodec) -> usize {
+    match codec {
+        AudioCodec::Mp3 => 1152,
+        AudioCodec::Aac => 1024,
+        // 20ms at 44.1kHz, which resamples to exactly one packet
+        AudioCodec::Opus => OUTPUT_SAMPLE_RATE as usize / 50,
+    }
+}
+
+/// Whether segments are packed audio, which start with a timed ID3 tag
+pub fn is_packed_audio(codec: AudioCodec) -> bool {
+    match codec {
+        AudioCodec::Mp3 | AudioCodec::Aac => true,
+        AudioCodec::Opus => false,
+    }
+}
+
+/// RFC 6381 codec string for the `CODECS` attribute of master playlists
+pub fn codecs_attribute(codec: AudioCodec) -> &'static str {
+    match codec {
+        AudioCodec::Mp3 => "mp4a.40.34",
+        AudioCodec::Aac => "mp4a.40.2",
+        AudioCodec::Opus => "opus",
+    }
+}
+
+/// Segment that has to be loaded before any media segment (fMP4 only)
+pub fn init_segment(codec: AudioCodec) -> Option<Vec<u8>> {
+    match codec {
+        AudioCodec::Opus => Some(opus_init_segment()),
+        AudioCodec::Mp3 | AudioCodec::Aac => None,
+    }
+}
+
+/// Turns interleaved pipeline samples into packaged HLS segments
+pub trait SegmentEncoder {
+    /// Encode one segment's worth of samples. Empty if the encoder produced nothing.
+    fn encode_segment(&mut self, samples: &[f32]) -> Vec<u8>;
+
+    /// Drop buffered audio (e.g., after skip) so the previous track doesn't bleed in.
+    /// Segment timestamps keep counting.
+    fn reset(&mut self) -> Result<(), String>;
+}
+
+/// Create an encoder for a codec at `bitrate` kbps. `resample_quality` applies to
+/// codecs that can't take the pipeline's sample rate.
+pub fn create_encoder(
+    codec: AudioCodec,
+    bitrate: u32,
+    resample_quality: ResampleQuality,
+) -> Result<Box<dyn SegmentEncoder>, String> {
+    Ok(match codec {
+        AudioCodec::Mp3 => Box::new(Mp3Encoder::new(bitrate)?),
+        AudioCodec::Aac => Box::new(AacEncoder::new(bitrate)?),
+        AudioCodec::Opus => Box::new(OpusEncoder::new(bitrate, resample_quality)?),
+    })
+}
+
+fn to_i16(samples: &[f32]) -> Vec<i16> {
+    samples
+        .iter()
+        .map(|&s| (s.clamp(-1.0, 1.0) * 32767.0) as i16)
+        .collect()
+}
+
+// ============================================================================
+// MP3
+// ============================================================================
+
+struct Mp3Encoder {
+    bitrate: u32,
+    encoder: mp3lame_encoder::Encoder,
+}
+
+impl Mp3Encoder {
+    fn new(bitrate: u32) -> Result<Self, String> {
+        Ok(Self {
+            bitrate,
+            encoder: Self::build(bitrate)?,
+        })
+    }
+
+    fn build(bitrate: u32) -> Result<mp3lame_encoder::Encoder, String> {
+        use mp3lame_encoder::Birtate;
+        let brate = match bitrate {
+            64 => Birtate::Kbps64,
+            96 => Birtate::Kbps96,
+            128 => Birtate::Kbps128,
+            192 => Birtate::Kbps192,
+            256 => Birtate::Kbps256,
+            320 => Birtate::Kbps320,
+            other => return Err(format!("Unsupported MP3 bitrate: {} kbps", other)),
+        };
+
+        let mut builder = Builder::new().ok_or("Failed to create MP3 encoder builder")?;
+        builder
+            .set_num_channels(OUTPUT_CHANNELS as u8)
+            .map_err(|e| format!("Failed to set channels: {:?}", e))?;
+        builder
+            .set_sample_rate(OUTPUT_SAMPLE_RATE)
+            .map_err(|e| format!("Failed to set sample rate: {:?}", e))?;
+        builder
+            .set_brate(brate)
+            .map_err(|e| format!("Failed to set bitrate: {:?}", e))?;
+        builder
+            .set_quality(mp3lame_encoder::Quality::Best)
+            .map_err(|e| format!("Failed to set quality: {:?}", e))?;
+        builder
+            .build()
+            .map_err(|e| format!("Failed to build MP3 encoder: {:?}", e))
+    }
+}
+
+impl SegmentEncoder for Mp3Encoder {
+    fn encode_segment(&mut self, samples: &[f32]) -> Vec<u8> {
+        let pcm = to_i16(samples);
+
+        // Allocate output buffer (generous size)
+        let mp3_buffer_size = (pcm.len() as f32 * 1.25) as usize + 7200;
+        let mut mp3_buffer: Vec<MaybeUninit<u8>> = vec![MaybeUninit::uninit(); mp3_buffer_size];
+
+        // Encode - no flush, encoder maintains state for gapless output
+        let input = InterleavedPcm(&pcm);
+        let bytes_written = match self.encoder.encode(input, &mut mp3_buffer) {
+            Ok(size) => size,
+            Err(e) => {
+                error!("MP3 encoding failed: {:?}", e);
+                return Vec::new();
+            }
+        };
+
+        // Defensive check: ensure encoder did not report writing beyond buffer
+        if bytes_written > mp3_buffer_size {
+            error!(
+                "MP3 encoder reported {} bytes written, exceeds buffer size {}",
+                bytes_written, mp3_buffer_size
+            );
+            return Vec::new();
+        }
+
+        // Copy to safe Vec
+        let mut mp3_data = Vec::with_capacity(bytes_written);
+        unsafe {
+            mp3_data.extend_from_slice(
+                std::slice::from_raw_parts(mp3_buffer.as_ptr() as *const u8, bytes_written)
+            );
+        }
+
+        debug!("Encoded {} samples -> {} bytes MP3", samples.len(), mp3_data.len());
+        mp3_data
+    }
+
+    fn reset(&mut self) -> Result<(), String> {
+        // LAME has no reset; a fresh encoder avoids artifacts from the old audio
+        self.encoder = Self::build(self.bitrate)?;
+        Ok(())
+    }
+}
+
+// ============================================================================
+// AAC (ADTS)
+// ============================================================================
+
+struct AacEncoder {
+    bitrate: u32,
+    encoder: fdk_aac::enc::Encoder,
+}
+
+impl AacEncoder {
+    fn new(bitrate: u32) -> Result<Self, String> {
+        Ok(Self {
+            bitrate,
+            encoder: Self::build(bitrate)?,
+        })
+    }
+
+    fn build(bitrate: u32) -> Result<fdk_aac::enc::Encoder, String> {
+        use fdk_aac::enc::{AudioObjectType, BitRate, ChannelMode, Encoder, EncoderParams, Transport};
+        Encoder::new(EncoderParams {
+            bit_rate: BitRate::Cbr(bitrate * 1000),
+            sample_rate: OUTPUT_SAMPLE_RATE,
+            transport: Transport::Adts,
+            channels: ChannelMode::Stereo,
+            audio_object_type: AudioObjectType::Mpeg4LowComplexity,
+        })
+        .map_err(|e| format!("Failed to create AAC encoder: {:?}", e))
+    }
+}
+
+impl SegmentEncoder for AacEncoder {
+    fn encode_segment(&mut self, samples: &[f32]) -> Vec<u8> {
+        let pcm = to_i16(samples);
+        let mut adts = Vec::with_capacity(pcm.len() / 4);
+        let mut frame = vec![0u8; 8192];
+        let mut input = &pcm[..];
+        // Each call consumes some input and emits at most one frame
+        loop {
+            let info = match self.encoder.encode(input, &mut frame) {
+                Ok(info) => info,
+                Err(e) => {
+                    error!("AAC encoding failed: {:?}", e);
+                    return Vec::new();
+                }
+            };
+            adts.extend_from_slice(&frame[..info.output_size]);
+            input = &input[info.input_consumed..];
+            if input.is_empty() || (info.input_consumed == 0 && info.output_size == 0) {
+                break;
+            }
+        }
+
+        debug!("Encoded {} samples -> {} bytes AAC", samples.len(), adts.len());
+        adts
+    }
+
+    fn reset(&mut self) -> Result<(), String> {
+        self.encoder = Self::build(self.bitrate)?;
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Opus (fragmented MP4)
+// ============================================================================
+
+struct OpusEncoder {
+    encoder: opus::Encoder,
+    resample_quality: ResampleQuality,
+    resampler: Box<dyn Resample>,
+    /// 48kHz samples waiting for a full packet
+    pending: Vec<f32>,
+    /// Number of the next `moof`
+    fragment: u32,
+    /// 48kHz samples per channel in all previous fragments
+    decode_time: u64,
+}
+
+impl OpusEncoder {
+    fn new(bitrate: u32, resample_quality: ResampleQuality) -> Result<Self, String> {
+        let mut encoder = opus::Encoder::new(OPUS_SAMPLE_RATE, opus::Channels::Stereo, opus::Application::Audio)
+            .map_err(|e| format!("Failed to create Opus encoder: {}", e))?;
+        encoder
+            .set_bitrate(opus::Bitrate::Bits((bitrate * 1000) as i32))
+            .map_err(|e| format!("Failed to set Opus bitrate: {}", e))?;
+
+        Ok(Self {
+            encoder,
+            resample_quality,
+            resampler: resampler::resampler(resample_quality, OUTPUT_SAMPLE_RATE, OPUS_SAMPLE_RATE, OUTPUT_CHANNELS),
+            pending: Vec::new(),
+            fragment: 1,
+            decode_time: 0,
+        })
+    }
+}
+
+impl SegmentEncoder for OpusEncoder {
+    fn encode_segment(&mut self, samples: &[f32]) -> Vec<u8> {
+        self.resampler.process(samples, &mut self.pending);
+
+        let frame_len = OPUS_FRAME_SAMPLES * OUTPUT_CHANNELS;
+        let mut packets = Vec::new();
+        let mut output = vec![0u8; OPUS_MAX_PACKET];
+        let mut consumed = 0;
+        while self.pending.len() - consumed >= frame_len {
+            let frame = &self.pending[consumed..consumed + frame_len];
+            consumed += frame_len;
+            match self.encoder.encode_float(frame, &mut output) {
+                Ok(len) => packets.push(output[..len].to_vec()),
+                Err(e) => {
+                    error!("Opus encoding failed: {}", e);
+                    return Vec::new();
+                }
+            }
+        }
+        self.pending.drain(..consumed);
+
+        if packets.is_empty() {
+            return Vec::new();
+        }
+        let segment = fmp4_fragment(self.fragment, self.decode_time, &packets);
+        self.fragment += 1;
+        self.decode_time += (packets.len() * OPUS_FRAME_SAMPLES) as u64;
+        debug!("Encoded {} samples -> {} Opus packets", samples.len(), packets.len());
+        segment
+    }
+
+    fn reset(&mut self) -> Result<(), String> {
+        self.pending.clear();
+        self.resampler =
+            resampler::resampler(self.resample_quality, OUTPUT_SAMPLE_RATE, OPUS_SAMPLE_RATE, OUTPUT_CHANNELS);
+        self.encoder
+            .reset_state()
+            .map_err(|e| format!("Failed to reset Opus encoder: {}", e))
+    }
+}
+
+fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len() + 8);
+    out.extend_from_slice(&(payload.len() as u32 + 8).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(payload);
+    out
+}
+
+fn mp4_full_box(kind: &[u8; 4], version: u8, flags: u32, payload: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(payload.len() + 4);
+    body.extend_from_slice(&((version as u32) << 24 | (flags & 0x00ff_ffff)).to_be_bytes());
+    body.extend_from_slice(payload);
+    mp4_box(kind, &body)
+}
+
+/// Unity transformation matrix of `mvhd` and `tkhd`
+const MP4_MATRIX: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+
+/// `ftyp` + `moov` describing one Opus track with empty sample tables; the samples
+/// come in the fragments
+fn opus_init_segment() -> Vec<u8> {
+    let mut ftyp = Vec::new();
+    ftyp.extend_from_slice(b"iso6");
+    ftyp.extend_from_slice(&0u32.to_be_bytes());
+    for brand in [b"iso6", b"cmfc", b"mp41"] {
+        ftyp.extend_from_slice(brand);
+    }
+
+    let mut mvhd = Vec::new();
+    mvhd.extend_from_slice(&[0; 8]); // creation / modification time
+    mvhd.extend_from_slice(&OPUS_SAMPLE_RATE.to_be_bytes());
+    mvhd.extend_from_slice(&0u32.to_be_bytes()); // duration: unknown (live)
+    mvhd.extend_from_slice(&0x0001_0000u32.to_be_bytes()); // rate 1.0
+    mvhd.extend_from_slice(&0x0100u16.to_be_bytes()); // volume 1.0
+    mvhd.extend_from_slice(&[0; 10]);
+    MP4_MATRIX.iter().for_each(|v| mvhd.extend_from_slice(&v.to_be_bytes()));
+    mvhd.extend_from_slice(&[0; 24]);
+    mvhd.extend_from_slice(&(MP4_TRACK_ID + 1).to_be_bytes()); // next track ID
+
+    let mut tkhd = Vec::new();
+    tkhd.extend_from_slice(&[0; 8]);
+    tkhd.extend_from_slice(&MP4_TRACK_ID.to_be_bytes());
+    tkhd.extend_from_slice(&[0; 4]);
+    tkhd.extend_from_slice(&0u32.to_be_bytes()); // duration
+    tkhd.extend_from_slice(&[0; 8]);
+    tkhd.extend_from_slice(&[0; 4]); // layer, alternate group
+    tkhd.extend_from_slice(&0x0100u16.to_be_bytes()); // volume
+    tkhd.extend_from_slice(&[0; 2]);
+    MP4_MATRIX.iter().for_each(|v| tkhd.extend_from_slice(&v.to_be_bytes()));
+    tkhd.extend_from_slice(&[0; 8]); // width, height
+
+    let mut mdhd = Vec::new();
+    mdhd.extend_from_slice(&[0; 8]);
+    mdhd.extend_from_slice(&OPUS_SAMPLE_RATE.to_be_bytes());
+    mdhd.extend_from_slice(&0u32.to_be_bytes());
+    mdhd.extend_from_slice(&0x55c4u16.to_be_bytes()); // language "und"
+    mdhd.extend_from_slice(&[0; 2]);
+
+    let mut hdlr = Vec::new();
+    hdlr.extend_from_slice(&[0; 4]);
+    hdlr.extend_from_slice(b"soun");
+    hdlr.extend_from_slice(&[0; 12]);
+    hdlr.extend_from_slice(b"SoundHandler\0");
+
+    // OpusSpecificBox, as defined by the Opus-in-ISOBMFF mapping
+    let mut dops = vec![0, OUTPUT_CHANNELS as u8];
+    dops.extend_from_slice(&OPUS_PRE_SKIP.to_be_bytes());
+    dops.extend_from_slice(&OUTPUT_SAMPLE_RATE.to_be_bytes()); // input sample rate
+    dops.extend_from_slice(&0i16.to_be_bytes()); // output gain
+    dops.push(0); // channel mapping family: mono/stereo
+
+    let mut opus_entry = Vec::new();
+    opus_entry.extend_from_slice(&[0; 6]);
+    opus_entry.extend_from_slice(&1u16.to_be_bytes()); // data reference index
+    opus_entry.extend_from_slice(&[0; 8]);
+    opus_entry.extend_from_slice(&(OUTPUT_CHANNELS as u16).to_be_bytes());
+    opus_entry.extend_from_slice(&16u16.to_be_bytes()); // sample size
+    opus_entry.extend_from_slice(&[0; 4]);
+    opus_entry.extend_from_slice(&(OPUS_SAMPLE_RATE << 16).to_be_bytes());
+    opus_entry.extend_from_slice(&mp4_box(b"dOps", &dops));
+
+    let mut stsd = 1u32.to_be_bytes().to_vec();
+    stsd.extend_from_slice(&mp4_box(b"Opus", &opus_entry));
+    let empty_table = 0u32.to_be_bytes();
+    let stbl = [
+        mp4_full_box(b"stsd", 0, 0, &stsd),
+        mp4_full_box(b"stts", 0, 0, &empty_table),
+        mp4_full_box(b"stsc", 0, 0, &empty_table),
+        mp4_full_box(b"stsz", 0, 0, &[0; 8]),
+        mp4_full_box(b"stco", 0, 0, &empty_table),
+    ]
+    .concat();
+
+    let mut dref = 1u32.to_be_bytes().to_vec();
+    dref.extend_from_slice(&mp4_full_box(b"url ", 0, 1, &[])); // media is in this file
+    let minf = [
+        mp4_full_box(b"smhd", 0, 0, &[0; 4]),
+        mp4_box(b"dinf", &mp4_full_box(b"dref", 0, 0, &dref)),
+        mp4_box(b"stbl", &stbl),
+    ]
+    .concat();
+
+    let mdia = [
+        mp4_full_box(b"mdhd", 0, 0, &mdhd),
+        mp4_full_box(b"hdlr", 0, 0, &hdlr),
+        mp4_box(b"minf", &minf),
+    ]
+    .concat();
+    let trak = [mp4_full_box(b"tkhd", 0, 0x3, &tkhd), mp4_box(b"mdia", &mdia)].concat();
+
+    let mut trex = Vec::new();
+    trex.extend_from_slice(&MP4_TRACK_ID.to_be_bytes());
+    trex.extend_from_slice(&1u32.to_be_bytes()); // sample description index
+    trex.extend_from_slice(&(OPUS_FRAME_SAMPLES as u32).to_be_bytes());
+    trex.extend_from_slice(&[0; 8]); // default size, flags (sync samples)
+
+    let moov = [
+        mp4_full_box(b"mvhd", 0, 0, &mvhd),
+        mp4_box(b"trak", &trak),
+        mp4_box(b"mvex", &mp4_full_box(b"trex", 0, 0, &trex)),
+    ]
+    .concat();
+
+    [mp4_box(b"ftyp", &ftyp), mp4_box(b"moov", &moov)].concat()
+}
+
+/// One `moof` + `mdat` holding `packets`, starting at `decode_time` (48kHz units)
+fn fmp4_fragment(sequence: u32, decode_time: u64, packets: &[Vec<u8>]) -> Vec<u8> {
+    let moof = |data_offset: u32| {
+        let mut trun = Vec::new();
+        trun.extend_from_slice(&(packets.len() as u32).to_be_bytes());
+        trun.extend_from_slice(&data_offset.to_be_bytes());
+        for packet in packets {
+            trun.extend_from_slice(&(OPUS_FRAME_SAMPLES as u32).to_be_bytes());
+            trun.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+        }
+
+        let traf = [
+            // default-base-is-moof: offsets count from the start of the moof
+            mp4_full_box(b"tfhd", 0, 0x02_0000, &MP4_TRACK_ID.to_be_bytes()),
+            mp4_full_box(b"tfdt", 1, 0, &decode_time.to_be_bytes()),
+            // data-offset, sample-duration and sample-size present
+            mp4_full_box(b"trun", 0, 0x000301, &trun),
+        ]
+        .concat();
+
+        [
+            mp4_full_box(b"mfhd", 0, 0, &sequence.to_be_bytes()),
+            mp4_box(b"traf", &traf),
+        ]
+        .concat()
+    };
+
+    // The data offset points past the moof and the mdat header
+    let moof_len = mp4_box(b"moof", &moof(0)).len() as u32;
+    let mut fragment = mp4_box(b"moof", &moof(moof_len + 8));
+    fragment.extend_from_slice(&mp4_box(b"mdat", &packets.concat()));
+    fragment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Top-level boxes as (type, size)
+    fn boxes(data: &[u8]) -> Vec<(String, usize)> {
+        let mut out = Vec::new();
+        let mut offset = 0;
+        while offset + 8 <= data.len() {
+            let size = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+            let kind = String::from_utf8_lossy(&data[offset + 4..offset + 8]).to_string();
+            out.push((kind, size));
+            offset += size;
+        }
+        assert_eq!(offset, data.len(), "box sizes don't add up");
+        out
+    }
+
+    #[test]
+    fn test_init_segment_layout() {
+        let init = init_segment(AudioCodec::Opus).unwrap();
+        let kinds: Vec<String> = boxes(&init).into_iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, vec!["ftyp", "moov"]);
+        assert!(init.windows(4).any(|w| w == b"dOps"));
+        assert!(init_segment(AudioCodec::Mp3).is_none());
+    }
+
+    #[test]
+    fn test_fragment_data_offset_points_at_samples() {
+        let packets = vec![vec![1u8; 10], vec![2u8; 7]];
+        let fragment = fmp4_fragment(3, 960, &packets);
+
+        let layout = boxes(&fragment);
+        assert_eq!(layout[0].0, "moof");
+        assert_eq!(layout[1], ("mdat".to_string(), 8 + 17));
+
+        // trun: full box header, sample count, then the data offset
+        let trun = fragment.windows(4).position(|w| w == b"trun").unwrap() - 4;
+        let offset = u32::from_be_bytes(fragment[trun + 16..trun + 20].try_into().unwrap()) as usize;
+        assert_eq!(&fragment[offset..offset + 10], &[1u8; 10]);
+    }
+}
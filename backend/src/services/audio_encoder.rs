@@ -10,24 +10,35 @@
 #![allow(dead_code)]
 
 use crate::error::{AppError, Result};
-use ndarray::{Array2, Array4, Axis};
-use ort::execution_providers::CoreMLExecutionProvider;
+use crate::models::JobKind;
+use crate::services::audio_features::{self, AudioFeatures};
+use crate::services::execution_provider::{self, ExecutionProviderKind, ExecutionProviderStatus, SkippedProvider};
+use crate::services::jobs::{JobContext, JobManager};
+use crate::services::resampler::{self, ResampleQuality};
+use crate::services::vector_index::{self, VectorSearchConfig};
+use crate::services::NavidromeClient;
+use ndarray::{concatenate, Array2, Array4, Axis};
 use ort::session::{builder::GraphOptimizationLevel, Session};
+use rayon::prelude::*;
 use rustfft::{num_complex::Complex, FftPlanner};
 use sqlx::PgPool;
+use std::collections::{BTreeMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use symphonia::core::audio::SampleBuffer;
 use symphonia::core::codecs::DecoderOptions;
 use symphonia::core::formats::FormatOptions;
 use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
-use tokio::sync::Semaphore;
+use tokio::sync::{mpsc, Semaphore};
 use tracing::{debug, info, warn};
+use uuid::Uuid;
 
 /// Audio encoder configuration
+#[derive(Debug, Clone)]
 pub struct AudioEncoderConfig {
     /// Path to ONNX model file
     pub model_path: PathBuf,
@@ -39,10 +50,26 @@ pub struct AudioEncoderConfig {
     pub n_fft: usize,
     /// Hop length between frames
     pub hop_length: usize,
-    /// Duration of audio to process (in seconds)
+    /// Length of each window embedded in multi-window mode (in seconds)
     pub duration_secs: f32,
     /// Maximum concurrent encoding operations
     pub max_concurrent: usize,
+    /// How audio is converted to `sample_rate`
+    pub resample_quality: ResampleQuality,
+    /// Windows of `duration_secs` embedded per track and averaged, run as one batch
+    /// (0 squeezes the whole track into a single spectrogram)
+    pub windows: usize,
+    /// Keep each window's embedding in `track_embedding_windows`
+    pub store_windows: bool,
+    /// Tracks whose spectrograms the backfill runs through the model together
+    pub batch_size: usize,
+    /// Execution providers to try, in order of preference
+    pub execution_providers: Vec<ExecutionProviderKind>,
+    /// Stored with each embedding; embeddings of other versions are outdated
+    pub model_version: String,
+    /// Measure tempo, key and energy from the decoded audio and store them with the
+    /// embedding
+    pub analyze_features: bool,
 }
 
 impl Default for AudioEncoderConfig {
@@ -66,10 +93,75 @@ impl Default for AudioEncoderConfig {
             hop_length: 512,
             duration_secs: 5.0,
             max_concurrent: num_cores,
+            resample_quality: ResampleQuality::default(),
+            windows: 0,
+            store_windows: false,
+            batch_size: 8,
+            execution_providers: execution_provider::AUTO.to_vec(),
+            model_version: MODEL_VERSION.to_string(),
+            analyze_features: true,
         }
     }
 }
 
+/// Version of the released embedding model, assumed when neither
+/// `AUDIO_ENCODER_MODEL_VERSION` nor the model's manifest names one
+pub const MODEL_VERSION: &str = "teticio/audio-encoder-v1";
+
+/// Share of a track skipped at either end when placing windows, so intros and
+/// fade-outs don't stand for the whole track
+const WINDOW_EDGE_SKIP: f64 = 0.1;
+
+/// Embedding of one window of a track
+#[derive(Debug, Clone)]
+pub struct WindowEmbedding {
+    /// Where the window starts in the track
+    pub start_ms: i32,
+    /// Unit length
+    pub embedding: Vec<f32>,
+}
+
+/// A track's window embeddings and the features measured from its audio
+#[derive(Debug, Clone)]
+pub struct EncodedTrack {
+    pub windows: Vec<WindowEmbedding>,
+    /// None when disabled, or for audio too short or silent to measure
+    pub features: Option<AudioFeatures>,
+}
+
+/// Tracks whose visualization coordinates are written per statement
+const VIZ_UPDATE_CHUNK: usize = 500;
+/// Share of a visualization rebuild's progress taken by computing the layout
+/// (the rest is storing it)
+const LAYOUT_PROGRESS_SHARE: f64 = 0.9;
+
+/// Largest library whose visualization neighbors are found by comparing every pair
+const EXACT_KNN_LIMIT: usize = 20_000;
+/// Tracks nearest to each journey waypoint that a step chooses from
+const JOURNEY_CANDIDATES: usize = 20;
+
+/// Random directions used to find neighbor candidates in larger libraries
+const APPROX_KNN_PROJECTIONS: usize = 8;
+/// Candidates taken from each side of a point along each direction
+const APPROX_KNN_WINDOW: usize = 32;
+
+/// Candidates at least this similar to a liked track are in its neighborhood
+const LIKED_NEIGHBORHOOD_SIMILARITY: f32 = 0.85;
+/// Similarity bonus of a candidate identical to a liked track, shrinking to nothing at
+/// the edge of the neighborhood
+const LIKED_NEIGHBORHOOD_BOOST: f32 = 0.05;
+/// Liked tracks whose neighborhoods are boosted per query
+const MAX_LIKED_NEIGHBORHOODS: usize = 100;
+
+/// 2D layout of all embeddings and the projection it started from
+struct VisualizationLayout {
+    pc1: Vec<f32>,
+    pc2: Vec<f32>,
+    mean: Vec<f32>,
+    /// Normalized to [-1, 1], in the order of the input embeddings
+    positions: Vec<(f32, f32)>,
+}
+
 /// A pool of ONNX sessions for parallel inference
 struct SessionPool {
     sessions: Vec<tokio::sync::Mutex<Session>>,
@@ -97,6 +189,12 @@ pub struct AudioEncoder {
     config: AudioEncoderConfig,
     db: PgPool,
     semaphore: Semaphore,
+    /// Source of similar tracks for tracks without an embedding
+    navidrome: Option<Arc<NavidromeClient>>,
+    /// Recall settings for similarity queries
+    vector_search: VectorSearchConfig,
+    /// The execution provider the sessions were created with
+    execution_provider: ExecutionProviderStatus,
 }
 
 impl AudioEncoder {
@@ -118,123 +216,285 @@ impl AudioEncoder {
             .unwrap_or(8);
         let threads_per_session = (num_cores / pool_size).max(1);
 
-        for i in 0..pool_size {
-            info!(
-                "Creating ONNX session {}/{} with {} threads",
-                i + 1,
-                pool_size,
-                threads_per_session
-            );
+        // The first session settles the execution provider; the others reuse it
+        let mut skipped = Vec::new();
+        let mut active = None;
+        let mut last_error = None;
+        for &provider in &config.execution_providers {
+            if !provider.is_available() {
+                skipped.push(SkippedProvider {
+                    provider,
+                    reason: "Not included in the installed ONNX Runtime".to_string(),
+                });
+                continue;
+            }
+            match Self::create_session(&config, provider, threads_per_session) {
+                Ok(session) => {
+                    sessions.push(session);
+                    active = Some(provider);
+                    break;
+                }
+                Err(e) => {
+                    warn!("{} execution provider failed, trying the next one: {}", provider.as_str(), e);
+                    skipped.push(SkippedProvider {
+                        provider,
+                        reason: e.to_string(),
+                    });
+                    last_error = Some(e);
+                }
+            }
+        }
+        let Some(active) = active else {
+            return Err(last_error.unwrap_or_else(|| AppError::Model("No execution provider available".to_string())));
+        };
+        info!(
+            "Running the audio encoder on {} with {} sessions of {} threads",
+            active.as_str(),
+            pool_size,
+            threads_per_session
+        );
 
-            // Try to use CoreML on macOS for Apple Neural Engine acceleration
-            let coreml = CoreMLExecutionProvider::default()
-                .with_subgraphs(true) // Enable CoreML on subgraphs
-                .build();
-
-            let session = Session::builder()
-                .map_err(|e| AppError::InternalMessage(format!("Failed to create session builder: {}", e)))?
-                .with_execution_providers([coreml])
-                .map_err(|e| {
-                    warn!("CoreML not available, falling back to CPU: {}", e);
-                    AppError::InternalMessage(format!("Failed to set execution provider: {}", e))
-                })
-                .unwrap_or_else(|_| {
-                    // Fallback: create session without CoreML
-                    Session::builder().unwrap()
-                })
-                .with_optimization_level(GraphOptimizationLevel::Level3)
-                .map_err(|e| AppError::InternalMessage(format!("Failed to set optimization level: {}", e)))?
-                .with_intra_threads(threads_per_session)
-                .map_err(|e| AppError::InternalMessage(format!("Failed to set threads: {}", e)))?
-                .commit_from_file(&config.model_path)
-                .map_err(|e| AppError::InternalMessage(format!("Failed to load ONNX model: {}", e)))?;
-
-            sessions.push(session);
+        for _ in 1..pool_size {
+            sessions.push(Self::create_session(&config, active, threads_per_session)?);
         }
 
         let max_concurrent = config.max_concurrent;
+        let requested = config.execution_providers.clone();
 
         Ok(Self {
             session_pool: Arc::new(SessionPool::new(sessions)),
             config,
             db,
             semaphore: Semaphore::new(max_concurrent),
+            navidrome: None,
+            vector_search: VectorSearchConfig::default(),
+            execution_provider: ExecutionProviderStatus {
+                active,
+                requested,
+                skipped,
+                sessions: pool_size,
+            },
         })
     }
 
-    /// Encode an audio file and return its 100-dimensional embedding
+    /// Load the model into a session on `provider`
+    fn create_session(config: &AudioEncoderConfig, provider: ExecutionProviderKind, threads: usize) -> Result<Session> {
+        let mut builder =
+            Session::builder().map_err(|e| AppError::Model(format!("Failed to create session builder: {}", e)))?;
+        if provider != ExecutionProviderKind::Cpu {
+            builder = builder.with_execution_providers([provider.dispatch()]).map_err(|e| {
+                AppError::Model(format!("Failed to register {} execution provider: {}", provider.as_str(), e))
+            })?;
+        }
+        builder
+            .with_optimization_level(GraphOptimizationLevel::Level3)
+            .map_err(|e| AppError::Model(format!("Failed to set optimization level: {}", e)))?
+            .with_intra_threads(threads)
+            .map_err(|e| AppError::Model(format!("Failed to set threads: {}", e)))?
+            .commit_from_file(&config.model_path)
+            .map_err(|e| AppError::Model(format!("Failed to load ONNX model: {}", e)))
+    }
+
+    pub fn config(&self) -> &AudioEncoderConfig {
+        &self.config
+    }
+
+    pub fn model_version(&self) -> &str {
+        &self.config.model_version
+    }
+
+    pub fn execution_provider(&self) -> &ExecutionProviderStatus {
+        &self.execution_provider
+    }
+
+    /// Fall back to Navidrome's similar songs in [`AudioEncoder::find_similar`] when the
+    /// source track has no embedding yet
+    pub fn with_navidrome(mut self, navidrome: Arc<NavidromeClient>) -> Self {
+        self.navidrome = Some(navidrome);
+        self
+    }
+
+    /// Tune the recall of similarity queries (HNSW_EF_SEARCH, IVFFLAT_PROBES)
+    pub fn with_vector_search(mut self, vector_search: VectorSearchConfig) -> Self {
+        self.vector_search = vector_search;
+        self
+    }
+
+    pub fn vector_search(&self) -> VectorSearchConfig {
+        self.vector_search
+    }
+
+    /// Encode an audio file and return its 100-dimensional embedding (the average of
+    /// its windows in multi-window mode), normalized to unit length
     pub async fn encode_file(&self, audio_path: &Path) -> Result<Vec<f32>> {
+        Ok(average_embedding(&self.encode_windows(audio_path).await?))
+    }
+
+    /// Encode the windows of an audio file in one inference run. Without multi-window
+    /// mode the whole track is a single window.
+    pub async fn encode_windows(&self, audio_path: &Path) -> Result<Vec<WindowEmbedding>> {
+        let mut results = self.encode_batch(vec![audio_path.to_path_buf()]).await?;
+        results
+            .pop()
+            .unwrap_or_else(|| Err(AppError::Model("No output from model".to_string())))
+            .map(|track| track.windows)
+    }
+
+    /// Encode several audio files with one inference run, stacking the spectrograms of
+    /// all their windows into a single batch. Returns each file's windows and measured
+    /// features, or why it couldn't be encoded, in the order given.
+    pub async fn encode_batch(&self, audio_paths: Vec<PathBuf>) -> Result<Vec<Result<EncodedTrack>>> {
         let _permit = self.semaphore.acquire().await.map_err(|e| {
-            AppError::InternalMessage(format!("Failed to acquire semaphore: {}", e))
+            AppError::Unavailable(format!("Failed to acquire semaphore: {}", e))
         })?;
 
-        let path = audio_path.to_path_buf();
-        let config = AudioEncoderConfig {
-            model_path: self.config.model_path.clone(),
-            sample_rate: self.config.sample_rate,
-            n_mels: self.config.n_mels,
-            n_fft: self.config.n_fft,
-            hop_length: self.config.hop_length,
-            duration_secs: self.config.duration_secs,
-            max_concurrent: self.config.max_concurrent,
-        };
-
-        // Pre-process audio (CPU-bound but doesn't need session)
-        let mel_spec = tokio::task::spawn_blocking(move || {
-            Self::load_and_preprocess(&path, &config)
+        // Pre-process audio in parallel (CPU-bound but doesn't need session)
+        let config = self.config.clone();
+        let preprocessed = tokio::task::spawn_blocking(move || {
+            audio_paths
+                .par_iter()
+                .map(|path| Self::load_and_preprocess(path, &config))
+                .collect::<Vec<_>>()
         })
         .await
-        .map_err(|e| AppError::InternalMessage(format!("Preprocessing task panicked: {}", e)))??;
+        .map_err(|e| AppError::InternalMessage(format!("Preprocessing task panicked: {}", e)))?;
+
+        let spectrograms: Vec<_> = preprocessed
+            .iter()
+            .filter_map(|p| p.as_ref().ok())
+            .map(|(mel_specs, _, _)| mel_specs.view())
+            .collect();
+        if spectrograms.is_empty() {
+            return Ok(preprocessed
+                .into_iter()
+                .map(|p| p.map(|(_, _, features)| EncodedTrack { windows: Vec::new(), features }))
+                .collect());
+        }
 
         // Acquire a session from the pool and run inference
         let mut session = self.session_pool.get().await;
-        Self::run_inference_async(&mut session, mel_spec)
+        let batch = concatenate(Axis(0), &spectrograms)
+            .map_err(|e| AppError::Model(format!("Failed to batch spectrograms: {}", e)));
+        let batched = spectrograms.len() > 1;
+        let (mut embeddings, mut batch_error) =
+            match batch.and_then(|batch| Self::run_inference_async(&mut session, batch)) {
+                Ok(embeddings) => (Some(embeddings.into_iter()), None),
+                Err(e) => {
+                    if batched {
+                        // Some models only take one track at a time
+                        warn!("Batched inference failed, encoding tracks one by one: {}", e);
+                    }
+                    (None, Some(e))
+                }
+            };
+        drop(spectrograms);
+
+        let mut results = Vec::with_capacity(preprocessed.len());
+        for track in preprocessed {
+            let (mel_specs, starts_ms, features) = match track {
+                Ok(track) => track,
+                Err(e) => {
+                    results.push(Err(e));
+                    continue;
+                }
+            };
+            let track_embeddings = match (embeddings.as_mut(), batch_error.take()) {
+                (Some(embeddings), _) => Ok(embeddings.by_ref().take(starts_ms.len()).collect()),
+                (None, Some(e)) if !batched => Err(e),
+                (None, _) => Self::run_inference_async(&mut session, mel_specs),
+            };
+            results.push(track_embeddings.map(|track_embeddings| EncodedTrack {
+                windows: starts_ms
+                    .into_iter()
+                    .zip(track_embeddings)
+                    .map(|(start_ms, embedding)| WindowEmbedding {
+                        start_ms,
+                        embedding: Self::normalize_embedding(embedding),
+                    })
+                    .collect(),
+                features,
+            }));
+        }
+        Ok(results)
     }
 
-    /// Load audio and compute mel spectrogram (CPU-bound preprocessing)
-    fn load_and_preprocess(audio_path: &Path, config: &AudioEncoderConfig) -> Result<Array4<f32>> {
+    /// Load audio and compute the mel spectrograms of its windows (CPU-bound
+    /// preprocessing), stacked into one batch, with where each window starts and the
+    /// features measured from the whole track
+    fn load_and_preprocess(
+        audio_path: &Path,
+        config: &AudioEncoderConfig,
+    ) -> Result<(Array4<f32>, Vec<i32>, Option<AudioFeatures>)> {
         debug!("Loading and preprocessing audio file: {:?}", audio_path);
 
         // Load and decode audio
-        let samples = Self::load_audio(audio_path, config.sample_rate)?;
-
-        // Generate mel spectrogram
-        Self::compute_mel_spectrogram(
-            &samples,
-            config.sample_rate,
-            config.n_fft,
-            config.hop_length,
-            config.n_mels,
-        )
+        let samples = Self::load_audio(audio_path, config.sample_rate, config.resample_quality)?;
+        let features = config
+            .analyze_features
+            .then(|| audio_features::analyze(&samples, config.sample_rate))
+            .flatten();
+
+        let mel_spec = |samples: &[f32]| {
+            Self::compute_mel_spectrogram(samples, config.sample_rate, config.n_fft, config.hop_length, config.n_mels)
+        };
+        if config.windows == 0 {
+            return Ok((mel_spec(&samples)?, vec![0], features));
+        }
+
+        let window_len = (config.duration_secs * config.sample_rate as f32) as usize;
+        let starts = window_starts(samples.len(), window_len, config.windows);
+        let spectrograms = starts
+            .iter()
+            .map(|&start| mel_spec(&samples[start..(start + window_len).min(samples.len())]))
+            .collect::<Result<Vec<_>>>()?;
+        let views: Vec<_> = spectrograms.iter().map(|s| s.view()).collect();
+        let batch = concatenate(Axis(0), &views)
+            .map_err(|e| AppError::Model(format!("Failed to batch spectrograms: {}", e)))?;
+
+        let starts_ms = starts
+            .iter()
+            .map(|&start| (start as u64 * 1000 / config.sample_rate as u64) as i32)
+            .collect();
+        Ok((batch, starts_ms, features))
     }
 
-    /// Run inference with an async-compatible session guard
+    /// Run inference on a batch of spectrograms with an async-compatible session guard,
+    /// returning one embedding per spectrogram
     fn run_inference_async(
         session: &mut tokio::sync::MutexGuard<'_, Session>,
         mel_spec: Array4<f32>,
-    ) -> Result<Vec<f32>> {
+    ) -> Result<Vec<Vec<f32>>> {
         use ort::value::Tensor;
 
+        let batch = mel_spec.shape()[0];
+
         // Create input tensor
         let input_tensor = Tensor::from_array(mel_spec)
-            .map_err(|e| AppError::InternalMessage(format!("Failed to create input tensor: {}", e)))?;
+            .map_err(|e| AppError::Model(format!("Failed to create input tensor: {}", e)))?;
 
         // Run inference
         let outputs = session
             .run(ort::inputs![input_tensor])
-            .map_err(|e| AppError::InternalMessage(format!("ONNX inference failed: {}", e)))?;
+            .map_err(|e| AppError::Model(format!("ONNX inference failed: {}", e)))?;
 
         // Extract output tensor
         let (_, output) = outputs
             .into_iter()
             .next()
-            .ok_or_else(|| AppError::InternalMessage("No output from model".to_string()))?;
+            .ok_or_else(|| AppError::Model("No output from model".to_string()))?;
 
         let (_, embedding_data) = output
             .try_extract_tensor::<f32>()
-            .map_err(|e| AppError::InternalMessage(format!("Failed to extract embedding: {}", e)))?;
+            .map_err(|e| AppError::Model(format!("Failed to extract embedding: {}", e)))?;
 
         let embedding: Vec<f32> = embedding_data.iter().cloned().collect();
+        if batch == 0 || embedding.is_empty() || !embedding.len().is_multiple_of(batch) {
+            return Err(AppError::Model(format!(
+                "Model returned {} values for a batch of {}",
+                embedding.len(),
+                batch
+            )));
+        }
 
         // Debug: log embedding stats
         let emb_min = embedding.iter().cloned().fold(f32::INFINITY, f32::min);
@@ -247,13 +507,13 @@ impl AudioEncoder {
         );
         debug!("First 5 embedding values: {:?}", &embedding[..5.min(embedding.len())]);
 
-        Ok(embedding)
+        Ok(embedding.chunks(embedding.len() / batch).map(<[f32]>::to_vec).collect())
     }
 
     /// Load and decode audio file to mono float samples
-    fn load_audio(path: &Path, target_sample_rate: u32) -> Result<Vec<f32>> {
+    fn load_audio(path: &Path, target_sample_rate: u32, resample_quality: ResampleQuality) -> Result<Vec<f32>> {
         let file = std::fs::File::open(path)
-            .map_err(|e| AppError::InternalMessage(format!("Failed to open audio file: {}", e)))?;
+            .map_err(|e| AppError::Storage(format!("Failed to open audio file: {}", e)))?;
 
         let mss = MediaSourceStream::new(Box::new(file), Default::default());
 
@@ -264,16 +524,16 @@ impl AudioEncoder {
 
         let probed = symphonia::default::get_probe()
             .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
-            .map_err(|e| AppError::InternalMessage(format!("Failed to probe audio format: {}", e)))?;
+            .map_err(|e| AppError::Decode(format!("Failed to probe audio format: {}", e)))?;
 
         let mut format = probed.format;
         let track = format
             .default_track()
-            .ok_or_else(|| AppError::InternalMessage("No audio track found".to_string()))?;
+            .ok_or_else(|| AppError::Decode("No audio track found".to_string()))?;
 
         let mut decoder = symphonia::default::get_codecs()
             .make(&track.codec_params, &DecoderOptions::default())
-            .map_err(|e| AppError::InternalMessage(format!("Failed to create decoder: {}", e)))?;
+            .map_err(|e| AppError::Decode(format!("Failed to create decoder: {}", e)))?;
 
         let track_id = track.id;
         let mut samples = Vec::new();
@@ -318,36 +578,14 @@ impl AudioEncoder {
             }
         }
 
-        // Resample if necessary (simple linear interpolation)
-        // TODO: Use a proper resampling library for better quality
         let original_rate = decoder.codec_params().sample_rate.unwrap_or(44100);
         if original_rate != target_sample_rate {
-            samples = Self::resample(&samples, original_rate, target_sample_rate);
+            samples = resampler::resample(resample_quality, &samples, original_rate, target_sample_rate, 1);
         }
 
         Ok(samples)
     }
 
-    /// Simple linear interpolation resampling
-    fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
-        let ratio = from_rate as f64 / to_rate as f64;
-        let output_len = (samples.len() as f64 / ratio) as usize;
-        let mut output = Vec::with_capacity(output_len);
-
-        for i in 0..output_len {
-            let src_idx = i as f64 * ratio;
-            let idx_floor = src_idx.floor() as usize;
-            let idx_ceil = (idx_floor + 1).min(samples.len() - 1);
-            let frac = src_idx - idx_floor as f64;
-
-            let value = samples[idx_floor] * (1.0 - frac as f32)
-                + samples[idx_ceil] * frac as f32;
-            output.push(value);
-        }
-
-        output
-    }
-
     /// Compute mel spectrogram from audio samples
     ///
     /// Matches the preprocessing from teticio/audio-encoder (audiodiffusion):
@@ -374,7 +612,7 @@ impl AudioEncoder {
         // Calculate number of frames from available samples
         let n_frames = (samples.len().saturating_sub(n_fft)) / hop_length + 1;
         if n_frames == 0 {
-            return Err(AppError::InternalMessage("Audio too short for analysis".to_string()));
+            return Err(AppError::Decode("Audio too short for analysis".to_string()));
         }
 
         // Compute STFT and mel spectrogram (power values)
@@ -570,87 +808,254 @@ impl AudioEncoder {
 
     /// Process a track and store its embedding in the database
     pub async fn process_track(&self, track_id: &str, audio_path: &Path) -> Result<()> {
-        let start = Instant::now();
+        let track = (track_id.to_string(), audio_path.to_path_buf());
+        self.process_batch(vec![track]).await?.pop().unwrap_or(Ok(()))
+    }
 
-        // Check if already processed
-        let exists = sqlx::query_scalar!(
-            "SELECT 1 FROM track_embeddings WHERE track_id = $1",
-            track_id
-        )
-        .fetch_optional(&self.db)
-        .await?;
+    /// Embed several tracks (track ID, audio file) in one inference run and store their
+    /// embeddings, or their failures for retry later. Tracks that already have an
+    /// embedding are skipped. Returns each track's outcome in the order given; each
+    /// stored processing time is the run's share per track.
+    pub async fn process_batch(&self, tracks: Vec<(String, PathBuf)>) -> Result<Vec<Result<()>>> {
+        self.embed_batch(tracks, EmbeddingTarget::Live).await
+    }
 
-        if exists.is_some() {
-            debug!("Track {} already has embedding, skipping", track_id);
-            return Ok(());
-        }
+    /// Like [`AudioEncoder::process_batch`], but for a model migration: embeddings go to
+    /// `track_embeddings_staging` until [`AudioEncoder::finish_migration`], and tracks
+    /// already staged with the current model version are skipped
+    pub async fn stage_batch(&self, tracks: Vec<(String, PathBuf)>) -> Result<Vec<Result<()>>> {
+        self.embed_batch(tracks, EmbeddingTarget::Staging).await
+    }
 
-        // Encode the audio
-        match self.encode_file(audio_path).await {
-            Ok(embedding) => {
-                let processing_time = start.elapsed().as_millis() as i32;
-
-                // Normalize embedding to unit length for L2 distance similarity
-                let normalized = Self::normalize_embedding(embedding);
-
-                // Format vector as string for safe SQL binding (avoids binary protocol issues)
-                let vec_str = format!(
-                    "[{}]",
-                    normalized
-                        .iter()
-                        .map(|v| v.to_string())
-                        .collect::<Vec<_>>()
-                        .join(",")
-                );
+    async fn embed_batch(&self, tracks: Vec<(String, PathBuf)>, target: EmbeddingTarget) -> Result<Vec<Result<()>>> {
+        let start = Instant::now();
 
-                // Store embedding using raw SQL with string cast
-                sqlx::query(
-                    r#"
-                    INSERT INTO track_embeddings (track_id, embedding, processing_time_ms)
-                    VALUES ($1, $2::vector, $3)
-                    ON CONFLICT (track_id) DO UPDATE SET
-                        embedding = EXCLUDED.embedding,
-                        computed_at = NOW(),
-                        processing_time_ms = EXCLUDED.processing_time_ms
-                    "#,
+        // Check if already processed
+        let ids: Vec<&str> = tracks.iter().map(|(id, _)| id.as_str()).collect();
+        let existing: Vec<String> = match target {
+            EmbeddingTarget::Live => {
+                sqlx::query_scalar("SELECT track_id FROM track_embeddings WHERE track_id = ANY($1)")
+                    .bind(&ids)
+                    .fetch_all(&self.db)
+                    .await?
+            }
+            EmbeddingTarget::Staging => {
+                sqlx::query_scalar(
+                    "SELECT track_id FROM track_embeddings_staging WHERE track_id = ANY($1) AND model_version = $2",
                 )
-                .bind(track_id)
-                .bind(&vec_str)
-                .bind(processing_time)
-                .execute(&self.db)
-                .await?;
-
-                info!(
-                    "Stored embedding for track {} ({} ms)",
-                    track_id, processing_time
-                );
-                Ok(())
+                .bind(&ids)
+                .bind(&self.config.model_version)
+                .fetch_all(&self.db)
+                .await?
             }
-            Err(e) => {
-                // Record failure for retry later
-                sqlx::query!(
-                    r#"
+        };
+        let existing: HashSet<String> = existing.into_iter().collect();
+        let mut outcomes: Vec<Option<Result<()>>> = tracks
+            .iter()
+            .map(|(track_id, _)| {
+                existing.contains(track_id).then(|| {
+                    debug!("Track {} already has embedding, skipping", track_id);
+                    Ok(())
+                })
+            })
+            .collect();
+
+        let pending: Vec<usize> = (0..tracks.len()).filter(|&i| outcomes[i].is_none()).collect();
+        if pending.is_empty() {
+            return Ok(outcomes.into_iter().flatten().collect());
+        }
+        let encoded = self
+            .encode_batch(pending.iter().map(|&i| tracks[i].1.clone()).collect())
+            .await?;
+        let processing_time = (start.elapsed().as_millis() / pending.len() as u128) as i32;
+
+        for (&i, result) in pending.iter().zip(encoded) {
+            let track_id = &tracks[i].0;
+            outcomes[i] = Some(match result {
+                Ok(track) => {
+                    self.store_embedding(track_id, &track.windows, processing_time, target).await?;
+                    if let Some(features) = &track.features {
+                        self.store_features(track_id, features).await?;
+                    }
+                    Ok(())
+                }
+                Err(e) => {
+                    // Record failure for retry later
+                    sqlx::query!(
+                        r#"
                     INSERT INTO embedding_failures (track_id, error_message, error_type)
                     VALUES ($1, $2, $3)
                     ON CONFLICT (track_id) DO UPDATE SET
                         error_message = EXCLUDED.error_message,
+                        error_type = EXCLUDED.error_type,
                         attempt_count = embedding_failures.attempt_count + 1,
                         last_attempt = NOW()
                     "#,
-                    track_id,
-                    e.to_string(),
-                    "encode_error"
-                )
-                .execute(&self.db)
-                .await?;
+                        track_id,
+                        e.to_string(),
+                        failure_type(&e)
+                    )
+                    .execute(&self.db)
+                    .await?;
+                    Err(e)
+                }
+            });
+        }
 
-                Err(e)
-            }
+        Ok(outcomes.into_iter().map(|outcome| outcome.unwrap_or(Ok(()))).collect())
+    }
+
+    /// Write measured features over the track's guessed ones. Tempo and key from
+    /// AcousticBrainz are kept; energy always comes from here.
+    async fn store_features(&self, track_id: &str, features: &AudioFeatures) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE library_index SET
+                tempo = COALESCE(CASE WHEN features_source = 'acousticbrainz' THEN tempo END, $2, tempo),
+                musical_key = COALESCE(CASE WHEN features_source = 'acousticbrainz' THEN musical_key END, $3, musical_key),
+                energy_level = $4,
+                features_source = COALESCE(features_source, $5)
+            WHERE id = $1
+            "#,
+        )
+        .bind(track_id)
+        .bind(features.tempo)
+        .bind(&features.key)
+        .bind(features.energy)
+        .bind(audio_features::SOURCE)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn store_embedding(
+        &self,
+        track_id: &str,
+        windows: &[WindowEmbedding],
+        processing_time: i32,
+        target: EmbeddingTarget,
+    ) -> Result<()> {
+        // Unit length, for L2 distance similarity
+        let normalized = average_embedding(windows);
+
+        // Format vector as string for safe SQL binding (avoids binary protocol issues)
+        let vec_str = format!(
+            "[{}]",
+            normalized
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+
+        // Store embedding using raw SQL with string cast
+        let table = match target {
+            EmbeddingTarget::Live => "track_embeddings",
+            EmbeddingTarget::Staging => "track_embeddings_staging",
+        };
+        sqlx::query(&format!(
+            r#"
+            INSERT INTO {} (track_id, embedding, processing_time_ms, model_version)
+            VALUES ($1, $2::vector, $3, $4)
+            ON CONFLICT (track_id) DO UPDATE SET
+                embedding = EXCLUDED.embedding,
+                computed_at = NOW(),
+                processing_time_ms = EXCLUDED.processing_time_ms,
+                model_version = EXCLUDED.model_version
+            "#,
+            table
+        ))
+        .bind(track_id)
+        .bind(&vec_str)
+        .bind(processing_time)
+        .bind(&self.config.model_version)
+        .execute(&self.db)
+        .await?;
+
+        // Windows of staged tracks are dropped with their old embedding at the swap
+        if target == EmbeddingTarget::Live && self.config.store_windows && self.config.windows > 0 {
+            self.store_windows(track_id, windows).await?;
         }
+
+        info!(
+            "Stored embedding for track {} ({} ms)",
+            track_id, processing_time
+        );
+        Ok(())
+    }
+
+    /// Swap the staged embeddings of a migration in for the outdated ones, in one
+    /// transaction. Outdated embeddings that weren't re-encoded are removed rather than
+    /// compared with vectors of another model. Returns the number of embeddings replaced.
+    pub async fn finish_migration(&self) -> Result<u64> {
+        let version = &self.config.model_version;
+        let mut tx = self.db.begin().await?;
+
+        sqlx::query(
+            "DELETE FROM track_embedding_windows WHERE track_id IN
+             (SELECT track_id FROM track_embeddings WHERE model_version <> $1)",
+        )
+        .bind(version)
+        .execute(&mut *tx)
+        .await?;
+        let replaced = sqlx::query(
+            r#"
+            UPDATE track_embeddings te
+            SET embedding = s.embedding, model_version = s.model_version,
+                processing_time_ms = s.processing_time_ms, computed_at = s.computed_at
+            FROM track_embeddings_staging s
+            WHERE s.track_id = te.track_id AND s.model_version = $1 AND te.model_version <> $1
+            "#,
+        )
+        .bind(version)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        let dropped = sqlx::query("DELETE FROM track_embeddings WHERE model_version <> $1")
+            .bind(version)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        sqlx::query("DELETE FROM track_embeddings_staging").execute(&mut *tx).await?;
+
+        tx.commit().await?;
+        if dropped > 0 {
+            warn!("Removed {} outdated embeddings that couldn't be re-encoded", dropped);
+        }
+        info!("Embedding migration to {} complete: {} embeddings replaced", version, replaced);
+        Ok(replaced)
+    }
+
+    /// Replace the stored window embeddings of a track
+    async fn store_windows(&self, track_id: &str, windows: &[WindowEmbedding]) -> Result<()> {
+        let mut tx = self.db.begin().await?;
+        sqlx::query("DELETE FROM track_embedding_windows WHERE track_id = $1")
+            .bind(track_id)
+            .execute(&mut *tx)
+            .await?;
+        for (i, window) in windows.iter().enumerate() {
+            let vec_str = format!(
+                "[{}]",
+                window.embedding.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",")
+            );
+            sqlx::query(
+                "INSERT INTO track_embedding_windows (track_id, window_index, start_ms, embedding)
+                 VALUES ($1, $2, $3, $4::vector)",
+            )
+            .bind(track_id)
+            .bind(i as i16)
+            .bind(window.start_ms)
+            .bind(&vec_str)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
     }
 
     /// Find tracks similar to a given track
-    /// Filters by genre to ensure results are in compatible genres with the source track
+    /// Filters by genre to ensure results are in compatible genres with the source track.
+    /// Tracks without an embedding fall back to Navidrome's similar songs.
     pub async fn find_similar(
         &self,
         track_id: &str,
@@ -658,10 +1063,9 @@ impl AudioEncoder {
         exclude_ids: &[String],
     ) -> Result<Vec<(String, f32)>> {
         // Get the source track's embedding first
-        let source_embedding = self.get_embedding(track_id).await?;
-        let source_embedding = source_embedding.ok_or_else(|| {
-            AppError::InternalMessage(format!("No embedding for track {}", track_id))
-        })?;
+        let Some(source_embedding) = self.get_embedding(track_id).await? else {
+            return self.find_similar_via_navidrome(track_id, limit, exclude_ids).await;
+        };
 
         // Convert to pgvector::Vector and format as string for safe SQL binding
         let vec_str = format!(
@@ -675,10 +1079,19 @@ impl AudioEncoder {
 
         // Use raw SQL with L2 distance (<->) for better similarity spread
         // For normalized vectors, L2 distance ranges [0, 2], convert to similarity [1, 0]
-        // Also filter by genre to ensure results share at least one genre with the source
+        // The nearest candidates come from the ANN index, then are filtered by genre so
+        // results share at least one genre with the source
+        let candidates = vector_index::candidate_limit(limit, exclude_ids.len() + 1);
+        let mut tx = self.vector_search.begin(&self.db, candidates).await?;
         let results = sqlx::query_as::<_, (String, f64)>(
             r#"
-            WITH source_genres AS (
+            WITH nearest AS MATERIALIZED (
+                SELECT track_id, embedding <-> $1::vector AS distance
+                FROM track_embeddings
+                ORDER BY embedding <-> $1::vector
+                LIMIT $5
+            ),
+            source_genres AS (
                 SELECT DISTINCT g.genre
                 FROM library_index li,
                      jsonb_array_elements_text(li.genres) AS g(genre)
@@ -688,15 +1101,15 @@ impl AudioEncoder {
                 SELECT array_agg(genre) as genres FROM source_genres
             )
             SELECT
-                te.track_id,
-                1.0 - (te.embedding <-> $1::vector) / 2.0 as similarity
-            FROM track_embeddings te
-            JOIN library_index li ON te.track_id = li.id
+                n.track_id,
+                1.0 - n.distance / 2.0 as similarity
+            FROM nearest n
+            JOIN library_index li ON n.track_id = li.id
             CROSS JOIN allowed_genres ag
-            WHERE te.track_id != $2
-            AND te.track_id != ALL($3)
+            WHERE n.track_id != $2
+            AND n.track_id != ALL($3)
             AND (ag.genres IS NULL OR li.genres ?| ag.genres)
-            ORDER BY te.embedding <-> $1::vector
+            ORDER BY n.distance
             LIMIT $4
             "#,
         )
@@ -704,8 +1117,10 @@ impl AudioEncoder {
         .bind(track_id)
         .bind(exclude_ids)
         .bind(limit as i64)
-        .fetch_all(&self.db)
+        .bind(candidates)
+        .fetch_all(&mut *tx)
         .await?;
+        tx.commit().await?;
 
         Ok(results
             .into_iter()
@@ -713,6 +1128,49 @@ impl AudioEncoder {
             .collect())
     }
 
+    /// Similar tracks according to Navidrome (Last.fm), limited to indexed tracks.
+    /// They have no distance, so scores just fall off with rank.
+    async fn find_similar_via_navidrome(
+        &self,
+        track_id: &str,
+        limit: usize,
+        exclude_ids: &[String],
+    ) -> Result<Vec<(String, f32)>> {
+        let Some(navidrome) = &self.navidrome else {
+            return Err(AppError::NotFound(format!("No embedding for track {}", track_id)));
+        };
+
+        // Ask for extra to make up for excluded and unindexed tracks
+        let similar = navidrome
+            .get_similar_songs(track_id, limit + exclude_ids.len() + 10)
+            .await?;
+        let candidates: Vec<String> = similar
+            .into_iter()
+            .map(|t| t.id)
+            .filter(|id| id != track_id && !exclude_ids.contains(id))
+            .collect();
+        if candidates.is_empty() {
+            return Err(AppError::NotFound(format!(
+                "No embedding or similar songs for track {}",
+                track_id
+            )));
+        }
+
+        let indexed: Vec<String> = sqlx::query_scalar("SELECT id FROM library_index WHERE id = ANY($1)")
+            .bind(&candidates)
+            .fetch_all(&self.db)
+            .await?;
+
+        debug!("Track {} has no embedding, using Navidrome similar songs", track_id);
+        Ok(candidates
+            .into_iter()
+            .filter(|id| indexed.contains(id))
+            .take(limit)
+            .enumerate()
+            .map(|(rank, id)| (id, 1.0 / (rank as f32 + 2.0)))
+            .collect())
+    }
+
     /// Find transition tracks between two songs
     /// Filters by genre to ensure results share at least one genre with the source tracks
     pub async fn find_transition_tracks(
@@ -730,10 +1188,10 @@ impl AudioEncoder {
         let to_emb = self.get_embedding(to_track_id).await?;
 
         let from_emb = from_emb.ok_or_else(|| {
-            AppError::InternalMessage(format!("No embedding for track {}", from_track_id))
+            AppError::NotFound(format!("No embedding for track {}", from_track_id))
         })?;
         let to_emb = to_emb.ok_or_else(|| {
-            AppError::InternalMessage(format!("No embedding for track {}", to_track_id))
+            AppError::NotFound(format!("No embedding for track {}", to_track_id))
         })?;
 
         // Find tracks at interpolation points
@@ -745,6 +1203,9 @@ impl AudioEncoder {
         // Collect both source track IDs for genre filtering
         let source_ids = vec![from_track_id.to_string(), to_track_id.to_string()];
 
+        let candidates = vector_index::candidate_limit(1, all_exclude.len() + count);
+        let mut tx = self.vector_search.begin(&self.db, candidates).await?;
+
         for i in 1..=count {
             let t = i as f32 / (count + 1) as f32;
             let interp: Vec<f32> = from_emb
@@ -768,7 +1229,13 @@ impl AudioEncoder {
             // Also filter by genre to ensure results share genres with source tracks
             let closest: Option<String> = sqlx::query_scalar(
                 r#"
-                WITH source_genres AS (
+                WITH nearest AS MATERIALIZED (
+                    SELECT track_id, embedding <-> $1::vector AS distance
+                    FROM track_embeddings
+                    ORDER BY embedding <-> $1::vector
+                    LIMIT $4
+                ),
+                source_genres AS (
                     SELECT DISTINCT g.genre
                     FROM library_index li,
                          jsonb_array_elements_text(li.genres) AS g(genre)
@@ -777,20 +1244,21 @@ impl AudioEncoder {
                 allowed_genres AS (
                     SELECT array_agg(genre) as genres FROM source_genres
                 )
-                SELECT te.track_id
-                FROM track_embeddings te
-                JOIN library_index li ON te.track_id = li.id
+                SELECT n.track_id
+                FROM nearest n
+                JOIN library_index li ON n.track_id = li.id
                 CROSS JOIN allowed_genres ag
-                WHERE te.track_id != ALL($2)
+                WHERE n.track_id != ALL($2)
                 AND (ag.genres IS NULL OR li.genres ?| ag.genres)
-                ORDER BY te.embedding <-> $1::vector
+                ORDER BY n.distance
                 LIMIT 1
                 "#,
             )
             .bind(&vec_str)
             .bind(&all_exclude)
             .bind(&source_ids)
-            .fetch_optional(&self.db)
+            .bind(candidates)
+            .fetch_optional(&mut *tx)
             .await?;
 
             if let Some(track_id) = closest {
@@ -798,10 +1266,96 @@ impl AudioEncoder {
                 result.push(track_id);
             }
         }
+        tx.commit().await?;
 
         Ok(result)
     }
 
+    /// A playlist of `length` tracks from `from_track_id` to `to_track_id` that walks
+    /// the straight line between their embeddings. Each step takes a track near the next
+    /// evenly spaced point on the line: of the tracks at most `drift_tolerance` (L2
+    /// distance) farther from the point than the nearest one, the one closest to the
+    /// previous pick, so a higher tolerance trades accuracy for smoother transitions.
+    /// Steps that find no unused track are left out.
+    pub async fn journey(
+        &self,
+        from_track_id: &str,
+        to_track_id: &str,
+        length: usize,
+        drift_tolerance: f32,
+        exclude_ids: &[String],
+    ) -> Result<Vec<String>> {
+        let from_emb = self
+            .get_embedding(from_track_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("No embedding for track {}", from_track_id)))?;
+        let to_emb = self
+            .get_embedding(to_track_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("No embedding for track {}", to_track_id)))?;
+
+        let steps = length.saturating_sub(2);
+        let mut exclude: Vec<String> = exclude_ids.to_vec();
+        exclude.push(from_track_id.to_string());
+        exclude.push(to_track_id.to_string());
+
+        let candidates = vector_index::candidate_limit(JOURNEY_CANDIDATES, exclude.len() + steps);
+        let mut tx = self.vector_search.begin(&self.db, candidates).await?;
+
+        let mut playlist = vec![from_track_id.to_string()];
+        for i in 1..=steps {
+            let t = i as f32 / (steps + 1) as f32;
+            let waypoint: Vec<f32> = from_emb.iter().zip(&to_emb).map(|(&a, &b)| a * (1.0 - t) + b * t).collect();
+            let vec_str = format!(
+                "[{}]",
+                Self::normalize_embedding(waypoint)
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            );
+
+            // Distance to the waypoint, and to the previous track
+            let nearest: Vec<(String, f64, f64)> = sqlx::query_as(
+                r#"
+                WITH nearest AS MATERIALIZED (
+                    SELECT track_id, embedding, embedding <-> $1::vector AS distance
+                    FROM track_embeddings
+                    ORDER BY embedding <-> $1::vector
+                    LIMIT $4
+                )
+                SELECT n.track_id, n.distance,
+                       n.embedding <-> (SELECT embedding FROM track_embeddings WHERE track_id = $2)
+                FROM nearest n
+                WHERE n.track_id != ALL($3)
+                ORDER BY n.distance
+                LIMIT $5
+                "#,
+            )
+            .bind(&vec_str)
+            .bind(&playlist[playlist.len() - 1])
+            .bind(&exclude)
+            .bind(candidates)
+            .bind(JOURNEY_CANDIDATES as i64)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            let picked = pick_journey_step(
+                nearest.iter().map(|(_, distance, step)| (*distance as f32, *step as f32)),
+                drift_tolerance,
+            );
+            if let Some(index) = picked {
+                let track_id = nearest[index].0.clone();
+                exclude.push(track_id.clone());
+                playlist.push(track_id);
+            }
+        }
+        tx.commit().await?;
+
+        playlist.push(to_track_id.to_string());
+        Ok(playlist)
+    }
+
     /// Get embedding for a track
     async fn get_embedding(&self, track_id: &str) -> Result<Option<Vec<f32>>> {
         // Use raw SQL to avoid binary protocol issues with pgvector
@@ -829,11 +1383,14 @@ impl AudioEncoder {
     /// not just happen to match one seed coincidentally.
     ///
     /// Also filters by genre to ensure tracks are in a compatible genre with the seeds.
+    /// Tracks close to one in `liked_ids` (the listener's upvoted tracks) get a bonus,
+    /// so their neighborhoods rank higher.
     pub async fn find_similar_to_seeds(
         &self,
         seed_ids: &[String],
         limit: usize,
         exclude_ids: &[String],
+        liked_ids: &[String],
     ) -> Result<Vec<(String, f32)>> {
         if seed_ids.is_empty() {
             return Ok(Vec::new());
@@ -881,9 +1438,23 @@ impl AudioEncoder {
         // Find tracks closest to the centroid that share genres with seeds
         // Strategy: Collect ALL genres from ALL seed tracks, then only include tracks
         // that have at least one genre matching that combined set
-        let results = sqlx::query_as::<_, (String, f64)>(
+        // With liked tracks, fetch extra so boosted neighbors can move up
+        let liked_ids = &liked_ids[..liked_ids.len().min(MAX_LIKED_NEIGHBORHOODS)];
+        let fetch = if liked_ids.is_empty() { limit } else { limit * 2 };
+        let candidates = vector_index::candidate_limit(fetch, all_exclude.len());
+        let mut tx = self.vector_search.begin(&self.db, candidates).await?;
+        let results = sqlx::query_as::<_, (String, f64, Option<f64>)>(
             r#"
-            WITH seed_genres AS (
+            WITH nearest AS MATERIALIZED (
+                SELECT track_id, embedding, embedding <-> $1::vector AS distance
+                FROM track_embeddings
+                ORDER BY embedding <-> $1::vector
+                LIMIT $5
+            ),
+            liked AS (
+                SELECT embedding FROM track_embeddings WHERE track_id = ANY($6)
+            ),
+            seed_genres AS (
                 -- Collect all unique genres from all seed tracks
                 SELECT DISTINCT g.genre
                 FROM library_index li,
@@ -894,28 +1465,35 @@ impl AudioEncoder {
                 SELECT array_agg(genre) as genres FROM seed_genres
             )
             SELECT
-                te.track_id,
-                1.0 - (te.embedding <-> $1::vector) / 2.0 as similarity
-            FROM track_embeddings te
-            JOIN library_index li ON te.track_id = li.id
+                n.track_id,
+                1.0 - n.distance / 2.0 as similarity,
+                (SELECT MAX(1.0 - (n.embedding <-> l.embedding) / 2.0) FROM liked l) as liked_similarity
+            FROM nearest n
+            JOIN library_index li ON n.track_id = li.id
             CROSS JOIN allowed_genres ag
-            WHERE te.track_id != ALL($2)
+            WHERE n.track_id != ALL($2)
             AND li.genres ?| ag.genres  -- Track has at least one genre from the seed genres
-            ORDER BY te.embedding <-> $1::vector
+            ORDER BY n.distance
             LIMIT $3
             "#,
         )
         .bind(&vec_str)
         .bind(&all_exclude)
-        .bind(limit as i64)
+        .bind(fetch as i64)
         .bind(seed_ids)
-        .fetch_all(&self.db)
+        .bind(candidates)
+        .bind(liked_ids)
+        .fetch_all(&mut *tx)
         .await?;
+        tx.commit().await?;
 
-        Ok(results
+        let mut scored: Vec<(String, f32)> = results
             .into_iter()
-            .map(|(id, sim)| (id, sim as f32))
-            .collect())
+            .map(|(id, sim, liked)| (id, sim as f32 + neighborhood_bonus(liked.map(|l| l as f32))))
+            .collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        scored.truncate(limit);
+        Ok(scored)
     }
 
     // ========================================
@@ -953,10 +1531,58 @@ impl AudioEncoder {
         }
     }
 
-    /// Compute neighborhood-preserving embedding and update visualization cache
-    /// Uses PCA initialization followed by force-directed refinement to preserve local structure
-    pub async fn rebuild_visualization_cache(&self) -> Result<()> {
+    /// Start a visualization rebuild job, or return the one already queued or running
+    pub async fn submit_visualization_rebuild(self: &Arc<Self>, jobs: &JobManager) -> Result<Uuid> {
+        let encoder = self.clone();
+        let submitted = jobs
+            .submit(JobKind::VisualizationRebuild, serde_json::json!({}), 1, move |ctx| {
+                let encoder = encoder.clone();
+                async move {
+                    let tracks = encoder.rebuild_visualization_cache(&ctx).await?;
+                    Ok(serde_json::json!({ "tracks": tracks }))
+                }
+            })
+            .await;
+
+        match submitted {
+            Err(AppError::Conflict(msg)) => match jobs.find_active(JobKind::VisualizationRebuild).await? {
+                Some(job) => Ok(job.id),
+                None => Err(AppError::Conflict(msg)),
+            },
+            result => result,
+        }
+    }
+
+    /// Check the visualization cache every `interval` and rebuild it once it is stale.
+    /// Nothing is scheduled while embeddings are being indexed, since every new
+    /// embedding would make the result stale again.
+    pub fn spawn_visualization_refresh(self: Arc<Self>, jobs: Arc<JobManager>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+
+                let indexing = matches!(jobs.find_active(JobKind::EmbeddingIndex).await, Ok(Some(_)));
+                if indexing || !self.is_visualization_cache_stale().await.unwrap_or(false) {
+                    continue;
+                }
+
+                tracing::info!("Visualization cache is stale, scheduling a rebuild");
+                if let Err(e) = self.submit_visualization_rebuild(&jobs).await {
+                    tracing::warn!("Failed to start visualization rebuild: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Compute neighborhood-preserving embedding and update visualization cache.
+    /// Runs as a background job: the layout is computed on a blocking thread with its
+    /// progress reported to `ctx`, and coordinates are written in chunks. The cache
+    /// config is stored last, so an interrupted rebuild leaves the cache stale.
+    /// Returns the number of tracks placed.
+    pub async fn rebuild_visualization_cache(&self, ctx: &JobContext) -> Result<usize> {
         tracing::info!("Rebuilding visualization cache...");
+        ctx.progress(0.0, "Loading embeddings").await;
 
         // Fetch all embeddings
         let rows: Vec<(String, Vec<f32>)> = sqlx::query_as(
@@ -967,20 +1593,109 @@ impl AudioEncoder {
 
         if rows.is_empty() {
             tracing::info!("No embeddings to visualize");
-            return Ok(());
+            return Ok(0);
         }
 
         let n_samples = rows.len();
-        let n_features = rows[0].1.len();
-        tracing::info!("Computing neighborhood embedding for {} embeddings with {} features", n_samples, n_features);
+        let (track_ids, embeddings): (Vec<String>, Vec<Vec<f32>>) = rows.into_iter().unzip();
+
+        // The layout is CPU-bound; compute it off the async runtime and relay its progress
+        let (progress_tx, mut progress_rx) = mpsc::unbounded_channel::<(f64, String)>();
+        let cancel_ctx = ctx.clone();
+        let layout = tokio::task::spawn_blocking(move || {
+            Self::compute_layout(
+                &embeddings,
+                &|progress, message| {
+                    let _ = progress_tx.send((progress, message));
+                },
+                &|| cancel_ctx.is_cancelled(),
+            )
+        });
+        while let Some((progress, message)) = progress_rx.recv().await {
+            ctx.progress(progress * LAYOUT_PROGRESS_SHARE, message).await;
+        }
+        let Some(layout) = layout
+            .await
+            .map_err(|e| AppError::InternalMessage(format!("Visualization layout failed: {}", e)))?
+        else {
+            tracing::info!("Visualization rebuild cancelled");
+            return Ok(0);
+        };
+
+        // Update viz coordinates in chunks, so no single statement holds row locks for long
+        let mut stored = 0;
+        for (track_ids, positions) in track_ids
+            .chunks(VIZ_UPDATE_CHUNK)
+            .zip(layout.positions.chunks(VIZ_UPDATE_CHUNK))
+        {
+            if ctx.is_cancelled() {
+                tracing::info!("Visualization rebuild cancelled after {} tracks", stored);
+                return Ok(0);
+            }
 
-        // Collect embeddings into a matrix
-        let embeddings: Vec<Vec<f32>> = rows.iter().map(|(_, emb)| emb.clone()).collect();
-        let track_ids: Vec<String> = rows.iter().map(|(id, _)| id.clone()).collect();
+            let xs: Vec<f32> = positions.iter().map(|(x, _)| *x).collect();
+            let ys: Vec<f32> = positions.iter().map(|(_, y)| *y).collect();
+            sqlx::query(
+                r#"
+                UPDATE track_embeddings te
+                SET viz_x = u.x, viz_y = u.y
+                FROM UNNEST($1::text[], $2::real[], $3::real[]) AS u(track_id, x, y)
+                WHERE te.track_id = u.track_id
+                "#
+            )
+            .bind(track_ids)
+            .bind(&xs)
+            .bind(&ys)
+            .execute(&self.db)
+            .await?;
+
+            stored += track_ids.len();
+            ctx.progress(
+                LAYOUT_PROGRESS_SHARE + (1.0 - LAYOUT_PROGRESS_SHARE) * stored as f64 / n_samples as f64,
+                format!("Stored {} of {} positions", stored, n_samples),
+            )
+            .await;
+        }
+
+        // Store PCA config for consistent future projections
+        sqlx::query(
+            r#"
+            INSERT INTO visualization_config (id, pc1, pc2, mean_vec, track_count)
+            VALUES (1, $1, $2, $3, $4)
+            ON CONFLICT (id) DO UPDATE SET
+                pc1 = EXCLUDED.pc1,
+                pc2 = EXCLUDED.pc2,
+                mean_vec = EXCLUDED.mean_vec,
+                track_count = EXCLUDED.track_count,
+                updated_at = NOW()
+            "#
+        )
+        .bind(&layout.pc1)
+        .bind(&layout.pc2)
+        .bind(&layout.mean)
+        .bind(n_samples as i32)
+        .execute(&self.db)
+        .await?;
+
+        tracing::info!("Visualization cache rebuilt for {} tracks", n_samples);
+        Ok(n_samples)
+    }
+
+    /// Uses PCA initialization followed by force-directed refinement to preserve local
+    /// structure. Reports progress (0.0 to 1.0) as it goes; returns None if cancelled.
+    fn compute_layout(
+        embeddings: &[Vec<f32>],
+        progress: &(dyn Fn(f64, String) + Sync),
+        cancelled: &(dyn Fn() -> bool + Sync),
+    ) -> Option<VisualizationLayout> {
+        let n_samples = embeddings.len();
+        let n_features = embeddings[0].len();
+        tracing::info!("Computing neighborhood embedding for {} embeddings with {} features", n_samples, n_features);
+        progress(0.0, "Computing principal components".to_string());
 
         // Compute mean for centering
         let mut mean = vec![0.0f32; n_features];
-        for emb in &embeddings {
+        for emb in embeddings {
             for (i, &v) in emb.iter().enumerate() {
                 mean[i] += v;
             }
@@ -1010,7 +1725,12 @@ impl AudioEncoder {
         // This is used to apply attractive forces between neighbors
         let k = 15.min(n_samples - 1); // Number of neighbors
         tracing::info!("Computing {} nearest neighbors...", k);
-        let neighbors = Self::compute_knn(&embeddings, k);
+        let neighbors = Self::compute_knn(embeddings, k, &|done| {
+            progress(
+                0.05 + 0.6 * done as f64 / n_samples as f64,
+                format!("Finding neighbors ({} of {} tracks)", done, n_samples),
+            )
+        }, cancelled)?;
 
         // Step 3: Force-directed refinement (simplified t-SNE-like optimization)
         // This adjusts positions to pull neighbors closer together
@@ -1019,6 +1739,16 @@ impl AudioEncoder {
         let learning_rate = 0.5f32;
 
         for iter in 0..iterations {
+            if iter % 10 == 0 {
+                if cancelled() {
+                    return None;
+                }
+                progress(
+                    0.65 + 0.35 * iter as f64 / iterations as f64,
+                    format!("Refining layout ({} of {} iterations)", iter, iterations),
+                );
+            }
+
             let mut forces: Vec<(f32, f32)> = vec![(0.0, 0.0); n_samples];
 
             // Attractive forces between neighbors
@@ -1075,57 +1805,73 @@ impl AudioEncoder {
         );
         let range_x = (max_x - min_x).max(1e-6);
         let range_y = (max_y - min_y).max(1e-6);
+        for (x, y) in &mut positions {
+            *x = 2.0 * (*x - min_x) / range_x - 1.0;
+            *y = 2.0 * (*y - min_y) / range_y - 1.0;
+        }
 
-        // Update database in a transaction
-        let mut tx = self.db.begin().await?;
+        progress(1.0, "Layout computed".to_string());
+        Some(VisualizationLayout { pc1, pc2, mean, positions })
+    }
 
-        // Store PCA config for consistent future projections
-        sqlx::query(
-            r#"
-            INSERT INTO visualization_config (id, pc1, pc2, mean_vec, track_count)
-            VALUES (1, $1, $2, $3, $4)
-            ON CONFLICT (id) DO UPDATE SET
-                pc1 = EXCLUDED.pc1,
-                pc2 = EXCLUDED.pc2,
-                mean_vec = EXCLUDED.mean_vec,
-                track_count = EXCLUDED.track_count,
-                updated_at = NOW()
-            "#
-        )
-        .bind(&pc1)
-        .bind(&pc2)
-        .bind(&mean)
-        .bind(n_samples as i32)
-        .execute(&mut *tx)
-        .await?;
+    /// Compute k-nearest neighbors for each point, in parallel across all cores.
+    /// Up to `EXACT_KNN_LIMIT` points every pair is compared; beyond that, candidates
+    /// come from neighbors along a few random projections (see `projection_candidates`),
+    /// which keeps rebuilds of large libraries near-linear.
+    /// Returns a vector of neighbor indices for each point, or None if cancelled
+    fn compute_knn(
+        embeddings: &[Vec<f32>],
+        k: usize,
+        progress: &(dyn Fn(usize) + Sync),
+        cancelled: &(dyn Fn() -> bool + Sync),
+    ) -> Option<Vec<Vec<usize>>> {
+        let n = embeddings.len();
+        let projections = (n > EXACT_KNN_LIMIT).then(|| Self::sorted_projections(embeddings));
+        if projections.is_some() {
+            tracing::info!("{} tracks, approximating nearest neighbors", n);
+        }
 
-        // Update viz coordinates for all tracks
-        for (i, track_id) in track_ids.iter().enumerate() {
-            let (x, y) = positions[i];
-            let norm_x = 2.0 * (x - min_x) / range_x - 1.0;
-            let norm_y = 2.0 * (y - min_y) / range_y - 1.0;
+        // Report progress every 5%
+        let report_interval = (n / 20).max(1);
+        let done = AtomicUsize::new(0);
 
-            sqlx::query(
-                "UPDATE track_embeddings SET viz_x = $1, viz_y = $2 WHERE track_id = $3"
-            )
-            .bind(norm_x)
-            .bind(norm_y)
-            .bind(track_id)
-            .execute(&mut *tx)
-            .await?;
-        }
+        let neighbors: Vec<Option<Vec<usize>>> = (0..n)
+            .into_par_iter()
+            .map(|i| {
+                if cancelled() {
+                    return None;
+                }
 
-        tx.commit().await?;
-        tracing::info!("Visualization cache rebuilt for {} tracks", n_samples);
-        Ok(())
+                let knn = match &projections {
+                    Some(projections) => {
+                        let candidates = Self::projection_candidates(projections, i);
+                        Self::k_nearest(embeddings, i, candidates.into_iter(), k)
+                    }
+                    None => Self::k_nearest(embeddings, i, 0..n, k),
+                };
+
+                let finished = done.fetch_add(1, AtomicOrdering::Relaxed) + 1;
+                if finished.is_multiple_of(report_interval) {
+                    tracing::debug!("KNN progress: {}/{} ({:.0}%)", finished, n, (finished as f32 / n as f32) * 100.0);
+                    progress(finished);
+                }
+                Some(knn)
+            })
+            .collect();
+
+        neighbors.into_iter().collect()
     }
 
-    /// Compute k-nearest neighbors for each point using memory-efficient approach
-    /// Uses a max-heap of size k instead of storing all n-1 distances
-    /// Returns a vector of neighbor indices for each point
-    fn compute_knn(embeddings: &[Vec<f32>], k: usize) -> Vec<Vec<usize>> {
-        use std::collections::BinaryHeap;
+    /// The k candidates closest to point `i`, using a max-heap of size k instead of
+    /// storing all distances
+    fn k_nearest(
+        embeddings: &[Vec<f32>],
+        i: usize,
+        candidates: impl Iterator<Item = usize>,
+        k: usize,
+    ) -> Vec<usize> {
         use std::cmp::Ordering;
+        use std::collections::BinaryHeap;
 
         // Wrapper for max-heap (we want k smallest, so invert comparison)
         #[derive(PartialEq)]
@@ -1135,57 +1881,87 @@ impl AudioEncoder {
 
         impl PartialOrd for MaxDist {
             fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-                // Reverse order for max-heap behavior
-                self.0.partial_cmp(&other.0)
+                Some(self.cmp(other))
             }
         }
 
         impl Ord for MaxDist {
             fn cmp(&self, other: &Self) -> Ordering {
-                self.partial_cmp(other).unwrap_or(Ordering::Equal)
+                self.0.partial_cmp(&other.0).unwrap_or(Ordering::Equal)
             }
         }
 
-        let n = embeddings.len();
-        let mut neighbors = Vec::with_capacity(n);
+        let mut heap: BinaryHeap<MaxDist> = BinaryHeap::with_capacity(k + 1);
 
-        // Log progress every 10%
-        let log_interval = (n / 10).max(1);
+        for j in candidates {
+            if i == j { continue; }
 
-        for i in 0..n {
-            if i % log_interval == 0 && i > 0 {
-                tracing::debug!("KNN progress: {}/{} ({:.0}%)", i, n, (i as f32 / n as f32) * 100.0);
+            let dist: f32 = embeddings[i]
+                .iter()
+                .zip(&embeddings[j])
+                .map(|(a, b)| (a - b).powi(2))
+                .sum();
+
+            if heap.len() < k {
+                heap.push(MaxDist(dist, j));
+            } else if let Some(max) = heap.peek() {
+                if dist < max.0 {
+                    heap.pop();
+                    heap.push(MaxDist(dist, j));
+                }
             }
+        }
 
-            // Use a max-heap of size k to track k smallest distances
-            let mut heap: BinaryHeap<MaxDist> = BinaryHeap::with_capacity(k + 1);
-
-            for j in 0..n {
-                if i == j { continue; }
+        // Extract neighbor indices (already the k nearest)
+        let mut knn: Vec<usize> = heap.into_iter().map(|MaxDist(_, j)| j).collect();
+        knn.sort(); // Optional: sort by index for consistency
+        knn
+    }
 
-                let dist: f32 = embeddings[i]
-                    .iter()
-                    .zip(&embeddings[j])
-                    .map(|(a, b)| (a - b).powi(2))
-                    .sum();
+    /// For each of `APPROX_KNN_PROJECTIONS` fixed pseudo-random directions: the points
+    /// sorted by their projection onto it, and each point's position in that order
+    fn sorted_projections(embeddings: &[Vec<f32>]) -> Vec<(Vec<usize>, Vec<usize>)> {
+        let n_features = embeddings[0].len();
+        // Deterministic LCG, so rebuilds of the same library give the same layout
+        let mut seed: u64 = 0x2545_F491_4F6C_DD1D;
+        let mut next = move || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((seed >> 33) as f32 / (1u64 << 31) as f32) - 0.5
+        };
 
-                if heap.len() < k {
-                    heap.push(MaxDist(dist, j));
-                } else if let Some(max) = heap.peek() {
-                    if dist < max.0 {
-                        heap.pop();
-                        heap.push(MaxDist(dist, j));
-                    }
+        (0..APPROX_KNN_PROJECTIONS)
+            .map(|_| {
+                let direction: Vec<f32> = (0..n_features).map(|_| next()).collect();
+                let values: Vec<f32> = embeddings
+                    .par_iter()
+                    .map(|e| e.iter().zip(&direction).map(|(a, b)| a * b).sum())
+                    .collect();
+
+                let mut order: Vec<usize> = (0..embeddings.len()).collect();
+                order.par_sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+                let mut rank = vec![0; order.len()];
+                for (position, &point) in order.iter().enumerate() {
+                    rank[point] = position;
                 }
-            }
+                (order, rank)
+            })
+            .collect()
+    }
 
-            // Extract neighbor indices (already the k nearest)
-            let mut knn: Vec<usize> = heap.into_iter().map(|MaxDist(_, j)| j).collect();
-            knn.sort(); // Optional: sort by index for consistency
-            neighbors.push(knn);
+    /// Points within `APPROX_KNN_WINDOW` places of point `i` along any projection.
+    /// Points close in the full space are close along most directions, so the true
+    /// nearest neighbors are very likely among them.
+    fn projection_candidates(projections: &[(Vec<usize>, Vec<usize>)], i: usize) -> Vec<usize> {
+        let mut candidates = Vec::with_capacity(projections.len() * APPROX_KNN_WINDOW * 2);
+        for (order, rank) in projections {
+            let position = rank[i];
+            let start = position.saturating_sub(APPROX_KNN_WINDOW);
+            let end = (position + APPROX_KNN_WINDOW + 1).min(order.len());
+            candidates.extend_from_slice(&order[start..end]);
         }
-
-        neighbors
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates
     }
 
     /// Power iteration to find top 2 principal components
@@ -1286,56 +2062,300 @@ impl AudioEncoder {
 
         Ok(())
     }
+}
 
-    /// Get embedding processing status
-    pub async fn get_status(&self) -> Result<EmbeddingStatus> {
-        // Get basic counts
-        let total_tracks = sqlx::query_scalar!(
-            "SELECT COUNT(*)::int4 as \"count!\" FROM library_index"
-        )
-        .fetch_one(&self.db)
+/// Minutes of recent embeddings that the indexing throughput is measured over
+const THROUGHPUT_WINDOW_MINUTES: i32 = 10;
+
+/// Track counts (total, embedded, pending, failed), average/p50/p95 processing times in
+/// ms, and the number of embeddings made within the throughput window
+type StatusCounts = (i64, i64, i64, i64, Option<f64>, Option<f64>, Option<f64>, i64);
+
+/// Get embedding processing status: coverage, failures by type, processing times,
+/// how long the pending tracks will take at the current rate, and how far embeddings
+/// are from `model_version`
+pub async fn embedding_status(db: &PgPool, model_version: &str) -> Result<EmbeddingStatus> {
+    let (total_tracks, with_embeddings, pending, failed, avg_ms, p50_ms, p95_ms, recent): StatusCounts = sqlx::query_as(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM library_index),
+            (SELECT COUNT(*) FROM track_embeddings),
+            (
+                SELECT COUNT(*) FROM library_index li
+                WHERE NOT EXISTS (SELECT 1 FROM track_embeddings te WHERE te.track_id = li.id)
+                AND NOT EXISTS (
+                    SELECT 1 FROM embedding_failures ef WHERE ef.track_id = li.id AND ef.resolved = false
+                )
+            ),
+            (SELECT COUNT(*) FROM embedding_failures WHERE resolved = false),
+            AVG(processing_time_ms)::float8,
+            percentile_cont(0.5) WITHIN GROUP (ORDER BY processing_time_ms)::float8,
+            percentile_cont(0.95) WITHIN GROUP (ORDER BY processing_time_ms)::float8,
+            COUNT(*) FILTER (WHERE computed_at > NOW() - make_interval(mins => $1))
+        FROM track_embeddings
+        "#,
+    )
+    .bind(THROUGHPUT_WINDOW_MINUTES)
+    .fetch_one(db)
+    .await?;
+
+    let failures_by_type: BTreeMap<String, i64> = sqlx::query_as(
+        r#"
+        SELECT COALESCE(error_type, 'unknown'), COUNT(*)
+        FROM embedding_failures
+        WHERE resolved = false
+        GROUP BY 1
+        "#,
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .collect();
+
+    let embeddings_by_version: BTreeMap<String, i64> =
+        sqlx::query_as("SELECT model_version, COUNT(*) FROM track_embeddings GROUP BY 1")
+            .fetch_all(db)
+            .await?
+            .into_iter()
+            .collect();
+    let outdated = embeddings_by_version
+        .iter()
+        .filter(|(version, _)| version.as_str() != model_version)
+        .map(|(_, count)| count)
+        .sum();
+    let staged: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM track_embeddings_staging WHERE model_version = $1")
+        .bind(model_version)
+        .fetch_one(db)
         .await?;
 
-        let with_embeddings = sqlx::query_scalar!(
-            "SELECT COUNT(*)::int4 as \"count!\" FROM track_embeddings"
-        )
-        .fetch_one(&self.db)
-        .await?;
+    let coverage = if total_tracks > 0 {
+        (with_embeddings as f64 / total_tracks as f64) * 100.0
+    } else {
+        0.0
+    };
+    let throughput = recent as f64 / THROUGHPUT_WINDOW_MINUTES as f64;
+    let eta_secs = (throughput > 0.0).then(|| (pending as f64 / throughput * 60.0).round() as u64);
+
+    Ok(EmbeddingStatus {
+        total_tracks,
+        tracks_with_embeddings: with_embeddings,
+        tracks_pending: pending,
+        tracks_failed: failed,
+        coverage_percent: coverage,
+        failures_by_type,
+        avg_processing_time_ms: avg_ms,
+        p50_processing_time_ms: p50_ms,
+        p95_processing_time_ms: p95_ms,
+        throughput_per_minute: throughput,
+        eta_secs,
+        model_version: model_version.to_string(),
+        embeddings_by_version,
+        outdated_embeddings: outdated,
+        staged_embeddings: staged,
+        updated_at: chrono::Utc::now(),
+    })
+}
 
-        let failed = sqlx::query_scalar!(
-            "SELECT COUNT(*)::int4 as \"count!\" FROM embedding_failures"
-        )
-        .fetch_one(&self.db)
-        .await?;
+/// Sample offsets of `count` windows of `window` samples in a track of `total`
+/// samples, spread evenly between the first and last `WINDOW_EDGE_SKIP` of it. Tracks
+/// too short for that use their full length; tracks shorter than one window are one.
+fn window_starts(total: usize, window: usize, count: usize) -> Vec<usize> {
+    if total <= window || count == 0 {
+        return vec![0];
+    }
+    let skip = (total as f64 * WINDOW_EDGE_SKIP) as usize;
+    let (first, last) = if total - 2 * skip >= window {
+        (skip, total - skip - window)
+    } else {
+        (0, total - window)
+    };
+    if count == 1 {
+        return vec![first + (last - first) / 2];
+    }
+    (0..count).map(|i| first + (last - first) * i / (count - 1)).collect()
+}
 
-        let pending = total_tracks - with_embeddings - failed;
-        let coverage = if total_tracks > 0 {
-            (with_embeddings as f64 / total_tracks as f64) * 100.0
-        } else {
-            0.0
-        };
+/// Mean of unit-length window embeddings, itself scaled to unit length
+fn average_embedding(windows: &[WindowEmbedding]) -> Vec<f32> {
+    let mut sum = vec![0.0f32; windows.first().map_or(0, |w| w.embedding.len())];
+    for window in windows {
+        for (total, value) in sum.iter_mut().zip(&window.embedding) {
+            *total += value;
+        }
+    }
+    AudioEncoder::normalize_embedding(sum)
+}
 
-        Ok(EmbeddingStatus {
-            total_tracks,
-            tracks_with_embeddings: with_embeddings,
-            tracks_pending: pending,
-            tracks_failed: failed,
-            coverage_percent: coverage,
-            avg_processing_time_ms: None,
-            model_version: "v1".to_string(),
-            updated_at: chrono::Utc::now(),
-        })
+/// The journey step among candidates (distance to the waypoint, distance to the previous
+/// track): the closest to the previous track of those within `drift_tolerance` of the
+/// nearest candidate's distance to the waypoint
+fn pick_journey_step(candidates: impl Iterator<Item = (f32, f32)>, drift_tolerance: f32) -> Option<usize> {
+    let candidates: Vec<(f32, f32)> = candidates.collect();
+    let nearest = candidates.iter().map(|&(distance, _)| distance).fold(f32::INFINITY, f32::min);
+    candidates
+        .iter()
+        .enumerate()
+        .filter(|(_, &(distance, _))| distance <= nearest + drift_tolerance)
+        .min_by(|(_, a), (_, b)| a.1.total_cmp(&b.1))
+        .map(|(index, _)| index)
+}
+
+/// Table [`AudioEncoder::embed_batch`] stores embeddings in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EmbeddingTarget {
+    /// `track_embeddings`, used by similarity search
+    Live,
+    /// `track_embeddings_staging`, for a model migration in progress
+    Staging,
+}
+
+/// Failure category stored in `embedding_failures.error_type`
+fn failure_type(error: &AppError) -> &'static str {
+    match error {
+        AppError::Storage(_) => "file_error",
+        AppError::Decode(msg) if msg.contains("too short") => "too_short",
+        AppError::Decode(_) => "decode_error",
+        AppError::Model(_) => "model_error",
+        _ => "encode_error",
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct EmbeddingStatus {
-    pub total_tracks: i32,
-    pub tracks_with_embeddings: i32,
-    pub tracks_pending: i32,
-    pub tracks_failed: i32,
+    pub total_tracks: i64,
+    pub tracks_with_embeddings: i64,
+    /// Tracks with neither an embedding nor an unresolved failure
+    pub tracks_pending: i64,
+    pub tracks_failed: i64,
     pub coverage_percent: f64,
+    /// Unresolved failures by error type (file_error, decode_error, model_error, ...)
+    pub failures_by_type: BTreeMap<String, i64>,
     pub avg_processing_time_ms: Option<f64>,
+    pub p50_processing_time_ms: Option<f64>,
+    pub p95_processing_time_ms: Option<f64>,
+    /// Embeddings stored per minute over the last few minutes
+    pub throughput_per_minute: f64,
+    /// Seconds until all pending tracks are embedded at the current throughput
+    /// (None while nothing is being embedded)
+    pub eta_secs: Option<u64>,
+    /// The configured model version
     pub model_version: String,
+    pub embeddings_by_version: BTreeMap<String, i64>,
+    /// Embeddings of other model versions, replaced by `POST /library/embeddings/migrate`
+    pub outdated_embeddings: i64,
+    /// Outdated embeddings already re-encoded by a migration, waiting for it to finish
+    pub staged_embeddings: i64,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
+
+/// Bonus of a candidate whose similarity to the closest liked track is `liked_similarity`
+fn neighborhood_bonus(liked_similarity: Option<f32>) -> f32 {
+    match liked_similarity {
+        Some(similarity) if similarity >= LIKED_NEIGHBORHOOD_SIMILARITY => {
+            let closeness = (similarity - LIKED_NEIGHBORHOOD_SIMILARITY) / (1.0 - LIKED_NEIGHBORHOOD_SIMILARITY);
+            LIKED_NEIGHBORHOOD_BOOST * closeness.min(1.0)
+        }
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_starts() {
+        // 100s track, 5s windows: spread over 10s..85s
+        assert_eq!(window_starts(100, 5, 4), vec![10, 35, 60, 85]);
+        assert_eq!(window_starts(100, 5, 1), vec![47]);
+        // Middle 80% shorter than a window: the whole track is used
+        assert_eq!(window_starts(10, 9, 2), vec![0, 1]);
+        assert_eq!(window_starts(4, 5, 3), vec![0]);
+    }
+
+    #[test]
+    fn test_average_embedding() {
+        let windows = [
+            WindowEmbedding { start_ms: 0, embedding: vec![1.0, 0.0] },
+            WindowEmbedding { start_ms: 5000, embedding: vec![0.0, 1.0] },
+        ];
+        let average = average_embedding(&windows);
+        assert!((average[0] - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+        assert!((average[1] - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_pick_journey_step() {
+        let candidates = [(0.30, 0.90), (0.35, 0.20), (0.60, 0.05)];
+        // No drift: the track nearest the waypoint
+        assert_eq!(pick_journey_step(candidates.into_iter(), 0.0), Some(0));
+        // Some drift: the smoother of the tracks nearly as close
+        assert_eq!(pick_journey_step(candidates.into_iter(), 0.1), Some(1));
+        assert_eq!(pick_journey_step(candidates.into_iter(), 1.0), Some(2));
+        assert_eq!(pick_journey_step(std::iter::empty(), 0.1), None);
+    }
+
+    #[test]
+    fn test_neighborhood_bonus() {
+        assert_eq!(neighborhood_bonus(None), 0.0);
+        assert_eq!(neighborhood_bonus(Some(0.5)), 0.0);
+        assert_eq!(neighborhood_bonus(Some(LIKED_NEIGHBORHOOD_SIMILARITY)), 0.0);
+        assert_eq!(neighborhood_bonus(Some(1.0)), LIKED_NEIGHBORHOOD_BOOST);
+        let halfway = (1.0 + LIKED_NEIGHBORHOOD_SIMILARITY) / 2.0;
+        assert!((neighborhood_bonus(Some(halfway)) - LIKED_NEIGHBORHOOD_BOOST / 2.0).abs() < 1e-6);
+    }
+
+    /// Points in tight clusters around well separated centers
+    fn clustered(clusters: usize, per_cluster: usize, dims: usize) -> Vec<Vec<f32>> {
+        let mut seed: u64 = 42;
+        let mut noise = move || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((seed >> 33) as f32 / (1u64 << 31) as f32) - 0.5
+        };
+        (0..clusters * per_cluster)
+            .map(|i| {
+                let cluster = i / per_cluster;
+                (0..dims)
+                    .map(|d| if d % clusters == cluster { 10.0 } else { 0.0 } + noise() * 0.1)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_knn_finds_points_in_same_cluster() {
+        let embeddings = clustered(4, 20, 16);
+        let neighbors = AudioEncoder::compute_knn(&embeddings, 5, &|_| {}, &|| false).unwrap();
+
+        assert_eq!(neighbors.len(), embeddings.len());
+        for (i, knn) in neighbors.iter().enumerate() {
+            assert_eq!(knn.len(), 5);
+            assert!(!knn.contains(&i));
+            assert!(knn.iter().all(|&j| j / 20 == i / 20), "point {} got {:?}", i, knn);
+        }
+    }
+
+    #[test]
+    fn test_projection_candidates_recall_exact_neighbors() {
+        let embeddings = clustered(8, 200, 32);
+        let projections = AudioEncoder::sorted_projections(&embeddings);
+
+        let mut found = 0;
+        let mut total = 0;
+        for i in (0..embeddings.len()).step_by(37) {
+            let exact = AudioEncoder::k_nearest(&embeddings, i, 0..embeddings.len(), 10);
+            let candidates = AudioEncoder::projection_candidates(&projections, i);
+            let approx = AudioEncoder::k_nearest(&embeddings, i, candidates.into_iter(), 10);
+            assert!(approx.iter().all(|&j| j / 200 == i / 200));
+            found += approx.iter().filter(|j| exact.contains(j)).count();
+            total += exact.len();
+        }
+        assert!(found * 10 >= total, "recall {}/{}", found, total);
+    }
+
+    #[test]
+    fn test_knn_stops_when_cancelled() {
+        let embeddings = clustered(2, 10, 4);
+        assert!(AudioEncoder::compute_knn(&embeddings, 3, &|_| {}, &|| true).is_none());
+    }
+}
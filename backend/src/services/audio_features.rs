@@ -0,0 +1,263 @@
+//! Audio features measured from decoded samples
+//!
+//! Runs on the mono, resampled audio the embedding model gets, so the features cost
+//! one short-time Fourier transform per track on top of embedding:
+//! - tempo: autocorrelation of the spectral-flux onset envelope between 60 and 180 BPM,
+//!   weighted towards 120 BPM to settle half/double-tempo ambiguity. Tracks without a
+//!   clear pulse get none.
+//! - key: the track's chroma profile correlated with the Krumhansl-Kessler major and
+//!   minor key profiles, named like AcousticBrainz does ("Eb major")
+//! - energy: loudness (RMS between -30 and -6 dBFS) and brightness (spectral centroid
+//!   up to 3 kHz) combined into 0-1
+
+use rustfft::{num_complex::Complex, FftPlanner};
+
+const FRAME: usize = 2048;
+const HOP: usize = 512;
+
+const MIN_BPM: f64 = 60.0;
+const MAX_BPM: f64 = 180.0;
+/// Tempo candidates are weighted by a log-normal centered here, one octave wide
+const PREFERRED_BPM: f64 = 120.0;
+/// Autocorrelation at the beat period, relative to the envelope's variance, below
+/// which a track has no discernible pulse
+const MIN_PULSE_STRENGTH: f64 = 0.05;
+
+/// Spectrum range that pitch classes are read from
+const CHROMA_MIN_HZ: f32 = 110.0;
+const CHROMA_MAX_HZ: f32 = 2000.0;
+
+/// Shorter audio isn't analyzed
+const MIN_DURATION_SECS: usize = 10;
+
+/// Krumhansl-Kessler key profiles, from the tonic up
+const MAJOR_PROFILE: [f64; 12] = [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f64; 12] = [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+const PITCH_CLASSES: [&str; 12] = ["C", "C#", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B"];
+
+/// Source recorded in `library_index.features_source` for these features
+pub const SOURCE: &str = "dsp";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioFeatures {
+    /// Beats per minute
+    pub tempo: Option<f64>,
+    /// e.g. "A minor"
+    pub key: Option<String>,
+    /// 0 (quiet, dark) to 1 (loud, bright)
+    pub energy: f64,
+}
+
+/// Measure a track's features. None for audio that is too short or silent.
+pub fn analyze(samples: &[f32], sample_rate: u32) -> Option<AudioFeatures> {
+    if samples.len() < sample_rate as usize * MIN_DURATION_SECS {
+        return None;
+    }
+    let mean_square = samples.iter().map(|&s| (s as f64) * (s as f64)).sum::<f64>() / samples.len() as f64;
+    if mean_square < 1e-8 {
+        return None;
+    }
+
+    let fft = FftPlanner::<f32>::new().plan_fft_forward(FRAME);
+    let window: Vec<f32> = (0..FRAME)
+        .map(|i| 0.5 * (1.0 - (2.0 * std::f32::consts::PI * i as f32 / (FRAME - 1) as f32).cos()))
+        .collect();
+    let n_bins = FRAME / 2 + 1;
+    let bin_hz = sample_rate as f32 / FRAME as f32;
+    let pitch_class: Vec<Option<usize>> = (0..n_bins)
+        .map(|bin| {
+            let hz = bin as f32 * bin_hz;
+            (CHROMA_MIN_HZ..=CHROMA_MAX_HZ).contains(&hz).then(|| {
+                let midi = 69.0 + 12.0 * (hz / 440.0).log2();
+                (midi.round() as i64).rem_euclid(12) as usize
+            })
+        })
+        .collect();
+    // A full-scale sine peaks at FRAME / 4 through the Hann window
+    let scale = FRAME as f32 / 4.0;
+
+    let mut onsets = Vec::with_capacity(samples.len() / HOP);
+    let mut chroma = [0.0f64; 12];
+    let mut power = vec![0.0f64; n_bins];
+    let mut previous: Option<Vec<f32>> = None;
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); FRAME];
+
+    for start in (0..samples.len() - FRAME).step_by(HOP) {
+        for (slot, (&sample, &w)) in buffer.iter_mut().zip(samples[start..start + FRAME].iter().zip(&window)) {
+            *slot = Complex::new(sample * w, 0.0);
+        }
+        fft.process(&mut buffer);
+
+        let magnitudes: Vec<f32> = buffer[..n_bins].iter().map(|c| c.norm() / scale).collect();
+        let compressed: Vec<f32> = magnitudes.iter().map(|&m| (1.0 + 100.0 * m).ln()).collect();
+        if let Some(previous) = &previous {
+            let flux: f32 = compressed
+                .iter()
+                .zip(previous)
+                .map(|(now, before)| (now - before).max(0.0))
+                .sum();
+            onsets.push(flux as f64);
+        }
+        previous = Some(compressed);
+
+        for (bin, &magnitude) in magnitudes.iter().enumerate() {
+            power[bin] += (magnitude as f64) * (magnitude as f64);
+            if let Some(pc) = pitch_class[bin] {
+                chroma[pc] += magnitude as f64;
+            }
+        }
+    }
+
+    let rms_db = 10.0 * mean_square.log10();
+    let loudness = ((rms_db + 30.0) / 24.0).clamp(0.0, 1.0);
+    let total_power: f64 = power.iter().sum();
+    let centroid_hz = if total_power > 0.0 {
+        power.iter().enumerate().map(|(bin, p)| bin as f64 * bin_hz as f64 * p).sum::<f64>() / total_power
+    } else {
+        0.0
+    };
+    let brightness = (centroid_hz / 3000.0).clamp(0.0, 1.0);
+
+    Some(AudioFeatures {
+        tempo: estimate_tempo(&onsets, sample_rate as f64 / HOP as f64),
+        key: estimate_key(&chroma),
+        energy: 0.6 * loudness + 0.4 * brightness,
+    })
+}
+
+/// Tempo of an onset envelope sampled `frame_rate` times per second
+fn estimate_tempo(onsets: &[f64], frame_rate: f64) -> Option<f64> {
+    let min_lag = ((frame_rate * 60.0 / MAX_BPM).floor() as usize).max(2);
+    let max_lag = (frame_rate * 60.0 / MIN_BPM).ceil() as usize;
+    if onsets.len() < max_lag * 4 {
+        return None;
+    }
+
+    let mean = onsets.iter().sum::<f64>() / onsets.len() as f64;
+    let envelope: Vec<f64> = onsets.iter().map(|o| o - mean).collect();
+    let autocorrelation = |lag: usize| {
+        let n = envelope.len() - lag;
+        envelope[..n].iter().zip(&envelope[lag..]).map(|(a, b)| a * b).sum::<f64>() / n as f64
+    };
+    let variance = autocorrelation(0);
+    if variance <= 0.0 {
+        return None;
+    }
+
+    let (best_lag, best_ac) = (min_lag..=max_lag)
+        .map(|lag| (lag, autocorrelation(lag)))
+        .filter(|(_, ac)| *ac > 0.0)
+        .max_by(|(lag_a, ac_a), (lag_b, ac_b)| {
+            let weighted = |lag: usize, ac: f64| {
+                let octaves = (60.0 * frame_rate / lag as f64 / PREFERRED_BPM).log2();
+                ac * (-0.5 * octaves * octaves).exp()
+            };
+            weighted(*lag_a, *ac_a).total_cmp(&weighted(*lag_b, *ac_b))
+        })?;
+    if best_ac / variance < MIN_PULSE_STRENGTH {
+        return None;
+    }
+
+    // Parabolic interpolation between the neighboring lags
+    let (before, after) = (autocorrelation(best_lag - 1), autocorrelation(best_lag + 1));
+    let curvature = before - 2.0 * best_ac + after;
+    let offset = if curvature < 0.0 { 0.5 * (before - after) / curvature } else { 0.0 };
+
+    Some(60.0 * frame_rate / (best_lag as f64 + offset))
+}
+
+/// Key whose profile correlates best with a chroma profile (C first)
+fn estimate_key(chroma: &[f64; 12]) -> Option<String> {
+    let mut best: Option<(f64, String)> = None;
+    for tonic in 0..12 {
+        for (profile, scale) in [(&MAJOR_PROFILE, "major"), (&MINOR_PROFILE, "minor")] {
+            let rotated: Vec<f64> = (0..12).map(|pc| profile[(pc + 12 - tonic) % 12]).collect();
+            let Some(r) = pearson(chroma, &rotated) else {
+                continue;
+            };
+            if !best.as_ref().is_some_and(|(best_r, _)| r <= *best_r) {
+                best = Some((r, format!("{} {}", PITCH_CLASSES[tonic], scale)));
+            }
+        }
+    }
+    best.map(|(_, key)| key)
+}
+
+fn pearson(a: &[f64], b: &[f64]) -> Option<f64> {
+    let mean_a = a.iter().sum::<f64>() / a.len() as f64;
+    let mean_b = b.iter().sum::<f64>() / b.len() as f64;
+    let covariance: f64 = a.iter().zip(b).map(|(x, y)| (x - mean_a) * (y - mean_b)).sum();
+    let spread_a = a.iter().map(|x| (x - mean_a).powi(2)).sum::<f64>().sqrt();
+    let spread_b = b.iter().map(|y| (y - mean_b).powi(2)).sum::<f64>().sqrt();
+    (spread_a > 0.0 && spread_b > 0.0).then_some(covariance / (spread_a * spread_b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 22050;
+
+    fn pulse_train(bpm: f64, frame_rate: f64, secs: f64) -> Vec<f64> {
+        let mut onsets = vec![0.0; (frame_rate * secs) as usize];
+        let period = frame_rate * 60.0 / bpm;
+        let mut beat = 0.0_f64;
+        while (beat.round() as usize) + 1 < onsets.len() {
+            let i = beat.round() as usize;
+            onsets[i] += 1.0;
+            onsets[i + 1] += 0.5;
+            beat += period;
+        }
+        onsets
+    }
+
+    #[test]
+    fn test_tempo_of_pulse_trains() {
+        let frame_rate = RATE as f64 / HOP as f64;
+        for bpm in [75.0, 90.0, 120.0, 128.0, 150.0] {
+            let tempo = estimate_tempo(&pulse_train(bpm, frame_rate, 30.0), frame_rate).unwrap();
+            assert!((tempo - bpm).abs() < 2.0, "{} BPM estimated as {}", bpm, tempo);
+        }
+        assert_eq!(estimate_tempo(&vec![1.0; 2000], frame_rate), None);
+    }
+
+    #[test]
+    fn test_key_of_triads() {
+        let chord = |pitch_classes: &[usize]| {
+            let mut chroma = [0.0; 12];
+            for &pc in pitch_classes {
+                chroma[pc] = 1.0;
+            }
+            chroma
+        };
+        assert_eq!(estimate_key(&chord(&[0, 4, 7])).as_deref(), Some("C major"));
+        assert_eq!(estimate_key(&chord(&[9, 0, 4])).as_deref(), Some("A minor"));
+        assert_eq!(estimate_key(&chord(&[7, 11, 2])).as_deref(), Some("G major"));
+        assert_eq!(estimate_key(&[0.0; 12]), None);
+    }
+
+    #[test]
+    fn test_loud_bright_audio_has_more_energy() {
+        let secs = 12;
+        let n = RATE as usize * secs;
+        // Deterministic white noise
+        let mut state: u32 = 1;
+        let noise: Vec<f32> = (0..n)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1u32 << 24) as f32 * 1.6 - 0.8
+            })
+            .collect();
+        let hum: Vec<f32> = (0..n)
+            .map(|i| 0.05 * (2.0 * std::f32::consts::PI * 110.0 * i as f32 / RATE as f32).sin())
+            .collect();
+
+        let loud = analyze(&noise, RATE).unwrap();
+        let quiet = analyze(&hum, RATE).unwrap();
+        assert!(loud.energy > 0.8, "noise energy {}", loud.energy);
+        assert!(quiet.energy < 0.2, "hum energy {}", quiet.energy);
+
+        assert_eq!(analyze(&hum[..RATE as usize], RATE), None);
+        assert_eq!(analyze(&vec![0.0; n], RATE), None);
+    }
+}
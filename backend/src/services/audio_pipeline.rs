@@ -1,25 +1,36 @@
 //! Audio Pipeline Service
 //!
 //! Server-side audio processing pipeline that:
-//! 1. Fetches audio from Navidrome
-//! 2. Decodes to PCM samples (using Symphonia)
+//! 1. Reads audio from the mounted music library, or fetches it from Navidrome (or
+//!    plays a station's fallback tracks while that keeps failing)
+//! 2. Decodes to PCM samples (using Symphonia) a chunk at a time as the audio arrives,
+//!    so memory is bounded by the playback buffer rather than the length of the track
 //! 3. Manages continuous playback buffer with track transitions
 //! 4. Provides samples for encoding/broadcasting
 
 #![allow(dead_code)]
 
 use crate::error::{AppError, Result};
+use crate::services::announcer::StationAnnouncer;
+use crate::services::audio_cache::{self, AudioCache};
+use crate::services::loudness::TrackLoudness;
+use crate::services::resampler::{self, ResampleQuality};
 use crate::services::NavidromeClient;
 use bytes::Bytes;
+use futures::StreamExt;
 use std::collections::VecDeque;
-use std::sync::Arc;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use symphonia::core::audio::SampleBuffer;
 use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
 use symphonia::core::formats::FormatOptions;
-use symphonia::core::io::MediaSourceStream;
+use symphonia::core::io::{MediaSource, MediaSourceStream};
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
-use tokio::sync::{broadcast, mpsc, RwLock};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{broadcast, mpsc, oneshot, RwLock};
+use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
 
 /// Target sample rate for the output stream (CD quality)
@@ -29,6 +40,34 @@ pub const OUTPUT_CHANNELS: usize = 2;
 /// Samples per second per channel
 pub const SAMPLES_PER_SECOND: usize = OUTPUT_SAMPLE_RATE as usize;
 
+/// Response body chunks downloaded ahead of the decoder (bounds memory per track)
+const STREAM_CHUNKS_AHEAD: usize = 32;
+
+/// Seconds of decoded audio per chunk handed from the decoder to the pipeline
+const DECODE_CHUNK_SECONDS: f32 = 0.5;
+
+/// Decoded chunks waiting for room in the playback buffer before the decoder pauses
+const DECODE_CHUNKS_AHEAD: usize = 4;
+
+/// Consecutive tracks failing to load before the pipeline turns to its fallback tracks
+const FALLBACK_AFTER_FAILURES: u32 = 3;
+
+/// How long the queue may stay empty with nothing left to play before fallback tracks fill in
+const FALLBACK_AFTER_IDLE: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Navidrome transcoding targets the decoder can read ("raw" = original file)
+pub const DECODABLE_FORMATS: &[&str] = &["raw", "mp3", "aac", "flac"];
+
+/// Transcoding Navidrome applies before sending a track (Subsonic `format`/`maxBitRate`).
+/// Asking for a compressed format saves bandwidth and decode time on lossless libraries.
+#[derive(Debug, Clone, Default)]
+pub struct StreamOptions {
+    /// Target format, e.g. "mp3" (None = Navidrome's default for this client)
+    pub format: Option<String>,
+    /// Maximum bitrate in kbps (None = no limit)
+    pub max_bit_rate: Option<u32>,
+}
+
 /// Configuration for the audio pipeline
 #[derive(Debug, Clone)]
 pub struct AudioPipelineConfig {
@@ -36,10 +75,22 @@ pub struct AudioPipelineConfig {
     pub sample_rate: u32,
     /// Buffer size in seconds (how much audio to keep decoded ahead)
     pub buffer_seconds: f32,
-    /// Crossfade duration in seconds for track transitions
+    /// Crossfade duration in seconds for track transitions (0 splices tracks gaplessly)
     pub crossfade_seconds: f32,
     /// Number of audio channels
     pub channels: usize,
+    /// Transcoding requested from Navidrome for each track
+    pub stream_options: StreamOptions,
+    /// Disk cache for fetched tracks (AUDIO_CACHE_DIR)
+    pub audio_cache: Option<Arc<AudioCache>>,
+    /// Integrated loudness analyzed tracks are normalized to (None plays them as-is)
+    pub loudness_target_lufs: Option<f32>,
+    /// Speaks a link before every few tracks (None = no announcements)
+    pub announcer: Option<StationAnnouncer>,
+    /// How tracks at other sample rates are converted
+    pub resample_quality: ResampleQuality,
+    /// Played in turn while queued tracks keep failing to load or the queue runs dry
+    pub fallback: Vec<QueuedTrack>,
 }
 
 impl Default for AudioPipelineConfig {
@@ -49,6 +100,12 @@ impl Default for AudioPipelineConfig {
             buffer_seconds: 10.0,
             crossfade_seconds: 3.0,
             channels: OUTPUT_CHANNELS,
+            stream_options: StreamOptions::default(),
+            audio_cache: None,
+            loudness_target_lufs: None,
+            announcer: None,
+            resample_quality: ResampleQuality::default(),
+            fallback: Vec::new(),
         }
     }
 }
@@ -111,22 +168,141 @@ pub struct AudioPipeline {
 struct AudioBuffer {
     /// Ring buffer of samples (interleaved stereo)
     samples: VecDeque<f32>,
-    /// Maximum buffer size in samples (total, all channels)
+    /// Maximum buffer size in samples (total, all channels), for each of the current
+    /// and the next track
     max_samples: usize,
     /// Current track being buffered
     current_track: Option<BufferedTrack>,
     /// Next track preloaded for transition
     next_track: Option<BufferedTrack>,
+    /// Start of the next track, decoded ahead while the current one plays
+    next_samples: VecDeque<f32>,
+}
+
+impl AudioBuffer {
+    /// Track and sample buffer a decoder delivers into: the current track, or the next
+    /// one being decoded ahead. None once the track was skipped.
+    fn slot_mut(&mut self, serial: u64) -> Option<(&mut BufferedTrack, &mut VecDeque<f32>)> {
+        if self.current_track.as_ref().is_some_and(|t| t.serial == serial) {
+            return self.current_track.as_mut().map(|t| (t, &mut self.samples));
+        }
+        if self.next_track.as_ref().is_some_and(|t| t.serial == serial) {
+            return self.next_track.as_mut().map(|t| (t, &mut self.next_samples));
+        }
+        None
+    }
+
+    /// Mix the start of the track decoded ahead into the end of the current one, once
+    /// the current track is fully decoded. Tracks from the same album are spliced
+    /// without a fade, so albums play gaplessly as recorded.
+    fn crossfade_next(&mut self, fade_samples: usize, channels: usize) {
+        let (Some(current), Some(next)) = (&self.current_track, &mut self.next_track) else {
+            return;
+        };
+        let same_album = !current.album.is_empty() && current.album == next.album;
+        if !current.decoded || next.faded_in || same_album {
+            return;
+        }
+
+        // Only the current track's remaining samples are in `samples` now
+        let mut fade = fade_samples.min(self.samples.len());
+        if next.decoded {
+            fade = fade.min(self.next_samples.len());
+        } else if self.next_samples.len() < fade {
+            // Wait for more of the next track; the fade shortens if it's late
+            return;
+        }
+        let fade = fade - fade % channels;
+        if fade == 0 {
+            return;
+        }
+
+        let start = self.samples.len() - fade;
+        let head: Vec<f32> = self.next_samples.drain(..fade).collect();
+        equal_power_crossfade(&mut self.samples.make_contiguous()[start..], &head, channels);
+        // The faded-in part plays before the next track becomes the current one
+        next.consumed_samples = fade;
+        next.faded_in = true;
+        debug!("Crossfading into {} over {} samples", next.track_id, fade);
+    }
+
+    /// Make the track decoded ahead the current one, appending its samples
+    fn promote_next(&mut self) -> Option<TrackState> {
+        let next = self.next_track.take()?;
+        let ahead = std::mem::take(&mut self.next_samples);
+        self.samples.extend(ahead);
+
+        let track_state = TrackState {
+            track_id: next.track_id.clone(),
+            title: next.title.clone(),
+            artist: next.artist.clone(),
+            duration_secs: next.duration_secs,
+            position_secs: next.consumed_samples.saturating_sub(next.intro_samples) as f32
+                / (OUTPUT_SAMPLE_RATE as f32 * OUTPUT_CHANNELS as f32),
+        };
+        self.current_track = Some(next);
+        Some(track_state)
+    }
+}
+
+/// Whether `next` follows `current` on the same album, so the two are spliced
+/// gaplessly instead of crossfaded
+fn continues_album(current: Option<&BufferedTrack>, next: &QueuedTrack) -> bool {
+    current.is_some_and(|current| !current.album.is_empty() && current.album == next.album)
+}
+
+/// Where a track is in the mounted library, from its path relative to the music folder.
+/// None for paths that would lead outside it (absolute, or with `..`).
+pub fn library_file(library: &Path, relative_path: &str) -> Option<PathBuf> {
+    let relative = Path::new(relative_path);
+    let inside = relative
+        .components()
+        .all(|component| matches!(component, std::path::Component::Normal(_) | std::path::Component::CurDir));
+    (inside && !relative_path.is_empty()).then(|| library.join(relative))
+}
+
+/// The fallback track after the one played last, cycling through them
+fn next_fallback(fallback: &[QueuedTrack], index: &mut usize) -> Option<QueuedTrack> {
+    if fallback.is_empty() {
+        return None;
+    }
+    let track = fallback[*index % fallback.len()].clone();
+    *index += 1;
+    Some(track)
+}
+
+/// Record a newly started track and announce it
+async fn track_started(
+    state: &RwLock<PipelineState>,
+    event_tx: &broadcast::Sender<PipelineEvent>,
+    track_state: TrackState,
+) {
+    info!("Now playing: {} - {}", track_state.artist, track_state.title);
+    state.write().await.current_track = Some(track_state.clone());
+    let _ = event_tx.send(PipelineEvent::TrackStarted(track_state));
 }
 
 struct BufferedTrack {
+    /// Identifies the decoder delivering this track
+    serial: u64,
     track_id: String,
     title: String,
     artist: String,
-    /// Total decoded samples for this track
+    album: String,
+    /// Length as reported by the container, exact once fully decoded
+    duration_secs: f32,
+    /// Samples decoded into the buffer so far, including the announcement
     total_samples: usize,
+    /// Spoken announcement played before the track, at the start of its samples
+    intro_samples: usize,
     /// Samples already consumed
     consumed_samples: usize,
+    /// Whether the whole track has been decoded
+    decoded: bool,
+    /// Whether its start was already mixed into the previous track
+    faded_in: bool,
+    /// Linear gain applied to decoded samples for loudness normalization
+    gain: f32,
 }
 
 struct PipelineState {
@@ -143,10 +319,22 @@ pub struct QueuedTrack {
     pub track_id: String,
     pub title: String,
     pub artist: String,
+    /// Consecutive tracks of the same album are spliced instead of crossfaded, at the
+    /// album's first track's level and without announcements between them
+    pub album: String,
+    /// Measured loudness, if the track has been analyzed
+    pub loudness: Option<TrackLoudness>,
+    /// Local copy of the track (in the mounted library, or fallback audio). It's read as
+    /// is, without Navidrome transcoding.
+    pub path: Option<PathBuf>,
+    /// Whether Navidrome has the track, to stream it from when `path` can't be opened
+    /// (false for fallback files)
+    pub streamable: bool,
 }
 
 enum PipelineCommand {
     QueueTrack(QueuedTrack),
+    ClearQueue,
     Skip,
     Stop,
 }
@@ -155,7 +343,10 @@ impl AudioPipeline {
     /// Create a new audio pipeline
     pub fn new(navidrome: Arc<NavidromeClient>, config: AudioPipelineConfig) -> Self {
         let (event_tx, _) = broadcast::channel(100);
-        let max_samples = (config.buffer_seconds * config.sample_rate as f32 * config.channels as f32) as usize;
+        // The current track must be fully decoded before its last seconds play for the
+        // next one to be mixed in, so the buffer has to hold more than a crossfade
+        let buffer_seconds = config.buffer_seconds.max(config.crossfade_seconds + 2.0);
+        let max_samples = (buffer_seconds * config.sample_rate as f32 * config.channels as f32) as usize;
 
         Self {
             config,
@@ -165,6 +356,7 @@ impl AudioPipeline {
                 max_samples,
                 current_track: None,
                 next_track: None,
+                next_samples: VecDeque::new(),
             })),
             state: Arc::new(RwLock::new(PipelineState {
                 running: false,
@@ -186,7 +378,7 @@ impl AudioPipeline {
         if let Some(tx) = &self.control_tx {
             tx.send(PipelineCommand::QueueTrack(track))
                 .await
-                .map_err(|e| AppError::InternalMessage(format!("Failed to queue track: {}", e)))?;
+                .map_err(|e| AppError::Unavailable(format!("Failed to queue track: {}", e)))?;
         } else {
             // Pipeline not started, queue directly
             let mut state = self.state.write().await;
@@ -195,12 +387,25 @@ impl AudioPipeline {
         Ok(())
     }
 
+    /// Drop the queued tracks, keeping the current one (and the next one once it is
+    /// being mixed in)
+    pub async fn clear_queue(&self) -> Result<()> {
+        if let Some(tx) = &self.control_tx {
+            tx.send(PipelineCommand::ClearQueue)
+                .await
+                .map_err(|e| AppError::Unavailable(format!("Failed to clear queue: {}", e)))?;
+        } else {
+            self.state.write().await.track_queue.clear();
+        }
+        Ok(())
+    }
+
     /// Skip to the next track
     pub async fn skip(&self) -> Result<()> {
         if let Some(tx) = &self.control_tx {
             tx.send(PipelineCommand::Skip)
                 .await
-                .map_err(|e| AppError::InternalMessage(format!("Failed to skip: {}", e)))?;
+                .map_err(|e| AppError::Unavailable(format!("Failed to skip: {}", e)))?;
         }
         Ok(())
     }
@@ -255,6 +460,23 @@ impl AudioPipeline {
                 }
             }
 
+            // Decoders still delivering into the current or next track
+            let mut decoders: Vec<TrackDecoder> = Vec::new();
+            // Next track whose stream is being opened (in the background, so the current
+            // track keeps being fed meanwhile), with its announcement if it gets one
+            let mut opening: Option<(QueuedTrack, bool, OpeningTrack)> = None;
+            let mut next_serial: u64 = 0;
+            // Queued tracks that failed to load in a row; from FALLBACK_AFTER_FAILURES on,
+            // fallback tracks and tries of the queue take turns
+            let mut failures: u32 = 0;
+            let mut played_fallback = false;
+            // Since when the queue had nothing when the next track was wanted
+            let mut starved_since: Option<std::time::Instant> = None;
+            let mut fallback_index: usize = 0;
+            let mut since_announcement: u32 = 0;
+            let fade_samples = (config.crossfade_seconds.max(0.0) * config.sample_rate as f32) as usize
+                * config.channels;
+
             loop {
                 // Check for control commands (non-blocking)
                 match control_rx.try_recv() {
@@ -262,8 +484,12 @@ impl AudioPipeline {
                         let mut s = state.write().await;
                         s.track_queue.push_back(track);
                     }
+                    Ok(PipelineCommand::ClearQueue) => {
+                        state.write().await.track_queue.clear();
+                    }
                     Ok(PipelineCommand::Skip) => {
-                        // Clear current track, force load next
+                        // Clear current track; the next one is promoted below and the
+                        // current track's decoder is dropped once it has nowhere to deliver
                         let mut buf = buffer.write().await;
                         buf.samples.clear();
                         buf.current_track = None;
@@ -282,85 +508,218 @@ impl AudioPipeline {
                     }
                 }
 
-                // Check if we need to load more audio
-                let needs_audio = {
-                    let buf = buffer.read().await;
-                    buf.samples.len() < buf.max_samples / 2
-                };
-
-                if needs_audio {
-                    // Get next track from queue if no current track
-                    let (next_track, queue_len) = {
-                        let buf = buffer.read().await;
-                        let s = state.read().await;
-                        let track = if buf.current_track.is_none() {
-                            s.track_queue.front().cloned()
-                        } else {
-                            None
+                // Move decoded audio into the current and next track's buffers while they
+                // have room. Decode threads wait while their chunks sit in the channel, so
+                // memory stays bounded.
+                if !decoders.is_empty() {
+                    let mut buf = buffer.write().await;
+                    let max_samples = buf.max_samples;
+                    let mut decoded: Vec<(String, f32)> = Vec::new();
+
+                    decoders.retain_mut(|decoder| {
+                        let Some((track, samples)) = buf.slot_mut(decoder.serial) else {
+                            // Skipped
+                            return false;
                         };
-                        (track, s.track_queue.len())
-                    };
-
-                    if next_track.is_none() && queue_len == 0 {
-                        // No tracks in queue, wait before checking again
-                        debug!("Audio pipeline: waiting for tracks in queue");
-                        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-                        continue;
-                    }
-
-                    if let Some(track) = next_track {
-                        // Pop from queue
-                        {
-                            let mut s = state.write().await;
-                            s.track_queue.pop_front();
-                        }
 
-                        info!("Loading track: {} - {} (id: {})", track.artist, track.title, track.track_id);
-
-                        // Fetch and decode track
-                        match Self::fetch_and_decode(&navidrome, &track.track_id, &config).await {
-                            Ok(samples) => {
-                                let duration_secs = samples.len() as f32
-                                    / (config.sample_rate as f32 * config.channels as f32);
-
-                                let track_state = TrackState {
-                                    track_id: track.track_id.clone(),
-                                    title: track.title.clone(),
-                                    artist: track.artist.clone(),
-                                    duration_secs,
-                                    position_secs: 0.0,
-                                };
-
-                                {
-                                    let mut buf = buffer.write().await;
-                                    buf.samples.extend(samples.iter());
-                                    buf.current_track = Some(BufferedTrack {
-                                        track_id: track.track_id.clone(),
-                                        title: track.title.clone(),
-                                        artist: track.artist.clone(),
-                                        total_samples: samples.len(),
-                                        consumed_samples: 0,
-                                    });
+                        while samples.len() < max_samples {
+                            match decoder.chunks.try_recv() {
+                                Ok(Ok(chunk)) => {
+                                    track.total_samples += chunk.len();
+                                    if track.gain == 1.0 {
+                                        samples.extend(chunk);
+                                    } else {
+                                        samples.extend(chunk.into_iter().map(|s| s * track.gain));
+                                    }
                                 }
-
-                                {
-                                    let mut s = state.write().await;
-                                    s.current_track = Some(track_state.clone());
+                                Ok(Err(e)) => {
+                                    error!("Failed to decode track {}: {}", track.track_id, e);
+                                    failures += 1;
+                                    let _ = event_tx.send(PipelineEvent::Error(format!(
+                                        "Failed to decode {}: {}",
+                                        track.title, e
+                                    )));
+                                    track.decoded = true;
+                                    return false;
+                                }
+                                Err(mpsc::error::TryRecvError::Empty) => return true,
+                                Err(mpsc::error::TryRecvError::Disconnected) => {
+                                    // The track ends once the buffer has played what was decoded
+                                    track.decoded = true;
+                                    track.duration_secs = (track.total_samples - track.intro_samples) as f32
+                                        / (config.sample_rate as f32 * config.channels as f32);
+                                    decoded.push((track.track_id.clone(), track.duration_secs));
+                                    return false;
                                 }
+                            }
+                        }
+                        true
+                    });
+                    drop(buf);
 
-                                let _ = event_tx.send(PipelineEvent::TrackStarted(track_state));
+                    if !decoded.is_empty() {
+                        let mut s = state.write().await;
+                        for (track_id, duration_secs) in decoded {
+                            debug!("Decoded track {} ({:.1}s)", track_id, duration_secs);
+                            if let Some(current) = s.current_track.as_mut().filter(|c| c.track_id == track_id) {
+                                current.duration_secs = duration_secs;
                             }
-                            Err(e) => {
-                                error!("Failed to load track {}: {}", track.track_id, e);
-                                let _ = event_tx.send(PipelineEvent::Error(format!(
-                                    "Failed to load {}: {}",
-                                    track.title, e
-                                )));
+                        }
+                    }
+                }
+
+                // A next track whose stream opened gets decoded ahead
+                if opening.as_ref().is_some_and(|(_, _, handle)| handle.is_finished()) {
+                    let (track, fallback, handle) = opening.take().expect("checked above");
+                    let opened = handle.await.unwrap_or_else(|e| {
+                        Err(AppError::InternalMessage(format!("Open task panicked: {}", e)))
+                    });
+                    match opened {
+                        Ok((decoder, intro)) => {
+                            if !fallback {
+                                failures = 0;
+                            }
+                            if !intro.is_empty() {
+                                info!("Announcing {} - {}", track.artist, track.title);
                             }
+                            let mut buf = buffer.write().await;
+                            // An album keeps the level it started at, so its tracks join
+                            // without a jump in volume
+                            let gain = match (config.loudness_target_lufs, &track.loudness) {
+                                _ if continues_album(buf.current_track.as_ref(), &track) => {
+                                    buf.current_track.as_ref().map_or(1.0, |current| current.gain)
+                                }
+                                (Some(target), Some(loudness)) => loudness.normalization_gain(target),
+                                _ => 1.0,
+                            };
+                            buf.next_track = Some(BufferedTrack {
+                                serial: decoder.serial,
+                                track_id: track.track_id.clone(),
+                                title: track.title.clone(),
+                                artist: track.artist.clone(),
+                                album: track.album.clone(),
+                                duration_secs: decoder.duration_secs.unwrap_or(0.0),
+                                total_samples: intro.len(),
+                                intro_samples: intro.len(),
+                                consumed_samples: 0,
+                                decoded: false,
+                                faded_in: false,
+                                gain,
+                            });
+                            buf.next_samples.extend(intro);
+                            decoders.push(decoder);
+                        }
+                        Err(e) => {
+                            error!("Failed to load track {}: {}", track.track_id, e);
+                            if !fallback {
+                                failures += 1;
+                            }
+                            let _ = event_tx.send(PipelineEvent::Error(format!(
+                                "Failed to load {}: {}",
+                                track.title, e
+                            )));
                         }
                     }
                 }
 
+                // Nothing playing (start, skip, or the next track wasn't ready when the
+                // current one ended): play the next track as soon as it is there.
+                // Otherwise mix the next track into the end of the current one.
+                let started = {
+                    let mut buf = buffer.write().await;
+                    buf.crossfade_next(fade_samples, config.channels);
+                    if buf.current_track.is_none() {
+                        buf.promote_next()
+                    } else {
+                        None
+                    }
+                };
+                if let Some(track_state) = started {
+                    track_started(&state, &event_tx, track_state).await;
+                }
+
+                // Open the next track once the current one is fully decoded
+                let (wants_next, idle) = {
+                    let buf = buffer.read().await;
+                    let current_done = buf.current_track.as_ref().is_none_or(|t| t.decoded);
+                    let wants_next = current_done && buf.next_track.is_none() && opening.is_none();
+                    (wants_next, buf.current_track.is_none() && buf.next_track.is_none())
+                };
+                // After repeated failures, fallback tracks alternate with tries of the
+                // queue; they also fill in once the queue has been empty for a while
+                let next_track = if !wants_next {
+                    None
+                } else if failures >= FALLBACK_AFTER_FAILURES && !played_fallback && !config.fallback.is_empty() {
+                    warn!("{} tracks in a row failed to load, playing a fallback track", failures);
+                    played_fallback = true;
+                    next_fallback(&config.fallback, &mut fallback_index).map(|track| (track, true))
+                } else if let Some(track) = state.write().await.track_queue.pop_front() {
+                    starved_since = None;
+                    played_fallback = false;
+                    Some((track, false))
+                } else if starved_since.get_or_insert_with(std::time::Instant::now).elapsed() >= FALLBACK_AFTER_IDLE {
+                    next_fallback(&config.fallback, &mut fallback_index).map(|track| (track, true))
+                } else {
+                    None
+                };
+
+                if let Some((track, fallback)) = next_track {
+                    info!("Loading track: {} - {} (id: {})", track.artist, track.title, track.track_id);
+
+                    next_serial += 1;
+                    let serial = next_serial;
+                    let navidrome = navidrome.clone();
+                    let config = config.clone();
+                    let queued = track.clone();
+
+                    // The announcement is generated while the track's stream opens. Tracks
+                    // continuing an album aren't announced, which would break the splice.
+                    let buf = buffer.read().await;
+                    let mid_album = continues_album(buf.current_track.as_ref(), &track);
+                    if !mid_album {
+                        since_announcement += 1;
+                    }
+                    let announcement = match &config.announcer {
+                        Some(announcer) if !mid_album && since_announcement >= announcer.every => {
+                            since_announcement = 0;
+                            let previous = buf.current_track.as_ref().map(|t| (t.title.clone(), t.artist.clone()));
+                            Some((announcer.clone(), previous, track.title.clone(), track.artist.clone()))
+                        }
+                        _ => None,
+                    };
+                    drop(buf);
+                    let (sample_rate, channels, quality) = (config.sample_rate, config.channels, config.resample_quality);
+                    let handle = tokio::spawn(async move {
+                        let intro = async move {
+                            let Some((announcer, previous, title, artist)) = announcement else {
+                                return Vec::new();
+                            };
+                            let previous = previous.as_ref().map(|(title, artist)| (title.as_str(), artist.as_str()));
+                            let next = (title.as_str(), artist.as_str());
+                            match announcer
+                                .announcer
+                                .announcement(previous, next, &announcer.voice, sample_rate, channels, quality)
+                                .await
+                            {
+                                Ok(samples) => samples,
+                                Err(e) => {
+                                    warn!("Skipping announcement of {}: {}", title, e);
+                                    Vec::new()
+                                }
+                            }
+                        };
+                        let (decoder, intro) =
+                            tokio::join!(TrackDecoder::open_queued(&navidrome, &queued, &config, serial), intro);
+                        decoder.map(|decoder| (decoder, intro))
+                    });
+                    opening = Some((track, fallback, handle));
+                } else if idle && wants_next && decoders.is_empty() {
+                    // No tracks in queue, wait before checking again
+                    debug!("Audio pipeline: waiting for tracks in queue");
+                    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+                    continue;
+                }
+
                 // Small sleep to prevent busy loop
                 tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
             }
@@ -380,11 +739,12 @@ impl AudioPipeline {
         }
 
         // Update consumed samples count and position
+        let mut started = None;
         if let Some(ref mut track) = buffer.current_track {
             track.consumed_samples += available;
 
             // Calculate current position in seconds
-            let position_secs = track.consumed_samples as f32
+            let position_secs = track.consumed_samples.saturating_sub(track.intro_samples) as f32
                 / (OUTPUT_SAMPLE_RATE as f32 * OUTPUT_CHANNELS as f32);
 
             // Update position in state
@@ -397,8 +757,8 @@ impl AudioPipeline {
                 }
             }
 
-            // Check if track finished
-            if track.consumed_samples >= track.total_samples {
+            // Check if track finished (it may still be decoding after an underrun)
+            if track.decoded && track.consumed_samples >= track.total_samples {
                 let track_id = track.track_id.clone();
                 info!(
                     "Track {} finished: consumed {} of {} samples",
@@ -406,8 +766,16 @@ impl AudioPipeline {
                 );
                 buffer.current_track = None;
                 let _ = self.event_tx.send(PipelineEvent::TrackEnded { track_id });
+
+                // Continue straight into the track decoded ahead
+                started = buffer.promote_next();
             }
         }
+        drop(buffer);
+
+        if let Some(track_state) = started {
+            track_started(&self.state, &self.event_tx, track_state).await;
+        }
 
         available
     }
@@ -417,225 +785,675 @@ impl AudioPipeline {
         let buffer = self.buffer.read().await;
         buffer.samples.len() as f32 / buffer.max_samples as f32
     }
+}
+
+/// Decode a short clip held in memory (e.g. an announcement) in full
+pub(crate) async fn decode_clip(
+    audio: Vec<u8>,
+    sample_rate: u32,
+    channels: usize,
+    resample_quality: ResampleQuality,
+) -> Result<Vec<f32>> {
+    let (probed_tx, probed_rx) = oneshot::channel();
+    let (chunks_tx, mut chunks_rx) = mpsc::channel(DECODE_CHUNKS_AHEAD);
+    tokio::task::spawn_blocking(move || {
+        let source = Box::new(io::Cursor::new(audio));
+        decode_track(source, sample_rate, channels, resample_quality, probed_tx, chunks_tx)
+    });
+    probed_rx
+        .await
+        .map_err(|_| AppError::InternalMessage("Decode task panicked".to_string()))??;
+
+    let mut samples = Vec::new();
+    while let Some(chunk) = chunks_rx.recv().await {
+        samples.extend(chunk?);
+    }
+    Ok(samples)
+}
+
+/// Equal-power crossfade from `from` into `to`, written over `from`. Both are
+/// interleaved with `channels` channels; the gains follow a quarter sine/cosine so the
+/// loudness stays constant through the transition.
+fn equal_power_crossfade(from: &mut [f32], to: &[f32], channels: usize) {
+    let frames = from.len().min(to.len()) / channels;
+    for frame in 0..frames {
+        let t = (frame as f32 + 0.5) / frames as f32 * std::f32::consts::FRAC_PI_2;
+        let (fade_out, fade_in) = (t.cos(), t.sin());
+        for ch in 0..channels {
+            let i = frame * channels + ch;
+            from[i] = from[i] * fade_out + to[i] * fade_in;
+        }
+    }
+}
 
-    /// Fetch audio from Navidrome and decode to PCM
-    async fn fetch_and_decode(
+/// Opening of the next track: its decoder, and the samples of its announcement (empty
+/// when it has none)
+type OpeningTrack = JoinHandle<Result<(TrackDecoder, Vec<f32>)>>;
+
+/// A track being decoded on a blocking thread and delivered in chunks. The thread
+/// pauses while `DECODE_CHUNKS_AHEAD` chunks are waiting, so memory use doesn't grow
+/// with the length of the track; dropping the decoder stops the thread.
+pub(crate) struct TrackDecoder {
+    /// Matches the `BufferedTrack` this decoder delivers into
+    serial: u64,
+    track_id: String,
+    chunks: mpsc::Receiver<Result<Vec<f32>>>,
+    /// Length reported by the container, if any
+    duration_secs: Option<f32>,
+}
+
+impl TrackDecoder {
+    /// Start fetching and decoding a track. Returns once the format has been probed.
+    pub(crate) async fn open(
         navidrome: &NavidromeClient,
         track_id: &str,
         config: &AudioPipelineConfig,
-    ) -> Result<Vec<f32>> {
+        serial: u64,
+    ) -> Result<Self> {
         info!("Fetching audio for track {}", track_id);
 
-        // Fetch audio data from Navidrome
-        let audio_data = navidrome.stream_track(track_id).await?;
+        // Start the download; decoding begins with the first chunks
+        let source = navidrome
+            .stream_track(track_id, &config.stream_options, config.audio_cache.as_ref())
+            .await?;
+        Self::decode(source, track_id, config, serial).await
+    }
+
+    /// Start decoding a queued track: its local copy if it has one, otherwise (or when
+    /// that can't be opened) the track streamed from Navidrome
+    pub(crate) async fn open_queued(
+        navidrome: &NavidromeClient,
+        track: &QueuedTrack,
+        config: &AudioPipelineConfig,
+        serial: u64,
+    ) -> Result<Self> {
+        if let Some(path) = &track.path {
+            match Self::open_file(path, &track.track_id, config, serial).await {
+                Ok(decoder) => return Ok(decoder),
+                Err(e) if track.streamable => {
+                    debug!("Streaming track {} from Navidrome instead: {}", track.track_id, e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Self::open(navidrome, &track.track_id, config, serial).await
+    }
 
-        info!("Received {} bytes from Navidrome, decoding...", audio_data.len());
+    /// Start decoding a local file: a track in the mounted library or a fallback file
+    pub(crate) async fn open_file(path: &Path, track_id: &str, config: &AudioPipelineConfig, serial: u64) -> Result<Self> {
+        info!("Reading audio for track {} from {:?}", track_id, path);
+        let file = tokio::fs::File::open(path)
+            .await
+            .map_err(|e| AppError::Storage(format!("Failed to open {:?}: {}", path, e)))?;
+        Self::decode(Box::new(file.into_std().await), track_id, config, serial).await
+    }
 
-        // Decode in a blocking task since Symphonia is sync
+    /// Decode `source` on a blocking thread. Returns once the format has been probed.
+    async fn decode(
+        source: Box<dyn MediaSource>,
+        track_id: &str,
+        config: &AudioPipelineConfig,
+        serial: u64,
+    ) -> Result<Self> {
+        let (probed_tx, probed_rx) = oneshot::channel();
+        let (chunks_tx, chunks_rx) = mpsc::channel(DECODE_CHUNKS_AHEAD);
         let sample_rate = config.sample_rate;
         let channels = config.channels;
+        let resample_quality = config.resample_quality;
 
-        let samples = tokio::task::spawn_blocking(move || {
-            Self::decode_audio(&audio_data, sample_rate, channels)
-        })
-        .await
-        .map_err(|e| AppError::InternalMessage(format!("Decode task panicked: {}", e)))??;
-
-        // Calculate track duration from samples
-        let duration_secs = samples.len() as f32 / (sample_rate as f32 * channels as f32);
-        info!(
-            "Decoded {} samples = {:.1} seconds of audio",
-            samples.len(),
-            duration_secs
-        );
-        Ok(samples)
-    }
-
-    /// Decode audio bytes to PCM samples
-    fn decode_audio(data: &[u8], target_sample_rate: u32, target_channels: usize) -> Result<Vec<f32>> {
-        let cursor = std::io::Cursor::new(data.to_vec());
-        let mss = MediaSourceStream::new(Box::new(cursor), Default::default());
-
-        let probed = symphonia::default::get_probe()
-            .format(
-                &Hint::new(),
-                mss,
-                &FormatOptions::default(),
-                &MetadataOptions::default(),
-            )
-            .map_err(|e| AppError::InternalMessage(format!("Failed to probe audio: {}", e)))?;
-
-        let mut format = probed.format;
-
-        // Find the audio track
-        let track = format
-            .tracks()
-            .iter()
-            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
-            .ok_or_else(|| AppError::InternalMessage("No audio track found".to_string()))?;
-
-        let track_id = track.id;
-        let codec_params = track.codec_params.clone();
-
-        let mut decoder = symphonia::default::get_codecs()
-            .make(&codec_params, &DecoderOptions::default())
-            .map_err(|e| AppError::InternalMessage(format!("Failed to create decoder: {}", e)))?;
-
-        let mut samples: Vec<f32> = Vec::new();
-        let source_sample_rate = codec_params.sample_rate.unwrap_or(44100);
-        let source_channels = codec_params.channels.map(|c| c.count()).unwrap_or(2);
-
-        // Decode all packets
-        loop {
-            let packet = match format.next_packet() {
-                Ok(packet) => packet,
-                Err(symphonia::core::errors::Error::IoError(e))
-                    if e.kind() == std::io::ErrorKind::UnexpectedEof =>
-                {
-                    break;
-                }
-                Err(e) => {
-                    warn!("Error reading packet: {}", e);
-                    break;
-                }
-            };
+        // Decode in a blocking task since Symphonia is sync
+        tokio::task::spawn_blocking(move || {
+            decode_track(source, sample_rate, channels, resample_quality, probed_tx, chunks_tx)
+        });
 
-            if packet.track_id() != track_id {
-                continue;
-            }
+        let duration_secs = probed_rx
+            .await
+            .map_err(|_| AppError::InternalMessage("Decode task panicked".to_string()))??;
 
-            let decoded = match decoder.decode(&packet) {
-                Ok(decoded) => decoded,
-                Err(e) => {
-                    warn!("Error decoding packet: {}", e);
-                    continue;
-                }
-            };
+        Ok(Self {
+            serial,
+            track_id: track_id.to_string(),
+            chunks: chunks_rx,
+            duration_secs,
+        })
+    }
 
-            let spec = *decoded.spec();
-            let mut sample_buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
-            sample_buf.copy_interleaved_ref(decoded);
+    /// The next chunk of interleaved samples, or None once the whole track is decoded
+    pub(crate) async fn next_chunk(&mut self) -> Option<Result<Vec<f32>>> {
+        self.chunks.recv().await
+    }
+}
 
-            let packet_samples = sample_buf.samples();
+/// Decode an audio source into chunks of PCM samples (blocks while a streamed source
+/// downloads and while the pipeline has no room). The probe result, with the track's
+/// duration if known, is reported on `probed` before the first chunk.
+fn decode_track(
+    source: Box<dyn MediaSource>,
+    target_sample_rate: u32,
+    target_channels: usize,
+    resample_quality: ResampleQuality,
+    probed: oneshot::Sender<Result<Option<f32>>>,
+    chunks: mpsc::Sender<Result<Vec<f32>>>,
+) {
+    let mss = MediaSourceStream::new(source, Default::default());
+
+    let opened = symphonia::default::get_probe()
+        .format(
+            &Hint::new(),
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| AppError::Decode(format!("Failed to probe audio: {}", e)))
+        .and_then(|probed| {
+            let format = probed.format;
+
+            // Find the audio track
+            let track = format
+                .tracks()
+                .iter()
+                .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+                .ok_or_else(|| AppError::Decode("No audio track found".to_string()))?;
+            let track_id = track.id;
+            let codec_params = track.codec_params.clone();
+
+            let decoder = symphonia::default::get_codecs()
+                .make(&codec_params, &DecoderOptions::default())
+                .map_err(|e| AppError::Decode(format!("Failed to create decoder: {}", e)))?;
+
+            Ok((format, decoder, track_id, codec_params))
+        });
 
-            // Handle channel conversion and collect samples
-            if source_channels == target_channels {
-                samples.extend_from_slice(packet_samples);
-            } else if source_channels == 1 && target_channels == 2 {
-                // Mono to stereo: duplicate samples
-                for &s in packet_samples {
-                    samples.push(s);
-                    samples.push(s);
-                }
-            } else if source_channels == 2 && target_channels == 1 {
-                // Stereo to mono: average channels
-                for chunk in packet_samples.chunks(2) {
-                    if chunk.len() == 2 {
-                        samples.push((chunk[0] + chunk[1]) / 2.0);
-                    }
-                }
-            } else {
-                // Other channel configs: just take first target_channels
-                for chunk in packet_samples.chunks(source_channels) {
-                    for i in 0..target_channels.min(chunk.len()) {
-                        samples.push(chunk[i]);
-                    }
-                }
-            }
+    let (mut format, mut decoder, track_id, codec_params) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+            let _ = probed.send(Err(e));
+            return;
         }
+    };
+
+    let source_sample_rate = codec_params.sample_rate.unwrap_or(44100);
+    let source_channels = codec_params.channels.map(|c| c.count()).unwrap_or(2);
+    let duration_secs = codec_params
+        .n_frames
+        .map(|frames| frames as f32 / source_sample_rate as f32);
+    if probed.send(Ok(duration_secs)).is_err() {
+        return;
+    }
 
-        // Resample if needed
-        if source_sample_rate != target_sample_rate {
-            samples = Self::resample(
-                &samples,
-                source_sample_rate,
-                target_sample_rate,
-                target_channels,
-            );
-        }
-
-        Ok(samples)
-    }
-
-    /// Linear interpolation resampling (preserving channel interleaving)
-    fn resample(
-        samples: &[f32],
-        from_rate: u32,
-        to_rate: u32,
-        channels: usize,
-    ) -> Vec<f32> {
-        let ratio = from_rate as f64 / to_rate as f64;
-        let input_frames = samples.len() / channels;
-        let output_frames = (input_frames as f64 / ratio) as usize;
-        let mut output = Vec::with_capacity(output_frames * channels);
-
-        for frame in 0..output_frames {
-            let src_pos = frame as f64 * ratio;
-            let src_frame = src_pos.floor() as usize;
-            let next_frame = (src_frame + 1).min(input_frames - 1);
-            let frac = (src_pos - src_frame as f64) as f32;
-
-            for ch in 0..channels {
-                let curr = samples[src_frame * channels + ch];
-                let next = samples[next_frame * channels + ch];
-                output.push(curr * (1.0 - frac) + next * frac);
+    let chunk_len = (DECODE_CHUNK_SECONDS * target_sample_rate as f32) as usize * target_channels;
+    let mut resampler =
+        resampler::resampler(resample_quality, source_sample_rate, target_sample_rate, target_channels);
+    let mut converted: Vec<f32> = Vec::new();
+    let mut chunk: Vec<f32> = Vec::with_capacity(chunk_len);
+
+    // Decode all packets
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(symphonia::core::errors::Error::IoError(e))
+                if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                break;
             }
+            Err(e) => {
+                warn!("Error reading packet: {}", e);
+                break;
+            }
+        };
+
+        if packet.track_id() != track_id {
+            continue;
         }
 
-        output
-    }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                warn!("Error decoding packet: {}", e);
+                continue;
+            }
+        };
 
-    /// Apply crossfade between two sample buffers
-    #[allow(dead_code)]
-    fn crossfade(from: &[f32], to: &[f32], fade_samples: usize) -> Vec<f32> {
-        let fade_len = fade_samples.min(from.len()).min(to.len());
-        let mut result = Vec::with_capacity(from.len() - fade_len + to.len());
+        let spec = *decoded.spec();
+        let mut sample_buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        sample_buf.copy_interleaved_ref(decoded);
 
-        // Copy non-fading part of 'from'
-        result.extend_from_slice(&from[..from.len() - fade_len]);
+        converted.clear();
+        convert_channels(sample_buf.samples(), source_channels, target_channels, &mut converted);
+        resampler.process(&converted, &mut chunk);
 
-        // Crossfade region
-        for i in 0..fade_len {
-            let t = i as f32 / fade_len as f32;
-            let from_idx = from.len() - fade_len + i;
-            let faded = from[from_idx] * (1.0 - t) + to[i] * t;
-            result.push(faded);
+        if chunk.len() >= chunk_len {
+            let full = std::mem::replace(&mut chunk, Vec::with_capacity(chunk_len));
+            if chunks.blocking_send(Ok(full)).is_err() {
+                // The pipeline moved on (skip or stop)
+                return;
+            }
         }
+    }
 
-        // Copy remaining 'to' samples
-        result.extend_from_slice(&to[fade_len..]);
+    resampler.flush(&mut chunk);
+    if !chunk.is_empty() {
+        let _ = chunks.blocking_send(Ok(chunk));
+    }
+}
 
-        result
+/// Append interleaved samples converted to `target_channels`
+fn convert_channels(samples: &[f32], source_channels: usize, target_channels: usize, output: &mut Vec<f32>) {
+    if source_channels == target_channels {
+        output.extend_from_slice(samples);
+    } else if source_channels == 1 && target_channels == 2 {
+        // Mono to stereo: duplicate samples
+        for &s in samples {
+            output.push(s);
+            output.push(s);
+        }
+    } else if source_channels == 2 && target_channels == 1 {
+        // Stereo to mono: average channels
+        for chunk in samples.chunks(2) {
+            if chunk.len() == 2 {
+                output.push((chunk[0] + chunk[1]) / 2.0);
+            }
+        }
+    } else {
+        // Other channel configs: just take first target_channels
+        for chunk in samples.chunks(source_channels) {
+            for i in 0..target_channels.min(chunk.len()) {
+                output.push(chunk[i]);
+            }
+        }
     }
 }
 
 impl NavidromeClient {
-    /// Stream a track and return the raw audio bytes
-    pub async fn stream_track(&self, track_id: &str) -> Result<Bytes> {
+    /// Start streaming a track. The returned source yields the file as it downloads, so
+    /// decoding starts immediately and only a few chunks are held in memory. MP4/M4A files
+    /// may keep their index at the end and need seeking, so those are downloaded to disk
+    /// first (into the cache, or a temporary file) and decoded from there.
+    /// With a cache, cached tracks are read from disk and new downloads are kept.
+    pub async fn stream_track(
+        &self,
+        track_id: &str,
+        options: &StreamOptions,
+        cache: Option<&Arc<AudioCache>>,
+    ) -> Result<Box<dyn MediaSource>> {
+        let cache_key = AudioCache::key(track_id, options);
+        if let Some(path) = cache.and_then(|c| c.get(&cache_key)) {
+            match std::fs::File::open(&path) {
+                Ok(file) => {
+                    debug!("Playing track {} from audio cache", track_id);
+                    return Ok(Box::new(file));
+                }
+                Err(e) => warn!("Failed to open cached audio {:?}: {}", path, e),
+            }
+        }
+
         let url = format!("{}/rest/stream", self.base_url());
 
-        let params = self.build_params(vec![("id", track_id)]);
+        let max_bit_rate = options.max_bit_rate.map(|b| b.to_string());
+        let mut additional = vec![("id", track_id)];
+        if let Some(format) = &options.format {
+            additional.push(("format", format));
+        }
+        if let Some(max_bit_rate) = &max_bit_rate {
+            additional.push(("maxBitRate", max_bit_rate));
+        }
+        let params = self.build_params(additional);
 
         let response = self
-            .client()
-            .get(&url)
-            .query(&params)
-            .send()
+            .send(self.client().get(&url).query(&params))
             .await
-            .map_err(|e| AppError::InternalMessage(format!("Failed to stream track: {}", e)))?;
+            .map_err(|e| AppError::Navidrome(format!("Failed to stream track: {}", e)))?;
 
         if !response.status().is_success() {
-            return Err(AppError::InternalMessage(format!(
+            return Err(AppError::Navidrome(format!(
                 "Stream request failed: {}",
                 response.status()
             )));
         }
 
-        let bytes = response
-            .bytes()
-            .await
-            .map_err(|e| AppError::InternalMessage(format!("Failed to read stream: {}", e)))?;
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok());
+        let needs_seeking = content_type.is_some_and(|t| t.contains("mp4") || t.contains("m4a"));
+        let cache_writer = cache.map(|cache| CacheWriter {
+            cache: cache.clone(),
+            temp_path: cache.temp_path(&cache_key),
+            key: cache_key,
+            extension: audio_cache::extension_for(content_type),
+        });
+
+        if needs_seeking {
+            return spool_to_disk(response, track_id, cache_writer).await;
+        }
+
+        Ok(Box::new(HttpMediaSource::spawn(response, cache_writer)))
+    }
+}
+
+/// A download being saved into the audio cache alongside playback
+struct CacheWriter {
+    cache: Arc<AudioCache>,
+    key: String,
+    extension: &'static str,
+    temp_path: PathBuf,
+}
+
+impl CacheWriter {
+    fn commit(self) {
+        if let Err(e) = self.cache.insert(&self.key, self.extension, &self.temp_path) {
+            warn!("{}", e);
+        }
+    }
+}
+
+/// Download a track that needs seeking to disk and open it there, so it isn't held in
+/// memory: into the cache when there is one, otherwise a temporary file removed once
+/// the decoder is done with it
+async fn spool_to_disk(
+    response: reqwest::Response,
+    track_id: &str,
+    cache_writer: Option<CacheWriter>,
+) -> Result<Box<dyn MediaSource>> {
+    let temp_path = match &cache_writer {
+        Some(writer) => writer.temp_path.clone(),
+        None => std::env::temp_dir().join(format!("navidrome-radio-{}.m4a", uuid::Uuid::new_v4().simple())),
+    };
+    if let Err(e) = audio_cache::download_to(response, &temp_path).await {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(e);
+    }
+    debug!("Downloaded MP4 audio for {} to {:?}", track_id, temp_path);
+
+    let open_error = |e: io::Error| AppError::Storage(format!("Failed to open downloaded audio: {}", e));
+    match cache_writer {
+        Some(writer) => {
+            let path = writer.cache.insert(&writer.key, writer.extension, &temp_path)?;
+            Ok(Box::new(std::fs::File::open(path).map_err(open_error)?))
+        }
+        None => {
+            let file = std::fs::File::open(&temp_path).map_err(|e| {
+                let _ = std::fs::remove_file(&temp_path);
+                open_error(e)
+            })?;
+            Ok(Box::new(TempAudioFile { file, path: temp_path }))
+        }
+    }
+}
+
+/// A downloaded file that is deleted when the decoder drops it
+struct TempAudioFile {
+    file: std::fs::File,
+    path: PathBuf,
+}
+
+impl Read for TempAudioFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Seek for TempAudioFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+impl MediaSource for TempAudioFile {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.file.metadata().ok().map(|m| m.len())
+    }
+}
+
+impl Drop for TempAudioFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            debug!("Failed to remove downloaded audio {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// An HTTP response body read as it arrives. A background task pulls chunks into a
+/// bounded channel and reads block until the next chunk is in, so this must be consumed
+/// from a blocking thread. Not seekable.
+struct HttpMediaSource {
+    chunks: Mutex<mpsc::Receiver<io::Result<Bytes>>>,
+    current: Bytes,
+    byte_len: Option<u64>,
+}
+
+impl HttpMediaSource {
+    /// Start the download. With a cache writer, chunks are also written to disk and
+    /// the download runs to the end even if the decoder stops early.
+    fn spawn(response: reqwest::Response, cache_writer: Option<CacheWriter>) -> Self {
+        let byte_len = response.content_length();
+        let (tx, rx) = mpsc::channel(STREAM_CHUNKS_AHEAD);
+
+        tokio::spawn(async move {
+            let mut file = match &cache_writer {
+                Some(writer) => match tokio::fs::File::create(&writer.temp_path).await {
+                    Ok(file) => Some(file),
+                    Err(e) => {
+                        warn!("Failed to create cached audio file: {}", e);
+                        None
+                    }
+                },
+                None => None,
+            };
+            let mut decoder_done = false;
+            let mut complete = true;
+
+            let mut body = response.bytes_stream();
+            while let Some(chunk) = body.next().await {
+                let chunk = chunk.map_err(io::Error::other);
+                if let (Some(f), Ok(bytes)) = (file.as_mut(), &chunk) {
+                    if let Err(e) = f.write_all(bytes).await {
+                        warn!("Failed to write cached audio: {}", e);
+                        file = None;
+                    }
+                }
+
+                let failed = chunk.is_err();
+                if !decoder_done && tx.send(chunk).await.is_err() {
+                    decoder_done = true;
+                }
+                // Stop when the download failed, or when the decoder is done with the
+                // file and nothing is being cached
+                if failed {
+                    complete = false;
+                    break;
+                }
+                if decoder_done && file.is_none() {
+                    complete = false;
+                    break;
+                }
+            }
+
+            if let Some(writer) = cache_writer {
+                let flushed = match file.as_mut() {
+                    Some(f) => f.flush().await.is_ok(),
+                    None => false,
+                };
+                drop(file);
+                if complete && flushed {
+                    writer.commit();
+                } else {
+                    let _ = tokio::fs::remove_file(&writer.temp_path).await;
+                }
+            }
+        });
+
+        Self {
+            chunks: Mutex::new(rx),
+            current: Bytes::new(),
+            byte_len,
+        }
+    }
+}
+
+impl Read for HttpMediaSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            let chunks = self
+                .chunks
+                .get_mut()
+                .map_err(|_| io::Error::other("stream reader poisoned"))?;
+            match chunks.blocking_recv() {
+                Some(chunk) => self.current = chunk?,
+                None => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.current.len());
+        buf[..n].copy_from_slice(&self.current.split_to(n));
+        Ok(n)
+    }
+}
+
+impl Seek for HttpMediaSource {
+    fn seek(&mut self, _pos: SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "streamed audio is not seekable"))
+    }
+}
+
+impl MediaSource for HttpMediaSource {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.byte_len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::mock_navidrome::{wav_tone, MockNavidrome, MockSong};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_decode_streamed_track_in_chunks() {
+        let mut song = MockSong::new("tone", "Tone", "Test", "Test");
+        song.duration = 2;
+        let mock = MockNavidrome::start(vec![song]).await;
+        let config = AudioPipelineConfig::default();
+
+        let mut decoder = TrackDecoder::open(&mock.client(), "tone", &config, 1).await.unwrap();
+        assert_eq!(decoder.duration_secs, Some(2.0));
+
+        let mut chunks = 0;
+        let mut samples = 0;
+        while let Some(chunk) = decoder.chunks.recv().await {
+            chunks += 1;
+            samples += chunk.unwrap().len();
+        }
+        let seconds = samples as f32 / (config.sample_rate as f32 * config.channels as f32);
+        assert!((seconds - 2.0).abs() < 0.05, "decoded {} seconds", seconds);
+        assert!(chunks >= 4, "expected chunked delivery, got {} chunks", chunks);
+
+        assert!(TrackDecoder::open(&mock.client(), "missing", &config, 2).await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_library_files_are_read_before_streaming() {
+        let mut song = MockSong::new("tone", "Tone", "Test", "Test");
+        song.duration = 2;
+        let mock = MockNavidrome::start(vec![song]).await;
+        let config = AudioPipelineConfig::default();
+
+        let library = std::env::temp_dir().join(format!("navidrome-radio-library-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(library.join("Test")).unwrap();
+        std::fs::write(library.join("Test/Tone.wav"), wav_tone(1)).unwrap();
+        let track = |path: Option<PathBuf>, streamable: bool| QueuedTrack {
+            track_id: "tone".to_string(),
+            title: "Tone".to_string(),
+            artist: "Test".to_string(),
+            album: "Test".to_string(),
+            loudness: None,
+            path,
+            streamable,
+        };
+
+        // Paths leading outside the library aren't read
+        assert_eq!(library_file(&library, "/etc/passwd"), None);
+        assert_eq!(library_file(&library, "../Tone.wav"), None);
+        assert_eq!(library_file(&library, ""), None);
+
+        // The local copy (one second long) is decoded without asking Navidrome
+        let local = track(library_file(&library, "Test/Tone.wav"), true);
+        let decoder = TrackDecoder::open_queued(&mock.client(), &local, &config, 1).await.unwrap();
+        assert_eq!(decoder.duration_secs, Some(1.0));
+        assert!(!mock.requests().contains(&"stream".to_string()));
+
+        // A missing file of a library track is streamed instead (two seconds long)
+        let missing = track(library_file(&library, "Test/Missing.wav"), true);
+        let decoder = TrackDecoder::open_queued(&mock.client(), &missing, &config, 2).await.unwrap();
+        assert_eq!(decoder.duration_secs, Some(2.0));
+        assert_eq!(mock.requests().iter().filter(|r| *r == "stream").count(), 1);
+
+        // A missing fallback file isn't looked for in Navidrome
+        let fallback = track(Some(library.join("Missing.wav")), false);
+        assert!(TrackDecoder::open_queued(&mock.client(), &fallback, &config, 3).await.is_err());
+        assert_eq!(mock.requests().iter().filter(|r| *r == "stream").count(), 1);
+
+        std::fs::remove_dir_all(&library).unwrap();
+    }
+
+    #[test]
+    fn test_temp_audio_file_is_removed_when_dropped() {
+        let path = std::env::temp_dir().join(format!("navidrome-radio-test-{}.m4a", uuid::Uuid::new_v4().simple()));
+        std::fs::write(&path, b"audio").unwrap();
+        let mut source = TempAudioFile {
+            file: std::fs::File::open(&path).unwrap(),
+            path: path.clone(),
+        };
+        assert_eq!(source.byte_len(), Some(5));
+        let mut bytes = Vec::new();
+        source.read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes, b"audio");
+
+        drop(source);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_fallback_tracks_cycle() {
+        let track = |id: &str| QueuedTrack {
+            track_id: id.to_string(),
+            title: String::new(),
+            artist: String::new(),
+            album: String::new(),
+            loudness: None,
+            path: None,
+            streamable: true,
+        };
+        let fallback = vec![track("a"), track("b")];
+        let mut index = 0;
+        let played: Vec<String> = (0..3)
+            .filter_map(|_| next_fallback(&fallback, &mut index))
+            .map(|t| t.track_id)
+            .collect();
+        assert_eq!(played, ["a", "b", "a"]);
+        assert!(next_fallback(&[], &mut index).is_none());
+    }
+
+    #[test]
+    fn test_equal_power_crossfade() {
+        let mut from = vec![1.0f32; 200];
+        let to = vec![1.0f32; 200];
+        equal_power_crossfade(&mut from, &to, 2);
+
+        // Correlated signals peak at sqrt(2) halfway, where both gains are ~0.707
+        assert!(from[0] > 0.99 && from[199] > 0.99);
+        assert!((from[100] - std::f32::consts::SQRT_2).abs() < 0.02);
 
-        Ok(bytes)
+        // The incoming track rises while the outgoing one falls
+        let mut from = vec![0.0f32; 8];
+        equal_power_crossfade(&mut from, &[1.0; 8], 2);
+        assert!(from[0] < from[2] && from[2] < from[4] && from[4] < from[6]);
     }
 }
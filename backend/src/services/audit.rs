@@ -0,0 +1,72 @@
+//! Audit trail of administrative actions
+//!
+//! Admin endpoints that change users (and other security-relevant state) record who
+//! did what to which object in `admin_audit_log`. Recording never fails the action
+//! itself; a failed insert is logged instead.
+
+use crate::error::Result;
+use crate::models::AuditLogEntry;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Maximum number of entries returned by a single list query
+const MAX_LIST_LIMIT: i64 = 500;
+
+pub async fn record(
+    db: &PgPool,
+    actor_id: Uuid,
+    action: &str,
+    target_type: &str,
+    target_id: impl ToString,
+    details: serde_json::Value,
+) {
+    let target_id = target_id.to_string();
+    tracing::info!("Audit: {} {} {} {} {}", actor_id, action, target_type, target_id, details);
+
+    if let Err(e) = sqlx::query(
+        r#"
+        INSERT INTO admin_audit_log (actor_id, action, target_type, target_id, details)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(actor_id)
+    .bind(action)
+    .bind(target_type)
+    .bind(&target_id)
+    .bind(&details)
+    .execute(db)
+    .await
+    {
+        tracing::error!("Failed to write audit log entry for {} {}: {}", action, target_id, e);
+    }
+}
+
+/// Most recent entries first, optionally filtered by target type and ID
+pub async fn list(
+    db: &PgPool,
+    target_type: Option<&str>,
+    target_id: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<AuditLogEntry>> {
+    let entries = sqlx::query_as::<_, AuditLogEntry>(
+        r#"
+        SELECT a.id, a.actor_id, u.username AS actor_username, a.action, a.target_type,
+               a.target_id, a.details, a.created_at
+        FROM admin_audit_log a
+        LEFT JOIN users u ON u.id = a.actor_id
+        WHERE ($1::TEXT IS NULL OR a.target_type = $1)
+          AND ($2::TEXT IS NULL OR a.target_id = $2)
+        ORDER BY a.created_at DESC
+        LIMIT $3 OFFSET $4
+        "#,
+    )
+    .bind(target_type)
+    .bind(target_id)
+    .bind(limit.clamp(1, MAX_LIST_LIMIT))
+    .bind(offset.max(0))
+    .fetch_all(db)
+    .await?;
+
+    Ok(entries)
+}
@@ -1,14 +1,24 @@
 use crate::config::Config;
 use crate::error::{AppError, Result};
-use crate::models::{AuthResponse, CreateUserRequest, LoginRequest, User, UserRole};
+use crate::models::{
+    AdminCreateUserRequest, ApiKey, AuthResponse, CreateApiKeyRequest, CreateApiKeyResponse,
+    CreateUserRequest, DeviceInfo, LoginRequest, UpdateUserRequest, User, UserPreferences,
+    UserRole,
+};
+use crate::services::oidc::{self, OidcIdentity};
+use crate::services::sessions;
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2,
 };
-use chrono::{Duration, Utc};
+use axum::http::Method;
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
+use std::sync::OnceLock;
 use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -16,11 +26,23 @@ pub struct Claims {
     pub sub: Uuid,
     pub role: UserRole,
     pub exp: i64,
+    /// Set when the request was authenticated with an API key rather than a user token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_id: Option<Uuid>,
+    /// Login session the token was issued for (see `/me/sessions`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>,
 }
 
+/// Prefix identifying API keys (as opposed to JWTs) in the Authorization header
+pub const API_KEY_PREFIX: &str = "nrk_";
+
 pub struct AuthService {
     db: PgPool,
     jwt_secret: String,
+    access_token_ttl: Duration,
+    /// Hash verified against when a login names an unknown user
+    dummy_password_hash: OnceLock<String>,
 }
 
 impl AuthService {
@@ -28,10 +50,12 @@ impl AuthService {
         Self {
             db,
             jwt_secret: config.jwt_secret.clone(),
+            access_token_ttl: Duration::minutes(config.access_token_ttl_mins.max(1)),
+            dummy_password_hash: OnceLock::new(),
         }
     }
 
-    pub async fn register(&self, req: CreateUserRequest) -> Result<AuthResponse> {
+    pub async fn register(&self, req: CreateUserRequest, device: &DeviceInfo) -> Result<AuthResponse> {
         // Hash password
         let password_hash = self.hash_password(&req.password)?;
 
@@ -40,10 +64,11 @@ impl AuthService {
             .fetch_one(&self.db)
             .await?;
 
+        // Self-registration never grants elevated roles; admins promote users afterwards
         let role = if user_count == 0 {
             UserRole::Admin // First user is always admin
         } else {
-            req.role.unwrap_or(UserRole::Listener)
+            UserRole::Listener
         };
 
         // Create user
@@ -68,27 +93,34 @@ impl AuthService {
             }
         })?;
 
-        // Generate token
-        let token = self.generate_token(&user)?;
-
-        Ok(AuthResponse {
-            token,
-            user: user.into(),
-        })
+        self.start_session(user, device).await
     }
 
-    pub async fn login(&self, req: LoginRequest) -> Result<AuthResponse> {
+    pub async fn login(&self, req: LoginRequest, device: &DeviceInfo) -> Result<AuthResponse> {
         // Fetch user
         let user = sqlx::query_as::<_, User>(
             "SELECT * FROM users WHERE username = $1",
         )
         .bind(&req.username)
         .fetch_optional(&self.db)
-        .await?
-        .ok_or(AppError::InvalidCredentials)?;
+        .await?;
 
-        // Verify password
-        self.verify_password(&req.password, &user.password_hash)?;
+        // Verify password (against a dummy hash for unknown users, so response timing
+        // doesn't reveal which usernames exist)
+        let user = match user {
+            Some(user) => {
+                self.verify_password(&req.password, &user.password_hash)?;
+                user
+            }
+            None => {
+                let _ = self.verify_password(&req.password, self.dummy_password_hash()?);
+                return Err(AppError::InvalidCredentials);
+            }
+        };
+
+        if user.disabled_at.is_some() {
+            return Err(AppError::AuthenticationFailed);
+        }
 
         // Update last login
         sqlx::query("UPDATE users SET last_login = NOW() WHERE id = $1")
@@ -96,13 +128,135 @@ impl AuthService {
             .execute(&self.db)
             .await?;
 
-        // Generate token
-        let token = self.generate_token(&user)?;
+        self.start_session(user, device).await
+    }
+
+    /// Sign in a user the OIDC provider authenticated. Their first login links the
+    /// account with the same verified email, or provisions a new one; after that the
+    /// account's role follows the provider's role claim.
+    pub async fn login_oidc(&self, identity: &OidcIdentity, device: &DeviceInfo) -> Result<AuthResponse> {
+        let user = match self.oidc_user(identity).await? {
+            Some(user) => user,
+            None => self.provision_oidc_user(identity).await?,
+        };
+        if user.disabled_at.is_some() {
+            return Err(AppError::AuthenticationFailed);
+        }
 
-        Ok(AuthResponse {
+        let mut role = user.role.clone();
+        if identity.role_claimed && identity.role != role {
+            let demotes_admin = role == UserRole::Admin && identity.role != UserRole::Admin;
+            if demotes_admin && self.ensure_other_active_admin(user.id).await.is_err() {
+                tracing::warn!("Keeping the admin role of {}: they are the last active admin", user.username);
+            } else {
+                role = identity.role.clone();
+            }
+        }
+
+        let user = sqlx::query_as::<_, User>(
+            "UPDATE users SET last_login = NOW(), role = $2 WHERE id = $1 RETURNING *",
+        )
+        .bind(user.id)
+        .bind(role)
+        .fetch_one(&self.db)
+        .await?;
+
+        self.start_session(user, device).await
+    }
+
+    /// The account an identity signed in with before. Identities never link to local
+    /// accounts by email: registration doesn't verify emails, so anyone could have
+    /// registered the address first.
+    async fn oidc_user(&self, identity: &OidcIdentity) -> Result<Option<User>> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            SELECT u.* FROM users u
+            JOIN user_identities i ON i.user_id = u.id
+            WHERE i.issuer = $1 AND i.subject = $2
+            "#,
+        )
+        .bind(&identity.issuer)
+        .bind(&identity.subject)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(user)
+    }
+
+    async fn provision_oidc_user(&self, identity: &OidcIdentity) -> Result<User> {
+        let user_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users")
+            .fetch_one(&self.db)
+            .await?;
+        let role = if user_count == 0 {
+            UserRole::Admin // First user is always admin
+        } else {
+            identity.role.clone()
+        };
+
+        // Accounts that only sign in through the provider get a password nobody knows
+        let mut password = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut password);
+        let password_hash = self.hash_password(&hex::encode(password))?;
+
+        let placeholder_email = format!(
+            "{}@oidc.invalid",
+            &hex::encode(Sha256::digest(format!("{}|{}", identity.issuer, identity.subject)))[..16]
+        );
+        let email = match identity.email.as_deref() {
+            Some(email) if !self.email_taken(email).await? => email.to_string(),
+            _ => placeholder_email,
+        };
+
+        let base = oidc::username_candidate(identity);
+        for attempt in 0..20 {
+            let username = match attempt {
+                0 => base.clone(),
+                1..=9 => format!("{}-{}", base, attempt + 1),
+                _ => format!("{}-{}", base, &hex::encode(rand::random::<[u8; 3]>())),
+            };
+
+            let created = sqlx::query_as::<_, User>(
+                r#"
+                INSERT INTO users (username, email, password_hash, role, has_password)
+                VALUES ($1, $2, $3, $4, FALSE)
+                ON CONFLICT (username) DO NOTHING
+                RETURNING *
+                "#,
+            )
+            .bind(&username)
+            .bind(&email)
+            .bind(&password_hash)
+            .bind(&role)
+            .fetch_optional(&self.db)
+            .await?;
+
+            if let Some(user) = created {
+                tracing::info!("Provisioned user {} ({}) from {}", user.username, user.role, identity.issuer);
+                return Ok(user);
+            }
+        }
+
+        Err(AppError::Conflict("Could not find a free username".to_string()))
+    }
+
+    async fn email_taken(&self, email: &str) -> Result<bool> {
+        let taken = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE LOWER(email) = LOWER($1))")
+            .bind(email)
+            .fetch_one(&self.db)
+            .await?;
+        Ok(taken)
+    }
+
+    /// User a token was signed for, checking only the signature and expiry. Cheap
+    /// enough for every request (used by the rate limiter), but unlike
+    /// [`verify_token`](Self::verify_token) it ignores disabled accounts and revoked sessions.
+    pub fn token_subject(&self, token: &str) -> Option<Uuid> {
+        decode::<Claims>(
             token,
-            user: user.into(),
-        })
+            &DecodingKey::from_secret(self.jwt_secret.as_bytes()),
+            &Validation::default(),
+        )
+        .ok()
+        .map(|data| data.claims.sub)
     }
 
     pub async fn verify_token(&self, token: &str) -> Result<Claims> {
@@ -112,19 +266,170 @@ impl AuthService {
             &Validation::default(),
         )
         .map_err(|_| AppError::Unauthorized)?;
+        let mut claims = token_data.claims;
 
-        Ok(token_data.claims)
+        // Tokens outlive role changes and account disabling, so the role always comes
+        // from the database and disabled or deleted users are rejected immediately
+        let (role, disabled_at): (UserRole, Option<DateTime<Utc>>) =
+            sqlx::query_as("SELECT role, disabled_at FROM users WHERE id = $1")
+                .bind(claims.sub)
+                .fetch_optional(&self.db)
+                .await?
+                .ok_or(AppError::Unauthorized)?;
+        if disabled_at.is_some() {
+            return Err(AppError::Unauthorized);
+        }
+        if let Some(sid) = claims.sid {
+            sessions::touch(&self.db, claims.sub, sid).await?;
+        }
+        claims.role = role;
+
+        Ok(claims)
     }
 
     /// Validate that a token belongs to an admin user
     pub async fn validate_admin_token(&self, token: &str) -> Result<Claims> {
+        self.validate_token_role(token, UserRole::Admin).await
+    }
+
+    /// Validate that a token belongs to a user with at least the given role
+    pub async fn validate_token_role(&self, token: &str, role: UserRole) -> Result<Claims> {
         let claims = self.verify_token(token).await?;
-        if claims.role != UserRole::Admin {
+        if !claims.role.includes(&role) {
             return Err(AppError::Forbidden);
         }
         Ok(claims)
     }
 
+    /// Create an API key owned by `user_id`. The plaintext key is only returned here.
+    pub async fn create_api_key(&self, user_id: Uuid, req: CreateApiKeyRequest) -> Result<CreateApiKeyResponse> {
+        if let Some(station_ids) = &req.station_ids {
+            let mut unique = station_ids.clone();
+            unique.sort_unstable();
+            unique.dedup();
+
+            let found: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM stations WHERE id = ANY($1)")
+                .bind(&unique)
+                .fetch_one(&self.db)
+                .await?;
+            if found as usize != unique.len() {
+                return Err(AppError::NotFound("Station not found".to_string()));
+            }
+        }
+
+        let mut secret = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        let key = format!("{}{}", API_KEY_PREFIX, hex::encode(secret));
+
+        let api_key = sqlx::query_as::<_, ApiKey>(
+            r#"
+            INSERT INTO api_keys (name, key_prefix, key_hash, scopes, created_by, expires_at, station_ids)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+        )
+        .bind(&req.name)
+        .bind(&key[..API_KEY_PREFIX.len() + 8])
+        .bind(hash_api_key(&key))
+        .bind(serde_json::to_value(&req.scopes)?)
+        .bind(user_id)
+        .bind(req.expires_in_days.map(|days| Utc::now() + Duration::days(days)))
+        .bind(&req.station_ids)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(CreateApiKeyResponse { key, api_key })
+    }
+
+    /// Authenticate an API key for a request. The key acts with its owner's current role,
+    /// limited to the request kinds its scopes allow.
+    pub async fn verify_api_key(&self, key: &str, method: &Method, path: &str) -> Result<Claims> {
+        let api_key = sqlx::query_as::<_, ApiKey>(
+            r#"
+            SELECT * FROM api_keys
+            WHERE key_hash = $1
+              AND revoked_at IS NULL
+              AND (expires_at IS NULL OR expires_at > NOW())
+            "#,
+        )
+        .bind(hash_api_key(key))
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+        if !api_key.allows(method, path) {
+            return Err(AppError::Forbidden);
+        }
+
+        let user = self.get_user_by_id(api_key.created_by).await?;
+        if user.disabled_at.is_some() {
+            return Err(AppError::Unauthorized);
+        }
+
+        sqlx::query("UPDATE api_keys SET last_used_at = NOW() WHERE id = $1")
+            .bind(api_key.id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(Claims {
+            sub: user.id,
+            role: user.role,
+            exp: api_key.expires_at.unwrap_or_else(|| Utc::now() + Duration::days(1)).timestamp(),
+            api_key_id: Some(api_key.id),
+            sid: None,
+        })
+    }
+
+    pub async fn list_api_keys(&self) -> Result<Vec<ApiKey>> {
+        let keys = sqlx::query_as::<_, ApiKey>("SELECT * FROM api_keys ORDER BY created_at DESC")
+            .fetch_all(&self.db)
+            .await?;
+        Ok(keys)
+    }
+
+    /// A user's own stream-only keys, including revoked ones
+    pub async fn list_stream_tokens(&self, user_id: Uuid) -> Result<Vec<ApiKey>> {
+        let keys = sqlx::query_as::<_, ApiKey>(
+            r#"
+            SELECT * FROM api_keys
+            WHERE created_by = $1 AND scopes = '["stream"]'::jsonb
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(keys)
+    }
+
+    /// Revoke an API key. With `owner` set, only a key created by that user is revoked.
+    pub async fn revoke_api_key(&self, id: Uuid, owner: Option<Uuid>) -> Result<()> {
+        let result = sqlx::query(
+            r#"
+            UPDATE api_keys SET revoked_at = NOW()
+            WHERE id = $1 AND revoked_at IS NULL AND ($2::UUID IS NULL OR created_by = $2)
+            "#,
+        )
+        .bind(id)
+        .bind(owner)
+        .execute(&self.db)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("API key not found".to_string()));
+        }
+        Ok(())
+    }
+
+    pub async fn update_preferences(&self, user_id: Uuid, preferences: &UserPreferences) -> Result<User> {
+        sqlx::query_as::<_, User>("UPDATE users SET preferences = $2 WHERE id = $1 RETURNING *")
+            .bind(user_id)
+            .bind(serde_json::to_value(preferences)?)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| AppError::NotFound("User not found".to_string()))
+    }
+
     pub async fn get_user_by_id(&self, user_id: Uuid) -> Result<User> {
         sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1")
             .bind(user_id)
@@ -133,7 +438,174 @@ impl AuthService {
             .ok_or_else(|| AppError::NotFound("User not found".to_string()))
     }
 
-    fn hash_password(&self, password: &str) -> Result<String> {
+    pub async fn list_users(&self) -> Result<Vec<User>> {
+        let users = sqlx::query_as::<_, User>("SELECT * FROM users ORDER BY created_at")
+            .fetch_all(&self.db)
+            .await?;
+        Ok(users)
+    }
+
+    /// Create an account with any role (admin only; self-registration always creates listeners)
+    pub async fn create_user(&self, req: AdminCreateUserRequest) -> Result<User> {
+        let password_hash = self.hash_password(&req.password)?;
+
+        sqlx::query_as::<_, User>(
+            r#"
+            INSERT INTO users (username, email, password_hash, role)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+            "#,
+        )
+        .bind(&req.username)
+        .bind(&req.email)
+        .bind(&password_hash)
+        .bind(req.role)
+        .fetch_one(&self.db)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("unique") {
+                AppError::Validation("Username or email already exists".to_string())
+            } else {
+                AppError::Database(e)
+            }
+        })
+    }
+
+    /// Apply an admin's changes to a user. Refuses to leave the server without an
+    /// active admin. A password reset signs the user out everywhere, so whoever knew the
+    /// old password loses their sessions too.
+    pub async fn update_user(&self, id: Uuid, req: &UpdateUserRequest) -> Result<User> {
+        let user = self.get_user_by_id(id).await?;
+
+        let loses_admin = user.role == UserRole::Admin
+            && user.disabled_at.is_none()
+            && (req.role.as_ref().is_some_and(|r| *r != UserRole::Admin) || req.disabled == Some(true));
+        if loses_admin {
+            self.ensure_other_active_admin(id).await?;
+        }
+
+        let password_hash = req
+            .password
+            .as_deref()
+            .map(|p| self.hash_password(p))
+            .transpose()?;
+
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            UPDATE users SET
+                email = COALESCE($2, email),
+                role = COALESCE($3, role),
+                disabled_at = CASE
+                    WHEN $4::BOOLEAN IS NULL THEN disabled_at
+                    WHEN $4 THEN COALESCE(disabled_at, NOW())
+                    ELSE NULL
+                END,
+                password_hash = COALESCE($5, password_hash),
+                has_password = has_password OR $5 IS NOT NULL
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(&req.email)
+        .bind(&req.role)
+        .bind(req.disabled)
+        .bind(password_hash)
+        .fetch_one(&self.db)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("unique") {
+                AppError::Validation("Email already in use".to_string())
+            } else {
+                AppError::Database(e)
+            }
+        })?;
+
+        if req.password.is_some() {
+            sessions::revoke_all(&self.db, id).await?;
+        }
+        Ok(user)
+    }
+
+    /// Delete a user. Their stations (other than their Daily Mix) are handed to
+    /// `new_owner` instead of being deleted with the account. Returns how many moved.
+    pub async fn delete_user(&self, id: Uuid, new_owner: Uuid) -> Result<u64> {
+        let user = self.get_user_by_id(id).await?;
+        if user.role == UserRole::Admin && user.disabled_at.is_none() {
+            self.ensure_other_active_admin(id).await?;
+        }
+
+        let mut tx = self.db.begin().await?;
+        let transferred = sqlx::query(
+            "UPDATE stations SET created_by = $2 WHERE created_by = $1 AND daily_mix_user_id IS NULL",
+        )
+        .bind(id)
+        .bind(new_owner)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(transferred)
+    }
+
+    /// Delete the caller's own account after re-checking their password, unless the account
+    /// only signs in through the OpenID Connect provider and has none. Their stations go
+    /// to the longest-serving other active admin so shared stations keep playing.
+    /// Returns how many stations moved.
+    pub async fn delete_own_account(&self, id: Uuid, password: Option<&str>) -> Result<u64> {
+        let user = self.get_user_by_id(id).await?;
+        let provider_only: bool = sqlx::query_scalar(
+            r#"
+            SELECT NOT has_password
+               AND EXISTS(SELECT 1 FROM user_identities WHERE user_id = users.id)
+            FROM users WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&self.db)
+        .await?;
+        if !provider_only {
+            let password = password.ok_or(AppError::InvalidCredentials)?;
+            self.verify_password(password, &user.password_hash)?;
+        }
+
+        let new_owner: Uuid = sqlx::query_scalar(
+            r#"
+            SELECT id FROM users
+            WHERE role = 'admin' AND disabled_at IS NULL AND id <> $1
+            ORDER BY created_at
+            LIMIT 1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| AppError::Conflict("Cannot remove the last active admin".to_string()))?;
+
+        self.delete_user(id, new_owner).await
+    }
+
+    async fn ensure_other_active_admin(&self, id: Uuid) -> Result<()> {
+        let others: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM users WHERE role = 'admin' AND disabled_at IS NULL AND id <> $1",
+        )
+        .bind(id)
+        .fetch_one(&self.db)
+        .await?;
+
+        if others == 0 {
+            return Err(AppError::Conflict("Cannot remove the last active admin".to_string()));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn hash_password(&self, password: &str) -> Result<String> {
         let salt = SaltString::generate(&mut OsRng);
         let argon2 = Argon2::default();
 
@@ -143,6 +615,14 @@ impl AuthService {
             .map_err(|e| AppError::Internal(anyhow::anyhow!("Password hashing failed: {}", e)))
     }
 
+    fn dummy_password_hash(&self) -> Result<&str> {
+        if let Some(hash) = self.dummy_password_hash.get() {
+            return Ok(hash.as_str());
+        }
+        let hash = self.hash_password("dummy-password-for-timing")?;
+        Ok(self.dummy_password_hash.get_or_init(|| hash).as_str())
+    }
+
     fn verify_password(&self, password: &str, password_hash: &str) -> Result<()> {
         let parsed_hash =
             PasswordHash::new(password_hash)
@@ -153,18 +633,102 @@ impl AuthService {
             .map_err(|_| AppError::InvalidCredentials)
     }
 
-    fn generate_token(&self, user: &User) -> Result<String> {
+    /// Start a session for the user and issue tokens bound to it
+    async fn start_session(&self, user: User, device: &DeviceInfo) -> Result<AuthResponse> {
+        let session = sessions::create(&self.db, user.id, device).await?;
+        self.auth_response(user, session)
+    }
+
+    /// Trade a refresh token for a new access token and refresh token
+    pub async fn refresh(&self, refresh_token: &str) -> Result<AuthResponse> {
+        let session = sessions::refresh(&self.db, refresh_token).await?;
+        let user = self.get_user_by_id(session.user_id).await.map_err(|_| AppError::Unauthorized)?;
+        if user.disabled_at.is_some() {
+            sessions::revoke_all(&self.db, user.id).await?;
+            return Err(AppError::Unauthorized);
+        }
+        self.auth_response(user, session)
+    }
+
+    fn auth_response(&self, user: User, session: sessions::IssuedSession) -> Result<AuthResponse> {
+        let expires_at = (Utc::now() + self.access_token_ttl).min(session.expires_at);
         let claims = Claims {
             sub: user.id,
             role: user.role.clone(),
-            exp: (Utc::now() + Duration::days(7)).timestamp(),
+            exp: expires_at.timestamp(),
+            api_key_id: None,
+            sid: Some(session.id),
         };
 
-        encode(
+        let token = encode(
             &Header::default(),
             &claims,
             &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
         )
-        .map_err(|e| AppError::Internal(anyhow::anyhow!("Token generation failed: {}", e)))
+        .map_err(|e| AppError::Internal(anyhow::anyhow!("Token generation failed: {}", e)))?;
+
+        Ok(AuthResponse {
+            token,
+            expires_in: (expires_at - Utc::now()).num_seconds().max(0),
+            refresh_token: session.refresh_token,
+            user: user.into(),
+        })
+    }
+}
+
+/// Keys are 256-bit random values, so a fast unsalted hash is sufficient for lookup
+fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::migrations::MIGRATOR;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    /// Runs against the database in TEST_DATABASE_URL (migrated first), and is skipped
+    /// without one
+    #[tokio::test]
+    async fn test_password_reset_revokes_refresh_tokens() {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            eprintln!("TEST_DATABASE_URL not set, skipping");
+            return;
+        };
+        let db = PgPool::connect(&url).await.unwrap();
+        MIGRATOR.run(&db).await.unwrap();
+        let auth = AuthService {
+            db: db.clone(),
+            jwt_secret: "test-secret".to_string(),
+            access_token_ttl: Duration::minutes(15),
+            dummy_password_hash: OnceLock::new(),
+        };
+
+        let name = format!("reset-{}", &Uuid::new_v4().simple().to_string()[..8]);
+        let user = auth
+            .create_user(AdminCreateUserRequest {
+                username: name.clone(),
+                email: format!("{}@example.com", name),
+                password: "old-password".to_string(),
+                role: UserRole::Listener,
+            })
+            .await
+            .unwrap();
+        let device = DeviceInfo { ip: IpAddr::V4(Ipv4Addr::LOCALHOST), user_agent: None };
+        let session = sessions::create(&db, user.id, &device).await.unwrap();
+
+        let reset = UpdateUserRequest {
+            email: None,
+            role: None,
+            disabled: None,
+            password: Some("new-password".to_string()),
+        };
+        auth.update_user(user.id, &reset).await.unwrap();
+
+        assert!(matches!(
+            sessions::refresh(&db, &session.refresh_token).await,
+            Err(AppError::Unauthorized)
+        ));
+        auth.delete_user(user.id, user.id).await.unwrap();
     }
 }
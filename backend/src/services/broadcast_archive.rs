@@ -0,0 +1,416 @@
+//! Broadcast archives
+//!
+//! With `ARCHIVE_DIR` set, stations whose config sets `archive` have their broadcast
+//! recorded: one file per hour (`<dir>/<station id>/<YYYY-MM-DDTHH>.<ext>`, UTC) next to
+//! a JSON cue sheet of the tracks in it, for replaying shows. Files are named after the
+//! hour they start in and kept for `ARCHIVE_RETENTION_DAYS`.
+//!
+//! Only what is broadcast is recorded, so a station that stops streaming (e.g. when its
+//! last listener leaves) has gaps. The archive is written on the node that broadcasts
+//! the station; with several streamer nodes it has to be shared storage.
+
+use crate::error::{AppError, Result};
+use crate::models::AudioCodec;
+use crate::services::audio_broadcaster::HlsSegment;
+use bytes::Bytes;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+/// strftime format of archive names
+const NAME_FORMAT: &str = "%Y-%m-%dT%H";
+
+/// Segments between cue sheet saves, so the current hour's sheet stays close to its file
+const CUE_SAVE_SEGMENTS: u32 = 30;
+
+/// Without a segment for this long the stream has stopped and the open file is closed
+const IDLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// A recorded hour and the tracks in it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CueSheet {
+    pub station_id: Uuid,
+    pub codec: AudioCodec,
+    /// When recording of the hour started
+    pub started_at: DateTime<Utc>,
+    /// Audio recorded so far
+    pub duration_secs: f64,
+    pub tracks: Vec<Cue>,
+}
+
+/// A track boundary in an archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cue {
+    /// Position in the file the track starts at
+    pub offset_secs: f64,
+    pub track_id: String,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+}
+
+/// An archive as listed for a station
+#[derive(Debug, Serialize)]
+pub struct ArchiveInfo {
+    /// `YYYY-MM-DDTHH` (UTC) of the hour, which identifies the archive
+    pub name: String,
+    pub codec: AudioCodec,
+    pub started_at: DateTime<Utc>,
+    pub duration_secs: f64,
+    pub size_bytes: u64,
+    pub tracks: usize,
+}
+
+pub struct BroadcastArchive {
+    dir: PathBuf,
+    /// Days archives are kept (0 keeps them forever)
+    retention_days: u32,
+}
+
+impl BroadcastArchive {
+    pub fn new(dir: PathBuf, retention_days: u32) -> Self {
+        Self { dir, retention_days }
+    }
+
+    fn station_dir(&self, station_id: Uuid) -> PathBuf {
+        self.dir.join(station_id.to_string())
+    }
+
+    /// A station's archives, newest first
+    pub async fn list(&self, station_id: Uuid) -> Result<Vec<ArchiveInfo>> {
+        let dir = self.station_dir(station_id);
+        let mut entries = match tokio::fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(storage_error(&dir, e)),
+        };
+
+        let mut archives = Vec::new();
+        while let Some(entry) = entries.next_entry().await.map_err(|e| storage_error(&dir, e))? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()).filter(|n| hour_of(n).is_some()) else {
+                continue;
+            };
+            let Ok(cues) = read_cue_sheet(&path).await else {
+                tracing::debug!("Skipping unreadable cue sheet {:?}", path);
+                continue;
+            };
+            let Ok(metadata) = tokio::fs::metadata(dir.join(audio_file_name(name, cues.codec))).await else {
+                continue;
+            };
+            archives.push(ArchiveInfo {
+                name: name.to_string(),
+                codec: cues.codec,
+                started_at: cues.started_at,
+                duration_secs: cues.duration_secs,
+                size_bytes: metadata.len(),
+                tracks: cues.tracks.len(),
+            });
+        }
+        archives.sort_by(|a, b| b.name.cmp(&a.name));
+        Ok(archives)
+    }
+
+    /// Cue sheet of one of a station's archives
+    pub async fn cue_sheet(&self, station_id: Uuid, name: &str) -> Result<CueSheet> {
+        if hour_of(name).is_none() {
+            return Err(AppError::NotFound("Archive not found".to_string()));
+        }
+        read_cue_sheet(&self.station_dir(station_id).join(format!("{}.json", name)))
+            .await
+            .map_err(|_| AppError::NotFound("Archive not found".to_string()))
+    }
+
+    /// Audio file of one of a station's archives, with its codec
+    pub async fn audio_file(&self, station_id: Uuid, name: &str) -> Result<(PathBuf, AudioCodec)> {
+        let cues = self.cue_sheet(station_id, name).await?;
+        let path = self.station_dir(station_id).join(audio_file_name(name, cues.codec));
+        if !tokio::fs::try_exists(&path).await.unwrap_or(false) {
+            return Err(AppError::NotFound("Archive not found".to_string()));
+        }
+        Ok((path, cues.codec))
+    }
+
+    /// Record a station's broadcast from its segments until the broadcaster goes away.
+    /// `init_segment` is written at the start of each file (fMP4 streams).
+    pub fn spawn_recorder(
+        self: std::sync::Arc<Self>,
+        db: PgPool,
+        station_id: Uuid,
+        codec: AudioCodec,
+        init_segment: Option<Bytes>,
+        mut segments: broadcast::Receiver<HlsSegment>,
+    ) {
+        tokio::spawn(async move {
+            let mut recording: Option<Recording> = None;
+            loop {
+                let segment = match tokio::time::timeout(IDLE_TIMEOUT, segments.recv()).await {
+                    Ok(Ok(segment)) => segment,
+                    Ok(Err(RecvError::Lagged(missed))) => {
+                        tracing::warn!("Archive of station {} skipped {} segments", station_id, missed);
+                        continue;
+                    }
+                    Ok(Err(RecvError::Closed)) => break,
+                    Err(_) => {
+                        if let Some(recording) = recording.take() {
+                            recording.finish().await;
+                        }
+                        continue;
+                    }
+                };
+
+                let name = Utc::now().format(NAME_FORMAT).to_string();
+                if recording.as_ref().is_none_or(|r| r.name != name) {
+                    if let Some(previous) = recording.take() {
+                        previous.finish().await;
+                    }
+                    self.prune(station_id).await;
+                    match Recording::open(&self.station_dir(station_id), &name, station_id, codec, &init_segment).await {
+                        Ok(opened) => recording = Some(opened),
+                        Err(e) => {
+                            tracing::warn!("Failed to open archive {} of station {}: {}", name, station_id, e);
+                            continue;
+                        }
+                    }
+                }
+
+                if let Some(current) = recording.as_mut() {
+                    if let Err(e) = current.append(&db, &segment).await {
+                        tracing::warn!("Failed to write archive {} of station {}: {}", current.name, station_id, e);
+                        recording = None;
+                    }
+                }
+            }
+
+            if let Some(recording) = recording {
+                recording.finish().await;
+            }
+            tracing::debug!("Stopped archiving station {}", station_id);
+        });
+    }
+
+    /// Remove a station's archives that are past the retention period
+    async fn prune(&self, station_id: Uuid) {
+        if self.retention_days == 0 {
+            return;
+        }
+        let cutoff = Utc::now() - Duration::days(self.retention_days as i64);
+        let Ok(mut entries) = tokio::fs::read_dir(self.station_dir(station_id)).await else {
+            return;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let expired = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(hour_of)
+                .is_some_and(|hour| hour < cutoff);
+            if expired {
+                if let Err(e) = tokio::fs::remove_file(&path).await {
+                    tracing::warn!("Failed to remove expired archive {:?}: {}", path, e);
+                }
+            }
+        }
+    }
+}
+
+/// The hour being recorded
+struct Recording {
+    name: String,
+    file: tokio::fs::File,
+    cue_path: PathBuf,
+    cues: CueSheet,
+    /// Segments since the cue sheet was last saved
+    unsaved: u32,
+}
+
+impl Recording {
+    /// Start (or, after a restart within the hour, continue) the archive `name`
+    async fn open(
+        dir: &Path,
+        name: &str,
+        station_id: Uuid,
+        codec: AudioCodec,
+        init_segment: &Option<Bytes>,
+    ) -> std::io::Result<Self> {
+        tokio::fs::create_dir_all(dir).await?;
+        let cue_path = dir.join(format!("{}.json", name));
+        let audio_path = dir.join(audio_file_name(name, codec));
+
+        let existing = read_cue_sheet(&cue_path).await.ok().filter(|cues| cues.codec == codec);
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&audio_path)
+            .await?;
+        let cues = match existing {
+            Some(cues) => cues,
+            None => {
+                file.set_len(0).await?;
+                if let Some(init) = init_segment {
+                    file.write_all(init).await?;
+                }
+                CueSheet {
+                    station_id,
+                    codec,
+                    started_at: Utc::now(),
+                    duration_secs: 0.0,
+                    tracks: Vec::new(),
+                }
+            }
+        };
+        tracing::info!("Archiving station {} to {:?}", station_id, audio_path);
+
+        Ok(Self {
+            name: name.to_string(),
+            file,
+            cue_path,
+            cues,
+            unsaved: 0,
+        })
+    }
+
+    async fn append(&mut self, db: &PgPool, segment: &HlsSegment) -> std::io::Result<()> {
+        // Continuous files don't need the segments' ID3 tags
+        self.file.write_all(&segment.audio()).await?;
+
+        let track_changed = !segment.track_id.is_empty()
+            && self.cues.tracks.last().is_none_or(|cue| cue.track_id != segment.track_id);
+        if track_changed {
+            self.cues.tracks.push(cue(db, &segment.track_id, self.cues.duration_secs).await);
+        }
+        self.cues.duration_secs += segment.duration as f64;
+
+        self.unsaved += 1;
+        if track_changed || self.unsaved >= CUE_SAVE_SEGMENTS {
+            self.save().await?;
+        }
+        Ok(())
+    }
+
+    async fn save(&mut self) -> std::io::Result<()> {
+        let json = serde_json::to_vec_pretty(&self.cues).map_err(std::io::Error::other)?;
+        // Replaced atomically, so listing never sees half a sheet
+        let temp_path = self.cue_path.with_extension("json.tmp");
+        tokio::fs::write(&temp_path, json).await?;
+        tokio::fs::rename(&temp_path, &self.cue_path).await?;
+        self.unsaved = 0;
+        Ok(())
+    }
+
+    async fn finish(mut self) {
+        let result = match self.file.flush().await {
+            Ok(()) => self.save().await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::warn!("Failed to finish archive {:?}: {}", self.cue_path, e);
+        }
+    }
+}
+
+/// Cue for a track starting at `offset_secs`, with its tags from the library index
+async fn cue(db: &PgPool, track_id: &str, offset_secs: f64) -> Cue {
+    let tags: Option<(String, String, String)> =
+        sqlx::query_as("SELECT title, artist, album FROM library_index WHERE id = $1")
+            .bind(track_id)
+            .fetch_optional(db)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::debug!("Failed to look up archived track {}: {}", track_id, e);
+                None
+            });
+    let (title, artist, album) = tags.unwrap_or_default();
+    Cue {
+        offset_secs,
+        track_id: track_id.to_string(),
+        title,
+        artist,
+        album,
+    }
+}
+
+async fn read_cue_sheet(path: &Path) -> std::io::Result<CueSheet> {
+    let json = tokio::fs::read(path).await?;
+    serde_json::from_slice(&json).map_err(std::io::Error::other)
+}
+
+/// Extension of archived audio: continuous MP3 and ADTS, fragmented MP4 for Opus
+pub fn file_extension(codec: AudioCodec) -> &'static str {
+    match codec {
+        AudioCodec::Mp3 => "mp3",
+        AudioCodec::Aac => "aac",
+        AudioCodec::Opus => "mp4",
+    }
+}
+
+fn audio_file_name(name: &str, codec: AudioCodec) -> String {
+    format!("{}.{}", name, file_extension(codec))
+}
+
+/// Start of the hour an archive name stands for; None for anything else, which also
+/// keeps names from reaching outside the archive directory
+fn hour_of(name: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(&format!("{}:00", name), "%Y-%m-%dT%H:%M")
+        .ok()
+        .filter(|hour| hour.format(NAME_FORMAT).to_string() == name)
+        .map(|hour| hour.and_utc())
+}
+
+fn storage_error(path: &Path, e: std::io::Error) -> AppError {
+    AppError::Storage(format!("Failed to read archives in {:?}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_hour_names_are_archives() {
+        let hour = hour_of("2026-10-16T13").unwrap();
+        assert_eq!(hour.to_rfc3339(), "2026-10-16T13:00:00+00:00");
+        assert!(hour_of("2026-10-16T25").is_none());
+        assert!(hour_of("2026-10-16T1").is_none());
+        assert!(hour_of("../../etc/passwd").is_none());
+        assert!(hour_of("2026-10-16T13/..").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_recordings_continue_within_the_hour() {
+        let root = std::env::temp_dir().join(format!("archive-{}", Uuid::new_v4()));
+        let archive = BroadcastArchive::new(root.clone(), 0);
+        let station_id = Uuid::new_v4();
+        let dir = archive.station_dir(station_id);
+        let init = Some(Bytes::from_static(b"init"));
+
+        let mut recording = Recording::open(&dir, "2026-10-16T13", station_id, AudioCodec::Opus, &init)
+            .await
+            .unwrap();
+        recording.file.write_all(b"moof").await.unwrap();
+        recording.cues.duration_secs = 2.0;
+        recording.finish().await;
+
+        // A restart within the hour appends instead of starting over
+        let recording = Recording::open(&dir, "2026-10-16T13", station_id, AudioCodec::Opus, &init)
+            .await
+            .unwrap();
+        assert_eq!(recording.cues.duration_secs, 2.0);
+        recording.finish().await;
+        assert_eq!(std::fs::read(dir.join("2026-10-16T13.mp4")).unwrap(), b"initmoof");
+
+        let archives = archive.list(station_id).await.unwrap();
+        assert_eq!(archives.len(), 1);
+        assert_eq!(archives[0].name, "2026-10-16T13");
+        assert_eq!(archives[0].size_bytes, 8);
+        assert!(archive.cue_sheet(station_id, "../2026-10-16T13").await.is_err());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
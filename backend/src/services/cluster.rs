@@ -0,0 +1,301 @@
+//! Station ownership between API and streamer nodes (`SERVER_ROLE`)
+//!
+//! In the default `all` role a process does everything itself. For horizontal scaling,
+//! `api` nodes handle auth, curation and the library but never run audio pipelines,
+//! while `streamer` nodes own the broadcasters. Streamers announce themselves in Redis
+//! with a short TTL, and each broadcasting station is leased to one streamer. Stream
+//! requests that reach a node which doesn't own the station are redirected to the
+//! streamer that does, and an unowned station is assigned to the least loaded one.
+//!
+//! When a streamer goes away, its leases expire and the surviving streamers take over
+//! its stations that still have listeners, spread by load, without waiting for a
+//! listener's next request. A streamer that finds its lease taken by another node stops
+//! its own broadcaster, so a station never streams from two places for long.
+
+use crate::config::ServerRole;
+use crate::error::{AppError, Result};
+use crate::services::audio_broadcaster::AudioBroadcaster;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Sorted set of streamer node IDs, scored by their last heartbeat (unix seconds)
+const STREAMERS_KEY: &str = "navidrome-radio:streamers";
+
+/// How often a streamer renews its registration and station leases
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A streamer that hasn't sent a heartbeat for this long is considered gone
+const STREAMER_TTL_SECS: u64 = 15;
+
+/// Lifetime of a station lease; renewed by the owner while it broadcasts
+const LEASE_TTL_SECS: u64 = 30;
+
+/// Delete a key only if it still holds the expected value
+const DELETE_IF_EQUALS: &str =
+    "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('DEL', KEYS[1]) else return 0 end";
+
+fn streamer_key(node_id: Uuid) -> String {
+    format!("navidrome-radio:streamer:{}", node_id)
+}
+
+fn lease_key(station_id: Uuid) -> String {
+    format!("navidrome-radio:station-owner:{}", station_id)
+}
+
+/// What a streamer publishes about itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamerInfo {
+    pub node_id: Uuid,
+    /// Base URL clients are redirected to, e.g. `https://stream1.example.com`
+    pub url: String,
+    /// Stations currently broadcasting on the node
+    pub stations: usize,
+}
+
+/// Where a station's stream is served from
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamLocation {
+    /// This process
+    Local,
+    /// Another streamer node, by base URL
+    Remote(String),
+}
+
+pub struct Cluster {
+    role: ServerRole,
+    node_id: Uuid,
+    /// This node's base URL as seen by clients (streamer role only)
+    advertise_url: Option<String>,
+    redis: ConnectionManager,
+}
+
+impl Cluster {
+    pub fn new(role: ServerRole, advertise_url: Option<String>, redis: ConnectionManager) -> Self {
+        Self {
+            role,
+            node_id: Uuid::new_v4(),
+            advertise_url,
+            redis,
+        }
+    }
+
+    pub fn role(&self) -> ServerRole {
+        self.role
+    }
+
+    pub fn node_id(&self) -> Uuid {
+        self.node_id
+    }
+
+    /// The node holding a station's lease, if any
+    pub async fn lease_owner(&self, station_id: Uuid) -> Result<Option<Uuid>> {
+        let mut conn = self.redis.clone();
+        let owner: Option<String> = conn.get(lease_key(station_id)).await?;
+        Ok(owner.and_then(|id| id.parse().ok()))
+    }
+
+    /// Claim the stations among `stations` whose owner is gone and that fall to this
+    /// node. Orphans are dealt to the least loaded live streamer in turn; every
+    /// streamer computes the same deal, so each station is claimed by one of them.
+    /// Returns the stations claimed.
+    pub async fn take_over_orphans(&self, stations: &[Uuid]) -> Result<Vec<Uuid>> {
+        if self.role != ServerRole::Streamer {
+            return Ok(Vec::new());
+        }
+        let mut streamers = self.live_streamers().await?;
+        if !streamers.iter().any(|s| s.node_id == self.node_id) {
+            return Ok(Vec::new());
+        }
+        streamers.sort_by_key(|s| s.node_id);
+        let mut stations = stations.to_vec();
+        stations.sort();
+
+        let mut conn = self.redis.clone();
+        let mut claimed = Vec::new();
+        for station_id in stations {
+            let key = lease_key(station_id);
+            if let Some(owner) = self.lease_owner(station_id).await? {
+                if streamers.iter().any(|s| s.node_id == owner) {
+                    continue;
+                }
+                let _: i64 = redis::Script::new(DELETE_IF_EQUALS)
+                    .key(&key)
+                    .arg(owner.to_string())
+                    .invoke_async(&mut conn)
+                    .await?;
+            }
+
+            let Some(target) = streamers.iter_mut().min_by_key(|s| s.stations) else {
+                break;
+            };
+            target.stations += 1;
+            if target.node_id != self.node_id {
+                continue;
+            }
+
+            let taken: Option<String> = redis::cmd("SET")
+                .arg(&key)
+                .arg(self.node_id.to_string())
+                .arg("NX")
+                .arg("EX")
+                .arg(LEASE_TTL_SECS)
+                .query_async(&mut conn)
+                .await?;
+            if taken.is_some() {
+                tracing::info!("Took over orphaned station {}", station_id);
+                claimed.push(station_id);
+            }
+        }
+        Ok(claimed)
+    }
+
+    /// Which node should serve a station's stream, assigning it to a streamer if no
+    /// live node holds the lease
+    pub async fn stream_location(&self, station_id: Uuid) -> Result<StreamLocation> {
+        if self.role == ServerRole::All {
+            return Ok(StreamLocation::Local);
+        }
+
+        let streamers = self.live_streamers().await?;
+        let mut conn = self.redis.clone();
+        let key = lease_key(station_id);
+
+        // Two attempts: a claim can lose the race against another node, in which case
+        // the winner's lease is read back
+        for _ in 0..2 {
+            let owner: Option<String> = conn.get(&key).await?;
+            let owner = owner.and_then(|id| id.parse::<Uuid>().ok());
+
+            match owner {
+                Some(owner) if owner == self.node_id => return Ok(StreamLocation::Local),
+                Some(owner) => {
+                    if let Some(streamer) = streamers.iter().find(|s| s.node_id == owner) {
+                        return Ok(StreamLocation::Remote(streamer.url.clone()));
+                    }
+                    // The owner is gone; drop its lease unless someone already replaced it
+                    let _: i64 = redis::Script::new(DELETE_IF_EQUALS)
+                        .key(&key)
+                        .arg(owner.to_string())
+                        .invoke_async(&mut conn)
+                        .await?;
+                }
+                None => {}
+            }
+
+            // Streamers serve unowned stations themselves; API nodes hand them to the
+            // streamer with the fewest stations
+            let candidate = match self.role {
+                ServerRole::Streamer => self.node_id,
+                _ => streamers
+                    .iter()
+                    .min_by_key(|s| s.stations)
+                    .map(|s| s.node_id)
+                    .ok_or_else(|| AppError::Unavailable("No streamer nodes available".to_string()))?,
+            };
+
+            let claimed: Option<String> = redis::cmd("SET")
+                .arg(&key)
+                .arg(candidate.to_string())
+                .arg("NX")
+                .arg("EX")
+                .arg(LEASE_TTL_SECS)
+                .query_async(&mut conn)
+                .await?;
+            if claimed.is_some() {
+                tracing::info!("Assigned station {} to streamer {}", station_id, candidate);
+            }
+        }
+
+        Err(AppError::Unavailable(format!(
+            "Could not assign station {} to a streamer",
+            station_id
+        )))
+    }
+
+    /// Streamers that have sent a heartbeat recently
+    pub async fn live_streamers(&self) -> Result<Vec<StreamerInfo>> {
+        let mut conn = self.redis.clone();
+        let cutoff = chrono::Utc::now().timestamp() - STREAMER_TTL_SECS as i64;
+        let _: () = conn.zrembyscore(STREAMERS_KEY, "-inf", cutoff).await?;
+
+        let ids: Vec<String> = conn.zrange(STREAMERS_KEY, 0, -1).await?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let keys: Vec<String> = ids
+            .iter()
+            .filter_map(|id| id.parse().ok())
+            .map(streamer_key)
+            .collect();
+        let infos: Vec<Option<String>> = redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+
+        Ok(infos
+            .into_iter()
+            .flatten()
+            .filter_map(|json| serde_json::from_str(&json).ok())
+            .collect())
+    }
+
+    /// Keep this streamer registered and renew the leases of the stations it is
+    /// broadcasting. Leases held by another node are left alone.
+    pub fn spawn_heartbeat(
+        self: Arc<Self>,
+        broadcasters: Arc<RwLock<HashMap<Uuid, Arc<AudioBroadcaster>>>>,
+    ) {
+        if self.role != ServerRole::Streamer {
+            return;
+        }
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+            loop {
+                interval.tick().await;
+
+                let running: Vec<Uuid> = broadcasters
+                    .read()
+                    .await
+                    .iter()
+                    .filter(|(_, broadcaster)| broadcaster.is_running())
+                    .map(|(id, _)| *id)
+                    .collect();
+
+                if let Err(e) = self.heartbeat(&running).await {
+                    tracing::warn!("Streamer heartbeat failed: {}", e);
+                }
+            }
+        });
+    }
+
+    async fn heartbeat(&self, running: &[Uuid]) -> Result<()> {
+        let Some(url) = &self.advertise_url else {
+            return Ok(());
+        };
+        let mut conn = self.redis.clone();
+
+        let info = serde_json::to_string(&StreamerInfo {
+            node_id: self.node_id,
+            url: url.clone(),
+            stations: running.len(),
+        })?;
+        let _: () = conn.set_ex(streamer_key(self.node_id), info, STREAMER_TTL_SECS).await?;
+        let _: () = conn
+            .zadd(STREAMERS_KEY, self.node_id.to_string(), chrono::Utc::now().timestamp())
+            .await?;
+
+        let node_id = self.node_id.to_string();
+        for station_id in running {
+            let key = lease_key(*station_id);
+            let owner: Option<String> = conn.get(&key).await?;
+            if owner.is_none() || owner.as_deref() == Some(node_id.as_str()) {
+                let _: () = conn.set_ex(&key, &node_id, LEASE_TTL_SECS).await?;
+            }
+        }
+        Ok(())
+    }
+}
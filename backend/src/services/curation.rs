@@ -1,57 +1,64 @@
-use crate::config::Config;
 use crate::error::{AppError, Result};
 use crate::models::{SelectionMode, Station, Track};
+use crate::services::duplicates;
+use crate::services::lastfm;
+use crate::services::llm::{LlmClient, LlmFeature, LlmRequest};
 use crate::services::navidrome::NavidromeClient;
-use rand::{seq::SliceRandom, Rng};
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use crate::services::ratings::AudienceFeedback;
+use crate::services::separation::{self, Play, PlayHistory, SeparationRules};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::SliceRandom;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
-pub struct CurationEngine {
-    navidrome_client: Arc<NavidromeClient>,
-    anthropic_api_key: Option<String>,
-    http_client: Client,
-}
+/// Claude model that turns a station description into search terms
+pub const DESCRIPTION_MODEL: &str = "claude-3-5-haiku-20241022";
 
-#[derive(Debug, Serialize)]
-struct ClaudeRequest {
-    model: String,
-    max_tokens: u32,
-    messages: Vec<ClaudeMessage>,
-}
+/// Artists looked up per search term when falling back to top songs
+const FALLBACK_ARTISTS_PER_TERM: usize = 3;
 
-#[derive(Debug, Serialize)]
-struct ClaudeMessage {
-    role: String,
-    content: String,
-}
+/// Most artists whose top songs are fetched in one fallback
+const FALLBACK_MAX_ARTISTS: usize = 10;
 
-#[derive(Debug, Deserialize)]
-struct ClaudeResponse {
-    content: Vec<ClaudeContent>,
-}
+/// Top songs fetched per artist
+const FALLBACK_TOP_SONGS: usize = 10;
 
-#[derive(Debug, Deserialize)]
-struct ClaudeContent {
-    text: String,
+/// Similar artists added per starred artist in a fallback
+const FALLBACK_SIMILAR_ARTISTS: usize = 5;
+
+pub struct CurationEngine {
+    navidrome_client: Arc<NavidromeClient>,
+    /// Claude, for turning station descriptions into search terms (ANTHROPIC_API_KEY)
+    llm: Option<Arc<LlmClient>>,
+    /// Reads Last.fm tags and similar artists (`external_metadata`) for fallbacks,
+    /// duplicate groups (`track_duplicates`), and explicit flags, artists and albums
+    /// (`library_index`)
+    db: Option<PgPool>,
 }
 
 impl CurationEngine {
-    pub fn new(navidrome_client: Arc<NavidromeClient>, config: &Config) -> Self {
+    pub fn new(navidrome_client: Arc<NavidromeClient>, llm: Option<Arc<LlmClient>>) -> Self {
         Self {
             navidrome_client,
-            anthropic_api_key: config.anthropic_api_key.clone(),
-            http_client: Client::new(),
+            llm,
+            db: None,
         }
     }
 
+    /// Use fetched artist metadata (Last.fm tags and similar artists) when searching
+    /// the library finds nothing, and keep duplicates of recent tracks off the air
+    pub fn with_external_metadata(mut self, db: PgPool) -> Self {
+        self.db = Some(db);
+        self
+    }
+
     pub fn has_ai_capabilities(&self) -> bool {
-        self.anthropic_api_key.is_some()
+        self.llm.is_some()
     }
 
     pub async fn analyze_description_and_find_tracks(&self, description: &str) -> Result<(Vec<String>, Vec<Track>)> {
-        let api_key = self.anthropic_api_key.as_ref().ok_or_else(|| {
+        let llm = self.llm.as_ref().ok_or_else(|| {
             AppError::Internal(anyhow::anyhow!("Anthropic API key not configured"))
         })?;
 
@@ -69,45 +76,9 @@ impl CurationEngine {
             description
         );
 
-        let request = ClaudeRequest {
-            model: "claude-3-5-haiku-20241022".to_string(),
-            max_tokens: 300,
-            messages: vec![ClaudeMessage {
-                role: "user".to_string(),
-                content: prompt,
-            }],
-        };
-
-        let response = self
-            .http_client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("content-type", "application/json")
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| AppError::Internal(anyhow::anyhow!("Claude API request failed: {}", e)))?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(AppError::Internal(anyhow::anyhow!(
-                "Claude API error {}: {}",
-                status, error_text
-            )));
-        }
-
-        let claude_response: ClaudeResponse = response
-            .json()
-            .await
-            .map_err(|e| AppError::Internal(anyhow::anyhow!("Failed to parse Claude response: {}", e)))?;
-
-        let text = claude_response
-            .content
-            .first()
-            .map(|c| c.text.as_str())
-            .unwrap_or("");
+        let text = llm
+            .complete(LlmRequest::new(LlmFeature::StationDescription, DESCRIPTION_MODEL, prompt).max_tokens(300))
+            .await?;
 
         // Parse comma-separated search queries
         let search_queries: Vec<String> = text
@@ -200,26 +171,12 @@ impl CurationEngine {
             }
         }
 
-        // Step 4: Last resort - get ANY tracks from library using common letters
+        // Step 4: Last resort - starred tracks and top songs of matching artists
         if all_tracks.is_empty() {
-            tracing::info!("Step 4: Last resort - searching for any tracks with common search terms");
-            let fallback_queries = vec!["a", "the", "e", "s", "t"];
-            for query in &fallback_queries {
-                match self.navidrome_client.search_tracks(query, 50).await {
-                    Ok(tracks) => {
-                        if !tracks.is_empty() {
-                            tracing::info!("Fallback found {} tracks with query: {}", tracks.len(), query);
-                            for track in tracks {
-                                if seen_ids.insert(track.id.clone()) {
-                                    all_tracks.push(track);
-                                }
-                            }
-                            break;  // Got some tracks, done
-                        }
-                    }
-                    Err(e) => {
-                        tracing::debug!("Fallback query '{}' failed: {:?}", query, e);
-                    }
+            tracing::info!("Step 4: Last resort - using starred tracks and artist top songs");
+            for track in self.fallback_tracks(&search_queries).await {
+                if seen_ids.insert(track.id.clone()) {
+                    all_tracks.push(track);
                 }
             }
         }
@@ -236,40 +193,178 @@ impl CurationEngine {
         Ok((search_queries, all_tracks))
     }
 
+    /// Pick the station's next track. `feedback` of the current audience keeps tracks
+    /// they thumbed down off the air and favors ones they thumbed up. The station's
+    /// separation rules keep tracks, artists and albums in `history` from coming around
+    /// again too soon; other versions of recently played tracks count as played.
     pub async fn select_next_track(
         &self,
         station: &Station,
-        recent_track_ids: &[String],
+        history: &PlayHistory,
+        feedback: &AudienceFeedback,
     ) -> Result<Track> {
+        let rules = SeparationRules::for_station(&station.config);
+        let recent_track_ids = &self.with_duplicates(&history.recent_track_ids(rules.tracks)).await;
+
+        if station.config.track_selection_mode == SelectionMode::AlbumSequential {
+            if let Some(track) = self.continue_album(station, history, feedback).await {
+                return Ok(track);
+            }
+        }
+
+        if station.config.track_selection_mode == SelectionMode::Journey && !station.track_ids.is_empty() {
+            if let Some(track) = self.continue_journey(station, history, feedback).await {
+                return Ok(track);
+            }
+        }
+
         // If station has curated track_ids, use those instead of genre-based selection
         if !station.track_ids.is_empty() {
             tracing::info!("Station '{}' has {} curated tracks, selecting from those", station.name, station.track_ids.len());
-            return self.select_from_curated(station, recent_track_ids).await;
+            return self.select_from_curated(station, recent_track_ids, history, feedback).await;
         }
         tracing::debug!("Station '{}' has no curated tracks, using genre-based selection", station.name);
 
         match station.config.track_selection_mode {
-            SelectionMode::Random | SelectionMode::Hybrid => {
-                self.select_random(station, recent_track_ids).await
+            // A new album starts from whichever of its tracks is picked
+            SelectionMode::Random | SelectionMode::Hybrid | SelectionMode::AlbumSequential | SelectionMode::Journey => {
+                self.select_random(station, recent_track_ids, history, feedback).await
             }
             SelectionMode::AIContextual | SelectionMode::AIEmbeddings => {
                 // Fall back to random if AI is not configured
-                if self.anthropic_api_key.is_some() {
+                if self.llm.is_some() {
                     // TODO: Implement AI selection
                     tracing::warn!("AI selection not yet implemented, falling back to random");
-                    self.select_random(station, recent_track_ids).await
+                    self.select_random(station, recent_track_ids, history, feedback).await
                 } else {
-                    self.select_random(station, recent_track_ids).await
+                    self.select_random(station, recent_track_ids, history, feedback).await
                 }
             }
         }
     }
 
-    /// Select a track from the station's curated track_ids list
+    /// Those of `ids` marked explicit, when the station doesn't allow explicit tracks.
+    /// Tracks whose status is unknown aren't included.
+    async fn explicit_among(&self, station: &Station, ids: &[&str]) -> HashSet<String> {
+        if station.config.allow_explicit || ids.is_empty() {
+            return HashSet::new();
+        }
+        let Some(db) = self.db.as_ref() else {
+            return HashSet::new();
+        };
+        let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+        match sqlx::query_scalar::<_, String>("SELECT id FROM library_index WHERE explicit AND id = ANY($1)")
+            .bind(&ids)
+            .fetch_all(db)
+            .await
+        {
+            Ok(explicit) => explicit.into_iter().collect(),
+            Err(e) => {
+                tracing::debug!("Explicit lookup failed: {:?}", e);
+                HashSet::new()
+            }
+        }
+    }
+
+    /// Artists and albums of `ids` from the library index (none without a database)
+    async fn credits(&self, ids: &[&String]) -> HashMap<String, Play> {
+        let Some(db) = self.db.as_ref() else {
+            return HashMap::new();
+        };
+        let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+        separation::credits(db, &ids).await.unwrap_or_else(|e| {
+            tracing::debug!("Track credits lookup failed: {:?}", e);
+            HashMap::new()
+        })
+    }
+
+    /// `recent_track_ids` followed by the other versions of those tracks
+    async fn with_duplicates(&self, recent_track_ids: &[String]) -> Vec<String> {
+        let mut ids = recent_track_ids.to_vec();
+        let Some(db) = self.db.as_ref() else {
+            return ids;
+        };
+        match duplicates::duplicates_of(db, recent_track_ids).await {
+            Ok(others) => ids.extend(others),
+            Err(e) => tracing::debug!("Duplicate lookup failed: {:?}", e),
+        }
+        ids
+    }
+
+    /// The track after the last one played on its album, unless the album is over, the
+    /// track is outside the station's pool or duration limits, is explicit on a station
+    /// that doesn't allow it, or the audience thumbed it down
+    async fn continue_album(
+        &self,
+        station: &Station,
+        history: &PlayHistory,
+        feedback: &AudienceFeedback,
+    ) -> Option<Track> {
+        let last = &history.last()?.track_id;
+        let track = match self.navidrome_client.next_album_track(last).await {
+            Ok(track) => track?,
+            Err(e) => {
+                tracing::warn!("Failed to find the track after {} on its album: {}", last, e);
+                return None;
+            }
+        };
+
+        let min_dur = station.config.min_track_duration as i32;
+        let max_dur = station.config.max_track_duration as i32;
+        let playable = (station.track_ids.is_empty() || station.track_ids.contains(&track.id))
+            && track.duration >= min_dur
+            && track.duration <= max_dur
+            && !feedback.is_excluded(&track.id)
+            && (station.config.allow_explicit || track.explicit() != Some(true))
+            && self.explicit_among(station, &[track.id.as_str()]).await.is_empty();
+        if !playable {
+            return None;
+        }
+        tracing::debug!("Station '{}' continues album '{}' with '{}'", station.name, track.album, track.title);
+        Some(track)
+    }
+
+    /// The curated track after the last one played from the list (the first if none
+    /// was), skipping tracks outside the duration limits, explicit ones the station
+    /// doesn't allow, or ones thumbed down by the audience
+    async fn continue_journey(
+        &self,
+        station: &Station,
+        history: &PlayHistory,
+        feedback: &AudienceFeedback,
+    ) -> Option<Track> {
+        let start = history
+            .recent_track_ids(usize::MAX)
+            .iter()
+            .find_map(|id| station.track_ids.iter().position(|t| t == id))
+            .map_or(0, |last| last + 1);
+
+        let min_dur = station.config.min_track_duration as i32;
+        let max_dur = station.config.max_track_duration as i32;
+        let len = station.track_ids.len();
+        let ids: Vec<&str> = station.track_ids.iter().map(String::as_str).collect();
+        let explicit = self.explicit_among(station, &ids).await;
+        for track_id in (0..len).map(|i| &station.track_ids[(start + i) % len]) {
+            if feedback.is_excluded(track_id) || explicit.contains(track_id) {
+                continue;
+            }
+            match self.navidrome_client.get_track(track_id).await {
+                Ok(track) if track.duration >= min_dur && track.duration <= max_dur => return Some(track),
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Failed to fetch track {}: {:?}", track_id, e),
+            }
+        }
+        None
+    }
+
+    /// Select a track from the station's curated track_ids list, keeping to the
+    /// separation rules when any candidate can
     async fn select_from_curated(
         &self,
         station: &Station,
         recent_track_ids: &[String],
+        history: &PlayHistory,
+        feedback: &AudienceFeedback,
     ) -> Result<Track> {
         let recent_set: HashSet<_> = recent_track_ids.iter().collect();
 
@@ -287,7 +382,47 @@ impl CurationEngine {
         } else {
             available_ids
         };
+        candidates = feedback.filter(candidates, |id| id.as_str());
+        let ids: Vec<&str> = candidates.iter().map(|id| id.as_str()).collect();
+        let explicit = self.explicit_among(station, &ids).await;
+        candidates.retain(|id| !explicit.contains(*id));
+
+        // Try candidates whose artist or album played too recently last
+        let rules = SeparationRules::for_station(&station.config);
+        let credits = self.credits(&candidates).await;
+        let (candidates, crowded): (Vec<&String>, Vec<&String>) = candidates
+            .into_iter()
+            .partition(|id| credits.get(*id).is_none_or(|play| history.allows(&rules, play)));
+
+        let mut relaxed = None;
+        if let Some(track) = self
+            .pick_curated(station, candidates, feedback, Some((history, &rules, &mut relaxed)))
+            .await
+        {
+            return Ok(track);
+        }
+        if let Some(track) = relaxed {
+            tracing::debug!("No curated track keeps to the separation rules, relaxing them");
+            return Ok(track);
+        }
+        if let Some(track) = self.pick_curated(station, crowded, feedback, None).await {
+            tracing::debug!("No curated track keeps to the separation rules, relaxing them");
+            return Ok(track);
+        }
 
+        Err(AppError::NotFound("No suitable curated tracks found".to_string()))
+    }
+
+    /// Fetch weighted-random `candidates` until one is within the duration limits. With
+    /// `spacing`, tracks that break the separation rules are passed over and the first
+    /// of them is kept in its `Option` for a relaxed pick.
+    async fn pick_curated(
+        &self,
+        station: &Station,
+        mut candidates: Vec<&String>,
+        feedback: &AudienceFeedback,
+        mut spacing: Option<(&PlayHistory, &SeparationRules, &mut Option<Track>)>,
+    ) -> Option<Track> {
         // Duration filters
         let min_dur = station.config.min_track_duration as i32;
         let max_dur = station.config.max_track_duration as i32;
@@ -296,8 +431,10 @@ impl CurationEngine {
         let mut tried_ids: HashSet<&String> = HashSet::new();
 
         while !candidates.is_empty() {
-            // Pick a random track ID from the remaining candidates
-            let idx = rand::thread_rng().gen_range(0..candidates.len());
+            // Pick a random track ID from the remaining candidates, favoring liked ones
+            let idx = WeightedIndex::new(candidates.iter().map(|id| feedback.weight(id)))
+                .map(|weights| weights.sample(&mut rand::thread_rng()))
+                .unwrap_or(0);
             let track_id = candidates[idx];
 
             // Skip if we've already tried this one
@@ -312,8 +449,18 @@ impl CurationEngine {
                 Ok(track) => {
                     // Check duration requirements
                     if track.duration >= min_dur && track.duration <= max_dur {
+                        if let Some((history, rules, relaxed)) = spacing.as_mut() {
+                            // Tracks the library index doesn't know are checked once fetched
+                            if !history.allows(rules, &Play::from(&track)) {
+                                if relaxed.is_none() {
+                                    **relaxed = Some(track);
+                                }
+                                candidates.remove(idx);
+                                continue;
+                            }
+                        }
                         tracing::info!("Selected curated track: {} - {}", track.artist, track.title);
-                        return Ok(track);
+                        return Some(track);
                     }
                     // Track doesn't meet duration requirements, remove from candidates
                     candidates.remove(idx);
@@ -325,21 +472,23 @@ impl CurationEngine {
             }
         }
 
-        Err(AppError::NotFound("No suitable curated tracks found".to_string()))
+        None
     }
 
     async fn select_random(
         &self,
         station: &Station,
         recent_track_ids: &[String],
+        history: &PlayHistory,
+        feedback: &AudienceFeedback,
     ) -> Result<Track> {
         let mut all_candidates = Vec::new();
 
         // Handle wildcard or multiple genres
-        if station.genres.iter().any(|g| g == "*") {
-            // Wildcard: search broadly
-            tracing::debug!("Searching Navidrome with wildcard query: a");
-            all_candidates = self.navidrome_client.search_tracks("a", 50).await?;
+        if station.genres.is_empty() || station.genres.iter().any(|g| g == "*") {
+            // Wildcard: no genre to search for
+            tracing::debug!("Wildcard station, using Navidrome fallback tracks");
+            all_candidates = self.fallback_tracks(&[]).await;
         } else if station.genres.len() > 1 {
             // Multiple genres: search each separately and combine
             let mut seen_ids = HashSet::new();
@@ -357,11 +506,16 @@ impl CurationEngine {
             }
         } else {
             // Single genre: search directly
-            let query = station.genres.first().map(|s| s.as_str()).unwrap_or("a");
+            let query = &station.genres[0];
             tracing::debug!("Searching Navidrome with single genre: {}", query);
             all_candidates = self.navidrome_client.search_tracks(query, 50).await?;
         }
 
+        if all_candidates.is_empty() {
+            tracing::debug!("Genre search found nothing, using Navidrome fallback tracks");
+            all_candidates = self.fallback_tracks(&station.genres).await;
+        }
+
         tracing::debug!("Found {} total candidates from Navidrome", all_candidates.len());
 
         // Filter out recently played tracks
@@ -373,12 +527,114 @@ impl CurationEngine {
         let max_dur = station.config.max_track_duration as i32;
         all_candidates.retain(|t| t.duration >= min_dur && t.duration <= max_dur);
 
-        // Select random track
-        all_candidates
-            .choose(&mut rand::thread_rng())
-            .cloned()
+        // Filter out explicit tracks, by their Navidrome tags or the library index
+        if !station.config.allow_explicit {
+            all_candidates.retain(|t| t.explicit() != Some(true));
+            let ids: Vec<&str> = all_candidates.iter().map(|t| t.id.as_str()).collect();
+            let explicit = self.explicit_among(station, &ids).await;
+            all_candidates.retain(|t| !explicit.contains(&t.id));
+        }
+        let all_candidates = feedback.filter(all_candidates, |t| t.id.as_str());
+
+        // Keep artists and albums apart, unless no candidate can be
+        let rules = SeparationRules::for_station(&station.config);
+        let mut pool: Vec<&Track> = all_candidates
+            .iter()
+            .filter(|t| history.allows(&rules, &Play::from(*t)))
+            .collect();
+        if pool.is_empty() && !all_candidates.is_empty() {
+            tracing::debug!("No candidate keeps to the separation rules, relaxing them");
+            pool = all_candidates.iter().collect();
+        }
+
+        // Select random track, favoring ones the audience liked
+        pool.choose_weighted(&mut rand::thread_rng(), |t| feedback.weight(&t.id))
+            .ok()
+            .map(|t| (*t).clone())
             .ok_or_else(|| {
                 crate::error::AppError::NotFound("No suitable tracks found".to_string())
             })
     }
+
+    /// Candidates when searching the library found nothing: the Navidrome user's starred
+    /// tracks plus the top songs of artists matching `terms`, either by name or by their
+    /// Last.fm tags. When no artist matches, the starred artists and artists similar to
+    /// them are used. Random songs are the final fallback for an unrated library.
+    async fn fallback_tracks(&self, terms: &[String]) -> Vec<Track> {
+        let mut tracks = self.navidrome_client.get_starred_songs().await.unwrap_or_else(|e| {
+            tracing::warn!("Failed to load starred tracks: {:?}", e);
+            Vec::new()
+        });
+
+        let mut artists = Vec::new();
+        for term in terms.iter().filter(|t| t.as_str() != "*") {
+            match self.navidrome_client.search_artists(term, FALLBACK_ARTISTS_PER_TERM).await {
+                Ok(names) => artists.extend(names),
+                Err(e) => tracing::debug!("Artist search for '{}' failed: {:?}", term, e),
+            }
+        }
+        if artists.is_empty() {
+            artists = self.tagged_artists(terms).await;
+        }
+        if artists.is_empty() {
+            artists = tracks.iter().map(|t| t.artist.clone()).collect();
+            artists.sort_unstable();
+            artists.dedup();
+            let similar = self.similar_artists(&artists).await;
+            artists.extend(similar);
+        }
+        artists.sort_unstable();
+        artists.dedup();
+        artists.shuffle(&mut rand::thread_rng());
+
+        for artist in artists.iter().take(FALLBACK_MAX_ARTISTS) {
+            match self.navidrome_client.get_top_songs(artist, FALLBACK_TOP_SONGS).await {
+                Ok(songs) => tracks.extend(songs),
+                Err(e) => tracing::debug!("Top songs for '{}' failed: {:?}", artist, e),
+            }
+        }
+
+        if tracks.is_empty() {
+            tracks = self.navidrome_client.get_random_songs(50).await.unwrap_or_else(|e| {
+                tracing::warn!("Failed to load random tracks: {:?}", e);
+                Vec::new()
+            });
+        }
+
+        let mut seen_ids = HashSet::new();
+        tracks.retain(|t| seen_ids.insert(t.id.clone()));
+        tracing::info!(
+            "Fallback found {} tracks ({} artists considered)",
+            tracks.len(),
+            artists.len().min(FALLBACK_MAX_ARTISTS)
+        );
+        tracks
+    }
+
+    /// Library artists Last.fm tags with any of `terms` (none without fetched metadata)
+    async fn tagged_artists(&self, terms: &[String]) -> Vec<String> {
+        let terms: Vec<String> = terms.iter().filter(|t| t.as_str() != "*").cloned().collect();
+        let Some(db) = self.db.as_ref().filter(|_| !terms.is_empty()) else {
+            return Vec::new();
+        };
+        lastfm::artists_tagged(db, &terms, FALLBACK_MAX_ARTISTS)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::debug!("Tagged artist lookup failed: {:?}", e);
+                Vec::new()
+            })
+    }
+
+    /// Library artists Last.fm considers similar to `artists`
+    async fn similar_artists(&self, artists: &[String]) -> Vec<String> {
+        let Some(db) = self.db.as_ref().filter(|_| !artists.is_empty()) else {
+            return Vec::new();
+        };
+        lastfm::similar_artists(db, artists, artists.len() * FALLBACK_SIMILAR_ARTISTS)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::debug!("Similar artist lookup failed: {:?}", e);
+                Vec::new()
+            })
+    }
 }
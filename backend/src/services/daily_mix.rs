@@ -0,0 +1,250 @@
+//! Personal "Daily Mix" stations
+//!
+//! Once a day every user with recent activity gets a station built from their taste:
+//! the tracks they listened to most in the last weeks, their favorites and their high
+//! ratings are used as seeds for the [`HybridCurator`], which fills the mix with tracks
+//! closest to the seeds' centroid (the user's taste vector). Each run replaces the
+//! user's previous mix in place, so the station ID stays stable.
+
+use crate::error::Result;
+use crate::models::{JobKind, Station};
+use crate::services::audio_broadcaster::AudioBroadcaster;
+use crate::services::hybrid_curator::HybridCurator;
+use crate::services::jobs::JobContext;
+use crate::services::ratings::RatingBias;
+use crate::services::JobManager;
+use chrono::{Duration as ChronoDuration, NaiveTime, Utc};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Tracks in each mix
+const MIX_SIZE: usize = 50;
+
+/// Seed tracks taken from the user's taste profile
+const SEED_COUNT: i64 = 8;
+
+/// Users need at least this many seed candidates to get a mix
+const MIN_SEEDS: usize = 3;
+
+/// Listening history considered for the taste profile
+const HISTORY_DAYS: i32 = 30;
+
+/// Tracks heard this recently are left out of the filler so the mix feels fresh
+const RECENTLY_HEARD_HOURS: i32 = 48;
+
+/// Tracks rated at or below this are never included
+const DISLIKED_RATING: f64 = 2.0;
+
+pub struct DailyMixService {
+    db: PgPool,
+    hybrid_curator: Arc<HybridCurator>,
+    station_broadcasters: Arc<RwLock<HashMap<Uuid, Arc<AudioBroadcaster>>>>,
+}
+
+impl DailyMixService {
+    pub fn new(
+        db: PgPool,
+        hybrid_curator: Arc<HybridCurator>,
+        station_broadcasters: Arc<RwLock<HashMap<Uuid, Arc<AudioBroadcaster>>>>,
+    ) -> Self {
+        Self {
+            db,
+            hybrid_curator,
+            station_broadcasters,
+        }
+    }
+
+    /// Submit a job that rebuilds every user's mix
+    pub async fn submit(self: &Arc<Self>, jobs: &JobManager) -> Result<Uuid> {
+        let service = self.clone();
+        jobs.submit(JobKind::DailyMix, serde_json::json!({}), 1, move |ctx| {
+            let service = service.clone();
+            async move { service.rebuild_all(&ctx).await }
+        })
+        .await
+    }
+
+    /// Rebuild all mixes every day at `hour` (UTC)
+    pub fn spawn_scheduler(self: Arc<Self>, jobs: Arc<JobManager>, hour: u32) {
+        tokio::spawn(async move {
+            loop {
+                let delay = until_next(hour);
+                tracing::info!("Next Daily Mix rebuild in {}m", delay.as_secs() / 60);
+                tokio::time::sleep(delay).await;
+
+                if let Err(e) = self.submit(&jobs).await {
+                    tracing::warn!("Failed to start Daily Mix rebuild: {}", e);
+                }
+            }
+        });
+    }
+
+    async fn rebuild_all(&self, ctx: &JobContext) -> Result<serde_json::Value> {
+        let users: Vec<(Uuid, String)> = sqlx::query_as(
+            r#"
+            SELECT id, username FROM users u
+            WHERE EXISTS (
+                SELECT 1 FROM listening_history h
+                WHERE h.user_id = u.id AND h.last_heard_at > NOW() - make_interval(days => $1)
+            )
+            OR EXISTS (SELECT 1 FROM user_favorite_tracks f WHERE f.user_id = u.id)
+            OR EXISTS (SELECT 1 FROM user_track_ratings r WHERE r.user_id = u.id)
+            ORDER BY username
+            "#,
+        )
+        .bind(HISTORY_DAYS)
+        .fetch_all(&self.db)
+        .await?;
+
+        let total = users.len();
+        let mut built = 0;
+        let mut skipped = 0;
+        let mut failed = 0;
+
+        for (i, (user_id, username)) in users.iter().enumerate() {
+            if ctx.is_cancelled() {
+                break;
+            }
+            ctx.progress(i as f64 / total.max(1) as f64, format!("Building mix for {}", username))
+                .await;
+
+            match self.build_for_user(*user_id, username).await {
+                Ok(Some(_)) => built += 1,
+                Ok(None) => skipped += 1,
+                Err(e) => {
+                    failed += 1;
+                    ctx.log("warn", format!("Daily Mix for {} failed: {}", username, e)).await;
+                }
+            }
+        }
+
+        ctx.progress(1.0, format!("Built {} Daily Mix station(s)", built)).await;
+        Ok(serde_json::json!({ "built": built, "skipped": skipped, "failed": failed }))
+    }
+
+    /// Build (or replace) a user's mix. Returns None if the user has too little
+    /// history to seed one.
+    pub async fn build_for_user(&self, user_id: Uuid, username: &str) -> Result<Option<Station>> {
+        let seeds = self.taste_seeds(user_id).await?;
+        if seeds.len() < MIN_SEEDS {
+            return Ok(None);
+        }
+
+        let exclude: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT track_id FROM listening_history
+            WHERE user_id = $1 AND last_heard_at > NOW() - make_interval(hours => $2)
+            UNION
+            SELECT track_id FROM user_track_ratings
+            WHERE user_id = $1 AND rating <= $3
+            UNION
+            SELECT track_id FROM user_track_feedback
+            WHERE user_id = $1 AND value < 0
+            "#,
+        )
+        .bind(user_id)
+        .bind(RECENTLY_HEARD_HOURS)
+        .bind(DISLIKED_RATING)
+        .fetch_all(&self.db)
+        .await?;
+
+        let liked = RatingBias::load(&self.db, Some(user_id)).await?.liked_ids();
+        let track_ids = self
+            .hybrid_curator
+            .curate_from_seeds(&seeds, MIX_SIZE, &exclude, &liked)
+            .await?;
+
+        let genres: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT g FROM library_index, jsonb_array_elements_text(genres) g
+            WHERE id = ANY($1)
+            GROUP BY g ORDER BY COUNT(*) DESC LIMIT 3
+            "#,
+        )
+        .bind(&track_ids)
+        .fetch_all(&self.db)
+        .await?;
+
+        let station = sqlx::query_as::<_, Station>(
+            r#"
+            INSERT INTO stations (path, name, description, genres, mood_tags, created_by, track_ids, daily_mix_user_id)
+            VALUES ($1, $2, $3, $4, '[]'::jsonb, $5, $6, $5)
+            ON CONFLICT (daily_mix_user_id) DO UPDATE
+            SET name = EXCLUDED.name,
+                description = EXCLUDED.description,
+                genres = EXCLUDED.genres,
+                track_ids = EXCLUDED.track_ids
+            RETURNING *
+            "#,
+        )
+        .bind(format!("daily-mix-{}", user_id.simple()))
+        .bind(format!("{}'s Daily Mix", username))
+        .bind(format!(
+            "Built {} from your recent listening, favorites and ratings.",
+            Utc::now().format("%B %-d")
+        ))
+        .bind(serde_json::to_value(&genres)?)
+        .bind(user_id)
+        .bind(serde_json::to_value(&track_ids)?)
+        .fetch_one(&self.db)
+        .await?;
+
+        // Drop the stream playing yesterday's mix; the next listener starts the new one
+        let broadcaster = self.station_broadcasters.write().await.remove(&station.id);
+        if let Some(broadcaster) = broadcaster {
+            broadcaster.shutdown().await;
+        }
+
+        tracing::info!("Built Daily Mix for {} ({} tracks)", username, station.track_ids.len());
+        Ok(Some(station))
+    }
+
+    /// Highest-scoring tracks in the user's taste profile. Minutes listened (decaying
+    /// over a week), favorites and ratings all add to a track's score; a little
+    /// randomness keeps consecutive mixes from starting identically.
+    async fn taste_seeds(&self, user_id: Uuid) -> Result<Vec<String>> {
+        let seeds = sqlx::query_scalar(
+            r#"
+            WITH scores AS (
+                SELECT track_id,
+                       (EXTRACT(EPOCH FROM (last_heard_at - first_heard_at)) + 10) / 60.0
+                         / (1.0 + EXTRACT(EPOCH FROM (NOW() - last_heard_at)) / 604800.0) AS score
+                FROM listening_history
+                WHERE user_id = $1 AND last_heard_at > NOW() - make_interval(days => $2)
+                UNION ALL
+                SELECT track_id, 5.0 FROM user_favorite_tracks WHERE user_id = $1
+                UNION ALL
+                SELECT track_id, (rating - 3.0) * 2.0 FROM user_track_ratings WHERE user_id = $1
+            )
+            SELECT s.track_id
+            FROM scores s
+            JOIN track_embeddings te ON te.track_id = s.track_id
+            GROUP BY s.track_id
+            HAVING SUM(s.score) > 0
+            ORDER BY SUM(s.score) * (0.75 + random() * 0.5) DESC
+            LIMIT $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(HISTORY_DAYS)
+        .bind(SEED_COUNT)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(seeds)
+    }
+}
+
+/// Time until the next occurrence of `hour`:00 UTC
+fn until_next(hour: u32) -> std::time::Duration {
+    let now = Utc::now();
+    let time = NaiveTime::from_hms_opt(hour.min(23), 0, 0).unwrap_or(NaiveTime::MIN);
+    let mut next = now.date_naive().and_time(time).and_utc();
+    if next <= now {
+        next += ChronoDuration::days(1);
+    }
+    (next - now).to_std().unwrap_or_default()
+}
@@ -0,0 +1,433 @@
+//! Duplicate track detection
+//!
+//! Finds library tracks that are versions of the same song: the same rip imported
+//! twice, remasters, compilation copies. Candidates are tracks by the same artist whose
+//! titles match after dropping release notes like "(2011 Remaster)" or "- Mono",
+//! compared by trigram similarity so small spelling differences still match. A
+//! candidate pair counts as a duplicate when the audio embeddings of both tracks are
+//! nearly identical, or, when one hasn't been embedded, when the titles are the same
+//! and so is the length. Live versions, remixes and the like are kept apart.
+//!
+//! Each scan replaces the groups in `track_duplicates`; station curation treats the
+//! other versions of a recently played track as played.
+
+use crate::error::Result;
+use crate::services::jobs::JobContext;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::collections::{HashMap, HashSet};
+
+/// Trigram similarity of normalized titles above which two tracks are candidates
+const TITLE_SIMILARITY: f64 = 0.7;
+
+/// Cosine similarity of audio embeddings above which candidates are the same recording
+const EMBEDDING_SIMILARITY: f32 = 0.95;
+
+/// Length difference allowed between candidates without embeddings
+const MAX_DURATION_DIFF_SECS: i32 = 3;
+
+/// Words marking a bracketed or dashed part of a title as describing the release
+/// rather than the song
+const RELEASE_WORDS: &[&str] = &[
+    "remaster", "remastered", "remasters", "mono", "stereo", "deluxe", "expanded", "anniversary", "bonus",
+    "single", "album", "explicit", "clean", "version", "digital", "reissue", "edition", "edit",
+];
+
+/// Words marking a different recording of the song, which isn't a duplicate
+const RECORDING_WORDS: &[&str] = &[
+    "live", "remix", "mix", "acoustic", "demo", "instrumental", "karaoke", "cover", "unplugged", "session",
+];
+
+#[derive(Debug, Default, Serialize)]
+pub struct DuplicateScanSummary {
+    pub tracks_compared: usize,
+    /// Pairs whose artist and title matched
+    pub candidate_pairs: usize,
+    pub groups: usize,
+    /// Tracks in a group other than its preferred version
+    pub duplicate_tracks: usize,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct DuplicateTrack {
+    pub id: String,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub year: Option<i32>,
+    pub duration: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    /// ID of the preferred version, which comes first in `tracks`
+    pub group_id: String,
+    pub tracks: Vec<DuplicateTrack>,
+}
+
+/// `GET /library/duplicates`
+#[derive(Debug, Serialize)]
+pub struct DuplicateReport {
+    pub groups: Vec<DuplicateGroup>,
+    /// When the groups were found; None before the first scan
+    pub scanned_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, FromRow)]
+struct DuplicateRow {
+    group_id: String,
+    detected_at: DateTime<Utc>,
+    #[sqlx(flatten)]
+    track: DuplicateTrack,
+}
+
+#[derive(Debug, FromRow)]
+struct LibraryTrack {
+    id: String,
+    title: String,
+    artist: String,
+    duration: i32,
+    play_count: i32,
+    year: Option<i32>,
+}
+
+/// Find duplicate groups across the library and replace the stored ones
+pub async fn scan(db: &PgPool, ctx: &JobContext) -> Result<DuplicateScanSummary> {
+    ctx.progress(0.0, "Loading library").await;
+    let tracks: Vec<LibraryTrack> =
+        sqlx::query_as("SELECT id, title, artist, duration, play_count, year FROM library_index")
+            .fetch_all(db)
+            .await?;
+
+    let mut by_artist: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, track) in tracks.iter().enumerate() {
+        by_artist.entry(normalize_artist(&track.artist)).or_default().push(i);
+    }
+    let titles: Vec<String> = tracks.iter().map(|t| normalize_title(&t.title)).collect();
+
+    ctx.progress(0.2, "Matching titles").await;
+    let mut candidates = Vec::new();
+    for indices in by_artist.values().filter(|indices| indices.len() > 1) {
+        for (n, &a) in indices.iter().enumerate() {
+            for &b in &indices[n + 1..] {
+                let similarity = trigram_similarity(&titles[a], &titles[b]);
+                if similarity >= TITLE_SIMILARITY {
+                    candidates.push((a, b, titles[a] == titles[b]));
+                }
+            }
+        }
+    }
+    if ctx.is_cancelled() {
+        ctx.log("info", "Cancelled").await;
+        return Ok(DuplicateScanSummary::default());
+    }
+
+    ctx.progress(0.5, format!("Comparing {} candidate pairs", candidates.len())).await;
+    let candidate_ids: Vec<&str> = candidates
+        .iter()
+        .flat_map(|&(a, b, _)| [tracks[a].id.as_str(), tracks[b].id.as_str()])
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let embeddings: HashMap<String, Vec<f32>> = sqlx::query_as::<_, (String, String)>(
+        "SELECT track_id, embedding::text FROM track_embeddings WHERE track_id = ANY($1)",
+    )
+    .bind(&candidate_ids)
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|(id, text)| (id, parse_vector(&text)))
+    .collect();
+
+    let mut groups = UnionFind::new(tracks.len());
+    for &(a, b, same_title) in &candidates {
+        let duplicate = match (embeddings.get(&tracks[a].id), embeddings.get(&tracks[b].id)) {
+            (Some(x), Some(y)) => cosine_similarity(x, y) >= EMBEDDING_SIMILARITY,
+            _ => same_title && (tracks[a].duration - tracks[b].duration).abs() <= MAX_DURATION_DIFF_SECS,
+        };
+        if duplicate {
+            groups.union(a, b);
+        }
+    }
+
+    let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+    for &(a, b, _) in &candidates {
+        for i in [a, b] {
+            members.entry(groups.find(i)).or_default().push(i);
+        }
+    }
+    let mut rows: Vec<(String, String)> = Vec::new();
+    let mut summary = DuplicateScanSummary {
+        tracks_compared: tracks.len(),
+        candidate_pairs: candidates.len(),
+        ..Default::default()
+    };
+    for mut group in members.into_values() {
+        group.sort_unstable();
+        group.dedup();
+        if group.len() < 2 {
+            continue;
+        }
+        let preferred = *group
+            .iter()
+            .min_by_key(|&&i| preference_key(&tracks[i], embeddings.contains_key(&tracks[i].id)))
+            .expect("groups have members");
+        summary.groups += 1;
+        summary.duplicate_tracks += group.len() - 1;
+        rows.extend(group.iter().map(|&i| (tracks[i].id.clone(), tracks[preferred].id.clone())));
+    }
+
+    ctx.progress(0.9, format!("Storing {} groups", summary.groups)).await;
+    let (track_ids, group_ids): (Vec<String>, Vec<String>) = rows.into_iter().unzip();
+    let mut tx = db.begin().await?;
+    sqlx::query("DELETE FROM track_duplicates").execute(&mut *tx).await?;
+    sqlx::query("INSERT INTO track_duplicates (track_id, group_id) SELECT * FROM UNNEST($1::text[], $2::text[])")
+        .bind(&track_ids)
+        .bind(&group_ids)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    tracing::info!(
+        "Duplicate scan found {} groups ({} duplicate tracks)",
+        summary.groups,
+        summary.duplicate_tracks
+    );
+    Ok(summary)
+}
+
+/// The stored duplicate groups, largest first
+pub async fn report(db: &PgPool) -> Result<DuplicateReport> {
+    let rows: Vec<DuplicateRow> = sqlx::query_as(
+        r#"
+        SELECT d.group_id, d.detected_at, li.id, li.title, li.artist, li.album, li.year, li.duration
+        FROM track_duplicates d
+        JOIN library_index li ON li.id = d.track_id
+        ORDER BY d.group_id, li.id = d.group_id DESC, li.id
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let scanned_at = rows.iter().map(|row| row.detected_at).max();
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    for row in rows {
+        match groups.last_mut() {
+            Some(group) if group.group_id == row.group_id => group.tracks.push(row.track),
+            _ => groups.push(DuplicateGroup {
+                group_id: row.group_id,
+                tracks: vec![row.track],
+            }),
+        }
+    }
+    // Groups whose other versions were removed from the library
+    groups.retain(|group| group.tracks.len() > 1);
+    groups.sort_by_key(|group| std::cmp::Reverse(group.tracks.len()));
+
+    Ok(DuplicateReport { groups, scanned_at })
+}
+
+/// The other versions of `track_ids`
+pub async fn duplicates_of(db: &PgPool, track_ids: &[String]) -> Result<Vec<String>> {
+    if track_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let ids = sqlx::query_scalar(
+        r#"
+        SELECT DISTINCT other.track_id
+        FROM track_duplicates d
+        JOIN track_duplicates other ON other.group_id = d.group_id AND other.track_id <> d.track_id
+        WHERE d.track_id = ANY($1)
+        "#,
+    )
+    .bind(track_ids)
+    .fetch_all(db)
+    .await?;
+    Ok(ids)
+}
+
+/// Orders a group's versions, preferred first: the most played, then one with an
+/// embedding, then the earliest release
+fn preference_key(track: &LibraryTrack, embedded: bool) -> (std::cmp::Reverse<i32>, bool, i32, &str) {
+    (
+        std::cmp::Reverse(track.play_count),
+        !embedded,
+        track.year.unwrap_or(i32::MAX),
+        &track.id,
+    )
+}
+
+/// Lowercased words of a title without release notes: bracketed or dashed parts like
+/// "(2011 Remaster)", "[Mono]", "- Single Version" or "(feat. X)"
+fn normalize_title(title: &str) -> String {
+    let lower = title.to_lowercase();
+    let mut kept = String::with_capacity(lower.len());
+    let mut rest = lower.as_str();
+    while let Some(open) = rest.find(['(', '[']) {
+        let close_char = if rest[open..].starts_with('(') { ')' } else { ']' };
+        let Some(close) = rest[open..].find(close_char).map(|i| open + i) else {
+            break;
+        };
+        kept.push_str(&rest[..open]);
+        let inner = &rest[open + 1..close];
+        if !is_release_note(inner) {
+            kept.push(' ');
+            kept.push_str(inner);
+        }
+        kept.push(' ');
+        rest = &rest[close + 1..];
+    }
+    kept.push_str(rest);
+
+    let mut parts = kept.split(" - ");
+    let mut title = parts.next().unwrap_or_default().to_string();
+    for part in parts.filter(|part| !is_release_note(part)) {
+        title.push(' ');
+        title.push_str(part);
+    }
+    words(&title).join(" ")
+}
+
+/// Lowercased words of the main artist, without featured artists or a leading "The"
+fn normalize_artist(artist: &str) -> String {
+    let lower = artist.to_lowercase();
+    let main = [" feat.", " feat ", " ft.", " featuring "]
+        .iter()
+        .filter_map(|marker| lower.find(marker))
+        .min()
+        .map_or(lower.as_str(), |end| &lower[..end]);
+    let words = words(main);
+    match words.split_first() {
+        Some((&"the", rest)) if !rest.is_empty() => rest.join(" "),
+        _ => words.join(" "),
+    }
+}
+
+fn is_release_note(text: &str) -> bool {
+    let words = words(text);
+    if words.is_empty() || words.iter().any(|w| RECORDING_WORDS.contains(w)) {
+        return false;
+    }
+    matches!(words[0], "feat" | "ft" | "featuring")
+        || words.iter().any(|w| RELEASE_WORDS.contains(w))
+        || words.iter().all(|w| w.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn words(text: &str) -> Vec<&str> {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect()
+}
+
+/// Shared trigrams of two strings over all their trigrams, with words padded like
+/// pg_trgm does
+fn trigram_similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let trigrams = |s: &str| -> HashSet<[char; 3]> {
+        s.split(' ')
+            .filter(|w| !w.is_empty())
+            .flat_map(|word| {
+                let padded: Vec<char> = "  ".chars().chain(word.chars()).chain(" ".chars()).collect();
+                padded.windows(3).map(|w| [w[0], w[1], w[2]]).collect::<Vec<_>>()
+            })
+            .collect()
+    };
+    let (a, b) = (trigrams(a), trigrams(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+/// Parse pgvector's text format, "[0.1,0.2,...]"
+fn parse_vector(text: &str) -> Vec<f32> {
+    text.trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .filter_map(|s| s.trim().parse::<f32>().ok())
+        .collect()
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|y| y * y).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(n: usize) -> Self {
+        Self { parent: (0..n).collect() }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut i = i;
+        while self.parent[i] != root {
+            let next = self.parent[i];
+            self.parent[i] = root;
+            i = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[b] = a;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_notes_are_dropped_from_titles() {
+        assert_eq!(normalize_title("Heroes (2017 Remaster)"), "heroes");
+        assert_eq!(normalize_title("Heroes - Single Version"), "heroes");
+        assert_eq!(normalize_title("Heroes [Mono] (feat. Someone)"), "heroes");
+        assert_eq!(normalize_title("Wish You Were Here (2011)"), "wish you were here");
+        // Different recordings stay apart
+        assert_eq!(normalize_title("Heroes (Live)"), "heroes live");
+        assert_eq!(normalize_title("Heroes - Acoustic Version"), "heroes acoustic version");
+        assert_eq!(normalize_title("Song (Part 2)"), "song part 2");
+    }
+
+    #[test]
+    fn test_featured_artists_and_articles_are_dropped() {
+        assert_eq!(normalize_artist("The Beatles"), "beatles");
+        assert_eq!(normalize_artist("Daft Punk feat. Pharrell Williams"), "daft punk");
+        assert_eq!(normalize_artist("The"), "the");
+    }
+
+    #[test]
+    fn test_trigram_similarity_tolerates_small_differences() {
+        assert_eq!(trigram_similarity("heroes", "heroes"), 1.0);
+        assert!(trigram_similarity("dont stop me now", "don t stop me now") >= TITLE_SIMILARITY);
+        assert!(trigram_similarity("heroes", "heroes live") < TITLE_SIMILARITY);
+        assert!(trigram_similarity("heroes", "zero") < TITLE_SIMILARITY);
+    }
+
+    #[test]
+    fn test_union_find_merges_transitively() {
+        let mut groups = UnionFind::new(4);
+        groups.union(0, 1);
+        groups.union(2, 1);
+        assert_eq!(groups.find(0), groups.find(2));
+        assert_ne!(groups.find(0), groups.find(3));
+    }
+}
@@ -0,0 +1,704 @@
+//! Resumable embedding backfill
+//!
+//! Walks `library_index` in id order and embeds every track that has neither an
+//! embedding nor an unresolved failure. After each batch the position and counters are
+//! saved in `embedding_processing_status`, so a backfill interrupted by a restart
+//! continues where it stopped instead of starting over. Pause, resume and stop go
+//! through the shared [`EmbeddingControlState`], and progress is broadcast as
+//! [`EmbeddingProgress`] events for the SSE endpoints.
+//!
+//! A model migration re-encodes the tracks whose embedding comes from another model
+//! version into a staging table and swaps them all in at the end, so similarity search
+//! keeps working on the old vectors meanwhile. Only tracks embedded for the first time
+//! since the new version was configured are from the new model before the swap.
+
+use crate::error::{AppError, Result};
+use crate::models::{EmbeddingProgress, JobKind};
+use crate::services::audio_cache::{self, AudioCache};
+use crate::services::audio_encoder::AudioEncoder;
+use crate::services::jobs::{JobContext, JobManager};
+use crate::services::NavidromeClient;
+use futures::stream::{self, StreamExt};
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, RwLock};
+use uuid::Uuid;
+
+/// Tracks embedded between two saves of the backfill position
+const BATCH_SIZE: i64 = 32;
+
+/// How often a paused backfill checks whether it was resumed
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// State for controlling embedding indexing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbeddingControlState {
+    Idle,
+    Running,
+    Paused,
+    Stopping,
+}
+
+impl Default for EmbeddingControlState {
+    fn default() -> Self {
+        Self::Idle
+    }
+}
+
+/// Id, path, title and artist of a track left to embed
+type BackfillTrack = (String, String, String, String);
+
+/// Backfill progress as saved in `embedding_processing_status`
+#[derive(Debug, sqlx::FromRow)]
+struct SavedBackfill {
+    is_processing: bool,
+    backfill_paused: bool,
+    /// Highest track id of the last completed batch
+    backfill_cursor: Option<String>,
+    backfill_success: i32,
+    backfill_errors: i32,
+}
+
+/// Counters shared by the tracks of a batch
+struct Counters {
+    success: AtomicUsize,
+    errors: AtomicUsize,
+    total: usize,
+    /// Names of the tracks being embedded right now
+    in_progress: tokio::sync::Mutex<Vec<String>>,
+}
+
+impl Counters {
+    fn completed(&self) -> usize {
+        self.success.load(Ordering::Relaxed) + self.errors.load(Ordering::Relaxed)
+    }
+}
+
+pub struct EmbeddingWorker {
+    db: PgPool,
+    encoder: Arc<AudioEncoder>,
+    navidrome: Arc<NavidromeClient>,
+    library_path: Option<String>,
+    audio_cache: Option<Arc<AudioCache>>,
+    control: Arc<RwLock<EmbeddingControlState>>,
+    progress: broadcast::Sender<EmbeddingProgress>,
+    /// Latest progress of the running backfill, replayed to clients that connect mid-run
+    latest: Mutex<Option<EmbeddingProgress>>,
+}
+
+impl EmbeddingWorker {
+    pub fn new(
+        db: PgPool,
+        encoder: Arc<AudioEncoder>,
+        navidrome: Arc<NavidromeClient>,
+        library_path: Option<String>,
+        audio_cache: Option<Arc<AudioCache>>,
+        control: Arc<RwLock<EmbeddingControlState>>,
+    ) -> Self {
+        let (progress, _) = broadcast::channel(256);
+        Self {
+            db,
+            encoder,
+            navidrome,
+            library_path,
+            audio_cache,
+            control,
+            progress,
+            latest: Mutex::new(None),
+        }
+    }
+
+    /// Progress events, preceded by the latest one if a backfill is running
+    pub fn subscribe(&self) -> (Option<EmbeddingProgress>, broadcast::Receiver<EmbeddingProgress>) {
+        let rx = self.progress.subscribe();
+        (self.latest.lock().unwrap().clone(), rx)
+    }
+
+    /// Number of tracks a backfill would embed: tracks with audio, no embedding and no
+    /// unresolved failure
+    pub async fn pending(&self) -> Result<i64> {
+        Ok(sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) FROM library_index li
+            WHERE li.path IS NOT NULL
+            AND NOT EXISTS (SELECT 1 FROM track_embeddings te WHERE te.track_id = li.id)
+            AND NOT EXISTS (
+                SELECT 1 FROM embedding_failures ef WHERE ef.track_id = li.id AND ef.resolved = false
+            )
+            "#,
+        )
+        .fetch_one(&self.db)
+        .await?)
+    }
+
+    /// Start a backfill from the beginning of the library.
+    /// Returns `Conflict` if indexing is already running or paused.
+    pub async fn start(self: &Arc<Self>, jobs: &JobManager) -> Result<Uuid> {
+        self.claim(EmbeddingControlState::Running).await?;
+
+        let result = async {
+            sqlx::query(
+                r#"
+                UPDATE embedding_processing_status
+                SET is_processing = true, backfill_paused = false, backfill_cursor = NULL,
+                    backfill_success = 0, backfill_errors = 0, last_error = NULL,
+                    last_processing_started = NOW(), updated_at = NOW()
+                WHERE id = 1
+                "#,
+            )
+            .execute(&self.db)
+            .await?;
+            self.submit(jobs).await
+        }
+        .await;
+
+        if result.is_err() {
+            *self.control.write().await = EmbeddingControlState::Idle;
+        }
+        result
+    }
+
+    /// Continue a backfill that was running (or paused) when the server stopped.
+    /// Should be called once at startup, after interrupted jobs were recovered.
+    pub async fn resume_interrupted(self: &Arc<Self>, jobs: &JobManager) -> Result<Option<Uuid>> {
+        let saved = self.saved().await?;
+        if !saved.is_processing {
+            return Ok(None);
+        }
+
+        let state = if saved.backfill_paused {
+            EmbeddingControlState::Paused
+        } else {
+            EmbeddingControlState::Running
+        };
+        self.claim(state).await?;
+        tracing::info!(
+            "Resuming embedding backfill after {} ({} embedded, {} failed so far)",
+            saved.backfill_cursor.as_deref().unwrap_or("the start"),
+            saved.backfill_success,
+            saved.backfill_errors
+        );
+
+        match self.submit(jobs).await {
+            Ok(id) => Ok(Some(id)),
+            Err(e) => {
+                *self.control.write().await = EmbeddingControlState::Idle;
+                Err(e)
+            }
+        }
+    }
+
+    /// Re-encode every track whose embedding is from another model version than the
+    /// configured one, then swap the new embeddings in. Runs under the same control as
+    /// a backfill, so pause and stop apply; a stopped migration keeps what it staged and
+    /// continues from there when started again. Returns `Conflict` if indexing is
+    /// already running or paused.
+    pub async fn start_migration(self: &Arc<Self>, jobs: &JobManager) -> Result<Uuid> {
+        self.claim(EmbeddingControlState::Running).await?;
+
+        let worker = Arc::clone(self);
+        let params = serde_json::json!({ "model_version": self.encoder.model_version() });
+        let submitted = jobs
+            .submit(JobKind::EmbeddingMigration, params, 1, move |ctx| {
+                let worker = worker.clone();
+                async move {
+                    let result = worker.migrate(&ctx).await;
+                    *worker.control.write().await = EmbeddingControlState::Idle;
+                    result
+                }
+            })
+            .await;
+
+        if submitted.is_err() {
+            *self.control.write().await = EmbeddingControlState::Idle;
+        }
+        submitted
+    }
+
+    async fn migrate(&self, ctx: &JobContext) -> Result<serde_json::Value> {
+        let version = self.encoder.model_version().to_string();
+        let remaining: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) FROM track_embeddings te
+            WHERE te.model_version <> $1
+            AND NOT EXISTS (
+                SELECT 1 FROM track_embeddings_staging s WHERE s.track_id = te.track_id AND s.model_version = $1
+            )
+            "#,
+        )
+        .bind(&version)
+        .fetch_one(&self.db)
+        .await?;
+        tracing::info!("Migrating embeddings to {}: {} tracks to re-encode", version, remaining);
+
+        let batch_size = self.encoder.config().batch_size.max(1);
+        let mut cursor: Option<String> = None;
+        let (mut encoded, mut failed) = (0usize, 0usize);
+        loop {
+            if !self.wait_while_paused(ctx).await {
+                tracing::info!("Embedding migration stopped: {} re-encoded, {} failed", encoded, failed);
+                return Ok(serde_json::json!({
+                    "model_version": version,
+                    "encoded": encoded,
+                    "failed": failed,
+                    "stopped_early": true
+                }));
+            }
+
+            let batch: Vec<(String, String)> = sqlx::query_as(
+                r#"
+                SELECT te.track_id, li.path
+                FROM track_embeddings te
+                JOIN library_index li ON li.id = te.track_id
+                WHERE te.model_version <> $1
+                AND li.path IS NOT NULL
+                AND ($2::varchar IS NULL OR te.track_id > $2)
+                AND NOT EXISTS (
+                    SELECT 1 FROM track_embeddings_staging s WHERE s.track_id = te.track_id AND s.model_version = $1
+                )
+                ORDER BY te.track_id
+                LIMIT $3
+                "#,
+            )
+            .bind(&version)
+            .bind(&cursor)
+            .bind(batch_size as i64)
+            .fetch_all(&self.db)
+            .await?;
+            let Some((last_id, _)) = batch.last().cloned() else {
+                break;
+            };
+
+            let sources = futures::future::join_all(batch.iter().map(|(track_id, path)| {
+                audio_cache::track_file(
+                    self.library_path.as_deref(),
+                    self.audio_cache.as_deref(),
+                    &self.navidrome,
+                    track_id,
+                    path,
+                )
+            }))
+            .await;
+            let mut fetched = Vec::new();
+            for ((track_id, _), source) in batch.into_iter().zip(sources) {
+                match source {
+                    Ok(full_path) => fetched.push((track_id, full_path)),
+                    Err(e) => {
+                        tracing::warn!("Can't re-encode track {}: {}", track_id, e);
+                        failed += 1;
+                    }
+                }
+            }
+            if !fetched.is_empty() {
+                for outcome in self.encoder.stage_batch(fetched).await? {
+                    match outcome {
+                        Ok(()) => encoded += 1,
+                        Err(_) => failed += 1,
+                    }
+                }
+            }
+
+            cursor = Some(last_id);
+            ctx.progress(
+                (encoded + failed) as f64 / remaining.max(1) as f64,
+                format!("{} re-encoded, {} failed", encoded, failed),
+            )
+            .await;
+        }
+
+        // Old vectors served similarity search until now
+        let replaced = self.encoder.finish_migration().await?;
+        Ok(serde_json::json!({
+            "model_version": version,
+            "encoded": encoded,
+            "failed": failed,
+            "replaced": replaced,
+            "stopped_early": false
+        }))
+    }
+
+    /// Move the control from idle to `state`
+    async fn claim(&self, state: EmbeddingControlState) -> Result<()> {
+        let mut control = self.control.write().await;
+        if *control != EmbeddingControlState::Idle {
+            return Err(AppError::Conflict("Embedding indexing is already running".to_string()));
+        }
+        *control = state;
+        Ok(())
+    }
+
+    async fn submit(self: &Arc<Self>, jobs: &JobManager) -> Result<Uuid> {
+        let worker = Arc::clone(self);
+        jobs.submit(JobKind::EmbeddingIndex, serde_json::json!({ "backfill": true }), 1, move |ctx| {
+            let worker = worker.clone();
+            async move { worker.run(ctx).await }
+        })
+        .await
+    }
+
+    async fn run(&self, ctx: JobContext) -> Result<serde_json::Value> {
+        let result = self.backfill(&ctx).await;
+
+        *self.control.write().await = EmbeddingControlState::Idle;
+        *self.latest.lock().unwrap() = None;
+
+        if let Err(e) = &result {
+            // The saved position is kept, so the next start of the server resumes
+            let _ = sqlx::query(
+                "UPDATE embedding_processing_status SET last_error = $1, last_error_at = NOW() WHERE id = 1",
+            )
+            .bind(e.to_string())
+            .execute(&self.db)
+            .await;
+            let _ = self.progress.send(EmbeddingProgress::Error {
+                message: e.to_string(),
+            });
+        }
+        result
+    }
+
+    async fn backfill(&self, ctx: &JobContext) -> Result<serde_json::Value> {
+        let start_time = Instant::now();
+        let saved = self.saved().await?;
+        let mut cursor = saved.backfill_cursor;
+
+        let remaining: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) FROM library_index li
+            WHERE li.path IS NOT NULL
+            AND ($1::varchar IS NULL OR li.id > $1)
+            AND NOT EXISTS (SELECT 1 FROM track_embeddings te WHERE te.track_id = li.id)
+            AND NOT EXISTS (
+                SELECT 1 FROM embedding_failures ef WHERE ef.track_id = li.id AND ef.resolved = false
+            )
+            "#,
+        )
+        .bind(&cursor)
+        .fetch_one(&self.db)
+        .await?;
+
+        let counters = Counters {
+            success: AtomicUsize::new(saved.backfill_success.max(0) as usize),
+            errors: AtomicUsize::new(saved.backfill_errors.max(0) as usize),
+            total: saved.backfill_success.max(0) as usize
+                + saved.backfill_errors.max(0) as usize
+                + remaining as usize,
+            in_progress: tokio::sync::Mutex::new(Vec::new()),
+        };
+
+        if remaining == 0 {
+            self.finish().await?;
+            let _ = self.progress.send(EmbeddingProgress::Completed {
+                success_count: counters.success.load(Ordering::Relaxed),
+                error_count: counters.errors.load(Ordering::Relaxed),
+                total_time_secs: 0.0,
+                message: "No tracks to index - all tracks already have embeddings".to_string(),
+            });
+            return Ok(serde_json::json!({ "success_count": 0, "error_count": 0 }));
+        }
+
+        // Determine parallelism based on available cores
+        let concurrency = std::thread::available_parallelism()
+            .map(|p| p.get())
+            .unwrap_or(4)
+            .min(8); // Cap at 8 for resource management
+
+        // Tracks are embedded in groups of one inference run each. At least two groups
+        // run at once, so one group's files are fetched while another is embedded.
+        let batch_size = self.encoder.config().batch_size.max(1);
+        let group_concurrency = (concurrency / batch_size).max(2);
+
+        let message = match &cursor {
+            Some(_) => format!(
+                "Resuming embedding indexing: {} of {} tracks left ({} parallel)",
+                remaining, counters.total, concurrency
+            ),
+            None => format!(
+                "Starting embedding indexing for {} tracks ({} parallel)",
+                counters.total, concurrency
+            ),
+        };
+        self.emit(EmbeddingProgress::Started {
+            message,
+            total_tracks: counters.total,
+        });
+
+        let mut stopped = false;
+        while !stopped {
+            if !self.wait_while_paused(ctx).await {
+                stopped = true;
+                break;
+            }
+
+            let batch: Vec<BackfillTrack> = sqlx::query_as(
+                r#"
+                SELECT li.id, li.path, li.title, li.artist
+                FROM library_index li
+                WHERE li.path IS NOT NULL
+                AND ($1::varchar IS NULL OR li.id > $1)
+                AND NOT EXISTS (SELECT 1 FROM track_embeddings te WHERE te.track_id = li.id)
+                AND NOT EXISTS (
+                    SELECT 1 FROM embedding_failures ef WHERE ef.track_id = li.id AND ef.resolved = false
+                )
+                ORDER BY li.id
+                LIMIT $2
+                "#,
+            )
+            .bind(&cursor)
+            .bind(BATCH_SIZE)
+            .fetch_all(&self.db)
+            .await?;
+
+            let Some(last_id) = batch.last().map(|(id, ..)| id.clone()) else {
+                break;
+            };
+
+            let interrupted = AtomicBool::new(false);
+            let groups: Vec<Vec<BackfillTrack>> = batch.chunks(batch_size).map(<[_]>::to_vec).collect();
+            stream::iter(groups)
+                .for_each_concurrent(group_concurrency, |group| {
+                    let counters = &counters;
+                    let interrupted = &interrupted;
+                    async move {
+                        // Tracks not started before a stop stay pending
+                        if interrupted.load(Ordering::Relaxed) || !self.wait_while_paused(ctx).await {
+                            interrupted.store(true, Ordering::Relaxed);
+                            return;
+                        }
+                        self.embed_group(ctx, counters, group).await;
+                    }
+                })
+                .await;
+
+            if interrupted.load(Ordering::Relaxed) {
+                stopped = true;
+            } else {
+                cursor = Some(last_id);
+                self.save(&cursor, &counters).await?;
+            }
+        }
+
+        let success_count = counters.success.load(Ordering::Relaxed);
+        let error_count = counters.errors.load(Ordering::Relaxed);
+        let total_time_secs = start_time.elapsed().as_secs_f64();
+        let message = if stopped {
+            format!(
+                "Embedding indexing stopped: {} success, {} errors in {:.1}s (stopped early)",
+                success_count, error_count, total_time_secs
+            )
+        } else {
+            format!(
+                "Embedding indexing complete: {} success, {} errors in {:.1}s",
+                success_count, error_count, total_time_secs
+            )
+        };
+        tracing::info!("{}", message);
+
+        // A stopped backfill is not resumed on restart; starting again skips embedded tracks
+        self.finish().await?;
+        let _ = self.progress.send(EmbeddingProgress::Completed {
+            success_count,
+            error_count,
+            total_time_secs,
+            message,
+        });
+
+        Ok(serde_json::json!({
+            "success_count": success_count,
+            "error_count": error_count,
+            "stopped_early": stopped
+        }))
+    }
+
+    /// Embed a group of tracks in one inference run and report each of them
+    async fn embed_group(&self, ctx: &JobContext, counters: &Counters, group: Vec<BackfillTrack>) {
+        let names: Vec<String> = group
+            .iter()
+            .map(|(_, _, title, artist)| format!("{} - {}", artist, title))
+            .collect();
+        {
+            let mut in_progress = counters.in_progress.lock().await;
+            in_progress.extend(names.iter().cloned());
+            self.emit_processing(counters, &in_progress);
+        }
+
+        let group_start = Instant::now();
+        let sources = futures::future::join_all(group.iter().map(|(track_id, path, ..)| {
+            audio_cache::track_file(
+                self.library_path.as_deref(),
+                self.audio_cache.as_deref(),
+                &self.navidrome,
+                track_id,
+                path,
+            )
+        }))
+        .await;
+
+        // Tracks whose file couldn't be fetched fail without reaching the encoder
+        let mut results: Vec<std::result::Result<(), String>> = Vec::with_capacity(group.len());
+        let mut fetched = Vec::new();
+        for ((track_id, ..), source) in group.iter().zip(sources) {
+            match source {
+                Ok(full_path) => {
+                    fetched.push((track_id.clone(), full_path));
+                    results.push(Ok(()));
+                }
+                Err(e) => results.push(Err(e)),
+            }
+        }
+
+        if !fetched.is_empty() {
+            let count = fetched.len();
+            let encoded: Vec<std::result::Result<(), String>> = match self.encoder.process_batch(fetched).await {
+                Ok(outcomes) => outcomes.into_iter().map(|r| r.map_err(|e| e.to_string())).collect(),
+                Err(e) => vec![Err(e.to_string()); count],
+            };
+            let mut encoded = encoded.into_iter();
+            for result in results.iter_mut().filter(|r| r.is_ok()) {
+                *result = encoded.next().unwrap_or(Ok(()));
+            }
+        }
+
+        let processing_time_ms = (group_start.elapsed() / group.len().max(1) as u32).as_millis() as u64;
+        let mut in_progress = counters.in_progress.lock().await;
+        for (((track_id, ..), name), result) in group.iter().zip(&names).zip(results) {
+            in_progress.retain(|n| n != name);
+            match result {
+                Ok(()) => {
+                    counters.success.fetch_add(1, Ordering::Relaxed);
+                    let _ = self.progress.send(EmbeddingProgress::TrackComplete {
+                        track_id: track_id.clone(),
+                        track_name: name.clone(),
+                        processing_time_ms,
+                        current: counters.completed(),
+                        total: counters.total,
+                    });
+                }
+                Err(error) => {
+                    counters.errors.fetch_add(1, Ordering::Relaxed);
+                    let _ = self.progress.send(EmbeddingProgress::TrackError {
+                        track_id: track_id.clone(),
+                        track_name: name.clone(),
+                        error,
+                        current: counters.completed(),
+                        total: counters.total,
+                    });
+                }
+            }
+        }
+        if !in_progress.is_empty() {
+            self.emit_processing(counters, &in_progress);
+        }
+        drop(in_progress);
+
+        ctx.progress(
+            counters.completed() as f64 / counters.total.max(1) as f64,
+            format!(
+                "{} success, {} errors",
+                counters.success.load(Ordering::Relaxed),
+                counters.errors.load(Ordering::Relaxed)
+            ),
+        )
+        .await;
+    }
+
+    /// Wait while indexing is paused. Returns false once it is stopped or the job cancelled.
+    async fn wait_while_paused(&self, ctx: &JobContext) -> bool {
+        let mut paused = false;
+        loop {
+            if ctx.is_cancelled() {
+                return false;
+            }
+            let state = *self.control.read().await;
+            match state {
+                EmbeddingControlState::Running => {
+                    if paused {
+                        self.save_paused(false).await;
+                    }
+                    return true;
+                }
+                EmbeddingControlState::Paused => {
+                    // Remembered, so a restart comes back paused
+                    if !paused {
+                        self.save_paused(true).await;
+                        paused = true;
+                    }
+                    tokio::time::sleep(PAUSE_POLL_INTERVAL).await;
+                }
+                EmbeddingControlState::Stopping | EmbeddingControlState::Idle => return false,
+            }
+        }
+    }
+
+    fn emit_processing(&self, counters: &Counters, in_progress: &[String]) {
+        self.emit(EmbeddingProgress::Processing {
+            completed: counters.completed(),
+            total: counters.total,
+            success_count: counters.success.load(Ordering::Relaxed),
+            error_count: counters.errors.load(Ordering::Relaxed),
+            in_progress: in_progress.to_vec(),
+            message: format!("Processing {} tracks in parallel", in_progress.len()),
+        });
+    }
+
+    /// Broadcast an overall progress event and keep it for late subscribers
+    fn emit(&self, event: EmbeddingProgress) {
+        *self.latest.lock().unwrap() = Some(event.clone());
+        let _ = self.progress.send(event);
+    }
+
+    async fn saved(&self) -> Result<SavedBackfill> {
+        let saved = sqlx::query_as(
+            r#"
+            SELECT is_processing, backfill_paused, backfill_cursor, backfill_success, backfill_errors
+            FROM embedding_processing_status
+            WHERE id = 1
+            "#,
+        )
+        .fetch_one(&self.db)
+        .await?;
+        Ok(saved)
+    }
+
+    async fn save(&self, cursor: &Option<String>, counters: &Counters) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE embedding_processing_status
+            SET backfill_cursor = $1, backfill_success = $2, backfill_errors = $3, updated_at = NOW()
+            WHERE id = 1
+            "#,
+        )
+        .bind(cursor)
+        .bind(counters.success.load(Ordering::Relaxed) as i32)
+        .bind(counters.errors.load(Ordering::Relaxed) as i32)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn save_paused(&self, paused: bool) {
+        if let Err(e) = sqlx::query("UPDATE embedding_processing_status SET backfill_paused = $1 WHERE id = 1")
+            .bind(paused)
+            .execute(&self.db)
+            .await
+        {
+            tracing::warn!("Failed to save embedding backfill pause state: {}", e);
+        }
+    }
+
+    async fn finish(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE embedding_processing_status
+            SET is_processing = false, backfill_paused = false,
+                last_processing_completed = NOW(), updated_at = NOW()
+            WHERE id = 1
+            "#,
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+//! Energy arcs for hybrid curation
+//!
+//! An arc is a target intensity curve over the playlist, such as warm-up → peak →
+//! cooldown. A track's intensity is its `library_index.energy_level` blended with its
+//! tempo (60-180 BPM mapped onto 0-1), so two tracks of the same energy sort by pace.
+//! Tracks with neither are treated as mid-intensity.
+
+use crate::error::{AppError, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Share of a track's intensity that comes from its energy when the tempo is known
+const ENERGY_SHARE: f64 = 0.7;
+
+const MIN_TEMPO: f64 = 60.0;
+const MAX_TEMPO: f64 = 180.0;
+
+/// Intensity of tracks whose energy and tempo are unknown
+pub const UNKNOWN_INTENSITY: f64 = 0.5;
+
+/// Most control points a custom arc can have
+const MAX_POINTS: usize = 16;
+
+/// Target intensity (0-1) through a playlist, as control points spread evenly from the
+/// first track to the last
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EnergyArc {
+    points: Vec<f64>,
+}
+
+impl EnergyArc {
+    /// Named arcs: `warm_up`, `peak` (warm-up → peak → cooldown) and `cool_down`
+    pub fn preset(name: &str) -> Option<Self> {
+        let points = match name {
+            "warm_up" => vec![0.3, 0.8],
+            "peak" => vec![0.3, 0.9, 0.4],
+            "cool_down" => vec![0.8, 0.25],
+            _ => return None,
+        };
+        Some(Self { points })
+    }
+
+    pub fn from_points(points: Vec<f64>) -> Result<Self> {
+        if points.is_empty() || points.len() > MAX_POINTS {
+            return Err(AppError::Validation(format!(
+                "energy_arc: must have between 1 and {} points",
+                MAX_POINTS
+            )));
+        }
+        if points.iter().any(|p| !(0.0..=1.0).contains(p)) {
+            return Err(AppError::Validation("energy_arc: points must be between 0 and 1".to_string()));
+        }
+        Ok(Self { points })
+    }
+
+    /// Target intensity of slot `index` of `len`
+    pub fn target(&self, index: usize, len: usize) -> f64 {
+        if self.points.len() == 1 || len <= 1 {
+            return self.points[0];
+        }
+        let position = index.min(len - 1) as f64 / (len - 1) as f64 * (self.points.len() - 1) as f64;
+        let before = position.floor() as usize;
+        let after = (before + 1).min(self.points.len() - 1);
+        let fraction = position - before as f64;
+        self.points[before] + (self.points[after] - self.points[before]) * fraction
+    }
+}
+
+/// A preset name or comma-separated control points, e.g. "peak" or "0.2,0.9,0.5"
+impl FromStr for EnergyArc {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(arc) = Self::preset(s.trim()) {
+            return Ok(arc);
+        }
+        let points = s
+            .split(',')
+            .map(|p| p.trim().parse::<f64>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| {
+                AppError::Validation(
+                    "energy_arc: must be warm_up, peak, cool_down or comma-separated points".to_string(),
+                )
+            })?;
+        Self::from_points(points)
+    }
+}
+
+/// Accepts the string forms and a JSON array of points
+impl<'de> Deserialize<'de> for EnergyArc {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Spec {
+            Text(String),
+            Points(Vec<f64>),
+        }
+        match Spec::deserialize(deserializer)? {
+            Spec::Text(text) => text.parse(),
+            Spec::Points(points) => Self::from_points(points),
+        }
+        .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Intensity of a track from its energy level and tempo, if either is known
+pub fn intensity(energy: Option<f64>, tempo: Option<f64>) -> Option<f64> {
+    let pace = tempo.map(|bpm| ((bpm - MIN_TEMPO) / (MAX_TEMPO - MIN_TEMPO)).clamp(0.0, 1.0));
+    match (energy.map(|e| e.clamp(0.0, 1.0)), pace) {
+        (Some(energy), Some(pace)) => Some(ENERGY_SHARE * energy + (1.0 - ENERGY_SHARE) * pace),
+        (energy, pace) => energy.or(pace),
+    }
+}
+
+/// Intensities of `ids` from the library index; unknown tracks are mid-intensity
+pub async fn intensities(db: &PgPool, ids: &[String]) -> Result<HashMap<String, f64>> {
+    let rows: Vec<(String, Option<f64>, Option<f64>)> =
+        sqlx::query_as("SELECT id, energy_level, tempo FROM library_index WHERE id = ANY($1)")
+            .bind(ids)
+            .fetch_all(db)
+            .await?;
+    let known: HashMap<String, f64> = rows
+        .into_iter()
+        .filter_map(|(id, energy, tempo)| Some((id, intensity(energy, tempo)?)))
+        .collect();
+    Ok(ids
+        .iter()
+        .map(|id| (id.clone(), known.get(id).copied().unwrap_or(UNKNOWN_INTENSITY)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_targets_follow_the_control_points() {
+        let arc = EnergyArc::preset("peak").unwrap();
+        assert_eq!(arc.target(0, 11), 0.3);
+        assert!((arc.target(5, 11) - 0.9).abs() < 1e-9);
+        assert!((arc.target(10, 11) - 0.4).abs() < 1e-9);
+        assert!((arc.target(2, 11) - 0.54).abs() < 1e-9);
+        assert_eq!(EnergyArc::from_points(vec![0.6]).unwrap().target(3, 10), 0.6);
+    }
+
+    #[test]
+    fn test_arcs_parse_from_names_points_and_json() {
+        assert_eq!("warm_up".parse::<EnergyArc>().unwrap(), EnergyArc::preset("warm_up").unwrap());
+        assert_eq!("0.2, 0.9".parse::<EnergyArc>().unwrap().points, vec![0.2, 0.9]);
+        assert!("loud".parse::<EnergyArc>().is_err());
+        assert!("0.2,1.5".parse::<EnergyArc>().is_err());
+
+        let arc: EnergyArc = serde_json::from_value(serde_json::json!([0.1, 0.5])).unwrap();
+        assert_eq!(arc.points, vec![0.1, 0.5]);
+        let arc: EnergyArc = serde_json::from_value(serde_json::json!("cool_down")).unwrap();
+        assert_eq!(arc, EnergyArc::preset("cool_down").unwrap());
+    }
+
+    #[test]
+    fn test_intensity_blends_energy_and_tempo() {
+        assert_eq!(intensity(Some(0.5), None), Some(0.5));
+        assert_eq!(intensity(None, Some(180.0)), Some(1.0));
+        assert!((intensity(Some(1.0), Some(60.0)).unwrap() - 0.7).abs() < 1e-9);
+        assert_eq!(intensity(None, None), None);
+    }
+}
@@ -0,0 +1,152 @@
+//! ONNX Runtime execution providers
+//!
+//! `ONNX_EXECUTION_PROVIDERS` lists the providers the audio encoder tries, in order
+//! (`auto` is CUDA, DirectML, CoreML, then CPU). A provider the installed ONNX Runtime
+//! wasn't built with, or that fails to initialize (no GPU, missing CUDA libraries), is
+//! skipped with a warning, and the CPU always remains as the last resort. The one
+//! that was used is reported by `GET /api/v1/embeddings/runtime`.
+
+use ort::execution_providers::{
+    CPUExecutionProvider, CUDAExecutionProvider, CoreMLExecutionProvider, DirectMLExecutionProvider,
+    ExecutionProvider, ExecutionProviderDispatch, TensorRTExecutionProvider,
+};
+use serde::Serialize;
+
+/// Tried when `ONNX_EXECUTION_PROVIDERS` is `auto`. TensorRT is left out: it compiles an
+/// engine for the model on every start, which takes minutes.
+pub const AUTO: [ExecutionProviderKind; 4] = [
+    ExecutionProviderKind::Cuda,
+    ExecutionProviderKind::DirectMl,
+    ExecutionProviderKind::CoreMl,
+    ExecutionProviderKind::Cpu,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionProviderKind {
+    TensorRt,
+    Cuda,
+    DirectMl,
+    CoreMl,
+    Cpu,
+}
+
+impl ExecutionProviderKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExecutionProviderKind::TensorRt => "tensorrt",
+            ExecutionProviderKind::Cuda => "cuda",
+            ExecutionProviderKind::DirectMl => "directml",
+            ExecutionProviderKind::CoreMl => "coreml",
+            ExecutionProviderKind::Cpu => "cpu",
+        }
+    }
+
+    /// Whether the loaded ONNX Runtime library includes this provider
+    pub fn is_available(self) -> bool {
+        let available = match self {
+            ExecutionProviderKind::TensorRt => TensorRTExecutionProvider::default().is_available(),
+            ExecutionProviderKind::Cuda => CUDAExecutionProvider::default().is_available(),
+            ExecutionProviderKind::DirectMl => DirectMLExecutionProvider::default().is_available(),
+            ExecutionProviderKind::CoreMl => CoreMLExecutionProvider::default().is_available(),
+            ExecutionProviderKind::Cpu => return true,
+        };
+        available.unwrap_or(false)
+    }
+
+    /// The provider to register on a session builder. Registration errors instead of
+    /// silently running on the CPU, so the next provider can be tried.
+    pub fn dispatch(self) -> ExecutionProviderDispatch {
+        let dispatch = match self {
+            ExecutionProviderKind::TensorRt => TensorRTExecutionProvider::default().build(),
+            ExecutionProviderKind::Cuda => CUDAExecutionProvider::default().build(),
+            ExecutionProviderKind::DirectMl => DirectMLExecutionProvider::default().build(),
+            // Run the parts CoreML can't on the CPU rather than refusing the model
+            ExecutionProviderKind::CoreMl => CoreMLExecutionProvider::default().with_subgraphs(true).build(),
+            ExecutionProviderKind::Cpu => CPUExecutionProvider::default().build(),
+        };
+        dispatch.error_on_failure()
+    }
+}
+
+impl std::str::FromStr for ExecutionProviderKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "tensorrt" | "trt" => Ok(ExecutionProviderKind::TensorRt),
+            "cuda" => Ok(ExecutionProviderKind::Cuda),
+            "directml" | "dml" => Ok(ExecutionProviderKind::DirectMl),
+            "coreml" => Ok(ExecutionProviderKind::CoreMl),
+            "cpu" => Ok(ExecutionProviderKind::Cpu),
+            other => Err(anyhow::anyhow!(
+                "ONNX_EXECUTION_PROVIDERS entries must be auto, tensorrt, cuda, directml, coreml or cpu (got {:?})",
+                other
+            )),
+        }
+    }
+}
+
+/// Parse a comma-separated preference list. `auto` (or nothing) expands to the default
+/// order, and the CPU is appended if the list doesn't name it.
+pub fn parse_list(s: &str) -> anyhow::Result<Vec<ExecutionProviderKind>> {
+    let mut providers = Vec::new();
+    for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        if entry.eq_ignore_ascii_case("auto") {
+            providers.extend(AUTO);
+        } else {
+            providers.push(entry.parse()?);
+        }
+    }
+    if providers.is_empty() {
+        providers.extend(AUTO);
+    }
+
+    let mut seen = Vec::new();
+    providers.retain(|p| {
+        let first = !seen.contains(p);
+        seen.push(*p);
+        first
+    });
+    // The CPU never fails, so nothing after it would be tried
+    match providers.iter().position(|p| *p == ExecutionProviderKind::Cpu) {
+        Some(cpu) => providers.truncate(cpu + 1),
+        None => providers.push(ExecutionProviderKind::Cpu),
+    }
+    Ok(providers)
+}
+
+/// Which provider the encoder's sessions run on
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecutionProviderStatus {
+    /// The provider in use
+    pub active: ExecutionProviderKind,
+    /// `ONNX_EXECUTION_PROVIDERS`, expanded
+    pub requested: Vec<ExecutionProviderKind>,
+    /// Why each provider before the active one was skipped
+    pub skipped: Vec<SkippedProvider>,
+    pub sessions: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedProvider {
+    pub provider: ExecutionProviderKind,
+    pub reason: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ExecutionProviderKind::*;
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(parse_list("").unwrap(), AUTO.to_vec());
+        assert_eq!(parse_list("auto").unwrap(), AUTO.to_vec());
+        assert_eq!(parse_list("TensorRT, cuda").unwrap(), vec![TensorRt, Cuda, Cpu]);
+        assert_eq!(parse_list("cuda,cuda,cpu").unwrap(), vec![Cuda, Cpu]);
+        assert_eq!(parse_list("cpu,cuda").unwrap(), vec![Cpu]);
+        assert_eq!(parse_list("tensorrt,auto").unwrap(), vec![TensorRt, Cuda, DirectMl, CoreMl, Cpu]);
+        assert!(parse_list("rocm").is_err());
+    }
+}
@@ -8,17 +8,30 @@
 //! 2. Seeds are placed evenly throughout the playlist
 //! 3. Audio encoder fills gaps with sonically similar tracks
 //! 4. Result: Playlist that matches query AND flows smoothly
+//!
+//! With an energy arc (see [`EnergyArc`]), seeds are placed and gaps filled so the
+//! playlist's intensity follows the arc, trading a little similarity for the right
+//! energy and tempo in each slot.
 
 #![allow(dead_code)]
 
 use crate::error::{AppError, Result};
 use crate::services::audio_encoder::AudioEncoder;
+use crate::services::energy_arc::{self, EnergyArc};
+use crate::services::llm::LlmClient;
+use crate::services::ratings::RatingBias;
 use crate::services::seed_selector::{SeedSelector, VerifiedSeed};
+use crate::services::separation::{self, Play, SeparationRules};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Similarity given up per unit of distance from an arc slot's target intensity
+const ARC_WEIGHT: f64 = 0.5;
 
 /// Progress updates for hybrid curation
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -78,6 +91,8 @@ pub struct HybridCurationConfig {
     pub min_embedding_coverage: f32,
     /// Fall back to traditional curation if embedding coverage is low
     pub fallback_enabled: bool,
+    /// Spacing of artists and albums among the tracks filling the gaps
+    pub separation: SeparationRules,
 }
 
 impl Default for HybridCurationConfig {
@@ -87,6 +102,7 @@ impl Default for HybridCurationConfig {
             playlist_size: 50,
             min_embedding_coverage: 0.03, // TODO: Temporarily lowered for testing, restore to 0.3
             fallback_enabled: true,
+            separation: SeparationRules::default(),
         }
     }
 }
@@ -102,14 +118,14 @@ pub struct HybridCurator {
 
 impl HybridCurator {
     pub fn new(
-        anthropic_api_key: String,
+        llm: Arc<LlmClient>,
         audio_encoder: Option<Arc<AudioEncoder>>,
         db: PgPool,
         config: HybridCurationConfig,
         library_path: Option<std::path::PathBuf>,
     ) -> Self {
         Self {
-            seed_selector: SeedSelector::new(anthropic_api_key, db.clone()),
+            seed_selector: SeedSelector::new(llm, db.clone()),
             audio_encoder,
             db,
             config,
@@ -117,14 +133,24 @@ impl HybridCurator {
         }
     }
 
-    /// Curate a playlist using hybrid approach
+    pub fn config(&self) -> &HybridCurationConfig {
+        &self.config
+    }
+
+    /// Curate a playlist using hybrid approach. With `for_user`, that user's ratings
+    /// personalize the playlist (see [`RatingBias`]). Without `allow_explicit`, neither
+    /// seeds nor the tracks filling the gaps are marked explicit. `energy_arc` shapes
+    /// the playlist's intensity from start to end.
     pub async fn curate(
         &self,
         query: &str,
         limit: usize,
+        for_user: Option<Uuid>,
+        allow_explicit: bool,
+        energy_arc: Option<&EnergyArc>,
     ) -> Result<Vec<String>> {
         let (tx, _rx) = mpsc::channel(10);
-        self.curate_with_progress(query, limit, tx).await
+        self.curate_with_progress(query, limit, for_user, allow_explicit, energy_arc, tx).await
     }
 
     /// Curate with progress updates
@@ -132,6 +158,9 @@ impl HybridCurator {
         &self,
         query: &str,
         limit: usize,
+        for_user: Option<Uuid>,
+        allow_explicit: bool,
+        energy_arc: Option<&EnergyArc>,
         progress_tx: mpsc::Sender<HybridCurationProgress>,
     ) -> Result<Vec<String>> {
         let send = |p: HybridCurationProgress| {
@@ -144,6 +173,11 @@ impl HybridCurator {
             message: "Starting hybrid curation...".to_string(),
         }).await;
 
+        let bias = RatingBias::load(&self.db, for_user)
+            .await?
+            .with_explicit(&self.db, allow_explicit)
+            .await?;
+
         // Check embedding coverage
         let coverage = self.get_embedding_coverage().await?;
         info!("Embedding coverage: {:.1}%", coverage * 100.0);
@@ -163,7 +197,7 @@ impl HybridCurator {
                     coverage * 100.0
                 );
                 let playlist = self.fallback_curation(query, limit, &progress_tx).await?;
-                return Ok(playlist);
+                return Ok(bias.filter(playlist));
             } else {
                 warn!("Low embedding coverage but fallback disabled, proceeding anyway");
             }
@@ -174,15 +208,16 @@ impl HybridCurator {
             message: "AI is selecting perfect seed songs...".to_string(),
         }).await;
 
-        let seeds = self
+        let mut seeds = self
             .seed_selector
             .select_seeds(query, self.config.seed_count, limit)
             .await?;
+        seeds.retain(|s| !bias.is_excluded(&s.track_id));
 
         if seeds.is_empty() {
             warn!("No seeds selected, falling back to traditional curation");
             let playlist = self.fallback_curation(query, limit, &progress_tx).await?;
-            return Ok(playlist);
+            return Ok(bias.filter(playlist));
         }
 
         send(HybridCurationProgress::SeedsSelected {
@@ -196,7 +231,7 @@ impl HybridCurator {
 
         // Step 2: Fill gaps between seeds using audio similarity
         let playlist = self
-            .fill_gaps_between_seeds(&seeds, limit, &progress_tx)
+            .fill_gaps_between_seeds(&seeds, limit, &bias, energy_arc, &progress_tx)
             .await?;
 
         send(HybridCurationProgress::Completed {
@@ -220,10 +255,12 @@ impl HybridCurator {
         &self,
         seeds: &[VerifiedSeed],
         total_size: usize,
+        bias: &RatingBias,
+        energy_arc: Option<&EnergyArc>,
         progress_tx: &mpsc::Sender<HybridCurationProgress>,
     ) -> Result<Vec<String>> {
         let audio_encoder = self.audio_encoder.as_ref().ok_or_else(|| {
-            AppError::InternalMessage("Audio encoder not available".to_string())
+            AppError::NotConfigured("Audio encoder not available".to_string())
         })?;
 
         // Check which seeds are missing embeddings and generate them
@@ -258,11 +295,20 @@ impl HybridCurator {
 
         // Find tracks with highest AVERAGE similarity to all seeds using centroid
         // This is more discriminative than max similarity to any single seed
+        // Over-fetch a little so boosting highly rated tracks has room to re-rank, and
+        // more with an arc so each slot has tracks of different energy to choose from
+        let fetch = if energy_arc.is_some() { tracks_to_fill * 2 } else { tracks_to_fill + tracks_to_fill / 2 };
         let similar_tracks = match audio_encoder
-            .find_similar_to_seeds(&seed_ids, tracks_to_fill, &[])
+            .find_similar_to_seeds(
+                &seed_ids,
+                fetch,
+                &bias.excluded_ids(),
+                &bias.liked_ids(),
+            )
             .await
         {
-            Ok(tracks) => tracks,
+            // The surplus leaves room to keep artists and albums apart
+            Ok(tracks) => bias.rerank(tracks),
             Err(e) => {
                 warn!("Failed to find tracks similar to seed centroid: {}", e);
                 Vec::new()
@@ -275,33 +321,19 @@ impl HybridCurator {
             tracks_to_fill
         );
 
-        // Build playlist by interleaving seeds with similar tracks
-        let mut playlist = Vec::with_capacity(total_size);
-        let mut similar_iter = similar_tracks.into_iter();
-
-        // Calculate tracks per gap for even distribution
-        let num_gaps = seeds.len();
-        let tracks_per_gap = tracks_to_fill / num_gaps;
-        let remainder = tracks_to_fill % num_gaps;
-
-        for (i, seed) in seeds.iter().enumerate() {
-            // Add seed
-            playlist.push(seed.track_id.clone());
-
-            // Calculate gap size (distribute remainder among first gaps)
-            let gap_size = if i < remainder {
-                tracks_per_gap + 1
-            } else {
-                tracks_per_gap
-            };
-
-            // Fill gap with similar tracks
-            for _ in 0..gap_size {
-                if let Some((track_id, _similarity)) = similar_iter.next() {
-                    playlist.push(track_id);
-                }
-            }
-        }
+        let credits = self.credits(&seed_ids, &similar_tracks).await;
+        let intensities = match energy_arc {
+            Some(_) => self.intensities(&seed_ids, &similar_tracks).await,
+            None => HashMap::new(),
+        };
+        let playlist = interleave_seeds(
+            &seed_ids,
+            similar_tracks,
+            tracks_to_fill,
+            &self.config.separation,
+            &credits,
+            energy_arc.map(|arc| (arc, &intensities)),
+        );
 
         debug!(
             "Built playlist with {} tracks ({} seeds, {} filled using centroid similarity)",
@@ -346,11 +378,11 @@ impl HybridCurator {
         progress_tx: &mpsc::Sender<HybridCurationProgress>,
     ) -> Result<()> {
         let audio_encoder = self.audio_encoder.as_ref().ok_or_else(|| {
-            AppError::InternalMessage("Audio encoder not available".to_string())
+            AppError::NotConfigured("Audio encoder not available".to_string())
         })?;
 
         let library_path = self.library_path.as_ref().ok_or_else(|| {
-            AppError::InternalMessage(
+            AppError::NotConfigured(
                 "Library path not configured - cannot generate embeddings".to_string(),
             )
         })?;
@@ -520,6 +552,76 @@ impl HybridCurator {
         Ok(tracks)
     }
 
+    /// Curate a playlist from seed tracks the caller already chose (e.g. from a user's
+    /// listening history) instead of LLM-selected seeds. Seeds without embeddings are
+    /// dropped; the rest are spread evenly and the gaps filled with tracks closest to
+    /// their centroid, favoring the neighborhoods of `liked_ids`.
+    pub async fn curate_from_seeds(
+        &self,
+        seed_ids: &[String],
+        limit: usize,
+        exclude_ids: &[String],
+        liked_ids: &[String],
+    ) -> Result<Vec<String>> {
+        let audio_encoder = self.audio_encoder.as_ref().ok_or_else(|| {
+            AppError::NotConfigured("Audio encoder not available".to_string())
+        })?;
+
+        let seeds: Vec<String> = sqlx::query_scalar(
+            "SELECT track_id FROM track_embeddings WHERE track_id = ANY($1)",
+        )
+        .bind(seed_ids)
+        .fetch_all(&self.db)
+        .await?;
+        // Keep the caller's seed order
+        let seeds: Vec<String> = seed_ids
+            .iter()
+            .filter(|id| seeds.contains(id))
+            .take(limit)
+            .cloned()
+            .collect();
+
+        if seeds.is_empty() {
+            return Err(AppError::BadRequest(
+                "None of the seed tracks have audio embeddings".to_string(),
+            ));
+        }
+
+        let tracks_to_fill = limit.saturating_sub(seeds.len());
+        let similar_tracks = audio_encoder
+            .find_similar_to_seeds(&seeds, tracks_to_fill + tracks_to_fill / 2, exclude_ids, liked_ids)
+            .await?;
+
+        let credits = self.credits(&seeds, &similar_tracks).await;
+        Ok(interleave_seeds(&seeds, similar_tracks, tracks_to_fill, &self.config.separation, &credits, None))
+    }
+
+    /// Artists and albums of the seeds and candidate tracks, for spacing them out
+    async fn credits(&self, seed_ids: &[String], similar_tracks: &[(String, f32)]) -> HashMap<String, Play> {
+        let ids: Vec<String> = seed_ids
+            .iter()
+            .cloned()
+            .chain(similar_tracks.iter().map(|(id, _)| id.clone()))
+            .collect();
+        separation::credits(&self.db, &ids).await.unwrap_or_else(|e| {
+            warn!("Failed to read track credits, artists won't be spaced out: {}", e);
+            HashMap::new()
+        })
+    }
+
+    /// Intensities of the seeds and candidate tracks, for following an energy arc
+    async fn intensities(&self, seed_ids: &[String], similar_tracks: &[(String, f32)]) -> HashMap<String, f64> {
+        let ids: Vec<String> = seed_ids
+            .iter()
+            .cloned()
+            .chain(similar_tracks.iter().map(|(id, _)| id.clone()))
+            .collect();
+        energy_arc::intensities(&self.db, &ids).await.unwrap_or_else(|e| {
+            warn!("Failed to read track energy, the energy arc is ignored: {}", e);
+            HashMap::new()
+        })
+    }
+
     /// Extend an existing playlist with more tracks
     ///
     /// Uses the last few tracks' embeddings to find similar music
@@ -529,7 +631,7 @@ impl HybridCurator {
         count: usize,
     ) -> Result<Vec<String>> {
         let audio_encoder = self.audio_encoder.as_ref().ok_or_else(|| {
-            AppError::InternalMessage("Audio encoder not available for extension".to_string())
+            AppError::NotConfigured("Audio encoder not available for extension".to_string())
         })?;
 
         if current_track_ids.is_empty() {
@@ -564,6 +666,77 @@ impl HybridCurator {
     }
 }
 
+/// Spread seeds evenly through the playlist, filling the gap after each seed with
+/// similar tracks (the remainder goes to the first gaps). Each slot takes the most
+/// similar track that keeps to the separation `rules`, or the most similar one left
+/// when none does; tracks beyond `tracks_to_fill` are only there to choose from.
+///
+/// With an energy arc and track intensities, seeds go to the seed slots whose targets
+/// rank like their intensities, and each gap slot weighs similarity against distance
+/// from the slot's target.
+fn interleave_seeds(
+    seed_ids: &[String],
+    similar_tracks: Vec<(String, f32)>,
+    tracks_to_fill: usize,
+    rules: &SeparationRules,
+    credits: &HashMap<String, Play>,
+    arc: Option<(&EnergyArc, &HashMap<String, f64>)>,
+) -> Vec<String> {
+    let play = |id: &str| credits.get(id).cloned().unwrap_or_else(|| Play::unknown(id));
+    let similarity: HashMap<&str, f64> = similar_tracks.iter().map(|(id, s)| (id.as_str(), *s as f64)).collect();
+    let mut placed: Vec<Play> = Vec::with_capacity(seed_ids.len() + tracks_to_fill);
+    let mut pool: Vec<Play> = similar_tracks.iter().map(|(id, _)| play(id)).collect();
+
+    let num_gaps = seed_ids.len().max(1);
+    let tracks_per_gap = tracks_to_fill / num_gaps;
+    let remainder = tracks_to_fill % num_gaps;
+    let gap_size = |i: usize| if i < remainder { tracks_per_gap + 1 } else { tracks_per_gap };
+    let len = seed_ids.len() + tracks_to_fill;
+
+    let seeds: Vec<&String> = match arc {
+        Some((arc, intensities)) => {
+            let mut slots: Vec<(usize, usize)> = (0..seed_ids.len())
+                .map(|i| (i, i + (0..i).map(gap_size).sum::<usize>()))
+                .collect();
+            slots.sort_by(|a, b| arc.target(a.1, len).total_cmp(&arc.target(b.1, len)));
+            let mut by_intensity: Vec<&String> = seed_ids.iter().collect();
+            by_intensity.sort_by(|a, b| intensity_of(intensities, a).total_cmp(&intensity_of(intensities, b)));
+            let mut seeds = vec![&seed_ids[0]; seed_ids.len()];
+            for ((seed_slot, _), seed) in slots.into_iter().zip(by_intensity) {
+                seeds[seed_slot] = seed;
+            }
+            seeds
+        }
+        None => seed_ids.iter().collect(),
+    };
+
+    for (i, seed_id) in seeds.into_iter().enumerate() {
+        placed.push(play(seed_id));
+
+        for _ in 0..gap_size(i) {
+            let track = match arc {
+                Some((arc, intensities)) => {
+                    let target = arc.target(placed.len(), len);
+                    separation::pick_best(&mut pool, &placed, rules, |candidate| {
+                        similarity.get(candidate.track_id.as_str()).copied().unwrap_or(0.0)
+                            - ARC_WEIGHT * (intensity_of(intensities, &candidate.track_id) - target).abs()
+                    })
+                }
+                None => separation::pick(&mut pool, &placed, rules),
+            };
+            if let Some(track) = track {
+                placed.push(track);
+            }
+        }
+    }
+
+    placed.into_iter().map(|p| p.track_id).collect()
+}
+
+fn intensity_of(intensities: &HashMap<String, f64>, id: &str) -> f64 {
+    intensities.get(id).copied().unwrap_or(energy_arc::UNKNOWN_INTENSITY)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -584,4 +757,54 @@ mod tests {
         assert_eq!(tracks_per_gap, 6);
         assert_eq!(remainder, 1);
     }
+
+    #[test]
+    fn test_gaps_keep_artists_apart() {
+        let credits: HashMap<String, Play> = [("s1", "A"), ("s2", "B"), ("a1", "A"), ("a2", "A"), ("c1", "C"), ("d1", "D")]
+            .into_iter()
+            .map(|(id, artist)| {
+                let play = Play {
+                    track_id: id.to_string(),
+                    artist: Some(artist.to_string()),
+                    album: Some(format!("{} album", id)),
+                };
+                (id.to_string(), play)
+            })
+            .collect();
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let similar: Vec<(String, f32)> = ["a1", "a2", "c1", "d1"].iter().map(|id| (id.to_string(), 0.9)).collect();
+        let rules = SeparationRules { tracks: 20, artists: 2, albums: 2 };
+
+        let playlist = interleave_seeds(&ids(&["s1", "s2"]), similar, 4, &rules, &credits, None);
+        // A's tracks stay two plays away from A's seed and each other where they can
+        assert_eq!(playlist, ids(&["s1", "c1", "d1", "s2", "a1", "a2"]));
+    }
+
+    #[test]
+    fn test_gaps_follow_the_energy_arc() {
+        let intensities: HashMap<String, f64> =
+            [("calm", 0.2), ("mid", 0.5), ("loud", 0.9), ("soft", 0.35), ("warm", 0.6), ("hot", 0.85)]
+                .into_iter()
+                .map(|(id, intensity)| (id.to_string(), intensity))
+                .collect();
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        // Most similar first, loud ones included
+        let similar: Vec<(String, f32)> = [("hot", 0.95), ("warm", 0.9), ("soft", 0.85)]
+            .iter()
+            .map(|(id, s)| (id.to_string(), *s))
+            .collect();
+        let rules = SeparationRules { tracks: 20, artists: 0, albums: 0 };
+        let arc = EnergyArc::preset("warm_up").unwrap();
+
+        let playlist = interleave_seeds(
+            &ids(&["loud", "mid", "calm"]),
+            similar,
+            3,
+            &rules,
+            &HashMap::new(),
+            Some((&arc, &intensities)),
+        );
+        // Seeds rise with the arc, and each gap takes the track closest to its slot
+        assert_eq!(playlist, ids(&["calm", "soft", "mid", "warm", "loud", "hot"]));
+    }
 }
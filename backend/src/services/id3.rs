@@ -0,0 +1,133 @@
+//! Timed ID3 metadata for packed audio HLS segments
+//!
+//! MP3 and AAC segments start with an ID3v2.4 tag. Its PRIV frame gives the
+//! segment's start time on the 90kHz MPEG-2 clock, which HLS requires for packed
+//! audio; players (hls.js, Safari) turn the other frames into metadata cues at that
+//! time, so now-playing info changes with the audio instead of with a poll.
+//!
+//! Every tag names the track (`TIT2`, `TPE1` and a `TXXX` "track_id"); the first
+//! segment of a track also carries a `TXXX` "track_change" marker.
+
+/// Owner of the PRIV frame with the segment timestamp
+const TIMESTAMP_OWNER: &[u8] = b"com.apple.streaming.transportStreamTimestamp\0";
+
+/// Text encoding byte for UTF-8 (ID3v2.4)
+const UTF8: u8 = 3;
+
+/// Track a segment's tag describes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaggedTrack {
+    pub track_id: String,
+    pub title: String,
+    pub artist: String,
+}
+
+/// ID3 tag for the start of a segment beginning at `timestamp_90khz`. `track_change`
+/// marks the first segment of `track`.
+pub fn segment_tag(timestamp_90khz: u64, track: Option<&TaggedTrack>, track_change: bool) -> Vec<u8> {
+    let mut timestamp = TIMESTAMP_OWNER.to_vec();
+    // 33-bit MPEG-2 timestamp in the low bits of 8 bytes
+    timestamp.extend_from_slice(&(timestamp_90khz & 0x1_FFFF_FFFF).to_be_bytes());
+
+    let mut frames = frame(b"PRIV", &timestamp);
+    if let Some(track) = track.filter(|t| !t.track_id.is_empty()) {
+        frames.extend(text_frame(b"TIT2", &track.title));
+        frames.extend(text_frame(b"TPE1", &track.artist));
+        frames.extend(user_text_frame("track_id", &track.track_id));
+        if track_change {
+            frames.extend(user_text_frame("track_change", &track.track_id));
+        }
+    }
+
+    let mut tag = b"ID3".to_vec();
+    tag.extend_from_slice(&[4, 0, 0]);
+    tag.extend_from_slice(&syncsafe(frames.len() as u32));
+    tag.extend_from_slice(&frames);
+    tag
+}
+
+fn frame(id: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut frame = id.to_vec();
+    frame.extend_from_slice(&syncsafe(payload.len() as u32));
+    frame.extend_from_slice(&[0, 0]);
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn text_frame(id: &[u8; 4], text: &str) -> Vec<u8> {
+    let mut payload = vec![UTF8];
+    payload.extend_from_slice(text.as_bytes());
+    frame(id, &payload)
+}
+
+/// `TXXX` frame: a description and a value
+fn user_text_frame(description: &str, value: &str) -> Vec<u8> {
+    let mut payload = vec![UTF8];
+    payload.extend_from_slice(description.as_bytes());
+    payload.push(0);
+    payload.extend_from_slice(value.as_bytes());
+    frame(b"TXXX", &payload)
+}
+
+/// ID3 sizes use 7 bits per byte
+fn syncsafe(n: u32) -> [u8; 4] {
+    [
+        ((n >> 21) & 0x7f) as u8,
+        ((n >> 14) & 0x7f) as u8,
+        ((n >> 7) & 0x7f) as u8,
+        (n & 0x7f) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frame ids and payloads of a tag
+    fn frames(tag: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let size = |b: &[u8]| b.iter().fold(0usize, |n, &byte| (n << 7) | byte as usize);
+        assert_eq!(&tag[..5], b"ID3\x04\x00");
+        assert_eq!(size(&tag[6..10]), tag.len() - 10);
+
+        let mut frames = Vec::new();
+        let mut rest = &tag[10..];
+        while !rest.is_empty() {
+            let len = size(&rest[4..8]);
+            frames.push((String::from_utf8_lossy(&rest[..4]).into_owned(), rest[10..10 + len].to_vec()));
+            rest = &rest[10 + len..];
+        }
+        frames
+    }
+
+    #[test]
+    fn test_tag_carries_timestamp_and_track() {
+        let track = TaggedTrack {
+            track_id: "t1".to_string(),
+            title: "Blue Light".to_string(),
+            artist: "Mazzy Star".to_string(),
+        };
+        let frames = frames(&segment_tag(0x1_2345_6789, Some(&track), false));
+
+        assert_eq!(frames[0].0, "PRIV");
+        assert!(frames[0].1.starts_with(TIMESTAMP_OWNER));
+        assert_eq!(&frames[0].1[TIMESTAMP_OWNER.len()..], &0x1_2345_6789u64.to_be_bytes());
+        assert_eq!(frames[1], ("TIT2".to_string(), b"\x03Blue Light".to_vec()));
+        assert_eq!(frames[2], ("TPE1".to_string(), b"\x03Mazzy Star".to_vec()));
+        assert_eq!(frames[3], ("TXXX".to_string(), b"\x03track_id\0t1".to_vec()));
+        assert_eq!(frames.len(), 4);
+    }
+
+    #[test]
+    fn test_first_segment_of_a_track_is_marked() {
+        let track = TaggedTrack {
+            track_id: "t2".to_string(),
+            ..Default::default()
+        };
+        let frames = frames(&segment_tag(0, Some(&track), true));
+        assert_eq!(frames.last().unwrap(), &("TXXX".to_string(), b"\x03track_change\0t2".to_vec()));
+
+        // Before the first track starts there is only the timestamp
+        assert_eq!(self::frames(&segment_tag(0, None, true)).len(), 1);
+        assert_eq!(syncsafe(200), [0, 0, 1, 72]);
+    }
+}
@@ -0,0 +1,545 @@
+//! Background job framework
+//!
+//! Long-running work (library sync, AI analysis, stats computation, embedding
+//! indexing, visualization rebuilds) is submitted through the [`JobManager`]
+//! instead of being spawned ad-hoc. Every job gets a row in `background_jobs`
+//! recording its state, progress, attempts and outcome, and can be cancelled
+//! cooperatively through its [`JobContext`].
+
+use crate::error::{AppError, Result};
+use crate::models::{Job, JobKind, JobLog, JobStatus, JobUpdate, WebhookEvent};
+use crate::services::webhooks::WebhookDispatcher;
+use futures::future::BoxFuture;
+use sqlx::PgPool;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
+use uuid::Uuid;
+
+/// Delay before the first retry of a failed attempt (doubles each retry)
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(5);
+
+/// Upper bound for the delay between retries
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(300);
+
+/// Maximum number of jobs returned by a single list query
+const MAX_LIST_LIMIT: i64 = 200;
+
+/// Failed and cancelled jobs whose tasks are kept for a manual retry; older ones can
+/// no longer be retried
+const MAX_RETRYABLE_JOBS: usize = 50;
+
+type JobTask = Arc<dyn Fn(JobContext) -> BoxFuture<'static, Result<serde_json::Value>> + Send + Sync>;
+
+/// Task closures of queued, running and retryable jobs
+#[derive(Default)]
+struct TaskStore {
+    tasks: HashMap<Uuid, JobTask>,
+    /// Failed and cancelled jobs, oldest first
+    retryable: VecDeque<Uuid>,
+}
+
+impl TaskStore {
+    fn insert(&mut self, id: Uuid, task: JobTask) {
+        self.tasks.insert(id, task);
+    }
+
+    fn remove(&mut self, id: Uuid) {
+        self.tasks.remove(&id);
+        self.retryable.retain(|retryable| *retryable != id);
+    }
+
+    /// Keep a failed or cancelled job's task for a retry, dropping the tasks of the
+    /// oldest such jobs beyond `MAX_RETRYABLE_JOBS`
+    fn keep_for_retry(&mut self, id: Uuid) {
+        self.retryable.retain(|retryable| *retryable != id);
+        self.retryable.push_back(id);
+        while self.retryable.len() > MAX_RETRYABLE_JOBS {
+            if let Some(oldest) = self.retryable.pop_front() {
+                self.tasks.remove(&oldest);
+            }
+        }
+    }
+
+    /// Stop counting a job as retryable while its retry runs, so its task isn't dropped
+    fn start_retry(&mut self, id: Uuid) {
+        self.retryable.retain(|retryable| *retryable != id);
+    }
+}
+
+/// Handle passed to a running job for reporting progress and observing cancellation
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct JobContext {
+    pub id: Uuid,
+    pub kind: JobKind,
+    pub payload: serde_json::Value,
+    /// 1-based attempt number
+    pub attempt: i32,
+    db: PgPool,
+    cancelled: Arc<AtomicBool>,
+    updates: broadcast::Sender<JobUpdate>,
+}
+
+impl JobContext {
+    /// Whether cancellation has been requested; jobs should check this between units of work
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Record progress (0.0 to 1.0) and a status message. Failures are logged, not returned.
+    pub async fn progress(&self, progress: f64, message: impl Into<String>) {
+        let progress = progress.clamp(0.0, 1.0);
+        let message = message.into();
+
+        if let Err(e) = sqlx::query(
+            "UPDATE background_jobs SET progress = $2, message = $3 WHERE id = $1",
+        )
+        .bind(self.id)
+        .bind(progress)
+        .bind(&message)
+        .execute(&self.db)
+        .await
+        {
+            tracing::warn!("Failed to record progress for job {}: {}", self.id, e);
+        }
+
+        let _ = self.updates.send(JobUpdate {
+            job_id: self.id,
+            kind: self.kind,
+            status: JobStatus::Running,
+            progress,
+            message: Some(message),
+        });
+    }
+
+    /// Append a line to the job's log (shown in the admin jobs dashboard)
+    pub async fn log(&self, level: &str, message: impl Into<String>) {
+        append_log(&self.db, self.id, level, &message.into()).await;
+    }
+}
+
+/// Runs background jobs and persists their state
+#[derive(Clone)]
+pub struct JobManager {
+    db: PgPool,
+    /// Task closures by job id, kept so recently failed jobs can be retried while the process is alive
+    tasks: Arc<RwLock<TaskStore>>,
+    /// Cancellation flags of jobs currently queued or running in this process
+    cancel_flags: Arc<RwLock<HashMap<Uuid, Arc<AtomicBool>>>>,
+    updates: broadcast::Sender<JobUpdate>,
+    webhooks: WebhookDispatcher,
+}
+
+impl JobManager {
+    pub fn new(db: PgPool, webhooks: WebhookDispatcher) -> Self {
+        let (updates, _) = broadcast::channel(256);
+        Self {
+            db,
+            webhooks,
+            tasks: Arc::new(RwLock::new(TaskStore::default())),
+            cancel_flags: Arc::new(RwLock::new(HashMap::new())),
+            updates,
+        }
+    }
+
+    /// Mark jobs left queued/running by a previous process as failed.
+    /// Should be called once at startup before any job is submitted.
+    pub async fn recover_interrupted(&self) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            UPDATE background_jobs
+            SET status = 'failed', error = 'Interrupted by server restart', finished_at = NOW()
+            WHERE status IN ('queued', 'running')
+            "#,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Subscribe to live job state changes
+    pub fn subscribe(&self) -> broadcast::Receiver<JobUpdate> {
+        self.updates.subscribe()
+    }
+
+    /// Submit a job. The task is invoked once per attempt (up to `max_attempts`)
+    /// with exponential backoff between attempts.
+    /// Returns `Conflict` if a job of the same kind is already queued or running.
+    pub async fn submit<F, Fut>(
+        &self,
+        kind: JobKind,
+        payload: serde_json::Value,
+        max_attempts: i32,
+        task: F,
+    ) -> Result<Uuid>
+    where
+        F: Fn(JobContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<serde_json::Value>> + Send + 'static,
+    {
+        let id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO background_jobs (kind, status, payload, max_attempts)
+            VALUES ($1, 'queued', $2, $3)
+            RETURNING id
+            "#,
+        )
+        .bind(kind)
+        .bind(&payload)
+        .bind(max_attempts.max(1))
+        .fetch_one(&self.db)
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23505") => {
+                AppError::Conflict(format!("A {} job is already in progress", kind))
+            }
+            _ => AppError::Database(e),
+        })?;
+
+        let task: JobTask = Arc::new(move |ctx| Box::pin(task(ctx)));
+        self.tasks.write().await.insert(id, task.clone());
+        self.spawn_runner(id, kind, payload, max_attempts.max(1), task).await;
+
+        tracing::info!("Submitted {} job {}", kind, id);
+        Ok(id)
+    }
+
+    /// Request cancellation of a queued or running job
+    pub async fn cancel(&self, id: Uuid) -> Result<()> {
+        let job = self.get(id).await?;
+        if job.status.is_terminal() {
+            return Err(AppError::Conflict(format!("Job is already {}", job.status)));
+        }
+
+        match self.cancel_flags.read().await.get(&id) {
+            Some(flag) => flag.store(true, Ordering::Relaxed),
+            None => {
+                // Not owned by this process (stale row) - close it out directly
+                self.finish(id, job.kind, JobStatus::Cancelled, None, None).await;
+            }
+        }
+
+        tracing::info!("Cancellation requested for job {}", id);
+        Ok(())
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<Job> {
+        sqlx::query_as::<_, Job>("SELECT * FROM background_jobs WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Job {} not found", id)))
+    }
+
+    /// List jobs, newest first, optionally filtered by status and kind
+    pub async fn list(
+        &self,
+        status: Option<JobStatus>,
+        kind: Option<JobKind>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Job>> {
+        let jobs = sqlx::query_as::<_, Job>(
+            r#"
+            SELECT * FROM background_jobs
+            WHERE ($1::text IS NULL OR status = $1)
+            AND ($2::text IS NULL OR kind = $2)
+            ORDER BY created_at DESC
+            LIMIT $3 OFFSET $4
+            "#,
+        )
+        .bind(status)
+        .bind(kind)
+        .bind(limit.clamp(1, MAX_LIST_LIMIT))
+        .bind(offset.max(0))
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(jobs)
+    }
+
+    /// Log lines recorded for a job, oldest first
+    pub async fn logs(&self, id: Uuid) -> Result<Vec<JobLog>> {
+        let logs = sqlx::query_as::<_, JobLog>(
+            "SELECT * FROM background_job_logs WHERE job_id = $1 ORDER BY id",
+        )
+        .bind(id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(logs)
+    }
+
+    /// Re-run a failed or cancelled job with its original payload.
+    /// Only possible while the process that created the job is still running,
+    /// since the task itself is not persisted, and for the last `MAX_RETRYABLE_JOBS`
+    /// failed or cancelled jobs.
+    pub async fn retry(&self, id: Uuid) -> Result<Job> {
+        let job = self.get(id).await?;
+        if !matches!(job.status, JobStatus::Failed | JobStatus::Cancelled) {
+            return Err(AppError::Conflict(format!("Job is {}, only failed or cancelled jobs can be retried", job.status)));
+        }
+
+        let task = self.tasks.read().await.tasks.get(&id).cloned().ok_or_else(|| {
+            AppError::Conflict("Job was created before the last server restart, or too long ago, and can no longer be retried".to_string())
+        })?;
+
+        sqlx::query(
+            r#"
+            UPDATE background_jobs
+            SET status = 'queued', attempts = 0, progress = 0, message = NULL, error = NULL,
+                result = NULL, started_at = NULL, finished_at = NULL
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .execute(&self.db)
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23505") => {
+                AppError::Conflict(format!("A {} job is already in progress", job.kind))
+            }
+            _ => AppError::Database(e),
+        })?;
+
+        append_log(&self.db, id, "info", "Retry requested").await;
+        self.tasks.write().await.start_retry(id);
+        self.spawn_runner(id, job.kind, job.payload, job.max_attempts, task).await;
+
+        self.get(id).await
+    }
+
+    /// The queued or running job of the given kind, if any
+    pub async fn find_active(&self, kind: JobKind) -> Result<Option<Job>> {
+        Ok(sqlx::query_as::<_, Job>(
+            "SELECT * FROM background_jobs WHERE kind = $1 AND status IN ('queued', 'running')",
+        )
+        .bind(kind)
+        .fetch_optional(&self.db)
+        .await?)
+    }
+
+    async fn spawn_runner(
+        &self,
+        id: Uuid,
+        kind: JobKind,
+        payload: serde_json::Value,
+        max_attempts: i32,
+        task: JobTask,
+    ) {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.cancel_flags.write().await.insert(id, cancelled.clone());
+
+        let manager = self.clone();
+        tokio::spawn(async move {
+            manager.run(id, kind, payload, max_attempts, task, cancelled).await;
+        });
+    }
+
+    async fn run(
+        &self,
+        id: Uuid,
+        kind: JobKind,
+        payload: serde_json::Value,
+        max_attempts: i32,
+        task: JobTask,
+        cancelled: Arc<AtomicBool>,
+    ) {
+        let mut attempt = 0;
+
+        let outcome = loop {
+            attempt += 1;
+
+            if let Err(e) = sqlx::query(
+                r#"
+                UPDATE background_jobs
+                SET status = 'running', attempts = $2, error = NULL,
+                    started_at = COALESCE(started_at, NOW())
+                WHERE id = $1
+                "#,
+            )
+            .bind(id)
+            .bind(attempt)
+            .execute(&self.db)
+            .await
+            {
+                tracing::error!("Failed to mark job {} as running: {}", id, e);
+            }
+            self.publish(id, kind, JobStatus::Running, 0.0, None);
+            append_log(&self.db, id, "info", &format!("Attempt {}/{} started", attempt, max_attempts)).await;
+
+            let ctx = JobContext {
+                id,
+                kind,
+                payload: payload.clone(),
+                attempt,
+                db: self.db.clone(),
+                cancelled: cancelled.clone(),
+                updates: self.updates.clone(),
+            };
+
+            match task(ctx).await {
+                Ok(result) => break Ok(result),
+                Err(_) if cancelled.load(Ordering::Relaxed) => break Err(None),
+                Err(e) if attempt < max_attempts => {
+                    let delay = retry_backoff(attempt);
+                    tracing::warn!(
+                        "{} job {} failed (attempt {}/{}): {}. Retrying in {:?}",
+                        kind, id, attempt, max_attempts, e, delay
+                    );
+                    let _ = sqlx::query("UPDATE background_jobs SET error = $2 WHERE id = $1")
+                        .bind(id)
+                        .bind(e.to_string())
+                        .execute(&self.db)
+                        .await;
+                    append_log(
+                        &self.db,
+                        id,
+                        "warn",
+                        &format!("Attempt {} failed: {}. Retrying in {:?}", attempt, e, delay),
+                    )
+                    .await;
+
+                    tokio::time::sleep(delay).await;
+                    if cancelled.load(Ordering::Relaxed) {
+                        break Err(None);
+                    }
+                }
+                Err(e) => break Err(Some(e.to_string())),
+            }
+        };
+
+        match outcome {
+            Ok(result) => {
+                tracing::info!("{} job {} completed", kind, id);
+                append_log(&self.db, id, "info", "Completed").await;
+                if matches!(kind, JobKind::EmbeddingIndex | JobKind::EmbeddingMigration) {
+                    self.webhooks.dispatch(
+                        WebhookEvent::EmbeddingsCompleted,
+                        serde_json::json!({ "job_id": id, "kind": kind, "result": result }),
+                    );
+                }
+                self.finish(id, kind, JobStatus::Completed, Some(result), None).await;
+                self.tasks.write().await.remove(id);
+            }
+            Err(None) => {
+                tracing::info!("{} job {} cancelled", kind, id);
+                append_log(&self.db, id, "warn", "Cancelled").await;
+                self.finish(id, kind, JobStatus::Cancelled, None, None).await;
+                self.tasks.write().await.keep_for_retry(id);
+            }
+            Err(Some(error)) => {
+                append_log(&self.db, id, "error", &format!("Failed: {}", error)).await;
+                self.webhooks.dispatch(
+                    WebhookEvent::Error,
+                    serde_json::json!({ "source": "job", "job_id": id, "kind": kind, "message": error }),
+                );
+                tracing::error!("{} job {} failed: {}", kind, id, error);
+                self.finish(id, kind, JobStatus::Failed, None, Some(error)).await;
+                self.tasks.write().await.keep_for_retry(id);
+            }
+        }
+
+        self.cancel_flags.write().await.remove(&id);
+    }
+
+    async fn finish(
+        &self,
+        id: Uuid,
+        kind: JobKind,
+        status: JobStatus,
+        result: Option<serde_json::Value>,
+        error: Option<String>,
+    ) {
+        let progress = if status == JobStatus::Completed { 1.0 } else { 0.0 };
+
+        if let Err(e) = sqlx::query(
+            r#"
+            UPDATE background_jobs
+            SET status = $2, result = $3, error = COALESCE($4, error), finished_at = NOW(),
+                progress = GREATEST(progress, $5)
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(status)
+        .bind(result)
+        .bind(error)
+        .bind(progress)
+        .execute(&self.db)
+        .await
+        {
+            tracing::error!("Failed to record final state of job {}: {}", id, e);
+        }
+
+        self.publish(id, kind, status, progress, None);
+    }
+
+    fn publish(&self, id: Uuid, kind: JobKind, status: JobStatus, progress: f64, message: Option<String>) {
+        let _ = self.updates.send(JobUpdate {
+            job_id: id,
+            kind,
+            status,
+            progress,
+            message,
+        });
+    }
+}
+
+/// Append a log line for a job. Failures are logged, not returned.
+async fn append_log(db: &PgPool, job_id: Uuid, level: &str, message: &str) {
+    if let Err(e) = sqlx::query(
+        "INSERT INTO background_job_logs (job_id, level, message) VALUES ($1, $2, $3)",
+    )
+    .bind(job_id)
+    .bind(level)
+    .bind(message)
+    .execute(db)
+    .await
+    {
+        tracing::warn!("Failed to write log for job {}: {}", job_id, e);
+    }
+}
+
+/// Backoff delay before the retry following the given (1-based) attempt
+fn retry_backoff(attempt: i32) -> Duration {
+    RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1).max(0) as u32))
+        .min(RETRY_MAX_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_recent_failed_jobs_keep_their_tasks() {
+        let task: JobTask = Arc::new(|_| Box::pin(async { Ok(serde_json::Value::Null) }));
+        let mut store = TaskStore::default();
+        let ids: Vec<Uuid> = (0..=MAX_RETRYABLE_JOBS).map(|_| Uuid::new_v4()).collect();
+        for id in &ids {
+            store.insert(*id, task.clone());
+            store.keep_for_retry(*id);
+        }
+        assert_eq!(store.tasks.len(), MAX_RETRYABLE_JOBS);
+        assert!(!store.tasks.contains_key(&ids[0]));
+
+        // A job being retried isn't dropped while it runs, even as newer failures push
+        // older ones out
+        store.start_retry(ids[1]);
+        for _ in 0..2 {
+            let failed = Uuid::new_v4();
+            store.insert(failed, task.clone());
+            store.keep_for_retry(failed);
+        }
+        assert!(store.tasks.contains_key(&ids[1]));
+        assert!(!store.tasks.contains_key(&ids[2]));
+
+        store.remove(ids[1]);
+        assert!(!store.tasks.contains_key(&ids[1]));
+    }
+}
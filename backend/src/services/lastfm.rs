@@ -0,0 +1,591 @@
+//! Last.fm scrobbling and artist metadata
+//!
+//! Tracks that a station's broadcast pipeline plays long enough are scrobbled to the
+//! server's Last.fm account (`LASTFM_SESSION_KEY`, or a session created from
+//! `LASTFM_USERNAME`/`LASTFM_PASSWORD`), queued like ListenBrainz listens (see
+//! [`ScrobbleQueue`]) and sent in batches of up to 50.
+//!
+//! The artist metadata job fetches each library artist's top tags and similar artists
+//! into `external_metadata`. Curation uses them to find tracks for stations whose
+//! genres don't match the library's tags, which matters most for tracks that have no
+//! embeddings. Only `LASTFM_API_KEY` is needed for that.
+
+use crate::error::{AppError, Result};
+use crate::services::audio_pipeline::TrackState;
+use crate::services::jobs::JobContext;
+use crate::services::scrobble_queue::{required_listen_secs, ScrobbleQueue, SubmitError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, Notify};
+use uuid::Uuid;
+
+/// `service` of Last.fm rows in `scrobble_queue` and `source` in `external_metadata`
+pub const SERVICE: &str = "lastfm";
+
+pub const DEFAULT_API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+
+/// Scrobbles per `track.scrobble` request (Last.fm's limit)
+const BATCH_SIZE: i64 = 50;
+
+/// How often the flusher looks for due scrobbles when nothing wakes it
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Pause between metadata requests (Last.fm asks for at most 5 per second)
+const METADATA_REQUEST_DELAY: Duration = Duration::from_millis(250);
+
+/// Similar artists and tags kept per artist
+const SIMILAR_ARTISTS: usize = 50;
+const TOP_TAGS: usize = 10;
+
+/// Artist metadata older than this is fetched again
+const METADATA_MAX_AGE_DAYS: i32 = 30;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Last.fm error codes that are worth retrying: service offline, temporarily
+/// unavailable, rate limit exceeded, invalid session key
+const RETRYABLE_ERRORS: &[i64] = &[9, 11, 16, 29];
+
+/// Last.fm error code for an unknown artist
+const ERROR_NOT_FOUND: i64 = 6;
+
+#[derive(Debug, Clone, Default)]
+pub struct LastFmConfig {
+    pub api_key: String,
+    pub api_secret: Option<String>,
+    pub session_key: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub api_url: String,
+}
+
+/// One queued scrobble
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Scrobble {
+    artist: String,
+    track: String,
+    album: Option<String>,
+    timestamp: i64,
+    duration: Option<i64>,
+}
+
+/// Outcome of a metadata run, recorded as the job result
+#[derive(Debug, Default, Serialize)]
+pub struct ArtistMetadataSummary {
+    pub fetched: usize,
+    /// Artists Last.fm doesn't know; stored empty so they aren't asked for again soon
+    pub not_found: usize,
+    pub failed: usize,
+}
+
+/// A Last.fm error response
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    error: i64,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SimilarArtistsResponse {
+    similarartists: SimilarArtists,
+}
+
+#[derive(Debug, Deserialize)]
+struct SimilarArtists {
+    #[serde(default)]
+    artist: Vec<SimilarArtist>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SimilarArtist {
+    name: String,
+    /// Sent as a string
+    #[serde(rename = "match")]
+    score: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopTagsResponse {
+    toptags: TopTags,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopTags {
+    #[serde(default)]
+    tag: Vec<Tag>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Tag {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionResponse {
+    session: Session,
+}
+
+#[derive(Debug, Deserialize)]
+struct Session {
+    key: String,
+}
+
+pub struct LastFmClient {
+    db: PgPool,
+    http: reqwest::Client,
+    config: LastFmConfig,
+    /// Session key of the scrobbling account, created on first use from the
+    /// username and password if none is configured
+    session_key: Mutex<Option<String>>,
+    queue: ScrobbleQueue,
+    /// Wakes the flusher when scrobbles are queued
+    queued: Notify,
+}
+
+impl LastFmClient {
+    pub fn new(db: PgPool, config: LastFmConfig) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            queue: ScrobbleQueue::new(db.clone(), SERVICE),
+            db,
+            http,
+            session_key: Mutex::new(config.session_key.clone()),
+            config,
+            queued: Notify::new(),
+        }
+    }
+
+    /// Whether scrobbling is configured: the API secret and either a session key or
+    /// the account's username and password
+    pub fn can_scrobble(&self) -> bool {
+        self.config.api_secret.is_some()
+            && (self.config.session_key.is_some()
+                || (self.config.username.is_some() && self.config.password.is_some()))
+    }
+
+    /// Queue a scrobble for a track the station's pipeline played from `started_at` to
+    /// `ended_at`. Returns false if it wasn't played long enough to count.
+    pub async fn track_played(
+        &self,
+        station_id: Uuid,
+        track: &TrackState,
+        started_at: DateTime<Utc>,
+        ended_at: DateTime<Utc>,
+    ) -> Result<bool> {
+        let indexed: Option<(String, i32)> =
+            sqlx::query_as("SELECT album, duration FROM library_index WHERE id = $1")
+                .bind(&track.track_id)
+                .fetch_optional(&self.db)
+                .await?;
+        // The decoder doesn't always know the length when the track starts
+        let duration = match (track.duration_secs, &indexed) {
+            (secs, _) if secs > 0.0 => secs.round() as i64,
+            (_, Some((_, duration))) => *duration as i64,
+            _ => 0,
+        };
+
+        let Some(required) = required_listen_secs(duration) else {
+            return Ok(false);
+        };
+        if (ended_at - started_at).num_seconds() < required {
+            return Ok(false);
+        }
+        let album = indexed.map(|(album, _)| album);
+
+        let scrobble = Scrobble {
+            artist: track.artist.clone(),
+            track: track.title.clone(),
+            album: album.filter(|album| !album.is_empty()),
+            timestamp: started_at.timestamp(),
+            duration: Some(duration),
+        };
+        self.queue.push(None, station_id, &scrobble).await?;
+        self.queued.notify_one();
+        Ok(true)
+    }
+
+    /// Submit queued scrobbles in the background, whenever some are queued and
+    /// periodically for retries
+    pub fn spawn_flusher(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = self.queued.notified() => {}
+                }
+                match self.flush().await {
+                    Ok(0) => {}
+                    Ok(submitted) => tracing::debug!("Scrobbled {} track(s) to Last.fm", submitted),
+                    Err(e) => tracing::warn!("Last.fm flush failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Submit every due scrobble. Returns the number submitted.
+    pub async fn flush(&self) -> Result<usize> {
+        if !self.can_scrobble() {
+            // Scrobbling was configured when these were queued
+            self.queue.discard_account(None).await?;
+            return Ok(0);
+        }
+
+        let mut submitted = 0;
+        loop {
+            let batch: Vec<(i64, Scrobble)> = self.queue.claim(None, BATCH_SIZE).await?;
+            if batch.is_empty() {
+                break;
+            }
+            let (ids, scrobbles): (Vec<i64>, Vec<Scrobble>) = batch.into_iter().unzip();
+
+            let outcome = self.scrobble(&scrobbles).await;
+            let succeeded = outcome.is_ok();
+            if !self.queue.finish(&ids, outcome).await? {
+                break;
+            }
+            if succeeded {
+                submitted += ids.len();
+            }
+        }
+        Ok(submitted)
+    }
+
+    async fn scrobble(&self, scrobbles: &[Scrobble]) -> std::result::Result<(), SubmitError> {
+        let session_key = self.session_key().await.map_err(|e| SubmitError::Failed(e.to_string()))?;
+
+        let mut params = vec![
+            ("method".to_string(), "track.scrobble".to_string()),
+            ("sk".to_string(), session_key),
+        ];
+        for (i, scrobble) in scrobbles.iter().enumerate() {
+            params.push((format!("artist[{}]", i), scrobble.artist.clone()));
+            params.push((format!("track[{}]", i), scrobble.track.clone()));
+            params.push((format!("timestamp[{}]", i), scrobble.timestamp.to_string()));
+            // Played by the station, not picked by the listener
+            params.push((format!("chosenByUser[{}]", i), "0".to_string()));
+            if let Some(album) = &scrobble.album {
+                params.push((format!("album[{}]", i), album.clone()));
+            }
+            if let Some(duration) = scrobble.duration {
+                params.push((format!("duration[{}]", i), duration.to_string()));
+            }
+        }
+
+        match self.signed_post(params).await {
+            Ok(_) => Ok(()),
+            Err(ApiCallError::Api(e)) if e.error == 9 => {
+                // Session revoked: create a new one next time if we can
+                if self.config.session_key.is_none() {
+                    *self.session_key.lock().await = None;
+                }
+                Err(SubmitError::Failed(e.message))
+            }
+            Err(ApiCallError::Api(e)) if RETRYABLE_ERRORS.contains(&e.error) => Err(SubmitError::Failed(e.message)),
+            Err(ApiCallError::Api(e)) => Err(SubmitError::Rejected(format!("{} ({})", e.message, e.error))),
+            Err(ApiCallError::Request(e)) => Err(SubmitError::Failed(e)),
+        }
+    }
+
+    /// The scrobbling account's session key, creating a session if needed
+    async fn session_key(&self) -> Result<String> {
+        let mut session_key = self.session_key.lock().await;
+        if let Some(key) = session_key.as_ref() {
+            return Ok(key.clone());
+        }
+
+        let (Some(username), Some(password)) = (&self.config.username, &self.config.password) else {
+            return Err(AppError::NotConfigured("Last.fm scrobbling is not configured".to_string()));
+        };
+        let response = self
+            .signed_post(vec![
+                ("method".to_string(), "auth.getMobileSession".to_string()),
+                ("username".to_string(), username.clone()),
+                ("password".to_string(), password.clone()),
+            ])
+            .await
+            .map_err(|e| AppError::ExternalApi(format!("Last.fm login failed: {}", e)))?;
+        let session: SessionResponse = serde_json::from_value(response)?;
+
+        tracing::info!("Created Last.fm session for '{}'", username);
+        *session_key = Some(session.session.key.clone());
+        Ok(session.session.key)
+    }
+
+    /// POST a signed (write) API call
+    async fn signed_post(&self, mut params: Vec<(String, String)>) -> std::result::Result<serde_json::Value, ApiCallError> {
+        let secret = self
+            .config
+            .api_secret
+            .as_deref()
+            .ok_or_else(|| ApiCallError::Request("LASTFM_API_SECRET is not set".to_string()))?;
+        params.push(("api_key".to_string(), self.config.api_key.clone()));
+        let signature = sign(&params, secret);
+        params.push(("api_sig".to_string(), signature));
+        params.push(("format".to_string(), "json".to_string()));
+
+        let request = self.http.post(&self.config.api_url).form(&params);
+        self.send(request).await
+    }
+
+    /// GET an unsigned (read) API call
+    async fn get(&self, params: &[(&str, &str)]) -> std::result::Result<serde_json::Value, ApiCallError> {
+        let request = self
+            .http
+            .get(&self.config.api_url)
+            .query(params)
+            .query(&[("api_key", self.config.api_key.as_str()), ("format", "json")]);
+        self.send(request).await
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> std::result::Result<serde_json::Value, ApiCallError> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ApiCallError::Request(e.to_string()))?;
+        let status = response.status();
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| ApiCallError::Request(format!("{}: unexpected response: {}", status, e)))?;
+
+        // Errors come back as JSON, with or without an error status
+        if let Ok(error) = serde_json::from_value::<ApiError>(body.clone()) {
+            return Err(ApiCallError::Api(error));
+        }
+        if !status.is_success() {
+            return Err(ApiCallError::Request(status.to_string()));
+        }
+        Ok(body)
+    }
+
+    /// Fetch tags and similar artists of up to `limit` library artists that have none
+    /// stored yet (or only old ones)
+    pub async fn fetch_artist_metadata(&self, ctx: &JobContext, limit: i64) -> Result<ArtistMetadataSummary> {
+        let artists: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT a.artist
+            FROM (
+                SELECT DISTINCT ON (LOWER(artist)) artist FROM library_index WHERE artist <> ''
+            ) a
+            LEFT JOIN external_metadata m ON m.source = $1 AND m.artist_key = LOWER(a.artist)
+            WHERE m.fetched_at IS NULL OR m.fetched_at < NOW() - make_interval(days => $2)
+            ORDER BY m.fetched_at NULLS FIRST, a.artist
+            LIMIT $3
+            "#,
+        )
+        .bind(SERVICE)
+        .bind(METADATA_MAX_AGE_DAYS)
+        .bind(limit)
+        .fetch_all(&self.db)
+        .await?;
+
+        let total = artists.len();
+        let mut summary = ArtistMetadataSummary::default();
+        ctx.log("info", format!("Fetching Last.fm metadata of {} artists", total)).await;
+
+        for (i, artist) in artists.into_iter().enumerate() {
+            if ctx.is_cancelled() {
+                ctx.log("info", "Cancelled").await;
+                break;
+            }
+            ctx.progress(i as f64 / total as f64, artist.clone()).await;
+
+            let (tags, similar) = match self.artist_metadata(&artist).await {
+                Ok(metadata) => {
+                    summary.fetched += 1;
+                    metadata
+                }
+                Err(ApiCallError::Api(e)) if e.error == ERROR_NOT_FOUND => {
+                    summary.not_found += 1;
+                    (Vec::new(), serde_json::json!([]))
+                }
+                Err(e) => {
+                    summary.failed += 1;
+                    ctx.log("warn", format!("Failed to fetch {}: {}", artist, e)).await;
+                    continue;
+                }
+            };
+
+            sqlx::query(
+                r#"
+                INSERT INTO external_metadata (source, artist_key, artist, tags, similar_artists, fetched_at)
+                VALUES ($1, LOWER($2), $2, $3, $4, NOW())
+                ON CONFLICT (source, artist_key) DO UPDATE
+                SET artist = EXCLUDED.artist,
+                    tags = EXCLUDED.tags,
+                    similar_artists = EXCLUDED.similar_artists,
+                    fetched_at = NOW()
+                "#,
+            )
+            .bind(SERVICE)
+            .bind(&artist)
+            .bind(&tags)
+            .bind(&similar)
+            .execute(&self.db)
+            .await?;
+        }
+
+        ctx.progress(1.0, format!("Fetched {} artists", summary.fetched)).await;
+        Ok(summary)
+    }
+
+    /// An artist's lowercased top tags and similar artists (as stored)
+    async fn artist_metadata(&self, artist: &str) -> std::result::Result<(Vec<String>, serde_json::Value), ApiCallError> {
+        let similar_limit = SIMILAR_ARTISTS.to_string();
+        let response = self
+            .get(&[
+                ("method", "artist.getSimilar"),
+                ("artist", artist),
+                ("autocorrect", "1"),
+                ("limit", similar_limit.as_str()),
+            ])
+            .await?;
+        let similar: SimilarArtistsResponse =
+            serde_json::from_value(response).map_err(|e| ApiCallError::Request(e.to_string()))?;
+        tokio::time::sleep(METADATA_REQUEST_DELAY).await;
+
+        let response = self
+            .get(&[("method", "artist.getTopTags"), ("artist", artist), ("autocorrect", "1")])
+            .await?;
+        let tags: TopTagsResponse =
+            serde_json::from_value(response).map_err(|e| ApiCallError::Request(e.to_string()))?;
+        tokio::time::sleep(METADATA_REQUEST_DELAY).await;
+
+        let similar: Vec<serde_json::Value> = similar
+            .similarartists
+            .artist
+            .into_iter()
+            .map(|a| {
+                let score = match &a.score {
+                    serde_json::Value::String(s) => s.parse().unwrap_or(0.0),
+                    other => other.as_f64().unwrap_or(0.0),
+                };
+                serde_json::json!({ "name": a.name, "match": score })
+            })
+            .collect();
+        let tags = tags
+            .toptags
+            .tag
+            .into_iter()
+            .take(TOP_TAGS)
+            .map(|t| t.name.trim().to_lowercase())
+            .filter(|t| !t.is_empty())
+            .collect();
+
+        Ok((tags, serde_json::Value::Array(similar)))
+    }
+}
+
+#[derive(Debug)]
+enum ApiCallError {
+    /// Last.fm answered with an error code
+    Api(ApiError),
+    /// The request failed or the response couldn't be read
+    Request(String),
+}
+
+impl std::fmt::Display for ApiCallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiCallError::Api(e) => write!(f, "{} (error {})", e.message, e.error),
+            ApiCallError::Request(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// `api_sig` of a call: MD5 of its parameters sorted by name and concatenated as
+/// name + value, followed by the secret (`format` and `callback` are left out)
+fn sign(params: &[(String, String)], secret: &str) -> String {
+    let mut sorted: Vec<&(String, String)> = params
+        .iter()
+        .filter(|(name, _)| name != "format" && name != "callback")
+        .collect();
+    sorted.sort();
+
+    let mut raw = String::new();
+    for (name, value) in sorted {
+        raw.push_str(name);
+        raw.push_str(value);
+    }
+    raw.push_str(secret);
+    format!("{:x}", md5::compute(raw))
+}
+
+/// Library artists that Last.fm tags with any of `tags`, in random order
+pub async fn artists_tagged(db: &PgPool, tags: &[String], limit: usize) -> Result<Vec<String>> {
+    let tags: Vec<String> = tags.iter().map(|t| t.trim().to_lowercase()).collect();
+    let artists = sqlx::query_scalar(
+        r#"
+        SELECT m.artist
+        FROM external_metadata m
+        WHERE m.source = $1 AND m.tags && $2
+          AND EXISTS (SELECT 1 FROM library_index li WHERE LOWER(li.artist) = m.artist_key)
+        ORDER BY random()
+        LIMIT $3
+        "#,
+    )
+    .bind(SERVICE)
+    .bind(&tags)
+    .bind(limit as i64)
+    .fetch_all(db)
+    .await?;
+    Ok(artists)
+}
+
+/// Library artists that Last.fm considers similar to any of `artists`, best match
+/// first, spelled as in the library
+pub async fn similar_artists(db: &PgPool, artists: &[String], limit: usize) -> Result<Vec<String>> {
+    let keys: Vec<String> = artists.iter().map(|a| a.to_lowercase()).collect();
+    let similar = sqlx::query_scalar(
+        r#"
+        WITH similar AS (
+            SELECT LOWER(s->>'name') AS artist_key, MAX((s->>'match')::float8) AS score
+            FROM external_metadata m, jsonb_array_elements(m.similar_artists) s
+            WHERE m.source = $1 AND m.artist_key = ANY($2)
+            GROUP BY 1
+        )
+        SELECT li.artist
+        FROM similar
+        JOIN LATERAL (
+            SELECT artist FROM library_index WHERE LOWER(artist) = similar.artist_key LIMIT 1
+        ) li ON true
+        WHERE similar.artist_key <> ALL($2)
+        ORDER BY similar.score DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(SERVICE)
+    .bind(&keys)
+    .bind(limit as i64)
+    .fetch_all(db)
+    .await?;
+    Ok(similar)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        let params = vec![
+            ("method".to_string(), "auth.getMobileSession".to_string()),
+            ("username".to_string(), "radio".to_string()),
+            ("api_key".to_string(), "key".to_string()),
+            ("format".to_string(), "json".to_string()),
+        ];
+        let expected = format!(
+            "{:x}",
+            md5::compute("api_keykeymethodauth.getMobileSessionusernameradiosecret")
+        );
+        assert_eq!(sign(&params, "secret"), expected);
+    }
+}
@@ -2,18 +2,95 @@
 
 use crate::error::{AppError, Result};
 use crate::models::{
-    LibraryTrack, LibrarySyncStatus, TrackAnalysisRequest, TrackAnalysisResult,
+    JobKind, LibraryTrack, LibrarySyncStatus, TrackAnalysisRequest, TrackAnalysisResult,
 };
+use crate::services::jobs::JobManager;
+use crate::services::llm::{LlmClient, LlmFeature, LlmRequest};
+use crate::services::webhooks::WebhookDispatcher;
 use crate::services::navidrome::NavidromeClient;
 use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::Semaphore;
 use tracing::{error, info, warn};
 
+/// What an incremental sync changed
+#[derive(Debug, Default)]
+pub struct IncrementalSync {
+    /// Tracks that were not indexed before
+    pub new_tracks: Vec<String>,
+    pub updated: usize,
+    pub pruned: PrunedTracks,
+}
+
+impl IncrementalSync {
+    pub fn changed(&self) -> bool {
+        !self.new_tracks.is_empty() || self.updated > 0 || self.pruned.removed > 0
+    }
+}
+
+/// What removing tracks that disappeared from Navidrome changed
+#[derive(Debug, Default)]
+pub struct PrunedTracks {
+    /// Rows deleted from the index, with their embeddings and analysis
+    pub removed: usize,
+    /// Removed tracks that reappeared under another id (a moved or re-tagged file)
+    pub moved: usize,
+    /// Stations and program blocks whose track pools referenced a removed track
+    pub pools_updated: usize,
+}
+
+/// Replace moved tracks in a pool with their new ids and drop removed ones, keeping
+/// the order. A new id the pool already has isn't added twice.
+fn reconcile_pool(pool: &[String], removed: &HashSet<String>, moved: &HashMap<String, String>) -> Vec<String> {
+    let mut reconciled: Vec<String> = Vec::with_capacity(pool.len());
+    for id in pool {
+        if let Some(new_id) = moved.get(id) {
+            if !pool.contains(new_id) && !reconciled.contains(new_id) {
+                reconciled.push(new_id.clone());
+            }
+        } else if !removed.contains(id) {
+            reconciled.push(id.clone());
+        }
+    }
+    reconciled
+}
+
+/// The Navidrome metadata of an indexed track, to tell whether it changed
+#[derive(Debug, PartialEq)]
+struct TrackFingerprint {
+    title: String,
+    artist: String,
+    album: String,
+    year: Option<i32>,
+    duration: i32,
+    genres: serde_json::Value,
+    path: Option<String>,
+}
+
+/// A `library_index` id followed by the columns of its fingerprint
+type FingerprintRow = (String, String, String, String, Option<i32>, i32, serde_json::Value, Option<String>);
+
+impl TrackFingerprint {
+    fn of(track: &crate::models::Track) -> Self {
+        Self {
+            title: track.title.clone(),
+            artist: track.artist.clone(),
+            album: track.album.clone(),
+            year: track.year,
+            duration: track.duration,
+            genres: serde_json::to_value(&track.genre).unwrap_or_default(),
+            path: Some(track.path.clone()),
+        }
+    }
+}
+
 pub struct LibraryIndexer {
     db: PgPool,
     navidrome_client: Arc<NavidromeClient>,
     ai_analyzer: Option<Arc<TrackAnalyzer>>,
+    jobs: Arc<JobManager>,
+    webhooks: WebhookDispatcher,
     max_concurrent_ai_calls: usize,
 }
 
@@ -22,11 +99,15 @@ impl LibraryIndexer {
         db: PgPool,
         navidrome_client: Arc<NavidromeClient>,
         ai_analyzer: Option<Arc<TrackAnalyzer>>,
+        jobs: Arc<JobManager>,
+        webhooks: WebhookDispatcher,
     ) -> Self {
         Self {
             db,
             navidrome_client,
             ai_analyzer,
+            jobs,
+            webhooks,
             max_concurrent_ai_calls: 5, // Process 5 tracks concurrently
         }
     }
@@ -47,40 +128,60 @@ impl LibraryIndexer {
         self.update_sync_status(true, None).await?;
 
         match self.perform_full_sync(progress_tx.clone()).await {
-            Ok(total_tracks) => {
+            Ok((total_tracks, pruned)) => {
                 info!("Full library sync completed successfully");
                 self.update_sync_status(false, None).await?;
 
                 // Send completed event
                 if let Some(tx) = &progress_tx {
-                    let _ = tx.send(crate::models::SyncProgress::Completed {
-                        total_tracks,
-                        message: format!("Library sync completed successfully. {} tracks synced.", total_tracks),
-                    });
+                    let mut message = format!("Library sync completed successfully. {} tracks synced.", total_tracks);
+                    if pruned.removed > 0 {
+                        message.push_str(&format!(" {} tracks no longer in Navidrome were removed.", pruned.removed));
+                    }
+                    let _ = tx.send(crate::models::SyncProgress::Completed { total_tracks, message });
                 }
 
-                // Update stats in background (don't block completion)
+                self.webhooks.dispatch(
+                    crate::models::WebhookEvent::SyncCompleted,
+                    serde_json::json!({
+                        "total_tracks": total_tracks,
+                        "removed_tracks": pruned.removed,
+                        "moved_tracks": pruned.moved,
+                    }),
+                );
+
+                // Update stats as a separate background job (don't block completion)
                 info!("Library sync complete. Stats computation will run in background.");
                 let db_clone = self.db.clone();
                 let progress_tx_clone = progress_tx;
-                tokio::spawn(async move {
-                    info!("Computing library statistics...");
-
-                    // Send computing stats event
-                    if let Some(tx) = &progress_tx_clone {
-                        let _ = tx.send(crate::models::SyncProgress::ComputingStats {
-                            message: "Computing library statistics...".to_string(),
-                        });
-                    }
+                let submitted = self
+                    .jobs
+                    .submit(JobKind::LibraryStats, serde_json::json!({}), 1, move |_ctx| {
+                        let db = db_clone.clone();
+                        let progress_tx = progress_tx_clone.clone();
+                        async move {
+                            info!("Computing library statistics...");
+
+                            // Send computing stats event
+                            if let Some(tx) = &progress_tx {
+                                let _ = tx.send(crate::models::SyncProgress::ComputingStats {
+                                    message: "Computing library statistics...".to_string(),
+                                });
+                            }
+
+                            sqlx::query("SELECT update_library_stats()")
+                                .execute(&db)
+                                .await?;
+                            info!("Library statistics updated successfully");
+
+                            Ok(serde_json::json!({}))
+                        }
+                    })
+                    .await;
 
-                    match sqlx::query!("SELECT update_library_stats()")
-                        .execute(&db_clone)
-                        .await
-                    {
-                        Ok(_) => info!("Library statistics updated successfully"),
-                        Err(e) => error!("Failed to update library statistics: {}", e),
-                    }
-                });
+                if let Err(e) = submitted {
+                    warn!("Could not schedule library statistics update: {}", e);
+                }
 
                 Ok(())
             }
@@ -88,6 +189,11 @@ impl LibraryIndexer {
                 error!("Full library sync failed: {}", e);
                 self.update_sync_status(false, Some(e.to_string())).await?;
 
+                self.webhooks.dispatch(
+                    crate::models::WebhookEvent::Error,
+                    serde_json::json!({ "source": "library_sync", "message": e.to_string() }),
+                );
+
                 // Send error event
                 if let Some(tx) = &progress_tx {
                     let _ = tx.send(crate::models::SyncProgress::Error {
@@ -100,12 +206,221 @@ impl LibraryIndexer {
         }
     }
 
-    async fn perform_full_sync(&self, progress_tx: Option<tokio::sync::broadcast::Sender<crate::models::SyncProgress>>) -> Result<usize> {
+    /// Bring the index up to date with the changes Navidrome made since the last sync:
+    /// add new tracks, rewrite changed ones and delete removed ones, leaving unchanged
+    /// rows (and their analysis) alone. Nothing is fetched beyond a single request when
+    /// Navidrome reports no library change since then.
+    /// Falls back to a full sync if the library has never been synced.
+    pub async fn sync_incremental(&self) -> Result<IncrementalSync> {
+        let status = self.get_sync_status().await?;
+        if status.sync_in_progress {
+            warn!("Sync already in progress, skipping");
+            return Ok(IncrementalSync::default());
+        }
+
+        let Some(since) = status.last_full_sync.max(status.last_incremental_sync) else {
+            info!("Library has never been synced, running a full sync instead");
+            self.sync_full(None).await?;
+            return Ok(IncrementalSync::default());
+        };
+
+        info!("Starting incremental library sync (changes after {})", since);
+        self.update_sync_status(true, None).await?;
+        let started_at = chrono::Utc::now();
+
+        match self.perform_incremental_sync(since).await {
+            Ok(sync) => {
+                info!(
+                    "Incremental library sync completed: {} new, {} updated, {} removed tracks",
+                    sync.new_tracks.len(),
+                    sync.updated,
+                    sync.pruned.removed
+                );
+                self.update_sync_status(false, None).await?;
+                sqlx::query("UPDATE library_sync_status SET last_incremental_sync = $1 WHERE id = 1")
+                    .bind(started_at)
+                    .execute(&self.db)
+                    .await?;
+
+                if sync.changed() {
+                    if let Err(e) = self.update_library_stats().await {
+                        warn!("Could not update library statistics: {}", e);
+                    }
+                }
+
+                self.webhooks.dispatch(
+                    crate::models::WebhookEvent::SyncCompleted,
+                    serde_json::json!({
+                        "incremental": true,
+                        "new_tracks": sync.new_tracks.len(),
+                        "updated_tracks": sync.updated,
+                        "removed_tracks": sync.pruned.removed,
+                        "moved_tracks": sync.pruned.moved,
+                    }),
+                );
+
+                Ok(sync)
+            }
+            Err(e) => {
+                error!("Incremental library sync failed: {}", e);
+                self.update_sync_status(false, Some(e.to_string())).await?;
+
+                self.webhooks.dispatch(
+                    crate::models::WebhookEvent::Error,
+                    serde_json::json!({ "source": "library_sync", "message": e.to_string() }),
+                );
+
+                Err(e)
+            }
+        }
+    }
+
+    async fn perform_incremental_sync(&self, since: chrono::DateTime<chrono::Utc>) -> Result<IncrementalSync> {
+        // Allow for the clocks of Navidrome and this server disagreeing a little
+        let checked_since = since - chrono::Duration::minutes(5);
+        if !self.navidrome_client.library_modified_since(checked_since).await? {
+            info!("Navidrome library unchanged since {}, nothing to sync", since);
+            return Ok(IncrementalSync::default());
+        }
+
+        let rows: Vec<FingerprintRow> =
+            sqlx::query_as("SELECT id, title, artist, album, year, duration, genres, path FROM library_index")
+                .fetch_all(&self.db)
+                .await?;
+        let mut indexed: HashMap<String, TrackFingerprint> = rows
+            .into_iter()
+            .map(|(id, title, artist, album, year, duration, genres, path)| {
+                (id, TrackFingerprint { title, artist, album, year, duration, genres, path })
+            })
+            .collect();
+
+        let page_size = 500;
+        let mut offset = 0;
+        let mut sync = IncrementalSync::default();
+
+        loop {
+            let (tracks, total_count) = self.navidrome_client.get_all_songs_paginated(page_size, offset).await?;
+            if tracks.is_empty() {
+                break;
+            }
+
+            for track in &tracks {
+                match indexed.remove(&track.id) {
+                    None => {
+                        self.upsert_track(track).await?;
+                        sync.new_tracks.push(track.id.clone());
+                    }
+                    Some(fingerprint) if fingerprint != TrackFingerprint::of(track) => {
+                        self.upsert_track(track).await?;
+                        sync.updated += 1;
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            offset += page_size;
+            if total_count > 0 && offset >= total_count {
+                break;
+            }
+        }
+
+        // Whatever wasn't seen in the walk is gone from Navidrome. An empty walk more likely
+        // means Navidrome is misbehaving than that the whole library was deleted.
+        if offset == 0 && !indexed.is_empty() {
+            return Err(AppError::Navidrome(
+                "Navidrome returned no tracks; not removing the indexed library".to_string(),
+            ));
+        }
+        sync.pruned = self.prune_tracks(indexed.into_keys().collect()).await?;
+
+        Ok(sync)
+    }
+
+    /// Remove tracks Navidrome no longer has from the index. Their embeddings, analysis
+    /// and listening history go with them (the foreign keys cascade); curated station
+    /// pools and program blocks stop referencing them, and pending requests for them
+    /// expire. A removed track whose title, artist and album now show up under another
+    /// id, within a couple of seconds of its duration, was most likely moved or
+    /// re-tagged: pools get the new id in its place rather than losing the track.
+    /// Must run after the sync upserted the tracks it found.
+    async fn prune_tracks(&self, removed: Vec<String>) -> Result<PrunedTracks> {
+        let mut pruned = PrunedTracks::default();
+        if removed.is_empty() {
+            return Ok(pruned);
+        }
+
+        let moved: HashMap<String, String> = sqlx::query_as::<_, (String, String)>(
+            r#"
+            SELECT DISTINCT ON (old.id) old.id, moved.id
+            FROM library_index old
+            JOIN library_index moved
+              ON LOWER(moved.title) = LOWER(old.title)
+             AND LOWER(moved.artist) = LOWER(old.artist)
+             AND LOWER(moved.album) = LOWER(old.album)
+             AND ABS(moved.duration - old.duration) <= 2
+            WHERE old.id = ANY($1) AND NOT (moved.id = ANY($1))
+            ORDER BY old.id, moved.last_synced DESC
+            "#,
+        )
+        .bind(&removed)
+        .fetch_all(&self.db)
+        .await?
+        .into_iter()
+        .collect();
+        pruned.moved = moved.len();
+
+        let removed_set: HashSet<String> = removed.iter().cloned().collect();
+        let mut tx = self.db.begin().await?;
+
+        for table in ["stations", "station_schedule_blocks"] {
+            let pools: Vec<(uuid::Uuid, sqlx::types::Json<Vec<String>>)> =
+                sqlx::query_as(&format!("SELECT id, track_ids FROM {} WHERE track_ids ?| $1", table))
+                    .bind(&removed)
+                    .fetch_all(&mut *tx)
+                    .await?;
+            for (id, pool) in pools {
+                let reconciled = reconcile_pool(&pool.0, &removed_set, &moved);
+                sqlx::query(&format!("UPDATE {} SET track_ids = $2, updated_at = NOW() WHERE id = $1", table))
+                    .bind(id)
+                    .bind(serde_json::to_value(&reconciled)?)
+                    .execute(&mut *tx)
+                    .await?;
+                pruned.pools_updated += 1;
+            }
+        }
+
+        sqlx::query("UPDATE track_requests SET status = 'expired' WHERE status = 'pending' AND track_id = ANY($1)")
+            .bind(&removed)
+            .execute(&mut *tx)
+            .await?;
+
+        pruned.removed = sqlx::query("DELETE FROM library_index WHERE id = ANY($1)")
+            .bind(&removed)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected() as usize;
+
+        tx.commit().await?;
+
+        info!(
+            "Pruned {} tracks missing from Navidrome ({} moved), updated {} track pools",
+            pruned.removed, pruned.moved, pruned.pools_updated
+        );
+        Ok(pruned)
+    }
+
+    async fn perform_full_sync(
+        &self,
+        progress_tx: Option<tokio::sync::broadcast::Sender<crate::models::SyncProgress>>,
+    ) -> Result<(usize, PrunedTracks)> {
         // Use paginated API to get ALL songs from Navidrome
         let page_size = 500;
         let mut offset = 0;
         let mut total_synced = 0;
         let mut total_count = 0;
+        // Every id Navidrome returned, and whether the walk got through all pages
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut complete = true;
 
         info!("Starting full library sync using paginated API");
 
@@ -122,6 +437,7 @@ impl LibraryIndexer {
                 Ok(result) => result,
                 Err(e) => {
                     warn!("Failed to fetch tracks at offset {}: {}", offset, e);
+                    complete = false;
                     break;
                 }
             };
@@ -148,6 +464,7 @@ impl LibraryIndexer {
 
             // Upsert all tracks
             for track in &tracks {
+                seen.insert(track.id.clone());
                 if let Err(e) = self.upsert_track(track).await {
                     warn!("Failed to upsert track {}: {}", track.id, e);
                 } else {
@@ -195,18 +512,33 @@ impl LibraryIndexer {
         .execute(&self.db)
         .await?;
 
-        Ok(total_synced)
+        // Only a walk that saw every page can tell which tracks are gone
+        let pruned = if complete && !seen.is_empty() {
+            let indexed: Vec<String> = sqlx::query_scalar("SELECT id FROM library_index")
+                .fetch_all(&self.db)
+                .await?;
+            let missing = indexed.into_iter().filter(|id| !seen.contains(id)).collect();
+            self.prune_tracks(missing).await?
+        } else {
+            if !complete {
+                warn!("Library walk was incomplete, not removing tracks missing from it");
+            }
+            PrunedTracks::default()
+        };
+
+        Ok((total_synced, pruned))
     }
 
     async fn upsert_track(&self, track: &crate::models::Track) -> Result<()> {
         let genres_json = serde_json::to_value(&track.genre)?;
 
-        sqlx::query!(
+        // An explicit tag overrides AI analysis' classification; untagged tracks keep it
+        sqlx::query(
             r#"
             INSERT INTO library_index (
-                id, title, artist, album, year, duration, genres, path, last_synced
+                id, title, artist, album, year, duration, genres, path, explicit, last_synced
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NOW())
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW())
             ON CONFLICT (id) DO UPDATE SET
                 title = EXCLUDED.title,
                 artist = EXCLUDED.artist,
@@ -215,23 +547,66 @@ impl LibraryIndexer {
                 duration = EXCLUDED.duration,
                 genres = EXCLUDED.genres,
                 path = EXCLUDED.path,
+                explicit = COALESCE(EXCLUDED.explicit, library_index.explicit),
                 last_synced = NOW()
             "#,
-            track.id,
-            track.title,
-            track.artist,
-            track.album,
-            track.year,
-            track.duration,
-            genres_json,
-            track.path
         )
+        .bind(&track.id)
+        .bind(&track.title)
+        .bind(&track.artist)
+        .bind(&track.album)
+        .bind(track.year)
+        .bind(track.duration)
+        .bind(genres_json)
+        .bind(&track.path)
+        .bind(track.explicit())
         .execute(&self.db)
         .await?;
 
         Ok(())
     }
 
+    /// Start a background job analyzing up to `limit` unanalyzed tracks (retried once
+    /// on failure). Returns the job ID.
+    pub async fn submit_ai_analysis(self: &Arc<Self>, limit: usize) -> Result<uuid::Uuid> {
+        let indexer = self.clone();
+        self.jobs
+            .submit(JobKind::AiAnalysis, serde_json::json!({ "limit": limit }), 2, move |_ctx| {
+                let indexer = indexer.clone();
+                async move {
+                    let analyzed = indexer.analyze_unanalyzed_tracks(limit).await?;
+                    Ok(serde_json::json!({ "tracks_analyzed": analyzed }))
+                }
+            })
+            .await
+    }
+
+    /// Whether tracks can be analyzed at all (an LLM is configured)
+    pub fn can_analyze(&self) -> bool {
+        self.ai_analyzer.is_some()
+    }
+
+    /// Number of tracks waiting for AI analysis
+    pub async fn unanalyzed_count(&self) -> Result<i64> {
+        Ok(sqlx::query_scalar("SELECT COUNT(*) FROM library_index WHERE ai_analyzed = false")
+            .fetch_one(&self.db)
+            .await?)
+    }
+
+    /// Start a background job recomputing the library statistics. Returns the job ID.
+    pub async fn submit_stats_refresh(self: &Arc<Self>) -> Result<uuid::Uuid> {
+        let indexer = self.clone();
+        self.jobs
+            .submit(JobKind::LibraryStats, serde_json::json!({}), 1, move |_ctx| {
+                let indexer = indexer.clone();
+                async move {
+                    indexer.update_library_stats().await?;
+                    Ok(serde_json::json!({}))
+                }
+            })
+            .await
+    }
+
     /// Perform AI analysis on unanalyzed tracks
     pub async fn analyze_unanalyzed_tracks(&self, limit: usize) -> Result<usize> {
         if self.ai_analyzer.is_none() {
@@ -338,31 +713,35 @@ impl LibraryIndexer {
         let song_type_json = serde_json::to_value(&analysis.song_type)?;
         let themes_json = serde_json::to_value(&analysis.themes)?;
 
-        sqlx::query!(
+        // Features measured from the audio (features_source) win over the model's guesses, and
+        // an explicit tag over its classification
+        sqlx::query(
             r#"
             UPDATE library_index SET
                 mood_tags = $2,
-                energy_level = $3,
-                danceability = $4,
-                valence = $5,
+                energy_level = COALESCE(CASE WHEN features_source IS NOT NULL THEN energy_level END, $3),
+                danceability = COALESCE(CASE WHEN features_source IS NOT NULL THEN danceability END, $4),
+                valence = COALESCE(CASE WHEN features_source IS NOT NULL THEN valence END, $5),
                 song_type = $6,
                 themes = $7,
-                acousticness = $8,
-                instrumentalness = $9,
+                acousticness = COALESCE(CASE WHEN features_source IS NOT NULL THEN acousticness END, $8),
+                instrumentalness = COALESCE(CASE WHEN features_source IS NOT NULL THEN instrumentalness END, $9),
+                explicit = COALESCE(explicit, $10),
                 ai_analyzed = true,
                 last_ai_analysis = NOW()
             WHERE id = $1
             "#,
-            track_id,
-            mood_tags_json,
-            analysis.energy_level,
-            analysis.danceability,
-            analysis.valence,
-            song_type_json,
-            themes_json,
-            analysis.acousticness,
-            analysis.instrumentalness
         )
+        .bind(track_id)
+        .bind(mood_tags_json)
+        .bind(analysis.energy_level)
+        .bind(analysis.danceability)
+        .bind(analysis.valence)
+        .bind(song_type_json)
+        .bind(themes_json)
+        .bind(analysis.acousticness)
+        .bind(analysis.instrumentalness)
+        .bind(analysis.explicit)
         .execute(db)
         .await?;
 
@@ -461,16 +840,12 @@ impl LibraryIndexer {
 
 /// AI-powered track analyzer using Claude
 pub struct TrackAnalyzer {
-    anthropic_api_key: String,
-    client: reqwest::Client,
+    llm: Arc<LlmClient>,
 }
 
 impl TrackAnalyzer {
-    pub fn new(anthropic_api_key: String) -> Self {
-        Self {
-            anthropic_api_key,
-            client: reqwest::Client::new(),
-        }
+    pub fn new(llm: Arc<LlmClient>) -> Self {
+        Self { llm }
     }
 
     pub async fn analyze_track(&self, request: TrackAnalysisRequest) -> Result<TrackAnalysisResult> {
@@ -491,6 +866,7 @@ Please analyze this track and provide:
 6. themes: List of themes (e.g., "love", "loss", "celebration", "introspection")
 7. acousticness: Float 0.0-1.0 (0 = electronic, 1 = acoustic)
 8. instrumentalness: Float 0.0-1.0 (0 = very vocal, 1 = purely instrumental)
+9. explicit: true if the lyrics contain profanity or explicit sexual, violent or drug-related content, false if they don't, null if you don't know the lyrics
 
 Respond with ONLY a JSON object in this exact format:
 {{
@@ -501,7 +877,8 @@ Respond with ONLY a JSON object in this exact format:
   "song_type": ["type1", "type2"],
   "themes": ["theme1", "theme2"],
   "acousticness": 0.3,
-  "instrumentalness": 0.1
+  "instrumentalness": 0.1,
+  "explicit": false
 }}"#,
             request.title,
             request.artist,
@@ -511,32 +888,13 @@ Respond with ONLY a JSON object in this exact format:
         );
 
         let response = self
-            .client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", &self.anthropic_api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("content-type", "application/json")
-            .json(&serde_json::json!({
-                "model": "claude-sonnet-4-5-20250929",
-                "max_tokens": 1024,
-                "messages": [{
-                    "role": "user",
-                    "content": prompt
-                }]
-            }))
-            .send()
-            .await
-            .map_err(|e| AppError::ExternalApi(format!("Failed to call Claude API: {}", e)))?;
-
-        let response_json: serde_json::Value = response
-            .json()
-            .await
-            .map_err(|e| AppError::ExternalApi(format!("Failed to parse Claude response: {}", e)))?;
-
-        // Extract the text content from Claude's response
-        let content_text = response_json["content"][0]["text"]
-            .as_str()
-            .ok_or_else(|| AppError::ExternalApi("Invalid response format from Claude".to_string()))?;
+            .llm
+            .complete(
+                LlmRequest::new(LlmFeature::TrackAnalysis, crate::services::ai_curator::CLAUDE_MODEL, prompt)
+                    .max_tokens(1024),
+            )
+            .await?;
+        let content_text = response.as_str();
 
         // Strip markdown code fences if present (Claude sometimes wraps JSON in ```json ... ```)
         let json_text = content_text
@@ -554,3 +912,33 @@ Respond with ONLY a JSON object in this exact format:
         Ok(analysis)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn test_reconcile_pool_drops_removed_and_follows_moved_tracks() {
+        let pool = ids(&["a", "gone", "moved", "b", "dup"]);
+        let removed: HashSet<String> = ids(&["gone", "moved", "dup"]).into_iter().collect();
+        let moved: HashMap<String, String> = [
+            ("moved".to_string(), "moved-new".to_string()),
+            // Already in the pool under its new id
+            ("dup".to_string(), "a".to_string()),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(reconcile_pool(&pool, &removed, &moved), ids(&["a", "moved-new", "b"]));
+    }
+
+    #[test]
+    fn test_reconcile_pool_keeps_untouched_pools() {
+        let pool = ids(&["a", "b"]);
+        assert_eq!(reconcile_pool(&pool, &HashSet::new(), &HashMap::new()), pool);
+    }
+}
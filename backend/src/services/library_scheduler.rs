@@ -0,0 +1,295 @@
+//! Scheduled library maintenance
+//!
+//! Runs the library's upkeep on fixed intervals so nothing depends on an admin
+//! remembering to POST: incremental syncs, AI analysis of tracks that haven't been
+//! analyzed, embedding backfills and the library statistics. Each task is a regular
+//! background job, so it shows up under `/jobs` like a manual run.
+//!
+//! A task never overlaps itself or the work it depends on: while a job of its own kind,
+//! or a library sync, is queued or running, a due task waits and is retried on the
+//! next check instead of piling up behind it. Tasks with nothing to do (no unanalyzed
+//! or unembedded tracks) don't start a job. The schedule state is kept in memory and
+//! served at `GET /library/scheduler`.
+
+use crate::error::AppError;
+use crate::models::JobKind;
+use crate::services::embedding_worker::EmbeddingWorker;
+use crate::services::jobs::JobManager;
+use crate::services::library_indexer::LibraryIndexer;
+use crate::services::library_watcher::LibraryWatcher;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How often due tasks are checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduledTask {
+    Sync,
+    Analysis,
+    Embeddings,
+    Stats,
+}
+
+impl ScheduledTask {
+    const ALL: [ScheduledTask; 4] = [
+        ScheduledTask::Sync,
+        ScheduledTask::Analysis,
+        ScheduledTask::Embeddings,
+        ScheduledTask::Stats,
+    ];
+
+    /// Jobs that must not be running when the task starts, its own kind first
+    fn blocked_by(self) -> &'static [JobKind] {
+        match self {
+            ScheduledTask::Sync => &[JobKind::LibrarySync],
+            ScheduledTask::Analysis => &[JobKind::AiAnalysis, JobKind::LibrarySync],
+            ScheduledTask::Embeddings => &[
+                JobKind::EmbeddingIndex,
+                JobKind::EmbeddingMigration,
+                JobKind::LibrarySync,
+            ],
+            ScheduledTask::Stats => &[JobKind::LibraryStats, JobKind::LibrarySync],
+        }
+    }
+}
+
+/// How a task's last check ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskOutcome {
+    /// A job was submitted
+    Started,
+    /// There was nothing to do
+    Idle,
+    /// A conflicting job was running; retried on the next check
+    Waiting,
+    Failed,
+}
+
+/// Intervals of the scheduled tasks; zero disables a task
+#[derive(Debug, Clone, Copy)]
+pub struct ScheduleConfig {
+    pub sync: Duration,
+    pub analysis: Duration,
+    /// Tracks analyzed per analysis run
+    pub analysis_batch: usize,
+    pub embeddings: Duration,
+    pub stats: Duration,
+}
+
+impl ScheduleConfig {
+    fn interval(&self, task: ScheduledTask) -> Duration {
+        match task {
+            ScheduledTask::Sync => self.sync,
+            ScheduledTask::Analysis => self.analysis,
+            ScheduledTask::Embeddings => self.embeddings,
+            ScheduledTask::Stats => self.stats,
+        }
+    }
+}
+
+/// Why a due task didn't start a job
+enum RunError {
+    /// A conflicting job is running
+    Waiting(String),
+    Failed(AppError),
+}
+
+impl From<AppError> for RunError {
+    fn from(e: AppError) -> Self {
+        RunError::Failed(e)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduledTaskStatus {
+    pub task: ScheduledTask,
+    pub enabled: bool,
+    /// Why an enabled task can't run on this server (no LLM or audio encoder)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unavailable: Option<String>,
+    pub interval_secs: u64,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_checked_at: Option<DateTime<Utc>>,
+    pub last_outcome: Option<TaskOutcome>,
+    pub last_message: Option<String>,
+    /// Job of the last run that started one
+    pub last_job_id: Option<Uuid>,
+}
+
+/// `GET /library/scheduler`
+#[derive(Debug, Clone, Serialize)]
+pub struct SchedulerStatus {
+    /// Whether this server runs the schedule (API-only instances don't)
+    pub running: bool,
+    pub check_interval_secs: u64,
+    pub tasks: Vec<ScheduledTaskStatus>,
+}
+
+pub struct LibraryScheduler {
+    jobs: Arc<JobManager>,
+    library_indexer: Arc<LibraryIndexer>,
+    library_watcher: Arc<LibraryWatcher>,
+    embedding_worker: Option<Arc<EmbeddingWorker>>,
+    config: ScheduleConfig,
+    running: AtomicBool,
+    tasks: Mutex<Vec<ScheduledTaskStatus>>,
+}
+
+impl LibraryScheduler {
+    pub fn new(
+        jobs: Arc<JobManager>,
+        library_indexer: Arc<LibraryIndexer>,
+        library_watcher: Arc<LibraryWatcher>,
+        embedding_worker: Option<Arc<EmbeddingWorker>>,
+        config: ScheduleConfig,
+    ) -> Self {
+        let now = Utc::now();
+        let tasks = ScheduledTask::ALL
+            .iter()
+            .map(|&task| {
+                let interval = config.interval(task);
+                let unavailable = match task {
+                    ScheduledTask::Analysis if !library_indexer.can_analyze() => {
+                        Some("AI analysis needs ANTHROPIC_API_KEY".to_string())
+                    }
+                    ScheduledTask::Embeddings if embedding_worker.is_none() => Some(
+                        "Embedding backfills need the audio encoder and NAVIDROME_LIBRARY_PATH or AUDIO_CACHE_DIR"
+                            .to_string(),
+                    ),
+                    _ => None,
+                };
+                let enabled = !interval.is_zero();
+                ScheduledTaskStatus {
+                    task,
+                    enabled,
+                    next_run_at: (enabled && unavailable.is_none()).then(|| now + interval),
+                    unavailable,
+                    interval_secs: interval.as_secs(),
+                    last_checked_at: None,
+                    last_outcome: None,
+                    last_message: None,
+                    last_job_id: None,
+                }
+            })
+            .collect();
+
+        Self {
+            jobs,
+            library_indexer,
+            library_watcher,
+            embedding_worker,
+            config,
+            running: AtomicBool::new(false),
+            tasks: Mutex::new(tasks),
+        }
+    }
+
+    pub fn status(&self) -> SchedulerStatus {
+        SchedulerStatus {
+            running: self.running.load(Ordering::Relaxed),
+            check_interval_secs: CHECK_INTERVAL.as_secs(),
+            tasks: self.tasks.lock().unwrap().clone(),
+        }
+    }
+
+    /// Check for due tasks every minute. Tasks first run one interval after startup.
+    pub fn spawn(self: Arc<Self>) {
+        self.running.store(true, Ordering::Relaxed);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let now = Utc::now();
+                let due: Vec<ScheduledTask> = self
+                    .tasks
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|status| status.next_run_at.is_some_and(|at| at <= now))
+                    .map(|status| status.task)
+                    .collect();
+
+                for task in due {
+                    let (outcome, message, job_id) = match self.run(task).await {
+                        Ok(Some(job_id)) => (TaskOutcome::Started, None, Some(job_id)),
+                        Ok(None) => (TaskOutcome::Idle, None, None),
+                        Err(RunError::Waiting(reason)) => (TaskOutcome::Waiting, Some(reason), None),
+                        Err(RunError::Failed(e)) => {
+                            tracing::warn!("Scheduled {:?} task failed: {}", task, e);
+                            (TaskOutcome::Failed, Some(e.to_string()), None)
+                        }
+                    };
+                    self.record(task, outcome, message, job_id);
+                }
+            }
+        });
+    }
+
+    fn record(&self, task: ScheduledTask, outcome: TaskOutcome, message: Option<String>, job_id: Option<Uuid>) {
+        let now = Utc::now();
+        let mut tasks = self.tasks.lock().unwrap();
+        let Some(status) = tasks.iter_mut().find(|status| status.task == task) else {
+            return;
+        };
+        status.last_checked_at = Some(now);
+        status.last_outcome = Some(outcome);
+        status.last_message = message;
+        if job_id.is_some() {
+            status.last_job_id = job_id;
+        }
+        // A waiting task stays due
+        if outcome != TaskOutcome::Waiting {
+            status.next_run_at = Some(now + self.config.interval(task));
+        }
+    }
+
+    /// Start the task's job, or None if there was nothing to do
+    async fn run(&self, task: ScheduledTask) -> std::result::Result<Option<Uuid>, RunError> {
+        for &kind in task.blocked_by() {
+            if let Some(job) = self.jobs.find_active(kind).await? {
+                return Err(RunError::Waiting(format!("Waiting for {} job {}", kind, job.id)));
+            }
+        }
+
+        let job_id = match task {
+            ScheduledTask::Sync => {
+                tracing::info!("Running scheduled incremental library sync");
+                self.library_watcher.scan_completed().await
+            }
+            ScheduledTask::Analysis => {
+                let pending = self.library_indexer.unanalyzed_count().await?;
+                if pending == 0 {
+                    return Ok(None);
+                }
+                tracing::info!("Running scheduled AI analysis ({} tracks unanalyzed)", pending);
+                self.library_indexer.submit_ai_analysis(self.config.analysis_batch).await
+            }
+            ScheduledTask::Embeddings => {
+                let Some(worker) = &self.embedding_worker else {
+                    return Ok(None);
+                };
+                let pending = worker.pending().await?;
+                if pending == 0 {
+                    return Ok(None);
+                }
+                tracing::info!("Running scheduled embedding backfill ({} tracks pending)", pending);
+                worker.start(&self.jobs).await
+            }
+            ScheduledTask::Stats => self.library_indexer.submit_stats_refresh().await,
+        };
+
+        match job_id {
+            Ok(job_id) => Ok(Some(job_id)),
+            // Started by someone else between the check and the submit, or a paused backfill
+            Err(AppError::Conflict(reason)) => Err(RunError::Waiting(reason)),
+            Err(e) => Err(RunError::Failed(e)),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! Automatic import of tracks Navidrome has just scanned in
+//!
+//! Polls Navidrome's scan status every `LIBRARY_WATCH_SECS`. When a scan finishes
+//! (the scanner goes idle, or `lastScan` moves forward), a library sync job applies the
+//! tracks added, changed and removed since the last sync and, if the audio encoder is
+//! available, computes embeddings for the new tracks so they can be curated right away.
+//! Removed tracks also leave the curated station pools. The same job can be triggered
+//! from outside with `POST /library/scan-complete`, and the library scheduler runs it
+//! every `LIBRARY_SYNC_INTERVAL_SECS`.
+
+use crate::error::Result;
+use crate::models::JobKind;
+use crate::services::audio_cache::{self, AudioCache};
+use crate::services::audio_encoder::AudioEncoder;
+use crate::services::jobs::JobManager;
+use crate::services::library_indexer::LibraryIndexer;
+use crate::services::NavidromeClient;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub struct LibraryWatcher {
+    db: PgPool,
+    navidrome_client: Arc<NavidromeClient>,
+    library_indexer: Arc<LibraryIndexer>,
+    jobs: Arc<JobManager>,
+    audio_encoder: Option<Arc<AudioEncoder>>,
+    library_path: Option<String>,
+    audio_cache: Option<Arc<AudioCache>>,
+}
+
+impl LibraryWatcher {
+    pub fn new(
+        db: PgPool,
+        navidrome_client: Arc<NavidromeClient>,
+        library_indexer: Arc<LibraryIndexer>,
+        jobs: Arc<JobManager>,
+        audio_encoder: Option<Arc<AudioEncoder>>,
+        library_path: Option<String>,
+        audio_cache: Option<Arc<AudioCache>>,
+    ) -> Self {
+        Self {
+            db,
+            navidrome_client,
+            library_indexer,
+            jobs,
+            audio_encoder,
+            library_path,
+            audio_cache,
+        }
+    }
+
+    /// Poll Navidrome's scan status every `interval`
+    pub fn spawn(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // State at the previous poll; nothing is imported for scans before startup
+            let mut last_seen: Option<(bool, Option<DateTime<Utc>>)> = None;
+            loop {
+                ticker.tick().await;
+                let status = match self.navidrome_client.get_scan_status().await {
+                    Ok(status) => status,
+                    Err(e) => {
+                        tracing::debug!("Could not read Navidrome scan status: {}", e);
+                        continue;
+                    }
+                };
+
+                let finished = match last_seen {
+                    Some((was_scanning, last_scan)) => {
+                        !status.scanning && (was_scanning || status.last_scan > last_scan)
+                    }
+                    None => false,
+                };
+                last_seen = Some((status.scanning, status.last_scan));
+
+                if finished {
+                    tracing::info!("Navidrome finished a library scan, importing new tracks");
+                    if let Err(e) = self.scan_completed().await {
+                        tracing::warn!("Could not start incremental library sync: {}", e);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Start a background job that syncs library changes and embeds new tracks.
+    /// Returns the job ID.
+    pub async fn scan_completed(&self) -> Result<Uuid> {
+        let indexer = self.library_indexer.clone();
+        let db = self.db.clone();
+        let navidrome = self.navidrome_client.clone();
+        let encoder = self.audio_encoder.clone();
+        let library_path = self.library_path.clone();
+        let audio_cache = self.audio_cache.clone();
+
+        self.jobs
+            .submit(
+                JobKind::LibrarySync,
+                serde_json::json!({ "incremental": true }),
+                1,
+                move |ctx| {
+                    let indexer = indexer.clone();
+                    let db = db.clone();
+                    let navidrome = navidrome.clone();
+                    let encoder = encoder.clone();
+                    let library_path = library_path.clone();
+                    let audio_cache = audio_cache.clone();
+                    async move {
+                        ctx.progress(0.0, "Syncing library changes").await;
+                        let sync = indexer.sync_incremental().await?;
+                        let new_tracks = sync.new_tracks;
+                        let summary = |embedded: usize| {
+                            serde_json::json!({
+                                "new_tracks": new_tracks.len(),
+                                "updated_tracks": sync.updated,
+                                "removed_tracks": sync.pruned.removed,
+                                "moved_tracks": sync.pruned.moved,
+                                "embedded": embedded,
+                            })
+                        };
+
+                        let Some(encoder) = encoder.filter(|_| !new_tracks.is_empty()) else {
+                            return Ok(summary(0));
+                        };
+
+                        let mut embedded = 0;
+                        for (index, track_id) in new_tracks.iter().enumerate() {
+                            if ctx.is_cancelled() {
+                                break;
+                            }
+
+                            let path: Option<String> =
+                                sqlx::query_scalar("SELECT path FROM library_index WHERE id = $1")
+                                    .bind(track_id)
+                                    .fetch_optional(&db)
+                                    .await?
+                                    .flatten();
+                            let file = audio_cache::track_file(
+                                library_path.as_deref().filter(|_| path.is_some()),
+                                audio_cache.as_deref(),
+                                &navidrome,
+                                track_id,
+                                path.as_deref().unwrap_or_default(),
+                            )
+                            .await;
+
+                            match file {
+                                Ok(file) => match encoder.process_track(track_id, &file).await {
+                                    Ok(()) => embedded += 1,
+                                    Err(e) => tracing::warn!("Failed to encode track {}: {}", track_id, e),
+                                },
+                                Err(e) => tracing::warn!("No audio for track {}: {}", track_id, e),
+                            }
+
+                            ctx.progress(
+                                (index + 1) as f64 / new_tracks.len() as f64,
+                                format!("Embedded {} of {} new tracks", embedded, new_tracks.len()),
+                            )
+                            .await;
+                        }
+
+                        tracing::info!("Embedded {} of {} new tracks", embedded, new_tracks.len());
+                        Ok(summary(embedded))
+                    }
+                },
+            )
+            .await
+    }
+}
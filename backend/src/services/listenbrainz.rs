@@ -0,0 +1,336 @@
+//! ListenBrainz scrobbling
+//!
+//! When a station moves on to the next track, the finished one is submitted as a
+//! "listen": to the server's own account (`LISTENBRAINZ_TOKEN`) if the station played
+//! it long enough, and to the account of every listener who connected one and heard
+//! enough of it (see [`required_listen_secs`]).
+//!
+//! Listens are queued (see [`ScrobbleQueue`]) and submitted in batches by a background
+//! flusher, so an unreachable ListenBrainz only delays them.
+
+use crate::error::{AppError, Result};
+use crate::models::{ScrobbleAccount, Station, Track};
+use crate::services::scrobble_queue::{required_listen_secs, ScrobbleQueue, SubmitError};
+use crate::services::secret_box::SecretBox;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+/// `service` of ListenBrainz rows in `user_scrobble_accounts` and `scrobble_queue`
+pub const SERVICE: &str = "listenbrainz";
+
+pub const DEFAULT_API_URL: &str = "https://api.listenbrainz.org";
+
+/// Listener heartbeats arrive every 10 seconds, so recorded listening time falls
+/// short of the real one by up to this much
+const HEARTBEAT_SLACK_SECS: i64 = 10;
+
+/// Listens per submission (ListenBrainz accepts up to 1000)
+const BATCH_SIZE: i64 = 100;
+
+/// How often the flusher looks for due listens when nothing wakes it
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// One listen in the `submit-listens` format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Listen {
+    pub listened_at: i64,
+    pub track_metadata: TrackMetadata,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackMetadata {
+    pub artist_name: String,
+    pub track_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_name: Option<String>,
+    pub additional_info: AdditionalInfo,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdditionalInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<i64>,
+    pub submission_client: String,
+    pub submission_client_version: String,
+}
+
+impl Listen {
+    pub fn new(track: &Track, started_at: DateTime<Utc>) -> Self {
+        Self {
+            listened_at: started_at.timestamp(),
+            track_metadata: TrackMetadata {
+                artist_name: track.artist.clone(),
+                track_name: track.title.clone(),
+                release_name: Some(track.album.clone()).filter(|album| !album.is_empty()),
+                additional_info: AdditionalInfo {
+                    duration_ms: Some(track.duration as i64 * 1000).filter(|ms| *ms > 0),
+                    submission_client: "Navidrome Radio".to_string(),
+                    submission_client_version: env!("CARGO_PKG_VERSION").to_string(),
+                },
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ValidateTokenResponse {
+    valid: bool,
+    user_name: Option<String>,
+    message: Option<String>,
+}
+
+pub struct ListenBrainzClient {
+    db: PgPool,
+    http: reqwest::Client,
+    api_url: String,
+    /// The server's own account, which receives every station's plays
+    token: Option<String>,
+    secret_box: SecretBox,
+    queue: ScrobbleQueue,
+    /// Wakes the flusher when listens are queued
+    queued: Notify,
+}
+
+impl ListenBrainzClient {
+    pub fn new(db: PgPool, secret_box: SecretBox, api_url: String, token: Option<String>) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            queue: ScrobbleQueue::new(db.clone(), SERVICE),
+            db,
+            http,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            token,
+            secret_box,
+            queued: Notify::new(),
+        }
+    }
+
+    /// The ListenBrainz account a user connected
+    pub async fn account(&self, user_id: Uuid) -> Result<Option<ScrobbleAccount>> {
+        let account = sqlx::query_as::<_, ScrobbleAccount>(
+            "SELECT service, username, created_at FROM user_scrobble_accounts WHERE user_id = $1 AND service = $2",
+        )
+        .bind(user_id)
+        .bind(SERVICE)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(account)
+    }
+
+    /// Check a user token with ListenBrainz and store it, replacing any previous one
+    pub async fn connect(&self, user_id: Uuid, token: &str) -> Result<ScrobbleAccount> {
+        let username = self.validate_token(token).await?;
+        let sealed = self.secret_box.seal(token, user_id.as_bytes())?;
+
+        let account = sqlx::query_as::<_, ScrobbleAccount>(
+            r#"
+            INSERT INTO user_scrobble_accounts (user_id, service, username, secret)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, service) DO UPDATE
+            SET username = EXCLUDED.username, secret = EXCLUDED.secret, created_at = NOW()
+            RETURNING service, username, created_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(SERVICE)
+        .bind(&username)
+        .bind(sealed)
+        .fetch_one(&self.db)
+        .await?;
+
+        tracing::info!("User {} connected ListenBrainz account '{}'", user_id, username);
+        Ok(account)
+    }
+
+    /// Forget a user's token and their unsent listens. Returns false if none was connected.
+    pub async fn disconnect(&self, user_id: Uuid) -> Result<bool> {
+        self.queue.discard_account(Some(user_id)).await?;
+        let result = sqlx::query("DELETE FROM user_scrobble_accounts WHERE user_id = $1 AND service = $2")
+            .bind(user_id)
+            .bind(SERVICE)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Queue listens for a track the station has finished playing. Returns how many
+    /// were queued.
+    pub async fn track_finished(
+        &self,
+        station: &Station,
+        track: &Track,
+        started_at: DateTime<Utc>,
+        ended_at: DateTime<Utc>,
+    ) -> Result<usize> {
+        let Some(required) = required_listen_secs(track.duration as i64) else {
+            return Ok(0);
+        };
+
+        // Connected listeners who heard enough of it on this station
+        let mut owners: Vec<Option<Uuid>> = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT h.user_id
+            FROM listening_history h
+            JOIN user_scrobble_accounts a ON a.user_id = h.user_id AND a.service = $1
+            WHERE h.station_id = $2 AND h.track_id = $3 AND h.first_heard_at >= $4
+            GROUP BY h.user_id
+            HAVING SUM(EXTRACT(EPOCH FROM (h.last_heard_at - h.first_heard_at))) + $5 >= $6
+            "#,
+        )
+        .bind(SERVICE)
+        .bind(station.id)
+        .bind(&track.id)
+        .bind(started_at)
+        .bind(HEARTBEAT_SLACK_SECS as f64)
+        .bind(required as f64)
+        .fetch_all(&self.db)
+        .await?
+        .into_iter()
+        .map(Some)
+        .collect();
+
+        if self.token.is_some() && (ended_at - started_at).num_seconds() >= required {
+            owners.push(None);
+        }
+        if owners.is_empty() {
+            return Ok(0);
+        }
+
+        let listen = Listen::new(track, started_at);
+        for user_id in &owners {
+            self.queue.push(*user_id, station.id, &listen).await?;
+        }
+
+        self.queued.notify_one();
+        Ok(owners.len())
+    }
+
+    /// Submit queued listens in the background, whenever some are queued and
+    /// periodically for retries
+    pub fn spawn_flusher(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = self.queued.notified() => {}
+                }
+                match self.flush().await {
+                    Ok(0) => {}
+                    Ok(submitted) => tracing::debug!("Submitted {} listen(s) to ListenBrainz", submitted),
+                    Err(e) => tracing::warn!("ListenBrainz flush failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Submit every due listen, one batch per account at a time. Returns the number
+    /// submitted.
+    pub async fn flush(&self) -> Result<usize> {
+        let mut submitted = 0;
+        for user_id in self.queue.due_accounts().await? {
+            let token = match user_id {
+                Some(user_id) => self.user_token(user_id).await?,
+                None => self.token.clone(),
+            };
+            let Some(token) = token else {
+                // Account disconnected or LISTENBRAINZ_TOKEN removed since the listens were queued
+                self.queue.discard_account(user_id).await?;
+                continue;
+            };
+
+            loop {
+                let batch: Vec<(i64, Listen)> = self.queue.claim(user_id, BATCH_SIZE).await?;
+                if batch.is_empty() {
+                    break;
+                }
+                let (ids, listens): (Vec<i64>, Vec<Listen>) = batch.into_iter().unzip();
+
+                let outcome = self.submit(&token, &listens).await;
+                let succeeded = outcome.is_ok();
+                if !self.queue.finish(&ids, outcome).await? {
+                    // Leave this account's remaining listens for the next flush
+                    break;
+                }
+                if succeeded {
+                    submitted += ids.len();
+                }
+            }
+        }
+
+        Ok(submitted)
+    }
+
+    async fn user_token(&self, user_id: Uuid) -> Result<Option<String>> {
+        let sealed: Option<Vec<u8>> = sqlx::query_scalar(
+            "SELECT secret FROM user_scrobble_accounts WHERE user_id = $1 AND service = $2",
+        )
+        .bind(user_id)
+        .bind(SERVICE)
+        .fetch_optional(&self.db)
+        .await?;
+
+        sealed
+            .map(|sealed| self.secret_box.open(&sealed, user_id.as_bytes()))
+            .transpose()
+    }
+
+    async fn submit(&self, token: &str, listens: &[Listen]) -> std::result::Result<(), SubmitError> {
+        let listen_type = if listens.len() == 1 { "single" } else { "import" };
+        let response = self
+            .http
+            .post(format!("{}/1/submit-listens", self.api_url))
+            .header("Authorization", format!("Token {}", token))
+            .json(&serde_json::json!({ "listen_type": listen_type, "payload": listens }))
+            .send()
+            .await
+            .map_err(|e| SubmitError::Failed(e.to_string()))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let body = response.text().await.unwrap_or_default();
+        let error = format!("{}: {}", status, body.chars().take(200).collect::<String>());
+        if status == reqwest::StatusCode::BAD_REQUEST {
+            Err(SubmitError::Rejected(error))
+        } else {
+            Err(SubmitError::Failed(error))
+        }
+    }
+
+    /// The username a token belongs to
+    async fn validate_token(&self, token: &str) -> Result<String> {
+        let response: ValidateTokenResponse = self
+            .http
+            .get(format!("{}/1/validate-token", self.api_url))
+            .header("Authorization", format!("Token {}", token))
+            .send()
+            .await
+            .map_err(|e| AppError::ExternalApi(format!("ListenBrainz is unreachable: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| AppError::ExternalApi(format!("Unexpected ListenBrainz response: {}", e)))?;
+
+        match (response.valid, response.user_name) {
+            (true, Some(username)) => Ok(username),
+            _ => Err(AppError::Validation(format!(
+                "ListenBrainz rejected the token: {}",
+                response.message.unwrap_or_else(|| "invalid token".to_string())
+            ))),
+        }
+    }
+}
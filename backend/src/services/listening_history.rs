@@ -0,0 +1,49 @@
+//! Per-user listening history
+//!
+//! Authenticated listener heartbeats record the track playing on the station at the
+//! time. Consecutive heartbeats for the same track extend one history row rather than
+//! adding new ones, so `last_heard_at - first_heard_at` approximates listening time.
+
+use crate::error::Result;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Heartbeats further apart than this start a new history row for the same track
+/// (the frontend sends one every 10 seconds)
+const CONTINUE_WINDOW_SECS: i64 = 60;
+
+/// Record that a user heard `track_id` on a station just now
+pub async fn record_listen(db: &PgPool, user_id: Uuid, station_id: Uuid, track_id: &str) -> Result<()> {
+    sqlx::query(
+        r#"
+        WITH latest AS (
+            SELECT id, station_id, track_id, last_heard_at
+            FROM listening_history
+            WHERE user_id = $1
+            ORDER BY last_heard_at DESC
+            LIMIT 1
+        ),
+        extended AS (
+            UPDATE listening_history h
+            SET last_heard_at = NOW()
+            FROM latest
+            WHERE h.id = latest.id
+              AND latest.station_id = $2
+              AND latest.track_id = $3
+              AND latest.last_heard_at > NOW() - make_interval(secs => $4)
+            RETURNING h.id
+        )
+        INSERT INTO listening_history (user_id, station_id, track_id)
+        SELECT $1, $2, $3
+        WHERE NOT EXISTS (SELECT 1 FROM extended)
+        "#,
+    )
+    .bind(user_id)
+    .bind(station_id)
+    .bind(track_id)
+    .bind(CONTINUE_WINDOW_SECS as f64)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,508 @@
+//! Shared client for Claude calls
+//!
+//! Every LLM call in the app goes through [`LlmClient::complete`], which:
+//! - answers repeated prompts from `llm_response_cache` while they are younger than
+//!   LLM_CACHE_TTL_HOURS, without calling the API,
+//! - refuses new calls once the day's spending (UTC) reaches LLM_DAILY_BUDGET_USD,
+//! - records the tokens and estimated cost of each call per feature in `llm_usage`,
+//!   which `GET /api/v1/ai/usage` reports on.
+//!
+//! [`LlmClient::complete_streaming`] uses the streaming API and hands out the reply
+//! as it is generated, for progress shown while a long call runs.
+//!
+//! The budget is checked before a call starts, so calls running concurrently when it is
+//! reached can overshoot it by their own cost. Costs are estimates from list prices.
+
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::models::{AiUsage, DailyUsage, FeatureUsage};
+use chrono::{Duration, Utc};
+use futures::StreamExt;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+
+const MESSAGES_URL: &str = "https://api.anthropic.com/v1/messages";
+
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// USD per million input and output tokens, by model name prefix. Models not listed
+/// are priced like Sonnet.
+const PRICES: [(&str, f64, f64); 7] = [
+    ("claude-opus-4-5", 5.0, 25.0),
+    ("claude-opus-4", 15.0, 75.0),
+    ("claude-sonnet-4", 3.0, 15.0),
+    ("claude-3-7-sonnet", 3.0, 15.0),
+    ("claude-3-5-sonnet", 3.0, 15.0),
+    ("claude-haiku-4-5", 1.0, 5.0),
+    ("claude-3-5-haiku", 0.8, 4.0),
+];
+const DEFAULT_PRICE: (f64, f64) = (3.0, 15.0);
+
+/// What a call was made for, for usage accounting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LlmFeature {
+    /// Turning a station description into search terms
+    StationDescription,
+    /// Extracting filters from a curation query
+    QueryAnalysis,
+    /// Picking tracks for a curation query
+    TrackSelection,
+    /// Picking seed tracks for hybrid curation
+    SeedSelection,
+    /// Mood and energy tags for a library track
+    TrackAnalysis,
+}
+
+impl LlmFeature {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LlmFeature::StationDescription => "station_description",
+            LlmFeature::QueryAnalysis => "query_analysis",
+            LlmFeature::TrackSelection => "track_selection",
+            LlmFeature::SeedSelection => "seed_selection",
+            LlmFeature::TrackAnalysis => "track_analysis",
+        }
+    }
+}
+
+/// One prompt to send
+#[derive(Debug, Clone)]
+pub struct LlmRequest {
+    pub feature: LlmFeature,
+    pub model: &'static str,
+    pub prompt: String,
+    pub max_tokens: u32,
+    /// Answer from the response cache when the same prompt was sent recently
+    pub cache: bool,
+}
+
+impl LlmRequest {
+    pub fn new(feature: LlmFeature, model: &'static str, prompt: impl Into<String>) -> Self {
+        Self {
+            feature,
+            model,
+            prompt: prompt.into(),
+            max_tokens: DEFAULT_MAX_TOKENS,
+            cache: true,
+        }
+    }
+
+    pub fn max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Identifies the request in the response cache
+    fn cache_key(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.model.as_bytes());
+        hasher.update(self.max_tokens.to_le_bytes());
+        hasher.update(self.prompt.as_bytes());
+        hex::encode(hasher.finalize())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+    #[serde(default)]
+    usage: TokenUsage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContentBlock {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TokenUsage {
+    #[serde(default)]
+    input_tokens: i64,
+    #[serde(default)]
+    output_tokens: i64,
+}
+
+pub struct LlmClient {
+    api_key: String,
+    http: reqwest::Client,
+    db: PgPool,
+    daily_budget_usd: Option<f64>,
+    cache_ttl: Option<Duration>,
+}
+
+impl LlmClient {
+    pub fn new(api_key: String, db: PgPool, config: &Config) -> Self {
+        Self {
+            api_key,
+            http: reqwest::Client::new(),
+            db,
+            daily_budget_usd: (config.llm_daily_budget_usd > 0.0).then_some(config.llm_daily_budget_usd),
+            cache_ttl: (config.llm_cache_ttl_hours > 0).then(|| Duration::hours(config.llm_cache_ttl_hours as i64)),
+        }
+    }
+
+    /// Send a prompt and return the text of the reply
+    pub async fn complete(&self, request: LlmRequest) -> Result<String> {
+        self.complete_with(request, None).await
+    }
+
+    /// Like [`complete`](Self::complete), but also sends each piece of the reply to
+    /// `deltas` as the model writes it. A cached reply arrives as a single piece.
+    pub async fn complete_streaming(&self, request: LlmRequest, deltas: mpsc::Sender<String>) -> Result<String> {
+        self.complete_with(request, Some(deltas)).await
+    }
+
+    async fn complete_with(&self, request: LlmRequest, deltas: Option<mpsc::Sender<String>>) -> Result<String> {
+        let cache_key = request.cache_key();
+        if request.cache {
+            if let Some(text) = self.cached_response(&cache_key).await {
+                self.record_usage(&request, 0, 0, true).await;
+                if let Some(deltas) = &deltas {
+                    let _ = deltas.send(text.clone()).await;
+                }
+                return Ok(text);
+            }
+        }
+
+        self.check_budget().await?;
+
+        let response = self
+            .http
+            .post(MESSAGES_URL)
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", "2023-06-01")
+            .header("content-type", "application/json")
+            .json(&serde_json::json!({
+                "model": request.model,
+                "max_tokens": request.max_tokens,
+                "stream": deltas.is_some(),
+                "messages": [{
+                    "role": "user",
+                    "content": request.prompt
+                }]
+            }))
+            .send()
+            .await
+            .map_err(|e| AppError::ExternalApi(format!("Failed to call Claude API: {}", e)))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(AppError::ExternalApi(format!(
+                "Claude API returned error status {}: {}",
+                status, error_text
+            )));
+        }
+
+        let (text, usage) = match &deltas {
+            Some(deltas) => read_stream(response, deltas).await?,
+            None => {
+                let response: MessagesResponse = response
+                    .json()
+                    .await
+                    .map_err(|e| AppError::ExternalApi(format!("Failed to parse Claude response: {}", e)))?;
+                let text = response
+                    .content
+                    .into_iter()
+                    .next()
+                    .map(|block| block.text)
+                    .ok_or_else(|| AppError::ExternalApi("Invalid response format from Claude".to_string()))?;
+                (text, response.usage)
+            }
+        };
+
+        self.record_usage(&request, usage.input_tokens, usage.output_tokens, false)
+            .await;
+
+        if request.cache && self.cache_ttl.is_some() {
+            self.cache_response(&cache_key, &request, &text).await;
+        }
+
+        Ok(text)
+    }
+
+    /// Spending per feature and per day over the last `days` days
+    pub async fn usage(&self, days: i64) -> Result<AiUsage> {
+        let from = Utc::now() - Duration::days(days);
+
+        let features = sqlx::query_as::<_, FeatureUsage>(
+            r#"
+            SELECT feature,
+                   COUNT(*) AS calls,
+                   COUNT(*) FILTER (WHERE cached) AS cached_calls,
+                   COALESCE(SUM(input_tokens), 0)::BIGINT AS input_tokens,
+                   COALESCE(SUM(output_tokens), 0)::BIGINT AS output_tokens,
+                   COALESCE(SUM(cost_usd), 0)::FLOAT8 AS cost_usd
+            FROM llm_usage
+            WHERE created_at >= $1
+            GROUP BY feature
+            ORDER BY cost_usd DESC, feature
+            "#,
+        )
+        .bind(from)
+        .fetch_all(&self.db)
+        .await?;
+
+        let daily = sqlx::query_as::<_, DailyUsage>(
+            r#"
+            SELECT (created_at AT TIME ZONE 'UTC')::DATE AS day,
+                   COUNT(*) AS calls,
+                   COUNT(*) FILTER (WHERE cached) AS cached_calls,
+                   COALESCE(SUM(input_tokens), 0)::BIGINT AS input_tokens,
+                   COALESCE(SUM(output_tokens), 0)::BIGINT AS output_tokens,
+                   COALESCE(SUM(cost_usd), 0)::FLOAT8 AS cost_usd
+            FROM llm_usage
+            WHERE created_at >= $1
+            GROUP BY 1
+            ORDER BY 1
+            "#,
+        )
+        .bind(from)
+        .fetch_all(&self.db)
+        .await?;
+
+        let spent_today_usd = self.spent_today().await?;
+
+        Ok(AiUsage {
+            days,
+            from,
+            daily_budget_usd: self.daily_budget_usd,
+            spent_today_usd,
+            remaining_today_usd: self.daily_budget_usd.map(|budget| (budget - spent_today_usd).max(0.0)),
+            total_cost_usd: features.iter().map(|f| f.cost_usd).sum(),
+            features,
+            daily,
+        })
+    }
+
+    async fn spent_today(&self) -> Result<f64> {
+        let midnight = Utc::now().date_naive().and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+        let spent = sqlx::query_scalar("SELECT COALESCE(SUM(cost_usd), 0)::FLOAT8 FROM llm_usage WHERE created_at >= $1")
+            .bind(midnight)
+            .fetch_one(&self.db)
+            .await?;
+        Ok(spent)
+    }
+
+    async fn check_budget(&self) -> Result<()> {
+        let Some(budget) = self.daily_budget_usd else {
+            return Ok(());
+        };
+
+        let spent = self.spent_today().await?;
+        if spent >= budget {
+            tracing::warn!("Daily LLM budget of ${:.2} reached (${:.2} spent), refusing call", budget, spent);
+            return Err(AppError::Unavailable(format!(
+                "The daily AI budget of ${:.2} has been used up; it resets at midnight UTC",
+                budget
+            )));
+        }
+        Ok(())
+    }
+
+    async fn cached_response(&self, cache_key: &str) -> Option<String> {
+        let ttl = self.cache_ttl?;
+        let result = sqlx::query_scalar(
+            r#"
+            UPDATE llm_response_cache
+            SET hits = hits + 1, last_used = NOW()
+            WHERE prompt_hash = $1 AND created_at >= $2
+            RETURNING response
+            "#,
+        )
+        .bind(cache_key)
+        .bind(Utc::now() - ttl)
+        .fetch_optional(&self.db)
+        .await;
+
+        match result {
+            Ok(text) => text,
+            Err(e) => {
+                tracing::warn!("Failed to read LLM response cache: {}", e);
+                None
+            }
+        }
+    }
+
+    async fn cache_response(&self, cache_key: &str, request: &LlmRequest, text: &str) {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO llm_response_cache (prompt_hash, feature, model, response)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (prompt_hash) DO UPDATE SET
+                response = EXCLUDED.response,
+                created_at = NOW(),
+                last_used = NOW()
+            "#,
+        )
+        .bind(cache_key)
+        .bind(request.feature.as_str())
+        .bind(request.model)
+        .bind(text)
+        .execute(&self.db)
+        .await;
+        if let Err(e) = result {
+            tracing::warn!("Failed to cache LLM response: {}", e);
+        }
+
+        // Expired entries are never read again
+        if let Some(ttl) = self.cache_ttl {
+            let _ = sqlx::query("DELETE FROM llm_response_cache WHERE created_at < $1")
+                .bind(Utc::now() - ttl)
+                .execute(&self.db)
+                .await;
+        }
+    }
+
+    /// Like the audit log, accounting never fails the call itself
+    async fn record_usage(&self, request: &LlmRequest, input_tokens: i64, output_tokens: i64, cached: bool) {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO llm_usage (feature, model, input_tokens, output_tokens, cost_usd, cached)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(request.feature.as_str())
+        .bind(request.model)
+        .bind(input_tokens)
+        .bind(output_tokens)
+        .bind(cost_usd(request.model, input_tokens, output_tokens))
+        .bind(cached)
+        .execute(&self.db)
+        .await;
+        if let Err(e) = result {
+            tracing::warn!("Failed to record {} LLM usage: {}", request.feature.as_str(), e);
+        }
+    }
+}
+
+/// Collect a streamed reply (server-sent events), forwarding text as it arrives
+async fn read_stream(response: reqwest::Response, deltas: &mpsc::Sender<String>) -> Result<(String, TokenUsage)> {
+    let mut body = response.bytes_stream();
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut text = String::new();
+    let mut usage = TokenUsage::default();
+
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|e| AppError::ExternalApi(format!("Claude stream interrupted: {}", e)))?;
+        buffer.extend_from_slice(&chunk);
+
+        // Events end with a blank line; keep a trailing partial event for the next chunk.
+        // Splitting bytes rather than text keeps characters cut across chunks intact.
+        while let Some(end) = buffer.windows(2).position(|pair| pair == b"\n\n") {
+            let event: Vec<u8> = buffer.drain(..end + 2).collect();
+            let event = String::from_utf8_lossy(&event);
+            for data in event.lines().filter_map(|line| line.strip_prefix("data:")) {
+                match parse_stream_event(data.trim())? {
+                    StreamEvent::Text(delta) => {
+                        text.push_str(&delta);
+                        let _ = deltas.send(delta).await;
+                    }
+                    StreamEvent::InputTokens(tokens) => usage.input_tokens = tokens,
+                    StreamEvent::OutputTokens(tokens) => usage.output_tokens = tokens,
+                    StreamEvent::Other => {}
+                }
+            }
+        }
+    }
+
+    if text.is_empty() {
+        return Err(AppError::ExternalApi("Claude stream ended without a reply".to_string()));
+    }
+    Ok((text, usage))
+}
+
+#[derive(Debug, PartialEq)]
+enum StreamEvent {
+    Text(String),
+    InputTokens(i64),
+    OutputTokens(i64),
+    Other,
+}
+
+/// Interpret the data of one streamed event
+fn parse_stream_event(data: &str) -> Result<StreamEvent> {
+    let event: serde_json::Value = match serde_json::from_str(data) {
+        Ok(event) => event,
+        Err(_) => return Ok(StreamEvent::Other),
+    };
+
+    Ok(match event["type"].as_str() {
+        Some("content_block_delta") => match event["delta"]["text"].as_str() {
+            Some(text) => StreamEvent::Text(text.to_string()),
+            None => StreamEvent::Other,
+        },
+        Some("message_start") => {
+            StreamEvent::InputTokens(event["message"]["usage"]["input_tokens"].as_i64().unwrap_or(0))
+        }
+        Some("message_delta") => match event["usage"]["output_tokens"].as_i64() {
+            Some(tokens) => StreamEvent::OutputTokens(tokens),
+            None => StreamEvent::Other,
+        },
+        Some("error") => {
+            return Err(AppError::ExternalApi(format!(
+                "Claude stream failed: {}",
+                event["error"]["message"].as_str().unwrap_or("unknown error")
+            )))
+        }
+        _ => StreamEvent::Other,
+    })
+}
+
+/// Estimated cost of a call from list prices
+fn cost_usd(model: &str, input_tokens: i64, output_tokens: i64) -> f64 {
+    let (input_price, output_price) = PRICES
+        .iter()
+        .find(|(prefix, _, _)| model.starts_with(prefix))
+        .map(|&(_, input, output)| (input, output))
+        .unwrap_or(DEFAULT_PRICE);
+    (input_tokens as f64 * input_price + output_tokens as f64 * output_price) / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cost_uses_the_model_price() {
+        assert!((cost_usd("claude-sonnet-4-5-20250929", 1_000_000, 100_000) - 4.5).abs() < 1e-9);
+        assert!((cost_usd("claude-3-5-haiku-20241022", 1_000_000, 1_000_000) - 4.8).abs() < 1e-9);
+        assert!((cost_usd("claude-opus-4-1", 1_000, 0) - 0.015).abs() < 1e-9);
+        assert!((cost_usd("some-new-model", 1_000_000, 0) - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_stream_events_are_parsed() {
+        assert_eq!(
+            parse_stream_event(r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hel"}}"#)
+                .unwrap(),
+            StreamEvent::Text("Hel".to_string())
+        );
+        assert_eq!(
+            parse_stream_event(r#"{"type":"message_start","message":{"usage":{"input_tokens":25,"output_tokens":1}}}"#)
+                .unwrap(),
+            StreamEvent::InputTokens(25)
+        );
+        assert_eq!(
+            parse_stream_event(r#"{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":15}}"#)
+                .unwrap(),
+            StreamEvent::OutputTokens(15)
+        );
+        assert_eq!(parse_stream_event(r#"{"type":"ping"}"#).unwrap(), StreamEvent::Other);
+        assert!(parse_stream_event(r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#).is_err());
+    }
+
+    #[test]
+    fn test_cache_key_covers_model_and_limits() {
+        let request = LlmRequest::new(LlmFeature::QueryAnalysis, "claude-sonnet-4-5", "prompt");
+        assert_eq!(request.cache_key(), request.clone().cache_key());
+        assert_ne!(request.cache_key(), request.clone().max_tokens(10).cache_key());
+        assert_ne!(
+            request.cache_key(),
+            LlmRequest::new(LlmFeature::QueryAnalysis, "claude-3-5-haiku", "prompt").cache_key()
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Brute-force protection for logins
+//!
+//! Failed logins are counted per username and per client IP in Redis. Each failure
+//! adds an exponentially growing delay to the response, and once a counter passes its
+//! threshold the username or IP is locked out for an exponentially growing period.
+//! Lockouts apply to unknown usernames exactly like real ones, so responses never
+//! reveal whether an account exists.
+//!
+//! If Redis is unavailable the guard fails open (logins still work, unprotected).
+
+use crate::error::{AppError, Result};
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Failures are forgotten after this long without another failure
+const FAILURE_WINDOW_SECS: i64 = 15 * 60;
+
+/// First lockout once the threshold is reached (doubles with each further failure)
+const LOCKOUT_BASE_SECS: u64 = 30;
+
+/// Longest lockout
+const LOCKOUT_MAX_SECS: u64 = 60 * 60;
+
+/// Delay added to the first failed attempt (doubles with each further failure)
+const FAILURE_DELAY_BASE: Duration = Duration::from_millis(100);
+
+/// Longest delay added to a failed attempt
+const FAILURE_DELAY_MAX: Duration = Duration::from_secs(3);
+
+/// A single IP may fail this many times more than a single username before lockout,
+/// so users behind a shared NAT don't lock each other out
+const IP_THRESHOLD_MULTIPLIER: u32 = 4;
+
+pub struct LoginGuard {
+    redis: ConnectionManager,
+    max_failures: u32,
+}
+
+impl LoginGuard {
+    pub fn new(redis: ConnectionManager, max_failures: u32) -> Self {
+        Self {
+            redis,
+            max_failures: max_failures.max(1),
+        }
+    }
+
+    /// Reject the attempt if the username or IP is currently locked out
+    pub async fn check(&self, username: &str, ip: IpAddr) -> Result<()> {
+        let mut conn = self.redis.clone();
+
+        for key in [lock_key("user", &normalize(username)), lock_key("ip", &ip.to_string())] {
+            match conn.ttl::<_, i64>(&key).await {
+                Ok(ttl) if ttl > 0 => {
+                    return Err(AppError::TooManyRequests {
+                        retry_after_secs: ttl as u64,
+                    });
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!("Login guard unavailable, skipping lockout check: {}", e);
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Count a failed attempt, locking out the username or IP past the threshold.
+    /// Returns how long to delay the failure response.
+    pub async fn record_failure(&self, username: &str, ip: IpAddr) -> Duration {
+        let user_failures = self
+            .register_failure("user", &normalize(username), self.max_failures)
+            .await;
+        let ip_failures = self
+            .register_failure("ip", &ip.to_string(), self.max_failures * IP_THRESHOLD_MULTIPLIER)
+            .await;
+
+        let failures = user_failures.max(ip_failures).max(1);
+        FAILURE_DELAY_BASE
+            .saturating_mul(2u32.saturating_pow(failures - 1))
+            .min(FAILURE_DELAY_MAX)
+    }
+
+    /// Reset the username's failure count after a successful login.
+    /// The IP counter is left alone so one valid account can't mask guessing against others.
+    pub async fn record_success(&self, username: &str) {
+        let mut conn = self.redis.clone();
+        let name = normalize(username);
+        let result: redis::RedisResult<()> = conn
+            .del(&[failure_key("user", &name), lock_key("user", &name)])
+            .await;
+        if let Err(e) = result {
+            tracing::warn!("Failed to reset login failures for {}: {}", username, e);
+        }
+    }
+
+    /// Increment a failure counter and apply a lockout if it passed `threshold`.
+    /// Returns the new count (0 if Redis is unavailable).
+    async fn register_failure(&self, scope: &str, id: &str, threshold: u32) -> u32 {
+        let mut conn = self.redis.clone();
+        let key = failure_key(scope, id);
+
+        let failures: u32 = match conn.incr(&key, 1).await {
+            Ok(n) => n,
+            Err(e) => {
+                tracing::warn!("Failed to record login failure: {}", e);
+                return 0;
+            }
+        };
+        let _: redis::RedisResult<()> = conn.expire(&key, FAILURE_WINDOW_SECS).await;
+
+        if failures >= threshold {
+            let lockout_secs = LOCKOUT_BASE_SECS
+                .saturating_mul(2u64.saturating_pow(failures - threshold))
+                .min(LOCKOUT_MAX_SECS);
+            let _: redis::RedisResult<()> = conn.set_ex(lock_key(scope, id), 1, lockout_secs).await;
+            tracing::warn!(
+                "Locked out login {} {} for {}s after {} failed attempts",
+                scope, id, lockout_secs, failures
+            );
+        }
+
+        failures
+    }
+}
+
+fn normalize(username: &str) -> String {
+    username.trim().to_lowercase()
+}
+
+fn failure_key(scope: &str, id: &str) -> String {
+    format!("login:failures:{}:{}", scope, id)
+}
+
+fn lock_key(scope: &str, id: &str) -> String {
+    format!("login:lock:{}:{}", scope, id)
+}
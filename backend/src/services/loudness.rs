@@ -0,0 +1,255 @@
+//! EBU R128 loudness analysis and normalization
+//!
+//! Tracks mastered decades apart can differ by 10 LU or more, which is jarring on a
+//! station that mixes them. The loudness analysis job decodes each track the way the
+//! audio pipeline does and stores its integrated loudness and sample peak in
+//! `library_index`. Pipelines then scale every analyzed track towards the configured
+//! target (`LOUDNESS_TARGET_LUFS`); tracks that haven't been analyzed play unchanged.
+
+use crate::error::{AppError, Result};
+use crate::services::audio_cache::AudioCache;
+use crate::services::audio_pipeline::{AudioPipelineConfig, StreamOptions, TrackDecoder};
+use crate::services::jobs::JobContext;
+use crate::services::resampler::ResampleQuality;
+use crate::services::NavidromeClient;
+use ebur128::{EbuR128, Mode};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Highest level a boosted track's peak may reach (-1 dBFS)
+const PEAK_CEILING: f32 = 0.891;
+
+/// Largest boost applied to a quiet track (+12 dB)
+const MAX_GAIN: f32 = 3.98;
+
+/// Measured loudness of a track
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackLoudness {
+    /// Integrated loudness (LUFS)
+    pub integrated_lufs: f32,
+    /// Highest absolute sample value (1.0 = full scale)
+    pub sample_peak: f32,
+}
+
+impl TrackLoudness {
+    /// Linear gain that brings the track to `target_lufs`. Boosts are capped so the
+    /// track's peak stays below the ceiling; cuts are applied in full.
+    pub fn normalization_gain(&self, target_lufs: f32) -> f32 {
+        let gain = 10f32.powf((target_lufs - self.integrated_lufs) / 20.0);
+        if gain <= 1.0 {
+            return gain;
+        }
+        let headroom = if self.sample_peak > 0.0 {
+            (PEAK_CEILING / self.sample_peak).max(1.0)
+        } else {
+            MAX_GAIN
+        };
+        gain.min(headroom).min(MAX_GAIN)
+    }
+}
+
+/// Stored loudness of the given tracks; tracks not analyzed yet are missing
+pub async fn loudness_for(db: &PgPool, track_ids: &[String]) -> Result<HashMap<String, TrackLoudness>> {
+    let rows: Vec<(String, f32, f32)> = sqlx::query_as(
+        r#"
+        SELECT id, loudness_lufs, sample_peak FROM library_index
+        WHERE id = ANY($1) AND loudness_lufs IS NOT NULL AND sample_peak IS NOT NULL
+        "#,
+    )
+    .bind(track_ids)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, integrated_lufs, sample_peak)| {
+            (id, TrackLoudness { integrated_lufs, sample_peak })
+        })
+        .collect())
+}
+
+/// Outcome of an analysis run, recorded as the job result
+#[derive(Debug, Default, Serialize)]
+pub struct LoudnessAnalysisSummary {
+    pub analyzed: usize,
+    /// Tracks that couldn't be fetched or decoded; they stay pending for the next run
+    pub failed: usize,
+    /// Tracks too quiet to measure (silence), stored without a loudness
+    pub silent: usize,
+}
+
+/// Measures tracks that have no stored loudness yet
+pub struct LoudnessAnalyzer {
+    db: PgPool,
+    navidrome: Arc<NavidromeClient>,
+    pipeline_config: AudioPipelineConfig,
+}
+
+impl LoudnessAnalyzer {
+    pub fn new(
+        db: PgPool,
+        navidrome: Arc<NavidromeClient>,
+        stream_options: StreamOptions,
+        audio_cache: Option<Arc<AudioCache>>,
+        resample_quality: ResampleQuality,
+    ) -> Self {
+        Self {
+            db,
+            navidrome,
+            // Decode exactly as broadcast pipelines do, so the measurement matches what plays
+            pipeline_config: AudioPipelineConfig {
+                stream_options,
+                audio_cache,
+                resample_quality,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Analyze up to `limit` tracks without a stored loudness
+    pub async fn analyze_pending(&self, ctx: &JobContext, limit: i64) -> Result<LoudnessAnalysisSummary> {
+        let pending: Vec<(String, String, String)> = sqlx::query_as(
+            r#"
+            SELECT id, artist, title FROM library_index
+            WHERE loudness_analyzed_at IS NULL
+            ORDER BY id
+            LIMIT $1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.db)
+        .await?;
+
+        let total = pending.len();
+        let mut summary = LoudnessAnalysisSummary::default();
+        ctx.log("info", format!("Analyzing loudness of {} tracks", total)).await;
+
+        for (i, (track_id, artist, title)) in pending.into_iter().enumerate() {
+            if ctx.is_cancelled() {
+                ctx.log("info", "Cancelled").await;
+                break;
+            }
+            ctx.progress(i as f64 / total as f64, format!("{} - {}", artist, title))
+                .await;
+
+            let loudness = match self.measure(&track_id).await {
+                Ok(loudness) => loudness,
+                Err(e) => {
+                    summary.failed += 1;
+                    ctx.log("warn", format!("Failed to analyze {}: {}", track_id, e)).await;
+                    continue;
+                }
+            };
+
+            sqlx::query(
+                r#"
+                UPDATE library_index
+                SET loudness_lufs = $2, sample_peak = $3, loudness_analyzed_at = NOW()
+                WHERE id = $1
+                "#,
+            )
+            .bind(&track_id)
+            .bind(loudness.map(|l| l.integrated_lufs))
+            .bind(loudness.map(|l| l.sample_peak))
+            .execute(&self.db)
+            .await?;
+
+            match loudness {
+                Some(_) => summary.analyzed += 1,
+                None => summary.silent += 1,
+            }
+        }
+
+        ctx.progress(1.0, format!("Analyzed {} tracks", summary.analyzed)).await;
+        Ok(summary)
+    }
+
+    /// Integrated loudness and sample peak of a track, or None if it is silent
+    async fn measure(&self, track_id: &str) -> Result<Option<TrackLoudness>> {
+        let config = &self.pipeline_config;
+        let mut decoder = TrackDecoder::open(&self.navidrome, track_id, config, 0).await?;
+        let mut meter = EbuR128::new(config.channels as u32, config.sample_rate, Mode::I | Mode::SAMPLE_PEAK)
+            .map_err(meter_error)?;
+
+        while let Some(chunk) = decoder.next_chunk().await {
+            meter.add_frames_f32(&chunk?).map_err(meter_error)?;
+        }
+
+        measurement(&meter, config.channels)
+    }
+}
+
+/// Read the result out of a meter that has been fed a whole track
+fn measurement(meter: &EbuR128, channels: usize) -> Result<Option<TrackLoudness>> {
+    let integrated = meter.loudness_global().map_err(meter_error)?;
+    // Silence has no gated loudness
+    if !integrated.is_finite() {
+        return Ok(None);
+    }
+
+    let mut peak: f64 = 0.0;
+    for channel in 0..channels {
+        peak = peak.max(meter.sample_peak(channel as u32).map_err(meter_error)?);
+    }
+
+    Ok(Some(TrackLoudness {
+        integrated_lufs: integrated as f32,
+        sample_peak: peak as f32,
+    }))
+}
+
+fn meter_error(e: ebur128::Error) -> AppError {
+    AppError::Decode(format!("Loudness measurement failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalization_gain() {
+        // 6 dB too loud: halve
+        let loud = TrackLoudness { integrated_lufs: -10.0, sample_peak: 1.0 };
+        assert!((loud.normalization_gain(-16.0) - 0.501).abs() < 0.01);
+
+        // 6 dB too quiet with plenty of headroom: double
+        let quiet = TrackLoudness { integrated_lufs: -22.0, sample_peak: 0.25 };
+        assert!((quiet.normalization_gain(-16.0) - 1.995).abs() < 0.01);
+
+        // Boost limited by the peak ceiling
+        let peaky = TrackLoudness { integrated_lufs: -22.0, sample_peak: 0.8 };
+        assert!((peaky.normalization_gain(-16.0) - PEAK_CEILING / 0.8).abs() < 0.001);
+
+        // Never cut a quiet track because its peak is already high
+        let clipped = TrackLoudness { integrated_lufs: -20.0, sample_peak: 1.0 };
+        assert_eq!(clipped.normalization_gain(-16.0), 1.0);
+
+        // Very quiet tracks get at most the maximum boost
+        let whisper = TrackLoudness { integrated_lufs: -50.0, sample_peak: 0.01 };
+        assert_eq!(whisper.normalization_gain(-16.0), MAX_GAIN);
+    }
+
+    #[test]
+    fn test_measure_sine() {
+        // A full-scale 1 kHz sine in both channels measures 0 LUFS; at half amplitude
+        // it is 6 dB lower
+        let rate = 44100;
+        let mut meter = EbuR128::new(2, rate, Mode::I | Mode::SAMPLE_PEAK).unwrap();
+        let frames: Vec<f32> = (0..rate as usize * 5)
+            .flat_map(|i| {
+                let s = 0.5 * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / rate as f32).sin();
+                [s, s]
+            })
+            .collect();
+        meter.add_frames_f32(&frames).unwrap();
+
+        let loudness = measurement(&meter, 2).unwrap().unwrap();
+        assert!((loudness.integrated_lufs - -6.0).abs() < 0.5, "{}", loudness.integrated_lufs);
+        assert!((loudness.sample_peak - 0.5).abs() < 0.01);
+
+        let silent = EbuR128::new(2, rate, Mode::I | Mode::SAMPLE_PEAK).unwrap();
+        assert_eq!(measurement(&silent, 2).unwrap(), None);
+    }
+}
@@ -0,0 +1,230 @@
+//! Schema migration status and rollback
+//!
+//! Forward migrations live in `migrations/` and are applied by sqlx at startup.
+//! Down-migrations for risky schema changes live in `migrations-down/` (sqlx does not
+//! allow mixing reversible and simple migrations in one directory) and are applied by
+//! `--rollback-to <version>`. See `docs/migrations.md` for the rollback procedure.
+
+use crate::error::{AppError, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::migrate::Migrator;
+use sqlx::{Executor, PgPool};
+
+/// Forward migrations embedded in the binary
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Down-migrations by version. A version without an entry cannot be rolled back.
+const DOWN_MIGRATIONS: &[(i64, &str)] = &[
+    (5, include_str!("../../migrations-down/005_audio_embeddings.down.sql")),
+    (6, include_str!("../../migrations-down/006_library_path.down.sql")),
+    (7, include_str!("../../migrations-down/007_l2_distance_index.down.sql")),
+    (8, include_str!("../../migrations-down/008_visualization_cache.down.sql")),
+    (9, include_str!("../../migrations-down/009_settings.down.sql")),
+    (10, include_str!("../../migrations-down/010_background_jobs.down.sql")),
+    (11, include_str!("../../migrations-down/011_job_logs.down.sql")),
+    (12, include_str!("../../migrations-down/012_webhooks.down.sql")),
+    (13, include_str!("../../migrations-down/013_navidrome_radio.down.sql")),
+    (14, include_str!("../../migrations-down/014_api_keys.down.sql")),
+    (15, include_str!("../../migrations-down/015_expanded_roles.down.sql")),
+    (16, include_str!("../../migrations-down/016_station_collaborators.down.sql")),
+    (17, include_str!("../../migrations-down/017_user_preferences.down.sql")),
+    (18, include_str!("../../migrations-down/018_listening_history.down.sql")),
+    (19, include_str!("../../migrations-down/019_daily_mix.down.sql")),
+    (20, include_str!("../../migrations-down/020_user_admin.down.sql")),
+    (21, include_str!("../../migrations-down/021_rating_trigger_delete.down.sql")),
+    (22, include_str!("../../migrations-down/022_account_deletion.down.sql")),
+    (23, include_str!("../../migrations-down/023_user_sessions.down.sql")),
+    (24, include_str!("../../migrations-down/024_stream_tokens.down.sql")),
+    (25, include_str!("../../migrations-down/025_station_playlist_export.down.sql")),
+    (26, include_str!("../../migrations-down/026_playlist_sources.down.sql")),
+    (27, include_str!("../../migrations-down/027_navidrome_accounts.down.sql")),
+    (28, include_str!("../../migrations-down/028_track_loudness.down.sql")),
+    (29, include_str!("../../migrations-down/029_embedding_backfill.down.sql")),
+    (30, include_str!("../../migrations-down/030_hnsw_index.down.sql")),
+    (31, include_str!("../../migrations-down/031_scrobbling.down.sql")),
+    (32, include_str!("../../migrations-down/032_external_metadata.down.sql")),
+    (33, include_str!("../../migrations-down/033_station_schedule.down.sql")),
+    (34, include_str!("../../migrations-down/034_track_requests.down.sql")),
+    (35, include_str!("../../migrations-down/035_track_feedback.down.sql")),
+    (36, include_str!("../../migrations-down/036_track_embedding_windows.down.sql")),
+    (37, include_str!("../../migrations-down/037_embedding_model_migration.down.sql")),
+    (38, include_str!("../../migrations-down/038_station_analytics.down.sql")),
+    (39, include_str!("../../migrations-down/039_oidc_identities.down.sql")),
+    (40, include_str!("../../migrations-down/040_refresh_tokens.down.sql")),
+    (41, include_str!("../../migrations-down/041_station_permissions.down.sql")),
+    (42, include_str!("../../migrations-down/042_llm_usage.down.sql")),
+    (43, include_str!("../../migrations-down/043_track_metadata.down.sql")),
+    (44, include_str!("../../migrations-down/044_track_duplicates.down.sql")),
+    (45, include_str!("../../migrations-down/045_explicit_content.down.sql")),
+    (46, include_str!("../../migrations-down/046_program_auto_curation.down.sql")),
+];
+
+#[derive(Debug, Serialize)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub description: String,
+    pub installed_on: DateTime<Utc>,
+    pub success: bool,
+    /// False if the migration file shipped with this binary differs from what was applied
+    pub checksum_matches: bool,
+    /// Whether a down-migration exists for this version
+    pub reversible: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PendingMigration {
+    pub version: i64,
+    pub description: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MigrationStatus {
+    /// Highest applied version, if any migrations have run
+    pub current_version: Option<i64>,
+    /// Highest version known to this binary
+    pub latest_version: i64,
+    pub applied: Vec<AppliedMigration>,
+    pub pending: Vec<PendingMigration>,
+    /// Human-readable schema drift warnings (empty when the schema matches the binary)
+    pub drift: Vec<String>,
+}
+
+/// Row of `_sqlx_migrations`: version, description, installed_on, success, checksum
+type MigrationRow = (i64, String, DateTime<Utc>, bool, Vec<u8>);
+
+/// Compare the applied migrations in the database with those embedded in the binary
+pub async fn status(db: &PgPool) -> Result<MigrationStatus> {
+    let table_exists: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(db)
+        .await?;
+
+    let rows: Vec<MigrationRow> = if table_exists {
+        sqlx::query_as(
+            "SELECT version, description, installed_on, success, checksum FROM _sqlx_migrations ORDER BY version",
+        )
+        .fetch_all(db)
+        .await?
+    } else {
+        Vec::new()
+    };
+
+    let mut drift = Vec::new();
+    let mut applied = Vec::with_capacity(rows.len());
+
+    for (version, description, installed_on, success, checksum) in rows {
+        let known = MIGRATOR.iter().find(|m| m.version == version);
+        let checksum_matches = known.is_some_and(|m| *m.checksum == *checksum);
+
+        match known {
+            None => drift.push(format!(
+                "Migration {} ({}) is applied but unknown to this build - the database is newer than the binary",
+                version, description
+            )),
+            Some(_) if !checksum_matches => drift.push(format!(
+                "Migration {} ({}) was modified after it was applied",
+                version, description
+            )),
+            Some(_) => {}
+        }
+        if !success {
+            drift.push(format!(
+                "Migration {} ({}) did not complete successfully",
+                version, description
+            ));
+        }
+
+        applied.push(AppliedMigration {
+            version,
+            description,
+            installed_on,
+            success,
+            checksum_matches,
+            reversible: down_migration(version).is_some(),
+        });
+    }
+
+    let pending = MIGRATOR
+        .iter()
+        .filter(|m| !applied.iter().any(|a| a.version == m.version))
+        .map(|m| PendingMigration {
+            version: m.version,
+            description: m.description.to_string(),
+        })
+        .collect();
+
+    Ok(MigrationStatus {
+        current_version: applied.iter().map(|a| a.version).max(),
+        latest_version: MIGRATOR.iter().map(|m| m.version).max().unwrap_or(0),
+        applied,
+        pending,
+        drift,
+    })
+}
+
+/// Log schema drift before migrations run, so a refusal to migrate has some context
+pub async fn warn_on_drift(db: &PgPool) -> Result<()> {
+    let status = status(db).await?;
+
+    for warning in &status.drift {
+        tracing::warn!("Schema drift: {}", warning);
+    }
+    if !status.pending.is_empty() {
+        tracing::info!(
+            "Schema at version {:?}, applying {} pending migration(s)",
+            status.current_version,
+            status.pending.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Revert every applied migration newer than `target` using its down-migration,
+/// newest first, in a single transaction. Returns the reverted versions.
+pub async fn rollback_to(db: &PgPool, target: i64) -> Result<Vec<i64>> {
+    let status = status(db).await?;
+
+    let mut versions: Vec<i64> = status
+        .applied
+        .iter()
+        .map(|a| a.version)
+        .filter(|v| *v > target)
+        .collect();
+    versions.sort_unstable_by(|a, b| b.cmp(a));
+
+    let missing: Vec<i64> = versions
+        .iter()
+        .copied()
+        .filter(|v| down_migration(*v).is_none())
+        .collect();
+    if !missing.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "Cannot roll back to {}: no down-migration for version(s) {:?}",
+            target, missing
+        )));
+    }
+
+    let mut tx = db.begin().await?;
+    for version in &versions {
+        if let Some(sql) = down_migration(*version) {
+            tracing::info!("Reverting migration {}", version);
+            // Down scripts may contain several statements, so run them unprepared
+            (&mut *tx).execute(sql).await?;
+        }
+        sqlx::query("DELETE FROM _sqlx_migrations WHERE version = $1")
+            .bind(version)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(versions)
+}
+
+fn down_migration(version: i64) -> Option<&'static str> {
+    DOWN_MIGRATIONS
+        .iter()
+        .find(|(v, _)| *v == version)
+        .map(|(_, sql)| *sql)
+}
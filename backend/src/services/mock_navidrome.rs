@@ -0,0 +1,368 @@
+//! In-process mock of the Subsonic API for tests
+//!
+//! [`MockNavidrome::start`] serves a small fixture library on a random local port, so
+//! code that talks to Navidrome through [`NavidromeClient`] can be exercised end to end
+//! without a real server. Supported endpoints: `ping`, `search3`, `getSong`,
+//! `getRandomSongs`, `stream` (a generated WAV tone), `createPlaylist`, `updatePlaylist`,
+//! `getPlaylists` and `getPlaylist`. Anything else answers with a Subsonic error.
+//!
+//! Built for unit tests and with the `mock-navidrome` feature.
+
+use crate::services::NavidromeClient;
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+/// A track in the mock library
+#[derive(Debug, Clone)]
+pub struct MockSong {
+    pub id: String,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub genre: String,
+    pub year: i32,
+    /// Seconds of audio `stream` returns
+    pub duration: i32,
+}
+
+impl MockSong {
+    pub fn new(id: &str, title: &str, artist: &str, genre: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            title: title.to_string(),
+            artist: artist.to_string(),
+            album: format!("{} - Greatest Hits", artist),
+            genre: genre.to_string(),
+            year: 2000,
+            duration: 1,
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "id": self.id,
+            "title": self.title,
+            "artist": self.artist,
+            "album": self.album,
+            "genre": self.genre,
+            "genres": [{ "name": self.genre }],
+            "year": self.year,
+            "duration": self.duration,
+            "path": format!("{}/{}/{}.wav", self.artist, self.album, self.title),
+            "contentType": "audio/wav",
+            "suffix": "wav",
+        })
+    }
+}
+
+/// A playlist created through the mock
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockPlaylist {
+    pub id: String,
+    pub name: String,
+    pub song_ids: Vec<String>,
+}
+
+#[derive(Default)]
+struct MockState {
+    songs: Vec<MockSong>,
+    playlists: Mutex<Vec<MockPlaylist>>,
+    /// Endpoints called, in order
+    requests: Mutex<Vec<String>>,
+}
+
+/// Canned library: a few tracks across three genres
+pub fn fixture_songs() -> Vec<MockSong> {
+    vec![
+        MockSong::new("tr-1", "Blue Train", "John Coltrane", "Jazz"),
+        MockSong::new("tr-2", "So What", "Miles Davis", "Jazz"),
+        MockSong::new("tr-3", "Take Five", "Dave Brubeck", "Jazz"),
+        MockSong::new("tr-4", "Teardrop", "Massive Attack", "Trip-Hop"),
+        MockSong::new("tr-5", "Glory Box", "Portishead", "Trip-Hop"),
+        MockSong::new("tr-6", "Windowlicker", "Aphex Twin", "Electronic"),
+    ]
+}
+
+pub struct MockNavidrome {
+    base_url: String,
+    state: Arc<MockState>,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl MockNavidrome {
+    /// Serve `songs` on a random local port until the mock is dropped
+    pub async fn start(songs: Vec<MockSong>) -> Self {
+        let state = Arc::new(MockState {
+            songs,
+            ..Default::default()
+        });
+
+        let app = Router::new()
+            .route("/rest/:endpoint", get(handle).post(handle))
+            .with_state(state.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind mock Navidrome");
+        let base_url = format!("http://{}", listener.local_addr().expect("mock address"));
+        let server = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+
+        Self { base_url, state, server }
+    }
+
+    /// Serve the [`fixture_songs`] library
+    pub async fn with_fixtures() -> Self {
+        Self::start(fixture_songs()).await
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Client pointed at the mock (any credentials are accepted)
+    pub fn client(&self) -> NavidromeClient {
+        NavidromeClient::new(self.base_url.clone(), "test".to_string(), "test".to_string())
+    }
+
+    /// Playlists created or updated so far
+    pub fn playlists(&self) -> Vec<MockPlaylist> {
+        self.state.playlists.lock().unwrap().clone()
+    }
+
+    /// Endpoints called so far, without the `.view` suffix
+    pub fn requests(&self) -> Vec<String> {
+        self.state.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockNavidrome {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+async fn handle(
+    State(state): State<Arc<MockState>>,
+    Path(endpoint): Path<String>,
+    Query(params): Query<Vec<(String, String)>>,
+) -> Response {
+    let endpoint = endpoint.trim_end_matches(".view").to_string();
+    state.requests.lock().unwrap().push(endpoint.clone());
+
+    let param = |name: &str| params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+    let all = |name: &str| -> Vec<String> {
+        params.iter().filter(|(k, _)| k == name).map(|(_, v)| v.clone()).collect()
+    };
+    let number = |name: &str, default: usize| param(name).and_then(|v| v.parse().ok()).unwrap_or(default);
+
+    match endpoint.as_str() {
+        "ping" => ok(json!({})),
+        "search3" => {
+            let query = param("query").unwrap_or_default().trim_matches('"').to_lowercase();
+            let songs: Vec<_> = state
+                .songs
+                .iter()
+                .filter(|s| {
+                    query.is_empty()
+                        || [&s.title, &s.artist, &s.album, &s.genre]
+                            .iter()
+                            .any(|field| field.to_lowercase().contains(&query))
+                })
+                .skip(number("songOffset", 0))
+                .take(number("songCount", 20))
+                .map(MockSong::to_json)
+                .collect();
+            ok(json!({ "searchResult3": { "song": songs } }))
+        }
+        "getSong" => match state.songs.iter().find(|s| Some(s.id.as_str()) == param("id")) {
+            Some(song) => ok(json!({ "song": song.to_json() })),
+            None => error(70, "Song not found"),
+        },
+        "getRandomSongs" => {
+            let songs: Vec<_> = state.songs.iter().take(number("size", 10)).map(MockSong::to_json).collect();
+            ok(json!({ "randomSongs": { "song": songs } }))
+        }
+        "stream" => match state.songs.iter().find(|s| Some(s.id.as_str()) == param("id")) {
+            Some(song) => ([(header::CONTENT_TYPE, "audio/wav")], wav_tone(song.duration)).into_response(),
+            None => error(70, "Song not found"),
+        },
+        "createPlaylist" => {
+            let mut playlists = state.playlists.lock().unwrap();
+            let song_ids = all("songId");
+            let playlist = match param("playlistId") {
+                Some(id) => match playlists.iter_mut().find(|p| p.id == id) {
+                    Some(existing) => {
+                        existing.song_ids = song_ids;
+                        existing.clone()
+                    }
+                    None => return error(70, "Playlist not found"),
+                },
+                None => {
+                    let playlist = MockPlaylist {
+                        id: format!("pl-{}", playlists.len() + 1),
+                        name: param("name").unwrap_or("Untitled").to_string(),
+                        song_ids,
+                    };
+                    playlists.push(playlist.clone());
+                    playlist
+                }
+            };
+            ok(json!({ "playlist": playlist_json(&playlist, &state.songs) }))
+        }
+        "updatePlaylist" => {
+            let mut playlists = state.playlists.lock().unwrap();
+            let Some(playlist) = playlists.iter_mut().find(|p| Some(p.id.as_str()) == param("playlistId")) else {
+                return error(70, "Playlist not found");
+            };
+            if let Some(name) = param("name") {
+                playlist.name = name.to_string();
+            }
+            playlist.song_ids.extend(all("songIdToAdd"));
+            ok(json!({}))
+        }
+        "getPlaylists" => {
+            let playlists = state.playlists.lock().unwrap();
+            let list: Vec<_> = playlists.iter().map(|p| playlist_json(p, &[])).collect();
+            ok(json!({ "playlists": { "playlist": list } }))
+        }
+        "getPlaylist" => {
+            let playlists = state.playlists.lock().unwrap();
+            match playlists.iter().find(|p| Some(p.id.as_str()) == param("id")) {
+                Some(playlist) => ok(json!({ "playlist": playlist_json(playlist, &state.songs) })),
+                None => error(70, "Playlist not found"),
+            }
+        }
+        other => error(0, &format!("Endpoint {} is not mocked", other)),
+    }
+}
+
+fn playlist_json(playlist: &MockPlaylist, songs: &[MockSong]) -> serde_json::Value {
+    let entries: Vec<_> = playlist
+        .song_ids
+        .iter()
+        .filter_map(|id| songs.iter().find(|s| &s.id == id))
+        .map(MockSong::to_json)
+        .collect();
+    json!({
+        "id": playlist.id,
+        "name": playlist.name,
+        "owner": "test",
+        "songCount": playlist.song_ids.len(),
+        "duration": 0,
+        "entry": entries,
+    })
+}
+
+fn ok(mut body: serde_json::Value) -> Response {
+    let fields = body.as_object_mut().expect("response body is an object");
+    fields.insert("status".to_string(), json!("ok"));
+    fields.insert("version".to_string(), json!("1.16.1"));
+    fields.insert("type".to_string(), json!("navidrome"));
+    fields.insert("serverVersion".to_string(), json!("mock"));
+    Json(json!({ "subsonic-response": body })).into_response()
+}
+
+fn error(code: i32, message: &str) -> Response {
+    Json(json!({
+        "subsonic-response": {
+            "status": "failed",
+            "version": "1.16.1",
+            "error": { "code": code, "message": message },
+        }
+    }))
+    .into_response()
+}
+
+/// `seconds` of a 440 Hz tone as 16-bit stereo 44.1 kHz WAV
+pub fn wav_tone(seconds: i32) -> Vec<u8> {
+    const SAMPLE_RATE: u32 = 44_100;
+    const CHANNELS: u16 = 2;
+
+    let frames = SAMPLE_RATE * seconds.max(0) as u32;
+    let data_len = frames * CHANNELS as u32 * 2;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&CHANNELS.to_le_bytes());
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * CHANNELS as u32 * 2).to_le_bytes());
+    wav.extend_from_slice(&(CHANNELS * 2).to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+
+    for frame in 0..frames {
+        let t = frame as f32 / SAMPLE_RATE as f32;
+        let sample = ((t * 440.0 * std::f32::consts::TAU).sin() * i16::MAX as f32 * 0.25) as i16;
+        for _ in 0..CHANNELS {
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+    }
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_search_and_get_song() {
+        let mock = MockNavidrome::with_fixtures().await;
+        let client = mock.client();
+
+        let jazz = client.search_tracks("jazz", 10).await.unwrap();
+        let ids: Vec<_> = jazz.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["tr-1", "tr-2", "tr-3"]);
+        assert_eq!(jazz[0].genre, vec!["Jazz"]);
+
+        let track = client.get_track("tr-4").await.unwrap();
+        assert_eq!(track.artist, "Massive Attack");
+        assert!(client.get_track("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_playlist_create_replace_append() {
+        let mock = MockNavidrome::with_fixtures().await;
+        let client = mock.client();
+
+        let ids = vec!["tr-1".to_string(), "tr-2".to_string()];
+        let playlist_id = client.create_playlist("Radio", &ids).await.unwrap();
+        client.replace_playlist(&playlist_id, &["tr-3".to_string()]).await.unwrap();
+        client.append_to_playlist(&playlist_id, &["tr-4".to_string()]).await.unwrap();
+
+        assert_eq!(
+            mock.playlists(),
+            vec![MockPlaylist {
+                id: playlist_id.clone(),
+                name: "Radio".to_string(),
+                song_ids: vec!["tr-3".to_string(), "tr-4".to_string()],
+            }]
+        );
+        assert_eq!(client.get_playlist_track_ids(&playlist_id).await.unwrap(), vec!["tr-3", "tr-4"]);
+        assert_eq!(client.get_playlists().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_full_library_paging_via_search3() {
+        let mock = MockNavidrome::with_fixtures().await;
+        let client = NavidromeClient::with_api_key(mock.base_url().to_string(), "test".to_string(), "key".to_string());
+
+        let (first, _) = client.get_all_songs_paginated(4, 0).await.unwrap();
+        let (second, _) = client.get_all_songs_paginated(4, 4).await.unwrap();
+        let (third, _) = client.get_all_songs_paginated(4, 8).await.unwrap();
+        assert_eq!((first.len(), second.len(), third.len()), (4, 2, 0));
+        assert_eq!(mock.requests(), vec!["search3"; 3]);
+    }
+}
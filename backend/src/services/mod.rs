@@ -1,17 +1,68 @@
 pub mod ai_curator;
+pub mod announcer;
 pub mod audio_broadcaster;
+pub mod audio_codec;
+pub mod audio_cache;
 pub mod audio_encoder;
+pub mod audio_features;
 pub mod audio_pipeline;
+pub mod audit;
 pub mod auth;
+pub mod broadcast_archive;
+pub mod cluster;
 pub mod curation;
+pub mod daily_mix;
+pub mod duplicates;
+pub mod embedding_worker;
+pub mod energy_arc;
+pub mod execution_provider;
+pub mod id3;
 pub mod hybrid_curator;
+pub mod jobs;
+pub mod lastfm;
 pub mod library_indexer;
+pub mod library_scheduler;
+pub mod library_watcher;
+pub mod listenbrainz;
+pub mod listening_history;
+pub mod llm;
+pub mod login_guard;
+pub mod loudness;
+pub mod migrations;
+pub mod model_manifest;
+#[cfg(any(test, feature = "mock-navidrome"))]
+pub mod mock_navidrome;
+pub mod musicbrainz;
 pub mod navidrome;
+pub mod navidrome_accounts;
+pub mod oidc;
+pub mod playlist_import;
+pub mod playlist_sync;
+pub mod program_curator;
+pub mod radio_publisher;
+pub mod rate_limit;
+pub mod resampler;
+pub mod ratings;
+pub mod schedule;
+pub mod scrobble_queue;
 pub mod seed_selector;
+pub mod secret_box;
+pub mod separation;
+pub mod sessions;
+pub mod station_analytics;
+pub mod station_events;
 pub mod station_manager;
+pub mod track_requests;
+pub mod user_data;
+pub mod vector_index;
+pub mod webhooks;
 
 pub use ai_curator::AiCurator;
 pub use auth::AuthService;
 pub use curation::CurationEngine;
+pub use daily_mix::DailyMixService;
+pub use jobs::JobManager;
+pub use login_guard::LoginGuard;
 pub use navidrome::NavidromeClient;
 pub use station_manager::StationManager;
+pub use webhooks::WebhookDispatcher;
@@ -0,0 +1,83 @@
+//! Audio encoder model manifests
+//!
+//! Each released model comes with a manifest naming its version and SHA-256 checksum.
+//! A download is only kept if its checksum matches, and the manifest is saved next to
+//! the model (`audio_encoder.json` beside `audio_encoder.onnx`), where it supplies the
+//! model version stored with embeddings unless `AUDIO_ENCODER_MODEL_VERSION` is set.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModelManifest {
+    pub version: String,
+    /// Hex-encoded SHA-256 of the model file
+    pub sha256: String,
+}
+
+impl ModelManifest {
+    /// The saved manifest of a model, if it has a readable one
+    pub fn for_model(model_path: &Path) -> Option<Self> {
+        let text = std::fs::read_to_string(manifest_path(model_path)).ok()?;
+        match serde_json::from_str(&text) {
+            Ok(manifest) => Some(manifest),
+            Err(e) => {
+                tracing::warn!("Ignoring invalid model manifest for {:?}: {}", model_path, e);
+                None
+            }
+        }
+    }
+
+    pub fn save_for_model(&self, model_path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(manifest_path(model_path), json)
+    }
+
+    /// Whether `bytes` are the model this manifest describes
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        checksum(bytes).eq_ignore_ascii_case(self.sha256.trim())
+    }
+
+    /// Check a model file on disk against the manifest
+    pub fn verify_file(&self, model_path: &Path) -> std::io::Result<bool> {
+        let mut file = std::fs::File::open(model_path)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 1 << 16];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(hex::encode(hasher.finalize()).eq_ignore_ascii_case(self.sha256.trim()))
+    }
+}
+
+/// Where the manifest of a model is saved
+pub fn manifest_path(model_path: &Path) -> PathBuf {
+    model_path.with_extension("json")
+}
+
+/// Hex-encoded SHA-256
+pub fn checksum(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_matches() {
+        let manifest = ModelManifest {
+            version: "teticio/audio-encoder-v1".to_string(),
+            sha256: "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD".to_string(),
+        };
+        assert!(manifest.matches(b"abc"));
+        assert!(!manifest.matches(b"abd"));
+        assert_eq!(manifest_path(Path::new("/app/models/audio_encoder.onnx")), Path::new("/app/models/audio_encoder.json"));
+    }
+}
@@ -0,0 +1,528 @@
+//! MusicBrainz and AcousticBrainz track metadata
+//!
+//! The track metadata job resolves library tracks to MusicBrainz recordings by title
+//! and artist, preferring recordings released on the track's album and with a length
+//! close to the track's. A match gives the track its `musicbrainz_id`, the recording's
+//! tags and the year of its first release, which corrects the tagged year of remasters
+//! and compilations (`original_year`).
+//!
+//! For matched recordings, AcousticBrainz is asked for the features it computed from
+//! the audio: tempo, key and the danceability, mood and voice/instrumental classifiers.
+//! Those replace the values AI analysis guessed from titles, and AI analysis doesn't
+//! overwrite them afterwards. AcousticBrainz no longer takes submissions, so recent
+//! recordings usually have no data; set `ACOUSTICBRAINZ_API_URL` empty to skip it.
+//!
+//! Everything fetched is kept in `track_external_metadata`, one row per track and
+//! source, including misses, so tracks aren't looked up again for 90 days.
+
+use crate::error::{AppError, Result};
+use crate::services::jobs::JobContext;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// `source` of the rows in `track_external_metadata`, and `features_source` of
+/// measured features in `library_index`
+pub const MUSICBRAINZ: &str = "musicbrainz";
+pub const ACOUSTICBRAINZ: &str = "acousticbrainz";
+
+pub const DEFAULT_API_URL: &str = "https://musicbrainz.org/ws/2";
+pub const DEFAULT_ACOUSTICBRAINZ_URL: &str = "https://acousticbrainz.org/api/v1";
+
+/// MusicBrainz asks for a User-Agent that identifies the application
+const USER_AGENT: &str = concat!(
+    "navidrome-radio/",
+    env!("CARGO_PKG_VERSION"),
+    " ( https://github.com/ethanbarclay/navidrome-radio )"
+);
+
+/// Pause between MusicBrainz requests (it allows one per second)
+const REQUEST_DELAY: Duration = Duration::from_millis(1100);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Search results scoring lower are not considered the same recording
+const MIN_SCORE: i64 = 80;
+
+/// Recordings whose length differs more from the track's are other versions
+const MAX_LENGTH_DIFF_MS: i64 = 5_000;
+
+const TOP_TAGS: usize = 10;
+
+/// Track metadata older than this is fetched again
+const METADATA_MAX_AGE_DAYS: i32 = 90;
+
+#[derive(Debug, Clone)]
+pub struct MusicBrainzConfig {
+    pub api_url: String,
+    /// None skips the AcousticBrainz lookups
+    pub acousticbrainz_url: Option<String>,
+}
+
+/// Outcome of a metadata run, recorded as the job result
+#[derive(Debug, Default, Serialize)]
+pub struct TrackMetadataSummary {
+    pub matched: usize,
+    /// Tracks no recording matched; stored empty so they aren't looked up again soon
+    pub not_found: usize,
+    /// Matched tracks whose first release year differs from the tagged one
+    pub year_corrected: usize,
+    /// Matched tracks AcousticBrainz had features for
+    pub with_features: usize,
+    pub failed: usize,
+}
+
+/// A library track to look up
+#[derive(Debug, sqlx::FromRow)]
+struct IndexedTrack {
+    id: String,
+    title: String,
+    artist: String,
+    album: String,
+    year: Option<i32>,
+    /// Seconds
+    duration: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecordingSearch {
+    #[serde(default)]
+    recordings: Vec<Recording>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Recording {
+    id: String,
+    #[serde(default)]
+    score: i64,
+    /// Milliseconds
+    length: Option<i64>,
+    #[serde(rename = "first-release-date")]
+    first_release_date: Option<String>,
+    #[serde(default)]
+    releases: Vec<Release>,
+    #[serde(default)]
+    tags: Vec<Tag>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Release {
+    title: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Tag {
+    name: String,
+    #[serde(default)]
+    count: i64,
+}
+
+impl Recording {
+    /// Tags with positive votes, lowercased, most voted first
+    fn top_tags(&self) -> Vec<String> {
+        let mut tags: Vec<&Tag> = self.tags.iter().filter(|t| t.count > 0).collect();
+        tags.sort_by_key(|t| -t.count);
+        tags.into_iter()
+            .map(|t| t.name.trim().to_lowercase())
+            .filter(|t| !t.is_empty())
+            .take(TOP_TAGS)
+            .collect()
+    }
+
+    fn first_release_year(&self) -> Option<i32> {
+        self.first_release_date.as_deref()?.get(..4)?.parse().ok()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LowLevel {
+    #[serde(default)]
+    rhythm: Rhythm,
+    #[serde(default)]
+    tonal: Tonal,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Rhythm {
+    bpm: Option<f64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Tonal {
+    key_key: Option<String>,
+    key_scale: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HighLevel {
+    highlevel: HashMap<String, Classifier>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Classifier {
+    /// Probability of each class
+    all: HashMap<String, f64>,
+}
+
+/// Audio features AcousticBrainz computed for a recording
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct AcousticFeatures {
+    pub bpm: Option<f64>,
+    /// e.g. "A minor"
+    pub key: Option<String>,
+    pub danceability: Option<f64>,
+    /// Probability of the "happy" mood, used as valence
+    pub valence: Option<f64>,
+    pub acousticness: Option<f64>,
+    pub instrumentalness: Option<f64>,
+}
+
+impl AcousticFeatures {
+    fn from_documents(low: Option<LowLevel>, high: Option<HighLevel>) -> Option<Self> {
+        let mut features = AcousticFeatures::default();
+        if let Some(low) = low {
+            features.bpm = low.rhythm.bpm.filter(|bpm| *bpm > 0.0);
+            features.key = match (low.tonal.key_key, low.tonal.key_scale) {
+                (Some(key), Some(scale)) if !key.is_empty() => Some(format!("{} {}", key, scale)),
+                _ => None,
+            };
+        }
+        if let Some(high) = high {
+            let probability =
+                |classifier: &str, class: &str| high.highlevel.get(classifier)?.all.get(class).copied();
+            features.danceability = probability("danceability", "danceable");
+            features.valence = probability("mood_happy", "happy");
+            features.acousticness = probability("mood_acoustic", "acoustic");
+            features.instrumentalness = probability("voice_instrumental", "instrumental");
+        }
+        (features != AcousticFeatures::default()).then_some(features)
+    }
+}
+
+pub struct MusicBrainzClient {
+    db: PgPool,
+    http: reqwest::Client,
+    config: MusicBrainzConfig,
+}
+
+impl MusicBrainzClient {
+    pub fn new(db: PgPool, config: MusicBrainzConfig) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(USER_AGENT)
+            .build()
+            .unwrap_or_default();
+
+        Self { db, http, config }
+    }
+
+    /// Look up up to `limit` library tracks that haven't been looked up yet (or only
+    /// long ago)
+    pub async fn enrich_tracks(&self, ctx: &JobContext, limit: i64) -> Result<TrackMetadataSummary> {
+        let tracks: Vec<IndexedTrack> = sqlx::query_as(
+            r#"
+            SELECT li.id, li.title, li.artist, li.album, li.year, li.duration
+            FROM library_index li
+            LEFT JOIN track_external_metadata m ON m.track_id = li.id AND m.source = $1
+            WHERE m.fetched_at IS NULL OR m.fetched_at < NOW() - make_interval(days => $2)
+            ORDER BY m.fetched_at NULLS FIRST, li.id
+            LIMIT $3
+            "#,
+        )
+        .bind(MUSICBRAINZ)
+        .bind(METADATA_MAX_AGE_DAYS)
+        .bind(limit)
+        .fetch_all(&self.db)
+        .await?;
+
+        let total = tracks.len();
+        let mut summary = TrackMetadataSummary::default();
+        ctx.log("info", format!("Looking up {} tracks on MusicBrainz", total)).await;
+
+        for (i, track) in tracks.iter().enumerate() {
+            if ctx.is_cancelled() {
+                ctx.log("info", "Cancelled").await;
+                break;
+            }
+            ctx.progress(i as f64 / total as f64, format!("{} - {}", track.artist, track.title))
+                .await;
+
+            let recording = self.find_recording(track).await;
+            tokio::time::sleep(REQUEST_DELAY).await;
+            let recording = match recording {
+                Ok(Some(recording)) => recording,
+                Ok(None) => {
+                    summary.not_found += 1;
+                    self.store(&track.id, MUSICBRAINZ, None, &[], None, serde_json::json!({}))
+                        .await?;
+                    continue;
+                }
+                Err(e) => {
+                    summary.failed += 1;
+                    ctx.log("warn", format!("Failed to look up {}: {}", track.id, e)).await;
+                    continue;
+                }
+            };
+
+            summary.matched += 1;
+            let tags = recording.top_tags();
+            let first_year = recording.first_release_year();
+            self.store(&track.id, MUSICBRAINZ, Some(&recording.id), &tags, first_year, serde_json::json!({}))
+                .await?;
+
+            // Only kept when it corrects the tagged year
+            let original_year = first_year.filter(|year| Some(*year) != track.year);
+            if original_year.is_some() {
+                summary.year_corrected += 1;
+            }
+            sqlx::query(
+                "UPDATE library_index SET musicbrainz_id = $2, musicbrainz_tags = $3, original_year = $4 WHERE id = $1",
+            )
+            .bind(&track.id)
+            .bind(&recording.id)
+            .bind(&tags)
+            .bind(original_year)
+            .execute(&self.db)
+            .await?;
+
+            let Some(acousticbrainz_url) = &self.config.acousticbrainz_url else {
+                continue;
+            };
+            match self.acoustic_features(acousticbrainz_url, &recording.id).await {
+                Ok(Some(features)) => {
+                    summary.with_features += 1;
+                    self.store_features(&track.id, &recording.id, &features).await?;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    ctx.log("warn", format!("AcousticBrainz lookup of {} failed: {}", recording.id, e))
+                        .await;
+                }
+            }
+        }
+
+        ctx.progress(1.0, format!("Matched {} of {} tracks", summary.matched, total))
+            .await;
+        Ok(summary)
+    }
+
+    /// The recording that best matches a track, if any is close enough
+    async fn find_recording(&self, track: &IndexedTrack) -> Result<Option<Recording>> {
+        let query = format!(
+            "recording:\"{}\" AND artist:\"{}\"",
+            escape_phrase(&track.title),
+            escape_phrase(&track.artist)
+        );
+        let response = self
+            .http
+            .get(format!("{}/recording", self.config.api_url.trim_end_matches('/')))
+            .query(&[("query", query.as_str()), ("fmt", "json"), ("limit", "10")])
+            .send()
+            .await
+            .map_err(|e| AppError::ExternalApi(format!("MusicBrainz request failed: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(AppError::ExternalApi(format!("MusicBrainz returned {}", response.status())));
+        }
+        let search: RecordingSearch = response
+            .json()
+            .await
+            .map_err(|e| AppError::ExternalApi(format!("Unexpected MusicBrainz response: {}", e)))?;
+
+        Ok(best_recording(search.recordings, &track.album, track.duration))
+    }
+
+    /// AcousticBrainz features of a recording; None if it has no data for it
+    async fn acoustic_features(&self, api_url: &str, recording_id: &str) -> Result<Option<AcousticFeatures>> {
+        let low: Option<LowLevel> = self.acousticbrainz_document(api_url, recording_id, "low-level").await?;
+        if low.is_none() {
+            return Ok(None);
+        }
+        let high: Option<HighLevel> = self.acousticbrainz_document(api_url, recording_id, "high-level").await?;
+        Ok(AcousticFeatures::from_documents(low, high))
+    }
+
+    async fn acousticbrainz_document<T: serde::de::DeserializeOwned>(
+        &self,
+        api_url: &str,
+        recording_id: &str,
+        level: &str,
+    ) -> Result<Option<T>> {
+        let response = self
+            .http
+            .get(format!("{}/{}/{}", api_url.trim_end_matches('/'), recording_id, level))
+            .send()
+            .await
+            .map_err(|e| AppError::ExternalApi(format!("AcousticBrainz request failed: {}", e)))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(AppError::ExternalApi(format!("AcousticBrainz returned {}", response.status())));
+        }
+        let document = response
+            .json()
+            .await
+            .map_err(|e| AppError::ExternalApi(format!("Unexpected AcousticBrainz response: {}", e)))?;
+        Ok(Some(document))
+    }
+
+    async fn store(
+        &self,
+        track_id: &str,
+        source: &str,
+        recording_id: Option<&str>,
+        tags: &[String],
+        year: Option<i32>,
+        features: serde_json::Value,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO track_external_metadata (track_id, source, recording_id, tags, year, features, fetched_at)
+            VALUES ($1, $2, $3, $4, $5, $6, NOW())
+            ON CONFLICT (track_id, source) DO UPDATE
+            SET recording_id = EXCLUDED.recording_id,
+                tags = EXCLUDED.tags,
+                year = EXCLUDED.year,
+                features = EXCLUDED.features,
+                fetched_at = NOW()
+            "#,
+        )
+        .bind(track_id)
+        .bind(source)
+        .bind(recording_id)
+        .bind(tags)
+        .bind(year)
+        .bind(features)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Keep the features and write them over the track's guessed values
+    async fn store_features(&self, track_id: &str, recording_id: &str, features: &AcousticFeatures) -> Result<()> {
+        self.store(track_id, ACOUSTICBRAINZ, Some(recording_id), &[], None, serde_json::to_value(features)?)
+            .await?;
+        sqlx::query(
+            r#"
+            UPDATE library_index SET
+                tempo = COALESCE($2, tempo),
+                musical_key = COALESCE($3, musical_key),
+                danceability = COALESCE($4, danceability),
+                valence = COALESCE($5, valence),
+                acousticness = COALESCE($6, acousticness),
+                instrumentalness = COALESCE($7, instrumentalness),
+                features_source = $8
+            WHERE id = $1
+            "#,
+        )
+        .bind(track_id)
+        .bind(features.bpm)
+        .bind(&features.key)
+        .bind(features.danceability)
+        .bind(features.valence)
+        .bind(features.acousticness)
+        .bind(features.instrumentalness)
+        .bind(ACOUSTICBRAINZ)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Escape a value for a quoted phrase in a MusicBrainz (Lucene) query
+fn escape_phrase(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The closest search result: recordings scoring below `MIN_SCORE` or with a length
+/// far from the track's are dropped; of the rest, ones released on the track's album
+/// come first, then the best scoring
+fn best_recording(recordings: Vec<Recording>, album: &str, duration_secs: i32) -> Option<Recording> {
+    let duration_ms = duration_secs as i64 * 1000;
+    recordings
+        .into_iter()
+        .filter(|r| r.score >= MIN_SCORE)
+        .filter(|r| duration_ms <= 0 || r.length.is_none_or(|len| (len - duration_ms).abs() <= MAX_LENGTH_DIFF_MS))
+        .min_by_key(|r| {
+            let on_album = r.releases.iter().any(|release| release.title.eq_ignore_ascii_case(album));
+            (!on_album, -r.score)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording(id: &str, score: i64, length: Option<i64>, albums: &[&str]) -> Recording {
+        Recording {
+            id: id.to_string(),
+            score,
+            length,
+            first_release_date: None,
+            releases: albums.iter().map(|title| Release { title: title.to_string() }).collect(),
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_best_recording_prefers_the_album_then_the_score() {
+        let recordings = vec![
+            recording("live", 100, Some(400_000), &["Live at Reading"]),
+            recording("single", 100, Some(301_000), &["Smells Like Teen Spirit"]),
+            recording("album", 95, Some(302_000), &["Nevermind"]),
+            recording("weak", 60, Some(301_000), &["Nevermind"]),
+        ];
+        assert_eq!(best_recording(recordings.clone(), "nevermind", 301).unwrap().id, "album");
+        assert_eq!(best_recording(recordings, "Greatest Hits", 301).unwrap().id, "single");
+        assert!(best_recording(vec![recording("weak", 60, None, &[])], "", 301).is_none());
+    }
+
+    #[test]
+    fn test_recording_tags_and_year() {
+        let recording: Recording = serde_json::from_value(serde_json::json!({
+            "id": "5fb524f1-8cc8-4c04-a921-e34c0a911ea7",
+            "score": 100,
+            "first-release-date": "1991-09-10",
+            "tags": [
+                { "count": 1, "name": "Alternative Rock" },
+                { "count": 4, "name": "grunge" },
+                { "count": -1, "name": "pop" }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(recording.top_tags(), vec!["grunge", "alternative rock"]);
+        assert_eq!(recording.first_release_year(), Some(1991));
+    }
+
+    #[test]
+    fn test_acoustic_features_from_documents() {
+        let low: LowLevel = serde_json::from_value(serde_json::json!({
+            "rhythm": { "bpm": 116.7 },
+            "tonal": { "key_key": "F", "key_scale": "minor" }
+        }))
+        .unwrap();
+        let high: HighLevel = serde_json::from_value(serde_json::json!({
+            "highlevel": {
+                "danceability": { "all": { "danceable": 0.8, "not_danceable": 0.2 } },
+                "mood_happy": { "all": { "happy": 0.3, "not_happy": 0.7 } }
+            }
+        }))
+        .unwrap();
+
+        let features = AcousticFeatures::from_documents(Some(low), Some(high)).unwrap();
+        assert_eq!(features.bpm, Some(116.7));
+        assert_eq!(features.key.as_deref(), Some("F minor"));
+        assert_eq!(features.danceability, Some(0.8));
+        assert_eq!(features.valence, Some(0.3));
+        assert_eq!(features.acousticness, None);
+
+        assert_eq!(AcousticFeatures::from_documents(None, None), None);
+    }
+
+    #[test]
+    fn test_phrases_are_escaped() {
+        assert_eq!(escape_phrase(r#"Say "Hi" \o/"#), r#"Say \"Hi\" \\o/"#);
+    }
+}
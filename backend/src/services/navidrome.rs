@@ -1,24 +1,114 @@
 #![allow(dead_code)]
 
 use crate::error::{AppError, Result};
+use crate::models::track::EXPLICIT_METADATA_KEY;
 use crate::models::Track;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
 use rand::Rng;
-use reqwest::Client;
-use serde::Deserialize;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::num::NonZeroU32;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{RwLock, Semaphore};
+
+/// Subsonic API version we speak
+const API_VERSION: &str = "1.16.1";
+
+/// Client name reported to Navidrome
+const CLIENT_NAME: &str = "navidrome-radio";
+
+/// Tries per request, including the first
+const MAX_ATTEMPTS: u32 = 4;
+
+/// Delay before the first retry (doubles with each further retry)
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+
+/// Last known state of the Navidrome server, refreshed by the periodic ping and by the
+/// outcome of every request
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NavidromeStatus {
+    pub reachable: bool,
+    /// Subsonic API version the server implements
+    pub api_version: Option<String>,
+    /// Server software and version (reported by OpenSubsonic servers such as Navidrome)
+    pub server_type: Option<String>,
+    pub server_version: Option<String>,
+    pub last_checked: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// Failed requests or pings since the server was last reachable
+    pub consecutive_failures: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct PingResponse {
+    status: String,
+    version: Option<String>,
+    #[serde(rename = "type")]
+    server_type: Option<String>,
+    #[serde(rename = "serverVersion")]
+    server_version: Option<String>,
+    error: Option<SubsonicError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubsonicError {
+    message: String,
+}
+
+/// How the client authenticates to Navidrome
+#[derive(Clone)]
+enum Credentials {
+    /// Subsonic token auth: every request carries a fresh salt and md5(password + salt).
+    /// The password is also needed to log in to the native API; it is shared between
+    /// clones and never sent or logged in plaintext.
+    Password(Arc<str>),
+    /// OpenSubsonic API key. No password is held, but the native API is unavailable.
+    ApiKey(Arc<str>),
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::Password(_) => write!(f, "Password(<redacted>)"),
+            Credentials::ApiKey(_) => write!(f, "ApiKey(<redacted>)"),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct NavidromeClient {
     base_url: String,
     username: String,
-    password: String,  // Store original password for native API
-    token: String,
-    salt: String,
+    credentials: Credentials,
     client: Client,
     /// Cached JWT token for native API (shared across clones)
     jwt_cache: Arc<RwLock<Option<String>>>,
+    /// Cached server status (shared across clones)
+    status: Arc<RwLock<NavidromeStatus>>,
+    /// Request rate and concurrency limits (shared across clones)
+    limits: Arc<RequestLimits>,
+}
+
+/// Client-side throttling of requests to Navidrome, so syncs, embedding downloads and
+/// live streams together don't overload a small server
+#[derive(Default)]
+struct RequestLimits {
+    rate: Option<DefaultDirectRateLimiter>,
+    /// Requests in flight (held until the response headers arrive)
+    concurrency: Option<Semaphore>,
+}
+
+impl fmt::Debug for RequestLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestLimits")
+            .field("rate_limited", &self.rate.is_some())
+            .field("concurrency_limited", &self.concurrency.is_some())
+            .finish()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -37,6 +127,37 @@ struct SearchResult3 {
 struct SearchResult3Data {
     #[serde(default)]
     song: Vec<NavidromeSong>,
+    #[serde(default)]
+    artist: Vec<NavidromeArtist>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NavidromeArtist {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Starred2Response {
+    #[serde(rename = "starred2")]
+    starred2: SongList,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopSongsResponse {
+    #[serde(rename = "topSongs")]
+    top_songs: SongList,
+}
+
+#[derive(Debug, Deserialize)]
+struct SimilarSongs2Response {
+    #[serde(rename = "similarSongs2", default)]
+    similar_songs2: SongList,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SongList {
+    #[serde(default)]
+    song: Vec<NavidromeSong>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -74,6 +195,15 @@ struct NavidromeSong {
     year: Option<i32>,
     duration: i32,
     path: String,
+    #[serde(rename = "albumId", default)]
+    album_id: Option<String>,
+    #[serde(default)]
+    track: Option<i32>,
+    #[serde(rename = "discNumber", default)]
+    disc_number: Option<i32>,
+    /// OpenSubsonic: "explicit", "clean" or empty
+    #[serde(rename = "explicitStatus", default)]
+    explicit_status: String,
 }
 
 /// Navidrome Native API response for /api/song
@@ -91,6 +221,9 @@ struct NativeApiSong {
     year: Option<i32>,
     duration: f64,  // Native API returns duration as float
     path: String,
+    /// "e" (explicit), "c" (clean) or empty
+    #[serde(default)]
+    explicit_status: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -99,6 +232,139 @@ struct NativeApiGenre {
     name: String,
 }
 
+/// Library scan state as returned by getScanStatus
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct ScanStatus {
+    pub scanning: bool,
+    /// Files scanned so far (or in total, once done)
+    #[serde(default)]
+    pub count: Option<i64>,
+    /// When the last scan finished (Navidrome extension)
+    #[serde(default)]
+    pub last_scan: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScanStatusResponse {
+    #[serde(rename = "scanStatus")]
+    scan_status: ScanStatus,
+}
+
+/// Album as listed by getAlbumList2
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlbumSummary {
+    pub id: String,
+    /// When the album was added to the library
+    #[serde(default)]
+    pub created: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlbumList2Response {
+    #[serde(rename = "albumList2", default)]
+    album_list2: AlbumList2Data,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct AlbumList2Data {
+    #[serde(default)]
+    album: Vec<AlbumSummary>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IndexesResponse {
+    #[serde(default)]
+    indexes: IndexesData,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct IndexesData {
+    /// Milliseconds since the epoch of the last library change (scan)
+    #[serde(rename = "lastModified", default)]
+    last_modified: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlbumResponse {
+    album: SongList,
+}
+
+/// Playlist as returned by getPlaylists
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct NavidromePlaylist {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub comment: Option<String>,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub song_count: i32,
+    /// Total length in seconds
+    #[serde(default)]
+    pub duration: i32,
+    #[serde(default)]
+    pub changed: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlaylistsResponse {
+    #[serde(default)]
+    playlists: PlaylistsData,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PlaylistsData {
+    #[serde(default)]
+    playlist: Vec<NavidromePlaylist>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlaylistResponse {
+    playlist: PlaylistEntries,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlaylistEntries {
+    #[serde(default)]
+    entry: Vec<PlaylistEntry>,
+}
+
+/// A track of a playlist
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlaylistEntry {
+    pub id: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub artist: String,
+}
+
+/// Internet radio station as returned by getInternetRadioStations
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InternetRadioStation {
+    pub id: String,
+    pub name: String,
+    pub stream_url: String,
+    #[serde(default)]
+    pub home_page_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InternetRadioStationsResponse {
+    #[serde(rename = "internetRadioStations", default)]
+    internet_radio_stations: InternetRadioStationsData,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct InternetRadioStationsData {
+    #[serde(rename = "internetRadioStation", default)]
+    internet_radio_station: Vec<InternetRadioStation>,
+}
+
 /// Login response from Navidrome's /auth/login endpoint
 #[derive(Debug, Deserialize)]
 struct LoginResponse {
@@ -106,19 +372,178 @@ struct LoginResponse {
 }
 
 impl NavidromeClient {
+    /// Authenticate with username and password (Subsonic salted-token auth)
     pub fn new(base_url: String, username: String, password: String) -> Self {
-        let salt = Self::generate_salt();
-        let token = format!("{:x}", md5::compute(format!("{}{}", password, salt)));
+        Self::with_credentials(base_url, username, Credentials::Password(password.into()))
+    }
+
+    /// Authenticate with an OpenSubsonic API key instead of a password
+    pub fn with_api_key(base_url: String, username: String, api_key: String) -> Self {
+        Self::with_credentials(base_url, username, Credentials::ApiKey(api_key.into()))
+    }
 
+    fn with_credentials(base_url: String, username: String, credentials: Credentials) -> Self {
         Self {
             base_url,
             username,
-            password,  // Store for native API auth
-            token,
-            salt,
+            credentials,
             client: Client::new(),
             jwt_cache: Arc::new(RwLock::new(None)),
+            status: Arc::new(RwLock::new(NavidromeStatus::default())),
+            limits: Arc::new(RequestLimits::default()),
+        }
+    }
+
+    /// Limit requests to `per_second` and `concurrent` in flight (0 = unlimited).
+    /// Applies to this client and its clones; health pings are exempt.
+    pub fn with_limits(mut self, per_second: u32, concurrent: usize) -> Self {
+        self.limits = Arc::new(RequestLimits {
+            rate: NonZeroU32::new(per_second).map(|rate| RateLimiter::direct(Quota::per_second(rate))),
+            concurrency: (concurrent > 0).then(|| Semaphore::new(concurrent)),
+        });
+        self
+    }
+
+    /// Share another client's limits, e.g. for a user's own account on the same server
+    pub fn with_limits_of(mut self, other: &NavidromeClient) -> Self {
+        self.limits = other.limits.clone();
+        self
+    }
+
+    /// Send a request to Navidrome, retrying with backoff while the server is briefly
+    /// unreachable (e.g. restarting). Only failures where Navidrome cannot have processed
+    /// the request are retried: refused connections, 502 and 503.
+    pub async fn send(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        let mut delay = RETRY_BASE_DELAY;
+
+        for attempt in 1..MAX_ATTEMPTS {
+            // Requests with streaming bodies can't be replayed
+            let Some(retry) = request.try_clone() else { break };
+
+            match self.throttled(retry).await {
+                Ok(response) if !is_transient(response.status()) => {
+                    self.record_success().await;
+                    return Ok(response);
+                }
+                Ok(response) => {
+                    self.record_failure(format!("HTTP {}", response.status())).await;
+                }
+                Err(e) if e.is_connect() => self.record_failure(e.to_string()).await,
+                Err(e) => return Err(e),
+            }
+
+            tracing::debug!("Navidrome unavailable (attempt {}), retrying in {:?}", attempt, delay);
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+
+        let result = self.throttled(request).await;
+        match &result {
+            Ok(response) if !is_transient(response.status()) => self.record_success().await,
+            Ok(response) => self.record_failure(format!("HTTP {}", response.status())).await,
+            Err(e) => self.record_failure(e.to_string()).await,
+        }
+        result
+    }
+
+    /// Send once, waiting for the rate limiter and a concurrency slot first
+    async fn throttled(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        if let Some(rate) = &self.limits.rate {
+            rate.until_ready().await;
+        }
+        let _permit = match &self.limits.concurrency {
+            Some(semaphore) => Some(semaphore.acquire().await.expect("semaphore is never closed")),
+            None => None,
+        };
+        request.send().await
+    }
+
+    /// Last known server status (see [`spawn_health_check`](Self::spawn_health_check))
+    pub async fn status(&self) -> NavidromeStatus {
+        self.status.read().await.clone()
+    }
+
+    /// Check that Navidrome is up and the credentials work, updating the cached status
+    pub async fn ping(&self) -> NavidromeStatus {
+        let url = format!("{}/rest/ping", self.base_url);
+        let params = self.build_params(vec![]);
+
+        let outcome = match self.client.get(&url).query(&params).send().await {
+            Ok(response) if response.status().is_success() => response
+                .json::<SubsonicResponse<PingResponse>>()
+                .await
+                .map_err(|e| format!("Invalid ping response: {}", e))
+                .and_then(|data| {
+                    let ping = data.subsonic_response;
+                    if ping.status == "ok" {
+                        Ok(ping)
+                    } else {
+                        Err(ping.error.map_or_else(|| "Ping failed".to_string(), |e| e.message))
+                    }
+                }),
+            Ok(response) => Err(format!("HTTP {}", response.status())),
+            Err(e) => Err(e.to_string()),
+        };
+
+        let mut status = self.status.write().await;
+        status.last_checked = Some(Utc::now());
+        match outcome {
+            Ok(ping) => {
+                if !status.reachable && status.consecutive_failures > 0 {
+                    tracing::info!("Navidrome is reachable again");
+                }
+                status.reachable = true;
+                status.consecutive_failures = 0;
+                status.last_error = None;
+                status.api_version = ping.version;
+                status.server_type = ping.server_type;
+                status.server_version = ping.server_version;
+            }
+            Err(error) => {
+                if status.reachable || status.consecutive_failures == 0 {
+                    tracing::warn!("Navidrome health check failed: {}", error);
+                }
+                status.reachable = false;
+                status.consecutive_failures += 1;
+                status.last_error = Some(error);
+            }
+        }
+        status.clone()
+    }
+
+    /// Ping Navidrome every `interval` to keep the cached status fresh
+    pub fn spawn_health_check(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                self.ping().await;
+            }
+        });
+    }
+
+    async fn record_success(&self) {
+        // Most requests succeed while the server is known to be up; avoid the write lock
+        if self.status.read().await.reachable {
+            return;
+        }
+        let mut status = self.status.write().await;
+        if status.consecutive_failures > 0 {
+            tracing::info!("Navidrome is reachable again");
+        }
+        status.reachable = true;
+        status.consecutive_failures = 0;
+        status.last_error = None;
+    }
+
+    async fn record_failure(&self, error: String) {
+        let mut status = self.status.write().await;
+        if status.reachable {
+            tracing::warn!("Navidrome request failed, server may be down: {}", error);
         }
+        status.reachable = false;
+        status.consecutive_failures += 1;
+        status.last_error = Some(error);
     }
 
     /// Get the base URL for constructing API endpoints
@@ -131,17 +556,28 @@ impl NavidromeClient {
         &self.client
     }
 
-    /// Build Subsonic API parameters (public for use by other services)
+    /// Build Subsonic API parameters (public for use by other services).
+    /// Each call signs the request with a new salt, as the Subsonic spec recommends.
     pub fn build_params(&self, additional: Vec<(&str, &str)>) -> Vec<(String, String)> {
-        let mut params = vec![
-            ("u".to_string(), self.username.clone()),
-            ("t".to_string(), self.token.clone()),
-            ("s".to_string(), self.salt.clone()),
-            ("v".to_string(), "1.16.1".to_string()),
-            ("c".to_string(), "navidrome-radio".to_string()),
-            ("f".to_string(), "json".to_string()),
-        ];
+        let mut params = match &self.credentials {
+            Credentials::Password(password) => {
+                let salt = Self::generate_salt();
+                let token = format!("{:x}", md5::compute(format!("{}{}", password, salt)));
+                vec![
+                    ("u".to_string(), self.username.clone()),
+                    ("t".to_string(), token),
+                    ("s".to_string(), salt),
+                ]
+            }
+            // OpenSubsonic forbids sending `u` alongside an API key
+            Credentials::ApiKey(key) => vec![("apiKey".to_string(), key.to_string())],
+        };
 
+        params.extend([
+            ("v".to_string(), API_VERSION.to_string()),
+            ("c".to_string(), CLIENT_NAME.to_string()),
+            ("f".to_string(), "json".to_string()),
+        ]);
         for (key, value) in additional {
             params.push((key.to_string(), value.to_string()));
         }
@@ -149,10 +585,19 @@ impl NavidromeClient {
         params
     }
 
+    /// Full URL for a Subsonic endpoint with signed parameters, for handing to players
+    fn signed_url(&self, endpoint: &str, additional: Vec<(&str, &str)>) -> String {
+        let url = format!("{}/rest/{}", self.base_url, endpoint);
+        let params = self.build_params(additional);
+        reqwest::Url::parse_with_params(&url, &params)
+            .map(String::from)
+            .unwrap_or(url)
+    }
+
     fn generate_salt() -> String {
         let mut rng = rand::thread_rng();
         (0..8)
-            .map(|_| format!("{:x}", rng.gen::<u8>()))
+            .map(|_| format!("{:02x}", rng.gen::<u8>()))
             .collect()
     }
 
@@ -166,17 +611,24 @@ impl NavidromeClient {
             }
         }
 
+        let Credentials::Password(password) = &self.credentials else {
+            return Err(AppError::Navidrome(
+                "Navidrome's native API needs a password; it does not accept API keys".to_string(),
+            ));
+        };
+
         // No cached token, perform login
         let url = format!("{}/auth/login", self.base_url);
 
         let response = self
-            .client
-            .post(&url)
-            .json(&serde_json::json!({
-                "username": self.username,
-                "password": self.password
-            }))
-            .send()
+            .send(
+                self.client
+                    .post(&url)
+                    .json(&serde_json::json!({
+                        "username": self.username,
+                        "password": password.as_ref()
+                    })),
+            )
             .await
             .map_err(|e| AppError::Navidrome(format!("Login request failed: {}", e)))?;
 
@@ -220,10 +672,7 @@ impl NavidromeClient {
         tracing::debug!("Searching Navidrome: {} with query: {}", url, query);
 
         let response = self
-            .client
-            .get(&url)
-            .query(&params)
-            .send()
+            .send(self.client.get(&url).query(&params))
             .await
             .map_err(|e| AppError::Navidrome(format!("Request failed: {}", e)))?;
 
@@ -288,10 +737,7 @@ impl NavidromeClient {
         tracing::debug!("Getting {} random songs from Navidrome", count);
 
         let response = self
-            .client
-            .get(&url)
-            .query(&params)
-            .send()
+            .send(self.client.get(&url).query(&params))
             .await
             .map_err(|e| AppError::Navidrome(format!("Request failed: {}", e)))?;
 
@@ -317,6 +763,45 @@ impl NavidromeClient {
         Ok(self.convert_navidrome_songs(data.subsonic_response.random_songs.song))
     }
 
+    /// Names of artists matching a search query
+    pub async fn search_artists(&self, query: &str, count: usize) -> Result<Vec<String>> {
+        let count = count.to_string();
+        let data: SearchResult3 = self
+            .subsonic_call(
+                "search3",
+                vec![("query", query), ("artistCount", &count), ("albumCount", "0"), ("songCount", "0")],
+            )
+            .await?;
+
+        Ok(data.search_result3.artist.into_iter().map(|a| a.name).collect())
+    }
+
+    /// Songs the Navidrome user has starred
+    pub async fn get_starred_songs(&self) -> Result<Vec<Track>> {
+        let data: Starred2Response = self.subsonic_call("getStarred2", vec![]).await?;
+        Ok(self.convert_navidrome_songs(data.starred2.song))
+    }
+
+    /// An artist's most popular songs. Navidrome fills this from Last.fm play counts,
+    /// so it is empty when no Last.fm agent is configured.
+    pub async fn get_top_songs(&self, artist: &str, count: usize) -> Result<Vec<Track>> {
+        let count = count.to_string();
+        let data: TopSongsResponse = self
+            .subsonic_call("getTopSongs", vec![("artist", artist), ("count", &count)])
+            .await?;
+        Ok(self.convert_navidrome_songs(data.top_songs.song))
+    }
+
+    /// Tracks similar to a track, from Navidrome's external metadata agents (Last.fm).
+    /// Empty when no agent is configured.
+    pub async fn get_similar_songs(&self, track_id: &str, count: usize) -> Result<Vec<Track>> {
+        let count = count.to_string();
+        let data: SimilarSongs2Response = self
+            .subsonic_call("getSimilarSongs2", vec![("id", track_id), ("count", &count)])
+            .await?;
+        Ok(self.convert_navidrome_songs(data.similar_songs2.song))
+    }
+
     /// Convert NavidromeSong objects to Track objects
     fn convert_navidrome_songs(&self, songs: Vec<NavidromeSong>) -> Vec<Track> {
         songs
@@ -348,17 +833,11 @@ impl NavidromeClient {
     }
 
     pub async fn get_stream_url(&self, track_id: &str) -> String {
-        format!(
-            "{}/rest/stream?id={}&u={}&t={}&s={}&v=1.16.1&c=navidrome-radio",
-            self.base_url, track_id, self.username, self.token, self.salt
-        )
+        self.signed_url("stream", vec![("id", track_id)])
     }
 
     pub async fn get_cover_url(&self, track_id: &str) -> String {
-        format!(
-            "{}/rest/getCoverArt?id={}&u={}&t={}&s={}&v=1.16.1&c=navidrome-radio&size=500",
-            self.base_url, track_id, self.username, self.token, self.salt
-        )
+        self.signed_url("getCoverArt", vec![("id", track_id), ("size", "500")])
     }
 
     pub async fn get_genres(&self) -> Result<Vec<String>> {
@@ -366,10 +845,7 @@ impl NavidromeClient {
         let params = self.build_params(vec![]);
 
         let _response = self
-            .client
-            .get(&url)
-            .query(&params)
-            .send()
+            .send(self.client.get(&url).query(&params))
             .await
             .map_err(|e| AppError::Navidrome(format!("Request failed: {}", e)))?;
 
@@ -389,6 +865,11 @@ impl NavidromeClient {
     /// Get all songs from Navidrome using the native API with pagination
     /// This is more reliable than getRandomSongs for large libraries
     pub async fn get_all_songs_paginated(&self, page_size: usize, offset: usize) -> Result<(Vec<Track>, usize)> {
+        // The native API only accepts password logins; page through search3 instead
+        if matches!(self.credentials, Credentials::ApiKey(_)) {
+            return self.get_all_songs_subsonic(page_size, offset).await;
+        }
+
         // Get JWT token for native API authentication
         let jwt_token = self.get_jwt_token().await?;
 
@@ -398,16 +879,17 @@ impl NavidromeClient {
         tracing::debug!("Fetching songs from Navidrome native API: offset={}, size={}", offset, page_size);
 
         let response = self
-            .client
-            .get(&url)
-            .header("x-nd-authorization", format!("Bearer {}", jwt_token))
-            .query(&[
-                ("_start", offset.to_string()),
-                ("_end", (offset + page_size).to_string()),
-                ("_order", "ASC".to_string()),
-                ("_sort", "id".to_string()),
-            ])
-            .send()
+            .send(
+                self.client
+                    .get(&url)
+                    .header("x-nd-authorization", format!("Bearer {}", jwt_token))
+                    .query(&[
+                        ("_start", offset.to_string()),
+                        ("_end", (offset + page_size).to_string()),
+                        ("_order", "ASC".to_string()),
+                        ("_sort", "id".to_string()),
+                    ]),
+            )
             .await
             .map_err(|e| AppError::Navidrome(format!("Request failed: {}", e)))?;
 
@@ -464,7 +946,7 @@ impl NavidromeClient {
                     year: song.year,
                     duration: song.duration as i32,
                     path: song.path,
-                    metadata: None,
+                    metadata: explicit_metadata(&song.explicit_status),
                     last_synced: Utc::now(),
                 }
             })
@@ -473,6 +955,55 @@ impl NavidromeClient {
         Ok((tracks, total_count))
     }
 
+    /// Page through the whole library with an empty search3 query. Subsonic reports no
+    /// total, so the count is 0 and callers stop at the first empty page.
+    async fn get_all_songs_subsonic(&self, page_size: usize, offset: usize) -> Result<(Vec<Track>, usize)> {
+        let page_size = page_size.to_string();
+        let offset = offset.to_string();
+        let data: SearchResult3 = self
+            .subsonic_call(
+                "search3",
+                vec![
+                    ("query", ""),
+                    ("artistCount", "0"),
+                    ("albumCount", "0"),
+                    ("songCount", &page_size),
+                    ("songOffset", &offset),
+                ],
+            )
+            .await?;
+
+        let tracks = data
+            .search_result3
+            .song
+            .into_iter()
+            .map(|song| {
+                let genres = if !song.genres.is_empty() {
+                    song.genres.into_iter().map(|g| g.name).collect()
+                } else if !song.genre.is_empty() {
+                    vec![song.genre]
+                } else {
+                    vec![]
+                };
+
+                Track {
+                    id: song.id,
+                    title: song.title,
+                    artist: song.artist,
+                    album: song.album,
+                    genre: genres,
+                    year: song.year,
+                    duration: song.duration,
+                    path: song.path,
+                    metadata: explicit_metadata(&song.explicit_status),
+                    last_synced: Utc::now(),
+                }
+            })
+            .collect();
+
+        Ok((tracks, 0))
+    }
+
     /// Create a playlist in Navidrome with the given name and track IDs
     pub async fn create_playlist(&self, name: &str, track_ids: &[String]) -> Result<String> {
         let url = format!("{}/rest/createPlaylist", self.base_url);
@@ -488,10 +1019,7 @@ impl NavidromeClient {
         tracing::info!("Creating Navidrome playlist '{}' with {} tracks", name, track_ids.len());
 
         let response = self
-            .client
-            .get(&url)
-            .query(&params)
-            .send()
+            .send(self.client.get(&url).query(&params))
             .await
             .map_err(|e| AppError::Navidrome(format!("Request failed: {}", e)))?;
 
@@ -548,6 +1076,107 @@ impl NavidromeClient {
         Ok(playlist_id)
     }
 
+    pub async fn get_scan_status(&self) -> Result<ScanStatus> {
+        let data: ScanStatusResponse = self.subsonic_call("getScanStatus", vec![]).await?;
+        Ok(data.scan_status)
+    }
+
+    /// Whether Navidrome's library changed after `since`, from getIndexes' `lastModified`.
+    /// Passing `ifModifiedSince` keeps the answer small when nothing changed. Servers that
+    /// don't report a modification time count as changed.
+    pub async fn library_modified_since(&self, since: DateTime<Utc>) -> Result<bool> {
+        let since_ms = since.timestamp_millis().to_string();
+        let data: IndexesResponse = self
+            .subsonic_call("getIndexes", vec![("ifModifiedSince", &since_ms)])
+            .await?;
+        Ok(data
+            .indexes
+            .last_modified
+            .is_none_or(|modified| modified > since.timestamp_millis()))
+    }
+
+    /// Albums by the time they were added to the library, newest first
+    pub async fn get_newest_albums(&self, size: usize, offset: usize) -> Result<Vec<AlbumSummary>> {
+        let size = size.to_string();
+        let offset = offset.to_string();
+        let data: AlbumList2Response = self
+            .subsonic_call(
+                "getAlbumList2",
+                vec![("type", "newest"), ("size", &size), ("offset", &offset)],
+            )
+            .await?;
+        Ok(data.album_list2.album)
+    }
+
+    pub async fn get_album_tracks(&self, album_id: &str) -> Result<Vec<Track>> {
+        let data: AlbumResponse = self.subsonic_call("getAlbum", vec![("id", album_id)]).await?;
+        Ok(self.convert_navidrome_songs(data.album.song))
+    }
+
+    /// The track following `track_id` on its album (by disc and track number), or None
+    /// if it is the album's last
+    pub async fn next_album_track(&self, track_id: &str) -> Result<Option<Track>> {
+        let data: GetSongResponse = self.subsonic_call("getSong", vec![("id", track_id)]).await?;
+        let Some(album_id) = data.song.album_id else {
+            return Ok(None);
+        };
+        let album: AlbumResponse = self.subsonic_call("getAlbum", vec![("id", &album_id)]).await?;
+
+        let mut songs = album.album.song;
+        songs.sort_by_key(|song| (song.disc_number.unwrap_or(1), song.track.unwrap_or(0)));
+        let Some(position) = songs.iter().position(|song| song.id == track_id) else {
+            return Ok(None);
+        };
+        Ok(self.convert_navidrome_songs(songs.split_off(position + 1)).into_iter().next())
+    }
+
+    /// Playlists visible to the Navidrome user (their own and public ones)
+    pub async fn get_playlists(&self) -> Result<Vec<NavidromePlaylist>> {
+        let data: PlaylistsResponse = self.subsonic_call("getPlaylists", vec![]).await?;
+        Ok(data.playlists.playlist)
+    }
+
+    /// Track IDs of a playlist, in playlist order
+    pub async fn get_playlist_track_ids(&self, playlist_id: &str) -> Result<Vec<String>> {
+        let entries = self.get_playlist_entries(playlist_id).await?;
+        Ok(entries.into_iter().map(|e| e.id).collect())
+    }
+
+    /// The tracks of a playlist with their titles and artists, in order
+    pub async fn get_playlist_entries(&self, playlist_id: &str) -> Result<Vec<PlaylistEntry>> {
+        let data: PlaylistResponse = self
+            .subsonic_call("getPlaylist", vec![("id", playlist_id)])
+            .await?;
+        Ok(data.playlist.entry)
+    }
+
+    /// Replace all entries of an existing playlist
+    pub async fn replace_playlist(&self, playlist_id: &str, track_ids: &[String]) -> Result<()> {
+        let mut params = vec![("playlistId", playlist_id)];
+        params.extend(track_ids.iter().map(|id| ("songId", id.as_str())));
+
+        tracing::info!("Replacing Navidrome playlist {} with {} tracks", playlist_id, track_ids.len());
+        let _: serde_json::Value = self.subsonic_call("createPlaylist", params).await?;
+        Ok(())
+    }
+
+    /// Add tracks to the end of an existing playlist
+    pub async fn append_to_playlist(&self, playlist_id: &str, track_ids: &[String]) -> Result<()> {
+        let mut params = vec![("playlistId", playlist_id)];
+        params.extend(track_ids.iter().map(|id| ("songIdToAdd", id.as_str())));
+
+        tracing::info!("Appending {} tracks to Navidrome playlist {}", track_ids.len(), playlist_id);
+        let _: serde_json::Value = self.subsonic_call("updatePlaylist", params).await?;
+        Ok(())
+    }
+
+    pub async fn rename_playlist(&self, playlist_id: &str, name: &str) -> Result<()> {
+        let _: serde_json::Value = self
+            .subsonic_call("updatePlaylist", vec![("playlistId", playlist_id), ("name", name)])
+            .await?;
+        Ok(())
+    }
+
     /// Get a single track by ID
     pub async fn get_track(&self, track_id: &str) -> Result<Track> {
         let url = format!("{}/rest/getSong", self.base_url);
@@ -556,10 +1185,7 @@ impl NavidromeClient {
         tracing::debug!("Getting track from Navidrome: {}", track_id);
 
         let response = self
-            .client
-            .get(&url)
-            .query(&params)
-            .send()
+            .send(self.client.get(&url).query(&params))
             .await
             .map_err(|e| AppError::Navidrome(format!("Request failed: {}", e)))?;
 
@@ -606,8 +1232,117 @@ impl NavidromeClient {
             year: song.year,
             duration: song.duration,
             path: song.path,
-            metadata: None,
+            metadata: explicit_metadata(&song.explicit_status),
             last_synced: Utc::now(),
         })
     }
+
+    /// Call a Subsonic endpoint and deserialize the `subsonic-response` body.
+    /// Fails if the HTTP status is not successful or the response status is not "ok".
+    async fn subsonic_call<T: DeserializeOwned>(&self, endpoint: &str, additional: Vec<(&str, &str)>) -> Result<T> {
+        let url = format!("{}/rest/{}", self.base_url, endpoint);
+        let params = self.build_params(additional);
+
+        let response = self
+            .send(self.client.get(&url).query(&params))
+            .await
+            .map_err(|e| AppError::Navidrome(format!("Request failed: {}", e)))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            tracing::error!("Navidrome {} error: {} - {}", endpoint, status, body);
+            return Err(AppError::Navidrome(format!(
+                "{} returned status: {} - {}",
+                endpoint, status, body
+            )));
+        }
+
+        let body: serde_json::Value = response.json().await.map_err(|e| {
+            AppError::Navidrome(format!("Failed to parse {} response: {}", endpoint, e))
+        })?;
+
+        let inner = body
+            .get("subsonic-response")
+            .cloned()
+            .ok_or_else(|| AppError::Navidrome(format!("{} response missing subsonic-response", endpoint)))?;
+
+        if inner.get("status").and_then(|s| s.as_str()) != Some("ok") {
+            let message = inner
+                .pointer("/error/message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("unknown error");
+            return Err(AppError::Navidrome(format!("{} failed: {}", endpoint, message)));
+        }
+
+        serde_json::from_value(inner).map_err(|e| {
+            AppError::Navidrome(format!("Failed to parse {} response: {}", endpoint, e))
+        })
+    }
+
+    /// List internet radio stations registered in Navidrome
+    pub async fn get_internet_radio_stations(&self) -> Result<Vec<InternetRadioStation>> {
+        let data: InternetRadioStationsResponse = self
+            .subsonic_call("getInternetRadioStations", vec![])
+            .await?;
+        Ok(data.internet_radio_stations.internet_radio_station)
+    }
+
+    /// Register an internet radio station (requires a Navidrome admin user).
+    /// The Subsonic API does not return the new ID; look it up by stream URL afterwards.
+    pub async fn create_internet_radio_station(
+        &self,
+        name: &str,
+        stream_url: &str,
+        homepage_url: Option<&str>,
+    ) -> Result<()> {
+        let mut params = vec![("name", name), ("streamUrl", stream_url)];
+        if let Some(homepage) = homepage_url {
+            params.push(("homepageUrl", homepage));
+        }
+
+        let _: serde_json::Value = self.subsonic_call("createInternetRadioStation", params).await?;
+        Ok(())
+    }
+
+    /// Update an existing internet radio station (requires a Navidrome admin user)
+    pub async fn update_internet_radio_station(
+        &self,
+        id: &str,
+        name: &str,
+        stream_url: &str,
+        homepage_url: Option<&str>,
+    ) -> Result<()> {
+        let mut params = vec![("id", id), ("name", name), ("streamUrl", stream_url)];
+        if let Some(homepage) = homepage_url {
+            params.push(("homepageUrl", homepage));
+        }
+
+        let _: serde_json::Value = self.subsonic_call("updateInternetRadioStation", params).await?;
+        Ok(())
+    }
+
+    /// Remove an internet radio station (requires a Navidrome admin user)
+    pub async fn delete_internet_radio_station(&self, id: &str) -> Result<()> {
+        let _: serde_json::Value = self
+            .subsonic_call("deleteInternetRadioStation", vec![("id", id)])
+            .await?;
+        Ok(())
+    }
+}
+
+/// `Track::metadata` for a song's explicit status, as OpenSubsonic ("explicit",
+/// "clean") or the native API ("e", "c") reports it
+fn explicit_metadata(status: &str) -> Option<HashMap<String, String>> {
+    let explicit = match status {
+        "explicit" | "e" => true,
+        "clean" | "c" => false,
+        _ => return None,
+    };
+    Some(HashMap::from([(EXPLICIT_METADATA_KEY.to_string(), explicit.to_string())]))
+}
+
+/// Responses that mean Navidrome (or the proxy in front of it) is temporarily unavailable
+fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::BAD_GATEWAY || status == StatusCode::SERVICE_UNAVAILABLE
 }
@@ -0,0 +1,136 @@
+//! Users' own Navidrome accounts
+//!
+//! By default everything talks to Navidrome as the shared service account from
+//! `NAVIDROME_USER`. A user can connect their own account instead; their password or
+//! API key is verified with a ping, stored encrypted (see [`SecretBox`]), and
+//! [`NavidromeAccounts::client_for`] hands out a client acting as them, so playlists
+//! and stars end up in their Navidrome library rather than the service account's.
+
+use crate::error::{AppError, Result};
+use crate::models::{ConnectNavidromeRequest, NavidromeAccount};
+use crate::services::secret_box::SecretBox;
+use crate::services::NavidromeClient;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub struct NavidromeAccounts {
+    db: PgPool,
+    secret_box: SecretBox,
+    /// Service account client, used for users without their own account
+    shared: Arc<NavidromeClient>,
+}
+
+impl NavidromeAccounts {
+    pub fn new(db: PgPool, secret_box: SecretBox, shared: Arc<NavidromeClient>) -> Self {
+        Self { db, secret_box, shared }
+    }
+
+    pub async fn get(&self, user_id: Uuid) -> Result<Option<NavidromeAccount>> {
+        let account = sqlx::query_as::<_, NavidromeAccount>(
+            r#"
+            SELECT navidrome_username, auth_type, created_at, updated_at, last_verified_at
+            FROM user_navidrome_accounts
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(account)
+    }
+
+    /// Verify the credentials against Navidrome and store them, replacing any
+    /// previously connected account
+    pub async fn connect(&self, user_id: Uuid, req: ConnectNavidromeRequest) -> Result<NavidromeAccount> {
+        let base_url = self.shared.base_url().to_string();
+        let (auth_type, secret, client) = match (req.api_key, req.password) {
+            (Some(api_key), _) => {
+                let client = NavidromeClient::with_api_key(base_url, req.username.clone(), api_key.clone());
+                ("api_key", api_key, client)
+            }
+            (None, Some(password)) => {
+                let client = NavidromeClient::new(base_url, req.username.clone(), password.clone());
+                ("password", password, client)
+            }
+            (None, None) => {
+                return Err(AppError::Validation("A password or API key is required".to_string()));
+            }
+        };
+
+        let status = client.ping().await;
+        if !status.reachable {
+            return Err(AppError::Validation(format!(
+                "Navidrome rejected the credentials: {}",
+                status.last_error.unwrap_or_else(|| "unknown error".to_string())
+            )));
+        }
+
+        let sealed = self.secret_box.seal(&secret, user_id.as_bytes())?;
+        let account = sqlx::query_as::<_, NavidromeAccount>(
+            r#"
+            INSERT INTO user_navidrome_accounts (user_id, navidrome_username, auth_type, secret, last_verified_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            ON CONFLICT (user_id) DO UPDATE
+            SET navidrome_username = EXCLUDED.navidrome_username,
+                auth_type = EXCLUDED.auth_type,
+                secret = EXCLUDED.secret,
+                updated_at = NOW(),
+                last_verified_at = NOW()
+            RETURNING navidrome_username, auth_type, created_at, updated_at, last_verified_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(&req.username)
+        .bind(auth_type)
+        .bind(sealed)
+        .fetch_one(&self.db)
+        .await?;
+
+        tracing::info!("User {} connected Navidrome account '{}'", user_id, req.username);
+        Ok(account)
+    }
+
+    /// Forget a user's account. Returns false if none was connected.
+    pub async fn disconnect(&self, user_id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM user_navidrome_accounts WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Client acting as the user's own Navidrome account, if they connected one
+    pub async fn client_for(&self, user_id: Uuid) -> Result<Option<NavidromeClient>> {
+        let row: Option<(String, String, Vec<u8>)> = sqlx::query_as(
+            "SELECT navidrome_username, auth_type, secret FROM user_navidrome_accounts WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        let Some((username, auth_type, sealed)) = row else {
+            return Ok(None);
+        };
+
+        let secret = self.secret_box.open(&sealed, user_id.as_bytes())?;
+        let base_url = self.shared.base_url().to_string();
+        let client = match auth_type.as_str() {
+            "api_key" => NavidromeClient::with_api_key(base_url, username, secret),
+            _ => NavidromeClient::new(base_url, username, secret),
+        };
+
+        // Same server, same budget
+        Ok(Some(client.with_limits_of(&self.shared)))
+    }
+
+    /// The user's own client, falling back to the service account
+    pub async fn client_or_shared(&self, user_id: Uuid) -> Result<Arc<NavidromeClient>> {
+        match self.client_for(user_id).await? {
+            Some(client) => Ok(Arc::new(client)),
+            None => Ok(self.shared.clone()),
+        }
+    }
+}
@@ -0,0 +1,552 @@
+//! OpenID Connect login
+//!
+//! With `OIDC_ISSUER_URL`, `OIDC_CLIENT_ID` and `OIDC_CLIENT_SECRET` set, users can sign
+//! in through an identity provider such as Authelia, Keycloak or Google using the
+//! authorization code flow with PKCE. The provider's endpoints and signing keys are
+//! discovered from its `/.well-known/openid-configuration`.
+//!
+//! The first login of an identity provisions a local account (or links the account
+//! with the same verified email). Its role comes from `OIDC_ROLE_MAPPING`, matched
+//! against the values of the `OIDC_ROLE_CLAIM` claim, and is updated on every login
+//! and refresh, so removing someone from a group takes effect at their next refresh.
+//! The provider's refresh token is stored encrypted, and
+//! `POST /api/v1/auth/oidc/refresh` trades it for a new session.
+
+use crate::error::{AppError, Result};
+use crate::models::UserRole;
+use crate::services::secret_box::SecretBox;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, DecodingKey, Validation};
+use rand::RngCore;
+use redis::aio::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// How long a started login may take to come back from the provider
+const PENDING_LOGIN_TTL_SECS: u64 = 600;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn pending_login_key(state: &str) -> String {
+    format!("navidrome-radio:oidc-login:{}", state)
+}
+
+/// Identity provider settings (`OIDC_*`)
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Where the provider sends users back to: `/api/v1/auth/oidc/callback` on this server
+    pub redirect_url: String,
+    pub scopes: String,
+    /// Claim holding the user's groups or roles; dotted paths reach into objects
+    /// (`realm_access.roles` for Keycloak)
+    pub role_claim: String,
+    /// Claim value to role, in order of precedence
+    pub role_mapping: Vec<(String, UserRole)>,
+    /// Role of users none of whose claim values are mapped
+    pub default_role: UserRole,
+    /// Shown on the login button
+    pub provider_name: String,
+}
+
+impl OidcConfig {
+    /// None unless `OIDC_ISSUER_URL` is set
+    pub fn from_env(public_url: Option<&str>) -> anyhow::Result<Option<Self>> {
+        let var = |key: &str| std::env::var(key).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+
+        let Some(issuer_url) = var("OIDC_ISSUER_URL") else {
+            return Ok(None);
+        };
+        let client_id = var("OIDC_CLIENT_ID").ok_or_else(|| anyhow::anyhow!("OIDC_ISSUER_URL requires OIDC_CLIENT_ID"))?;
+        let client_secret =
+            var("OIDC_CLIENT_SECRET").ok_or_else(|| anyhow::anyhow!("OIDC_ISSUER_URL requires OIDC_CLIENT_SECRET"))?;
+        let redirect_url = var("OIDC_REDIRECT_URL")
+            .or_else(|| public_url.map(|url| format!("{}/api/v1/auth/oidc/callback", url)))
+            .ok_or_else(|| anyhow::anyhow!("OIDC_ISSUER_URL requires OIDC_REDIRECT_URL or PUBLIC_URL"))?;
+
+        Ok(Some(Self {
+            issuer_url: issuer_url.trim_end_matches('/').to_string(),
+            client_id,
+            client_secret,
+            redirect_url,
+            scopes: var("OIDC_SCOPES").unwrap_or_else(|| "openid profile email".to_string()),
+            role_claim: var("OIDC_ROLE_CLAIM").unwrap_or_else(|| "groups".to_string()),
+            role_mapping: parse_role_mapping(&var("OIDC_ROLE_MAPPING").unwrap_or_default())?,
+            default_role: parse_role(&var("OIDC_DEFAULT_ROLE").unwrap_or_else(|| "listener".to_string()))?,
+            provider_name: var("OIDC_PROVIDER_NAME").unwrap_or_else(|| "SSO".to_string()),
+        }))
+    }
+}
+
+fn parse_role(s: &str) -> anyhow::Result<UserRole> {
+    match s.trim().to_lowercase().as_str() {
+        "admin" => Ok(UserRole::Admin),
+        "curator" => Ok(UserRole::Curator),
+        "moderator" => Ok(UserRole::Moderator),
+        "listener" => Ok(UserRole::Listener),
+        other => Err(anyhow::anyhow!(
+            "OIDC roles must be one of admin, curator, moderator, listener (got {:?})",
+            other
+        )),
+    }
+}
+
+/// Parse `value=role` pairs, e.g. `radio-admins=admin,djs=curator`
+fn parse_role_mapping(s: &str) -> anyhow::Result<Vec<(String, UserRole)>> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (value, role) = entry
+                .rsplit_once('=')
+                .ok_or_else(|| anyhow::anyhow!("OIDC_ROLE_MAPPING entries must be value=role (got {:?})", entry))?;
+            Ok((value.trim().to_string(), parse_role(role)?))
+        })
+        .collect()
+}
+
+/// The endpoints of the provider, from its discovery document
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+    userinfo_endpoint: Option<String>,
+}
+
+/// A login started here, kept in Redis until the provider redirects back
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingLogin {
+    nonce: String,
+    code_verifier: String,
+    /// Path in the app to return to afterwards
+    return_to: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    id_token: Option<String>,
+    refresh_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    #[serde(flatten)]
+    profile: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Who signed in, as the provider describes them
+#[derive(Debug, Clone)]
+pub struct OidcIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub username: Option<String>,
+    pub email: Option<String>,
+    /// Role from the mapped claim values (the default role if none matched)
+    pub role: UserRole,
+    /// Whether the token carried the role claim at all. Without it the role of an
+    /// existing account is left alone.
+    pub role_claimed: bool,
+    pub refresh_token: Option<String>,
+}
+
+pub struct OidcClient {
+    config: OidcConfig,
+    db: PgPool,
+    redis: ConnectionManager,
+    secret_box: SecretBox,
+    http: reqwest::Client,
+    metadata: RwLock<Option<ProviderMetadata>>,
+    jwks: RwLock<Option<JwkSet>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig, db: PgPool, redis: ConnectionManager, secret_box: SecretBox) -> Self {
+        let http = reqwest::Client::builder().timeout(HTTP_TIMEOUT).build().unwrap_or_default();
+        Self {
+            config,
+            db,
+            redis,
+            secret_box,
+            http,
+            metadata: RwLock::new(None),
+            jwks: RwLock::new(None),
+        }
+    }
+
+    pub fn provider_name(&self) -> &str {
+        &self.config.provider_name
+    }
+
+    /// The provider URL to send the user to. `return_to` must be a path in the app.
+    pub async fn authorization_url(&self, return_to: Option<String>) -> Result<String> {
+        let metadata = self.metadata().await?;
+        let state = random_token();
+        let pending = PendingLogin {
+            nonce: random_token(),
+            code_verifier: random_token(),
+            return_to: return_to.filter(|path| is_local_path(path)),
+        };
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(pending.code_verifier.as_bytes()));
+
+        let mut conn = self.redis.clone();
+        redis::cmd("SET")
+            .arg(pending_login_key(&state))
+            .arg(serde_json::to_string(&pending)?)
+            .arg("EX")
+            .arg(PENDING_LOGIN_TTL_SECS)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+
+        let url = reqwest::Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("scope", self.config.scopes.as_str()),
+                ("state", state.as_str()),
+                ("nonce", pending.nonce.as_str()),
+                ("code_challenge", challenge.as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| AppError::ExternalApi(format!("Invalid OIDC authorization endpoint: {}", e)))?;
+        Ok(url.into())
+    }
+
+    /// Finish a login the provider redirected back with. Returns the identity and the
+    /// path to return to.
+    pub async fn complete(&self, code: &str, state: &str) -> Result<(OidcIdentity, Option<String>)> {
+        let mut conn = self.redis.clone();
+        let pending: Option<String> = redis::cmd("GETDEL")
+            .arg(pending_login_key(state))
+            .query_async(&mut conn)
+            .await?;
+        let pending: PendingLogin = pending
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .ok_or(AppError::Unauthorized)?;
+
+        let tokens = self
+            .token_request(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("code_verifier", pending.code_verifier.as_str()),
+            ])
+            .await?;
+        let id_token = tokens
+            .id_token
+            .as_deref()
+            .ok_or_else(|| AppError::ExternalApi("OIDC provider returned no ID token".to_string()))?;
+        let claims = self.verify_id_token(id_token).await?;
+        if claims.nonce.as_deref() != Some(pending.nonce.as_str()) {
+            return Err(AppError::Unauthorized);
+        }
+
+        Ok((self.identity(claims, tokens.refresh_token), pending.return_to))
+    }
+
+    /// Trade a stored refresh token for the identity's current claims. The provider
+    /// may rotate the refresh token; the returned identity carries the one to keep.
+    pub async fn refresh(&self, subject: &str, refresh_token: &str) -> Result<OidcIdentity> {
+        let tokens = self
+            .token_request(&[("grant_type", "refresh_token"), ("refresh_token", refresh_token)])
+            .await?;
+
+        // Not every provider issues a new ID token on refresh; userinfo has the claims then
+        let claims = match tokens.id_token.as_deref() {
+            Some(id_token) => self.verify_id_token(id_token).await?,
+            None => self.userinfo(&tokens.access_token).await?,
+        };
+        if claims.sub != subject {
+            return Err(AppError::Unauthorized);
+        }
+
+        let refresh_token = tokens.refresh_token.or_else(|| Some(refresh_token.to_string()));
+        Ok(self.identity(claims, refresh_token))
+    }
+
+    /// Link an identity to a user, keeping its refresh token
+    pub async fn save_identity(&self, user_id: Uuid, identity: &OidcIdentity) -> Result<()> {
+        let sealed = identity
+            .refresh_token
+            .as_deref()
+            .map(|token| self.secret_box.seal(token, user_id.as_bytes()))
+            .transpose()?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO user_identities (user_id, issuer, subject, refresh_token, last_login_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            ON CONFLICT (issuer, subject) DO UPDATE
+            SET refresh_token = COALESCE(EXCLUDED.refresh_token, user_identities.refresh_token),
+                last_login_at = NOW()
+            "#,
+        )
+        .bind(user_id)
+        .bind(&identity.issuer)
+        .bind(&identity.subject)
+        .bind(sealed)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// The user's identity at this provider and its refresh token, if they have one
+    pub async fn stored_identity(&self, user_id: Uuid) -> Result<Option<(String, String)>> {
+        let row: Option<(String, Option<Vec<u8>>)> = sqlx::query_as(
+            "SELECT subject, refresh_token FROM user_identities WHERE user_id = $1 AND issuer = $2",
+        )
+        .bind(user_id)
+        .bind(&self.config.issuer_url)
+        .fetch_optional(&self.db)
+        .await?;
+
+        match row {
+            Some((subject, Some(sealed))) => Ok(Some((subject, self.secret_box.open(&sealed, user_id.as_bytes())?))),
+            _ => Ok(None),
+        }
+    }
+
+    fn identity(&self, claims: IdTokenClaims, refresh_token: Option<String>) -> OidcIdentity {
+        let string = |key: &str| claims.profile.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        let values = claim_values(&claims.profile, &self.config.role_claim);
+        let role_claimed = claim(&claims.profile, &self.config.role_claim).is_some();
+        let role = self
+            .config
+            .role_mapping
+            .iter()
+            .find(|(value, _)| values.contains(value))
+            .map(|(_, role)| role.clone())
+            .unwrap_or_else(|| self.config.default_role.clone());
+
+        OidcIdentity {
+            issuer: self.config.issuer_url.clone(),
+            username: string("preferred_username").or_else(|| string("nickname")).or_else(|| string("name")),
+            email: string("email"),
+            subject: claims.sub,
+            role,
+            role_claimed,
+            refresh_token,
+        }
+    }
+
+    async fn token_request(&self, params: &[(&str, &str)]) -> Result<TokenResponse> {
+        let metadata = self.metadata().await?;
+        let response = self
+            .http
+            .post(&metadata.token_endpoint)
+            .basic_auth(&self.config.client_id, Some(&self.config.client_secret))
+            .form(params)
+            .send()
+            .await
+            .map_err(|e| AppError::ExternalApi(format!("OIDC token request failed: {}", e)))?;
+
+        let status = response.status();
+        if status.is_client_error() {
+            // An expired code or revoked refresh token: the user has to sign in again
+            let body = response.text().await.unwrap_or_default();
+            tracing::warn!("OIDC token request rejected: {}: {}", status, body.chars().take(200).collect::<String>());
+            return Err(AppError::Unauthorized);
+        }
+        if !status.is_success() {
+            return Err(AppError::ExternalApi(format!("OIDC token request failed: {}", status)));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| AppError::ExternalApi(format!("Invalid OIDC token response: {}", e)))
+    }
+
+    async fn userinfo(&self, access_token: &str) -> Result<IdTokenClaims> {
+        let metadata = self.metadata().await?;
+        let endpoint = metadata
+            .userinfo_endpoint
+            .ok_or_else(|| AppError::ExternalApi("OIDC provider has no userinfo endpoint".to_string()))?;
+        let response = self
+            .http
+            .get(&endpoint)
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AppError::ExternalApi(format!("OIDC userinfo request failed: {}", e)))?;
+        response
+            .json()
+            .await
+            .map_err(|e| AppError::ExternalApi(format!("Invalid OIDC userinfo response: {}", e)))
+    }
+
+    async fn verify_id_token(&self, id_token: &str) -> Result<IdTokenClaims> {
+        let header = decode_header(id_token).map_err(|_| AppError::Unauthorized)?;
+        let metadata = self.metadata().await?;
+
+        let key = match self.signing_key(header.kid.as_deref(), false).await? {
+            Some(key) => key,
+            // The provider may have rotated its keys since they were fetched
+            None => self
+                .signing_key(header.kid.as_deref(), true)
+                .await?
+                .ok_or(AppError::Unauthorized)?,
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_issuer(&[&metadata.issuer]);
+        let data = decode::<IdTokenClaims>(id_token, &key, &validation).map_err(|e| {
+            tracing::warn!("Rejected OIDC ID token: {}", e);
+            AppError::Unauthorized
+        })?;
+        Ok(data.claims)
+    }
+
+    /// The provider's key with ID `kid` (or its only key, for tokens without one)
+    async fn signing_key(&self, kid: Option<&str>, refetch: bool) -> Result<Option<DecodingKey>> {
+        if refetch || self.jwks.read().await.is_none() {
+            let metadata = self.metadata().await?;
+            let jwks: JwkSet = self
+                .http
+                .get(&metadata.jwks_uri)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .map_err(|e| AppError::ExternalApi(format!("Failed to fetch OIDC signing keys: {}", e)))?
+                .json()
+                .await
+                .map_err(|e| AppError::ExternalApi(format!("Invalid OIDC signing keys: {}", e)))?;
+            *self.jwks.write().await = Some(jwks);
+        }
+
+        let jwks = self.jwks.read().await;
+        let Some(jwks) = jwks.as_ref() else {
+            return Ok(None);
+        };
+        let jwk = match kid {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        };
+        jwk.map(|jwk| DecodingKey::from_jwk(jwk).map_err(|_| AppError::Unauthorized))
+            .transpose()
+    }
+
+    async fn metadata(&self) -> Result<ProviderMetadata> {
+        if let Some(metadata) = self.metadata.read().await.as_ref() {
+            return Ok(metadata.clone());
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", self.config.issuer_url);
+        let metadata: ProviderMetadata = self
+            .http
+            .get(&url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AppError::ExternalApi(format!("OIDC discovery failed: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| AppError::ExternalApi(format!("Invalid OIDC discovery document: {}", e)))?;
+        *self.metadata.write().await = Some(metadata.clone());
+        Ok(metadata)
+    }
+}
+
+/// A claim by name, with dotted paths reaching into objects (`realm_access.roles`)
+fn claim<'a>(profile: &'a serde_json::Map<String, serde_json::Value>, path: &str) -> Option<&'a serde_json::Value> {
+    let mut parts = path.split('.');
+    let mut value = profile.get(parts.next()?)?;
+    for part in parts {
+        value = value.get(part)?;
+    }
+    Some(value)
+}
+
+/// The string values of a claim: a space-separated string or an array of strings
+fn claim_values(profile: &serde_json::Map<String, serde_json::Value>, path: &str) -> Vec<String> {
+    match claim(profile, path) {
+        Some(serde_json::Value::String(s)) => s.split_whitespace().map(str::to_string).collect(),
+        Some(serde_json::Value::Array(items)) => items.iter().filter_map(|v| v.as_str()).map(str::to_string).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// A path within the app, not another site (`//host` and `/\host` are both
+/// protocol-relative to browsers)
+fn is_local_path(path: &str) -> bool {
+    path.starts_with('/') && !path.starts_with("//") && !path.starts_with("/\\")
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Usernames of provisioned accounts: what the provider calls the user, limited to
+/// the characters and length local accounts allow
+pub fn username_candidate(identity: &OidcIdentity) -> String {
+    let source = identity
+        .username
+        .clone()
+        .or_else(|| identity.email.as_deref().and_then(|e| e.split('@').next()).map(str::to_string))
+        .unwrap_or_default();
+    let cleaned: String = source
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        .take(40)
+        .collect();
+    if cleaned.len() < 3 {
+        format!("user-{}", &hex::encode(Sha256::digest(identity.subject.as_bytes()))[..8])
+    } else {
+        cleaned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_claim_values() {
+        let profile = serde_json::json!({
+            "groups": ["radio-admins", "users"],
+            "realm_access": { "roles": ["dj"] },
+            "scope": "a b",
+        });
+        let profile = profile.as_object().unwrap();
+        assert_eq!(claim_values(profile, "groups"), vec!["radio-admins", "users"]);
+        assert_eq!(claim_values(profile, "realm_access.roles"), vec!["dj"]);
+        assert_eq!(claim_values(profile, "scope"), vec!["a", "b"]);
+        assert!(claim_values(profile, "missing.path").is_empty());
+    }
+
+    #[test]
+    fn test_parse_role_mapping() {
+        let mapping = parse_role_mapping("radio-admins=admin, djs=curator").unwrap();
+        assert_eq!(mapping, vec![("radio-admins".to_string(), UserRole::Admin), ("djs".to_string(), UserRole::Curator)]);
+        assert!(parse_role_mapping("djs").is_err());
+        assert!(parse_role_mapping("djs=superuser").is_err());
+        assert!(parse_role_mapping("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_is_local_path() {
+        assert!(is_local_path("/station/jazz"));
+        assert!(!is_local_path("//evil.example"));
+        assert!(!is_local_path("/\\evil.example"));
+        assert!(!is_local_path("https://evil.example"));
+    }
+}
@@ -0,0 +1,285 @@
+//! Resolving imported playlists against the library
+//!
+//! `POST /stations/import` takes a Navidrome playlist or the text of an M3U file. Each
+//! entry is matched to a `library_index` track: by ID (Navidrome entries), by file path
+//! (M3U entries), then by exact title and artist, then by trigram similarity (see
+//! [`seed_selector::find_fuzzy_match`]). Entries nothing matches are reported back.
+
+use crate::error::Result;
+use crate::services::navidrome::PlaylistEntry;
+use crate::services::seed_selector;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::HashSet;
+
+/// Trailing path components tried when matching an M3U path to a library path
+/// (`Artist/Album/Track.flac` and deeper)
+const MAX_PATH_SUFFIX: usize = 5;
+const MIN_PATH_SUFFIX: usize = 2;
+
+/// One entry of an imported playlist
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportEntry {
+    /// Navidrome track ID
+    pub track_id: Option<String>,
+    /// File path or URL as written in the M3U
+    pub path: Option<String>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+}
+
+impl ImportEntry {
+    /// How the entry appears in the unresolved list
+    fn describe(&self) -> String {
+        match (&self.artist, &self.title, &self.path) {
+            (Some(artist), Some(title), _) => format!("{} - {}", artist, title),
+            (None, Some(title), _) => title.clone(),
+            (_, None, Some(path)) => path.clone(),
+            _ => self.track_id.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl From<PlaylistEntry> for ImportEntry {
+    fn from(entry: PlaylistEntry) -> Self {
+        Self {
+            track_id: Some(entry.id),
+            path: None,
+            title: Some(entry.title).filter(|t| !t.is_empty()),
+            artist: Some(entry.artist).filter(|a| !a.is_empty()),
+        }
+    }
+}
+
+/// A parsed M3U playlist
+#[derive(Debug, Default, PartialEq)]
+pub struct M3uPlaylist {
+    /// From a `#PLAYLIST:` line
+    pub name: Option<String>,
+    pub entries: Vec<ImportEntry>,
+}
+
+/// Parse plain or extended M3U. `#EXTINF:<secs>,<artist> - <title>` lines name the
+/// path that follows them; other comments are ignored.
+pub fn parse_m3u(text: &str) -> M3uPlaylist {
+    let mut playlist = M3uPlaylist::default();
+    let mut pending: Option<(Option<String>, Option<String>)> = None;
+
+    for line in text.trim_start_matches('\u{feff}').lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("#PLAYLIST:") {
+            playlist.name = Some(name.trim().to_string()).filter(|n| !n.is_empty());
+        } else if let Some(info) = line.strip_prefix("#EXTINF:") {
+            let display = info.split_once(',').map(|(_, display)| display.trim()).unwrap_or("");
+            pending = Some(match display.split_once(" - ") {
+                Some((artist, title)) => (Some(title.trim().to_string()), Some(artist.trim().to_string())),
+                None => (Some(display.to_string()).filter(|d| !d.is_empty()), None),
+            });
+        } else if !line.starts_with('#') {
+            let (title, artist) = pending.take().unwrap_or((None, None));
+            playlist.entries.push(ImportEntry {
+                track_id: None,
+                path: Some(normalize_path(line)),
+                title,
+                artist,
+            });
+        }
+    }
+
+    playlist
+}
+
+/// Forward slashes, without a `file://` scheme or percent-encoding
+fn normalize_path(path: &str) -> String {
+    match path.strip_prefix("file://") {
+        Some(url) => percent_decode(url),
+        None => path.to_string(),
+    }
+    .replace('\\', "/")
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Trailing parts of a path, longest first, that a library path may equal
+fn path_suffixes(path: &str) -> Vec<String> {
+    let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+    (MIN_PATH_SUFFIX..=MAX_PATH_SUFFIX.min(parts.len()))
+        .rev()
+        .map(|n| parts[parts.len() - n..].join("/"))
+        .collect()
+}
+
+/// Title and artist guessed from an `Artist/Album/NN - Title.ext` path
+fn tags_from_path(path: &str) -> Option<(String, String)> {
+    let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+    let [.., artist, _album, file] = parts.as_slice() else {
+        return None;
+    };
+    let stem = file.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(file);
+    let title = stem
+        .trim_start_matches(|c: char| c.is_ascii_digit())
+        .trim_start_matches([' ', '-', '.', '_'])
+        .trim();
+    (!title.is_empty()).then(|| (title.to_string(), artist.to_string()))
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnresolvedEntry {
+    /// Position in the playlist (from 1)
+    pub position: usize,
+    pub entry: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportResolution {
+    /// Matched tracks in playlist order, without duplicates
+    pub track_ids: Vec<String>,
+    /// Entries matched only by title/artist similarity
+    pub fuzzy_matched: usize,
+    pub unresolved: Vec<UnresolvedEntry>,
+}
+
+/// Match playlist entries to library tracks
+pub async fn resolve(db: &PgPool, entries: &[ImportEntry]) -> Result<ImportResolution> {
+    let mut resolution = ImportResolution::default();
+    let mut seen = HashSet::new();
+
+    for (i, entry) in entries.iter().enumerate() {
+        let (track_id, fuzzy) = match resolve_entry(db, entry).await? {
+            Some(matched) => matched,
+            None => {
+                resolution.unresolved.push(UnresolvedEntry {
+                    position: i + 1,
+                    entry: entry.describe(),
+                });
+                continue;
+            }
+        };
+        if fuzzy {
+            resolution.fuzzy_matched += 1;
+        }
+        if seen.insert(track_id.clone()) {
+            resolution.track_ids.push(track_id);
+        }
+    }
+
+    Ok(resolution)
+}
+
+/// The matching track, and whether it was only a fuzzy match
+async fn resolve_entry(db: &PgPool, entry: &ImportEntry) -> Result<Option<(String, bool)>> {
+    if let Some(track_id) = &entry.track_id {
+        let known: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM library_index WHERE id = $1)")
+            .bind(track_id)
+            .fetch_one(db)
+            .await?;
+        if known {
+            return Ok(Some((track_id.clone(), false)));
+        }
+    }
+
+    let mut tags = entry.title.clone().map(|title| (title, entry.artist.clone().unwrap_or_default()));
+    if let Some(path) = &entry.path {
+        let suffixes = path_suffixes(path);
+        let by_path: Option<String> = sqlx::query_scalar(
+            "SELECT id FROM library_index WHERE path = ANY($1) ORDER BY LENGTH(path) DESC LIMIT 1",
+        )
+        .bind(&suffixes)
+        .fetch_optional(db)
+        .await?;
+        if let Some(track_id) = by_path {
+            return Ok(Some((track_id, false)));
+        }
+        if tags.is_none() {
+            tags = tags_from_path(path);
+        }
+    }
+
+    let Some((title, artist)) = tags else {
+        return Ok(None);
+    };
+    if let Some(track) = seed_selector::find_exact_match(db, &title, &artist).await? {
+        return Ok(Some((track.id, false)));
+    }
+    if artist.is_empty() {
+        return Ok(None);
+    }
+    Ok(seed_selector::find_fuzzy_match(db, &title, &artist)
+        .await?
+        .map(|track| (track.id, true)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_m3u() {
+        let playlist = parse_m3u(
+            "#EXTM3U\n#PLAYLIST:Road Trip\n#EXTINF:354,David Bowie - Heroes\n/music/David Bowie/Heroes/03 - Heroes.flac\n\n\
+             C:\\Music\\Björk\\Post\\Hyperballad.mp3\n#EXTINF:-1,Untitled\nfile:///music/Some%20Song.ogg\n",
+        );
+        assert_eq!(playlist.name.as_deref(), Some("Road Trip"));
+        assert_eq!(
+            playlist.entries,
+            vec![
+                ImportEntry {
+                    track_id: None,
+                    path: Some("/music/David Bowie/Heroes/03 - Heroes.flac".to_string()),
+                    title: Some("Heroes".to_string()),
+                    artist: Some("David Bowie".to_string()),
+                },
+                ImportEntry {
+                    track_id: None,
+                    path: Some("C:/Music/Björk/Post/Hyperballad.mp3".to_string()),
+                    title: None,
+                    artist: None,
+                },
+                ImportEntry {
+                    track_id: None,
+                    path: Some("/music/Some Song.ogg".to_string()),
+                    title: Some("Untitled".to_string()),
+                    artist: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_path_matching_helpers() {
+        assert_eq!(
+            path_suffixes("/mnt/music/Björk/Post/Hyperballad.mp3"),
+            vec![
+                "mnt/music/Björk/Post/Hyperballad.mp3",
+                "music/Björk/Post/Hyperballad.mp3",
+                "Björk/Post/Hyperballad.mp3",
+                "Post/Hyperballad.mp3",
+            ]
+        );
+        assert_eq!(
+            tags_from_path("C:/Music/Björk/Post/05 - Hyperballad.mp3"),
+            Some(("Hyperballad".to_string(), "Björk".to_string()))
+        );
+        assert_eq!(tags_from_path("Hyperballad.mp3"), None);
+    }
+}
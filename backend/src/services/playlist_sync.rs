@@ -0,0 +1,115 @@
+//! Stations that follow a Navidrome playlist
+//!
+//! A station imported from a playlist remembers it in `stations.source_playlist_id`.
+//! When the import asked for syncing, a periodic check compares the playlist's
+//! `changed` timestamp with the one seen at the last sync and replaces the station's
+//! `track_ids` with the playlist's current entries. Playlists are read as the station
+//! creator's own Navidrome account when they connected one.
+
+use crate::error::Result;
+use crate::services::navidrome::NavidromePlaylist;
+use crate::services::navidrome_accounts::NavidromeAccounts;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub struct PlaylistSync {
+    db: PgPool,
+    navidrome_accounts: Arc<NavidromeAccounts>,
+}
+
+impl PlaylistSync {
+    pub fn new(db: PgPool, navidrome_accounts: Arc<NavidromeAccounts>) -> Self {
+        Self { db, navidrome_accounts }
+    }
+
+    /// Check the source playlists every `interval`
+    pub fn spawn(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match self.sync_all().await {
+                    Ok(0) => {}
+                    Ok(updated) => tracing::info!("Synced {} station(s) from their Navidrome playlists", updated),
+                    Err(e) => tracing::warn!("Playlist sync failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Refresh every syncing station whose playlist changed. Returns the number updated.
+    pub async fn sync_all(&self) -> Result<usize> {
+        let stations: Vec<(Uuid, Uuid, String, Option<DateTime<Utc>>)> = sqlx::query_as(
+            r#"
+            SELECT id, created_by, source_playlist_id, source_playlist_synced_at
+            FROM stations
+            WHERE source_playlist_sync AND source_playlist_id IS NOT NULL
+            "#,
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        if stations.is_empty() {
+            return Ok(0);
+        }
+
+        // Playlists visible to each station owner, fetched once per owner
+        let mut playlists: HashMap<Uuid, Vec<NavidromePlaylist>> = HashMap::new();
+        let mut updated = 0;
+
+        for (station_id, owner, playlist_id, synced_at) in stations {
+            let navidrome = self.navidrome_accounts.client_or_shared(owner).await?;
+            if let Entry::Vacant(entry) = playlists.entry(owner) {
+                entry.insert(navidrome.get_playlists().await?);
+            }
+
+            let Some(playlist) = playlists[&owner].iter().find(|p| p.id == playlist_id) else {
+                tracing::warn!("Source playlist {} of station {} no longer exists", playlist_id, station_id);
+                continue;
+            };
+
+            // Playlists without a change timestamp are re-read every time
+            if let (Some(changed), Some(synced_at)) = (playlist.changed, synced_at) {
+                if changed <= synced_at {
+                    continue;
+                }
+            }
+
+            let track_ids = match navidrome.get_playlist_track_ids(&playlist.id).await {
+                Ok(ids) => ids,
+                Err(e) => {
+                    tracing::warn!("Failed to read playlist {}: {}", playlist.id, e);
+                    continue;
+                }
+            };
+            if track_ids.is_empty() {
+                // Keep the station playable rather than leaving it without tracks
+                tracing::warn!("Source playlist {} is empty, keeping station {} as is", playlist.id, station_id);
+                continue;
+            }
+
+            sqlx::query(
+                r#"
+                UPDATE stations
+                SET track_ids = $2, source_playlist_synced_at = $3, updated_at = NOW()
+                WHERE id = $1
+                "#,
+            )
+            .bind(station_id)
+            .bind(serde_json::to_value(&track_ids).unwrap())
+            .bind(playlist.changed.unwrap_or_else(Utc::now))
+            .execute(&self.db)
+            .await?;
+
+            tracing::debug!("Station {} now has {} tracks from playlist {}", station_id, track_ids.len(), playlist.id);
+            updated += 1;
+        }
+
+        Ok(updated)
+    }
+}
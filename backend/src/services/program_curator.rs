@@ -0,0 +1,97 @@
+//! Automatic curation of program blocks
+//!
+//! Program blocks with a curation prompt ("weekday mornings → upbeat") get their pool
+//! from the [`HybridCurator`] instead of a fixed track list. Shortly before a block
+//! comes on air, and again if it's still on air when its pool goes stale, the prompt is
+//! curated in the background and the block's `track_ids` replaced. Broadcasting
+//! stations pick the new pool up on their next schedule check and swap their queue,
+//! leaving the current track playing.
+//!
+//! Until its first curation, a block plays from its genres, or from the station's
+//! fallbacks when it has none.
+
+use crate::error::Result;
+use crate::services::hybrid_curator::HybridCurator;
+use crate::services::schedule;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// How often blocks due for curation are looked for
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A block whose curation failed isn't tried again for this long
+const RETRY_AFTER: Duration = Duration::from_secs(30 * 60);
+
+pub struct ProgramCurator {
+    db: sqlx::PgPool,
+    hybrid_curator: Arc<HybridCurator>,
+    /// When curating each block last failed
+    failures: Mutex<HashMap<Uuid, Instant>>,
+}
+
+impl ProgramCurator {
+    pub fn new(db: sqlx::PgPool, hybrid_curator: Arc<HybridCurator>) -> Self {
+        Self {
+            db,
+            hybrid_curator,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Curate due blocks every minute, one at a time
+    pub fn spawn(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.curate_due().await {
+                    tracing::warn!("Failed to check program blocks for curation: {}", e);
+                }
+            }
+        });
+    }
+
+    async fn curate_due(&self) -> Result<()> {
+        for (station, block) in schedule::due_for_curation(&self.db, Utc::now()).await? {
+            if self
+                .failures
+                .lock()
+                .await
+                .get(&block.id)
+                .is_some_and(|failed| failed.elapsed() < RETRY_AFTER)
+            {
+                continue;
+            }
+            let Some(prompt) = block.curation_prompt.as_deref() else {
+                continue;
+            };
+
+            tracing::info!("Curating program '{}' of station {} from \"{}\"", block.name, station.name, prompt);
+            let limit = self.hybrid_curator.config().playlist_size;
+            match self
+                .hybrid_curator
+                .curate(prompt, limit, None, station.config.allow_explicit, None)
+                .await
+            {
+                Ok(track_ids) if !track_ids.is_empty() => {
+                    schedule::store_curated(&self.db, block.id, &track_ids).await?;
+                    self.failures.lock().await.remove(&block.id);
+                    tracing::info!("Program '{}' of station {} has {} new tracks", block.name, station.name, track_ids.len());
+                }
+                Ok(_) => {
+                    tracing::warn!("Curating program '{}' of station {} found no tracks", block.name, station.name);
+                    self.failures.lock().await.insert(block.id, Instant::now());
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to curate program '{}' of station {}: {}", block.name, station.name, e);
+                    self.failures.lock().await.insert(block.id, Instant::now());
+                }
+            }
+        }
+        Ok(())
+    }
+}
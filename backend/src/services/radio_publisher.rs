@@ -0,0 +1,135 @@
+//! Publishes stations into Navidrome's internet radio list
+//!
+//! Each station is registered with Navidrome as an internet radio station pointing
+//! at its HLS stream, so it shows up in Navidrome and Subsonic clients. The
+//! Navidrome-side ID is stored in `stations.navidrome_radio_id` and the entry is
+//! refreshed whenever the station is created or renamed.
+
+use crate::error::Result;
+use crate::models::Station;
+use crate::services::NavidromeClient;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub struct RadioPublisher {
+    db: PgPool,
+    navidrome_client: Arc<NavidromeClient>,
+    public_url: String,
+}
+
+impl RadioPublisher {
+    pub fn new(db: PgPool, navidrome_client: Arc<NavidromeClient>, public_url: String) -> Self {
+        Self {
+            db,
+            navidrome_client,
+            public_url,
+        }
+    }
+
+    /// Stream URL registered in Navidrome for a station
+    pub fn stream_url(&self, station_id: Uuid) -> String {
+        format!("{}/api/v1/stations/{}/stream/playlist.m3u8", self.public_url, station_id)
+    }
+
+    /// Station page in the web UI
+    fn homepage_url(&self, station: &Station) -> String {
+        format!("{}/station/{}", self.public_url, station.path)
+    }
+
+    /// Create or refresh the Navidrome radio entry for a station
+    pub async fn publish(&self, station: &Station) -> Result<()> {
+        let stream_url = self.stream_url(station.id);
+        let homepage_url = self.homepage_url(station);
+
+        let stored_id: Option<String> =
+            sqlx::query_scalar("SELECT navidrome_radio_id FROM stations WHERE id = $1")
+                .bind(station.id)
+                .fetch_optional(&self.db)
+                .await?
+                .flatten();
+
+        let existing = self.navidrome_client.get_internet_radio_stations().await?;
+
+        // Prefer the stored ID, but fall back to matching the stream URL in case the
+        // ID was lost (e.g. the entry was recreated by hand in Navidrome)
+        let current = existing
+            .iter()
+            .find(|r| Some(&r.id) == stored_id.as_ref())
+            .or_else(|| existing.iter().find(|r| r.stream_url == stream_url));
+
+        let radio_id = match current {
+            Some(radio) => {
+                if radio.name != station.name
+                    || radio.stream_url != stream_url
+                    || radio.home_page_url.as_deref() != Some(homepage_url.as_str())
+                {
+                    self.navidrome_client
+                        .update_internet_radio_station(&radio.id, &station.name, &stream_url, Some(&homepage_url))
+                        .await?;
+                    tracing::info!("Updated Navidrome radio entry for station {}", station.path);
+                }
+                radio.id.clone()
+            }
+            None => {
+                self.navidrome_client
+                    .create_internet_radio_station(&station.name, &stream_url, Some(&homepage_url))
+                    .await?;
+
+                let created = self
+                    .navidrome_client
+                    .get_internet_radio_stations()
+                    .await?
+                    .into_iter()
+                    .find(|r| r.stream_url == stream_url)
+                    .map(|r| r.id);
+
+                tracing::info!("Published station {} to Navidrome radio list", station.path);
+                match created {
+                    Some(id) => id,
+                    None => {
+                        tracing::warn!("Created Navidrome radio entry for {} but could not find its ID", station.path);
+                        return Ok(());
+                    }
+                }
+            }
+        };
+
+        if stored_id.as_deref() != Some(radio_id.as_str()) {
+            sqlx::query("UPDATE stations SET navidrome_radio_id = $1 WHERE id = $2")
+                .bind(&radio_id)
+                .bind(station.id)
+                .execute(&self.db)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove a station's radio entry from Navidrome
+    pub async fn unpublish(&self, navidrome_radio_id: &str) -> Result<()> {
+        self.navidrome_client
+            .delete_internet_radio_station(navidrome_radio_id)
+            .await?;
+        tracing::info!("Removed Navidrome radio entry {}", navidrome_radio_id);
+        Ok(())
+    }
+
+    /// Publish every station (except personal Daily Mixes), returning how many were synced successfully
+    pub async fn sync_all(&self) -> Result<usize> {
+        let stations: Vec<Station> =
+            sqlx::query_as("SELECT * FROM stations WHERE daily_mix_user_id IS NULL ORDER BY created_at")
+            .fetch_all(&self.db)
+            .await?;
+
+        let mut synced = 0;
+        for station in &stations {
+            match self.publish(station).await {
+                Ok(()) => synced += 1,
+                Err(e) => tracing::warn!("Failed to publish station {} to Navidrome: {}", station.path, e),
+            }
+        }
+
+        Ok(synced)
+    }
+}
@@ -0,0 +1,250 @@
+//! API rate limiting
+//!
+//! Every `/api/v1` request takes a token from a bucket in Redis. Signed-in requests
+//! draw from a bucket per user, anonymous ones from a bucket per client IP. Routes
+//! that start expensive work (AI curation, library syncs, embedding runs) have their
+//! own, much smaller budget on top of the general one. Listening (HLS playlist and
+//! segment polling, continuous streams, now-playing streams and listener heartbeats)
+//! isn't limited: anonymous listeners behind one NAT share an IP, and a single
+//! listener polls for as long as they listen. Buckets refill continuously,
+//! so a client that used its burst gets a token back every `60 / per_minute` seconds.
+//!
+//! Buckets live in Redis so all API instances share them. If Redis is unavailable the
+//! limiter fails open.
+
+use crate::error::{AppError, Result};
+use axum::http::Method;
+use redis::aio::ConnectionManager;
+use redis::Script;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+/// Takes one token from the bucket in KEYS[1], refilling it first.
+/// ARGV: capacity, refill per millisecond, now (ms). Returns {allowed, retry_after_ms}.
+const TOKEN_BUCKET_SCRIPT: &str = r#"
+local capacity = tonumber(ARGV[1])
+local rate = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'at')
+local tokens = tonumber(bucket[1]) or capacity
+local at = tonumber(bucket[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - at) * rate)
+
+local allowed = 0
+local retry_after = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+else
+    retry_after = math.ceil((1 - tokens) / rate)
+end
+
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'at', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / rate))
+return {allowed, retry_after}
+"#;
+
+/// Which budget a route draws from, besides the general one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteBudget {
+    General,
+    /// AI curation, library syncs and embedding runs
+    Expensive,
+    /// Station streams and listener heartbeats, which aren't limited
+    Listening,
+}
+
+impl RouteBudget {
+    /// Classify a request by method and path (without the `/api/v1` prefix)
+    pub fn for_request(method: &Method, path: &str) -> Self {
+        const EXPENSIVE: [&str; 5] = [
+            "/ai/",
+            "/library/sync",
+            "/library/curate",
+            "/library/embeddings/",
+            "/embeddings/",
+        ];
+        // Status reads and pause/stop controls that share those prefixes
+        const EXEMPT: [&str; 9] = [
+            "/ai/capabilities",
+            "/ai/usage",
+            "/library/sync-status",
+            "/embeddings/status",
+            "/embeddings/runtime",
+            "/embeddings/pause",
+            "/embeddings/resume",
+            "/embeddings/stop",
+            "/embeddings/visualization/progress",
+        ];
+
+        if let Some(rest) = path.strip_prefix("/stations/") {
+            // Below /stations/:id
+            let route = rest.split_once('/').map_or("", |(_, route)| route);
+            if route.starts_with("stream/")
+                || route == "stream.mp3"
+                || route == "nowplaying/stream"
+                || route.starts_with("listener/")
+            {
+                return RouteBudget::Listening;
+            }
+        }
+        if EXEMPT.iter().any(|prefix| path.starts_with(prefix))
+            || (method == Method::GET && path == "/embeddings/vector-index")
+        {
+            return RouteBudget::General;
+        }
+        if EXPENSIVE.iter().any(|prefix| path.starts_with(prefix)) {
+            return RouteBudget::Expensive;
+        }
+        RouteBudget::General
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            RouteBudget::General => "general",
+            RouteBudget::Expensive => "expensive",
+            RouteBudget::Listening => "listening",
+        }
+    }
+}
+
+/// Who a bucket belongs to
+#[derive(Debug, Clone, Copy)]
+pub enum Client {
+    User(Uuid),
+    Ip(IpAddr),
+}
+
+impl Client {
+    fn key(&self) -> String {
+        match self {
+            Client::User(id) => format!("user:{}", id),
+            Client::Ip(ip) => format!("ip:{}", ip),
+        }
+    }
+}
+
+pub struct RateLimiter {
+    redis: ConnectionManager,
+    script: Script,
+    per_minute: u32,
+    expensive_per_minute: u32,
+}
+
+impl RateLimiter {
+    pub fn new(redis: ConnectionManager, per_minute: u32, expensive_per_minute: u32) -> Self {
+        Self {
+            redis,
+            script: Script::new(TOKEN_BUCKET_SCRIPT),
+            per_minute: per_minute.max(1),
+            expensive_per_minute: expensive_per_minute.max(1),
+        }
+    }
+
+    /// Take a token for a request, or fail with `TooManyRequests` if the client is
+    /// out of budget
+    pub async fn check(&self, client: Client, budget: RouteBudget) -> Result<()> {
+        if budget == RouteBudget::Listening {
+            return Ok(());
+        }
+        self.take(client, RouteBudget::General, self.per_minute).await?;
+        if budget == RouteBudget::Expensive {
+            self.take(client, budget, self.expensive_per_minute).await?;
+        }
+        Ok(())
+    }
+
+    async fn take(&self, client: Client, budget: RouteBudget, per_minute: u32) -> Result<()> {
+        let mut conn = self.redis.clone();
+        let key = format!("navidrome-radio:ratelimit:{}:{}", budget.as_str(), client.key());
+        let rate = per_minute as f64 / 60_000.0;
+        let now = chrono::Utc::now().timestamp_millis();
+
+        let result: redis::RedisResult<(i64, i64)> = self
+            .script
+            .key(&key)
+            .arg(per_minute)
+            .arg(rate)
+            .arg(now)
+            .invoke_async(&mut conn)
+            .await;
+
+        match result {
+            Ok((1, _)) => Ok(()),
+            Ok((_, retry_after_ms)) => Err(AppError::TooManyRequests {
+                retry_after_secs: (retry_after_ms.max(0) as u64).div_ceil(1000).max(1),
+            }),
+            Err(e) => {
+                tracing::warn!("Rate limiter unavailable, allowing request: {}", e);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expensive_routes_get_their_own_budget() {
+        for path in [
+            "/ai/curate",
+            "/ai/hybrid-curate-stream",
+            "/library/sync",
+            "/library/sync-stream",
+            "/library/curate",
+            "/library/embeddings/migrate",
+            "/embeddings/index",
+        ] {
+            assert_eq!(RouteBudget::for_request(&Method::POST, path), RouteBudget::Expensive, "{}", path);
+        }
+
+        for path in [
+            "/stations",
+            "/ai/capabilities",
+            "/library/sync-status",
+            "/embeddings/status",
+            "/embeddings/stop",
+            "/library/search",
+        ] {
+            assert_eq!(RouteBudget::for_request(&Method::POST, path), RouteBudget::General, "{}", path);
+        }
+
+        assert_eq!(
+            RouteBudget::for_request(&Method::GET, "/embeddings/vector-index"),
+            RouteBudget::General
+        );
+        assert_eq!(
+            RouteBudget::for_request(&Method::POST, "/embeddings/vector-index"),
+            RouteBudget::Expensive
+        );
+    }
+
+    #[test]
+    fn test_listening_is_not_limited() {
+        let id = "6f9619ff-8b86-d011-b42d-00cf4fc964ff";
+        for (method, route) in [
+            (Method::GET, "stream/playlist.m3u8"),
+            (Method::GET, "stream/segment/42"),
+            (Method::GET, "stream/variant/64/segment/42"),
+            (Method::GET, "stream.mp3"),
+            (Method::GET, "nowplaying/stream"),
+            (Method::POST, "listener/heartbeat"),
+            (Method::POST, "listener/leave"),
+        ] {
+            let path = format!("/stations/{}/{}", id, route);
+            assert_eq!(RouteBudget::for_request(&method, &path), RouteBudget::Listening, "{}", path);
+        }
+
+        for path in [
+            format!("/stations/{}", id),
+            format!("/stations/{}/nowplaying", id),
+            format!("/stations/{}/skip", id),
+            "/stations/listeners".to_string(),
+        ] {
+            assert_eq!(RouteBudget::for_request(&Method::GET, &path), RouteBudget::General, "{}", path);
+        }
+    }
+}
@@ -0,0 +1,366 @@
+//! User track ratings and the personalization they drive
+//!
+//! Ratings (0-5 stars) are stored per user in `user_track_ratings`; a trigger keeps
+//! `library_index.avg_rating` / `rating_count` up to date. When a user curates a
+//! station, [`RatingBias`] steers the curators: tracks the user rated 1 star or less
+//! are never picked, and tracks rated 4 stars or more are favored. Curations for
+//! stations that don't allow explicit content also leave out explicit tracks through it.
+//!
+//! Listeners can also give a quick thumbs up or down (`user_track_feedback`). It counts
+//! like a 5 or 0 star rating for tracks the user hasn't rated, and while the user
+//! listens to a station, [`AudienceFeedback`] keeps their thumbed-down tracks off the
+//! air and plays thumbed-up ones more often.
+
+use crate::error::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// Tracks rated at or below this are excluded from the rater's curations
+pub const EXCLUDE_AT_OR_BELOW: f64 = 1.0;
+
+/// Tracks rated at or above this are favored in the rater's curations
+pub const BOOST_AT_OR_ABOVE: f64 = 4.0;
+
+/// Similarity bonus per star above 3 when re-ranking audio-similarity results
+const SIMILARITY_BOOST_PER_STAR: f32 = 0.03;
+
+/// Listeners heard on a station this recently make up its audience
+const AUDIENCE_WINDOW_SECS: i64 = 120;
+
+/// Extra selection weight per net thumbs up of the audience (a track starts at 1)
+const WEIGHT_PER_THUMB_UP: f64 = 1.0;
+const MAX_WEIGHT: f64 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Thumb {
+    Up,
+    Down,
+}
+
+impl Thumb {
+    fn value(self) -> i16 {
+        match self {
+            Thumb::Up => 1,
+            Thumb::Down => -1,
+        }
+    }
+}
+
+/// Save (or replace) a user's rating for a track
+pub async fn rate(db: &PgPool, user_id: Uuid, track_id: &str, rating: f64) -> Result<()> {
+    if !(0.0..=5.0).contains(&rating) {
+        return Err(AppError::Validation(
+            "Rating must be between 0.0 and 5.0".to_string(),
+        ));
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO user_track_ratings (user_id, track_id, rating)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (user_id, track_id) DO UPDATE SET rating = EXCLUDED.rating, updated_at = NOW()
+        "#,
+    )
+    .bind(user_id)
+    .bind(track_id)
+    .bind(rating)
+    .execute(db)
+    .await
+    .map_err(|e| match &e {
+        // Foreign key violation: unknown track
+        sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23503") => {
+            AppError::NotFound("Track not found".to_string())
+        }
+        _ => AppError::Database(e),
+    })?;
+
+    Ok(())
+}
+
+/// Remove a user's rating for a track. Returns false if there was none.
+pub async fn remove(db: &PgPool, user_id: Uuid, track_id: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM user_track_ratings WHERE user_id = $1 AND track_id = $2")
+        .bind(user_id)
+        .bind(track_id)
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// A user's own rating for a track, if any
+pub async fn user_rating(db: &PgPool, user_id: Uuid, track_id: &str) -> Result<Option<f64>> {
+    let rating = sqlx::query_scalar("SELECT rating FROM user_track_ratings WHERE user_id = $1 AND track_id = $2")
+        .bind(user_id)
+        .bind(track_id)
+        .fetch_optional(db)
+        .await?;
+
+    Ok(rating)
+}
+
+/// Save (or replace) a user's thumbs up/down for a track, given on `station_id` if
+/// they were listening to one
+pub async fn set_thumb(
+    db: &PgPool,
+    user_id: Uuid,
+    track_id: &str,
+    thumb: Thumb,
+    station_id: Option<Uuid>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO user_track_feedback (user_id, track_id, value, station_id)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (user_id, track_id) DO UPDATE
+        SET value = EXCLUDED.value, station_id = EXCLUDED.station_id, updated_at = NOW()
+        "#,
+    )
+    .bind(user_id)
+    .bind(track_id)
+    .bind(thumb.value())
+    .bind(station_id)
+    .execute(db)
+    .await
+    .map_err(|e| match &e {
+        // Foreign key violation: unknown track or station
+        sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23503") => {
+            AppError::NotFound("Track or station not found".to_string())
+        }
+        _ => AppError::Database(e),
+    })?;
+
+    Ok(())
+}
+
+/// Remove a user's thumbs up/down for a track. Returns false if there was none.
+pub async fn remove_thumb(db: &PgPool, user_id: Uuid, track_id: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM user_track_feedback WHERE user_id = $1 AND track_id = $2")
+        .bind(user_id)
+        .bind(track_id)
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// A user's own thumbs up/down for a track, if any
+pub async fn user_thumb(db: &PgPool, user_id: Uuid, track_id: &str) -> Result<Option<Thumb>> {
+    let value: Option<i16> =
+        sqlx::query_scalar("SELECT value FROM user_track_feedback WHERE user_id = $1 AND track_id = $2")
+            .bind(user_id)
+            .bind(track_id)
+            .fetch_optional(db)
+            .await?;
+
+    Ok(value.map(|v| if v > 0 { Thumb::Up } else { Thumb::Down }))
+}
+
+/// One user's ratings, used to personalize a curation run
+#[derive(Debug, Clone, Default)]
+pub struct RatingBias {
+    ratings: HashMap<String, f64>,
+    /// Tracks excluded whatever their rating
+    blocked: HashSet<String>,
+}
+
+impl RatingBias {
+    /// Load the ratings of the user a curation runs for (no bias without a user)
+    pub async fn load(db: &PgPool, user_id: Option<Uuid>) -> Result<Self> {
+        let Some(user_id) = user_id else {
+            return Ok(Self::default());
+        };
+
+        // Thumbs count as 5 or 0 stars on tracks without a star rating
+        let rows: Vec<(String, f64)> = sqlx::query_as(
+            r#"
+            SELECT track_id, rating FROM user_track_ratings
+            WHERE user_id = $1 AND (rating <= $2 OR rating >= $3)
+            UNION ALL
+            SELECT f.track_id, CASE WHEN f.value > 0 THEN 5.0 ELSE 0.0 END
+            FROM user_track_feedback f
+            WHERE f.user_id = $1
+              AND NOT EXISTS(SELECT 1 FROM user_track_ratings r WHERE r.user_id = $1 AND r.track_id = f.track_id)
+            "#,
+        )
+        .bind(user_id)
+        .bind(EXCLUDE_AT_OR_BELOW)
+        .bind(BOOST_AT_OR_ABOVE)
+        .fetch_all(db)
+        .await?;
+
+        Ok(Self {
+            ratings: rows.into_iter().collect(),
+            blocked: HashSet::new(),
+        })
+    }
+
+    /// Also exclude tracks marked explicit, unless `allow_explicit`
+    pub async fn with_explicit(mut self, db: &PgPool, allow_explicit: bool) -> Result<Self> {
+        if !allow_explicit {
+            let explicit: Vec<String> = sqlx::query_scalar("SELECT id FROM library_index WHERE explicit")
+                .fetch_all(db)
+                .await?;
+            self.blocked.extend(explicit);
+        }
+        Ok(self)
+    }
+
+    pub fn is_excluded(&self, track_id: &str) -> bool {
+        self.blocked.contains(track_id) || self.ratings.get(track_id).is_some_and(|r| *r <= EXCLUDE_AT_OR_BELOW)
+    }
+
+    /// The user's rating if it is high enough to favor the track
+    pub fn boost(&self, track_id: &str) -> Option<f64> {
+        self.ratings.get(track_id).copied().filter(|r| *r >= BOOST_AT_OR_ABOVE)
+    }
+
+    pub fn excluded_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.blocked.iter().cloned().collect();
+        ids.extend(
+            self.ratings
+                .iter()
+                .filter(|(id, r)| **r <= EXCLUDE_AT_OR_BELOW && !self.blocked.contains(*id))
+                .map(|(id, _)| id.clone()),
+        );
+        ids
+    }
+
+    /// Tracks the user favors, whose embedding neighborhoods similarity search boosts
+    pub fn liked_ids(&self) -> Vec<String> {
+        self.ratings
+            .iter()
+            .filter(|(_, r)| **r >= BOOST_AT_OR_ABOVE)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Drop excluded tracks, keeping order
+    pub fn filter(&self, track_ids: Vec<String>) -> Vec<String> {
+        track_ids.into_iter().filter(|id| !self.is_excluded(id)).collect()
+    }
+
+    /// Re-rank similarity results so highly rated tracks move up
+    pub fn rerank(&self, mut scored: Vec<(String, f32)>) -> Vec<(String, f32)> {
+        if self.ratings.is_empty() && self.blocked.is_empty() {
+            return scored;
+        }
+
+        scored.retain(|(id, _)| !self.is_excluded(id));
+        for (id, score) in scored.iter_mut() {
+            if let Some(rating) = self.boost(id) {
+                *score += (rating as f32 - 3.0) * SIMILARITY_BOOST_PER_STAR;
+            }
+        }
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        scored
+    }
+}
+
+/// Net thumbs (ups minus downs) of the signed-in listeners currently tuned in to a
+/// station, steering what it plays next
+#[derive(Debug, Clone, Default)]
+pub struct AudienceFeedback {
+    scores: HashMap<String, i64>,
+}
+
+impl AudienceFeedback {
+    pub async fn load(db: &PgPool, station_id: Uuid) -> Result<Self> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            r#"
+            SELECT f.track_id, SUM(f.value)::bigint
+            FROM user_track_feedback f
+            WHERE f.user_id IN (
+                SELECT DISTINCT user_id FROM listening_history
+                WHERE station_id = $1 AND last_heard_at > NOW() - make_interval(secs => $2)
+            )
+            GROUP BY f.track_id
+            "#,
+        )
+        .bind(station_id)
+        .bind(AUDIENCE_WINDOW_SECS as f64)
+        .fetch_all(db)
+        .await?;
+
+        Ok(Self {
+            scores: rows.into_iter().filter(|(_, score)| *score != 0).collect(),
+        })
+    }
+
+    /// More of the audience thumbed the track down than up
+    pub fn is_excluded(&self, track_id: &str) -> bool {
+        self.scores.get(track_id).is_some_and(|score| *score < 0)
+    }
+
+    /// Relative chance of picking the track among candidates
+    pub fn weight(&self, track_id: &str) -> f64 {
+        let ups = self.scores.get(track_id).copied().unwrap_or(0).max(0);
+        (1.0 + ups as f64 * WEIGHT_PER_THUMB_UP).min(MAX_WEIGHT)
+    }
+
+    /// Drop excluded candidates, unless that would leave none
+    pub fn filter<T>(&self, candidates: Vec<T>, id: impl Fn(&T) -> &str) -> Vec<T> {
+        if candidates.iter().all(|c| self.is_excluded(id(c))) {
+            return candidates;
+        }
+        candidates.into_iter().filter(|c| !self.is_excluded(id(c))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bias(ratings: &[(&str, f64)]) -> RatingBias {
+        RatingBias {
+            ratings: ratings.iter().map(|(id, r)| (id.to_string(), *r)).collect(),
+            blocked: HashSet::new(),
+        }
+    }
+
+    #[test]
+    fn test_blocked_tracks_are_excluded_whatever_their_rating() {
+        let mut bias = bias(&[("explicit", 5.0), ("hated", 0.0)]);
+        bias.blocked.insert("explicit".to_string());
+        assert!(bias.is_excluded("explicit"));
+        assert_eq!(bias.filter(vec!["explicit".to_string(), "a".to_string()]), vec!["a".to_string()]);
+        let mut excluded = bias.excluded_ids();
+        excluded.sort();
+        assert_eq!(excluded, vec!["explicit".to_string(), "hated".to_string()]);
+    }
+
+    #[test]
+    fn test_rerank_drops_excluded_and_boosts_liked() {
+        let bias = bias(&[("hated", 1.0), ("loved", 5.0)]);
+        let scored = vec![
+            ("a".to_string(), 0.90),
+            ("hated".to_string(), 0.95),
+            ("loved".to_string(), 0.85),
+        ];
+
+        let ids: Vec<String> = bias.rerank(scored).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec!["loved", "a"]);
+    }
+
+    #[test]
+    fn test_audience_feedback() {
+        let feedback = AudienceFeedback {
+            scores: [("booed".to_string(), -2), ("cheered".to_string(), 2), ("loved".to_string(), 9)]
+                .into_iter()
+                .collect(),
+        };
+        assert!(feedback.is_excluded("booed"));
+        assert_eq!(feedback.weight("booed"), 1.0);
+        assert_eq!(feedback.weight("other"), 1.0);
+        assert_eq!(feedback.weight("cheered"), 3.0);
+        assert_eq!(feedback.weight("loved"), MAX_WEIGHT);
+
+        let kept = feedback.filter(vec!["booed", "other"], |id| *id);
+        assert_eq!(kept, vec!["other"]);
+        let all_booed = feedback.filter(vec!["booed"], |id| *id);
+        assert_eq!(all_booed, vec!["booed"]);
+    }
+}
@@ -0,0 +1,161 @@
+//! Station program schedules
+//!
+//! A station's schedule is a set of non-overlapping program blocks (see
+//! [`ScheduleBlock`]). While a block is on air, the station selects tracks from the
+//! block's pool or genres instead of its own; outside of blocks it plays as usual.
+//! Blocks with a curation prompt have their pool curated shortly before each airing
+//! (see [`crate::services::program_curator`]).
+
+use crate::error::{AppError, Result};
+use crate::models::{ScheduleBlock, ScheduleBlockRequest, Station};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+/// A station's blocks, in the order they start in the week
+pub async fn list(db: &PgPool, station_id: Uuid) -> Result<Vec<ScheduleBlock>> {
+    let blocks = sqlx::query_as::<_, ScheduleBlock>(
+        "SELECT * FROM station_schedule_blocks WHERE station_id = $1 ORDER BY days, start_time",
+    )
+    .bind(station_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(blocks)
+}
+
+pub async fn create(db: &PgPool, station_id: Uuid, req: &ScheduleBlockRequest) -> Result<ScheduleBlock> {
+    check(db, station_id, None, req).await?;
+
+    let block = sqlx::query_as::<_, ScheduleBlock>(
+        r#"
+        INSERT INTO station_schedule_blocks
+            (station_id, name, days, start_time, end_time, genres, track_ids, curation_prompt)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING *
+        "#,
+    )
+    .bind(station_id)
+    .bind(&req.name)
+    .bind(&req.days)
+    .bind(req.start_time)
+    .bind(req.end_time)
+    .bind(serde_json::to_value(&req.genres)?)
+    .bind(serde_json::to_value(&req.track_ids)?)
+    .bind(req.prompt())
+    .fetch_one(db)
+    .await?;
+
+    Ok(block)
+}
+
+pub async fn update(
+    db: &PgPool,
+    station_id: Uuid,
+    block_id: Uuid,
+    req: &ScheduleBlockRequest,
+) -> Result<ScheduleBlock> {
+    check(db, station_id, Some(block_id), req).await?;
+
+    sqlx::query_as::<_, ScheduleBlock>(
+        r#"
+        UPDATE station_schedule_blocks
+        SET name = $3, days = $4, start_time = $5, end_time = $6, genres = $7, track_ids = $8,
+            -- A new prompt is curated again right away
+            curated_at = CASE WHEN curation_prompt IS DISTINCT FROM $9 THEN NULL ELSE curated_at END,
+            curation_prompt = $9,
+            updated_at = NOW()
+        WHERE id = $1 AND station_id = $2
+        RETURNING *
+        "#,
+    )
+    .bind(block_id)
+    .bind(station_id)
+    .bind(&req.name)
+    .bind(&req.days)
+    .bind(req.start_time)
+    .bind(req.end_time)
+    .bind(serde_json::to_value(&req.genres)?)
+    .bind(serde_json::to_value(&req.track_ids)?)
+    .bind(req.prompt())
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Schedule block not found".to_string()))
+}
+
+/// Returns false if the station has no such block
+pub async fn delete(db: &PgPool, station_id: Uuid, block_id: Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM station_schedule_blocks WHERE id = $1 AND station_id = $2")
+        .bind(block_id)
+        .bind(station_id)
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// The block on air on a station at `now`, if any
+pub async fn active_block(db: &PgPool, station: &Station, now: DateTime<Utc>) -> Result<Option<ScheduleBlock>> {
+    let local = now.with_timezone(&station.config.tz()).naive_local();
+    Ok(list(db, station.id)
+        .await?
+        .into_iter()
+        .find(|block| block.is_active(local)))
+}
+
+/// Blocks of active stations whose pool should be curated from their prompt now,
+/// with their stations
+pub async fn due_for_curation(db: &PgPool, now: DateTime<Utc>) -> Result<Vec<(Station, ScheduleBlock)>> {
+    let blocks = sqlx::query_as::<_, ScheduleBlock>(
+        r#"
+        SELECT b.* FROM station_schedule_blocks b
+        JOIN stations s ON s.id = b.station_id
+        WHERE b.curation_prompt IS NOT NULL AND s.active
+        ORDER BY b.curated_at NULLS FIRST
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut due = Vec::new();
+    for block in blocks {
+        let station = sqlx::query_as::<_, Station>("SELECT * FROM stations WHERE id = $1")
+            .bind(block.station_id)
+            .fetch_one(db)
+            .await?;
+        let local = now.with_timezone(&station.config.tz()).naive_local();
+        if block.needs_curation(local, now) {
+            due.push((station, block));
+        }
+    }
+    Ok(due)
+}
+
+/// Replace a block's pool with freshly curated tracks
+pub async fn store_curated(db: &PgPool, block_id: Uuid, track_ids: &[String]) -> Result<()> {
+    sqlx::query("UPDATE station_schedule_blocks SET track_ids = $2, curated_at = NOW() WHERE id = $1")
+        .bind(block_id)
+        .bind(serde_json::to_value(track_ids)?)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Reject invalid blocks and blocks overlapping the station's others (except
+/// `replacing`, the block being updated)
+async fn check(db: &PgPool, station_id: Uuid, replacing: Option<Uuid>, req: &ScheduleBlockRequest) -> Result<()> {
+    req.validate()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+    req.check().map_err(AppError::Validation)?;
+
+    let clash = list(db, station_id)
+        .await?
+        .into_iter()
+        .filter(|block| Some(block.id) != replacing)
+        .find(|block| req.overlaps(block));
+    if let Some(block) = clash {
+        return Err(AppError::Conflict(format!("Overlaps the program block '{}'", block.name)));
+    }
+    Ok(())
+}
@@ -0,0 +1,211 @@
+//! Listens waiting to be submitted to a scrobble service
+//!
+//! Each service (ListenBrainz, Last.fm) queues its own payloads in `scrobble_queue`,
+//! one row per listen and account (`user_id` NULL for the server's account), and
+//! submits them in batches. A batch is claimed before it is sent, so several nodes can
+//! flush the same queue. Failed batches are retried with backoff and dropped after
+//! `MAX_ATTEMPTS`.
+
+use crate::error::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Tracks shorter than this are never submitted
+const MIN_TRACK_SECS: i64 = 30;
+
+/// Listening time after which any track counts, however long it is
+const MAX_REQUIRED_SECS: i64 = 240;
+
+/// Submission attempts before a listen is dropped
+pub const MAX_ATTEMPTS: i32 = 10;
+
+/// Delay before the first retry (doubles each retry, up to `MAX_RETRY_DELAY_SECS`)
+const RETRY_BASE_DELAY_SECS: i64 = 60;
+const MAX_RETRY_DELAY_SECS: i64 = 6 * 3600;
+
+/// Claimed listens are hidden from other flushers for this long
+const CLAIM_SECS: i64 = 300;
+
+/// Why a submission failed
+pub enum SubmitError {
+    /// The service refused the listens themselves; sending them again won't help
+    Rejected(String),
+    /// Network errors, rate limits, server errors and bad credentials, worth retrying
+    Failed(String),
+}
+
+/// Seconds a track of `duration_secs` must be heard to count as a listen, or None if it
+/// is too short to submit at all. ListenBrainz and Last.fm both count a track heard for
+/// half its length or four minutes, whichever is shorter.
+pub fn required_listen_secs(duration_secs: i64) -> Option<i64> {
+    (duration_secs >= MIN_TRACK_SECS).then(|| (duration_secs / 2).min(MAX_REQUIRED_SECS))
+}
+
+/// Delay before retrying a listen that has failed `attempts` times
+fn retry_delay_secs(attempts: i32) -> i64 {
+    let exponent = attempts.clamp(1, 16) as u32 - 1;
+    (RETRY_BASE_DELAY_SECS << exponent).min(MAX_RETRY_DELAY_SECS)
+}
+
+#[derive(Clone)]
+pub struct ScrobbleQueue {
+    db: PgPool,
+    service: &'static str,
+}
+
+impl ScrobbleQueue {
+    pub fn new(db: PgPool, service: &'static str) -> Self {
+        Self { db, service }
+    }
+
+    /// Queue a listen for one account
+    pub async fn push<T: Serialize>(&self, user_id: Option<Uuid>, station_id: Uuid, listen: &T) -> Result<()> {
+        sqlx::query("INSERT INTO scrobble_queue (service, user_id, station_id, listen) VALUES ($1, $2, $3, $4)")
+            .bind(self.service)
+            .bind(user_id)
+            .bind(station_id)
+            .bind(serde_json::to_value(listen)?)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Accounts that have listens due for submission
+    pub async fn due_accounts(&self) -> Result<Vec<Option<Uuid>>> {
+        let accounts = sqlx::query_scalar(
+            "SELECT DISTINCT user_id FROM scrobble_queue WHERE service = $1 AND next_attempt_at <= NOW()",
+        )
+        .bind(self.service)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(accounts)
+    }
+
+    /// Take up to `limit` of an account's oldest due listens, hiding them from other
+    /// flushers until they are completed or rescheduled
+    pub async fn claim<T: DeserializeOwned + Send + Unpin + 'static>(
+        &self,
+        user_id: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<(i64, T)>> {
+        let batch: Vec<(i64, sqlx::types::Json<T>)> = sqlx::query_as(
+            r#"
+            UPDATE scrobble_queue
+            SET next_attempt_at = NOW() + make_interval(secs => $4)
+            WHERE id IN (
+                SELECT id FROM scrobble_queue
+                WHERE service = $1 AND user_id IS NOT DISTINCT FROM $2 AND next_attempt_at <= NOW()
+                ORDER BY id
+                LIMIT $3
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, listen
+            "#,
+        )
+        .bind(self.service)
+        .bind(user_id)
+        .bind(limit)
+        .bind(CLAIM_SECS as f64)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(batch.into_iter().map(|(id, listen)| (id, listen.0)).collect())
+    }
+
+    /// Handle the outcome of submitting claimed listens. Returns false if they are to be
+    /// retried later, in which case the account's other listens should wait too.
+    pub async fn finish(&self, ids: &[i64], outcome: std::result::Result<(), SubmitError>) -> Result<bool> {
+        match outcome {
+            Ok(()) => self.remove(ids).await?,
+            Err(SubmitError::Rejected(e)) => {
+                tracing::warn!("{} rejected {} listen(s), dropping them: {}", self.service, ids.len(), e);
+                self.remove(ids).await?
+            }
+            Err(SubmitError::Failed(e)) => {
+                self.reschedule(ids, &e).await?;
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Drop every queued listen of an account (e.g. after it was disconnected)
+    pub async fn discard_account(&self, user_id: Option<Uuid>) -> Result<()> {
+        sqlx::query("DELETE FROM scrobble_queue WHERE service = $1 AND user_id IS NOT DISTINCT FROM $2")
+            .bind(self.service)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove(&self, ids: &[i64]) -> Result<()> {
+        sqlx::query("DELETE FROM scrobble_queue WHERE id = ANY($1)")
+            .bind(ids)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Record a failed attempt: retry later, or drop listens that have run out of attempts
+    async fn reschedule(&self, ids: &[i64], error: &str) -> Result<()> {
+        let attempts: Vec<i32> = sqlx::query_scalar(
+            "UPDATE scrobble_queue SET attempts = attempts + 1, last_error = $2 WHERE id = ANY($1) RETURNING attempts",
+        )
+        .bind(ids)
+        .bind(error)
+        .fetch_all(&self.db)
+        .await?;
+
+        let attempts = attempts.into_iter().max().unwrap_or(1);
+        sqlx::query(
+            "UPDATE scrobble_queue SET next_attempt_at = NOW() + make_interval(secs => $2) WHERE id = ANY($1)",
+        )
+        .bind(ids)
+        .bind(retry_delay_secs(attempts) as f64)
+        .execute(&self.db)
+        .await?;
+
+        let dropped = sqlx::query("DELETE FROM scrobble_queue WHERE id = ANY($1) AND attempts >= $2")
+            .bind(ids)
+            .bind(MAX_ATTEMPTS)
+            .execute(&self.db)
+            .await?
+            .rows_affected();
+
+        if dropped > 0 {
+            tracing::warn!(
+                "Dropped {} {} listen(s) after {} failed submissions: {}",
+                dropped, self.service, MAX_ATTEMPTS, error
+            );
+        } else {
+            tracing::info!(
+                "{} submission failed, retrying {} listen(s) later: {}",
+                self.service, ids.len(), error
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_listen_secs() {
+        assert_eq!(required_listen_secs(20), None);
+        assert_eq!(required_listen_secs(30), Some(15));
+        assert_eq!(required_listen_secs(200), Some(100));
+        assert_eq!(required_listen_secs(1200), Some(MAX_REQUIRED_SECS));
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay_secs(1), RETRY_BASE_DELAY_SECS);
+        assert_eq!(retry_delay_secs(3), RETRY_BASE_DELAY_SECS * 4);
+        assert_eq!(retry_delay_secs(MAX_ATTEMPTS), MAX_RETRY_DELAY_SECS);
+    }
+}
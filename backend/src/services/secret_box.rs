@@ -0,0 +1,75 @@
+//! Encryption of secrets stored in the database
+//!
+//! Used for users' Navidrome passwords and API keys, which have to be recoverable to
+//! talk to Navidrome on the user's behalf (so they can't be hashed). Values are sealed
+//! with AES-256-GCM under a key derived from `CREDENTIALS_SECRET`; the nonce is stored
+//! in front of the ciphertext. Changing the secret makes stored values unreadable.
+
+use crate::error::{AppError, Result};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use sha2::{Digest, Sha256};
+
+/// Length of the random nonce prefixed to each sealed value
+const NONCE_LEN: usize = 12;
+
+#[derive(Clone)]
+pub struct SecretBox {
+    cipher: Aes256Gcm,
+}
+
+impl SecretBox {
+    pub fn new(secret: &str) -> Self {
+        let key = Sha256::digest(secret.as_bytes());
+        Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+        }
+    }
+
+    /// Encrypt `plaintext`. `context` (e.g. the owner's ID) must be passed again to open
+    /// it, so a sealed value can't be copied to another row.
+    pub fn seal(&self, plaintext: &str, context: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, Payload { msg: plaintext.as_bytes(), aad: context })
+            .map_err(|_| AppError::Internal(anyhow::anyhow!("Failed to encrypt secret")))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    pub fn open(&self, sealed: &[u8], context: &[u8]) -> Result<String> {
+        if sealed.len() < NONCE_LEN {
+            return Err(AppError::Internal(anyhow::anyhow!("Stored secret is truncated")));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: context })
+            .map_err(|_| {
+                AppError::Internal(anyhow::anyhow!(
+                    "Failed to decrypt stored secret (was CREDENTIALS_SECRET changed?)"
+                ))
+            })?;
+
+        String::from_utf8(plaintext)
+            .map_err(|_| AppError::Internal(anyhow::anyhow!("Stored secret is not valid UTF-8")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_open_round_trip_and_context_binding() {
+        let secret_box = SecretBox::new("a-secret-that-is-long-enough-for-tests");
+        let sealed = secret_box.seal("hunter2", b"user-1").unwrap();
+
+        assert_eq!(secret_box.open(&sealed, b"user-1").unwrap(), "hunter2");
+        assert!(secret_box.open(&sealed, b"user-2").is_err());
+        assert!(SecretBox::new("another-secret").open(&sealed, b"user-1").is_err());
+    }
+}
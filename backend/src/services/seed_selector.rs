@@ -13,13 +13,15 @@
 #![allow(dead_code)]
 
 use crate::error::{AppError, Result};
+use crate::services::llm::{LlmClient, LlmFeature, LlmRequest};
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, PgPool};
+use std::sync::Arc;
 use tracing::{debug, info, warn};
 
 /// Simplified track info for seed selection (avoids needing all LibraryTrack fields)
 #[derive(Debug, Clone, FromRow)]
-struct SeedTrackInfo {
+pub struct SeedTrackInfo {
     pub id: String,
     pub title: String,
     pub artist: String,
@@ -86,16 +88,14 @@ struct GenreSelectionResponse {
 }
 
 pub struct SeedSelector {
-    anthropic_api_key: String,
-    client: reqwest::Client,
+    llm: Arc<LlmClient>,
     db: PgPool,
 }
 
 impl SeedSelector {
-    pub fn new(anthropic_api_key: String, db: PgPool) -> Self {
+    pub fn new(llm: Arc<LlmClient>, db: PgPool) -> Self {
         Self {
-            anthropic_api_key,
-            client: reqwest::Client::new(),
+            llm,
             db,
         }
     }
@@ -210,7 +210,7 @@ impl SeedSelector {
 
         for ideal in ideal_songs {
             // Try exact match first
-            if let Some(track) = self.find_exact_match(&ideal.title, &ideal.artist).await? {
+            if let Some(track) = find_exact_match(&self.db, &ideal.title, &ideal.artist).await? {
                 verified.push(VerifiedSeed {
                     track_id: track.id.clone(),
                     title: track.title.clone(),
@@ -226,7 +226,7 @@ impl SeedSelector {
             }
 
             // Try fuzzy match
-            if let Some(track) = self.find_fuzzy_match(&ideal.title, &ideal.artist).await? {
+            if let Some(track) = find_fuzzy_match(&self.db, &ideal.title, &ideal.artist).await? {
                 verified.push(VerifiedSeed {
                     track_id: track.id.clone(),
                     title: track.title.clone(),
@@ -272,7 +272,7 @@ Respond with ONLY a JSON object:
             query, count
         );
 
-        let response: IdealSongsResponse = self.call_claude(&prompt).await?;
+        let response: IdealSongsResponse = self.call_claude(prompt).await?;
         Ok(response.songs)
     }
 
@@ -380,7 +380,7 @@ Respond with ONLY a JSON object:
             query, track_list, count, query
         );
 
-        let response: LibraryPicksResponse = self.call_claude(&prompt).await?;
+        let response: LibraryPicksResponse = self.call_claude(prompt).await?;
 
         // Convert IDs to VerifiedSeeds
         let mut seeds = Vec::new();
@@ -443,7 +443,7 @@ Respond with ONLY a JSON object:
             query, genre_list, query
         );
 
-        let response: GenreSelectionResponse = self.call_claude(&prompt).await?;
+        let response: GenreSelectionResponse = self.call_claude(prompt).await?;
 
         // Validate that returned genres actually exist in the library
         let valid_genres: Vec<String> = response
@@ -494,52 +494,6 @@ Respond with ONLY a JSON object:
         Ok(tracks)
     }
 
-    /// Find exact title + artist match in library
-    async fn find_exact_match(&self, title: &str, artist: &str) -> Result<Option<SeedTrackInfo>> {
-        // Use runtime query with ::text cast to avoid pgvector binary protocol issues
-        let track = sqlx::query_as::<_, SeedTrackInfo>(
-            r#"
-            SELECT
-                id, title, artist,
-                genres::text as genres
-            FROM library_index
-            WHERE LOWER(title) = LOWER($1)
-            AND (LOWER(artist) = LOWER($2) OR LOWER(artist) LIKE LOWER($3))
-            LIMIT 1
-            "#,
-        )
-        .bind(title)
-        .bind(artist)
-        .bind(format!("%{}%", artist))
-        .fetch_optional(&self.db)
-        .await?;
-
-        Ok(track)
-    }
-
-    /// Find fuzzy match using trigram similarity
-    async fn find_fuzzy_match(&self, title: &str, artist: &str) -> Result<Option<SeedTrackInfo>> {
-        // Use runtime query with ::text cast to avoid pgvector binary protocol issues
-        let track = sqlx::query_as::<_, SeedTrackInfo>(
-            r#"
-            SELECT
-                id, title, artist,
-                genres::text as genres
-            FROM library_index
-            WHERE similarity(title, $1) > 0.4
-            AND similarity(artist, $2) > 0.4
-            ORDER BY similarity(title, $1) + similarity(artist, $2) DESC
-            LIMIT 1
-            "#,
-        )
-        .bind(title)
-        .bind(artist)
-        .fetch_optional(&self.db)
-        .await?;
-
-        Ok(track)
-    }
-
     /// Get a representative sample of tracks from the library
     async fn get_library_sample(
         &self,
@@ -567,45 +521,15 @@ Respond with ONLY a JSON object:
     }
 
     /// Call Claude API
-    async fn call_claude<T: serde::de::DeserializeOwned>(&self, prompt: &str) -> Result<T> {
-        let response = self
-            .client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", &self.anthropic_api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("content-type", "application/json")
-            .json(&serde_json::json!({
-                "model": "claude-sonnet-4-5-20250929",
-                "max_tokens": 4096,
-                "messages": [{
-                    "role": "user",
-                    "content": prompt
-                }]
-            }))
-            .send()
-            .await
-            .map_err(|e| AppError::ExternalApi(format!("Claude API call failed: {}", e)))?;
-
-        let status = response.status();
-        if !status.is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(AppError::ExternalApi(format!(
-                "Claude API error {}: {}",
-                status, error_text
-            )));
-        }
-
-        let response_json: serde_json::Value = response.json().await.map_err(|e| {
-            AppError::ExternalApi(format!("Failed to parse Claude response: {}", e))
-        })?;
-
-        let content_text = response_json["content"][0]["text"]
-            .as_str()
-            .ok_or_else(|| AppError::ExternalApi("Invalid Claude response format".to_string()))?;
+    async fn call_claude<T: serde::de::DeserializeOwned>(&self, prompt: String) -> Result<T> {
+        let content_text = self
+            .llm
+            .complete(LlmRequest::new(LlmFeature::SeedSelection, crate::services::ai_curator::CLAUDE_MODEL, prompt))
+            .await?;
 
         // Extract the first complete JSON object from the response
         // This handles cases where Claude adds commentary after the JSON
-        let json_text = Self::extract_first_json_object(content_text)
+        let json_text = Self::extract_first_json_object(&content_text)
             .ok_or_else(|| AppError::ExternalApi(format!(
                 "No valid JSON object found in response: {}",
                 &content_text[..content_text.len().min(500)]
@@ -686,3 +610,49 @@ Respond with ONLY a JSON object:
         None
     }
 }
+
+/// Find exact title + artist match in library (also used by playlist imports)
+pub async fn find_exact_match(db: &PgPool, title: &str, artist: &str) -> Result<Option<SeedTrackInfo>> {
+    // Use runtime query with ::text cast to avoid pgvector binary protocol issues
+    let track = sqlx::query_as::<_, SeedTrackInfo>(
+        r#"
+        SELECT
+            id, title, artist,
+            genres::text as genres
+        FROM library_index
+        WHERE LOWER(title) = LOWER($1)
+        AND (LOWER(artist) = LOWER($2) OR LOWER(artist) LIKE LOWER($3))
+        LIMIT 1
+        "#,
+    )
+    .bind(title)
+    .bind(artist)
+    .bind(format!("%{}%", artist))
+    .fetch_optional(db)
+    .await?;
+
+    Ok(track)
+}
+
+/// Find fuzzy match using trigram similarity (also used by playlist imports)
+pub async fn find_fuzzy_match(db: &PgPool, title: &str, artist: &str) -> Result<Option<SeedTrackInfo>> {
+    // Use runtime query with ::text cast to avoid pgvector binary protocol issues
+    let track = sqlx::query_as::<_, SeedTrackInfo>(
+        r#"
+        SELECT
+            id, title, artist,
+            genres::text as genres
+        FROM library_index
+        WHERE similarity(title, $1) > 0.4
+        AND similarity(artist, $2) > 0.4
+        ORDER BY similarity(title, $1) + similarity(artist, $2) DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(title)
+    .bind(artist)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(track)
+}
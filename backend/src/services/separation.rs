@@ -0,0 +1,251 @@
+//! Track, artist and album separation
+//!
+//! Keeps a track, its artist and its album from coming around again too soon. A
+//! candidate is spaced when none of the last `tracks` plays was the same track, none of
+//! the last `artists` the same artist and none of the last `albums` the same album.
+//! Artists and albums are compared by name, ignoring case; tracks that aren't in the
+//! library index only have the track rule applied.
+//!
+//! When no candidate is spaced, callers relax the rules rather than play nothing, so a
+//! single-artist station keeps playing.
+
+use crate::error::Result;
+use crate::models::{StationConfig, Track};
+use sqlx::{FromRow, PgPool};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeparationRules {
+    /// Plays before a track can repeat
+    pub tracks: usize,
+    /// Plays between two tracks by the same artist
+    pub artists: usize,
+    /// Plays between two tracks from the same album
+    pub albums: usize,
+}
+
+impl SeparationRules {
+    pub fn for_station(config: &StationConfig) -> Self {
+        Self {
+            tracks: config.track_separation as usize,
+            artists: config.artist_separation as usize,
+            albums: config.album_separation as usize,
+        }
+    }
+
+    /// Plays that have to be remembered to apply the rules
+    pub fn lookback(&self) -> usize {
+        self.tracks.max(self.artists).max(self.albums)
+    }
+
+    /// Whether `candidate` may follow `recent` (most recent first)
+    pub fn allows<'a>(&self, recent: impl IntoIterator<Item = &'a Play>, candidate: &Play) -> bool {
+        recent.into_iter().take(self.lookback()).enumerate().all(|(i, play)| {
+            !(i < self.tracks && play.track_id == candidate.track_id
+                || i < self.artists && same_name(&play.artist, &candidate.artist)
+                || i < self.albums && same_name(&play.album, &candidate.album))
+        })
+    }
+}
+
+impl Default for SeparationRules {
+    fn default() -> Self {
+        Self::for_station(&StationConfig::default())
+    }
+}
+
+/// A track as far as separation goes
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct Play {
+    pub track_id: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+impl Play {
+    /// A track whose artist and album aren't known
+    pub fn unknown(track_id: &str) -> Self {
+        Self {
+            track_id: track_id.to_string(),
+            artist: None,
+            album: None,
+        }
+    }
+}
+
+impl From<&Track> for Play {
+    fn from(track: &Track) -> Self {
+        Self {
+            track_id: track.id.clone(),
+            artist: Some(track.artist.clone()),
+            album: Some(track.album.clone()),
+        }
+    }
+}
+
+/// A station's recent plays, most recent first
+#[derive(Debug, Clone, Default)]
+pub struct PlayHistory {
+    plays: Vec<Play>,
+}
+
+impl PlayHistory {
+    #[cfg(test)]
+    pub fn new(plays: Vec<Play>) -> Self {
+        Self { plays }
+    }
+
+    /// The last `limit` plays of a station, with artists and albums from the library index
+    pub async fn load(db: &PgPool, station_id: Uuid, limit: usize) -> Result<Self> {
+        let plays = sqlx::query_as::<_, Play>(
+            "SELECT h.track_id, li.artist, li.album
+             FROM playlist_history h
+             LEFT JOIN library_index li ON li.id = h.track_id
+             WHERE h.station_id = $1
+             ORDER BY h.played_at DESC
+             LIMIT $2",
+        )
+        .bind(station_id)
+        .bind(limit as i64)
+        .fetch_all(db)
+        .await?;
+        Ok(Self { plays })
+    }
+
+    /// The track played last
+    pub fn last(&self) -> Option<&Play> {
+        self.plays.first()
+    }
+
+    /// Ids of the last `n` tracks, most recent first
+    pub fn recent_track_ids(&self, n: usize) -> Vec<String> {
+        self.plays.iter().take(n).map(|p| p.track_id.clone()).collect()
+    }
+
+    pub fn allows(&self, rules: &SeparationRules, candidate: &Play) -> bool {
+        rules.allows(&self.plays, candidate)
+    }
+}
+
+/// Artists and albums of library tracks, by id
+pub async fn credits(db: &PgPool, ids: &[String]) -> Result<HashMap<String, Play>> {
+    let plays = sqlx::query_as::<_, Play>(
+        "SELECT id AS track_id, artist, album FROM library_index WHERE id = ANY($1)",
+    )
+    .bind(ids)
+    .fetch_all(db)
+    .await?;
+    Ok(plays.into_iter().map(|p| (p.track_id.clone(), p)).collect())
+}
+
+/// Take the first track of `pool` that may follow `placed` (oldest first), or the
+/// first one at all when none may
+pub fn pick(pool: &mut Vec<Play>, placed: &[Play], rules: &SeparationRules) -> Option<Play> {
+    if pool.is_empty() {
+        return None;
+    }
+    let index = pool
+        .iter()
+        .position(|candidate| rules.allows(placed.iter().rev(), candidate))
+        .unwrap_or(0);
+    Some(pool.remove(index))
+}
+
+/// Take the highest-scoring track of `pool` that may follow `placed` (oldest first), or
+/// the highest-scoring one at all when none may. Ties go to the earlier track.
+pub fn pick_best(
+    pool: &mut Vec<Play>,
+    placed: &[Play],
+    rules: &SeparationRules,
+    score: impl Fn(&Play) -> f64,
+) -> Option<Play> {
+    let best = |spaced_only: bool| {
+        pool.iter()
+            .enumerate()
+            .filter(|(_, candidate)| !spaced_only || rules.allows(placed.iter().rev(), candidate))
+            .map(|(i, candidate)| (i, score(candidate)))
+            .fold(None, |best: Option<(usize, f64)>, (i, s)| match best {
+                Some((_, best_score)) if best_score >= s => best,
+                _ => Some((i, s)),
+            })
+    };
+    let (index, _) = best(true).or_else(|| best(false))?;
+    Some(pool.remove(index))
+}
+
+/// Names match ignoring case; unknown or blank names match nothing
+fn same_name(a: &Option<String>, b: &Option<String>) -> bool {
+    match (a.as_deref().map(str::trim), b.as_deref().map(str::trim)) {
+        (Some(a), Some(b)) => !a.is_empty() && a.eq_ignore_ascii_case(b),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(id: &str, artist: &str, album: &str) -> Play {
+        Play {
+            track_id: id.to_string(),
+            artist: Some(artist.to_string()),
+            album: Some(album.to_string()),
+        }
+    }
+
+    const RULES: SeparationRules = SeparationRules {
+        tracks: 20,
+        artists: 2,
+        albums: 3,
+    };
+
+    #[test]
+    fn test_artists_and_albums_are_kept_apart() {
+        let history = PlayHistory::new(vec![
+            play("3", "Low", "Things We Lost in the Fire"),
+            play("2", "Slowdive", "Souvlaki"),
+            play("1", "Cocteau Twins", "Heaven or Las Vegas"),
+        ]);
+
+        assert!(!history.allows(&RULES, &play("4", "LOW", "Double Negative")));
+        assert!(!history.allows(&RULES, &play("5", "Slowdive", "Pygmalion")));
+        // Two plays ago is outside the artist rule but inside the album rule
+        assert!(history.allows(&RULES, &play("6", "Cocteau Twins", "Treasure")));
+        assert!(!history.allows(&RULES, &play("7", "Cocteau Twins", "Heaven or Las Vegas")));
+        assert!(!history.allows(&RULES, &Play::unknown("1")));
+        assert!(history.allows(&RULES, &Play::unknown("8")));
+    }
+
+    #[test]
+    fn test_blank_names_match_nothing() {
+        let history = PlayHistory::new(vec![play("1", "", "")]);
+        assert!(history.allows(&RULES, &play("2", "", "")));
+    }
+
+    #[test]
+    fn test_pick_relaxes_when_nothing_is_spaced() {
+        let placed = vec![play("1", "Low", "Hey What")];
+        let mut pool = vec![
+            play("2", "Low", "Hey What"),
+            play("3", "Low", "Ones and Sixes"),
+            play("4", "Duster", "Stratosphere"),
+        ];
+
+        assert_eq!(pick(&mut pool, &placed, &RULES).unwrap().track_id, "4");
+        assert_eq!(pick(&mut pool, &placed, &RULES).unwrap().track_id, "2");
+        assert_eq!(pick(&mut pool, &placed, &RULES).unwrap().track_id, "3");
+        assert_eq!(pick(&mut pool, &placed, &RULES), None);
+    }
+
+    #[test]
+    fn test_pick_best_prefers_spaced_tracks() {
+        let placed = vec![play("1", "Low", "Hey What")];
+        let mut pool = vec![play("2", "Low", "C'mon"), play("3", "Duster", "Stratosphere")];
+        let score = |p: &Play| if p.track_id == "2" { 1.0 } else { 0.5 };
+
+        assert_eq!(pick_best(&mut pool, &placed, &RULES, score).unwrap().track_id, "3");
+        assert_eq!(pick_best(&mut pool, &placed, &RULES, score).unwrap().track_id, "2");
+        assert_eq!(pick_best(&mut pool, &placed, &RULES, score), None);
+    }
+}
@@ -0,0 +1,262 @@
+//! Server-side login sessions
+//!
+//! Every login (or registration) creates a row in `user_sessions` and the issued JWT
+//! carries its ID as `sid`. Token verification rejects tokens whose session was revoked
+//! or has expired, which lets users sign out other devices from `/me/sessions`.
+//!
+//! JWTs are short-lived. Each session also has a refresh token that
+//! `POST /auth/refresh` trades for a new JWT and a new refresh token, extending the
+//! session. A refresh token that was already rotated away is only ever presented again
+//! by someone who copied it, so its session is revoked.
+
+use crate::error::{AppError, Result};
+use crate::models::{AdminSession, DeviceInfo, UserSession};
+use chrono::{DateTime, Duration, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// How long a session stays valid without being refreshed
+pub const SESSION_TTL_DAYS: i64 = 7;
+
+/// Prefix identifying refresh tokens
+pub const REFRESH_TOKEN_PREFIX: &str = "nrr_";
+
+/// A replaced refresh token presented this soon after the rotation is taken to be a
+/// concurrent refresh from another tab rather than a stolen token
+const REUSE_GRACE_SECS: i32 = 30;
+
+/// Longest user agent stored per session
+const MAX_USER_AGENT_LEN: usize = 255;
+
+/// `last_seen_at` is only written once per interval to keep token checks cheap
+const TOUCH_INTERVAL_SECS: i32 = 60;
+
+/// A session that was just started or refreshed
+#[derive(Debug, Clone)]
+pub struct IssuedSession {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// Only available here; the database keeps its hash
+    pub refresh_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Start a session for a fresh login. Expired sessions of the same user are pruned.
+pub async fn create(db: &PgPool, user_id: Uuid, device: &DeviceInfo) -> Result<IssuedSession> {
+    sqlx::query("DELETE FROM user_sessions WHERE user_id = $1 AND expires_at < NOW()")
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    let expires_at = Utc::now() + Duration::days(SESSION_TTL_DAYS);
+    let user_agent = device
+        .user_agent
+        .as_deref()
+        .map(|ua| ua.chars().take(MAX_USER_AGENT_LEN).collect::<String>());
+
+    let refresh_token = new_refresh_token();
+
+    let id = sqlx::query_scalar(
+        r#"
+        INSERT INTO user_sessions (user_id, user_agent, ip, expires_at, refresh_token_hash)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+    )
+    .bind(user_id)
+    .bind(user_agent)
+    .bind(device.ip.to_string())
+    .bind(expires_at)
+    .bind(hash_refresh_token(&refresh_token))
+    .fetch_one(db)
+    .await?;
+
+    Ok(IssuedSession {
+        id,
+        user_id,
+        refresh_token,
+        expires_at,
+    })
+}
+
+/// Rotate a session's refresh token and extend the session
+pub async fn refresh(db: &PgPool, refresh_token: &str) -> Result<IssuedSession> {
+    let presented = hash_refresh_token(refresh_token);
+    let refresh_token = new_refresh_token();
+    let expires_at = Utc::now() + Duration::days(SESSION_TTL_DAYS);
+
+    let rotated: Option<(Uuid, Uuid)> = sqlx::query_as(
+        r#"
+        UPDATE user_sessions
+        SET previous_refresh_token_hash = refresh_token_hash,
+            refresh_token_hash = $2,
+            refreshed_at = NOW(),
+            last_seen_at = NOW(),
+            expires_at = $3
+        WHERE refresh_token_hash = $1 AND revoked_at IS NULL AND expires_at > NOW()
+        RETURNING id, user_id
+        "#,
+    )
+    .bind(&presented)
+    .bind(hash_refresh_token(&refresh_token))
+    .bind(expires_at)
+    .fetch_optional(db)
+    .await?;
+
+    if let Some((id, user_id)) = rotated {
+        return Ok(IssuedSession {
+            id,
+            user_id,
+            refresh_token,
+            expires_at,
+        });
+    }
+
+    let reused: Option<(Uuid, Uuid)> = sqlx::query_as(
+        r#"
+        UPDATE user_sessions SET revoked_at = NOW()
+        WHERE previous_refresh_token_hash = $1 AND revoked_at IS NULL
+          AND refreshed_at < NOW() - make_interval(secs => $2)
+        RETURNING id, user_id
+        "#,
+    )
+    .bind(&presented)
+    .bind(REUSE_GRACE_SECS)
+    .fetch_optional(db)
+    .await?;
+    if let Some((id, user_id)) = reused {
+        tracing::warn!(
+            "Refresh token of session {} (user {}) was used after rotation; revoked the session",
+            id,
+            user_id
+        );
+    }
+
+    Err(AppError::Unauthorized)
+}
+
+/// Check that a session is still active and record that it was used
+pub async fn touch(db: &PgPool, user_id: Uuid, session_id: Uuid) -> Result<()> {
+    let active: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM user_sessions
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL AND expires_at > NOW()
+        )
+        "#,
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .fetch_one(db)
+    .await?;
+
+    if !active {
+        return Err(AppError::Unauthorized);
+    }
+
+    sqlx::query(
+        r#"
+        UPDATE user_sessions SET last_seen_at = NOW()
+        WHERE id = $1 AND last_seen_at < NOW() - make_interval(secs => $2)
+        "#,
+    )
+    .bind(session_id)
+    .bind(TOUCH_INTERVAL_SECS)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// A user's active sessions, most recently used first
+pub async fn list(db: &PgPool, user_id: Uuid, current: Option<Uuid>) -> Result<Vec<UserSession>> {
+    let sessions = sqlx::query_as::<_, UserSession>(
+        r#"
+        SELECT id, user_agent, ip, created_at, last_seen_at, expires_at,
+               (id = $2) IS TRUE AS current
+        FROM user_sessions
+        WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+        ORDER BY last_seen_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .bind(current)
+    .fetch_all(db)
+    .await?;
+
+    Ok(sessions)
+}
+
+/// Every active session, for admins, most recently used first
+pub async fn list_all(db: &PgPool, current: Option<Uuid>) -> Result<Vec<AdminSession>> {
+    let sessions = sqlx::query_as::<_, AdminSession>(
+        r#"
+        SELECT s.id, s.user_id, u.username, s.user_agent, s.ip, s.created_at, s.last_seen_at,
+               s.expires_at, (s.id = $1) IS TRUE AS current
+        FROM user_sessions s
+        JOIN users u ON u.id = s.user_id
+        WHERE s.revoked_at IS NULL AND s.expires_at > NOW()
+        ORDER BY s.last_seen_at DESC
+        "#,
+    )
+    .bind(current)
+    .fetch_all(db)
+    .await?;
+
+    Ok(sessions)
+}
+
+/// Revoke all of a user's sessions, signing them out everywhere. Returns how many
+/// were active.
+pub async fn revoke_all(db: &PgPool, user_id: Uuid) -> Result<u64> {
+    let result = sqlx::query("UPDATE user_sessions SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL")
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// Revoke any session by ID (admins)
+pub async fn revoke_any(db: &PgPool, session_id: Uuid) -> Result<()> {
+    let result = sqlx::query("UPDATE user_sessions SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL")
+        .bind(session_id)
+        .execute(db)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Session not found".to_string()));
+    }
+    Ok(())
+}
+
+/// Revoke one of a user's sessions; tokens issued for it stop working immediately
+pub async fn revoke(db: &PgPool, user_id: Uuid, session_id: Uuid) -> Result<()> {
+    let result = sqlx::query(
+        r#"
+        UPDATE user_sessions SET revoked_at = NOW()
+        WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .execute(db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Session not found".to_string()));
+    }
+    Ok(())
+}
+
+fn new_refresh_token() -> String {
+    let mut secret = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    format!("{}{}", REFRESH_TOKEN_PREFIX, hex::encode(secret))
+}
+
+/// Refresh tokens are 256-bit random values, so a fast unsalted hash is sufficient
+fn hash_refresh_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
@@ -0,0 +1,219 @@
+//! Station event history and analytics
+//!
+//! The station manager records every play, skip, listener join and leave, and curation
+//! run in `station_analytics_events`. Like the audit log, recording never fails the
+//! action itself. `GET /api/v1/stations/:id/analytics` aggregates the events of a time
+//! window into plays per hour, top artists, average listeners, per-track skip rates
+//! and a retention curve of listener sessions.
+
+use crate::error::Result;
+use crate::models::{
+    AnalyticsWindow, ArtistPlays, HourlyPlays, RetentionPoint, StationAnalytics, StationEventKind, TrackSkipRate,
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Points of the retention curve, in minutes after joining
+const RETENTION_MINUTES: [i32; 8] = [1, 5, 10, 15, 30, 60, 120, 240];
+
+const TOP_ARTISTS: i64 = 10;
+const TOP_TRACKS: i64 = 50;
+
+/// One event to record
+#[derive(Debug, Clone)]
+pub struct AnalyticsEvent {
+    pub station_id: Uuid,
+    pub kind: StationEventKind,
+    pub track_id: Option<String>,
+    pub session_id: Option<String>,
+    pub listeners: Option<i32>,
+    pub details: serde_json::Value,
+    pub at: DateTime<Utc>,
+}
+
+impl AnalyticsEvent {
+    pub fn new(station_id: Uuid, kind: StationEventKind) -> Self {
+        Self {
+            station_id,
+            kind,
+            track_id: None,
+            session_id: None,
+            listeners: None,
+            details: serde_json::json!({}),
+            at: Utc::now(),
+        }
+    }
+
+    pub fn track(mut self, track_id: impl Into<String>) -> Self {
+        self.track_id = Some(track_id.into());
+        self
+    }
+
+    pub fn session(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    pub fn listeners(mut self, listeners: usize) -> Self {
+        self.listeners = Some(listeners.min(i32::MAX as usize) as i32);
+        self
+    }
+
+    pub fn details(mut self, details: serde_json::Value) -> Self {
+        self.details = details;
+        self
+    }
+
+    /// When it happened, if not now
+    pub fn at(mut self, at: DateTime<Utc>) -> Self {
+        self.at = at;
+        self
+    }
+}
+
+pub async fn record(db: &PgPool, event: AnalyticsEvent) {
+    if let Err(e) = sqlx::query(
+        r#"
+        INSERT INTO station_analytics_events (station_id, kind, track_id, session_id, listeners, details, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+    )
+    .bind(event.station_id)
+    .bind(event.kind.as_str())
+    .bind(&event.track_id)
+    .bind(&event.session_id)
+    .bind(event.listeners)
+    .bind(&event.details)
+    .bind(event.at)
+    .execute(db)
+    .await
+    {
+        tracing::warn!(
+            "Failed to record {} event of station {}: {}",
+            event.kind.as_str(),
+            event.station_id,
+            e
+        );
+    }
+}
+
+/// Aggregate a station's events over the window ending now
+pub async fn station_analytics(db: &PgPool, station_id: Uuid, window: AnalyticsWindow) -> Result<StationAnalytics> {
+    let to = Utc::now();
+    let from = to - window.duration();
+
+    let (plays, skips, curation_runs, sessions, average_listeners): (i64, i64, i64, i64, Option<f64>) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*) FILTER (WHERE kind = 'play'),
+               COUNT(*) FILTER (WHERE kind = 'skip'),
+               COUNT(*) FILTER (WHERE kind = 'curation'),
+               COUNT(*) FILTER (WHERE kind = 'listener_join'),
+               AVG(listeners) FILTER (WHERE kind = 'play')::FLOAT8
+        FROM station_analytics_events
+        WHERE station_id = $1 AND created_at >= $2
+        "#,
+    )
+    .bind(station_id)
+    .bind(from)
+    .fetch_one(db)
+    .await?;
+
+    let plays_per_hour = sqlx::query_as::<_, HourlyPlays>(
+        r#"
+        SELECT date_trunc('hour', created_at) AS hour, COUNT(*) AS plays
+        FROM station_analytics_events
+        WHERE station_id = $1 AND kind = 'play' AND created_at >= $2
+        GROUP BY 1
+        ORDER BY 1
+        "#,
+    )
+    .bind(station_id)
+    .bind(from)
+    .fetch_all(db)
+    .await?;
+
+    let top_artists = sqlx::query_as::<_, ArtistPlays>(
+        r#"
+        SELECT l.artist, COUNT(*) AS plays
+        FROM station_analytics_events e
+        JOIN library_index l ON l.id = e.track_id
+        WHERE e.station_id = $1 AND e.kind = 'play' AND e.created_at >= $2
+        GROUP BY l.artist
+        ORDER BY plays DESC, l.artist
+        LIMIT $3
+        "#,
+    )
+    .bind(station_id)
+    .bind(from)
+    .bind(TOP_ARTISTS)
+    .fetch_all(db)
+    .await?;
+
+    let tracks = sqlx::query_as::<_, TrackSkipRate>(
+        r#"
+        SELECT e.track_id, l.title, l.artist,
+               COUNT(*) FILTER (WHERE e.kind = 'play') AS plays,
+               COUNT(*) FILTER (WHERE e.kind = 'skip') AS skips,
+               COUNT(*) FILTER (WHERE e.kind = 'skip')::FLOAT8
+                   / GREATEST(COUNT(*) FILTER (WHERE e.kind = 'play'), 1) AS skip_rate
+        FROM station_analytics_events e
+        LEFT JOIN library_index l ON l.id = e.track_id
+        WHERE e.station_id = $1 AND e.kind IN ('play', 'skip') AND e.created_at >= $2
+          AND e.track_id IS NOT NULL
+        GROUP BY e.track_id, l.title, l.artist
+        ORDER BY plays DESC, skip_rate DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(station_id)
+    .bind(from)
+    .bind(TOP_TRACKS)
+    .fetch_all(db)
+    .await?;
+
+    // Each join is paired with the session's first leave after it
+    let retention = sqlx::query_as::<_, RetentionPoint>(
+        r#"
+        WITH sessions AS (
+            SELECT j.created_at AS joined_at, MIN(l.created_at) AS left_at
+            FROM station_analytics_events j
+            LEFT JOIN station_analytics_events l
+                ON l.station_id = j.station_id AND l.session_id = j.session_id
+               AND l.kind = 'listener_leave' AND l.created_at >= j.created_at
+            WHERE j.station_id = $1 AND j.kind = 'listener_join' AND j.created_at >= $2
+            GROUP BY j.id, j.created_at
+        )
+        SELECT m AS minutes,
+               COALESCE(
+                   COUNT(*) FILTER (
+                       WHERE COALESCE(left_at, NOW()) - joined_at >= make_interval(mins => m)
+                   )::FLOAT8 / NULLIF(COUNT(*), 0),
+                   0
+               ) AS retained
+        FROM sessions CROSS JOIN unnest($3::INT[]) AS m
+        GROUP BY m
+        ORDER BY m
+        "#,
+    )
+    .bind(station_id)
+    .bind(from)
+    .bind(&RETENTION_MINUTES[..])
+    .fetch_all(db)
+    .await?;
+
+    Ok(StationAnalytics {
+        window,
+        from,
+        to,
+        plays,
+        skips,
+        curation_runs,
+        average_listeners,
+        plays_per_hour,
+        top_artists,
+        tracks,
+        sessions,
+        retention,
+    })
+}
@@ -0,0 +1,159 @@
+//! Station control events shared between API instances over Redis pub/sub
+//!
+//! Each instance keeps its own in-memory station state and HLS broadcasters, so with
+//! several instances behind a load balancer a skip or track change would otherwise
+//! only affect the instance that handled the request. Every instance publishes the
+//! changes it makes to [`CHANNEL`] and applies the ones published by the others.
+//!
+//! Delivery is best effort: Redis pub/sub doesn't buffer for disconnected subscribers,
+//! and a failed publish is only logged, as the local change has already happened.
+
+use crate::models::Track;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Redis channel all instances publish and subscribe to
+pub const CHANNEL: &str = "navidrome-radio:station-events";
+
+/// Delay before resubscribing after the pub/sub connection drops (doubles up to the max)
+const RESUBSCRIBE_BASE_DELAY: Duration = Duration::from_millis(500);
+const RESUBSCRIBE_MAX_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StationEvent {
+    Started { station_id: Uuid },
+    Stopped { station_id: Uuid },
+    /// The current track was skipped; instances skip their own broadcasters
+    Skipped { station_id: Uuid },
+    /// The station manager moved on to a new track
+    NowPlaying {
+        station_id: Uuid,
+        track: Box<Track>,
+        started_at: DateTime<Utc>,
+    },
+}
+
+/// Wire format: the event plus the instance that published it
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope {
+    origin: Uuid,
+    #[serde(flatten)]
+    event: StationEvent,
+}
+
+pub struct StationEventBus {
+    /// Identifies this process, so it can ignore its own messages
+    instance_id: Uuid,
+    client: redis::Client,
+    redis: ConnectionManager,
+}
+
+impl StationEventBus {
+    pub fn new(client: redis::Client, redis: ConnectionManager) -> Self {
+        Self {
+            instance_id: Uuid::new_v4(),
+            client,
+            redis,
+        }
+    }
+
+    /// Tell the other instances about a change made here
+    pub async fn publish(&self, event: StationEvent) {
+        let payload = match serde_json::to_string(&Envelope {
+            origin: self.instance_id,
+            event,
+        }) {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::warn!("Failed to serialize station event: {}", e);
+                return;
+            }
+        };
+
+        let mut conn = self.redis.clone();
+        let result: redis::RedisResult<()> = conn.publish(CHANNEL, payload).await;
+        if let Err(e) = result {
+            tracing::warn!("Failed to publish station event: {}", e);
+        }
+    }
+
+    /// Apply events published by other instances with `handler`, resubscribing with
+    /// backoff whenever the pub/sub connection is lost
+    pub fn spawn_subscriber<F, Fut>(self: Arc<Self>, handler: F)
+    where
+        F: Fn(StationEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        tokio::spawn(async move {
+            let mut delay = RESUBSCRIBE_BASE_DELAY;
+            loop {
+                match self.client.get_async_connection().await {
+                    Ok(conn) => {
+                        let mut pubsub = conn.into_pubsub();
+                        match pubsub.subscribe(CHANNEL).await {
+                            Ok(()) => {
+                                tracing::info!("Subscribed to station events");
+                                delay = RESUBSCRIBE_BASE_DELAY;
+                                let mut messages = pubsub.on_message();
+                                while let Some(msg) = messages.next().await {
+                                    let Ok(payload) = msg.get_payload::<String>() else { continue };
+                                    match self.decode(&payload) {
+                                        Some(event) => handler(event).await,
+                                        None => continue,
+                                    }
+                                }
+                                tracing::warn!("Station event subscription closed");
+                            }
+                            Err(e) => tracing::warn!("Failed to subscribe to station events: {}", e),
+                        }
+                    }
+                    Err(e) => tracing::warn!("Failed to connect for station events: {}", e),
+                }
+
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(RESUBSCRIBE_MAX_DELAY);
+            }
+        });
+    }
+
+    /// The event in a message, unless it came from this instance or can't be parsed
+    fn decode(&self, payload: &str) -> Option<StationEvent> {
+        match serde_json::from_str::<Envelope>(payload) {
+            Ok(envelope) if envelope.origin == self.instance_id => None,
+            Ok(envelope) => Some(envelope.event),
+            Err(e) => {
+                tracing::warn!("Ignoring malformed station event: {}", e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let station_id = Uuid::new_v4();
+        let envelope = Envelope {
+            origin: Uuid::new_v4(),
+            event: StationEvent::Skipped { station_id },
+        };
+
+        let json = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(json["type"], "skipped");
+        assert_eq!(json["station_id"], station_id.to_string());
+
+        let decoded: Envelope = serde_json::from_value(json).unwrap();
+        assert!(matches!(decoded.event, StationEvent::Skipped { station_id: id } if id == station_id));
+    }
+}
@@ -1,26 +1,173 @@
 #![allow(dead_code)]
 
+use crate::config::ServerRole;
 use crate::error::{AppError, Result};
-use crate::models::{NowPlaying, Station, Track};
-use crate::services::{CurationEngine, NavidromeClient};
-use chrono::{DateTime, Utc, Duration};
+use crate::models::{NowPlaying, ScheduleBlock, Station, StationEventKind, Track, TrackInfo, WebhookEvent};
+use crate::services::announcer::{Announcer, StationAnnouncer};
+use crate::services::audio_broadcaster::{AudioBroadcaster, AudioBroadcasterConfig};
+use crate::services::audio_cache::AudioCache;
+use crate::services::broadcast_archive::BroadcastArchive;
+use crate::services::audio_pipeline::{
+    self, AudioPipeline, AudioPipelineConfig, PipelineEvent, QueuedTrack, StreamOptions, TrackState,
+};
+use crate::services::lastfm::LastFmClient;
+use crate::services::listenbrainz::ListenBrainzClient;
+use crate::services::loudness::{self, TrackLoudness};
+use crate::services::ratings::AudienceFeedback;
+use crate::services::resampler::ResampleQuality;
+use crate::services::cluster::Cluster;
+use crate::services::schedule;
+use crate::services::separation::{PlayHistory, SeparationRules};
+use crate::services::station_analytics::{self, AnalyticsEvent};
+use crate::services::station_events::{StationEvent, StationEventBus};
+use crate::services::track_requests;
+use crate::services::{CurationEngine, NavidromeClient, WebhookDispatcher};
+use chrono::{DateTime, Utc};
 use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::Serialize;
 use sqlx::PgPool;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
 use uuid::Uuid;
 
 /// How long before a listener is considered disconnected (no heartbeat)
 const LISTENER_TIMEOUT_SECONDS: i64 = 15;
 
+/// Listener sets outlive their last heartbeat by this much, so the sweep records the
+/// sessions in them leaving before they expire
+const LISTENERS_TTL_SECONDS: i64 = 4 * LISTENER_TIMEOUT_SECONDS;
+
+/// Remove a listener session only if it is still stale (no heartbeat since the sweep
+/// read it)
+const REMOVE_IF_STALE: &str = "local score = redis.call('ZSCORE', KEYS[1], ARGV[1]) \
+     if score and tonumber(score) < tonumber(ARGV[2]) then return redis.call('ZREM', KEYS[1], ARGV[1]) else return 0 end";
+
+/// Sorted set of a station's listener sessions, scored by their last heartbeat (unix
+/// milliseconds). Shared by all replicas, and expires once nobody sends heartbeats.
+fn listeners_key(station_id: Uuid) -> String {
+    format!("navidrome-radio:listeners:{}", station_id)
+}
+
+/// Oldest heartbeat score (unix milliseconds) of a connected listener at `now`
+fn listener_cutoff(now: i64) -> i64 {
+    now - LISTENER_TIMEOUT_SECONDS * 1000
+}
+
+/// How often a broadcasting station checks whether its program block changed
+const SCHEDULE_CHECK_SECONDS: u64 = 30;
+
+/// The program block on air and when its pool was last curated
+type ProgramKey = (Uuid, Option<DateTime<Utc>>);
+
+fn program_key(block: Option<&ScheduleBlock>) -> Option<ProgramKey> {
+    block.map(|block| (block.id, block.curated_at))
+}
+
+/// How often a streamer looks for stations to take over or give up
+const FAILOVER_CHECK_SECONDS: u64 = 10;
+
+/// How far HLS listeners are behind the pipeline (about three 2-second segments)
+pub const HLS_LATENCY_SECS: i64 = 6;
+
+/// Track changes buffered for slow now-playing subscribers
+const NOW_PLAYING_CAPACITY: usize = 64;
+
+/// Files in the fallback audio directory that get played
+const FALLBACK_EXTENSIONS: &[&str] = &["mp3", "flac", "ogg", "opus", "m4a", "aac", "wav"];
+
+/// Track ids of fallback files start with this, followed by the file name
+const FALLBACK_FILE_PREFIX: &str = "fallback:";
+
+/// Audio files directly in `dir`, by name
+async fn fallback_files(dir: &std::path::Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let audio = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| FALLBACK_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()));
+        if audio && entry.file_type().await?.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// How often the watchdog checks a broadcast
+const WATCHDOG_INTERVAL_SECS: u64 = 5;
+
 #[derive(Clone)]
 pub struct ActiveStation {
     pub station_id: Uuid,
     pub current_track: Option<Track>,
     pub started_at: Option<DateTime<Utc>>,
-    /// Map of session_id -> last heartbeat time
-    pub listener_heartbeats: HashMap<String, DateTime<Utc>>,
+}
+
+/// Per-station audio broadcasters, shared with the cluster heartbeat and Daily Mix
+pub type Broadcasters = Arc<RwLock<HashMap<Uuid, Arc<AudioBroadcaster>>>>;
+
+/// How the manager runs audio pipelines and broadcasters for its stations
+#[derive(Clone, Default)]
+pub struct BroadcastSettings {
+    /// Whether this process streams at all (false on `api` nodes)
+    pub enabled: bool,
+    /// Start a station's broadcaster together with the station instead of with its
+    /// first listener (single-node setups, where no streamer node owns the stream)
+    pub eager: bool,
+    /// Transcoding requested from Navidrome for broadcast tracks
+    pub stream_options: StreamOptions,
+    /// Disk cache of fetched tracks (AUDIO_CACHE_DIR)
+    pub audio_cache: Option<Arc<AudioCache>>,
+    /// Where broadcasters spill their segment windows, one directory per station
+    pub spill_dir: Option<PathBuf>,
+    /// Records the broadcasts of stations that set `archive` (ARCHIVE_DIR)
+    pub archive: Option<Arc<BroadcastArchive>>,
+    /// Local audio files played when a station's tracks keep failing (FALLBACK_AUDIO_DIR)
+    pub fallback_dir: Option<PathBuf>,
+    /// Mounted music library tracks are read from before asking Navidrome
+    /// (NAVIDROME_LIBRARY_PATH)
+    pub library_path: Option<PathBuf>,
+    /// Loudness analyzed tracks are normalized to (LOUDNESS_TARGET_LUFS)
+    pub loudness_target_lufs: Option<f32>,
+    /// Speaks links between tracks of stations that set `announce_every`
+    pub announcer: Option<Arc<Announcer>>,
+    /// How decoded tracks and encoder input are resampled (RESAMPLE_QUALITY)
+    pub resample_quality: ResampleQuality,
+    /// When the watchdog steps in (all zero disables it)
+    pub watchdog: WatchdogSettings,
+}
+
+/// Limits the watchdog holds broadcasts to
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WatchdogSettings {
+    /// Seconds without a segment before the pipeline is restarted (0 disables)
+    pub stall_secs: u64,
+    /// Seconds of silence before the track is skipped (0 disables)
+    pub silence_secs: u64,
+}
+
+/// What the watchdog found wrong with a broadcast
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamProblem {
+    /// No audio is being produced (pipeline starved, Navidrome unreachable)
+    Stalled,
+    /// Audio is produced but silent
+    Silent,
+}
+
+/// Broadcasts the watchdog found unhealthy, and its interventions so far
+#[derive(Debug, Default)]
+pub struct WatchdogState {
+    pub unhealthy: HashMap<Uuid, StreamProblem>,
+    pub restarts: u64,
+    pub skips: u64,
 }
 
 #[derive(Clone)]
@@ -30,6 +177,18 @@ pub struct StationManager {
     active_stations: Arc<RwLock<HashMap<Uuid, ActiveStation>>>,
     curation_engine: Arc<CurationEngine>,
     navidrome_client: Arc<NavidromeClient>,
+    webhooks: WebhookDispatcher,
+    /// Keeps other API instances in step with changes made here
+    events: Arc<StationEventBus>,
+    broadcasters: Broadcasters,
+    broadcast: BroadcastSettings,
+    /// Receives every finished track of stations that scrobble
+    listenbrainz: Option<Arc<ListenBrainzClient>>,
+    /// Receives the tracks that stations' broadcast pipelines play
+    lastfm: Option<Arc<LastFmClient>>,
+    /// Track changes of every station, as listeners hear them
+    now_playing_tx: broadcast::Sender<(Uuid, NowPlaying)>,
+    watchdog: Arc<RwLock<WatchdogState>>,
 }
 
 impl StationManager {
@@ -38,6 +197,8 @@ impl StationManager {
         redis: ConnectionManager,
         curation_engine: Arc<CurationEngine>,
         navidrome_client: Arc<NavidromeClient>,
+        webhooks: WebhookDispatcher,
+        events: Arc<StationEventBus>,
     ) -> Self {
         Self {
             db,
@@ -45,9 +206,36 @@ impl StationManager {
             active_stations: Arc::new(RwLock::new(HashMap::new())),
             curation_engine,
             navidrome_client,
+            webhooks,
+            events,
+            broadcasters: Arc::new(RwLock::new(HashMap::new())),
+            broadcast: BroadcastSettings::default(),
+            listenbrainz: None,
+            lastfm: None,
+            now_playing_tx: broadcast::channel(NOW_PLAYING_CAPACITY).0,
+            watchdog: Arc::new(RwLock::new(WatchdogState::default())),
         }
     }
 
+    /// Submit tracks to ListenBrainz when stations move past them
+    pub fn with_listenbrainz(mut self, listenbrainz: Arc<ListenBrainzClient>) -> Self {
+        self.listenbrainz = Some(listenbrainz);
+        self
+    }
+
+    /// Scrobble the tracks broadcast pipelines play to Last.fm
+    pub fn with_lastfm(mut self, lastfm: Arc<LastFmClient>) -> Self {
+        self.lastfm = Some(lastfm);
+        self
+    }
+
+    /// Run audio pipelines for stations, registering their broadcasters in `broadcasters`
+    pub fn with_broadcasting(mut self, broadcasters: Broadcasters, settings: BroadcastSettings) -> Self {
+        self.broadcasters = broadcasters;
+        self.broadcast = settings;
+        self
+    }
+
     pub async fn load_active_stations(&self) -> Result<()> {
         // Load all active stations from database
         let stations: Vec<Station> = sqlx::query_as(
@@ -67,7 +255,6 @@ impl StationManager {
                     station_id: station.id,
                     current_track: None,
                     started_at: None,
-                    listener_heartbeats: HashMap::new(),
                 },
             );
             drop(active_stations);
@@ -77,6 +264,7 @@ impl StationManager {
                 tracing::error!("Failed to start station {}: {:?}", station.id, e);
             } else {
                 tracing::info!("Started station: {} ({})", station.name, station.path);
+                self.start_broadcaster_eagerly(station.id).await;
             }
         }
 
@@ -98,15 +286,20 @@ impl StationManager {
                 station_id,
                 current_track: None,
                 started_at: None,
-                listener_heartbeats: HashMap::new(),
             },
         );
 
         // Start playing first track
         drop(stations);
         self.play_next_track(station_id).await?;
+        self.start_broadcaster_eagerly(station_id).await;
 
         tracing::info!("Started station: {}", station_id);
+        self.events.publish(StationEvent::Started { station_id }).await;
+        self.webhooks.dispatch(
+            WebhookEvent::StationStarted,
+            serde_json::json!({ "station_id": station_id }),
+        );
         Ok(())
     }
 
@@ -120,12 +313,53 @@ impl StationManager {
         // Remove from active stations
         let mut stations = self.active_stations.write().await;
         stations.remove(&station_id);
+        drop(stations);
+        self.stop_broadcaster(station_id).await;
+
+        // Its listeners are gone with it
+        let mut conn = self.redis.clone();
+        let key = listeners_key(station_id);
+        let cleared: redis::RedisResult<(Vec<String>, ())> =
+            redis::pipe().atomic().zrange(&key, 0, -1).del(&key).query_async(&mut conn).await;
+        match cleared {
+            Ok((sessions, ())) => {
+                for session_id in sessions {
+                    let event = AnalyticsEvent::new(station_id, StationEventKind::ListenerLeave)
+                        .session(session_id)
+                        .details(serde_json::json!({ "reason": "station_stopped" }));
+                    station_analytics::record(&self.db, event).await;
+                }
+            }
+            Err(e) => tracing::warn!("Failed to clear listeners of station {}: {}", station_id, e),
+        }
 
         tracing::info!("Stopped station: {}", station_id);
+        self.events.publish(StationEvent::Stopped { station_id }).await;
+        self.webhooks.dispatch(
+            WebhookEvent::StationStopped,
+            serde_json::json!({ "station_id": station_id }),
+        );
         Ok(())
     }
 
+    /// Skip the current track: in the station's pipeline when it is broadcasting here,
+    /// otherwise by advancing the station's metadata
     pub async fn skip_track(&self, station_id: Uuid) -> Result<()> {
+        let skipped = self
+            .active_stations
+            .read()
+            .await
+            .get(&station_id)
+            .and_then(|active| active.current_track.as_ref().map(|track| track.id.clone()));
+        if let Some(track_id) = skipped {
+            let event = AnalyticsEvent::new(station_id, StationEventKind::Skip).track(track_id);
+            station_analytics::record(&self.db, event).await;
+        }
+
+        if self.skip_broadcaster(station_id).await? {
+            return Ok(());
+        }
+
         // Mark current track as skipped in history
         let stations = self.active_stations.read().await;
         if let Some(active) = stations.get(&station_id) {
@@ -156,18 +390,36 @@ impl StationManager {
     }
 
     pub async fn play_next_track(&self, station_id: Uuid) -> Result<()> {
-        // Get station
-        let station = self.get_station_by_id(station_id).await?;
+        // Get station, as the program block on air plays it
+        let (station, _) = self.programmed_station(station_id).await?;
 
-        // Get recent tracks to avoid repetition
-        let recent_tracks = self.get_recent_tracks(station_id, 20).await?;
-        let recent_ids: Vec<String> = recent_tracks.iter().map(|t| t.clone()).collect();
+        // Recent plays, far enough back for the station's separation rules
+        let rules = SeparationRules::for_station(&station.config);
+        let history = PlayHistory::load(&self.db, station_id, rules.lookback()).await?;
 
-        // Select next track
-        let track = self
-            .curation_engine
-            .select_next_track(&station, &recent_ids)
-            .await?;
+        // Listener requests go first, then curated selection
+        let (track, selection_method) = match self.requested_track(station_id).await {
+            Some(track) => (track, "request"),
+            None => {
+                let feedback = AudienceFeedback::load(&self.db, station_id).await.unwrap_or_else(|e| {
+                    tracing::warn!("Failed to read listener feedback of station {}: {}", station_id, e);
+                    AudienceFeedback::default()
+                });
+                let started = std::time::Instant::now();
+                let track = self
+                    .curation_engine
+                    .select_next_track(&station, &history, &feedback)
+                    .await?;
+                let event = AnalyticsEvent::new(station_id, StationEventKind::Curation)
+                    .track(&track.id)
+                    .details(serde_json::json!({
+                        "mode": station.config.track_selection_mode,
+                        "duration_ms": started.elapsed().as_millis() as u64,
+                    }));
+                station_analytics::record(&self.db, event).await;
+                (track, "random")
+            }
+        };
 
         let now = Utc::now();
 
@@ -179,18 +431,57 @@ impl StationManager {
         .bind(station_id)
         .bind(&track.id)
         .bind(now)
-        .bind("random")
+        .bind(selection_method)
         .execute(&self.db)
         .await?;
 
-        // Update active station
-        let mut stations = self.active_stations.write().await;
-        if let Some(active) = stations.get_mut(&station_id) {
-            active.current_track = Some(track.clone());
-            active.started_at = Some(now);
+        let mut event = AnalyticsEvent::new(station_id, StationEventKind::Play)
+            .track(&track.id)
+            .details(serde_json::json!({ "selection_method": selection_method }))
+            .at(now);
+        if let Ok(listeners) = self.get_listener_count(station_id).await {
+            event = event.listeners(listeners);
         }
+        station_analytics::record(&self.db, event).await;
+
+        // Update active station, keeping the track it replaces for scrobbling
+        let mut stations = self.active_stations.write().await;
+        let finished = stations.get_mut(&station_id).and_then(|active| {
+            let previous = active.current_track.replace(track.clone());
+            previous.zip(active.started_at.replace(now))
+        });
+        drop(stations);
+
+        self.events
+            .publish(StationEvent::NowPlaying {
+                station_id,
+                track: Box::new(track.clone()),
+                started_at: now,
+            })
+            .await;
+        self.announce_selected(station_id, &track, now).await;
 
         tracing::info!("Playing track '{}' on station {}", track.title, station_id);
+        if let (Some(listenbrainz), Some((finished, started_at))) = (&self.listenbrainz, finished) {
+            if station.config.scrobble {
+                let listenbrainz = listenbrainz.clone();
+                let station = station.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = listenbrainz.track_finished(&station, &finished, started_at, now).await {
+                        tracing::warn!("Failed to queue listens of '{}': {}", finished.title, e);
+                    }
+                });
+            }
+        }
+        self.webhooks.dispatch(
+            WebhookEvent::TrackChanged,
+            serde_json::json!({
+                "station_id": station_id,
+                "station_path": station.path,
+                "track": TrackInfo::from(track),
+                "started_at": now,
+            }),
+        );
 
         Ok(())
     }
@@ -219,97 +510,864 @@ impl StationManager {
         }
 
         // Get current now playing after potential track advance
-        let stations = self.active_stations.read().await;
-        let active = stations
-            .get(&station_id)
-            .ok_or_else(|| AppError::NotFound("Station not active".to_string()))?;
+        let (track, started_at) = {
+            let stations = self.active_stations.read().await;
+            let active = stations
+                .get(&station_id)
+                .ok_or_else(|| AppError::NotFound("Station not active".to_string()))?;
 
-        let track = active
-            .current_track
-            .clone()
-            .ok_or_else(|| AppError::NotFound("No track playing".to_string()))?;
+            let track = active
+                .current_track
+                .clone()
+                .ok_or_else(|| AppError::NotFound("No track playing".to_string()))?;
+            (track, active.started_at)
+        };
 
-        // Count active listeners (heartbeat within timeout)
-        let now = Utc::now();
-        let timeout = Duration::seconds(LISTENER_TIMEOUT_SECONDS);
-        let active_listeners = active
-            .listener_heartbeats
-            .values()
-            .filter(|&last_heartbeat| now - *last_heartbeat < timeout)
-            .count();
+        // A missing count shouldn't take now playing down with it
+        let listeners = self.get_listener_count(station_id).await.unwrap_or_else(|e| {
+            tracing::warn!("Failed to count listeners of station {}: {}", station_id, e);
+            0
+        });
 
         Ok(NowPlaying {
             track: track.into(),
-            started_at: active.started_at.unwrap_or_else(Utc::now),
-            listeners: active_listeners,
+            started_at: started_at.unwrap_or_else(Utc::now),
+            listeners,
         })
     }
 
-    /// Record a heartbeat for a listener session. Returns the current listener count.
-    pub async fn listener_heartbeat(&self, station_id: Uuid, session_id: String) -> Result<usize> {
-        let now = Utc::now();
-        let timeout = Duration::seconds(LISTENER_TIMEOUT_SECONDS);
+    /// Track changes of all stations: `(station_id, now playing)` whenever a new track
+    /// starts for listeners
+    pub fn subscribe_now_playing(&self) -> broadcast::Receiver<(Uuid, NowPlaying)> {
+        self.now_playing_tx.subscribe()
+    }
+
+    /// Tell now-playing subscribers about a track the manager selected, unless a
+    /// broadcaster here plays the station (its pipeline reports what listeners hear)
+    async fn announce_selected(&self, station_id: Uuid, track: &Track, started_at: DateTime<Utc>) {
+        if self.now_playing_tx.receiver_count() == 0 || self.running_broadcaster(station_id).await.is_some() {
+            return;
+        }
+        let listeners = self.get_listener_count(station_id).await.unwrap_or(0);
+        let _ = self.now_playing_tx.send((
+            station_id,
+            NowPlaying {
+                track: track.clone().into(),
+                started_at,
+                listeners,
+            },
+        ));
+    }
+
+    /// Tell now-playing subscribers about each track a station's pipeline starts, once
+    /// it reaches HLS listeners, until the pipeline stops
+    fn spawn_now_playing(&self, station_id: Uuid, mut events: broadcast::Receiver<PipelineEvent>) {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(PipelineEvent::TrackStarted(track)) => {
+                        tokio::time::sleep(std::time::Duration::from_secs(HLS_LATENCY_SECS as u64)).await;
+                        if manager.now_playing_tx.receiver_count() == 0 {
+                            continue;
+                        }
+                        let now_playing = manager.pipeline_now_playing(station_id, &track, 0.0).await;
+                        let _ = manager.now_playing_tx.send((station_id, now_playing));
+                    }
+                    Ok(PipelineEvent::Stopped) | Err(broadcast::error::RecvError::Closed) => break,
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                }
+            }
+        });
+    }
+
+    /// Now playing for a pipeline's track, `position_secs` in as HLS listeners hear it,
+    /// with the album and duration from the library index when it has the track
+    pub async fn pipeline_now_playing(&self, station_id: Uuid, track: &TrackState, position_secs: f32) -> NowPlaying {
+        let indexed: Option<(String, String, String, i32)> =
+            sqlx::query_as("SELECT title, artist, album, duration FROM library_index WHERE id = $1")
+                .bind(&track.track_id)
+                .fetch_optional(&self.db)
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::debug!("Failed to read track {} for now playing: {}", track.track_id, e);
+                    None
+                });
+        let (title, artist, album, duration) = indexed.unwrap_or_else(|| {
+            (track.title.clone(), track.artist.clone(), String::new(), track.duration_secs as i32)
+        });
+        let listeners = self.get_listener_count(station_id).await.unwrap_or(0);
+
+        NowPlaying {
+            track: TrackInfo {
+                id: track.track_id.clone(),
+                title,
+                artist,
+                album,
+                duration,
+                album_art: Some(format!("/api/v1/navidrome/cover/{}", track.track_id)),
+            },
+            started_at: Utc::now() - chrono::Duration::milliseconds((position_secs * 1000.0) as i64),
+            listeners,
+        }
+    }
+
+    /// Mirror a change published by another instance. Nothing is written to the
+    /// database or re-published; the originating instance has done both.
+    pub async fn apply_remote_event(&self, event: &StationEvent) {
+        // This instance's own stream follows the change
+        match event {
+            StationEvent::Stopped { station_id } => self.stop_broadcaster(*station_id).await,
+            StationEvent::Skipped { station_id } => {
+                if let Err(e) = self.skip_broadcaster(*station_id).await {
+                    tracing::warn!("Failed to apply remote skip for station {}: {}", station_id, e);
+                }
+            }
+            _ => {}
+        }
 
         let mut stations = self.active_stations.write().await;
-        if let Some(active) = stations.get_mut(&station_id) {
-            // Update this session's heartbeat
-            active.listener_heartbeats.insert(session_id, now);
+        match event {
+            StationEvent::Started { station_id } => {
+                stations.entry(*station_id).or_insert_with(|| ActiveStation {
+                    station_id: *station_id,
+                    current_track: None,
+                    started_at: None,
+                });
+            }
+            StationEvent::Stopped { station_id } => {
+                stations.remove(station_id);
+            }
+            StationEvent::NowPlaying {
+                station_id,
+                track,
+                started_at,
+            } => {
+                if let Some(active) = stations.get_mut(station_id) {
+                    active.current_track = Some(track.as_ref().clone());
+                    active.started_at = Some(*started_at);
+                }
+            }
+            StationEvent::Skipped { .. } => {}
+        }
+        drop(stations);
+
+        if let StationEvent::NowPlaying {
+            station_id,
+            track,
+            started_at,
+        } = event
+        {
+            self.announce_selected(*station_id, track, *started_at).await;
+        }
+    }
+
+    /// The running broadcaster of a station, starting its pipeline if there is none.
+    /// A background task keeps the pipeline's queue filled from the station's tracks.
+    pub async fn broadcaster(&self, station_id: Uuid) -> Result<Arc<AudioBroadcaster>> {
+        if !self.broadcast.enabled {
+            return Err(AppError::Unavailable("Streams are served by streamer nodes".to_string()));
+        }
+
+        // Check if broadcaster already exists and is running
+        if let Some(broadcaster) = self.running_broadcaster(station_id).await {
+            return Ok(broadcaster);
+        }
 
-            // Clean up stale sessions while we're here
-            active.listener_heartbeats.retain(|_, last_heartbeat| {
-                now - *last_heartbeat < timeout
-            });
+        let (station, program) = self.programmed_station(station_id).await?;
+        let pipeline = Arc::new(self.start_pipeline(&station).await?);
+        // Before the broadcaster starts reading, so the first track isn't missed
+        let pipeline_events = pipeline.subscribe();
+        let now_playing_events = pipeline.subscribe();
+        let broadcaster = Arc::new(AudioBroadcaster::new(
+            pipeline.clone(),
+            AudioBroadcasterConfig {
+                codec: station.config.codec,
+                bitrate: station.config.bitrate,
+                variant_bitrates: station.config.variant_bitrates.clone(),
+                spill_dir: self.broadcast.spill_dir.as_ref().map(|dir| dir.join(station_id.to_string())),
+                resample_quality: self.broadcast.resample_quality,
+                ..AudioBroadcasterConfig::default()
+            },
+        ));
+        let archive_segments = broadcaster.subscribe_segments();
+        if let Err(e) = broadcaster.start().await {
+            broadcaster.shutdown().await;
+            return Err(e);
+        }
+
+        // Two listeners arriving together may both have started one; keep the first
+        let replaced = {
+            let mut broadcasters = self.broadcasters.write().await;
+            if let Some(existing) = broadcasters.get(&station_id).filter(|b| b.is_running()).cloned() {
+                drop(broadcasters);
+                broadcaster.shutdown().await;
+                return Ok(existing);
+            }
+            broadcasters.insert(station_id, broadcaster.clone())
+        };
+        if let Some(stopped) = replaced {
+            stopped.shutdown().await;
+        }
+
+        let pooled = !station.track_ids.is_empty();
+        self.spawn_refill(station_id, broadcaster.clone(), pipeline, program_key(program.as_ref()), pooled);
+        self.spawn_scrobbler(station_id, pipeline_events);
+        self.spawn_now_playing(station_id, now_playing_events);
+        self.spawn_watchdog(station_id, broadcaster.clone());
+        if let Some(archive) = self.broadcast.archive.clone().filter(|_| station.config.archive) {
+            archive.spawn_recorder(
+                self.db.clone(),
+                station_id,
+                broadcaster.codec(),
+                broadcaster.init_segment(),
+                archive_segments,
+            );
+        }
+        tracing::info!("Started broadcasting station {}", station.name);
+        Ok(broadcaster)
+    }
+
+    /// Broadcasts the watchdog currently finds unhealthy, with restart and skip counts
+    pub async fn watchdog_report(&self) -> serde_json::Value {
+        let watchdog = self.watchdog.read().await;
+        serde_json::json!({
+            "unhealthy": watchdog.unhealthy,
+            "restarts": watchdog.restarts,
+            "skips": watchdog.skips,
+        })
+    }
+
+    /// Whether the watchdog currently finds any broadcast unhealthy
+    pub async fn has_unhealthy_streams(&self) -> bool {
+        !self.watchdog.read().await.unhealthy.is_empty()
+    }
+
+    /// Watch a broadcaster until it stops or is replaced: restart its pipeline when it
+    /// stops producing segments, skip the track when its output stays silent. Both are
+    /// reported with a `stream.unhealthy` webhook, and recovery with `stream.recovered`.
+    fn spawn_watchdog(&self, station_id: Uuid, broadcaster: Arc<AudioBroadcaster>) {
+        let limits = self.broadcast.watchdog;
+        if limits.stall_secs == 0 && limits.silence_secs == 0 {
+            return;
+        }
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(WATCHDOG_INTERVAL_SECS));
+            loop {
+                interval.tick().await;
+                let current = manager.running_broadcaster(station_id).await;
+                if !current.is_some_and(|current| Arc::ptr_eq(&current, &broadcaster)) {
+                    break;
+                }
+
+                let health = broadcaster.health();
+                if limits.stall_secs > 0 && health.stalled_secs >= limits.stall_secs {
+                    manager
+                        .report_unhealthy(station_id, StreamProblem::Stalled, health.stalled_secs, "restarted")
+                        .await;
+                    manager.watchdog.write().await.restarts += 1;
+                    // The new broadcaster gets a watchdog of its own
+                    manager.stop_broadcaster(station_id).await;
+                    if let Err(e) = manager.broadcaster(station_id).await {
+                        tracing::warn!("Watchdog failed to restart the broadcast of station {}: {}", station_id, e);
+                    }
+                    break;
+                }
+                if limits.silence_secs > 0 && health.silent_secs >= limits.silence_secs {
+                    manager
+                        .report_unhealthy(station_id, StreamProblem::Silent, health.silent_secs, "skipped")
+                        .await;
+                    manager.watchdog.write().await.skips += 1;
+                    if let Err(e) = broadcaster.skip().await {
+                        tracing::warn!("Watchdog failed to skip the silent track of station {}: {}", station_id, e);
+                    }
+                    continue;
+                }
+
+                if health.silent_secs == 0 && manager.watchdog.write().await.unhealthy.remove(&station_id).is_some() {
+                    tracing::info!("Broadcast of station {} recovered", station_id);
+                    manager.webhooks.dispatch(
+                        WebhookEvent::StreamRecovered,
+                        serde_json::json!({ "station_id": station_id }),
+                    );
+                }
+            }
+        });
+    }
+
+    /// Log a broadcast problem and, unless it was already reported, raise the webhook
+    async fn report_unhealthy(&self, station_id: Uuid, problem: StreamProblem, secs: u64, action: &str) {
+        tracing::warn!(
+            "Broadcast of station {} is {:?} for {}s; watchdog {} it",
+            station_id,
+            problem,
+            secs,
+            action
+        );
+        let previous = self.watchdog.write().await.unhealthy.insert(station_id, problem);
+        if previous != Some(problem) {
+            self.webhooks.dispatch(
+                WebhookEvent::StreamUnhealthy,
+                serde_json::json!({
+                    "station_id": station_id,
+                    "problem": problem,
+                    "seconds": secs,
+                    "action": action,
+                }),
+            );
+        }
+    }
+
+    /// A station's broadcaster, if one is running in this process
+    pub async fn running_broadcaster(&self, station_id: Uuid) -> Option<Arc<AudioBroadcaster>> {
+        self.broadcasters
+            .read()
+            .await
+            .get(&station_id)
+            .filter(|broadcaster| broadcaster.is_running())
+            .cloned()
+    }
 
-            Ok(active.listener_heartbeats.len())
+    /// Skip the track in this process's pipeline. Returns false if the station isn't
+    /// broadcasting here.
+    pub async fn skip_broadcaster(&self, station_id: Uuid) -> Result<bool> {
+        match self.running_broadcaster(station_id).await {
+            Some(broadcaster) => {
+                broadcaster.skip().await?;
+                tracing::info!("Skipped track in HLS pipeline for station {}", station_id);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Stop a station's broadcaster and pipeline, if it has any
+    async fn stop_broadcaster(&self, station_id: Uuid) {
+        let broadcaster = self.broadcasters.write().await.remove(&station_id);
+        if let Some(broadcaster) = broadcaster {
+            broadcaster.shutdown().await;
+            tracing::info!("Stopped broadcasting station {}", station_id);
+        }
+    }
+
+    /// Keep station ownership consistent across streamer nodes: take over stations
+    /// with listeners whose streamer went away, and stop broadcasters whose lease
+    /// another node holds (e.g. after this node lost Redis for longer than a lease)
+    pub fn spawn_failover(self: Arc<Self>, cluster: Arc<Cluster>) {
+        if cluster.role() != ServerRole::Streamer || !self.broadcast.enabled {
+            return;
+        }
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(FAILOVER_CHECK_SECONDS));
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.failover(&cluster).await {
+                    tracing::warn!("Station failover check failed: {}", e);
+                }
+            }
+        });
+    }
+
+    async fn failover(&self, cluster: &Cluster) -> Result<()> {
+        let running: Vec<Uuid> = self
+            .broadcasters
+            .read()
+            .await
+            .iter()
+            .filter(|(_, broadcaster)| broadcaster.is_running())
+            .map(|(id, _)| *id)
+            .collect();
+        for station_id in &running {
+            match cluster.lease_owner(*station_id).await? {
+                Some(owner) if owner != cluster.node_id() => {
+                    tracing::warn!("Station {} is now owned by streamer {}, stopping here", station_id, owner);
+                    self.stop_broadcaster(*station_id).await;
+                }
+                _ => {}
+            }
+        }
+
+        // Stations without listeners start on the next one's request
+        let orphans: Vec<Uuid> = self
+            .get_all_listener_counts()
+            .await
+            .into_iter()
+            .filter(|(station_id, listeners)| *listeners > 0 && !running.contains(station_id))
+            .map(|(station_id, _)| station_id)
+            .collect();
+        for station_id in cluster.take_over_orphans(&orphans).await? {
+            if let Err(e) = self.broadcaster(station_id).await {
+                tracing::warn!("Failed to take over station {}: {}", station_id, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn start_broadcaster_eagerly(&self, station_id: Uuid) {
+        if !(self.broadcast.enabled && self.broadcast.eager) {
+            return;
+        }
+        if let Err(e) = self.broadcaster(station_id).await {
+            tracing::warn!("Failed to start broadcasting station {}: {}", station_id, e);
+        }
+    }
+
+    /// A started pipeline with the station's curated tracks queued, or the track now
+    /// playing if it has none
+    async fn start_pipeline(&self, station: &Station) -> Result<AudioPipeline> {
+        let mut pipeline = AudioPipeline::new(
+            self.navidrome_client.clone(),
+            AudioPipelineConfig {
+                crossfade_seconds: station.config.crossfade_ms as f32 / 1000.0,
+                stream_options: self.broadcast.stream_options.clone(),
+                audio_cache: self.broadcast.audio_cache.clone(),
+                loudness_target_lufs: self.broadcast.loudness_target_lufs,
+                announcer: self
+                    .broadcast
+                    .announcer
+                    .clone()
+                    .filter(|_| station.config.announce_every > 0)
+                    .map(|announcer| StationAnnouncer {
+                        announcer,
+                        every: station.config.announce_every,
+                        voice: station.config.announcer_voice.clone(),
+                    }),
+                resample_quality: self.broadcast.resample_quality,
+                fallback: self.fallback_tracks(station).await,
+                ..Default::default()
+            },
+        );
+
+        if !station.track_ids.is_empty() {
+            self.queue_pool(&pipeline, station).await?;
+        } else if let Ok(np) = self.get_now_playing(station.id).await {
+            // No curated tracks - start with the station's current track
+            pipeline
+                .queue_track(QueuedTrack {
+                    track_id: np.track.id.clone(),
+                    title: np.track.title.clone(),
+                    artist: np.track.artist.clone(),
+                    album: np.track.album.clone(),
+                    loudness: self.track_loudness(&np.track.id).await,
+                    path: self.track_copy(&np.track.id).await,
+                    streamable: true,
+                })
+                .await?;
+            tracing::info!("Queued current track for station {} HLS stream", station.name);
         } else {
-            Err(AppError::NotFound("Station not active".to_string()))
+            tracing::warn!("No tracks available for station {} HLS stream", station.name);
+        }
+
+        pipeline.start().await?;
+        tracing::info!("Started audio pipeline for station {}", station.name);
+        Ok(pipeline)
+    }
+
+    /// Queue a station's curated tracks in order
+    async fn queue_pool(&self, pipeline: &AudioPipeline, station: &Station) -> Result<()> {
+        let mut tracks = self.indexed_tracks(&station.track_ids).await?;
+        let loudness = loudness::loudness_for(&self.db, &station.track_ids).await?;
+
+        // Queue tracks in order
+        for track_id in &station.track_ids {
+            if let Some(mut track) = tracks.remove(track_id) {
+                track.loudness = loudness.get(track_id).copied();
+                pipeline.queue_track(track).await?;
+            }
+        }
+        tracing::info!(
+            "Queued {} tracks for station {} HLS stream",
+            station.track_ids.len(),
+            station.name
+        );
+        Ok(())
+    }
+
+    /// Pipeline tracks for `ids` from the library index, by id (unindexed ids are left out)
+    async fn indexed_tracks(&self, ids: &[String]) -> Result<HashMap<String, QueuedTrack>> {
+        let rows: Vec<(String, String, String, String, Option<String>)> =
+            sqlx::query_as("SELECT id, title, artist, album, path FROM library_index WHERE id = ANY($1)")
+                .bind(ids)
+                .fetch_all(&self.db)
+                .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(id, title, artist, album, path)| {
+                let track = QueuedTrack {
+                    track_id: id.clone(),
+                    title,
+                    artist,
+                    album,
+                    loudness: None,
+                    path: self.local_copy(path.as_deref()),
+                    streamable: true,
+                };
+                (id, track)
+            })
+            .collect())
+    }
+
+    /// Where a track's file is in the mounted library (NAVIDROME_LIBRARY_PATH), given
+    /// its path relative to the music folder. The pipeline reads it from there instead
+    /// of streaming it from Navidrome while it exists.
+    fn local_copy(&self, relative_path: Option<&str>) -> Option<PathBuf> {
+        audio_pipeline::library_file(self.broadcast.library_path.as_ref()?, relative_path?)
+    }
+
+    /// `local_copy` of an indexed track, looked up by id
+    async fn track_copy(&self, track_id: &str) -> Option<PathBuf> {
+        self.broadcast.library_path.as_ref()?;
+        match self.indexed_tracks(&[track_id.to_string()]).await {
+            Ok(mut indexed) => indexed.remove(track_id)?.path,
+            Err(e) => {
+                tracing::warn!("Failed to look up the library path of track {}: {}", track_id, e);
+                None
+            }
         }
     }
 
+    /// What the pipeline plays while the station's tracks fail to load: its
+    /// `fallback_track_ids`, then the files of `FALLBACK_AUDIO_DIR` (its subdirectory
+    /// named after the station if there is one). Lookup failures leave tracks out.
+    async fn fallback_tracks(&self, station: &Station) -> Vec<QueuedTrack> {
+        let mut tracks = Vec::new();
+        let ids = &station.config.fallback_track_ids;
+        if !ids.is_empty() {
+            match self.indexed_tracks(ids).await {
+                Ok(mut indexed) => tracks.extend(ids.iter().filter_map(|id| indexed.remove(id))),
+                Err(e) => tracing::warn!("Failed to look up fallback tracks of station {}: {}", station.name, e),
+            }
+        }
+
+        if let Some(dir) = &self.broadcast.fallback_dir {
+            let station_dir = dir.join(station.id.to_string());
+            let dir = if station_dir.is_dir() { station_dir } else { dir.clone() };
+            match fallback_files(&dir).await {
+                Ok(files) => tracks.extend(files.into_iter().map(|path| {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                    QueuedTrack {
+                        track_id: format!("{}{}", FALLBACK_FILE_PREFIX, name),
+                        title: path.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+                        artist: String::new(),
+                        album: String::new(),
+                        loudness: None,
+                        path: Some(path),
+                        streamable: false,
+                    }
+                })),
+                Err(e) => tracing::warn!("Failed to read fallback audio in {:?}: {}", dir, e),
+            }
+        }
+        tracks
+    }
+
+    /// Stored loudness of a track; lookup failures just skip normalization
+    async fn track_loudness(&self, track_id: &str) -> Option<TrackLoudness> {
+        self.broadcast.loudness_target_lufs?;
+        match loudness::loudness_for(&self.db, &[track_id.to_string()]).await {
+            Ok(mut loudness) => loudness.remove(track_id),
+            Err(e) => {
+                tracing::warn!("Failed to read loudness of track {}: {}", track_id, e);
+                None
+            }
+        }
+    }
+
+    /// Keep the pipeline's queue filled with the station's next tracks until the
+    /// broadcaster stops, switching to the tracks of each program block that comes on air
+    /// and to the new pool of an auto-curated block
+    fn spawn_refill(
+        &self,
+        station_id: Uuid,
+        broadcaster: Arc<AudioBroadcaster>,
+        pipeline: Arc<AudioPipeline>,
+        mut program: Option<ProgramKey>,
+        mut pooled: bool,
+    ) {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let mut last_queued_track_id: Option<String> = None;
+            let mut schedule_check = tokio::time::Instant::now();
+
+            while broadcaster.is_running() {
+                if schedule_check.elapsed().as_secs() >= SCHEDULE_CHECK_SECONDS {
+                    schedule_check = tokio::time::Instant::now();
+                    match manager.programmed_station(station_id).await {
+                        Ok((station, block)) if program_key(block.as_ref()) != program => {
+                            let recurated = program.is_some_and(|(id, _)| block.as_ref().is_some_and(|b| b.id == id));
+                            program = program_key(block.as_ref());
+                            let switched = if recurated {
+                                tracing::info!("Station {} swaps in its program's newly curated tracks", station.name);
+                                manager.replace_pool(&pipeline, &station, pooled).await
+                            } else {
+                                manager.switch_program(&pipeline, &station, block.as_ref(), pooled).await
+                            };
+                            match switched {
+                                Ok(()) => pooled = !station.track_ids.is_empty(),
+                                Err(e) => tracing::error!("Failed to switch program of station {}: {:?}", station_id, e),
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(e) => tracing::debug!("Could not check schedule of station {}: {:?}", station_id, e),
+                    }
+                }
+
+                // If queue is running low (less than 2 tracks), add more
+                if pipeline.queue_length().await < 2 {
+                    match manager.get_now_playing(station_id).await {
+                        Ok(np) => {
+                            // Only queue if it's a different track than last time
+                            let track_id = np.track.id.clone();
+                            if last_queued_track_id.as_ref() != Some(&track_id) {
+                                let queued = QueuedTrack {
+                                    track_id: track_id.clone(),
+                                    title: np.track.title.clone(),
+                                    artist: np.track.artist.clone(),
+                                    album: np.track.album.clone(),
+                                    loudness: manager.track_loudness(&track_id).await,
+                                    path: manager.track_copy(&track_id).await,
+                                    streamable: true,
+                                };
+                                if let Err(e) = pipeline.queue_track(queued).await {
+                                    tracing::error!("Failed to queue track for station {}: {:?}", station_id, e);
+                                } else {
+                                    tracing::debug!("Refilled queue with track: {} for station {}", np.track.title, station_id);
+                                    last_queued_track_id = Some(track_id);
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            tracing::debug!("Could not get now playing for refill: {:?}", e);
+                        }
+                    }
+                }
+
+                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+            }
+            tracing::info!("Refill task ended for station {}", station_id);
+        });
+    }
+
+    /// Replace the queued pool of the previous program (if `pooled`) with the new one's.
+    /// Programs without a pool take over from the next track selected.
+    async fn switch_program(
+        &self,
+        pipeline: &AudioPipeline,
+        station: &Station,
+        program: Option<&ScheduleBlock>,
+        pooled: bool,
+    ) -> Result<()> {
+        match program {
+            Some(block) => tracing::info!("Station {} switched to program '{}'", station.name, block.name),
+            None => tracing::info!("Station {} switched back to its regular programming", station.name),
+        }
+        self.replace_pool(pipeline, station, pooled).await
+    }
+
+    /// Replace the queued pool (if `pooled`) with the station's, leaving the current
+    /// track playing
+    async fn replace_pool(&self, pipeline: &AudioPipeline, station: &Station, pooled: bool) -> Result<()> {
+        if pooled {
+            pipeline.clear_queue().await?;
+        }
+        if !station.track_ids.is_empty() {
+            self.queue_pool(pipeline, station).await?;
+        }
+        Ok(())
+    }
+
+    /// Scrobble the tracks a station's pipeline plays to Last.fm, until it stops
+    fn spawn_scrobbler(&self, station_id: Uuid, mut events: broadcast::Receiver<PipelineEvent>) {
+        let Some(lastfm) = self.lastfm.clone().filter(|lastfm| lastfm.can_scrobble()) else {
+            return;
+        };
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let mut playing: Option<(TrackState, DateTime<Utc>)> = None;
+            loop {
+                let (next, stopped) = match events.recv().await {
+                    Ok(PipelineEvent::TrackStarted(track)) => (Some((track, Utc::now())), false),
+                    Ok(PipelineEvent::TrackEnded { .. }) => (None, false),
+                    Ok(PipelineEvent::Stopped) | Err(broadcast::error::RecvError::Closed) => (None, true),
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                };
+                if let Some((track, started_at)) = std::mem::replace(&mut playing, next) {
+                    manager.scrobble_played(station_id, &lastfm, &track, started_at).await;
+                }
+                if stopped {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Queue a Last.fm scrobble of a track the pipeline finished or moved past
+    async fn scrobble_played(&self, station_id: Uuid, lastfm: &LastFmClient, track: &TrackState, started_at: DateTime<Utc>) {
+        // Fallback files aren't library tracks
+        if track.track_id.starts_with(FALLBACK_FILE_PREFIX) {
+            return;
+        }
+        match self.get_station_by_id(station_id).await {
+            Ok(station) if station.config.scrobble => {}
+            Ok(_) => return,
+            Err(e) => {
+                tracing::debug!("Not scrobbling '{}': {}", track.title, e);
+                return;
+            }
+        }
+        if let Err(e) = lastfm.track_played(station_id, track, started_at, Utc::now()).await {
+            tracing::warn!("Failed to queue Last.fm scrobble of '{}': {}", track.title, e);
+        }
+    }
+
+    /// Record a heartbeat for a listener session. Returns the current listener count.
+    pub async fn listener_heartbeat(&self, station_id: Uuid, session_id: String) -> Result<usize> {
+        if !self.active_stations.read().await.contains_key(&station_id) {
+            return Err(AppError::NotFound("Station not active".to_string()));
+        }
+
+        let key = listeners_key(station_id);
+        let now = Utc::now().timestamp_millis();
+        let mut conn = self.redis.clone();
+        // Stale sessions are left to the sweep, which records them leaving
+        let (added, count): (usize, usize) = redis::pipe()
+            .atomic()
+            .zadd(&key, &session_id, now)
+            .expire(&key, LISTENERS_TTL_SECONDS)
+            .ignore()
+            .zcount(&key, listener_cutoff(now), "+inf")
+            .query_async(&mut conn)
+            .await?;
+
+        if added > 0 {
+            let event = AnalyticsEvent::new(station_id, StationEventKind::ListenerJoin)
+                .session(session_id)
+                .listeners(count);
+            station_analytics::record(&self.db, event).await;
+        }
+        Ok(count)
+    }
+
     /// Remove a listener session
     pub async fn listener_leave(&self, station_id: Uuid, session_id: &str) -> Result<()> {
-        let mut stations = self.active_stations.write().await;
-        if let Some(active) = stations.get_mut(&station_id) {
-            active.listener_heartbeats.remove(session_id);
+        let mut conn = self.redis.clone();
+        let removed: usize = conn.zrem(listeners_key(station_id), session_id).await?;
+        if removed > 0 {
+            let event = AnalyticsEvent::new(station_id, StationEventKind::ListenerLeave).session(session_id);
+            station_analytics::record(&self.db, event).await;
         }
         Ok(())
     }
 
-    /// Get the current listener count for a station
-    pub async fn get_listener_count(&self, station_id: Uuid) -> Result<usize> {
-        let now = Utc::now();
-        let timeout = Duration::seconds(LISTENER_TIMEOUT_SECONDS);
+    /// Periodically drop listener sessions that stopped sending heartbeats, recording
+    /// them leaving at their last heartbeat. Every replica sweeps; each session is
+    /// removed, and recorded, by one of them.
+    pub fn spawn_listener_sweep(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(LISTENER_TIMEOUT_SECONDS as u64));
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.sweep_listeners().await {
+                    tracing::warn!("Listener sweep failed: {}", e);
+                }
+            }
+        });
+    }
 
-        let stations = self.active_stations.read().await;
-        if let Some(active) = stations.get(&station_id) {
-            let count = active
-                .listener_heartbeats
-                .values()
-                .filter(|&last_heartbeat| now - *last_heartbeat < timeout)
-                .count();
-            Ok(count)
-        } else {
-            Ok(0)
+    async fn sweep_listeners(&self) -> Result<()> {
+        let station_ids: Vec<Uuid> = self.active_stations.read().await.keys().copied().collect();
+        let cutoff = listener_cutoff(Utc::now().timestamp_millis());
+        let mut conn = self.redis.clone();
+
+        for station_id in station_ids {
+            let key = listeners_key(station_id);
+            let stale: Vec<(String, f64)> = conn.zrangebyscore_withscores(&key, "-inf", cutoff - 1).await?;
+            for (session_id, last_heartbeat) in stale {
+                let removed: i64 = redis::Script::new(REMOVE_IF_STALE)
+                    .key(&key)
+                    .arg(&session_id)
+                    .arg(cutoff)
+                    .invoke_async(&mut conn)
+                    .await?;
+                if removed > 0 {
+                    let left_at = DateTime::from_timestamp_millis(last_heartbeat as i64).unwrap_or_else(Utc::now);
+                    let event = AnalyticsEvent::new(station_id, StationEventKind::ListenerLeave)
+                        .session(session_id)
+                        .details(serde_json::json!({ "reason": "timeout" }))
+                        .at(left_at);
+                    station_analytics::record(&self.db, event).await;
+                }
+            }
         }
+        Ok(())
+    }
+
+    /// Get the current listener count for a station, across all replicas
+    pub async fn get_listener_count(&self, station_id: Uuid) -> Result<usize> {
+        let mut conn = self.redis.clone();
+        let count: usize = conn
+            .zcount(listeners_key(station_id), listener_cutoff(Utc::now().timestamp_millis()), "+inf")
+            .await?;
+        Ok(count)
     }
 
-    /// Get listener counts for all active stations
+    /// Get listener counts for all active stations, across all replicas. Stations
+    /// count 0 while Redis is unreachable.
     pub async fn get_all_listener_counts(&self) -> HashMap<Uuid, usize> {
-        let now = Utc::now();
-        let timeout = Duration::seconds(LISTENER_TIMEOUT_SECONDS);
+        let station_ids: Vec<Uuid> = self.active_stations.read().await.keys().copied().collect();
+        if station_ids.is_empty() {
+            return HashMap::new();
+        }
 
-        let stations = self.active_stations.read().await;
-        stations
-            .iter()
-            .map(|(id, active)| {
-                let count = active
-                    .listener_heartbeats
-                    .values()
-                    .filter(|&last_heartbeat| now - *last_heartbeat < timeout)
-                    .count();
-                (*id, count)
-            })
-            .collect()
+        let cutoff = listener_cutoff(Utc::now().timestamp_millis());
+        let mut pipe = redis::pipe();
+        for station_id in &station_ids {
+            pipe.zcount(listeners_key(*station_id), cutoff, "+inf");
+        }
+        let mut conn = self.redis.clone();
+        let counts: Vec<usize> = match pipe.query_async(&mut conn).await {
+            Ok(counts) => counts,
+            Err(e) => {
+                tracing::warn!("Failed to count listeners: {}", e);
+                vec![0; station_ids.len()]
+            }
+        };
+        station_ids.into_iter().zip(counts).collect()
+    }
+
+    /// A station as it plays now: with the genres or tracks of the program block on
+    /// air, if any. Schedule lookup failures fall back to the station's own.
+    async fn programmed_station(&self, station_id: Uuid) -> Result<(Station, Option<ScheduleBlock>)> {
+        let station = self.get_station_by_id(station_id).await?;
+        match schedule::active_block(&self.db, &station, Utc::now()).await {
+            Ok(Some(block)) => Ok((block.apply(&station), Some(block))),
+            Ok(None) => Ok((station, None)),
+            Err(e) => {
+                tracing::warn!("Failed to read schedule of station {}: {}", station.name, e);
+                Ok((station, None))
+            }
+        }
+    }
+
+    /// The track of the station's top pending request, if any. Requests whose track
+    /// Navidrome no longer has are skipped.
+    async fn requested_track(&self, station_id: Uuid) -> Option<Track> {
+        loop {
+            let track_id = match track_requests::take_next(&self.db, station_id).await {
+                Ok(Some(track_id)) => track_id,
+                Ok(None) => return None,
+                Err(e) => {
+                    tracing::warn!("Failed to read requests of station {}: {}", station_id, e);
+                    return None;
+                }
+            };
+            match self.navidrome_client.get_track(&track_id).await {
+                Ok(track) => return Some(track),
+                Err(e) => tracing::warn!("Skipping request for track {}: {}", track_id, e),
+            }
+        }
     }
 
     async fn get_station_by_id(&self, station_id: Uuid) -> Result<Station> {
@@ -320,21 +1378,6 @@ impl StationManager {
             .ok_or_else(|| AppError::NotFound("Station not found".to_string()))
     }
 
-    async fn get_recent_tracks(&self, station_id: Uuid, limit: i64) -> Result<Vec<String>> {
-        let tracks: Vec<(String,)> = sqlx::query_as(
-            "SELECT track_id FROM playlist_history
-             WHERE station_id = $1
-             ORDER BY played_at DESC
-             LIMIT $2",
-        )
-        .bind(station_id)
-        .bind(limit)
-        .fetch_all(&self.db)
-        .await?;
-
-        Ok(tracks.into_iter().map(|(id,)| id).collect())
-    }
-
     pub fn get_stream_url(&self, track_id: &str) -> String {
         // For MVP, we'll proxy directly to Navidrome
         format!("/api/stream/{}", track_id)
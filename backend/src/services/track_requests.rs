@@ -0,0 +1,230 @@
+//! Listener track requests
+//!
+//! Signed-in listeners request tracks from the library on stations that accept them.
+//! Requesting a track that is already pending votes for it instead, and other listeners
+//! can vote too. When the station picks its next track, the pending request with the
+//! most votes (the oldest on a tie) plays before any curated selection.
+//!
+//! Each user can make `REQUESTS_PER_HOUR` requests; votes are not limited. Requests
+//! that stay pending for `REQUEST_TTL_HOURS` expire.
+
+use crate::error::{AppError, Result};
+use crate::models::{Station, TrackRequest};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Pending requests expire after this long
+const REQUEST_TTL_HOURS: i64 = 12;
+
+/// A track played within this many of the station's last tracks can't be requested
+const RECENT_PLAYS: i64 = 20;
+
+/// Requests with their track, requester and votes. `$1` is the viewer whose votes
+/// fill `voted`.
+const SELECT_REQUESTS: &str = r#"
+    SELECT r.id, r.station_id, r.track_id, l.title, l.artist, l.album,
+           r.requested_by, u.username AS requested_by_username, r.status,
+           (SELECT COUNT(*) FROM track_request_votes v WHERE v.request_id = r.id) AS votes,
+           EXISTS(SELECT 1 FROM track_request_votes v WHERE v.request_id = r.id AND v.user_id = $1) AS voted,
+           r.created_at, r.played_at
+    FROM track_requests r
+    JOIN users u ON u.id = r.requested_by
+    LEFT JOIN library_index l ON l.id = r.track_id
+"#;
+
+/// A station's pending requests, in the order they will play
+pub async fn list(db: &PgPool, station_id: Uuid, viewer: Option<Uuid>) -> Result<Vec<TrackRequest>> {
+    expire(db).await?;
+
+    let requests = sqlx::query_as::<_, TrackRequest>(&format!(
+        "{} WHERE r.station_id = $2 AND r.status = 'pending' ORDER BY votes DESC, r.created_at",
+        SELECT_REQUESTS
+    ))
+    .bind(viewer)
+    .bind(station_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(requests)
+}
+
+pub async fn get(db: &PgPool, request_id: Uuid, viewer: Option<Uuid>) -> Result<TrackRequest> {
+    sqlx::query_as::<_, TrackRequest>(&format!("{} WHERE r.id = $2", SELECT_REQUESTS))
+        .bind(viewer)
+        .bind(request_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Request not found".to_string()))
+}
+
+/// Request a track on a station, or vote for it if it is already pending
+pub async fn create(
+    db: &PgPool,
+    station: &Station,
+    user_id: Uuid,
+    track_id: &str,
+    per_hour: u32,
+) -> Result<TrackRequest> {
+    if !station.config.accept_requests {
+        return Err(AppError::Conflict("This station doesn't take requests".to_string()));
+    }
+
+    // Per-user rate limit: retry when the oldest request of the last hour ages out
+    let (recent, oldest): (i64, Option<f64>) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*), EXTRACT(EPOCH FROM (MIN(created_at) + INTERVAL '1 hour' - NOW()))::float8
+        FROM track_requests
+        WHERE requested_by = $1 AND created_at > NOW() - INTERVAL '1 hour'
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(db)
+    .await?;
+    if recent >= per_hour as i64 {
+        return Err(AppError::TooManyRequests {
+            retry_after_secs: oldest.unwrap_or(3600.0).ceil().max(1.0) as u64,
+        });
+    }
+
+    let known: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM library_index WHERE id = $1)")
+        .bind(track_id)
+        .fetch_one(db)
+        .await?;
+    if !known {
+        return Err(AppError::NotFound("Track not found".to_string()));
+    }
+
+    let recently_played: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM (
+                SELECT track_id FROM playlist_history
+                WHERE station_id = $1
+                ORDER BY played_at DESC
+                LIMIT $3
+            ) recent
+            WHERE track_id = $2
+        )
+        "#,
+    )
+    .bind(station.id)
+    .bind(track_id)
+    .bind(RECENT_PLAYS)
+    .fetch_one(db)
+    .await?;
+    if recently_played {
+        return Err(AppError::Conflict("This track was played recently".to_string()));
+    }
+
+    expire(db).await?;
+    let created: Option<Uuid> = sqlx::query_scalar(
+        r#"
+        INSERT INTO track_requests (station_id, track_id, requested_by)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (station_id, track_id) WHERE status = 'pending' DO NOTHING
+        RETURNING id
+        "#,
+    )
+    .bind(station.id)
+    .bind(track_id)
+    .bind(user_id)
+    .fetch_optional(db)
+    .await?;
+
+    let request_id = match created {
+        Some(id) => {
+            tracing::info!("User {} requested track {} on station {}", user_id, track_id, station.name);
+            id
+        }
+        // Already pending: count this as a vote
+        None => sqlx::query_scalar(
+            "SELECT id FROM track_requests WHERE station_id = $1 AND track_id = $2 AND status = 'pending'",
+        )
+        .bind(station.id)
+        .bind(track_id)
+        .fetch_one(db)
+        .await?,
+    };
+
+    add_vote(db, request_id, user_id).await?;
+    get(db, request_id, Some(user_id)).await
+}
+
+/// Vote for a pending request. Voting twice counts once.
+pub async fn vote(db: &PgPool, request_id: Uuid, user_id: Uuid) -> Result<TrackRequest> {
+    pending_station(db, request_id).await?;
+    add_vote(db, request_id, user_id).await?;
+    get(db, request_id, Some(user_id)).await
+}
+
+/// Withdraw a vote from a pending request
+pub async fn unvote(db: &PgPool, request_id: Uuid, user_id: Uuid) -> Result<TrackRequest> {
+    pending_station(db, request_id).await?;
+    sqlx::query("DELETE FROM track_request_votes WHERE request_id = $1 AND user_id = $2")
+        .bind(request_id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    get(db, request_id, Some(user_id)).await
+}
+
+/// The station of a request that can still be voted on
+pub async fn pending_station(db: &PgPool, request_id: Uuid) -> Result<Uuid> {
+    expire(db).await?;
+
+    let request: Option<(Uuid, String)> =
+        sqlx::query_as("SELECT station_id, status FROM track_requests WHERE id = $1")
+            .bind(request_id)
+            .fetch_optional(db)
+            .await?;
+
+    match request {
+        Some((station_id, status)) if status == "pending" => Ok(station_id),
+        Some(_) => Err(AppError::Conflict("This request is no longer pending".to_string())),
+        None => Err(AppError::NotFound("Request not found".to_string())),
+    }
+}
+
+/// Mark the station's top request as played and return its track, if any is pending
+pub async fn take_next(db: &PgPool, station_id: Uuid) -> Result<Option<String>> {
+    expire(db).await?;
+
+    let track_id = sqlx::query_scalar(
+        r#"
+        UPDATE track_requests SET status = 'played', played_at = NOW()
+        WHERE id = (
+            SELECT r.id FROM track_requests r
+            WHERE r.station_id = $1 AND r.status = 'pending'
+            ORDER BY (SELECT COUNT(*) FROM track_request_votes v WHERE v.request_id = r.id) DESC, r.created_at
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING track_id
+        "#,
+    )
+    .bind(station_id)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(track_id)
+}
+
+async fn add_vote(db: &PgPool, request_id: Uuid, user_id: Uuid) -> Result<()> {
+    sqlx::query("INSERT INTO track_request_votes (request_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+        .bind(request_id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+async fn expire(db: &PgPool) -> Result<()> {
+    sqlx::query(
+        "UPDATE track_requests SET status = 'expired'
+         WHERE status = 'pending' AND created_at < NOW() - make_interval(hours => $1)",
+    )
+    .bind(REQUEST_TTL_HOURS as i32)
+    .execute(db)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,193 @@
+//! Export of everything stored about a user
+//!
+//! Backs `GET /me/export`, so users on a shared instance can take a copy of their
+//! data. Account deletion itself lives in [`AuthService`](crate::services::AuthService);
+//! the schema cascades or anonymizes the rows listed here.
+
+use crate::error::Result;
+use crate::models::{ApiKey, User};
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Every row tied to `user`, grouped by kind, as one JSON document
+pub async fn export(db: &PgPool, user: &User) -> Result<serde_json::Value> {
+    let listening_history = rows(
+        db,
+        user.id,
+        r#"
+        SELECT h.station_id, s.name AS station_name, h.track_id, l.title, l.artist, l.album,
+               h.first_heard_at, h.last_heard_at
+        FROM listening_history h
+        JOIN stations s ON s.id = h.station_id
+        LEFT JOIN library_index l ON l.id = h.track_id
+        WHERE h.user_id = $1
+        ORDER BY h.last_heard_at
+        "#,
+    )
+    .await?;
+
+    let ratings = rows(
+        db,
+        user.id,
+        r#"
+        SELECT r.track_id, l.title, l.artist, r.rating, r.rated_at, r.updated_at
+        FROM user_track_ratings r
+        LEFT JOIN library_index l ON l.id = r.track_id
+        WHERE r.user_id = $1
+        ORDER BY r.rated_at
+        "#,
+    )
+    .await?;
+
+    let favorite_stations = rows(
+        db,
+        user.id,
+        r#"
+        SELECT f.station_id, s.name, f.created_at
+        FROM user_favorite_stations f
+        JOIN stations s ON s.id = f.station_id
+        WHERE f.user_id = $1
+        ORDER BY f.created_at
+        "#,
+    )
+    .await?;
+
+    let favorite_tracks = rows(
+        db,
+        user.id,
+        r#"
+        SELECT f.track_id, l.title, l.artist, l.album, f.created_at
+        FROM user_favorite_tracks f
+        LEFT JOIN library_index l ON l.id = f.track_id
+        WHERE f.user_id = $1
+        ORDER BY f.created_at
+        "#,
+    )
+    .await?;
+
+    let stations = rows(
+        db,
+        user.id,
+        r#"
+        SELECT id, path, name, description, genres, mood_tags, created_at, updated_at,
+               daily_mix_user_id IS NOT NULL AS daily_mix
+        FROM stations
+        WHERE created_by = $1
+        ORDER BY created_at
+        "#,
+    )
+    .await?;
+
+    let collaborations = rows(
+        db,
+        user.id,
+        r#"
+        SELECT c.station_id, s.name AS station_name, c.role, c.created_at
+        FROM station_permissions c
+        JOIN stations s ON s.id = c.station_id
+        WHERE c.user_id = $1
+        ORDER BY c.created_at
+        "#,
+    )
+    .await?;
+
+    let track_feedback = rows(
+        db,
+        user.id,
+        r#"
+        SELECT f.track_id, l.title, l.artist, l.album,
+               CASE WHEN f.value > 0 THEN 'up' ELSE 'down' END AS thumb,
+               f.station_id, f.created_at, f.updated_at
+        FROM user_track_feedback f
+        LEFT JOIN library_index l ON l.id = f.track_id
+        WHERE f.user_id = $1
+        ORDER BY f.created_at
+        "#,
+    )
+    .await?;
+
+    let track_requests = rows(
+        db,
+        user.id,
+        r#"
+        SELECT r.station_id, s.name AS station_name, r.track_id, l.title, l.artist,
+               r.requested_by = $1 AS requested, r.status, r.created_at, r.played_at
+        FROM track_requests r
+        JOIN stations s ON s.id = r.station_id
+        LEFT JOIN library_index l ON l.id = r.track_id
+        WHERE r.requested_by = $1
+           OR EXISTS(SELECT 1 FROM track_request_votes v WHERE v.request_id = r.id AND v.user_id = $1)
+        ORDER BY r.created_at
+        "#,
+    )
+    .await?;
+
+    let sessions = rows(
+        db,
+        user.id,
+        r#"
+        SELECT id, user_agent, ip, created_at, last_seen_at, expires_at, revoked_at
+        FROM user_sessions
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+    )
+    .await?;
+
+    let navidrome_account = rows(
+        db,
+        user.id,
+        r#"
+        SELECT navidrome_username, auth_type, created_at, updated_at, last_verified_at
+        FROM user_navidrome_accounts
+        WHERE user_id = $1
+        "#,
+    )
+    .await?;
+
+    let scrobble_accounts = rows(
+        db,
+        user.id,
+        r#"
+        SELECT service, username, created_at
+        FROM user_scrobble_accounts
+        WHERE user_id = $1
+        ORDER BY service
+        "#,
+    )
+    .await?;
+
+    let api_keys = sqlx::query_as::<_, ApiKey>(
+        "SELECT * FROM api_keys WHERE created_by = $1 ORDER BY created_at",
+    )
+    .bind(user.id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(serde_json::json!({
+        "exported_at": Utc::now(),
+        "account": user,
+        "listening_history": listening_history,
+        "ratings": ratings,
+        "track_feedback": track_feedback,
+        "favorites": {
+            "stations": favorite_stations,
+            "tracks": favorite_tracks,
+        },
+        "stations": stations,
+        "collaborations": collaborations,
+        "track_requests": track_requests,
+        "sessions": sessions,
+        "api_keys": api_keys,
+        "navidrome_account": navidrome_account,
+        "scrobble_accounts": scrobble_accounts,
+    }))
+}
+
+/// Run a query with the user ID as `$1` and collect its rows as a JSON array
+async fn rows(db: &PgPool, user_id: Uuid, query: &str) -> Result<serde_json::Value> {
+    let sql = format!("SELECT COALESCE(jsonb_agg(t), '[]'::jsonb) FROM ({}) t", query);
+    let rows = sqlx::query_scalar(&sql).bind(user_id).fetch_one(db).await?;
+    Ok(rows)
+}
@@ -0,0 +1,252 @@
+//! Approximate nearest neighbor index on `track_embeddings.embedding`
+//!
+//! Similarity search orders embeddings by L2 distance, which pgvector answers from an
+//! HNSW or IVFFlat index instead of scanning every row. HNSW (the default) has better
+//! recall at the same speed and needs no training; IVFFlat builds faster, but its lists
+//! are fixed from the embeddings present at build time, so it should be rebuilt after
+//! large imports. The index can be rebuilt at runtime with other parameters, and
+//! `ef_search` (HNSW) and `probes` (IVFFlat) trade recall for speed per query.
+//!
+//! Queries take a pool of nearest candidates from the index and apply their genre and
+//! exclusion filters to it, so a very selective filter can return fewer tracks than asked.
+
+use crate::error::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+
+/// Name of the index used by similarity search
+pub const INDEX_NAME: &str = "idx_track_embeddings_vector";
+
+/// A rebuild creates the new index under this name and swaps it in when done
+const BUILD_INDEX_NAME: &str = "idx_track_embeddings_vector_new";
+
+/// Candidates fetched from the index per requested track, before filtering
+const CANDIDATES_PER_RESULT: usize = 20;
+const MIN_CANDIDATES: usize = 200;
+/// pgvector's upper bound for `hnsw.ef_search`, which caps how many rows an HNSW scan returns
+const MAX_CANDIDATES: usize = 1000;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VectorIndexKind {
+    Hnsw,
+    Ivfflat,
+}
+
+/// How to build the index
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct VectorIndexParams {
+    pub kind: VectorIndexKind,
+    /// HNSW: connections per node (2-100)
+    #[serde(default = "default_m")]
+    pub m: u32,
+    /// HNSW: candidate list size while building (at least 2 * m, up to 1000)
+    #[serde(default = "default_ef_construction")]
+    pub ef_construction: u32,
+    /// IVFFlat: number of lists (default: one per 1000 embeddings, at least 10)
+    #[serde(default)]
+    pub lists: Option<u32>,
+}
+
+fn default_m() -> u32 {
+    16
+}
+
+fn default_ef_construction() -> u32 {
+    64
+}
+
+impl VectorIndexParams {
+    pub fn validate(&self) -> Result<()> {
+        match self.kind {
+            VectorIndexKind::Hnsw => {
+                if !(2..=100).contains(&self.m) {
+                    return Err(AppError::Validation("m must be between 2 and 100".to_string()));
+                }
+                if self.ef_construction < 2 * self.m || self.ef_construction > 1000 {
+                    return Err(AppError::Validation(
+                        "ef_construction must be between 2 * m and 1000".to_string(),
+                    ));
+                }
+            }
+            VectorIndexKind::Ivfflat => {
+                if self.lists.is_some_and(|lists| !(1..=32768).contains(&lists)) {
+                    return Err(AppError::Validation("lists must be between 1 and 32768".to_string()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `CREATE INDEX` statement for these parameters (`embeddings` sizes IVFFlat lists)
+    fn create_sql(&self, name: &str, embeddings: i64) -> String {
+        match self.kind {
+            VectorIndexKind::Hnsw => format!(
+                "CREATE INDEX CONCURRENTLY {} ON track_embeddings \
+                 USING hnsw (embedding vector_l2_ops) WITH (m = {}, ef_construction = {})",
+                name, self.m, self.ef_construction
+            ),
+            VectorIndexKind::Ivfflat => {
+                let lists = self
+                    .lists
+                    .unwrap_or_else(|| (embeddings / 1000).clamp(10, 32768) as u32);
+                format!(
+                    "CREATE INDEX CONCURRENTLY {} ON track_embeddings \
+                     USING ivfflat (embedding vector_l2_ops) WITH (lists = {})",
+                    name, lists
+                )
+            }
+        }
+    }
+}
+
+/// Query-time recall settings (HNSW_EF_SEARCH, IVFFLAT_PROBES)
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct VectorSearchConfig {
+    /// HNSW: candidate list size per scan (raised to the candidate pool if smaller)
+    pub ef_search: u32,
+    /// IVFFlat: lists searched per query
+    pub probes: u32,
+}
+
+impl Default for VectorSearchConfig {
+    fn default() -> Self {
+        Self {
+            ef_search: 100,
+            probes: 10,
+        }
+    }
+}
+
+impl VectorSearchConfig {
+    /// Start a transaction whose queries use these settings. `candidates` is the number
+    /// of rows the query takes from the index, which HNSW can only return if
+    /// `ef_search` is at least as large.
+    pub async fn begin(&self, db: &PgPool, candidates: i64) -> Result<Transaction<'static, Postgres>> {
+        let mut tx = db.begin().await?;
+        let ef_search = (self.ef_search as i64).max(candidates).min(MAX_CANDIDATES as i64);
+        sqlx::query("SELECT set_config('hnsw.ef_search', $1, true), set_config('ivfflat.probes', $2, true)")
+            .bind(ef_search.to_string())
+            .bind(self.probes.max(1).to_string())
+            .execute(&mut *tx)
+            .await?;
+        Ok(tx)
+    }
+}
+
+/// Size of the candidate pool taken from the index for a query returning `limit`
+/// tracks after skipping `excluded` ones
+pub fn candidate_limit(limit: usize, excluded: usize) -> i64 {
+    (limit * CANDIDATES_PER_RESULT + excluded).clamp(MIN_CANDIDATES, MAX_CANDIDATES) as i64
+}
+
+/// The current index and search settings
+#[derive(Debug, Serialize)]
+pub struct VectorIndexInfo {
+    /// None if there is no index (similarity search scans every embedding)
+    pub kind: Option<VectorIndexKind>,
+    pub definition: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub embeddings: i64,
+    pub search: VectorSearchConfig,
+}
+
+pub async fn index_info(db: &PgPool, search: VectorSearchConfig) -> Result<VectorIndexInfo> {
+    let index: Option<(String, i64)> = sqlx::query_as(
+        r#"
+        SELECT indexdef, pg_relation_size(format('%I.%I', schemaname, indexname)::regclass)
+        FROM pg_indexes
+        WHERE tablename = 'track_embeddings' AND indexname = $1
+        "#,
+    )
+    .bind(INDEX_NAME)
+    .fetch_optional(db)
+    .await?;
+    let embeddings: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM track_embeddings")
+        .fetch_one(db)
+        .await?;
+
+    let kind = index.as_ref().and_then(|(definition, _)| {
+        let definition = definition.to_lowercase();
+        if definition.contains("using hnsw") {
+            Some(VectorIndexKind::Hnsw)
+        } else if definition.contains("using ivfflat") {
+            Some(VectorIndexKind::Ivfflat)
+        } else {
+            None
+        }
+    });
+
+    Ok(VectorIndexInfo {
+        kind,
+        size_bytes: index.as_ref().map(|(_, size)| *size),
+        definition: index.map(|(definition, _)| definition),
+        embeddings,
+        search,
+    })
+}
+
+/// Build a new index with `params` next to the current one and swap it in. Similarity
+/// search keeps using the old index while the new one builds.
+pub async fn rebuild(db: &PgPool, params: &VectorIndexParams) -> Result<()> {
+    params.validate()?;
+
+    let embeddings: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM track_embeddings")
+        .fetch_one(db)
+        .await?;
+
+    // Left behind (invalid) if an earlier build was interrupted
+    sqlx::query(&format!("DROP INDEX CONCURRENTLY IF EXISTS {}", BUILD_INDEX_NAME))
+        .execute(db)
+        .await?;
+    sqlx::query(&params.create_sql(BUILD_INDEX_NAME, embeddings))
+        .execute(db)
+        .await?;
+    sqlx::query(&format!("DROP INDEX CONCURRENTLY IF EXISTS {}", INDEX_NAME))
+        .execute(db)
+        .await?;
+    sqlx::query(&format!("ALTER INDEX {} RENAME TO {}", BUILD_INDEX_NAME, INDEX_NAME))
+        .execute(db)
+        .await?;
+
+    tracing::info!("Rebuilt {} index on {} embeddings", INDEX_NAME, embeddings);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_sql() {
+        let hnsw = VectorIndexParams {
+            kind: VectorIndexKind::Hnsw,
+            m: 24,
+            ef_construction: 100,
+            lists: None,
+        };
+        assert!(hnsw.validate().is_ok());
+        assert!(hnsw
+            .create_sql("idx", 0)
+            .ends_with("USING hnsw (embedding vector_l2_ops) WITH (m = 24, ef_construction = 100)"));
+
+        let ivfflat = VectorIndexParams {
+            kind: VectorIndexKind::Ivfflat,
+            m: default_m(),
+            ef_construction: default_ef_construction(),
+            lists: None,
+        };
+        assert!(ivfflat.create_sql("idx", 50_000).ends_with("WITH (lists = 50)"));
+        assert!(ivfflat.create_sql("idx", 500).ends_with("WITH (lists = 10)"));
+
+        let too_narrow = VectorIndexParams { ef_construction: 20, ..hnsw };
+        assert!(too_narrow.validate().is_err());
+    }
+
+    #[test]
+    fn test_candidate_limit() {
+        assert_eq!(candidate_limit(1, 0), MIN_CANDIDATES as i64);
+        assert_eq!(candidate_limit(20, 50), 450);
+        assert_eq!(candidate_limit(500, 0), MAX_CANDIDATES as i64);
+    }
+}
@@ -0,0 +1,199 @@
+//! Outbound webhooks
+//!
+//! Station and system events are delivered as signed JSON POSTs to every active
+//! webhook subscribed to the event. The body is signed with HMAC-SHA256 using the
+//! webhook's secret and sent in the `X-Webhook-Signature: sha256=<hex>` header.
+//! Each delivery is recorded in `webhook_deliveries` and retried with backoff. Only the
+//! latest `MAX_DELIVERIES_KEPT` deliveries of each webhook are kept. Retries are
+//! scheduled in memory, so deliveries still pending when the process exits stay
+//! `pending` and are not sent again.
+
+use crate::error::Result;
+use crate::models::{Webhook, WebhookEvent};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::PgPool;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Attempts per delivery before it is marked failed
+const MAX_DELIVERY_ATTEMPTS: i32 = 5;
+
+/// Delay before the first retry (doubles each retry)
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+
+/// Timeout for a single delivery request
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Deliveries kept per webhook; older ones are deleted as new ones are recorded
+const MAX_DELIVERIES_KEPT: i64 = 500;
+
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    db: PgPool,
+    client: reqwest::Client,
+}
+
+impl WebhookDispatcher {
+    pub fn new(db: PgPool) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+
+        Self { db, client }
+    }
+
+    /// Deliver an event to all subscribed webhooks in the background.
+    /// Never fails the caller; lookup and delivery errors are logged.
+    pub fn dispatch(&self, event: WebhookEvent, data: serde_json::Value) {
+        let dispatcher = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = dispatcher.deliver_all(event, data).await {
+                tracing::warn!("Failed to dispatch {} webhooks: {}", event, e);
+            }
+        });
+    }
+
+    /// Announce the result of an AI or hybrid curation request
+    pub fn curation_completed(&self, method: &str, query: &str, track_ids: &[String], user_id: Option<Uuid>) {
+        self.dispatch(
+            WebhookEvent::CurationCompleted,
+            serde_json::json!({
+                "method": method,
+                "query": query,
+                "track_ids": track_ids,
+                "track_count": track_ids.len(),
+                "user_id": user_id,
+            }),
+        );
+    }
+
+    async fn deliver_all(&self, event: WebhookEvent, data: serde_json::Value) -> Result<()> {
+        let webhooks: Vec<Webhook> = sqlx::query_as("SELECT * FROM webhooks WHERE active = true")
+            .fetch_all(&self.db)
+            .await?;
+
+        for webhook in webhooks.into_iter().filter(|w| w.wants(event)) {
+            let payload = serde_json::json!({
+                "event": event,
+                "timestamp": Utc::now(),
+                "data": data,
+            });
+
+            let delivery_id: Uuid = sqlx::query_scalar(
+                "INSERT INTO webhook_deliveries (webhook_id, event, payload) VALUES ($1, $2, $3) RETURNING id",
+            )
+            .bind(webhook.id)
+            .bind(event.as_str())
+            .bind(&payload)
+            .fetch_one(&self.db)
+            .await?;
+            self.prune_deliveries(webhook.id).await?;
+
+            let dispatcher = self.clone();
+            tokio::spawn(async move {
+                dispatcher.deliver(&webhook, delivery_id, event, &payload).await;
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Delete a webhook's deliveries beyond the latest `MAX_DELIVERIES_KEPT`
+    async fn prune_deliveries(&self, webhook_id: Uuid) -> Result<()> {
+        sqlx::query(
+            r#"
+            DELETE FROM webhook_deliveries
+            WHERE webhook_id = $1 AND id NOT IN (
+                SELECT id FROM webhook_deliveries
+                WHERE webhook_id = $1
+                ORDER BY created_at DESC
+                LIMIT $2
+            )
+            "#,
+        )
+        .bind(webhook_id)
+        .bind(MAX_DELIVERIES_KEPT)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Deliver one payload to one webhook, retrying with exponential backoff
+    async fn deliver(&self, webhook: &Webhook, delivery_id: Uuid, event: WebhookEvent, payload: &serde_json::Value) {
+        let body = payload.to_string();
+        let signature = sign(&webhook.secret, body.as_bytes());
+
+        for attempt in 1..=MAX_DELIVERY_ATTEMPTS {
+            let result = self
+                .client
+                .post(&webhook.url)
+                .header("Content-Type", "application/json")
+                .header("X-Webhook-Event", event.as_str())
+                .header("X-Webhook-Delivery", delivery_id.to_string())
+                .header("X-Webhook-Signature", format!("sha256={}", signature))
+                .body(body.clone())
+                .send()
+                .await;
+
+            let (response_status, error) = match result {
+                Ok(response) if response.status().is_success() => {
+                    let _ = sqlx::query(
+                        r#"
+                        UPDATE webhook_deliveries
+                        SET status = 'delivered', attempts = $2, response_status = $3, error = NULL, delivered_at = NOW()
+                        WHERE id = $1
+                        "#,
+                    )
+                    .bind(delivery_id)
+                    .bind(attempt)
+                    .bind(response.status().as_u16() as i32)
+                    .execute(&self.db)
+                    .await;
+                    return;
+                }
+                Ok(response) => (
+                    Some(response.status().as_u16() as i32),
+                    format!("HTTP {}", response.status()),
+                ),
+                Err(e) => (None, e.to_string()),
+            };
+
+            let final_attempt = attempt == MAX_DELIVERY_ATTEMPTS;
+            let _ = sqlx::query(
+                r#"
+                UPDATE webhook_deliveries
+                SET status = $2, attempts = $3, response_status = $4, error = $5
+                WHERE id = $1
+                "#,
+            )
+            .bind(delivery_id)
+            .bind(if final_attempt { "failed" } else { "pending" })
+            .bind(attempt)
+            .bind(response_status)
+            .bind(&error)
+            .execute(&self.db)
+            .await;
+
+            if final_attempt {
+                tracing::warn!(
+                    "Webhook delivery {} to {} failed after {} attempts: {}",
+                    delivery_id, webhook.url, attempt, error
+                );
+                return;
+            }
+
+            tokio::time::sleep(RETRY_BASE_DELAY * 2u32.pow(attempt as u32 - 1)).await;
+        }
+    }
+}
+
+/// Hex-encoded HMAC-SHA256 of the body using the webhook secret
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
@@ -1,4 +1,22 @@
-import type { AuthResponse, Station, NowPlaying } from '$lib/types';
+import type {
+	AdminSession,
+	AuthResponse,
+	Capabilities,
+	Favorites,
+	ListeningHistoryEntry,
+	NavidromeAccount,
+	NavidromePlaylist,
+	ScrobbleAccount,
+	Station,
+	StationConfig,
+	NowPlaying,
+	TrackRating,
+	TrackThumb,
+	User,
+	UserPreferences,
+	UserSession,
+	UserRole
+} from '$lib/types';
 
 const API_BASE = '/api/v1';
 
@@ -81,6 +99,11 @@ export interface FillGapsResponse {
 	filled_count: number;
 }
 
+export interface JourneyResponse {
+	track_ids: string[];
+	tracks: Array<{ id: string; title: string; artist: string }>;
+}
+
 // Embedding visualization types
 export interface EmbeddingPoint {
 	id: string;
@@ -94,7 +117,35 @@ export interface EmbeddingPoint {
 
 export interface EmbeddingVisualizationResponse {
 	points: EmbeddingPoint[];
-	cache_rebuilt: boolean;
+	rebuild_job_id: string | null;  // Set while the map is rebuilt; points are from the previous build
+}
+
+export interface EmbeddingStatus {
+	total_tracks: number;
+	tracks_with_embeddings: number;
+	tracks_pending: number;
+	tracks_failed: number;
+	coverage_percent: number;
+	failures_by_type: Record<string, number>;
+	avg_processing_time_ms: number | null;
+	p50_processing_time_ms: number | null;
+	p95_processing_time_ms: number | null;
+	throughput_per_minute: number;
+	eta_secs: number | null;  // Null while nothing is being embedded
+	model_version: string;
+	embeddings_by_version: Record<string, number>;
+	outdated_embeddings: number;  // Embeddings of other model versions
+	staged_embeddings: number;  // Re-encoded by a migration, swapped in when it finishes
+	indexing_in_progress: boolean;
+	control_state: string;
+}
+
+export interface JobUpdate {
+	job_id: string;
+	kind: string;
+	status: 'queued' | 'running' | 'completed' | 'failed' | 'cancelled';
+	progress: number;
+	message: string | null;
 }
 
 function getAuthToken(): string | null {
@@ -115,7 +166,76 @@ function getHeaders(): HeadersInit {
 	return headers;
 }
 
-async function request<T>(url: string, options: RequestInit = {}): Promise<T> {
+// RFC 7807 problem document returned for every API error
+export interface Problem {
+	type: string;
+	title: string;
+	status: number;
+	detail: string;
+	retryable: boolean;
+	retry_after_secs?: number;
+}
+
+export class ApiError extends Error {
+	readonly status: number;
+	readonly type: string;
+	// Whether the same request may succeed later (upstream outage, timeout, rate limit)
+	readonly retryable: boolean;
+
+	constructor(problem: Problem) {
+		super(problem.detail || problem.title);
+		this.status = problem.status;
+		this.type = problem.type;
+		this.retryable = problem.retryable;
+	}
+}
+
+function storeTokens(response: AuthResponse) {
+	localStorage.setItem('auth_token', response.token);
+	localStorage.setItem('refresh_token', response.refresh_token);
+}
+
+function clearTokens() {
+	localStorage.removeItem('auth_token');
+	localStorage.removeItem('refresh_token');
+}
+
+// Requests whose 401 means wrong credentials rather than an expired token
+const NO_REFRESH = ['/auth/login', '/auth/register', '/auth/logout'];
+
+let refreshing: Promise<boolean> | null = null;
+
+// Renew the access token with the refresh token; concurrent callers share one request
+async function refreshSession(): Promise<boolean> {
+	if (typeof localStorage === 'undefined') return false;
+	const refreshToken = localStorage.getItem('refresh_token');
+	if (!refreshToken) return false;
+
+	refreshing ??= (async () => {
+		try {
+			const response = await fetch(`${API_BASE}/auth/refresh`, {
+				method: 'POST',
+				headers: { 'Content-Type': 'application/json' },
+				body: JSON.stringify({ refresh_token: refreshToken })
+			});
+			if (response.ok) {
+				storeTokens(await response.json());
+				return true;
+			}
+			// Another tab may have rotated the token in the meantime
+			if (localStorage.getItem('refresh_token') !== refreshToken) return true;
+			clearTokens();
+			return false;
+		} catch {
+			return false;
+		} finally {
+			refreshing = null;
+		}
+	})();
+	return refreshing;
+}
+
+async function request<T>(url: string, options: RequestInit = {}, retried = false): Promise<T> {
 	const response = await fetch(`${API_BASE}${url}`, {
 		...options,
 		headers: {
@@ -124,9 +244,26 @@ async function request<T>(url: string, options: RequestInit = {}): Promise<T> {
 		}
 	});
 
+	// Access tokens are short-lived: renew once and try again
+	if (
+		response.status === 401 &&
+		!retried &&
+		!NO_REFRESH.includes(url) &&
+		getAuthToken() &&
+		(await refreshSession())
+	) {
+		return request(url, options, true);
+	}
+
 	if (!response.ok) {
-		const error = await response.json().catch(() => ({ error: 'Unknown error' }));
-		throw new Error(error.error || `HTTP ${response.status}`);
+		const problem: Problem = await response.json().catch(() => ({
+			type: 'about:blank',
+			title: `HTTP ${response.status}`,
+			status: response.status,
+			detail: '',
+			retryable: response.status >= 500
+		}));
+		throw new ApiError(problem);
 	}
 
 	return response.json();
@@ -139,7 +276,7 @@ export const api = {
 			method: 'POST',
 			body: JSON.stringify({ username, password })
 		});
-		localStorage.setItem('auth_token', response.token);
+		storeTokens(response);
 		return response;
 	},
 
@@ -148,34 +285,275 @@ export const api = {
 			method: 'POST',
 			body: JSON.stringify({ username, email, password })
 		});
-		localStorage.setItem('auth_token', response.token);
+		storeTokens(response);
+		return response;
+	},
+
+	// Full-page navigation to the OpenID Connect provider; it returns to /login
+	oidcLoginUrl(redirect?: string): string {
+		const params = redirect ? `?redirect=${encodeURIComponent(redirect)}` : '';
+		return `${API_BASE}/auth/oidc/login${params}`;
+	},
+
+	async refreshOidcSession(): Promise<AuthResponse> {
+		const response = await request<AuthResponse>('/auth/oidc/refresh', { method: 'POST' });
+		storeTokens(response);
 		return response;
 	},
 
 	async logout(): Promise<void> {
-		localStorage.removeItem('auth_token');
+		await request('/auth/logout', { method: 'POST' }).catch(() => {});
+		clearTokens();
 	},
 
-	async getCurrentUser(): Promise<{ id: string; username: string; email: string; role: 'admin' | 'listener' }> {
+	// Sign out on every device
+	async logoutAll(): Promise<void> {
+		await request('/auth/logout-all', { method: 'POST' });
+		clearTokens();
+	},
+
+	// Tokens handed back by an OpenID Connect login
+	setTokens(token: string, refreshToken: string | null) {
+		localStorage.setItem('auth_token', token);
+		if (refreshToken) localStorage.setItem('refresh_token', refreshToken);
+	},
+
+	async getCurrentUser(): Promise<User> {
 		return request('/auth/me');
 	},
 
+	async updatePreferences(preferences: UserPreferences): Promise<UserPreferences> {
+		return request('/auth/me/preferences', {
+			method: 'PUT',
+			body: JSON.stringify(preferences)
+		});
+	},
+
+	// Listening history & favorites
+	async getListeningHistory(limit = 50, offset = 0): Promise<ListeningHistoryEntry[]> {
+		return request(`/me/history?limit=${limit}&offset=${offset}`);
+	},
+
+	async getFavorites(): Promise<Favorites> {
+		return request('/me/favorites');
+	},
+
+	async getDailyMix(): Promise<Station> {
+		return request('/me/daily-mix');
+	},
+
+	async setFavoriteStation(stationId: string, favorite: boolean): Promise<void> {
+		return request(`/me/favorites/stations/${stationId}`, {
+			method: favorite ? 'PUT' : 'DELETE'
+		});
+	},
+
+	async setFavoriteTrack(trackId: string, favorite: boolean): Promise<void> {
+		return request(`/me/favorites/tracks/${encodeURIComponent(trackId)}`, {
+			method: favorite ? 'PUT' : 'DELETE'
+		});
+	},
+
+	// Stream-only tokens for hardware players
+	async getStreamTokens(): Promise<ApiKey[]> {
+		return request('/me/stream-tokens');
+	},
+
+	async createStreamToken(name: string, stationIds: string[], expiresInDays?: number): Promise<{ key: string; api_key: ApiKey }> {
+		return request('/me/stream-tokens', {
+			method: 'POST',
+			body: JSON.stringify({ name, station_ids: stationIds, expires_in_days: expiresInDays })
+		});
+	},
+
+	async revokeStreamToken(id: string): Promise<void> {
+		await request(`/me/stream-tokens/${id}`, { method: 'DELETE' });
+	},
+
+	async getSessions(): Promise<UserSession[]> {
+		return request('/me/sessions');
+	},
+
+	async revokeSession(sessionId: string): Promise<void> {
+		return request(`/me/sessions/${sessionId}`, { method: 'DELETE' });
+	},
+
+	// The user's own Navidrome account (null when the shared account is used)
+	async getNavidromeAccount(): Promise<NavidromeAccount | null> {
+		return request('/me/navidrome');
+	},
+
+	async connectNavidromeAccount(data: {
+		username: string;
+		password?: string;
+		api_key?: string;
+	}): Promise<NavidromeAccount> {
+		return request('/me/navidrome', {
+			method: 'PUT',
+			body: JSON.stringify(data)
+		});
+	},
+
+	async disconnectNavidromeAccount(): Promise<void> {
+		return request('/me/navidrome', { method: 'DELETE' });
+	},
+
+	// The user's ListenBrainz account, which their listens are submitted to
+	async getListenBrainzAccount(): Promise<ScrobbleAccount | null> {
+		return request('/me/listenbrainz');
+	},
+
+	async connectListenBrainzAccount(token: string): Promise<ScrobbleAccount> {
+		return request('/me/listenbrainz', {
+			method: 'PUT',
+			body: JSON.stringify({ token })
+		});
+	},
+
+	async disconnectListenBrainzAccount(): Promise<void> {
+		return request('/me/listenbrainz', { method: 'DELETE' });
+	},
+
+	async exportMyData(): Promise<Blob> {
+		const response = await fetch(`${API_BASE}/me/export`, { headers: getHeaders() });
+		if (!response.ok) {
+			throw new Error(`Export failed: ${response.status}`);
+		}
+		return response.blob();
+	},
+
+	async deleteAccount(password?: string): Promise<void> {
+		await request('/me/delete', {
+			method: 'POST',
+			body: JSON.stringify({ password })
+		});
+	},
+
+	async rateTrack(trackId: string, rating: number): Promise<void> {
+		await request(`/tracks/${encodeURIComponent(trackId)}/rate`, {
+			method: 'POST',
+			body: JSON.stringify({ rating })
+		});
+	},
+
+	async getTrackRating(trackId: string): Promise<TrackRating> {
+		return request(`/tracks/${encodeURIComponent(trackId)}/rating`);
+	},
+
+	async deleteTrackRating(trackId: string): Promise<void> {
+		return request(`/tracks/${encodeURIComponent(trackId)}/rating`, { method: 'DELETE' });
+	},
+
+	// Thumbs up/down; pass the station being listened to so it steers that station
+	async thumbTrack(trackId: string, thumb: TrackThumb, stationId?: string): Promise<void> {
+		await request(`/library/tracks/${encodeURIComponent(trackId)}/rate`, {
+			method: 'POST',
+			body: JSON.stringify({ thumb, station_id: stationId ?? null })
+		});
+	},
+
+	async deleteTrackThumb(trackId: string): Promise<void> {
+		await request(`/library/tracks/${encodeURIComponent(trackId)}/rate`, { method: 'DELETE' });
+	},
+
 	// Stations
 	async getStations(): Promise<Station[]> {
 		return request('/stations');
 	},
 
+	async getManagedStations(): Promise<Station[]> {
+		return request('/stations/managed');
+	},
+
+	async getStationCollaborators(stationId: string): Promise<StationCollaborator[]> {
+		return request(`/stations/${stationId}/collaborators`);
+	},
+
+	async addStationCollaborator(
+		stationId: string,
+		username: string,
+		role: StationRole = 'curator'
+	): Promise<StationCollaborator> {
+		return request(`/stations/${stationId}/collaborators`, {
+			method: 'POST',
+			body: JSON.stringify({ username, role })
+		});
+	},
+
+	async removeStationCollaborator(stationId: string, userId: string): Promise<void> {
+		await request(`/stations/${stationId}/collaborators/${userId}`, { method: 'DELETE' });
+	},
+
+	async getStationSchedule(stationId: string): Promise<StationSchedule> {
+		return request(`/stations/${stationId}/schedule`);
+	},
+
+	async createScheduleBlock(stationId: string, block: ScheduleBlockInput): Promise<ScheduleBlock> {
+		return request(`/stations/${stationId}/schedule`, {
+			method: 'POST',
+			body: JSON.stringify(block)
+		});
+	},
+
+	async updateScheduleBlock(
+		stationId: string,
+		blockId: string,
+		block: ScheduleBlockInput
+	): Promise<ScheduleBlock> {
+		return request(`/stations/${stationId}/schedule/${blockId}`, {
+			method: 'PUT',
+			body: JSON.stringify(block)
+		});
+	},
+
+	async deleteScheduleBlock(stationId: string, blockId: string): Promise<void> {
+		await request(`/stations/${stationId}/schedule/${blockId}`, { method: 'DELETE' });
+	},
+
+	async getStationAnalytics(stationId: string, window: AnalyticsWindow = '7d'): Promise<StationAnalytics> {
+		return request(`/stations/${stationId}/analytics?window=${window}`);
+	},
+
+	async searchLibrary(q: string, limit = 20): Promise<LibrarySearchResult[]> {
+		return request(`/library/search?q=${encodeURIComponent(q)}&limit=${limit}`);
+	},
+
+	async getTrackRequests(stationId: string): Promise<TrackRequest[]> {
+		return request(`/stations/${stationId}/requests`);
+	},
+
+	// Request a track, or vote for it if it is already requested
+	async requestTrack(stationId: string, trackId: string): Promise<TrackRequest> {
+		return request(`/stations/${stationId}/requests`, {
+			method: 'POST',
+			body: JSON.stringify({ track_id: trackId })
+		});
+	},
+
+	async voteTrackRequest(requestId: string): Promise<TrackRequest> {
+		return request(`/requests/${requestId}/vote`, { method: 'POST' });
+	},
+
+	async unvoteTrackRequest(requestId: string): Promise<TrackRequest> {
+		return request(`/requests/${requestId}/vote`, { method: 'DELETE' });
+	},
+
 	async getStation(id: string): Promise<Station> {
 		return request(`/stations/${id}`);
 	},
 
+	// JSON Schema of station config, for rendering the settings form
+	async getStationConfigSchema(): Promise<Record<string, unknown>> {
+		return request('/stations/config-schema');
+	},
+
 	async createStation(data: {
 		path: string;
 		name: string;
 		description: string;
 		genres: string[];
 		mood_tags?: string[];
-		config?: Partial<any>;
+		config?: Partial<StationConfig>;
 		track_ids?: string[];
 	}): Promise<Station> {
 		return request('/stations', {
@@ -184,6 +562,43 @@ export const api = {
 		});
 	},
 
+	// Navidrome playlists that can become stations
+	async getNavidromePlaylists(): Promise<NavidromePlaylist[]> {
+		return request('/stations/from-playlist');
+	},
+
+	async importPlaylistStation(data: {
+		playlist_id: string;
+		path: string;
+		name?: string;
+		description?: string;
+		genres?: string[];
+		config?: Partial<StationConfig>;
+		sync?: boolean;
+	}): Promise<Station> {
+		return request('/stations/from-playlist', {
+			method: 'POST',
+			body: JSON.stringify(data)
+		});
+	},
+
+	// Station from a Navidrome playlist (playlist_id) or M3U file contents (m3u),
+	// matched against the library
+	async importStation(data: {
+		playlist_id?: string;
+		m3u?: string;
+		path: string;
+		name?: string;
+		description?: string;
+		genres?: string[];
+		config?: Partial<StationConfig>;
+	}): Promise<StationImport> {
+		return request('/stations/import', {
+			method: 'POST',
+			body: JSON.stringify(data)
+		});
+	},
+
 	async updateStation(
 		id: string,
 		data: {
@@ -191,7 +606,7 @@ export const api = {
 			description?: string;
 			genres?: string[];
 			mood_tags?: string[];
-			config?: Partial<any>;
+			config?: Partial<StationConfig>;
 		}
 	): Promise<Station> {
 		return request(`/stations/${id}`, {
@@ -224,10 +639,41 @@ export const api = {
 		});
 	},
 
+	// HLS master playlist URL. The token goes in the query string because native HLS players
+	// can't send headers; the backend carries it over to the variant and segment URLs.
+	getStreamUrl(stationId: string): string {
+		const params = new URLSearchParams({ _t: Date.now().toString() });
+		const token = getAuthToken();
+		if (token) {
+			params.set('token', token);
+		}
+		return `${API_BASE}/stations/${stationId}/stream/master.m3u8?${params}`;
+	},
+
 	async getNowPlaying(id: string): Promise<NowPlaying> {
 		return request(`/stations/${id}/nowplaying`);
 	},
 
+	// Follow a station's track changes; returns a function that stops listening
+	subscribeNowPlaying(id: string, onChange: (nowPlaying: NowPlaying) => void): () => void {
+		const url = new URL(`${API_BASE}/stations/${id}/nowplaying/stream`, window.location.origin);
+		const token = getAuthToken();
+		if (token) {
+			url.searchParams.set('token', token);
+		}
+
+		const eventSource = new EventSource(url.toString());
+		eventSource.onmessage = (event) => {
+			try {
+				onChange(JSON.parse(event.data));
+			} catch (e) {
+				console.error('Failed to parse now playing event:', e);
+			}
+		};
+
+		return () => eventSource.close();
+	},
+
 	// Listener tracking
 	async listenerHeartbeat(stationId: string, sessionId: string): Promise<{ listeners: number }> {
 		return request(`/stations/${stationId}/listener/heartbeat`, {
@@ -247,6 +693,11 @@ export const api = {
 		return request('/stations/listeners');
 	},
 
+	// Subsystems, model versions and limits of this deployment
+	async getCapabilities(): Promise<Capabilities> {
+		return request('/capabilities');
+	},
+
 	// AI Capabilities
 	async getAiCapabilities(): Promise<{ available: boolean; features: string[] }> {
 		return request('/ai/capabilities');
@@ -370,6 +821,26 @@ export const api = {
 		});
 	},
 
+	async importNewTracks(): Promise<{ message: string; status: string; job_id: string }> {
+		return request('/library/scan-complete', {
+			method: 'POST'
+		});
+	},
+
+	async analyzeLoudness(limit?: number): Promise<{ message: string; status: string; job_id: string }> {
+		return request('/library/loudness', {
+			method: 'POST',
+			body: JSON.stringify({ limit })
+		});
+	},
+
+	async fetchArtistMetadata(limit?: number): Promise<{ message: string; status: string; job_id: string }> {
+		return request('/library/artist-metadata', {
+			method: 'POST',
+			body: JSON.stringify({ limit })
+		});
+	},
+
 	async getLibraryStats(): Promise<{
 		total_tracks: number;
 		total_ai_analyzed: number;
@@ -396,15 +867,20 @@ export const api = {
 		return request(`/stations/${stationId}/tracks${params}`);
 	},
 
-	// Create Navidrome playlist from station tracks
-	async createNavidromePlaylist(stationId: string, name?: string): Promise<{
+	// Export station tracks to Navidrome (updates the playlist from earlier exports)
+	async createNavidromePlaylist(
+		stationId: string,
+		name?: string,
+		append = false
+	): Promise<{
 		playlist_id: string;
 		name: string;
 		track_count: number;
+		mode: 'created' | 'replaced' | 'appended';
 	}> {
 		return request(`/stations/${stationId}/playlist`, {
 			method: 'POST',
-			body: JSON.stringify({ name })
+			body: JSON.stringify({ name, append })
 		});
 	},
 
@@ -419,12 +895,7 @@ export const api = {
 	},
 
 	// Audio Embedding APIs
-	async getEmbeddingStatus(): Promise<{
-		total_tracks: number;
-		tracks_with_embeddings: number;
-		coverage_percent: number;
-		indexing_in_progress: boolean;
-	}> {
+	async getEmbeddingStatus(): Promise<EmbeddingStatus> {
 		return request('/embeddings/status');
 	},
 
@@ -441,6 +912,10 @@ export const api = {
 		});
 	},
 
+	async migrateEmbeddings(): Promise<{ message: string; status: string; job_id: string }> {
+		return request('/library/embeddings/migrate', { method: 'POST' });
+	}
+
 	async pauseEmbeddings(): Promise<{ message: string; status: string }> {
 		return request('/embeddings/pause', { method: 'POST' });
 	},
@@ -459,7 +934,8 @@ export const api = {
 		limit: number,
 		onProgress: (progress: HybridCurationProgress) => void,
 		onComplete: (trackIds: string[]) => void,
-		onError: (error: string) => void
+		onError: (error: string) => void,
+		energyArc?: string
 	): () => void {
 		const token = getAuthToken();
 		if (!token) {
@@ -472,6 +948,9 @@ export const api = {
 		url.searchParams.set('token', token);
 		url.searchParams.set('query', query);
 		url.searchParams.set('limit', limit.toString());
+		if (energyArc) {
+			url.searchParams.set('energy_arc', energyArc);
+		}
 
 		const eventSource = new EventSource(url.toString());
 
@@ -528,12 +1007,57 @@ export const api = {
 		});
 	},
 
+	// Tracks along the embedding-space path between two tracks, for journey stations
+	async journey(
+		startTrackId: string,
+		endTrackId: string,
+		length?: number,
+		driftTolerance?: number
+	): Promise<JourneyResponse> {
+		return request('/ai/journey', {
+			method: 'POST',
+			body: JSON.stringify({
+				start_track_id: startTrackId,
+				end_track_id: endTrackId,
+				length,
+				drift_tolerance: driftTolerance
+			})
+		});
+	},
+
 	// Embedding visualization
 	async getEmbeddingsForVisualization(limit?: number): Promise<EmbeddingVisualizationResponse> {
 		const params = limit ? `?limit=${limit}` : '';
 		return request(`/embeddings/visualization${params}`);
 	},
 
+	// Follow the background rebuild of the visualization; onDone gets whether it completed
+	watchVisualizationRebuild(
+		onProgress: (update: JobUpdate) => void,
+		onDone: (completed: boolean) => void
+	): () => void {
+		const eventSource = new EventSource(`${API_BASE}/embeddings/visualization/progress`);
+		let finished = false;
+
+		eventSource.onmessage = (event) => {
+			const update: JobUpdate = JSON.parse(event.data);
+			onProgress(update);
+			if (update.status === 'completed' || update.status === 'failed' || update.status === 'cancelled') {
+				finished = true;
+				eventSource.close();
+				onDone(update.status === 'completed');
+			}
+		};
+
+		// Also fires when the server ends the stream (no rebuild running)
+		eventSource.onerror = () => {
+			eventSource.close();
+			if (!finished) onDone(false);
+		};
+
+		return () => eventSource.close();
+	},
+
 	// Settings
 	async getSettings(): Promise<AppSettings> {
 		return request('/settings');
@@ -544,6 +1068,51 @@ export const api = {
 			method: 'PUT',
 			body: JSON.stringify(settings)
 		});
+	},
+
+	// API keys
+	async getApiKeys(): Promise<ApiKey[]> {
+		return request('/admin/api-keys');
+	},
+
+	async createApiKey(name: string, scopes: ApiKeyScope[], expiresInDays?: number): Promise<{ key: string; api_key: ApiKey }> {
+		return request('/admin/api-keys', {
+			method: 'POST',
+			body: JSON.stringify({ name, scopes, expires_in_days: expiresInDays })
+		});
+	},
+
+	async revokeApiKey(id: string): Promise<void> {
+		await request(`/admin/api-keys/${id}`, { method: 'DELETE' });
+	},
+
+	// User management (admin)
+	async getUsers(): Promise<AdminUser[]> {
+		return request('/admin/users');
+	},
+
+	async updateUser(id: string, changes: UpdateUserRequest): Promise<AdminUser> {
+		return request(`/admin/users/${id}`, {
+			method: 'PATCH',
+			body: JSON.stringify(changes)
+		});
+	},
+
+	async deleteUser(id: string): Promise<{ message: string; stations_transferred: number }> {
+		return request(`/admin/users/${id}`, { method: 'DELETE' });
+	},
+
+	async revokeUserSessions(id: string): Promise<{ revoked: number }> {
+		return request(`/admin/users/${id}/sessions`, { method: 'DELETE' });
+	},
+
+	// Active login sessions of all users (admin)
+	async getAllSessions(): Promise<AdminSession[]> {
+		return request('/admin/sessions');
+	},
+
+	async revokeAnySession(id: string): Promise<void> {
+		await request(`/admin/sessions/${id}`, { method: 'DELETE' });
 	}
 };
 
@@ -551,3 +1120,131 @@ export const api = {
 export interface AppSettings {
 	site_title: string;
 }
+
+// Station sharing types
+// Role granted on a single station, whatever the user's global role
+export type StationRole = 'curator' | 'moderator';
+
+export interface StationCollaborator {
+	station_id: string;
+	user_id: string;
+	username: string;
+	role: StationRole;
+	granted_by: string | null;
+	created_at: string;
+}
+
+// Program schedule types. Times are "HH:MM:SS" in the station's timezone; days are
+// 0 (Monday) to 6 (Sunday), empty for every day.
+export interface ScheduleBlockInput {
+	name: string;
+	days: number[];
+	start_time: string;
+	end_time: string;
+	genres: string[];
+	track_ids: string[];
+}
+
+export interface ScheduleBlock extends ScheduleBlockInput {
+	id: string;
+	station_id: string;
+	created_at: string;
+	updated_at: string;
+}
+
+export interface StationSchedule {
+	timezone: string;
+	on_air: string | null;
+	blocks: ScheduleBlock[];
+}
+
+// Station analytics types
+export type AnalyticsWindow = '24h' | '7d' | '30d' | '90d';
+
+export interface StationAnalytics {
+	window: AnalyticsWindow;
+	from: string;
+	to: string;
+	plays: number;
+	skips: number;
+	curation_runs: number;
+	average_listeners: number | null;
+	plays_per_hour: { hour: string; plays: number }[];
+	top_artists: { artist: string; plays: number }[];
+	tracks: {
+		track_id: string;
+		title: string | null;
+		artist: string | null;
+		plays: number;
+		skips: number;
+		skip_rate: number;
+	}[];
+	sessions: number;
+	retention: { minutes: number; retained: number }[];
+}
+
+// Playlist import types
+export interface StationImport {
+	station: Station;
+	entries: number;
+	fuzzy_matched: number;
+	unresolved: { position: number; entry: string }[];
+}
+
+// Track request types
+export interface LibrarySearchResult {
+	id: string;
+	title: string;
+	artist: string;
+	album: string;
+	duration: number;
+}
+
+export interface TrackRequest {
+	id: string;
+	station_id: string;
+	track_id: string;
+	title: string | null;
+	artist: string | null;
+	album: string | null;
+	requested_by: string;
+	requested_by_username: string;
+	status: 'pending' | 'played' | 'expired';
+	votes: number;
+	voted: boolean;
+	created_at: string;
+	played_at: string | null;
+}
+
+// API key types
+export type ApiKeyScope = 'read' | 'stations' | 'library' | 'admin' | 'stream';
+
+export interface ApiKey {
+	id: string;
+	name: string;
+	key_prefix: string;
+	scopes: ApiKeyScope[];
+	station_ids: string[] | null;
+	created_by: string;
+	created_at: string;
+	expires_at: string | null;
+	last_used_at: string | null;
+	revoked_at: string | null;
+}
+
+export interface AdminUser {
+	id: string;
+	username: string;
+	email: string;
+	role: UserRole;
+	created_at: string;
+	last_login: string | null;
+	disabled_at: string | null;
+}
+
+export interface UpdateUserRequest {
+	email?: string;
+	role?: UserRole;
+	disabled?: boolean;
+	password?: string;
+}
@@ -1,4 +1,4 @@
-import type { User } from '$lib/types';
+import type { User, UserPreferences } from '$lib/types';
 import { api } from '$lib/api/client';
 
 interface AuthState {
@@ -24,6 +24,14 @@ export const authStore = {
 	get isAdmin() {
 		return state.user?.role === 'admin';
 	},
+	// Curators manage their own stations
+	get canCurate() {
+		return state.user?.role === 'admin' || state.user?.role === 'curator';
+	},
+	// Moderators (and above) can skip tracks
+	get canModerate() {
+		return state.user?.role === 'admin' || state.user?.role === 'curator' || state.user?.role === 'moderator';
+	},
 
 	async login(username: string, password: string) {
 		const response = await api.login(username, password);
@@ -37,11 +45,28 @@ export const authStore = {
 		return response;
 	},
 
+	// Token handed back by an OpenID Connect login
+	async loginWithToken(token: string, refreshToken: string | null) {
+		api.setTokens(token, refreshToken);
+		state.user = await api.getCurrentUser();
+	},
+
+	async updatePreferences(changes: Partial<UserPreferences>) {
+		if (!state.user) return;
+		const preferences = await api.updatePreferences({ ...state.user.preferences, ...changes });
+		state.user = { ...state.user, preferences };
+	},
+
 	async logout() {
 		await api.logout();
 		state.user = null;
 	},
 
+	async logoutAll() {
+		await api.logoutAll();
+		state.user = null;
+	},
+
 	async init() {
 		try {
 			const user = await api.getCurrentUser();
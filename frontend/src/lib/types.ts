@@ -1,12 +1,108 @@
+export type UserRole = 'admin' | 'curator' | 'moderator' | 'listener';
+
+export interface UserPreferences {
+	default_station_id: string | null;
+	stream_bitrate: number | null;
+	visualization_enabled: boolean;
+	theme: 'dark' | 'light' | 'system';
+}
+
 export interface User {
 	id: string;
 	username: string;
 	email: string;
-	role: 'admin' | 'listener';
+	role: UserRole;
+	preferences: UserPreferences;
+}
+
+export interface ListeningHistoryEntry {
+	id: number;
+	station_id: string;
+	station_name: string;
+	track_id: string;
+	title: string | null;
+	artist: string | null;
+	album: string | null;
+	first_heard_at: string;
+	last_heard_at: string;
+	listened_secs: number;
+}
+
+export interface FavoriteStation {
+	station_id: string;
+	path: string;
+	name: string;
+	active: boolean;
+	created_at: string;
+}
+
+export interface FavoriteTrack {
+	track_id: string;
+	title: string;
+	artist: string;
+	album: string;
+	created_at: string;
+}
+
+export interface Favorites {
+	stations: FavoriteStation[];
+	tracks: FavoriteTrack[];
+}
+
+export interface UserSession {
+	id: string;
+	user_agent: string | null;
+	ip: string | null;
+	created_at: string;
+	last_seen_at: string;
+	expires_at: string;
+	current: boolean;
+}
+
+export interface AdminSession extends UserSession {
+	user_id: string;
+	username: string;
+}
+
+export interface NavidromeAccount {
+	navidrome_username: string;
+	auth_type: 'password' | 'api_key';
+	created_at: string;
+	updated_at: string;
+	last_verified_at: string | null;
+}
+
+export interface ScrobbleAccount {
+	service: 'listenbrainz';
+	username: string;
+	created_at: string;
+}
+
+export type TrackThumb = 'up' | 'down';
+
+export interface TrackRating {
+	track_id: string;
+	user_rating: number | null;
+	user_thumb: TrackThumb | null;
+	avg_rating: number | null;
+	rating_count: number;
+}
+
+export interface NavidromePlaylist {
+	id: string;
+	name: string;
+	comment: string | null;
+	owner: string | null;
+	song_count: number;
+	duration: number;
+	changed: string | null;
 }
 
 export interface AuthResponse {
 	token: string;
+	// Seconds until the access token expires
+	expires_in: number;
+	refresh_token: string;
 	user: User;
 }
 
@@ -22,16 +118,34 @@ export interface Station {
 	updated_at: string;
 	active: boolean;
 	config: StationConfig;
+	daily_mix_user_id: string | null;
+	navidrome_playlist_id: string | null;
+	source_playlist_id: string | null;
+	source_playlist_sync: boolean;
 }
 
+export type AudioCodec = 'mp3' | 'aac' | 'opus';
+
 export interface StationConfig {
+	codec: AudioCodec;
 	bitrate: number;
+	variant_bitrates: number[];
 	sample_rate: number;
 	crossfade_ms: number;
-	track_selection_mode: 'ai_contextual' | 'ai_embeddings' | 'random' | 'hybrid';
+	track_selection_mode: 'ai_contextual' | 'ai_embeddings' | 'random' | 'hybrid' | 'album_sequential' | 'journey';
 	min_track_duration: number;
 	max_track_duration: number;
-	explicit_content: boolean;
+	track_separation: number;
+	artist_separation: number;
+	album_separation: number;
+	allow_explicit: boolean;
+	scrobble: boolean;
+	timezone: string;
+	announce_every: number;
+	announcer_voice: string;
+	accept_requests: boolean;
+	archive: boolean;
+	fallback_track_ids: string[];
 }
 
 export interface Track {
@@ -48,3 +162,44 @@ export interface NowPlaying {
 	started_at: string;
 	listeners: number;
 }
+
+// GET /capabilities: optional subsystems enabled on this deployment
+export interface Capabilities {
+	version: string;
+	role: 'all' | 'api' | 'streamer';
+	ai: {
+		enabled: boolean;
+		curation_model: string | null;
+		description_model: string | null;
+	};
+	audio_encoder: {
+		enabled: boolean;
+		model_version: string | null;
+		sample_rate: number | null;
+		clip_secs: number | null;
+	};
+	hybrid_curation: {
+		enabled: boolean;
+		seed_count: number | null;
+		playlist_size: number | null;
+	};
+	broadcasting: {
+		local: boolean;
+		codecs: AudioCodec[];
+		segment_duration_secs: number;
+		segment_spill: boolean;
+		audio_cache: boolean;
+	};
+	daily_mix: boolean;
+	anonymous_listening: boolean;
+	oidc: {
+		enabled: boolean;
+		provider_name: string | null;
+	};
+	limits: {
+		stream_bitrates: number[];
+		station_sample_rates: number[];
+		max_crossfade_ms: number;
+		max_track_duration_secs: number;
+	};
+}
@@ -10,7 +10,7 @@ const config = {
 			pages: 'build',
 			assets: 'build',
 			fallback: 'index.html', // SPA mode
-			precompress: false,
+			precompress: true, // .br/.gz variants served by the backend
 			strict: false
 		})
 	}